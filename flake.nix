@@ -16,6 +16,9 @@
         src = ./.;
         buildInputs = with pkgs; [  ];
         nativeBuildInputs = [ pkgs.pkg-config ];
+        postInstall = ''
+          $out/bin/declair-rs man --dir $out/share/man/man1
+        '';
       };
     in {
       packages.default = declair-rs;
@@ -0,0 +1,22 @@
+# Renamed and removed nixpkgs attributes, in the format of nixpkgs'
+# pkgs/top-level/aliases.nix. Used when no local nixpkgs checkout is on NIX_PATH.
+lib: self: super:
+
+mapAliases {
+  bitwarden = bitwarden-desktop; # Added 2024-02-25
+  du-dust = dust; # Added 2025-03-08
+  exa = throw "'exa' has been removed because it is unmaintained upstream. Consider using 'eza', a maintained fork"; # Added 2023-09-07
+  gnome-firmware-updater = gnome-firmware; # Added 2022-04-14
+  microsoft_gsl = microsoft-gsl; # Added 2023-05-26
+  nerdfonts = throw "nerdfonts has been separated into individual font packages under the namespace nerd-fonts. For usage details, check the release notes"; # Added 2024-11-09
+  nix-direnv-flakes = nix-direnv; # Added 2021-11-09
+  nodejs_16 = throw "nodejs_16 has been removed as it is EOL. Consider using 'nodejs_20'"; # Added 2023-09-15
+  nodejs_18 = throw "nodejs_18 has been removed as it is EOL. Consider using 'nodejs_20'"; # Added 2025-04-23
+  noto-fonts-cjk = noto-fonts-cjk-sans; # Added 2021-12-16
+  noto-fonts-emoji = noto-fonts-color-emoji; # Added 2023-09-09
+  openssl_1_1 = throw "'openssl_1_1' has been removed as it is EOL. Consider using 'openssl_3'"; # Added 2024-05-19
+  rnix-lsp = throw "'rnix-lsp' has been removed as it is unmaintained. Consider using 'nil'"; # Added 2024-03-09
+  rtx = mise; # Added 2024-01-05
+  teams = throw "'teams' has been removed as Microsoft no longer maintains the Linux client"; # Added 2023-12-29
+  vscode-fhs = vscode.fhs; # Added 2023-04-26
+}
@@ -0,0 +1,130 @@
+//! Renamed and removed nixpkgs attributes, read from nixpkgs' own
+//! `pkgs/top-level/aliases.nix` when a local nixpkgs is on `NIX_PATH`, otherwise from a
+//! bundled excerpt. Adding such a name would only fail later, during the rebuild.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The excerpt of `aliases.nix` shipped with declair.
+const BUNDLED: &str = include_str!("aliases.nix");
+
+/// What became of an attribute name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alias {
+    /// The package is now called `new`; the old name still evaluates, with a warning,
+    /// until it is removed.
+    Renamed { new: String },
+    /// The name throws `message` when evaluated. `suggestion` is the package the message
+    /// recommends instead, if any.
+    Removed {
+        message: String,
+        suggestion: Option<String>,
+    },
+}
+
+impl Alias {
+    /// The package to add instead, if there is one.
+    pub fn replacement(&self) -> Option<&str> {
+        match self {
+            Alias::Renamed { new } => Some(new),
+            Alias::Removed { suggestion, .. } => suggestion.as_deref(),
+        }
+    }
+}
+
+/// Aliases by old name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aliases {
+    entries: HashMap<String, Alias>,
+}
+
+fn is_attr_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_-'.".contains(c)
+}
+
+fn is_attr(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(is_attr_char) && !s.starts_with('.')
+}
+
+/// The first `'name'` in `message` other than `old`.
+fn suggested_name(message: &str, old: &str) -> Option<String> {
+    message
+        .split('\'')
+        .skip(1)
+        .step_by(2)
+        .find(|name| *name != old && is_attr(name))
+        .map(str::to_string)
+}
+
+/// Parse the entries of an `aliases.nix` file: `old = new;` renames and
+/// `old = throw "...";` removals, one per line. Anything else is skipped.
+pub fn parse_aliases(contents: &str) -> Aliases {
+    let mut entries = HashMap::new();
+    for line in contents.lines() {
+        let Some((old, rest)) = line.trim().split_once(" = ") else {
+            continue;
+        };
+        if !is_attr(old) || old.contains('.') {
+            continue;
+        }
+        let alias = if let Some(thrown) = rest.strip_prefix("throw \"") {
+            let Some(end) = thrown.find("\";") else {
+                continue;
+            };
+            let message = thrown[..end].to_string();
+            Alias::Removed {
+                suggestion: suggested_name(&message, old),
+                message,
+            }
+        } else {
+            let Some((new, _)) = rest.split_once(';') else {
+                continue;
+            };
+            if !is_attr(new) {
+                continue;
+            }
+            Alias::Renamed {
+                new: new.to_string(),
+            }
+        };
+        entries.insert(old.to_string(), alias);
+    }
+    Aliases { entries }
+}
+
+impl Aliases {
+    /// The bundled aliases.
+    pub fn bundled() -> Self {
+        parse_aliases(BUNDLED)
+    }
+
+    /// The aliases of the nixpkgs at `nixpkgs`, falling back to the bundled ones when it
+    /// is `None` or has no readable `aliases.nix`.
+    pub fn load(nixpkgs: Option<&Path>) -> Self {
+        nixpkgs
+            .and_then(|dir| fs::read_to_string(dir.join("pkgs/top-level/aliases.nix")).ok())
+            .map(|contents| parse_aliases(&contents))
+            .filter(|aliases| !aliases.entries.is_empty())
+            .unwrap_or_else(Self::bundled)
+    }
+
+    /// What became of `name`, if it is an alias.
+    pub fn get(&self, name: &str) -> Option<&Alias> {
+        self.entries.get(name)
+    }
+}
+
+/// The directory `<nixpkgs>` resolves to through `nix_path` (a `NIX_PATH` value): a
+/// `nixpkgs=<dir>` entry, or `<entry>/nixpkgs` of a plain entry. URLs are skipped.
+pub fn nixpkgs_dir(nix_path: &str) -> Option<PathBuf> {
+    nix_path.split(':').find_map(|entry| {
+        let dir = match entry.split_once('=') {
+            Some(("nixpkgs", dir)) => PathBuf::from(dir),
+            Some(_) => return None,
+            None if entry.is_empty() => return None,
+            None => Path::new(entry).join("nixpkgs"),
+        };
+        dir.is_dir().then_some(dir)
+    })
+}
@@ -0,0 +1,73 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replace the contents of `path` without ever leaving a half-written file behind.
+///
+/// The data is written to a temporary file in the same directory, synced, and renamed
+/// over the target, so a crash or full disk leaves either the old or the new contents.
+/// Symlinks are followed (the link target is replaced, not the link), and the original
+/// permissions and, where allowed, ownership are carried over.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let tmp = temp_path(&target)?;
+    let result = write_and_rename(&target, &tmp, contents);
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Whether [`write_atomic`] can replace `path` as the current user: the file must be
+/// writable and its directory must allow creating the temporary file next to it.
+pub fn can_replace(path: &Path) -> bool {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if OpenOptions::new().write(true).open(&target).is_err() {
+        return false;
+    }
+    let Ok(tmp) = temp_path(&target) else {
+        return false;
+    };
+    let created = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)
+        .is_ok();
+    if created {
+        let _ = fs::remove_file(&tmp);
+    }
+    created
+}
+
+pub(crate) fn temp_path(target: &Path) -> io::Result<PathBuf> {
+    let name = target.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("`{}` has no file name", target.display()),
+        )
+    })?;
+    let tmp_name = format!(".{}.declair.tmp", name.to_string_lossy());
+    Ok(target.with_file_name(tmp_name))
+}
+
+fn write_and_rename(target: &Path, tmp: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(tmp)?;
+    file.write_all(contents)?;
+    if let Ok(meta) = fs::metadata(target) {
+        file.set_permissions(meta.permissions())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{MetadataExt, fchown};
+            // Only root may hand a file to another user; otherwise the new file keeps
+            // our ownership, which is what a plain rewrite would have produced anyway.
+            let _ = fchown(&file, Some(meta.uid()), Some(meta.gid()));
+        }
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::rename(tmp, target)
+}
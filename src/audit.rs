@@ -0,0 +1,334 @@
+//! Known vulnerabilities of configured packages, found with
+//! [vulnix](https://github.com/nix-community/vulnix) in the derivations of the nixpkgs
+//! revision the configuration is built from.
+
+use crate::config::Config;
+use crate::error::{DeclairError, Result};
+use crate::flake::{find_flake_dir, locked_nixpkgs};
+use crate::runner::{CommandClass, CommandRunner, CommandSpec, eval_json};
+use crate::search::NixpkgsSource;
+use crate::syntax::nix_string;
+use crate::t;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+const EVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// vulnix exits with this code when it found vulnerabilities.
+const VULNIX_FOUND: i32 = 2;
+
+/// Security checks, from the `[audit]` config table.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct AuditSettings {
+    /// Check a package for advisories before adding it (when vulnix is installed).
+    pub on_add: bool,
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        AuditSettings { on_add: true }
+    }
+}
+
+/// The nixpkgs whose derivations are audited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Nixpkgs {
+    /// A flake reference: the revision locked by the configuration's `flake.lock`, or
+    /// `nixpkgs` from the registry.
+    Flake(String),
+    /// `<nixpkgs>` from `NIX_PATH`.
+    NixPath,
+}
+
+impl Nixpkgs {
+    /// The nixpkgs `nix_file` in `repo` is built from: the revision pinned by the flake's
+    /// lock file, the channel on `NIX_PATH` without a flake, or the registry's `nixpkgs`.
+    pub fn for_config(config: &Config, repo: &Path, nix_file: &Path) -> Self {
+        if config.flake {
+            let locked = find_flake_dir(repo, nix_file).and_then(|dir| locked_nixpkgs(&dir));
+            return Nixpkgs::Flake(locked.unwrap_or_else(|| "nixpkgs".to_string()));
+        }
+        match NixpkgsSource::for_config(config) {
+            NixpkgsSource::NixPath => Nixpkgs::NixPath,
+            NixpkgsSource::Registry => Nixpkgs::Flake("nixpkgs".to_string()),
+        }
+    }
+
+    /// A Nix expression for its package set.
+    pub(crate) fn expr(&self) -> String {
+        match self {
+            Nixpkgs::Flake(reference) => format!(
+                "(builtins.getFlake {}).legacyPackages.${{builtins.currentSystem}}",
+                nix_string(reference)
+            ),
+            Nixpkgs::NixPath => "import <nixpkgs> { }".to_string(),
+        }
+    }
+
+    /// A Nix expression for its source tree.
+    pub(crate) fn source(&self) -> String {
+        match self {
+            Nixpkgs::Flake(reference) => {
+                format!("(builtins.getFlake {}).outPath", nix_string(reference))
+            }
+            Nixpkgs::NixPath => "<nixpkgs>".to_string(),
+        }
+    }
+}
+
+/// CVSS v3 severity rating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// The rating of a CVSS v3 base score.
+    pub fn from_score(score: Option<f64>) -> Self {
+        match score {
+            None => Severity::Unknown,
+            Some(s) if s >= 9.0 => Severity::Critical,
+            Some(s) if s >= 7.0 => Severity::High,
+            Some(s) if s >= 4.0 => Severity::Medium,
+            Some(_) => Severity::Low,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Unknown => "unknown",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One advisory, e.g. `CVE-2023-5678`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advisory {
+    pub id: String,
+    /// CVSS v3 base score, when the advisory has one.
+    pub score: Option<f64>,
+}
+
+impl Advisory {
+    pub fn severity(&self) -> Severity {
+        Severity::from_score(self.score)
+    }
+}
+
+impl fmt::Display for Advisory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.score {
+            Some(score) => write!(f, "{} ({:.1})", self.id, score),
+            None => f.write_str(&self.id),
+        }
+    }
+}
+
+/// A package with advisories against it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// The attribute name, as configured.
+    pub package: String,
+    /// The derivation name, e.g. `openssl-1.1.1w`.
+    pub name: String,
+    /// Most severe first.
+    pub advisories: Vec<Advisory>,
+}
+
+impl Finding {
+    /// The severity of the worst advisory.
+    pub fn severity(&self) -> Severity {
+        self.advisories
+            .iter()
+            .map(Advisory::severity)
+            .max()
+            .unwrap_or(Severity::Unknown)
+    }
+
+    /// One line about the finding: its worst severity and first few advisories.
+    pub fn summary(&self) -> String {
+        const SHOWN: usize = 5;
+        let mut ids: Vec<String> = self
+            .advisories
+            .iter()
+            .take(SHOWN)
+            .map(ToString::to_string)
+            .collect();
+        if self.advisories.len() > SHOWN {
+            ids.push(t!("audit.more", self.advisories.len() - SHOWN));
+        }
+        t!(
+            "audit.vulnerable",
+            self.package,
+            self.name,
+            self.severity(),
+            ids.join(", ")
+        )
+    }
+}
+
+/// Parse `vulnix --json` output into findings by derivation path, skipping whitelisted
+/// advisories.
+pub fn parse_vulnix(json: &[u8]) -> Result<BTreeMap<String, (String, Vec<Advisory>)>> {
+    let entries: Vec<Value> = serde_json::from_slice(json)
+        .map_err(|e| DeclairError::Audit(t!("command.unexpected_output", "vulnix", e)))?;
+    let mut findings = BTreeMap::new();
+    for entry in entries {
+        let (Some(drv), Some(name)) = (entry["derivation"].as_str(), entry["name"].as_str()) else {
+            continue;
+        };
+        let mut advisories: Vec<Advisory> = entry["affected_by"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(|id| Advisory {
+                id: id.to_string(),
+                score: entry["cvssv3_basescore"][id].as_f64(),
+            })
+            .collect();
+        if advisories.is_empty() {
+            continue;
+        }
+        advisories.sort_by(|a, b| {
+            b.score
+                .unwrap_or(-1.0)
+                .total_cmp(&a.score.unwrap_or(-1.0))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        findings.insert(drv.to_string(), (name.to_string(), advisories));
+    }
+    Ok(findings)
+}
+
+/// `nix eval` of the derivation paths of `packages` in `nixpkgs`. Attributes that don't
+/// exist or fail to evaluate (unfree, broken, removed) map to `null`.
+pub fn derivations_command(nixpkgs: &Nixpkgs, packages: &[String]) -> CommandSpec {
+    let names: Vec<String> = packages.iter().map(|p| nix_string(p)).collect();
+    let expr = format!(
+        "let pkgs = {}; \
+         drv = n: let p = pkgs.lib.attrByPath (pkgs.lib.splitString \".\" n) null pkgs; \
+         r = builtins.tryEval (p.drvPath or null); in if r.success then r.value else null; \
+         in builtins.listToAttrs (map (n: {{ name = n; value = drv n; }}) [ {} ])",
+        nixpkgs.expr(),
+        names.join(" ")
+    );
+    CommandSpec::new(
+        "nix",
+        [
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+            &expr,
+            "--extra-experimental-features",
+            "nix-command flakes",
+        ],
+    )
+    .timeout(EVAL_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query()
+}
+
+/// `vulnix` on just the given derivations, not their dependencies.
+pub fn vulnix_command(drvs: &[&str]) -> CommandSpec {
+    CommandSpec::new(
+        "vulnix",
+        ["--json", "--no-requisites"]
+            .into_iter()
+            .chain(drvs.iter().copied()),
+    )
+    .query()
+}
+
+/// Advisories against `pkg` before it is added to `nix_file`, when `[audit] on_add` asks
+/// for them. Without vulnix there are none.
+pub fn advisories_on_add(
+    runner: &dyn CommandRunner,
+    config: &Config,
+    repo: &Path,
+    nix_file: &Path,
+    pkg: &str,
+) -> Result<Vec<Finding>> {
+    if !config.audit.on_add {
+        return Ok(Vec::new());
+    }
+    let nixpkgs = Nixpkgs::for_config(config, repo, nix_file);
+    match audit_packages(runner, &nixpkgs, &[pkg.to_string()]) {
+        Err(DeclairError::VulnixMissing) => Ok(Vec::new()),
+        result => result,
+    }
+}
+
+/// The advisories against `packages` in `nixpkgs`, worst first. Packages without any are
+/// left out. Fails with [`DeclairError::VulnixMissing`] when vulnix isn't installed.
+pub fn audit_packages(
+    runner: &dyn CommandRunner,
+    nixpkgs: &Nixpkgs,
+    packages: &[String],
+) -> Result<Vec<Finding>> {
+    if packages.is_empty() {
+        return Ok(Vec::new());
+    }
+    let drvs: BTreeMap<String, Option<String>> =
+        eval_json(runner, &derivations_command(nixpkgs, packages))?;
+    let by_drv: BTreeMap<&str, &str> = drvs
+        .iter()
+        .filter_map(|(pkg, drv)| Some((drv.as_deref()?, pkg.as_str())))
+        .collect();
+    if by_drv.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let paths: Vec<&str> = by_drv.keys().copied().collect();
+    let output = match runner.output(&vulnix_command(&paths)) {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(DeclairError::VulnixMissing);
+        }
+        Err(e) => {
+            return Err(DeclairError::Audit(t!("command.spawn", "vulnix", e)));
+        }
+    };
+    if !output.status.success() && output.status.code() != Some(VULNIX_FOUND) {
+        return Err(DeclairError::Audit(t!(
+            "command.failed",
+            "vulnix",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let mut findings: Vec<Finding> = parse_vulnix(&output.stdout)?
+        .into_iter()
+        .filter_map(|(drv, (name, advisories))| {
+            Some(Finding {
+                package: by_drv.get(drv.as_str())?.to_string(),
+                name,
+                advisories,
+            })
+        })
+        .collect();
+    findings.sort_by(|a, b| {
+        b.severity()
+            .cmp(&a.severity())
+            .then_with(|| a.package.cmp(&b.package))
+    });
+    Ok(findings)
+}
@@ -0,0 +1,232 @@
+//! Backup history: besides the latest backup of a file (see [`crate::edit::backup_path`]),
+//! which declair puts back to revert an edit, every edit keeps the previous contents as a
+//! numbered entry, so older states can be listed, shown and diffed. The `[backups]` config
+//! table limits how many entries and how old ones are kept.
+
+use crate::error::{DeclairError, Result};
+use crate::paths::get_history_dir;
+use crate::state::unix_now;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Seconds in a day, for `max_age_days`.
+const DAY: u64 = 24 * 60 * 60;
+
+/// Retention of the backup history, from the `[backups]` config table. `0` lifts a limit.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// Most entries kept per file; the oldest go first.
+    pub max_backups: usize,
+    /// Entries older than this many days are removed.
+    pub max_age_days: u64,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings {
+            max_backups: 20,
+            max_age_days: 90,
+        }
+    }
+}
+
+static RETENTION: OnceLock<BackupSettings> = OnceLock::new();
+
+/// Select the retention applied whenever a backup is recorded for the rest of the process.
+/// Only the first call has an effect.
+pub fn set_retention(settings: BackupSettings) {
+    let _ = RETENTION.set(settings);
+}
+
+/// The selected retention, the default one if none was set.
+pub fn retention() -> BackupSettings {
+    RETENTION.get().copied().unwrap_or_default()
+}
+
+/// One earlier state of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupEntry {
+    /// What `declair backups show` takes: `<seconds since the epoch>-<n>`.
+    pub id: String,
+    /// The file the contents were taken from.
+    pub file: PathBuf,
+    /// Where the contents are stored.
+    pub path: PathBuf,
+    /// When they were replaced, in seconds since the epoch.
+    pub at: u64,
+}
+
+impl BackupEntry {
+    /// The stored contents.
+    pub fn contents(&self) -> Result<String> {
+        fs::read_to_string(&self.path).map_err(|e| DeclairError::Read {
+            path: self.path.clone(),
+            source: e,
+        })
+    }
+
+    /// Size of the stored contents in bytes.
+    pub fn size(&self) -> u64 {
+        fs::metadata(&self.path).map_or(0, |meta| meta.len())
+    }
+}
+
+/// `(seconds, n)` of an entry id.
+fn parse_id(id: &str) -> Option<(u64, u64)> {
+    let (secs, n) = id.split_once('-')?;
+    Some((secs.parse().ok()?, n.parse().ok()?))
+}
+
+/// `file` as an absolute path, made relative to `/` to go below an entry's directory.
+fn relative_path(file: &Path) -> PathBuf {
+    let absolute = fs::canonicalize(file)
+        .or_else(|_| std::path::absolute(file))
+        .unwrap_or_else(|_| file.to_path_buf());
+    absolute
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+/// The one file below an entry's directory.
+fn stored_file(dir: &Path) -> Option<PathBuf> {
+    let mut current = dir.to_path_buf();
+    loop {
+        let mut entries = fs::read_dir(&current).ok()?.flatten();
+        let entry = entries.next()?;
+        if entries.next().is_some() {
+            return None;
+        }
+        current = entry.path();
+        if !entry.file_type().ok()?.is_dir() {
+            return Some(current);
+        }
+    }
+}
+
+/// Keep `contents`, the state of `file` before an edit, as a new entry of `history`.
+pub fn record(history: &Path, file: &Path, contents: &[u8], at: u64) -> Result<BackupEntry> {
+    let relative = relative_path(file);
+    let backup_error = |path: &Path, e| DeclairError::Backup {
+        path: path.to_path_buf(),
+        source: e,
+    };
+    fs::create_dir_all(history).map_err(|e| backup_error(history, e))?;
+    // Several files edited in the same second get entries of their own.
+    let (id, dir) = (1..)
+        .map(|n| format!("{}-{}", at, n))
+        .find_map(|id| {
+            let dir = history.join(&id);
+            match fs::create_dir(&dir) {
+                Ok(()) => Some(Ok((id, dir))),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => None,
+                Err(e) => Some(Err(backup_error(&dir, e))),
+            }
+        })
+        .expect("some entry id is free")?;
+    let path = dir.join(&relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| backup_error(parent, e))?;
+    }
+    fs::write(&path, contents).map_err(|e| backup_error(&path, e))?;
+    Ok(BackupEntry {
+        id,
+        file: Path::new("/").join(relative),
+        path,
+        at,
+    })
+}
+
+/// The entries of `history`, oldest first.
+pub fn list_backups(history: &Path) -> Result<Vec<BackupEntry>> {
+    let dirs = match fs::read_dir(history) {
+        Ok(dirs) => dirs,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(DeclairError::Read {
+                path: history.to_path_buf(),
+                source: e,
+            });
+        }
+    };
+    let mut entries: Vec<((u64, u64), BackupEntry)> = dirs
+        .flatten()
+        .filter_map(|dir| {
+            let id = dir.file_name().to_str()?.to_string();
+            let key = parse_id(&id)?;
+            let path = stored_file(&dir.path())?;
+            let relative = path.strip_prefix(dir.path()).ok()?;
+            Some((
+                key,
+                BackupEntry {
+                    file: Path::new("/").join(relative),
+                    id,
+                    path,
+                    at: key.0,
+                },
+            ))
+        })
+        .collect();
+    entries.sort_by_key(|(key, _)| *key);
+    Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+}
+
+/// The entry `id` of `history`.
+pub fn find_backup(history: &Path, id: &str) -> Result<BackupEntry> {
+    list_backups(history)?
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| DeclairError::BackupNotFound { id: id.to_string() })
+}
+
+/// Remove the entries of `history` that `settings` doesn't keep at `now`, returning them.
+pub fn prune_backups(
+    history: &Path,
+    settings: &BackupSettings,
+    now: u64,
+) -> Result<Vec<BackupEntry>> {
+    let entries = list_backups(history)?;
+    let mut removed = Vec::new();
+    for (idx, entry) in entries.iter().enumerate() {
+        let too_old =
+            settings.max_age_days > 0 && now.saturating_sub(entry.at) > settings.max_age_days * DAY;
+        let newer = entries[idx + 1..]
+            .iter()
+            .filter(|other| other.file == entry.file)
+            .count();
+        let too_many = settings.max_backups > 0 && newer >= settings.max_backups;
+        if too_old || too_many {
+            let dir = history.join(&entry.id);
+            fs::remove_dir_all(&dir).map_err(|e| DeclairError::Backup {
+                path: dir,
+                source: e,
+            })?;
+            removed.push(entry.clone());
+        }
+    }
+    Ok(removed)
+}
+
+/// Record the state of `file` before an edit in declair's history and apply the
+/// retention. Scratch copies in the temp dir have no history; their original gets it when
+/// the copy is written back.
+pub(crate) fn record_backup(file: &Path, contents: &[u8]) -> Result<()> {
+    let Some(history) = get_history_dir() else {
+        return Ok(());
+    };
+    let temp = env::temp_dir();
+    let absolute = std::path::absolute(file).unwrap_or_else(|_| file.to_path_buf());
+    if absolute.starts_with(&temp)
+        || fs::canonicalize(&temp).is_ok_and(|temp| absolute.starts_with(temp))
+    {
+        return Ok(());
+    }
+    let now = unix_now();
+    record(&history, file, contents, now)?;
+    prune_backups(&history, &retention(), now)?;
+    Ok(())
+}
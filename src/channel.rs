@@ -0,0 +1,113 @@
+//! Nix channels of configurations without a flake, managed through `nix-channel`.
+
+use crate::error::{DeclairError, Result};
+use crate::module::ModuleKind;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use crate::t;
+use std::path::Path;
+
+/// A subscribed channel, as listed by `nix-channel --list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Channel {
+    pub name: String,
+    pub url: String,
+}
+
+/// `nix-channel args`, as root for NixOS, whose channels belong to root.
+pub fn nix_channel<I, S>(kind: ModuleKind, args: I) -> CommandSpec
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let args: Vec<String> = args.into_iter().map(Into::into).collect();
+    match kind {
+        ModuleKind::HomeManager => CommandSpec::new("nix-channel", args),
+        ModuleKind::Nixos => CommandSpec::new(
+            "sudo",
+            std::iter::once("nix-channel".to_string()).chain(args),
+        ),
+    }
+}
+
+/// Parse `nix-channel --list` output: one `name url` pair per line.
+pub fn parse_channels(output: &str) -> Vec<Channel> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().split_once(char::is_whitespace))
+        .map(|(name, url)| Channel {
+            name: name.to_string(),
+            url: url.trim().to_string(),
+        })
+        .collect()
+}
+
+/// The channels of the NixOS system or the user.
+pub fn list_channels(runner: &dyn CommandRunner, kind: ModuleKind) -> Result<Vec<Channel>> {
+    let output = runner
+        .output(&nix_channel(kind, ["--list"]))
+        .map_err(|e| DeclairError::Channel(t!("command.spawn", "nix-channel", e)))?;
+    if !output.status.success() {
+        return Err(DeclairError::Channel(t!(
+            "command.failed",
+            "nix-channel --list",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(parse_channels(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Subscribe to the channel at `url`, called `name` or, by default, the last part of
+/// the URL (`nixos-24.05` for `https://nixos.org/channels/nixos-24.05`).
+pub fn add_channel(
+    runner: &dyn CommandRunner,
+    kind: ModuleKind,
+    url: &str,
+    name: Option<&str>,
+) -> Result<()> {
+    let cmd = nix_channel(kind, ["--add", url].into_iter().chain(name));
+    let status = runner
+        .status(&cmd)
+        .map_err(|e| DeclairError::Channel(t!("command.spawn", "nix-channel", e)))?;
+    if !status.success() {
+        return Err(DeclairError::Channel(t!(
+            "command.failed_status",
+            format!("nix-channel --add {}", url),
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// `nix-channel --update` for the named channels, or all of them.
+pub fn channel_update_command(kind: ModuleKind, names: &[String]) -> CommandSpec {
+    nix_channel(
+        kind,
+        std::iter::once("--update".to_string()).chain(names.iter().cloned()),
+    )
+    .class(CommandClass::Rebuild)
+}
+
+/// Download the latest version of the named channels, or all of them.
+pub fn update_channels(
+    runner: &dyn CommandRunner,
+    kind: ModuleKind,
+    names: &[String],
+) -> Result<()> {
+    let status = runner.status(&channel_update_command(kind, names))?;
+    if !status.success() {
+        return Err(DeclairError::UpdateFailed { status });
+    }
+    Ok(())
+}
+
+/// Whether `nix_path` (a `NIX_PATH` value) resolves `<nixpkgs>`: through a
+/// `nixpkgs=...` entry, or a directory holding `nixpkgs` (such as the channels profile).
+pub fn nix_path_has_nixpkgs(nix_path: &str) -> bool {
+    nix_path
+        .split(':')
+        .any(|entry| match entry.split_once('=') {
+            Some((prefix, _)) => prefix == "nixpkgs",
+            None => !entry.is_empty() && Path::new(entry).join("nixpkgs").exists(),
+        })
+}
@@ -0,0 +1,698 @@
+//! The command line of `declair-rs`: its options and subcommands, shared by the binary
+//! and `cargo xtask man`, which writes the man pages from it.
+
+use crate::events::LogFormat;
+use crate::examples::with_examples;
+use crate::module::Scope;
+use crate::output::OutputFormat;
+use crate::rebuild::RebuildMode;
+use crate::scaffold::Template;
+use crate::search::{PackageInfo, SearchMode};
+use crate::theme::ColorChoice;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+/// A command-line tool to search, add, and manage NixOS or Home Manager packages with optional automatic rebuilds.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// Set config file (path to your NixOS configuration file or directory); without a
+    /// path, pick one of the recently used ones
+    #[arg(
+        short = 'c',
+        long = "config",
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = ""
+    )]
+    pub config: Option<PathBuf>,
+
+    /// Package name to add (used as search query in interactive mode or as the
+    /// literal package name in --no-interactive mode)
+    #[arg(short = 'p', long = "package", value_name = "PACKAGE")]
+    pub package: Option<String>,
+
+    /// Use fzf for package selection (Needs fzf installed)
+    #[arg(short = 'f', long = "fzf")]
+    pub fzf: bool,
+
+    #[command(flatten)]
+    pub matching: MatchArgs,
+
+    #[command(flatten)]
+    pub status: StatusArgs,
+
+    /// Do not prompt interactively; fail if necessary information is missing
+    #[arg(long = "no-interactive")]
+    pub no_interactive: bool,
+
+    /// Don't perform rebuild even if config requests it
+    #[arg(long = "no-rebuild")]
+    pub no_rebuild: bool,
+
+    /// Perform a dry-run (Only print selected package)
+    #[arg(short = 'd', long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Print the external commands (searches, rebuilds, sudo, git) instead of running
+    /// them, quoted for a POSIX shell
+    #[arg(long = "print-commands", global = true)]
+    pub print_commands: bool,
+
+    /// Add ARG to every `nix` command declair runs, after the config's `nix_args`
+    /// (repeatable), e.g. `--nix-arg=--show-trace`
+    #[arg(
+        long = "nix-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        global = true
+    )]
+    pub nix_args: Vec<String>,
+
+    /// Add ARG to `nixos-rebuild` and `home-manager`, after the config's `rebuild_args`
+    /// (repeatable), e.g. `--rebuild-arg=--impure`
+    #[arg(
+        long = "rebuild-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        global = true
+    )]
+    pub rebuild_args: Vec<String>,
+
+    /// Show the full trace of evaluation errors: `--show-trace` for `nix` and rebuilds
+    #[arg(long = "show-trace", global = true)]
+    pub show_trace: bool,
+
+    /// Print long outputs (search results, lists, diffs, backups, traces) directly
+    /// instead of through `$DECLAIR_PAGER`, `$PAGER` or `less`
+    #[arg(long = "no-pager", global = true)]
+    pub no_pager: bool,
+
+    /// Explain what each step changes in the Nix config and why, for newcomers to NixOS
+    #[arg(long = "explain", global = true)]
+    pub explain: bool,
+
+    /// Also report progress as one JSON event per line (see "Event stream" in the README)
+    #[arg(long = "log-format", value_enum, default_value_t, global = true)]
+    pub log_format: LogFormat,
+
+    /// With `--log-format json-lines`, write the events to file descriptor FD instead of
+    /// stderr
+    #[arg(long = "log-fd", value_name = "FD", global = true)]
+    pub log_fd: Option<i32>,
+
+    /// Use `program.{package}.enable` = true instead of adding pkg to `with pkgs; [...]` (if available)
+    #[arg(long = "program")]
+    pub program: bool,
+
+    /// Remove package from NixOS config
+    #[arg(short = 'r', long = "remove")]
+    pub remove: bool,
+
+    /// Add `(callPackage FILE { })` for a local derivation, offering to create a template
+    /// when FILE doesn't exist
+    #[arg(
+        long = "local",
+        value_name = "FILE",
+        conflicts_with_all = ["package", "remove", "program", "list"]
+    )]
+    pub local: Option<String>,
+    /// List currently configured packages
+    #[arg(short = 'l', long = "list")]
+    pub list: bool,
+
+    /// Package list to edit: `system`, `user:<NAME>` (`users.users.<NAME>.packages`) or
+    /// `home`; defaults to the one of the config file's kind. --list lists every scope
+    /// without it
+    #[arg(long = "scope", value_name = "SCOPE", global = true)]
+    pub scope: Option<Scope>,
+
+    /// With --list, only show packages declair added (marked `# managed by declair`)
+    #[arg(long = "managed-only", requires = "list")]
+    pub managed_only: bool,
+
+    /// With --list, also show when, by whom and why each package was added
+    #[arg(long = "details", requires = "list")]
+    pub details: bool,
+
+    /// With --list, show every `with pkgs; [...]` list of every .nix file in the
+    /// configuration's repository, grouped by file and by list, with counts
+    #[arg(long = "tree", requires = "list", conflicts_with = "details")]
+    pub tree: bool,
+
+    /// Go ahead despite the checks that refuse a change: add packages the config tree
+    /// already installs, that a configured package duplicates or that don't build on the
+    /// target system, and remove or replace pinned packages
+    #[arg(long = "force", global = true)]
+    pub force: bool,
+
+    /// Add packages even if their license breaks the `[licenses]` policy
+    #[arg(long = "override-policy", global = true)]
+    pub override_policy: bool,
+
+    /// Edit as root without asking first (see `declair` under sudo in the README)
+    #[arg(long = "allow-root", global = true)]
+    pub allow_root: bool,
+
+    /// Note stored with added packages, shown by `--list --details` and `why`
+    #[arg(long = "reason", value_name = "TEXT", global = true)]
+    pub reason: Option<String>,
+
+    /// Commit the change with this message, even without `[git] commit = true`
+    #[arg(long = "commit-message", value_name = "MESSAGE", global = true)]
+    pub commit_message: Option<String>,
+
+    /// Print per-phase timings as JSON on stderr when done
+    #[arg(long = "timings", global = true)]
+    pub timings: bool,
+
+    /// Output format of `--list`, `search` and `info`
+    #[arg(
+        long = "output",
+        visible_alias = "format",
+        value_enum,
+        default_value_t = OutputFormat::Table,
+        global = true
+    )]
+    pub output: OutputFormat,
+
+    /// When to colour output; `auto` also honours `NO_COLOR`
+    #[arg(long = "color", value_name = "WHEN", value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    pub color: ColorChoice,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+    Powershell,
+    Nu,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default)]
+pub enum GraphStyle {
+    /// An indented tree
+    #[default]
+    Tree,
+    /// Graphviz DOT, e.g. for `dot -Tsvg`
+    Dot,
+    /// A Mermaid flowchart
+    Mermaid,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GroupAction {
+    /// Add every package of the group
+    Add {
+        /// Group name, e.g. `dev` for `[groups.dev]`
+        name: String,
+    },
+    /// Remove every package of the group
+    Remove {
+        /// Group name, e.g. `dev` for `[groups.dev]`
+        name: String,
+    },
+    /// List the configured groups and their packages
+    List,
+    /// Fetch shared group definitions from a URL, git repository or file and store them
+    Import {
+        /// `https://...` URL, git repository (`git+https://...` or `*.git`, optionally
+        /// `#path/to/file.toml`) or local path of a TOML group file
+        source: String,
+
+        /// Name for a file holding a single group (defaults to the file name)
+        #[arg(long = "name", value_name = "NAME")]
+        name: Option<String>,
+
+        /// Replace existing groups with the same name
+        #[arg(long = "force")]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum OverlayAction {
+    /// Add an overlay: the flake input `NAME` in a flake config, otherwise the tarball at `URL`
+    Add {
+        /// Overlay name, also used as the flake input name
+        name: String,
+        /// Flake reference (e.g. `github:nix-community/emacs-overlay`) or tarball URL
+        url: String,
+    },
+    /// List the entries of `nixpkgs.overlays`
+    List,
+    /// Remove an overlay (and its flake input)
+    Remove {
+        /// Overlay name as shown by `declair overlay list`
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EnvAction {
+    /// Set a variable to a string value, replacing an existing value
+    Set { name: String, value: String },
+    /// Remove a variable's assignment
+    Unset { name: String },
+    /// List the variables set in the config file
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ServiceAction {
+    /// Add `services.<name>.enable = true;`, checking that NixOS has the option
+    Enable { name: String },
+    /// Remove the `services.<name>.enable` assignment
+    Disable { name: String },
+    /// List the services enabled in the config file
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum OptionsAction {
+    /// Search the NixOS (or Home Manager) options by name and description, then offer to
+    /// set one
+    Search {
+        query: String,
+        #[command(flatten)]
+        matching: MatchArgs,
+    },
+    /// Assign a Nix expression to an option, e.g. `networking.hostName '"box"'`
+    Set { name: String, value: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupsAction {
+    /// List the earlier states of edited files, oldest first
+    List,
+    /// Show how a file changed since the backup `ID`
+    Show {
+        #[arg(value_name = "ID")]
+        id: String,
+        /// Print the backed-up contents instead of the diff
+        #[arg(long = "contents")]
+        contents: bool,
+    },
+    /// Remove the backups the `[backups]` retention doesn't keep
+    Prune {
+        /// Keep at most this many backups per file instead of `max_backups`
+        #[arg(long = "max-backups", value_name = "N")]
+        max_backups: Option<usize>,
+        /// Remove backups older than this many days instead of `max_age_days`
+        #[arg(long = "max-age-days", value_name = "DAYS")]
+        max_age_days: Option<u64>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ChannelAction {
+    /// List the subscribed channels
+    List,
+    /// Subscribe to a channel, e.g. `https://nixos.org/channels/nixos-unstable`
+    Add {
+        url: String,
+        /// Channel name (defaults to the last part of the URL)
+        name: Option<String>,
+    },
+    /// Download the latest version of the named channels (all by default)
+    Update {
+        #[arg(value_name = "NAME")]
+        names: Vec<String>,
+    },
+}
+
+/// How a search query matches packages; by default it is a literal substring of the
+/// attribute path, name or description.
+#[derive(clap::Args, Debug, Clone, Copy, Default)]
+#[group(multiple = false)]
+pub struct MatchArgs {
+    /// Only list packages whose attribute path or name is exactly the query
+    #[arg(long = "exact")]
+    pub exact: bool,
+    /// Only list packages whose attribute path or name starts with the query
+    #[arg(long = "prefix")]
+    pub prefix: bool,
+    /// Treat the query as a regular expression, like `nix search` does
+    #[arg(long = "regex")]
+    pub regex: bool,
+}
+
+impl MatchArgs {
+    pub fn mode(self) -> SearchMode {
+        if self.exact {
+            SearchMode::Exact
+        } else if self.prefix {
+            SearchMode::Prefix
+        } else if self.regex {
+            SearchMode::Regex
+        } else {
+            SearchMode::Substring
+        }
+    }
+}
+
+/// Which search results to keep by whether the configuration already lists them.
+#[derive(clap::Args, Debug, Clone, Copy, Default)]
+#[group(multiple = false)]
+pub struct StatusArgs {
+    /// Only list packages the configuration already has
+    #[arg(long = "installed")]
+    pub installed: bool,
+    /// Only list packages the configuration doesn't have yet
+    #[arg(long = "not-installed")]
+    pub not_installed: bool,
+}
+
+impl StatusArgs {
+    pub fn is_set(self) -> bool {
+        self.installed || self.not_installed
+    }
+
+    pub fn keep(self, pkg: &PackageInfo) -> bool {
+        if self.installed {
+            pkg.installed
+        } else if self.not_installed {
+            !pkg.installed
+        } else {
+            true
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SelfAction {
+    /// Detect how declair was installed (Nix profile, the configuration's flake, cargo)
+    /// and update it, or print the command that does
+    Check {
+        /// Update (and rebuild, for a flake input) without asking
+        #[arg(short = 'y', long = "yes")]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ScheduleAction {
+    /// Write and start a user systemd timer running `declair update --yes --no-interactive`
+    #[command(group(ArgGroup::new("frequency").multiple(false)))]
+    Install {
+        /// Run every day
+        #[arg(long = "daily", group = "frequency")]
+        daily: bool,
+        /// Run every week (the default)
+        #[arg(long = "weekly", group = "frequency")]
+        weekly: bool,
+        /// Run every month
+        #[arg(long = "monthly", group = "frequency")]
+        monthly: bool,
+        /// Run at a systemd calendar expression, e.g. `Sat 04:00`
+        #[arg(long = "on-calendar", value_name = "SPEC", group = "frequency")]
+        on_calendar: Option<String>,
+    },
+    /// Show the timer's next run and the service's last result
+    Status,
+    /// Stop the timer and delete its units
+    Uninstall,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Create a `declair-packages.nix` module owned by declair and point the config at it
+    InitConfigFile {
+        /// Where to create the module (defaults to `declair-packages.nix` next to the current config file)
+        #[arg(value_name = "FILE")]
+        path: Option<PathBuf>,
+
+        /// Add the module to the current config file's `imports` without asking
+        #[arg(long = "insert-import")]
+        insert_import: bool,
+    },
+    /// Watch the managed Nix files and rebuild whenever they change
+    Watch {
+        /// Wait this long after the last change before rebuilding
+        #[arg(long = "debounce", value_name = "MS", default_value_t = 500)]
+        debounce: u64,
+
+        /// Ask before each rebuild
+        #[arg(long = "confirm")]
+        confirm: bool,
+    },
+    /// Serve search, list, add, remove and rebuild requests as JSON-RPC over a Unix socket
+    Daemon {
+        /// Socket to listen on (defaults to `$XDG_RUNTIME_DIR/declair.sock`)
+        #[arg(long = "socket", value_name = "PATH")]
+        socket: Option<PathBuf>,
+    },
+    /// Add several packages (exact attribute names) at once, review the diff, rebuild once
+    Add {
+        /// Package attribute names, e.g. `ripgrep fd htop`
+        #[arg(required = true, value_name = "PACKAGE")]
+        packages: Vec<String>,
+    },
+    /// Replace a package with another in place, keeping its position and comment, e.g.
+    /// `exa eza`, then rebuild once
+    Replace {
+        /// The package to replace
+        #[arg(value_name = "OLD")]
+        old: String,
+        /// The package taking its place
+        #[arg(value_name = "NEW")]
+        new: String,
+    },
+    /// Create a starter derivation at `pkgs/<NAME>/default.nix` in the config repository
+    /// and add it to the package list with `callPackage`
+    NewPackage {
+        /// Package name, used for the directory and `pname`
+        name: String,
+        /// The builder the derivation starts from
+        #[arg(long, value_enum, default_value_t)]
+        template: Template,
+    },
+    /// Manage named package groups from the config's `[groups]` table
+    Group {
+        #[command(subcommand)]
+        action: GroupAction,
+    },
+    /// Manage the `nixpkgs.overlays` list and the flake inputs providing overlays
+    Overlay {
+        #[command(subcommand)]
+        action: OverlayAction,
+    },
+    /// Set or unset environment variables: `environment.variables`, or
+    /// `home.sessionVariables` in Home Manager
+    Env {
+        #[command(subcommand)]
+        action: EnvAction,
+    },
+    /// Enable or disable services: `services.<name>.enable`
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+    /// Search and set options of the configuration's modules
+    Options {
+        #[command(subcommand)]
+        action: OptionsAction,
+    },
+    /// Pin packages so that removing or replacing them needs `--force`
+    Pin {
+        #[arg(required = true)]
+        packages: Vec<String>,
+    },
+    /// Unpin packages
+    Unpin {
+        #[arg(required = true)]
+        packages: Vec<String>,
+    },
+    /// List the pinned packages
+    ListPins,
+    /// Remove packages declair added; hand-written entries are never touched
+    Prune {
+        /// Managed packages to remove (asks which ones if omitted)
+        #[arg(value_name = "PACKAGE")]
+        packages: Vec<String>,
+    },
+    /// Explain why a package is installed: install record, config entry and
+    /// which installed packages depend on it (via `nix-store` and `nix why-depends`)
+    Why {
+        #[arg(value_name = "PACKAGE")]
+        package: String,
+    },
+    /// Compare the configured packages with the ones in the running generation and offer
+    /// to rebuild when they differ
+    Drift {
+        /// Report every package of the generation the configuration doesn't list, not just
+        /// the ones declair added or removed
+        #[arg(long)]
+        all: bool,
+        /// Rebuild without asking when there is drift
+        #[arg(short = 'y', long = "yes")]
+        yes: bool,
+    },
+    /// Rebuild the configuration now, e.g. after edits made with `--no-rebuild` or by
+    /// hand; joins a rebuild another declair run is doing
+    Rebuild {
+        /// What `nixos-rebuild` does with the new generation; `rebuild_mode` of the
+        /// config, or `switch`, when not given
+        #[arg(long, value_enum)]
+        mode: Option<RebuildMode>,
+        /// System profile to build into (`nixos-rebuild --profile-name`)
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Update the configuration's flake inputs (or channels) and rebuild
+    Update {
+        /// Rebuild without asking
+        #[arg(short = 'y', long = "yes")]
+        yes: bool,
+    },
+    /// Manage the `nix-channel` channels of a configuration without a flake (root's for NixOS)
+    Channel {
+        #[command(subcommand)]
+        action: ChannelAction,
+    },
+    /// Run `declair update` on a schedule with a user systemd timer
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+    /// List the plugins (`declair-<name>` executables) found on PATH
+    Plugins,
+    /// Run the plugin `declair-<name>` with the remaining arguments
+    #[command(external_subcommand)]
+    External(Vec<String>),
+    /// List every line of the config repository's `.nix` files referring to a package:
+    /// package lists, `pkgs.<name>` in overrides and wrappers, module options
+    Grep {
+        /// Package name, or a pattern with `*` wildcards such as `nodejs_*`
+        #[arg(value_name = "PACKAGE")]
+        pattern: String,
+    },
+    /// Check the configured packages for known vulnerabilities with `vulnix`, in the
+    /// nixpkgs revision the configuration is built from
+    Audit {
+        /// Check the packages' licenses against the `[licenses]` policy instead
+        #[arg(long)]
+        licenses: bool,
+    },
+    /// Show which modules import which, with the number of packages each one lists
+    Graph {
+        /// How to draw the graph
+        #[arg(value_enum, default_value_t)]
+        style: GraphStyle,
+    },
+    /// Compare the configured packages with another machine's: a Nix file or a
+    /// `--list` export (`--output nix|json|csv|tsv`, or one package per line)
+    Diff {
+        #[arg(value_name = "OTHER")]
+        other: PathBuf,
+    },
+    /// Apply a package manifest (as read by `diff`) to the configuration: changes of the
+    /// manifest since the last sync are made, edits of the file since then are reported
+    /// as conflicts instead of being overwritten
+    Sync {
+        #[arg(value_name = "MANIFEST")]
+        manifest: PathBuf,
+        /// Apply without asking; conflicts keep the file's side unless `--force` is given
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Find the packages providing a command (via nix-locate or the channel's
+    /// programs.sqlite) and add one of them
+    Provides {
+        /// Command name, e.g. `convert`
+        command: String,
+    },
+    /// Add the package providing a command that was not found, taken from an error
+    /// message or the last command in the shell history
+    Fix {
+        /// Error message such as "command not found: rg" (defaults to the last command
+        /// in the shell history)
+        message: Option<String>,
+    },
+    /// Check the Nix installation: version, enabled experimental features and the
+    /// search backend declair will use
+    Doctor,
+    /// Print where declair keeps its config, install records, backups, caches and socket
+    Paths,
+    /// List, inspect and prune the history of backups of edited files
+    Backups {
+        #[command(subcommand)]
+        action: BackupsAction,
+    },
+    /// Show the configuration's files, the edits made since the last successful rebuild
+    /// and whether the running generation is the one that rebuild activated
+    Status,
+    /// Run a package's main program: the installed one if the configuration has the
+    /// package, otherwise once with `nix run` or after adding it
+    Run {
+        /// Attribute name in nixpkgs, e.g. `ripgrep`
+        #[arg(value_name = "PACKAGE")]
+        package: String,
+        /// Arguments passed to the program
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "ARGS"
+        )]
+        args: Vec<String>,
+    },
+    /// Show a package's version, full description, homepage and license
+    Info {
+        /// Attribute name in nixpkgs, e.g. `ripgrep`
+        #[arg(value_name = "PACKAGE")]
+        package: String,
+    },
+    /// Search nixpkgs and print the results without adding anything
+    Search {
+        /// Search query; a literal substring unless --exact, --prefix or --regex is given
+        query: String,
+        #[command(flatten)]
+        matching: MatchArgs,
+        #[command(flatten)]
+        status: StatusArgs,
+    },
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: CompletionShell,
+    },
+    /// Print the man page, or write the pages of every subcommand to a directory
+    Man {
+        /// Write `declair-rs.1`, `declair-rs-add.1`, ... to DIR instead, e.g.
+        /// `$out/share/man/man1`
+        #[arg(long = "dir", value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+    /// Print common workflows as copy-pasteable commands
+    Examples,
+    /// Check how declair itself was installed and update it
+    #[command(name = "self")]
+    SelfManage {
+        #[command(subcommand)]
+        action: SelfAction,
+    },
+    /// Send a single request to a running daemon and print the result as JSON
+    Rpc {
+        /// Method name, e.g. `search`, `list`, `add`, `remove`, `rebuild-status`
+        method: String,
+
+        /// Parameters as a JSON object, e.g. `{"query": "ripgrep"}`
+        #[arg(value_name = "PARAMS")]
+        params: Option<String>,
+
+        /// Socket of the daemon (defaults to `$XDG_RUNTIME_DIR/declair.sock`)
+        #[arg(long = "socket", value_name = "PATH")]
+        socket: Option<PathBuf>,
+    },
+}
+
+/// The command line, with examples at the end of `--help`.
+pub fn command() -> clap::Command {
+    with_examples(Args::command())
+}
@@ -0,0 +1,101 @@
+//! What a rebuild changed: `nix store diff-closures` between the generation active before
+//! and after it, reduced to the packages added, updated and removed.
+
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use console::strip_ansi_codes;
+use std::path::Path;
+
+/// How `nix store diff-closures` writes a missing version.
+const NONE: &str = "∅";
+
+/// A package whose versions differ between two closures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClosureChange {
+    Added {
+        name: String,
+        version: String,
+    },
+    Updated {
+        name: String,
+        from: String,
+        to: String,
+    },
+    Removed {
+        name: String,
+        version: String,
+    },
+}
+
+impl ClosureChange {
+    pub fn name(&self) -> &str {
+        match self {
+            ClosureChange::Added { name, .. }
+            | ClosureChange::Updated { name, .. }
+            | ClosureChange::Removed { name, .. } => name,
+        }
+    }
+}
+
+/// The version changes in the output of `nix store diff-closures`, one line per package
+/// like `firefox: 120.0 → 121.0, +3.2 MiB`. Lines only changing the size are left out.
+pub fn parse_diff_closures(output: &str) -> Vec<ClosureChange> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = strip_ansi_codes(line);
+            let (name, rest) = line.trim().split_once(": ")?;
+            let (from, to) = rest.split_once(" → ")?;
+            // The size change follows the new versions: `1.1, +12.0 KiB`.
+            let to = match to.rsplit_once(", ") {
+                Some((versions, size)) if size.starts_with(['+', '-']) => versions,
+                _ => to,
+            };
+            let name = name.to_string();
+            let version = |v: &str| v.trim().to_string();
+            Some(match (from.trim(), to.trim()) {
+                (NONE, to) => ClosureChange::Added {
+                    name,
+                    version: version(to),
+                },
+                (from, NONE) => ClosureChange::Removed {
+                    name,
+                    version: version(from),
+                },
+                (from, to) => ClosureChange::Updated {
+                    name,
+                    from: version(from),
+                    to: version(to),
+                },
+            })
+        })
+        .collect()
+}
+
+/// The package changes between the closures of `before` and `after`; `None` when
+/// `nix store diff-closures` fails.
+pub fn diff_closures(
+    runner: &dyn CommandRunner,
+    before: &Path,
+    after: &Path,
+) -> Option<Vec<ClosureChange>> {
+    let cmd = CommandSpec::new(
+        "nix",
+        [
+            "--extra-experimental-features",
+            "nix-command",
+            "store",
+            "diff-closures",
+            before.to_string_lossy().as_ref(),
+            after.to_string_lossy().as_ref(),
+        ],
+    )
+    .class(CommandClass::Eval)
+    .query();
+    let output = runner.output(&cmd).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_diff_closures(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
@@ -0,0 +1,110 @@
+//! Committing declair's edits to the configuration's git repository, from the `[git]`
+//! config table.
+
+use crate::error::{DeclairError, Result};
+use crate::hooks::HookContext;
+use crate::runner::{CommandRunner, CommandSpec};
+use crate::t;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Default commit message template.
+pub const DEFAULT_MESSAGE: &str = "{action} {packages} via declair";
+
+/// Git integration, from the `[git]` config table.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct GitSettings {
+    /// Commit the edited files after each successful change.
+    pub commit: bool,
+    /// Template of the commit message: `{action}`, `{packages}` and `{file}` are replaced.
+    pub message: String,
+    /// Start the message with a conventional-commit type and scope, e.g. `feat(packages): `.
+    pub conventional: bool,
+    /// Scope of the conventional-commit prefix.
+    pub scope: String,
+}
+
+impl Default for GitSettings {
+    fn default() -> Self {
+        GitSettings {
+            commit: false,
+            message: DEFAULT_MESSAGE.to_string(),
+            conventional: false,
+            scope: "packages".to_string(),
+        }
+    }
+}
+
+impl GitSettings {
+    /// The message for the change described by `ctx`: `message` if given (from
+    /// `--commit-message`), otherwise the template, either behind the conventional-commit
+    /// prefix when enabled.
+    pub fn message(&self, ctx: &HookContext, message: Option<&str>) -> String {
+        let body = message.map_or_else(|| expand(&self.message, ctx), str::to_string);
+        if !self.conventional {
+            return body;
+        }
+        let scope = if self.scope.is_empty() {
+            String::new()
+        } else {
+            format!("({})", self.scope)
+        };
+        format!("{}{}: {}", commit_type(ctx.action), scope, body)
+    }
+}
+
+/// `template` with `{action}`, `{packages}` (space-separated) and `{file}` replaced.
+pub fn expand(template: &str, ctx: &HookContext) -> String {
+    template
+        .replace("{action}", ctx.action)
+        .replace("{packages}", &ctx.packages.join(" "))
+        .replace("{file}", &ctx.file.display().to_string())
+}
+
+/// Conventional-commit type of a change: new packages are features, dependency updates
+/// `build`, everything else a chore.
+fn commit_type(action: &str) -> &'static str {
+    match action {
+        "add" => "feat",
+        "update" => "build",
+        _ => "chore",
+    }
+}
+
+/// `git add` and `git commit` of just `files` in `repo`, leaving anything else staged alone.
+pub fn commit_commands(repo: &Path, files: &[PathBuf], message: &str) -> [CommandSpec; 2] {
+    let files = files.iter().map(|f| f.display().to_string());
+    let git = |args: Vec<String>| CommandSpec::new("git", args).current_dir(repo);
+    let add = ["add", "--"]
+        .map(str::to_string)
+        .into_iter()
+        .chain(files.clone());
+    let commit = ["commit", "-m", message, "--"]
+        .map(str::to_string)
+        .into_iter()
+        .chain(files);
+    [git(add.collect()), git(commit.collect())]
+}
+
+/// Commit `files` in `repo` with `message`.
+pub fn commit_files(
+    runner: &dyn CommandRunner,
+    repo: &Path,
+    files: &[PathBuf],
+    message: &str,
+) -> Result<()> {
+    for cmd in commit_commands(repo, files, message) {
+        let output = runner
+            .output(&cmd)
+            .map_err(|e| DeclairError::Git(t!("command.spawn", "git", e)))?;
+        if !output.status.success() {
+            return Err(DeclairError::Git(t!(
+                "command.failed_output",
+                format!("git {}", cmd.args[0]),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use crate::paths::expand_path;
+use dialoguer::Completion;
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+
+/// File name completion for path prompts. Repeated Tab presses cycle through every entry
+/// matching what was typed, directories first.
+#[derive(Default)]
+pub struct FileCompletion {
+    cycle: RefCell<Option<Cycle>>,
+}
+
+/// The candidates of the last completion and which one was offered.
+struct Cycle {
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl FileCompletion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Completions of `input`, directories first, then by name ignoring case. What comes
+    /// before the last component is kept as typed, so `~/`, `~user/` and `$HOME/`
+    /// prefixes survive; spaces escaped as `\ ` stay escaped.
+    pub fn candidates(input: &str) -> Vec<String> {
+        let (typed_dir, prefix) = match input.rfind('/') {
+            Some(pos) => input.split_at(pos + 1),
+            None if input.starts_with('~') => {
+                return expand_path(input)
+                    .ok()
+                    .filter(|dir| dir.is_dir())
+                    .map(|_| vec![format!("{}/", input)])
+                    .unwrap_or_default();
+            }
+            None => ("", input),
+        };
+        let escaped = input.contains("\\ ");
+        let dir = if typed_dir.is_empty() {
+            PathBuf::from(".")
+        } else {
+            match expand_path(&unescape(typed_dir)) {
+                Ok(dir) => dir,
+                Err(_) => return Vec::new(),
+            }
+        };
+        let Ok(read) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let prefix = unescape(prefix).to_lowercase();
+        let mut matches: Vec<(bool, String)> = read
+            .filter_map(|e| e.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let is_dir = entry.path().is_dir();
+                name.to_lowercase()
+                    .starts_with(&prefix)
+                    .then_some((is_dir, name))
+            })
+            .collect();
+        matches.sort_by(|(a_dir, a), (b_dir, b)| {
+            b_dir
+                .cmp(a_dir)
+                .then_with(|| a.to_lowercase().cmp(&b.to_lowercase()))
+                .then_with(|| a.cmp(b))
+        });
+        matches
+            .into_iter()
+            .map(|(is_dir, name)| {
+                let name = if escaped {
+                    name.replace(' ', "\\ ")
+                } else {
+                    name
+                };
+                format!("{}{}{}", typed_dir, name, if is_dir { "/" } else { "" })
+            })
+            .collect()
+    }
+}
+
+impl Completion for FileCompletion {
+    /// The first candidate for `input`, or the next one when `input` is the candidate the
+    /// previous Tab press put there.
+    fn get(&self, input: &str) -> Option<String> {
+        let mut cycle = self.cycle.borrow_mut();
+        if let Some(current) = cycle.as_mut()
+            && current.candidates.len() > 1
+            && current
+                .candidates
+                .get(current.index)
+                .is_some_and(|c| c == input)
+        {
+            current.index = (current.index + 1) % current.candidates.len();
+            return current.candidates.get(current.index).cloned();
+        }
+        let candidates = Self::candidates(input);
+        let first = candidates.first().cloned();
+        *cycle = Some(Cycle {
+            candidates,
+            index: 0,
+        });
+        first
+    }
+}
+
+/// `text` with `\ ` turned back into spaces, for a path typed or completed with escapes.
+pub fn unescape(text: &str) -> String {
+    text.replace("\\ ", " ")
+}
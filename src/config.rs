@@ -0,0 +1,327 @@
+use crate::atomic::write_atomic;
+use crate::audit::AuditSettings;
+use crate::backups::BackupSettings;
+use crate::commit::GitSettings;
+use crate::completion::{FileCompletion, unescape};
+use crate::error::{DeclairError, Result};
+use crate::format::Formatter;
+use crate::hooks::Hooks;
+use crate::host::is_root;
+use crate::i18n::Language;
+use crate::module::ModuleKind;
+use crate::nur::Nur;
+use crate::paths::get_config_dir;
+use crate::plugin::Plugins;
+use crate::policy::LicensePolicy;
+use crate::rebuild::RebuildMode;
+use crate::runner::Timeouts;
+use crate::state::recent_configs;
+use crate::t;
+use crate::theme::{Theme, prompt_theme, theme};
+use dialoguer::{Confirm, Input, Select};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml::Table;
+
+/// Where the system-wide config is read from unless root's `$DECLAIR_SYSTEM_CONFIG`
+/// says otherwise. Admins set defaults there (the Nix configuration, profiles, policies) that
+/// each user's `config.toml` is merged over.
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/declair/config.toml";
+
+/// Keys every `config.toml` keeps, so a user's config stays valid without the system one.
+const REQUIRED_KEYS: [&str; 4] = ["nix_path", "auto_rebuild", "home_manager", "flake"];
+
+/// Key of the system config listing the top-level keys users can't override.
+const LOCKED_KEY: &str = "locked";
+
+/// Key of the read-only policy, which is locked wherever the system config sets it.
+const READ_ONLY_KEY: &str = "read_only";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Config {
+    pub nix_path: String,
+    pub auto_rebuild: bool,
+    pub home_manager: bool,
+    pub flake: bool,
+    /// Formatter run on the edited file after each change.
+    #[serde(default)]
+    pub formatter: Formatter,
+    /// Per-file module kind, for files whose kind can't be detected or differs from
+    /// `home_manager` (e.g. a flake holding both NixOS and Home Manager modules).
+    #[serde(default)]
+    pub modules: BTreeMap<String, ModuleKind>,
+    /// Named package sets for `declair group add/remove`.
+    #[serde(default)]
+    pub groups: BTreeMap<String, Group>,
+    /// Per-class timeouts for external commands, in seconds.
+    #[serde(default)]
+    pub timeouts: Timeouts,
+    /// How many `nix eval`s run at once when looking up many packages; the number of CPUs
+    /// (at most 8) when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+    /// How long to wait after the last edit before rebuilding, in milliseconds, so that
+    /// declair runs finishing close together share one rebuild.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rebuild_debounce_ms: Option<u64>,
+    /// What rebuilds after an edit do with the new generation; `switch` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rebuild_mode: Option<RebuildMode>,
+    /// System profile rebuilds build into (`nixos-rebuild --profile-name`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Command running `nixos-rebuild` as root, `sudo` when unset, e.g. `doas` or `run0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sudo: Option<String>,
+    /// Arguments added to every `nix` command declair runs, e.g. `["--show-trace"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nix_args: Vec<String>,
+    /// Arguments added to `nixos-rebuild` and `home-manager` when rebuilding or checking
+    /// the configuration, e.g. `["--impure"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rebuild_args: Vec<String>,
+    /// Package sets listed first when a search finds the same package in several, e.g.
+    /// `["python312Packages"]` for `requests`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefer_sets: Vec<String>,
+    /// Package sets left out of search results unless the query names them, e.g.
+    /// `["haskellPackages"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_sets: Vec<String>,
+    /// Language of prompts and messages; taken from `LC_ALL`/`LC_MESSAGES`/`LANG` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    /// Colours of tables, diffs, messages and prompts.
+    #[serde(default)]
+    pub theme: Theme,
+    /// Commands and notifications fired after each rebuild.
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// External search backends and editor.
+    #[serde(default)]
+    pub plugins: Plugins,
+    /// Searching the Nix User Repository.
+    #[serde(default)]
+    pub nur: Nur,
+    /// Checking packages for security advisories.
+    #[serde(default)]
+    pub audit: AuditSettings,
+    /// Licenses packages may and may not have.
+    #[serde(default)]
+    pub licenses: LicensePolicy,
+    /// Committing declair's edits to the configuration's repository.
+    #[serde(default)]
+    pub git: GitSettings,
+    /// How much backup history is kept.
+    #[serde(default)]
+    pub backups: BackupSettings,
+    /// Refuse to edit the configuration or rebuild; searching and listing still work.
+    /// Users can't turn it off when the system config sets it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+}
+
+/// A named set of packages, e.g. `[groups.dev] packages = ["git", "gcc", "gdb"]`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Group {
+    pub packages: Vec<String>,
+    /// Where `declair group import` fetched the group from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// Location of declair's own `config.toml`.
+pub fn config_file_path() -> Result<PathBuf> {
+    let config_dir =
+        get_config_dir().ok_or_else(|| DeclairError::Config(t!("config.no_config_dir")))?;
+    Ok(config_dir.join("config.toml"))
+}
+
+/// Location of the system-wide config: [`SYSTEM_CONFIG_PATH`], or
+/// `$DECLAIR_SYSTEM_CONFIG` for root. Ordinary users can't point it elsewhere, or they
+/// could drop the settings the admin locked; [`read_config_from`] and
+/// [`save_config_to`] take any path.
+pub fn system_config_path() -> PathBuf {
+    env::var_os("DECLAIR_SYSTEM_CONFIG")
+        .filter(|path| !path.is_empty() && is_root())
+        .map_or_else(|| PathBuf::from(SYSTEM_CONFIG_PATH), PathBuf::from)
+}
+
+/// The config file at `path` as a TOML table, or `None` if there is none.
+fn read_table(path: &Path) -> Result<Option<Table>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(toml::from_str(&contents)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Merge `over` into `base`: tables are merged key by key, any other value of `over`
+/// (arrays included) replaces the one of `base`.
+pub fn merge_tables(base: &mut Table, over: Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge_tables(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The keys of `user` that the system config `system` locks (`locked = ["licenses"]`,
+/// and `read_only` when it sets it).
+pub fn locked_keys(system: &Table, user: &Table) -> Vec<String> {
+    let listed = system
+        .get(LOCKED_KEY)
+        .and_then(toml::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(toml::Value::as_str);
+    let read_only = system.contains_key(READ_ONLY_KEY).then_some(READ_ONLY_KEY);
+    let keys: BTreeSet<&str> = listed
+        .chain(read_only)
+        .filter(|key| user.contains_key(*key))
+        .collect();
+    keys.into_iter().map(String::from).collect()
+}
+
+/// The user config `user` merged over the system config `system`, except for the keys
+/// the system config locks; `None` without either.
+pub fn layer_configs(system: Option<Table>, user: Option<Table>) -> Result<Option<Config>> {
+    let merged = match (system, user) {
+        (None, None) => return Ok(None),
+        (Some(mut system), user) => {
+            if let Some(mut user) = user {
+                for key in locked_keys(&system, &user) {
+                    user.remove(&key);
+                }
+                merge_tables(&mut system, user);
+            }
+            system.remove(LOCKED_KEY);
+            system
+        }
+        (None, Some(user)) => user,
+    };
+    Ok(Some(merged.try_into()?))
+}
+
+/// Remove from `table` what `base` sets to the same value, except [`REQUIRED_KEYS`], so a
+/// user's config only holds what differs from the system config.
+pub fn strip_inherited(table: &mut Table, base: &Table) {
+    table.retain(|key, value| {
+        let Some(inherited) = base.get(key) else {
+            return true;
+        };
+        if let (toml::Value::Table(table), toml::Value::Table(base)) = (&mut *value, inherited) {
+            strip_inherited(table, base);
+            return !table.is_empty();
+        }
+        REQUIRED_KEYS.contains(&key) || value != inherited
+    });
+}
+
+/// Overwrite declair's `config.toml` with `cfg`, leaving out what the system config
+/// already sets.
+pub fn save_config(cfg: &Config) -> Result<()> {
+    save_config_to(cfg, &system_config_path(), &config_file_path()?)
+}
+
+/// [`save_config`] with the system config at `system` and the user's at `user`.
+pub fn save_config_to(cfg: &Config, system: &Path, user: &Path) -> Result<()> {
+    if let Some(dir) = user.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut table = Table::try_from(cfg)?;
+    if let Some(system) = read_table(system)? {
+        strip_inherited(&mut table, &system);
+    }
+    write_atomic(user, toml::to_string(&table)?.as_bytes())?;
+    Ok(())
+}
+
+/// Read the program config: the user's `config.toml` merged over the system config, or
+/// `None` if neither exists yet. User settings the system config locks are ignored, with
+/// a warning.
+pub fn read_config() -> Result<Option<Config>> {
+    read_config_from(&system_config_path(), &config_file_path()?)
+}
+
+/// [`read_config`] with the system config at `system` and the user's at `user`.
+pub fn read_config_from(system: &Path, user: &Path) -> Result<Option<Config>> {
+    let system = read_table(system)?;
+    let user = read_table(user)?;
+    if let (Some(system), Some(user)) = (&system, &user) {
+        for key in locked_keys(system, user) {
+            eprintln!("{}", theme().warning(t!("config.locked", key)).for_stderr());
+        }
+    }
+    layer_configs(system, user)
+}
+
+/// Ask for the path of the Nix configuration, offering the recently used `recent` ones
+/// first when there are any.
+pub fn prompt_nix_path(recent: &[String]) -> Result<String> {
+    if !recent.is_empty() {
+        let mut items = recent.to_vec();
+        items.push(t!("config.other_path"));
+        let choice = Select::with_theme(prompt_theme())
+            .with_prompt(t!("config.recent_prompt"))
+            .items(&items)
+            .default(0)
+            .interact()?;
+        if let Some(path) = recent.get(choice) {
+            return Ok(path.clone());
+        }
+    }
+    let completion = FileCompletion::new();
+    let path: String = Input::with_theme(prompt_theme())
+        .with_prompt(t!("config.nix_path_prompt"))
+        .completion_with(&completion)
+        .interact_text()?;
+    Ok(unescape(&path))
+}
+
+/// Read existing program config or interactively create it.
+/// Fails instead of prompting when `no_interactive` is set.
+pub fn read_or_create_config(no_interactive: bool) -> Result<Config> {
+    if let Some(cfg) = read_config()? {
+        Ok(cfg)
+    } else {
+        if no_interactive {
+            return Err(DeclairError::Config(t!("config.not_found_noninteractive")));
+        }
+        let nix_path = prompt_nix_path(&recent_configs())?;
+        let auto_rebuild: bool = Confirm::with_theme(prompt_theme())
+            .with_prompt(t!("config.auto_rebuild_prompt"))
+            .default(false)
+            .interact()?;
+        let (home_manager, flake) = if auto_rebuild {
+            (
+                Confirm::with_theme(prompt_theme())
+                    .with_prompt(t!("config.home_manager_prompt"))
+                    .default(false)
+                    .interact()?,
+                Confirm::with_theme(prompt_theme())
+                    .with_prompt(t!("config.flake_prompt"))
+                    .default(false)
+                    .interact()?,
+            )
+        } else {
+            (false, false)
+        };
+        let cfg = Config {
+            nix_path,
+            auto_rebuild,
+            home_manager,
+            flake,
+            ..Default::default()
+        };
+        save_config(&cfg)?;
+        Ok(cfg)
+    }
+}
@@ -0,0 +1,217 @@
+use crate::edit::list_packages;
+use crate::error::{DeclairError, Result};
+use crate::groups::is_attr_path;
+use crate::module::ModuleKind;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec, eval_json};
+use crate::syntax::nix_string;
+use crate::t;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Evaluating `meta` of a few packages may have to fetch nixpkgs first.
+const EVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Something in the config tree that makes adding a package redundant or colliding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// `programs.<pkg>.enable = true` already installs the package.
+    ProgramEnabled { file: PathBuf, line: usize },
+    /// `programs.<program>.package = pkgs.<pkg>` already installs the package.
+    ProgramPackage {
+        program: String,
+        file: PathBuf,
+        line: usize,
+    },
+    /// The package is in the package list of another file.
+    ListedElsewhere { file: PathBuf },
+    /// An already configured package provides the same main command.
+    SameCommand { command: String, package: String },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Conflict::ProgramEnabled { file, line } => {
+                t!("conflict.program_enabled", file.display(), line)
+            }
+            Conflict::ProgramPackage {
+                program,
+                file,
+                line,
+            } => t!("conflict.program_package", program, file.display(), line),
+            Conflict::ListedElsewhere { file } => t!("conflict.listed_elsewhere", file.display()),
+            Conflict::SameCommand { command, package } => {
+                t!("conflict.same_command", package, command)
+            }
+        };
+        f.write_str(&message)
+    }
+}
+
+/// Scan every `.nix` file under `repo` for settings that already install `pkg`:
+/// an enabled `programs.<pkg>` module, a `programs.*.package = pkgs.<pkg>` override, or an
+/// entry in another file's package list. `target` (the file being edited) is only
+/// checked for program settings, since its own list is checked when adding.
+///
+/// Only the attribute-path form (`programs.neovim.enable = true;`) is recognised, not
+/// nested `programs.neovim = { enable = true; }` blocks.
+pub fn find_conflicts(
+    repo: &Path,
+    target: &Path,
+    pkg: &str,
+    kind: ModuleKind,
+) -> Result<Vec<Conflict>> {
+    let enable = format!("programs.{}.enable=true", pkg);
+    let package_suffix = format!(".package=pkgs.{};", pkg);
+    let target = fs::canonicalize(target).unwrap_or_else(|_| target.to_path_buf());
+
+    let mut conflicts = Vec::new();
+    for file in nix_files(repo) {
+        let Ok(contents) = fs::read_to_string(&file) else {
+            continue;
+        };
+        for (i, line) in contents.lines().enumerate() {
+            let code: String = line
+                .split('#')
+                .next()
+                .unwrap_or("")
+                .split_whitespace()
+                .collect();
+            if code.contains(&enable) {
+                conflicts.push(Conflict::ProgramEnabled {
+                    file: file.clone(),
+                    line: i + 1,
+                });
+            } else if let Some(lhs) = code.strip_suffix(&package_suffix)
+                && let Some(program) = lhs.strip_prefix("programs.")
+            {
+                conflicts.push(Conflict::ProgramPackage {
+                    program: program.to_string(),
+                    file: file.clone(),
+                    line: i + 1,
+                });
+            }
+        }
+        let is_target = fs::canonicalize(&file).is_ok_and(|f| f == target);
+        if !is_target && list_packages(&file, kind).is_ok_and(|pkgs| pkgs.iter().any(|p| p == pkg))
+        {
+            conflicts.push(Conflict::ListedElsewhere { file });
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Everything [`find_conflicts`] and [`command_conflicts`] find against adding `pkg` to
+/// `nix_file`. A command check that failed doesn't fail this; its error is returned next
+/// to the conflicts, to be shown as a warning.
+pub fn package_conflicts(
+    runner: &dyn CommandRunner,
+    repo: &Path,
+    nix_file: &Path,
+    pkg: &str,
+    kind: ModuleKind,
+) -> Result<(Vec<Conflict>, Option<DeclairError>)> {
+    let mut conflicts = find_conflicts(repo, nix_file, pkg, kind)?;
+    match command_conflicts(runner, pkg, &list_packages(nix_file, kind)?) {
+        Ok(found) => {
+            conflicts.extend(found);
+            Ok((conflicts, None))
+        }
+        Err(e) => Ok((conflicts, Some(e))),
+    }
+}
+
+/// The error refusing to add `pkg` over `conflicts` unless forced.
+pub fn conflict_error(pkg: &str, conflicts: &[Conflict]) -> DeclairError {
+    let reasons: Vec<String> = conflicts.iter().map(ToString::to_string).collect();
+    DeclairError::PackageConflict {
+        pkg: pkg.to_string(),
+        reason: t!("conflict.use_force", reasons.join("; ")),
+    }
+}
+
+/// Ask nixpkgs for `meta.mainProgram` of `pkg` and the `installed` packages and report the
+/// installed ones providing the same command. Packages without `mainProgram` are ignored.
+pub fn command_conflicts(
+    runner: &dyn CommandRunner,
+    pkg: &str,
+    installed: &[String],
+) -> Result<Vec<Conflict>> {
+    let others: Vec<&String> = installed
+        .iter()
+        .filter(|p| *p != pkg && is_attr_path(p))
+        .collect();
+    if others.is_empty() || !is_attr_path(pkg) {
+        return Ok(Vec::new());
+    }
+    let names: Vec<String> = std::iter::once(pkg)
+        .chain(others.iter().map(|p| p.as_str()))
+        .map(nix_string)
+        .collect();
+    let expr = format!(
+        "let pkgs = (builtins.getFlake \"nixpkgs\").legacyPackages.${{builtins.currentSystem}}; \
+         lib = pkgs.lib; \
+         main = name: let r = builtins.tryEval \
+         ((lib.attrByPath (lib.splitString \".\" name) {{ }} pkgs).meta.mainProgram or null); \
+         in if r.success then r.value else null; \
+         in map main [ {} ]",
+        names.join(" ")
+    );
+    let cmd = CommandSpec::new(
+        "nix",
+        [
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+            &expr,
+            "--extra-experimental-features",
+            "nix-command flakes",
+        ],
+    )
+    .timeout(EVAL_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query();
+    let programs: Vec<Option<String>> = eval_json(runner, &cmd)?;
+    let Some((Some(command), rest)) = programs.split_first() else {
+        return Ok(Vec::new());
+    };
+    Ok(others
+        .iter()
+        .zip(rest)
+        .filter(|(_, program)| program.as_ref() == Some(command))
+        .map(|(package, _)| Conflict::SameCommand {
+            command: command.clone(),
+            package: package.to_string(),
+        })
+        .collect())
+}
+
+/// All `.nix` files below `dir`, skipping hidden directories (`.git`) and symlinks
+/// (`result` links into the store).
+pub fn nix_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    pending.push(path);
+                }
+            } else if file_type.is_file() && path.extension().is_some_and(|e| e == "nix") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
@@ -0,0 +1,453 @@
+use crate::aliases::{Alias, Aliases, nixpkgs_dir};
+use crate::audit::{Nixpkgs, advisories_on_add};
+use crate::config::Config;
+use crate::conflicts::{conflict_error, package_conflicts};
+use crate::edit::{installed_packages, list_packages};
+use crate::error::{DeclairError, Result};
+use crate::evalcache;
+use crate::format::{Formatter, format_file};
+use crate::hooks::{HookContext, Phase, run_phase};
+use crate::paths::get_config_dir;
+use crate::policy::check_policy;
+use crate::rebuild::{RebuildTarget, rebuild_with_hooks};
+use crate::references::references_elsewhere;
+use crate::runner::CommandRunner;
+use crate::search::{PackageInfo, Query, SearchMode, search_configured};
+use crate::state::{State, state_file_path, unix_now, update_state};
+use crate::t;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::env;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+/// JSON-RPC error codes for malformed requests (application errors use the CLI exit codes).
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Default socket path: `$XDG_RUNTIME_DIR/declair.sock`, else inside declair's config dir.
+pub fn default_socket_path() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir).join("declair.sock"));
+    }
+    let dir = get_config_dir().ok_or_else(|| DeclairError::Config(t!("config.no_config_dir")))?;
+    Ok(dir.join("declair.sock"))
+}
+
+/// Outcome of the last rebuild started through the daemon.
+#[derive(Debug, Clone, Default)]
+enum RebuildStatus {
+    #[default]
+    Never,
+    Succeeded {
+        finished_at: u64,
+    },
+    Failed {
+        finished_at: u64,
+        error: String,
+    },
+}
+
+/// Long-running declair serving JSON-RPC 2.0 requests, one JSON object per line.
+/// Search results are cached for the daemon's lifetime, so repeated queries are instant.
+///
+/// Methods: `search {query}`, `list`, `add {package, force?, reason?}`, `remove {package}`,
+/// `rebuild` and `rebuild-status`. Adds and removes go through the same checks as on the
+/// command line and are recorded in the state file; `force` adds over conflicts.
+pub struct Daemon<'a> {
+    runner: &'a dyn CommandRunner,
+    config: Config,
+    nix_file: PathBuf,
+    target: RebuildTarget,
+    rebuild_dir: PathBuf,
+    search_cache: HashMap<(String, SearchMode), Vec<PackageInfo>>,
+    rebuild_status: RebuildStatus,
+    aliases: Aliases,
+    state_file: Option<PathBuf>,
+}
+
+impl<'a> Daemon<'a> {
+    pub fn new(
+        runner: &'a dyn CommandRunner,
+        config: Config,
+        nix_file: PathBuf,
+        target: RebuildTarget,
+        rebuild_dir: PathBuf,
+    ) -> Self {
+        Daemon {
+            runner,
+            config,
+            nix_file,
+            target,
+            rebuild_dir,
+            search_cache: HashMap::new(),
+            rebuild_status: RebuildStatus::default(),
+            aliases: Aliases::load(
+                nixpkgs_dir(&env::var("NIX_PATH").unwrap_or_default()).as_deref(),
+            ),
+            state_file: None,
+        }
+    }
+
+    /// Record adds and removes in `path` instead of the user's state file.
+    pub fn state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+
+    /// Accept clients on `socket` until the process is killed. A stale socket file left by
+    /// a crashed daemon is replaced; a live one is an error.
+    #[cfg(unix)]
+    pub fn serve(&mut self, socket: &Path) -> Result<()> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(DeclairError::InvalidPath {
+                    path: socket.to_path_buf(),
+                    reason: t!("daemon.already_listening"),
+                });
+            }
+            std::fs::remove_file(socket)?;
+        }
+        let listener = UnixListener::bind(socket)?;
+        for stream in listener.incoming() {
+            // A client hanging up mid-request must not take the daemon down.
+            if let Err(e) = stream
+                .map_err(DeclairError::from)
+                .and_then(|s| self.serve_client(s))
+            {
+                eprintln!("{}", t!("warning", e));
+            }
+        }
+        Ok(())
+    }
+
+    /// The daemon listens on a Unix socket, which this host doesn't have.
+    #[cfg(not(unix))]
+    pub fn serve(&mut self, _socket: &Path) -> Result<()> {
+        Err(DeclairError::UnsupportedHost {
+            operation: "daemon".to_string(),
+        })
+    }
+
+    #[cfg(unix)]
+    fn serve_client(&mut self, stream: UnixStream) -> Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = self.handle_line(&line);
+            writeln!(writer, "{}", response)?;
+        }
+        Ok(())
+    }
+
+    /// Answer one request line with one response object.
+    pub fn handle_line(&mut self, line: &str) -> Value {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return error_response(Value::Null, PARSE_ERROR, &e.to_string()),
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return error_response(id, INVALID_REQUEST, "Missing `method`");
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        match self.call(method, &params) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(RpcError::Protocol(code, message)) => error_response(id, code, &message),
+            Err(RpcError::Declair(e)) => error_response(id, e.exit_code() as i64, &e.to_string()),
+        }
+    }
+
+    fn call(&mut self, method: &str, params: &Value) -> std::result::Result<Value, RpcError> {
+        match method {
+            "search" => {
+                let query = string_param(params, "query")?;
+                let mode = match params.get("mode").and_then(Value::as_str) {
+                    None => SearchMode::default(),
+                    Some(name) => SearchMode::from_name(name).ok_or_else(|| {
+                        RpcError::Protocol(
+                            INVALID_PARAMS,
+                            format!("Unknown search mode `{}`", name),
+                        )
+                    })?,
+                };
+                Ok(json!(self.search(&Query::new(&query, mode)?)?))
+            }
+            "list" => Ok(json!(list_packages(&self.nix_file, self.target.kind)?)),
+            "add" => {
+                let requested = string_param(params, "package")?;
+                let package = self.resolve_alias(&requested)?;
+                if !self.config.licenses.is_empty() {
+                    let nixpkgs =
+                        Nixpkgs::for_config(&self.config, &self.rebuild_dir, &self.nix_file);
+                    let (nixpkgs, cache) = evalcache::open(self.runner, &nixpkgs);
+                    let policy = &self.config.licenses;
+                    check_policy(self.runner, policy, &nixpkgs, cache.as_ref(), &package)?;
+                }
+                let mut warnings = Vec::new();
+                if !params
+                    .get("force")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false)
+                {
+                    let (conflicts, skipped) = package_conflicts(
+                        self.runner,
+                        &self.rebuild_dir,
+                        &self.nix_file,
+                        &package,
+                        self.target.kind,
+                    )?;
+                    if !conflicts.is_empty() {
+                        return Err(conflict_error(&package, &conflicts).into());
+                    }
+                    warnings.extend(skipped.map(|e| t!("conflict.check_skipped", e)));
+                }
+                let advisories: Vec<String> = match advisories_on_add(
+                    self.runner,
+                    &self.config,
+                    &self.rebuild_dir,
+                    &self.nix_file,
+                    &package,
+                ) {
+                    Ok(findings) => findings.iter().map(|f| f.summary()).collect(),
+                    Err(e) => {
+                        warnings.push(e.to_string());
+                        Vec::new()
+                    }
+                };
+                let ctx = HookContext::new("add", vec![package.clone()], &self.nix_file);
+                run_phase(self.runner, &self.config.hooks, Phase::PreEdit, &ctx, None)?;
+                self.config.plugins.add_package(
+                    self.runner,
+                    &self.nix_file,
+                    &package,
+                    self.target.kind,
+                )?;
+                self.after_edit(&ctx);
+                let reason = params
+                    .get("reason")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                let nix_file = self.nix_file.clone();
+                warnings.extend(
+                    self.update_state(|state| state.record_added(&package, &nix_file, reason)),
+                );
+                let mut result = json!({
+                    "package": package,
+                    "file": self.nix_file,
+                    "advisories": advisories,
+                    "warnings": warnings,
+                });
+                if package != requested {
+                    result["replaces"] = json!(requested);
+                }
+                Ok(result)
+            }
+            "remove" => {
+                let package = string_param(params, "package")?;
+                State::load(&self.state_path()?)?.ensure_unpinned([package.as_str()])?;
+                let ctx = HookContext::new("remove", vec![package.clone()], &self.nix_file);
+                run_phase(self.runner, &self.config.hooks, Phase::PreEdit, &ctx, None)?;
+                let references: Vec<Value> =
+                    references_elsewhere(&self.rebuild_dir, &self.nix_file, &package)
+                        .into_iter()
+                        .map(|r| {
+                            json!({
+                                "file": r.file,
+                                "line": r.line,
+                                "kind": r.kind.to_string(),
+                                "text": r.text,
+                            })
+                        })
+                        .collect();
+                self.config.plugins.remove_package(
+                    self.runner,
+                    &self.nix_file,
+                    &package,
+                    self.target.kind,
+                )?;
+                self.after_edit(&ctx);
+                let warnings: Vec<String> = self
+                    .update_state(|state| state.forget(&package))
+                    .into_iter()
+                    .collect();
+                Ok(json!({
+                    "package": package,
+                    "file": self.nix_file,
+                    "references": references,
+                    "warnings": warnings,
+                }))
+            }
+            "rebuild" => {
+                let ctx = HookContext::new("rebuild", Vec::new(), &self.nix_file);
+                let result = rebuild_with_hooks(
+                    self.runner,
+                    &self.config,
+                    &self.target,
+                    &self.rebuild_dir,
+                    &ctx,
+                );
+                let finished_at = unix_now();
+                self.rebuild_status = match &result {
+                    Ok(()) => RebuildStatus::Succeeded { finished_at },
+                    Err(e) => RebuildStatus::Failed {
+                        finished_at,
+                        error: e.to_string(),
+                    },
+                };
+                result?;
+                Ok(self.rebuild_status_json())
+            }
+            "rebuild-status" => Ok(self.rebuild_status_json()),
+            _ => Err(RpcError::Protocol(
+                METHOD_NOT_FOUND,
+                format!("Unknown method `{}`", method),
+            )),
+        }
+    }
+
+    /// Results are cached, but whether each is installed is looked up anew: adds and
+    /// removes change it.
+    fn search(&mut self, query: &Query) -> Result<Vec<PackageInfo>> {
+        let key = (query.text.clone(), query.mode);
+        let mut results = match self.search_cache.get(&key) {
+            Some(cached) => cached.clone(),
+            None => {
+                let mut results: Vec<PackageInfo> =
+                    search_configured(self.runner, &self.config, query)?
+                        .into_values()
+                        .collect();
+                results.sort_by(|a, b| a.pname.cmp(&b.pname));
+                self.search_cache.insert(key, results.clone());
+                results
+            }
+        };
+        let installed = installed_packages(&self.rebuild_dir);
+        for info in &mut results {
+            info.installed = installed.contains(info.name());
+        }
+        Ok(results)
+    }
+
+    /// The package to add for `package`: its new name if nixpkgs renamed it, or the
+    /// suggested replacement if it was removed. A removed package without one is an error.
+    fn resolve_alias(&self, package: &str) -> Result<String> {
+        match self.aliases.get(package) {
+            None => Ok(package.to_string()),
+            Some(Alias::Renamed { new })
+            | Some(Alias::Removed {
+                suggestion: Some(new),
+                ..
+            }) => Ok(new.clone()),
+            Some(Alias::Removed { message, .. }) => Err(DeclairError::RemovedPackage {
+                pkg: package.to_string(),
+                message: message.clone(),
+            }),
+        }
+    }
+
+    /// Format the edited file and run the `post_edit` hooks; failures only warn.
+    fn state_path(&self) -> Result<PathBuf> {
+        match &self.state_file {
+            Some(path) => Ok(path.clone()),
+            None => state_file_path(),
+        }
+    }
+
+    /// Apply `f` to the state file. The edit it records is already written, so a failure
+    /// only comes back as a warning.
+    fn update_state(&self, f: impl FnOnce(&mut State)) -> Option<String> {
+        self.state_path()
+            .and_then(|path| update_state(&path, f))
+            .err()
+            .map(|e| t!("warning.state_update", e))
+    }
+
+    fn after_edit(&self, ctx: &HookContext) {
+        if self.config.formatter != Formatter::None
+            && let Err(e) = format_file(self.runner, self.config.formatter, &self.nix_file)
+        {
+            eprintln!("{}", t!("warning", e));
+        }
+        if let Err(e) = run_phase(self.runner, &self.config.hooks, Phase::PostEdit, ctx, None) {
+            eprintln!("{}", t!("warning", e));
+        }
+    }
+
+    fn rebuild_status_json(&self) -> Value {
+        match &self.rebuild_status {
+            RebuildStatus::Never => json!({ "state": "never" }),
+            RebuildStatus::Succeeded { finished_at } => {
+                json!({ "state": "succeeded", "finished_at": finished_at })
+            }
+            RebuildStatus::Failed { finished_at, error } => {
+                json!({ "state": "failed", "finished_at": finished_at, "error": error })
+            }
+        }
+    }
+}
+
+enum RpcError {
+    Protocol(i64, String),
+    Declair(DeclairError),
+}
+
+impl From<DeclairError> for RpcError {
+    fn from(e: DeclairError) -> Self {
+        RpcError::Declair(e)
+    }
+}
+
+fn string_param(params: &Value, name: &str) -> std::result::Result<String, RpcError> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| {
+            RpcError::Protocol(
+                INVALID_PARAMS,
+                format!("Missing string parameter `{}`", name),
+            )
+        })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Send one request to a running daemon and return its `result`, or its error as
+/// [`DeclairError::Rpc`].
+#[cfg(unix)]
+pub fn call_daemon(socket: &Path, method: &str, params: Value) -> Result<Value> {
+    let mut stream = UnixStream::connect(socket).map_err(|e| DeclairError::Read {
+        path: socket.to_path_buf(),
+        source: e,
+    })?;
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    writeln!(stream, "{}", request)?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let response: Value = serde_json::from_str(&line)
+        .map_err(|e| DeclairError::Rpc(t!("daemon.invalid_response", e)))?;
+    if let Some(error) = response.get("error") {
+        let message = error.get("message").and_then(Value::as_str).unwrap_or("");
+        return Err(DeclairError::Rpc(message.to_string()));
+    }
+    Ok(response.get("result").cloned().unwrap_or(Value::Null))
+}
+
+/// Without Unix sockets there is no daemon to call.
+#[cfg(not(unix))]
+pub fn call_daemon(_socket: &Path, _method: &str, _params: Value) -> Result<Value> {
+    Err(DeclairError::UnsupportedHost {
+        operation: "rpc".to_string(),
+    })
+}
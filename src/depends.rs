@@ -0,0 +1,150 @@
+use crate::error::{DeclairError, Result};
+use crate::host::home_dir;
+use crate::module::ModuleKind;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use crate::t;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Store queries are local, but `why-depends` walks the whole closure.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Store paths that merely collect a profile's packages (`environment.systemPackages`,
+/// `home.packages`); a package referenced by one of them was installed explicitly.
+const PROFILE_PATHS: &[&str] = &["system-path", "home-manager-path"];
+
+/// The profile whose closure holds what a config of `kind` installs.
+pub fn profile_root(kind: ModuleKind) -> Result<PathBuf> {
+    match kind {
+        ModuleKind::Nixos => Ok(PathBuf::from("/run/current-system")),
+        ModuleKind::HomeManager => {
+            let home = home_dir().ok_or(DeclairError::NoHomeDir)?;
+            Ok(home.join(".local/state/nix/profiles/home-manager"))
+        }
+    }
+}
+
+/// How one store path of a package ended up in a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReverseDependencies {
+    /// Store path of the package, e.g. `/nix/store/...-ripgrep-14.1.0`.
+    pub path: String,
+    /// Referenced by the profile's package list, i.e. installed explicitly.
+    pub in_profile: bool,
+    /// Names of the other store paths in the profile that depend on it.
+    pub required_by: Vec<String>,
+}
+
+/// Find `pkg` in the closure of `root` and list what references each of its store paths.
+/// An empty result means the package is not installed in that profile.
+pub fn reverse_dependencies(
+    runner: &dyn CommandRunner,
+    root: &Path,
+    pkg: &str,
+) -> Result<Vec<ReverseDependencies>> {
+    let root = root.to_string_lossy();
+    let closure = nix_store(runner, &["--query", "--requisites", &root])?;
+    let in_closure: HashSet<&str> = closure.iter().map(String::as_str).collect();
+
+    let mut result = Vec::new();
+    for path in closure.iter().filter(|p| is_package_path(p, pkg)) {
+        let mut in_profile = false;
+        let mut required_by = BTreeSet::new();
+        for referrer in nix_store(runner, &["--query", "--referrers", path])? {
+            if referrer == *path || !in_closure.contains(referrer.as_str()) {
+                continue;
+            }
+            let name = store_name(&referrer);
+            if PROFILE_PATHS.contains(&name) {
+                in_profile = true;
+            } else {
+                required_by.insert(name.to_string());
+            }
+        }
+        result.push(ReverseDependencies {
+            path: path.clone(),
+            in_profile,
+            required_by: required_by.into_iter().collect(),
+        });
+    }
+    Ok(result)
+}
+
+/// Store paths of the packages the profile at `root` lists explicitly: what its package
+/// collection (`system-path`, `home-manager-path`) references.
+pub fn profile_packages(runner: &dyn CommandRunner, root: &Path) -> Result<Vec<String>> {
+    let root = root.to_string_lossy();
+    let collection = nix_store(runner, &["--query", "--references", &root])?
+        .into_iter()
+        .find(|path| PROFILE_PATHS.contains(&store_name(path)))
+        .ok_or_else(|| DeclairError::StoreQueryFailed(t!("depends.no_collection", root)))?;
+    Ok(
+        nix_store(runner, &["--query", "--references", &collection])?
+            .into_iter()
+            .filter(|path| *path != collection)
+            .collect(),
+    )
+}
+
+/// Output of `nix why-depends`: the chain of references from `root` to `path`.
+pub fn why_depends(runner: &dyn CommandRunner, root: &Path, path: &str) -> Result<String> {
+    let root = root.to_string_lossy();
+    let cmd = CommandSpec::new(
+        "nix",
+        [
+            "why-depends",
+            &root,
+            path,
+            "--extra-experimental-features",
+            "nix-command",
+        ],
+    )
+    .timeout(QUERY_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query();
+    let stdout = run_query(runner, &cmd, "nix why-depends")?;
+    Ok(stdout.trim_end().to_string())
+}
+
+/// Name part of a store path: `/nix/store/<hash>-ripgrep-14.1.0` -> `ripgrep-14.1.0`.
+pub fn store_name(path: &str) -> &str {
+    let base = path.rsplit('/').next().unwrap_or(path);
+    base.split_once('-').map_or(base, |(_, name)| name)
+}
+
+/// Whether a store path is (a version or output of) `pkg`: `ripgrep-14.1.0`, `ripgrep`,
+/// but not `ripgrep-all-0.10.6`.
+fn is_package_path(path: &str, pkg: &str) -> bool {
+    match store_name(path).strip_prefix(pkg) {
+        Some("") => true,
+        Some(rest) => rest
+            .strip_prefix('-')
+            .and_then(|r| r.chars().next())
+            .is_some_and(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+fn nix_store(runner: &dyn CommandRunner, args: &[&str]) -> Result<Vec<String>> {
+    let cmd = CommandSpec::new("nix-store", args.iter().copied())
+        .timeout(QUERY_TIMEOUT)
+        .class(CommandClass::Eval)
+        .query();
+    let stdout = run_query(runner, &cmd, "nix-store")?;
+    Ok(stdout.lines().map(str::to_string).collect())
+}
+
+fn run_query(runner: &dyn CommandRunner, cmd: &CommandSpec, name: &str) -> Result<String> {
+    let output = runner
+        .output(cmd)
+        .map_err(|e| DeclairError::StoreQueryFailed(t!("command.spawn", name, e)))?;
+    if !output.status.success() {
+        return Err(DeclairError::StoreQueryFailed(t!(
+            "command.failed_output",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
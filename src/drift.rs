@@ -0,0 +1,72 @@
+//! Drift between the configuration and the running system: packages the configuration
+//! lists that the active generation lacks, and the other way round.
+
+use std::collections::BTreeSet;
+
+/// Packages the configuration and the running generation disagree on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Drift {
+    /// Configured attributes no package of the running generation matches.
+    pub missing: Vec<String>,
+    /// Names of packages in the running generation no configured attribute matches.
+    pub extra: Vec<String>,
+}
+
+impl Drift {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Package name of a store name without its version and output: `ripgrep-14.1.0` ->
+/// `ripgrep`, `curl-8.9.1-bin` -> `curl`, `python3-3.12.4` -> `python3`.
+pub fn package_name(store_name: &str) -> &str {
+    store_name
+        .match_indices('-')
+        .find(|(i, _)| {
+            store_name[i + 1..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_digit())
+        })
+        .map_or(store_name, |(i, _)| &store_name[..i])
+}
+
+/// Whether the attribute `attr` (e.g. `ripgrep`, `gnome.nautilus`, `nodejs_20`) plausibly
+/// builds the package `name`: the last attribute component equals it, or only adds a
+/// `_<version>` or digit suffix to it.
+pub fn provides(attr: &str, name: &str) -> bool {
+    let attr = attr.rsplit('.').next().unwrap_or(attr);
+    if attr.eq_ignore_ascii_case(name) {
+        return true;
+    }
+    attr.strip_prefix(name).is_some_and(|rest| {
+        let rest = rest.strip_prefix('_').unwrap_or(rest);
+        !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit() || c == '_')
+    })
+}
+
+/// Compare the `configured` attributes with the package names `installed` in the running
+/// generation. A generation holds many packages nobody listed (NixOS's defaults, packages
+/// enabled through `programs.*`), so with `tracked` only installed packages one of those
+/// attributes provides count as extra, e.g. the ones declair added or removed.
+pub fn drift(
+    configured: &BTreeSet<String>,
+    installed: &BTreeSet<String>,
+    tracked: Option<&BTreeSet<String>>,
+) -> Drift {
+    let missing = configured
+        .iter()
+        .filter(|attr| !installed.iter().any(|name| provides(attr, name)))
+        .cloned()
+        .collect();
+    let extra = installed
+        .iter()
+        .filter(|name| !configured.iter().any(|attr| provides(attr, name)))
+        .filter(|name| {
+            tracked.is_none_or(|tracked| tracked.iter().any(|attr| provides(attr, name)))
+        })
+        .cloned()
+        .collect();
+    Drift { missing, extra }
+}
@@ -0,0 +1,1288 @@
+use crate::atomic::write_atomic;
+use crate::backups::record_backup;
+use crate::conflicts::nix_files;
+use crate::error::{DeclairError, Result};
+use crate::groups::is_attr_path;
+use crate::lock::{FileLock, FileSnapshot};
+use crate::module::{ModuleKind, Scope};
+use crate::paths::get_backups_dir;
+use crate::syntax::nix_string;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Trailing comment on every entry declair inserts into a multiline list, so its own
+/// additions can be told apart from hand-written ones.
+pub const MANAGED_MARKER: &str = "# managed by declair";
+
+/// A locked read-modify-write of one config file. The file is locked against other
+/// declair processes, and the write is refused if its contents changed since reading.
+pub(crate) struct Edit<'a> {
+    path: &'a Path,
+    contents: String,
+    snapshot: FileSnapshot,
+    _lock: FileLock,
+}
+
+impl<'a> Edit<'a> {
+    pub(crate) fn begin(path: &'a Path) -> Result<Self> {
+        let lock = FileLock::acquire(path)?;
+        let contents = fs::read_to_string(path).map_err(|e| DeclairError::Read {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        let snapshot = FileSnapshot::of_contents(path, contents.as_bytes());
+        Ok(Edit {
+            path,
+            contents,
+            snapshot,
+            _lock: lock,
+        })
+    }
+
+    pub(crate) fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.contents.lines().map(|s| s.to_string()).collect()
+    }
+
+    /// Write a backup copy of the original contents (overwrite if already exists) and
+    /// keep them in the backup history.
+    pub(crate) fn backup(&self) -> Result<()> {
+        let backup_path = backup_path(self.path);
+        create_backup_dir(&backup_path)?;
+        fs::write(&backup_path, &self.contents).map_err(|e| DeclairError::Backup {
+            path: backup_path.clone(),
+            source: e,
+        })?;
+        record_backup(self.path, self.contents.as_bytes())
+    }
+
+    pub(crate) fn commit(self, new_contents: &str) -> Result<()> {
+        self.snapshot.verify()?;
+        write_atomic(self.path, new_contents.as_bytes()).map_err(|e| DeclairError::Write {
+            path: self.path.to_path_buf(),
+            source: e,
+        })
+    }
+}
+
+/// Add a package to NixOS config (input — already valid file path)
+pub fn add_package_to_nix(file_path: &Path, pkg: &str, scope: impl Into<Scope>) -> Result<()> {
+    add_package_to_segment(file_path, pkg, scope, 0)
+}
+
+/// Like [`add_package_to_nix`], but into the `segment`th list of the block as returned by
+/// [`list_segments`]: 0 is the `with pkgs; [` list itself, the others its conditional
+/// segments.
+pub fn add_package_to_segment(
+    file_path: &Path,
+    pkg: &str,
+    scope: impl Into<Scope>,
+    segment: usize,
+) -> Result<()> {
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+    // make backup (overwrite if already exists)
+    edit.backup()?;
+    insert_package(file_path, &mut lines, pkg, &scope.into(), segment)?;
+    edit.commit(&lines.join("\n"))
+}
+
+/// How to get a `with pkgs; [` block into a file that has none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MissingBlock {
+    /// Turn an existing list (see [`find_candidate_lists`]) into a `with pkgs; [` block.
+    Adopt(CandidateList),
+    /// Create `<attr> = with pkgs; [ ];` before the file's closing brace.
+    Create(String),
+}
+
+/// Like [`add_package_to_nix`], but first creates the package block as described by
+/// `resolution`. The backup holds the file as it was before both edits.
+pub fn add_package_resolving_block(
+    file_path: &Path,
+    pkg: &str,
+    scope: impl Into<Scope>,
+    resolution: &MissingBlock,
+) -> Result<()> {
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+    edit.backup()?;
+    match resolution {
+        MissingBlock::Adopt(candidate) => adopt_list(file_path, &mut lines, candidate)?,
+        MissingBlock::Create(attr) => create_block(file_path, &mut lines, attr)?,
+    }
+    insert_package(file_path, &mut lines, pkg, &scope.into(), 0)?;
+    edit.commit(&lines.join("\n"))
+}
+
+fn read_lines(file_path: &Path) -> Result<Vec<String>> {
+    let contents = fs::read_to_string(file_path).map_err(|e| DeclairError::Read {
+        path: file_path.to_path_buf(),
+        source: e,
+    })?;
+    Ok(contents.lines().map(|s| s.to_string()).collect())
+}
+
+/// Names inherited from `pkgs` in an attribute set of packages, as in
+/// `environment.systemPackages = builtins.attrValues { inherit (pkgs) git ripgrep; };`.
+const INHERIT_PKGS: &str = "inherit (pkgs)";
+
+/// Attributes of the system fonts list: `fonts.packages`, `fonts.fonts` before NixOS 23.11.
+const FONT_ATTRS: &[&str] = &["fonts.packages", "fonts.fonts"];
+
+/// One bracketed list of a package block: the `with pkgs; [ ... ]` list itself, or a
+/// conditional segment appended to it, as in `with pkgs; [ git ] ++ lib.optionals
+/// stdenv.isLinux [ strace ]`. The names of an `inherit (pkgs) ...;` are one too, from
+/// the closing parenthesis to the `;`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Segment {
+    /// What the segment is appended with, e.g. `lib.optionals stdenv.isLinux`; `None` for
+    /// the list itself.
+    condition: Option<String>,
+    /// Line and byte offset of the opening bracket.
+    open: (usize, usize),
+    /// Line and byte offset of the matching closing bracket.
+    close: (usize, usize),
+    /// Whether this is an `inherit (pkgs) ...;`.
+    inherit: bool,
+}
+
+/// Segments of the package block of `scope`. A `with pkgs; [` block or a
+/// `builtins.attrValues { inherit (pkgs) ...; }` assigned to the scope's package attribute
+/// comes first. The system and Home Manager scopes then fall back to the first
+/// `with pkgs; [` block in the file, then its first `inherit (pkgs)`, leaving out the
+/// lists of users and fonts; a user's list is also found as `packages = ...` inside a
+/// `users.users.<name> = { ... };`. The list itself is the first segment.
+fn find_package_block(lines: &[String], scope: &Scope) -> Option<Vec<Segment>> {
+    let is_block = |l: &String| l.contains("with pkgs; [");
+    let is_set = |l: &String| l.contains("attrValues");
+    let attr = scope.packages_attr();
+    let users = user_blocks(lines);
+    let found = |idx: usize| {
+        if is_block(&lines[idx]) {
+            block_segments(lines, idx)
+        } else {
+            inherit_segment(lines, idx).map(|segment| vec![segment])
+        }
+    };
+    let assigned = |idx: usize, attr: &str| assigns(&lines[idx], attr);
+    let owner = |idx: usize| {
+        users
+            .iter()
+            .find(|(_, start, end)| (*start..=*end).contains(&idx))
+            .map(|(name, _, _)| name.as_str())
+    };
+
+    if let Scope::User(name) = scope {
+        let idx = (0..lines.len()).find(|&idx| {
+            (is_block(&lines[idx]) || is_set(&lines[idx]))
+                && (assigned(idx, &attr)
+                    || (owner(idx) == Some(name.as_str()) && assigned(idx, "packages")))
+        })?;
+        return found(idx);
+    }
+    if *scope == Scope::Fonts {
+        let idx = (0..lines.len()).find(|&idx| {
+            (is_block(&lines[idx]) || is_set(&lines[idx]))
+                && FONT_ATTRS.iter().any(|attr| assigned(idx, attr))
+        })?;
+        return found(idx);
+    }
+    if let Some(start_idx) =
+        (0..lines.len()).find(|&idx| is_block(&lines[idx]) && assigned(idx, &attr))
+    {
+        return block_segments(lines, start_idx);
+    }
+    if let Some(set_idx) = (0..lines.len()).find(|&idx| is_set(&lines[idx]) && assigned(idx, &attr))
+        && let Some(segment) = inherit_segment(lines, set_idx)
+    {
+        return Some(vec![segment]);
+    }
+    let shared = |idx: usize| {
+        owner(idx).is_none()
+            && !lines[idx].contains("users.users.")
+            && !FONT_ATTRS.iter().any(|attr| assigned(idx, attr))
+    };
+    if let Some(start_idx) = (0..lines.len()).find(|&idx| is_block(&lines[idx]) && shared(idx)) {
+        return block_segments(lines, start_idx);
+    }
+    let idx = (0..lines.len()).find(|&idx| {
+        lines[idx]
+            .split('#')
+            .next()
+            .is_some_and(|code| code.contains(INHERIT_PKGS))
+            && shared(idx)
+    })?;
+    inherit_segment(lines, idx).map(|segment| vec![segment])
+}
+
+/// Whether `line` assigns to an attribute path ending in `attr`; quotes around path
+/// components, as in `users.users."alice".packages`, don't matter.
+fn assigns(line: &str, attr: &str) -> bool {
+    line.split_once('=')
+        .is_some_and(|(lhs, _)| lhs.trim().replace('"', "").ends_with(attr))
+}
+
+/// The `users.users.<name> = { ... };` sets of `lines`: each user's name with the lines
+/// of the set's braces.
+fn user_blocks(lines: &[String]) -> Vec<(String, usize, usize)> {
+    let mut blocks = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let Some((lhs, rhs)) = line.split('#').next().unwrap_or("").split_once('=') else {
+            continue;
+        };
+        let Some(name) = lhs
+            .trim()
+            .replace('"', "")
+            .strip_prefix("users.users.")
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        if name.is_empty() || name.contains('.') || !rhs.trim_start().starts_with('{') {
+            continue;
+        }
+        let brace = lhs.len() + 1 + rhs.find('{').unwrap_or(0);
+        let mut depth = 0i32;
+        let end = code_chars(lines, (idx, brace))
+            .into_iter()
+            .find(|&(_, _, c)| {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            });
+        if let Some((end, _, _)) = end {
+            blocks.push((name, idx, end));
+        }
+    }
+    blocks
+}
+
+/// The scopes `file_path` has a package list for: the system's or Home Manager's, after
+/// `kind`, the system fonts, then those of the users with a list of their own, in file
+/// order.
+pub fn package_scopes(file_path: &Path, kind: ModuleKind) -> Result<Vec<Scope>> {
+    let lines = read_lines(file_path)?;
+    let mut names: Vec<String> = user_blocks(&lines)
+        .into_iter()
+        .map(|(name, _, _)| name)
+        .collect();
+    for line in &lines {
+        if let Some((lhs, _)) = line.split('#').next().unwrap_or("").split_once('=')
+            && let Some(name) = lhs
+                .trim()
+                .replace('"', "")
+                .strip_prefix("users.users.")
+                .and_then(|rest| rest.strip_suffix(".packages"))
+            && !names.iter().any(|n| n == name)
+        {
+            names.push(name.to_string());
+        }
+    }
+    Ok([Scope::from(kind), Scope::Fonts]
+        .into_iter()
+        .chain(names.into_iter().map(Scope::User))
+        .filter(|scope| scope.kind() == kind && find_package_block(&lines, scope).is_some())
+        .collect())
+}
+
+/// The first `inherit (pkgs) ...;` on line `from_idx` or after it.
+fn inherit_segment(lines: &[String], from_idx: usize) -> Option<Segment> {
+    let (idx, col) = lines
+        .iter()
+        .enumerate()
+        .skip(from_idx)
+        .find_map(|(idx, l)| Some((idx, l.split('#').next()?.find(INHERIT_PKGS)?)))?;
+    let open = (idx, col + INHERIT_PKGS.len() - 1);
+    let &(line, col, _) = code_chars(lines, open)
+        .iter()
+        .find(|&&(_, _, c)| c == ';')?;
+    Some(Segment {
+        condition: None,
+        open,
+        close: (line, col),
+        inherit: true,
+    })
+}
+
+/// Whether `pkg` can be listed in an `inherit (pkgs) ...;`: a plain attribute name, not
+/// a path like `python3Packages.requests` or an expression.
+fn inheritable(pkg: &str) -> bool {
+    pkg.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && pkg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-'".contains(c))
+}
+
+/// Segments of the block opening on line `start_idx`; `None` when its list isn't closed.
+/// Segments are followed as long as the list is continued with `++ <condition> [`, where
+/// the condition is anything without a list or a `;` in it, such as `lib.optionals
+/// stdenv.isLinux` or `lib.optionals (!stdenv.isDarwin)`.
+fn block_segments(lines: &[String], start_idx: usize) -> Option<Vec<Segment>> {
+    let marker = "with pkgs; ";
+    let open_col = lines[start_idx].find(marker)? + marker.len();
+    let chars = code_chars(lines, (start_idx, open_col));
+    let mut segments = Vec::new();
+    let mut condition = None;
+    let mut open = 0;
+    loop {
+        let mut depth = 0i32;
+        let close = open
+            + chars[open..].iter().position(|&(_, _, c)| {
+                match c {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    _ => {}
+                }
+                depth == 0
+            })?;
+        segments.push(Segment {
+            condition: condition.take(),
+            open: (chars[open].0, chars[open].1),
+            close: (chars[close].0, chars[close].1),
+            inherit: false,
+        });
+
+        let rest = &chars[close + 1..];
+        let Some(plus) = rest.iter().position(|&(_, _, c)| !c.is_whitespace()) else {
+            break;
+        };
+        if rest[plus].2 != '+' || rest.get(plus + 1).map(|&(_, _, c)| c) != Some('+') {
+            break;
+        }
+        let after = plus + 2;
+        let mut parens = 0i32;
+        let Some(bracket) = rest[after..].iter().position(|&(_, _, c)| {
+            match c {
+                '(' => parens += 1,
+                ')' => parens -= 1,
+                _ => {}
+            }
+            parens == 0 && matches!(c, '[' | ']' | ';' | '{' | '}')
+        }) else {
+            break;
+        };
+        let bracket = after + bracket;
+        let text = text_between(
+            lines,
+            (rest[after].0, rest[after].1),
+            (rest[bracket].0, rest[bracket].1),
+        );
+        if rest[bracket].2 != '[' || text.is_empty() || text.contains("++") {
+            break;
+        }
+        condition = Some(text);
+        open = close + 1 + bracket;
+    }
+    Some(segments)
+}
+
+/// The characters of `lines` from `from` on, with their line and byte offset, leaving
+/// out comments and strings; every line ends in a `\n`.
+fn code_chars(lines: &[String], from: (usize, usize)) -> Vec<(usize, usize, char)> {
+    let mut chars = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, line) in lines.iter().enumerate().skip(from.0) {
+        let offset = if idx == from.0 { from.1 } else { 0 };
+        for (col, c) in line[offset..].char_indices() {
+            if in_string {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '#' => break,
+                '"' => in_string = true,
+                _ => chars.push((idx, offset + col, c)),
+            }
+        }
+        chars.push((idx, line.len(), '\n'));
+    }
+    chars
+}
+
+/// The source from `from` up to (not including) `to`, without comments and with runs of
+/// whitespace, line breaks included, collapsed to one space.
+fn text_between(lines: &[String], from: (usize, usize), to: (usize, usize)) -> String {
+    let mut parts = Vec::new();
+    for (idx, line) in lines.iter().enumerate().take(to.0 + 1).skip(from.0) {
+        let start = if idx == from.0 { from.1 } else { 0 };
+        let end = if idx == to.0 { to.1 } else { line.len() };
+        parts.push(line[start..end].split('#').next().unwrap_or(""));
+    }
+    parts
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Insert `pkg` into the `segment`th list of the block chosen by [`find_package_block`].
+/// The package must not be in any of the block's lists yet.
+fn insert_package(
+    file_path: &Path,
+    lines: &mut Vec<String>,
+    pkg: &str,
+    scope: &Scope,
+    segment: usize,
+) -> Result<()> {
+    // find the lists of the "with pkgs; [" block
+    let Some(segments) = find_package_block(lines, scope) else {
+        return Err(DeclairError::BlockNotFound {
+            file: file_path.to_path_buf(),
+        });
+    };
+    // find line with pkg (comments, including the managed marker, don't count)
+    for existing in &segments {
+        if let Some((idx, _, _)) = segment_entries(lines, existing)
+            .into_iter()
+            .find(|(_, entry, _)| entry == pkg)
+        {
+            return Err(DeclairError::PackageExists {
+                pkg: pkg.to_string(),
+                file: file_path.to_path_buf(),
+                line: idx + 1,
+            });
+        }
+    }
+    let Segment {
+        open,
+        close,
+        inherit,
+        ..
+    } = segments
+        .get(segment)
+        .ok_or_else(|| DeclairError::MalformedBlock {
+            file: file_path.to_path_buf(),
+        })?
+        .clone();
+    if inherit && !inheritable(pkg) {
+        return Err(DeclairError::NotInheritable {
+            pkg: pkg.to_string(),
+            file: file_path.to_path_buf(),
+        });
+    }
+    if open.0 == close.0 {
+        // everything in one line, e.g.: with pkgs; [ git ] ++ lib.optionals cond [ ]
+        let line = &lines[open.0];
+        let (before, after) = line.split_at(close.1);
+        lines[open.0] = if inherit {
+            format!("{} {}{}", before.trim_end(), pkg, after)
+        } else if line[open.1 + 1..close.1].trim().is_empty() {
+            format!("{}[ {} {}", &line[..open.1], pkg, after)
+        } else if before.ends_with(' ') {
+            format!("{}{} {}", before, pkg, after)
+        } else {
+            format!("{} {} {}", before, pkg, after)
+        };
+    } else {
+        // multiline case
+        let indent = entry_indent(&lines[open.0 + 1..close.0], &lines[close.0]);
+        lines.insert(close.0, format!("{}{} {}", indent, pkg, MANAGED_MARKER));
+    }
+    Ok(())
+}
+
+/// Package-like tokens on a line of a list, ignoring any trailing comment.
+fn entries_of(line: &str) -> impl Iterator<Item = &str> {
+    let code = line.split('#').next().unwrap_or("");
+    split_entries(code).into_iter()
+}
+
+/// Entries of (part of) a package list: separated by whitespace, brackets and the `;`
+/// ending an `inherit`, except that a parenthesized expression such as
+/// `(callPackage ./pkgs/foo.nix { })` is one entry.
+fn split_entries(text: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut start = None;
+    let mut depth = 0usize;
+    for (i, c) in text.char_indices() {
+        if depth == 0 && (c.is_whitespace() || matches!(c, '[' | ']' | ';')) {
+            if let Some(s) = start.take() {
+                entries.push(&text[s..i]);
+            }
+            continue;
+        }
+        start.get_or_insert(i);
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        entries.push(&text[s..]);
+    }
+    entries
+}
+
+/// The `(callPackage <path> { })` entry for the derivation in `derivation`, with the path
+/// relative to `nix_file`'s directory.
+pub fn call_package_entry(nix_file: &Path, derivation: &Path) -> String {
+    format!(
+        "(callPackage {} {{ }})",
+        relative_nix_path(nix_file, derivation)
+    )
+}
+
+/// `target` as a Nix path literal relative to the directory of `from`: `./pkgs/foo.nix`,
+/// `../pkgs/foo.nix`, or absolute when they share no ancestor.
+pub fn relative_nix_path(from: &Path, target: &Path) -> String {
+    let absolute = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.to_path_buf());
+    let target = absolute(target);
+    let base = absolute(from);
+    let base = base.parent().unwrap_or(Path::new("/"));
+    let common = base
+        .components()
+        .zip(target.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common <= 1 {
+        return target.display().to_string();
+    }
+    let ups = base.components().count() - common;
+    let rest: PathBuf = target.components().skip(common).collect();
+    let prefix = if ups == 0 {
+        "./".to_string()
+    } else {
+        "../".repeat(ups)
+    };
+    format!("{}{}", prefix, rest.display())
+}
+
+/// Indentation for a new entry in a multiline list: the same as the last existing entry,
+/// or one level deeper than the closing bracket when the list is empty.
+fn entry_indent(entries: &[String], closing_line: &str) -> String {
+    let leading = |l: &str| -> String { l.chars().take_while(|c| c.is_whitespace()).collect() };
+    if let Some(sibling) = entries.iter().rev().find(|l| !l.trim().is_empty()) {
+        return leading(sibling);
+    }
+    let closing = leading(closing_line);
+    let unit = if closing.contains('\t') { "\t" } else { "  " };
+    format!("{}{}", closing, unit)
+}
+
+/// A list assignment that could hold packages but isn't a `with pkgs; [` block yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandidateList {
+    /// Zero-based index of the line holding the assignment.
+    pub line: usize,
+    /// The trimmed source line, for display.
+    pub text: String,
+}
+
+/// Find assignments such as `home.packages = [` or `environment.systemPackages = with pkgs;[`
+/// that declair could adopt when the file has no `with pkgs; [` block.
+pub fn find_candidate_lists(file_path: &Path) -> Result<Vec<CandidateList>> {
+    let lines = read_lines(file_path)?;
+    Ok(lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, l)| {
+            let trimmed = l.trim();
+            let (lhs, _) = trimmed.split_once('=')?;
+            if !lhs.trim().to_lowercase().ends_with("packages") {
+                return None;
+            }
+            adopted_line(l)?;
+            Some(CandidateList {
+                line: i,
+                text: trimmed.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// `line` with its list opening in `with pkgs; [`, when that keeps what it means: a bare
+/// `= [` (or `= with pkgs;[`) becomes `= with pkgs; [`, and `with pkgs; ` goes right
+/// before the `[` of a list already inside other `with`s (`= with lib; [`). Anything else
+/// between `=` and `[`, like `lib.optionals cond [` or `extra ++ [`, isn't adopted.
+fn adopted_line(line: &str) -> Option<String> {
+    let eq = line.find('=')?;
+    let lbr = eq + line[eq..].find('[')?;
+    let between = line[eq + 1..lbr].trim();
+    let scopes: Vec<&str> = between.split(';').map(str::trim).collect();
+    let (last, withs) = scopes.split_last()?;
+    if !last.is_empty() {
+        return None;
+    }
+    let mut names = Vec::new();
+    for scope in withs {
+        names.push(scope.strip_prefix("with ")?.trim());
+    }
+    if names.iter().any(|name| !is_attr_path(name)) {
+        return None;
+    }
+    if names.iter().all(|name| *name == "pkgs") {
+        return Some(format!("{}= with pkgs; [{}", &line[..eq], &line[lbr + 1..]));
+    }
+    Some(format!("{}with pkgs; {}", &line[..lbr], &line[lbr..]))
+}
+
+/// Make the candidate's list open in `with pkgs; [`. Existing `pkgs.foo` entries keep
+/// working since `pkgs` stays in scope.
+fn adopt_list(file_path: &Path, lines: &mut [String], candidate: &CandidateList) -> Result<()> {
+    let adopted = lines
+        .get(candidate.line)
+        .and_then(|line| adopted_line(line))
+        .ok_or_else(|| DeclairError::MalformedBlock {
+            file: file_path.to_path_buf(),
+        })?;
+    lines[candidate.line] = adopted;
+    Ok(())
+}
+
+/// Insert an empty `<attr> = with pkgs; [ ];` block right before the last closing brace.
+fn create_block(file_path: &Path, lines: &mut Vec<String>, attr: &str) -> Result<()> {
+    let close_idx = lines
+        .iter()
+        .rposition(|l| l.trim_start().starts_with('}'))
+        .ok_or_else(|| DeclairError::NoInsertionPoint {
+            file: file_path.to_path_buf(),
+        })?;
+    let outer: String = lines[close_idx]
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+    let indent = format!("{}  ", outer);
+    let mut block = Vec::new();
+    if close_idx > 0 && !lines[close_idx - 1].trim().is_empty() {
+        block.push(String::new());
+    }
+    block.push(format!("{}{} = with pkgs; [", indent, attr));
+    block.push(format!("{}];", indent));
+    lines.splice(close_idx..close_idx, block);
+    Ok(())
+}
+
+/// Add `import` to the file's `imports = [ ... ];` list, creating the list before the
+/// closing brace if the file has none.
+pub fn add_import(file_path: &Path, import: &str) -> Result<()> {
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+    edit.backup()?;
+
+    if let Some(start_idx) = lines
+        .iter()
+        .position(|l| l.trim_start().starts_with("imports") && l.contains('['))
+        && let Some(end_idx_rel) = lines[start_idx..].iter().position(|l| l.contains(']'))
+    {
+        let end_idx = start_idx + end_idx_rel;
+        if lines[start_idx..=end_idx]
+            .iter()
+            .any(|l| l.split_whitespace().any(|tok| tok == import))
+        {
+            return Err(DeclairError::ImportExists {
+                import: import.to_string(),
+                file: file_path.to_path_buf(),
+            });
+        }
+        if start_idx == end_idx {
+            let line = &lines[start_idx];
+            let rbr = line.rfind(']').unwrap_or(line.len());
+            lines[start_idx] = format!("{} {} {}", line[..rbr].trim_end(), import, &line[rbr..]);
+        } else {
+            let indent_of =
+                |l: &str| -> String { l.chars().take_while(|c| c.is_whitespace()).collect() };
+            let indent = if end_idx - start_idx > 1 {
+                indent_of(&lines[end_idx - 1])
+            } else {
+                format!("{}  ", indent_of(&lines[end_idx]))
+            };
+            lines.insert(end_idx, format!("{}{}", indent, import));
+        }
+    } else {
+        let close_idx = lines
+            .iter()
+            .rposition(|l| l.trim_start().starts_with('}'))
+            .ok_or_else(|| DeclairError::NoInsertionPoint {
+                file: file_path.to_path_buf(),
+            })?;
+        let outer: String = lines[close_idx]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .collect();
+        lines.insert(close_idx, format!("{}  imports = [ {} ];", outer, import));
+    }
+
+    edit.commit(&lines.join("\n"))
+}
+
+/// Add the input `name` fetched from `url` to a `flake.nix`, following the flake's own
+/// `nixpkgs` when it has one: inside its `inputs = { ... };` block, or after the last
+/// `inputs.<x>...` line.
+pub fn add_flake_input(file_path: &Path, name: &str, url: &str) -> Result<()> {
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+    edit.backup()?;
+    let follows = edit.contents.contains("nixpkgs.url");
+    let indent_of = |l: &str| -> String { l.chars().take_while(|c| c.is_whitespace()).collect() };
+
+    let (idx, indent, prefix) = if let Some(open_idx) = lines.iter().position(|l| {
+        let t = l.trim();
+        t.starts_with("inputs") && t.trim_start_matches("inputs").trim_start().starts_with('=')
+    }) {
+        let indent = match lines.get(open_idx + 1) {
+            Some(next) if !next.trim_start().starts_with('}') => indent_of(next),
+            _ => format!("{}  ", indent_of(&lines[open_idx])),
+        };
+        (open_idx + 1, indent, String::new())
+    } else if let Some(last_idx) = lines
+        .iter()
+        .rposition(|l| l.trim_start().starts_with("inputs."))
+    {
+        (
+            last_idx + 1,
+            indent_of(&lines[last_idx]),
+            "inputs.".to_string(),
+        )
+    } else {
+        return Err(DeclairError::NoInsertionPoint {
+            file: file_path.to_path_buf(),
+        });
+    };
+    let mut new_lines = vec![format!(
+        "{}{}{}.url = {};",
+        indent,
+        prefix,
+        name,
+        nix_string(url)
+    )];
+    if follows {
+        new_lines.push(format!(
+            "{}{}{}.inputs.nixpkgs.follows = \"nixpkgs\";",
+            indent, prefix, name
+        ));
+    }
+    lines.splice(idx..idx, new_lines);
+    edit.commit(&lines.join("\n"))
+}
+
+/// Remove the input `name` from a `flake.nix`: its lines in the `inputs = { ... };` block
+/// (including a multi-line `name = { ... };`) and any `inputs.name...` line.
+/// Returns whether anything was removed.
+pub fn remove_flake_input(file_path: &Path, name: &str) -> Result<bool> {
+    let edit = Edit::begin(file_path)?;
+    let lines = edit.lines();
+    let is_input_block =
+        |t: &str| t.starts_with("inputs") && t["inputs".len()..].trim_start().starts_with('=');
+    let starts_entry = |t: &str, prefix: &str| {
+        t.strip_prefix(prefix)
+            .and_then(|rest| rest.strip_prefix(name))
+            .is_some_and(|rest| rest.starts_with('.') || rest.trim_start().starts_with('='))
+    };
+
+    let mut kept = Vec::with_capacity(lines.len());
+    let mut in_inputs = false;
+    let mut inputs_depth = 0i32;
+    // Depth of braces still open in an entry being removed.
+    let mut removing = 0i32;
+    let mut removed = false;
+    for line in &lines {
+        let t = line.trim();
+        let depth = line.matches('{').count() as i32 - line.matches('}').count() as i32;
+        if removing > 0 {
+            removing += depth;
+            continue;
+        }
+        if (in_inputs && starts_entry(t, "")) || starts_entry(t, "inputs.") {
+            removed = true;
+            removing = depth.max(0);
+            continue;
+        }
+        if in_inputs {
+            inputs_depth += depth;
+            in_inputs = inputs_depth > 0;
+        } else if is_input_block(t) {
+            in_inputs = depth > 0;
+            inputs_depth = depth;
+        }
+        kept.push(line.clone());
+    }
+    if removed {
+        edit.backup()?;
+        edit.commit(&kept.join("\n"))?;
+    }
+    Ok(removed)
+}
+
+/// Adds `programs.<program>.enable = true;` into the given Nix configuration file.
+pub fn add_program_to_nix(file_path: &Path, pattern: &str) -> Result<()> {
+    // Read the whole file into a string.
+    let edit = Edit::begin(file_path)?;
+    let mut contents = edit.contents.clone();
+
+    // Write a backup copy (overwrite if already exists).
+    edit.backup()?;
+
+    if contents.contains(pattern) {
+        return Err(DeclairError::ProgramExists {
+            pattern: pattern.to_string(),
+            file: file_path.to_path_buf(),
+        });
+    }
+
+    // Try to locate the insertion point. We will insert before the last '}' in the file.
+    let insert_pos = contents
+        .rfind('}')
+        .ok_or_else(|| DeclairError::NoInsertionPoint {
+            file: file_path.to_path_buf(),
+        })?;
+
+    // Determine the indentation of the line containing the chosen '}'.
+    // Find the start of that line (last newline before insert_pos).
+    let line_start = contents[..insert_pos]
+        .rfind('\n')
+        .map(|i| i + 1)
+        .unwrap_or(0);
+
+    // Collect leading whitespace from the line to preserve indentation style.
+    let mut indent = String::new();
+    for ch in contents[line_start..insert_pos].chars() {
+        if ch.is_whitespace() {
+            indent.push(ch);
+        } else {
+            break;
+        }
+    }
+
+    // If indentation couldn't be inferred, fall back to two spaces.
+    if indent.is_empty() {
+        indent = "  ".to_string();
+    }
+
+    // Build the insertion line. Use the inferred indent level.
+    // The inserted line will be placed directly before the final '}'.
+    let insertion = format!("{}{} = true;\n", indent, pattern);
+
+    // Insert the text at the computed position.
+    contents.insert_str(insert_pos, &insertion);
+
+    // Write the modified contents back to the file.
+    edit.commit(&contents)
+}
+
+/// Where an edit of `file` keeps the previous contents: under `backups/` in declair's state
+/// dir, at the file's absolute path plus `.bak`
+/// (`~/.local/state/declair/backups/etc/nixos/configuration.nix.bak`), so backups don't
+/// show up in the configuration's repository. Files in the temp dir, i.e. scratch copies,
+/// keep `configuration.declair.bak` beside them, which goes away with the copy.
+pub fn backup_path(file: &Path) -> PathBuf {
+    let absolute = fs::canonicalize(file)
+        .or_else(|_| std::path::absolute(file))
+        .unwrap_or_else(|_| file.to_path_buf());
+    let temp = env::temp_dir();
+    let in_temp = absolute.starts_with(&temp)
+        || fs::canonicalize(&temp).is_ok_and(|temp| absolute.starts_with(temp));
+    match get_backups_dir() {
+        Some(backups) if !in_temp => {
+            let relative: PathBuf = absolute
+                .components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .collect();
+            let mut backup = backups.join(relative).into_os_string();
+            backup.push(".bak");
+            PathBuf::from(backup)
+        }
+        _ => file.with_extension("declair.bak"),
+    }
+}
+
+/// Create the directory the backup at `backup` (see [`backup_path`]) goes in.
+pub(crate) fn create_backup_dir(backup: &Path) -> Result<()> {
+    let Some(dir) = backup.parent() else {
+        return Ok(());
+    };
+    fs::create_dir_all(dir).map_err(|e| DeclairError::Backup {
+        path: backup.to_path_buf(),
+        source: e,
+    })
+}
+
+/// Put the contents of `file`'s backup back, undoing declair's last edit of it.
+pub fn restore_backup(file: &Path) -> Result<()> {
+    let backup = backup_path(file);
+    let contents = fs::read(&backup).map_err(|e| DeclairError::Read {
+        path: backup,
+        source: e,
+    })?;
+    write_atomic(file, &contents).map_err(|e| DeclairError::Write {
+        path: file.to_path_buf(),
+        source: e,
+    })
+}
+
+/// List packages found in `with pkgs; [ ... ]` block of given file.
+pub fn list_packages(file_path: &Path, scope: impl Into<Scope>) -> Result<Vec<String>> {
+    Ok(list_entries(file_path, scope)?
+        .into_iter()
+        .map(|(pkg, _)| pkg)
+        .collect())
+}
+
+/// List only the packages declair inserted, i.e. entries carrying [`MANAGED_MARKER`].
+/// Entries of single-line lists can't carry the marker and are never listed.
+pub fn list_managed_packages(file_path: &Path, scope: impl Into<Scope>) -> Result<Vec<String>> {
+    Ok(list_entries(file_path, scope)?
+        .into_iter()
+        .filter(|(_, managed)| *managed)
+        .map(|(pkg, _)| pkg)
+        .collect())
+}
+
+/// Every package in a `with pkgs; [ ... ]` block of the `.nix` files under `repo`, so
+/// search results can be told apart by whether the configuration already has them.
+/// Files that can't be read are skipped.
+pub fn installed_packages(repo: &Path) -> HashSet<String> {
+    nix_files(repo)
+        .iter()
+        .filter_map(|file| package_lists(file).ok())
+        .flatten()
+        .flat_map(|list| list.entries.into_iter().map(|(pkg, _)| pkg))
+        .collect()
+}
+
+/// Packages of the block, each with whether it carries the managed marker.
+fn list_entries(file_path: &Path, scope: impl Into<Scope>) -> Result<Vec<(String, bool)>> {
+    Ok(list_segments(file_path, scope)?
+        .into_iter()
+        .flat_map(|segment| segment.entries)
+        .collect())
+}
+
+/// One list of a `with pkgs; [ ... ]` block and the packages in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListSegment {
+    /// What the list is appended with, e.g. `lib.optionals stdenv.isLinux`; `None` for the
+    /// `with pkgs; [` list itself.
+    pub condition: Option<String>,
+    /// Entries, each with whether it carries [`MANAGED_MARKER`].
+    pub entries: Vec<(String, bool)>,
+}
+
+/// The lists of the package block: the `with pkgs; [` list first, then the conditional
+/// segments appended to it with `++`, in file order.
+pub fn list_segments(file_path: &Path, scope: impl Into<Scope>) -> Result<Vec<ListSegment>> {
+    let lines = read_lines(file_path)?;
+    let segments =
+        find_package_block(&lines, &scope.into()).ok_or_else(|| DeclairError::BlockNotFound {
+            file: file_path.to_path_buf(),
+        })?;
+    Ok(segments
+        .into_iter()
+        .map(|segment| ListSegment {
+            entries: segment_entries(&lines, &segment)
+                .into_iter()
+                .map(|(_, pkg, managed)| (pkg, managed))
+                .collect(),
+            condition: segment.condition,
+        })
+        .collect())
+}
+
+/// Entries of one list with their zero-based line, each with whether it carries the
+/// managed marker.
+fn segment_entries(lines: &[String], segment: &Segment) -> Vec<(usize, String, bool)> {
+    let mut packages: Vec<(usize, String, bool)> = Vec::new();
+    let (open, close) = (segment.open, segment.close);
+
+    if open.0 == close.0 {
+        // single-line case
+        let inside = &lines[open.0][open.1 + 1..close.1];
+        for token in split_entries(inside) {
+            packages.push((open.0, token.to_string(), false));
+        }
+        return packages;
+    }
+    // multiline case: entries after the opening bracket, on the lines in between (the
+    // first entry of each), and before the closing bracket
+    let managed = |idx: usize| lines[idx].trim_end().ends_with(MANAGED_MARKER);
+    for token in entries_of(&lines[open.0][open.1 + 1..]) {
+        packages.push((open.0, token.to_string(), managed(open.0)));
+    }
+    for (idx, l) in lines.iter().enumerate().take(close.0).skip(open.0 + 1) {
+        let trimmed = l.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        // take the first entry on the line as package name
+        if let Some(tok) = split_entries(trimmed).first() {
+            // skip lines that are just comments
+            if tok.starts_with('#') || tok.starts_with("//") {
+                continue;
+            }
+            packages.push((idx, tok.to_string(), trimmed.ends_with(MANAGED_MARKER)));
+        }
+    }
+    for token in entries_of(&lines[close.0][..close.1]) {
+        packages.push((close.0, token.to_string(), false));
+    }
+    packages
+}
+
+/// One `with pkgs; [ ... ]` block of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageList {
+    /// Attribute the block is assigned to, e.g. `environment.systemPackages`, `home.packages`
+    /// or a dev shell's `buildInputs`; empty when the block isn't assigned directly.
+    pub attr: String,
+    /// One-based line of the block's opening bracket.
+    pub line: usize,
+    /// Entries of all its lists, conditional segments included, each with whether it
+    /// carries [`MANAGED_MARKER`].
+    pub entries: Vec<(String, bool)>,
+}
+
+/// Every `with pkgs; [ ... ]` block and `inherit (pkgs) ...;` of `file_path`, in file
+/// order.
+pub fn package_lists(file_path: &Path) -> Result<Vec<PackageList>> {
+    let lines = read_lines(file_path)?;
+    let mut lists = Vec::new();
+    let mut idx = 0;
+    let is_inherit = |l: &String| {
+        l.split('#')
+            .next()
+            .is_some_and(|code| code.contains(INHERIT_PKGS))
+    };
+    while let Some(start_rel) = lines[idx..]
+        .iter()
+        .position(|l| l.contains("with pkgs; [") || is_inherit(l))
+    {
+        let start_idx = idx + start_rel;
+        let segments = if lines[start_idx].contains("with pkgs; [") {
+            block_segments(&lines, start_idx)
+        } else {
+            inherit_segment(&lines, start_idx).map(|segment| vec![segment])
+        };
+        let Some(segments) = segments else {
+            break;
+        };
+        // the names of an `inherit (pkgs)` belong to the set it is in
+        let assignment = if segments[0].inherit {
+            lines[..=start_idx]
+                .iter()
+                .rev()
+                .find(|l| l.contains("attrValues"))
+        } else {
+            Some(&lines[start_idx])
+        };
+        let attr = assignment
+            .and_then(|l| l.split_once('='))
+            .map(|(lhs, _)| lhs.trim().to_string())
+            .unwrap_or_default();
+        lists.push(PackageList {
+            attr,
+            line: start_idx + 1,
+            entries: segments
+                .iter()
+                .flat_map(|segment| segment_entries(&lines, segment))
+                .map(|(_, pkg, managed)| (pkg, managed))
+                .collect(),
+        });
+        idx = segments[segments.len() - 1].close.0 + 1;
+    }
+    Ok(lists)
+}
+
+/// Remove a package from NixOS config (with backup). Does not perform rebuild itself.
+/// The package is removed from whichever list of the block holds it.
+pub fn remove_package_from_nix(file_path: &Path, pkg: &str, scope: impl Into<Scope>) -> Result<()> {
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+
+    // make backup (overwrite if already exists)
+    edit.backup()?;
+
+    // find the lists of the "with pkgs; [" block
+    let Some(segments) = find_package_block(&lines, &scope.into()) else {
+        return Err(DeclairError::BlockNotFound {
+            file: file_path.to_path_buf(),
+        });
+    };
+    let (segment, idx) = segments
+        .iter()
+        .find_map(|segment| {
+            segment_entries(&lines, segment)
+                .into_iter()
+                .find(|(_, entry, _)| entry == pkg)
+                .map(|(idx, _, _)| (segment, idx))
+        })
+        .ok_or_else(|| DeclairError::PackageNotFound {
+            pkg: pkg.to_string(),
+            file: file_path.to_path_buf(),
+        })?;
+    let (open, close) = (segment.open, segment.close);
+    let line = &lines[idx];
+    if idx != open.0 && idx != close.0 {
+        // multiline case: the package starts a line of its own
+        lines.remove(idx);
+    } else {
+        // the entries between the brackets on this line
+        let from = if idx == open.0 { open.1 + 1 } else { 0 };
+        let to = if idx == close.0 {
+            close.1
+        } else {
+            line.find('#').unwrap_or(line.len())
+        };
+        let parts: Vec<&str> = split_entries(&line[from..to])
+            .into_iter()
+            .filter(|&p| p != pkg)
+            .collect();
+        let (head, tail) = (&line[..from], &line[to..]);
+        let names: String = parts.iter().map(|p| format!(" {}", p)).collect();
+        lines[idx] = if open.0 == close.0 && !segment.inherit {
+            format!("{} {} {}", &line[..open.1 + 1], parts.join(" "), tail)
+        } else if idx == open.0 {
+            // `inherit (pkgs) a b;`, or the rest of a line opening a list
+            let gap = if tail.starts_with('#') { " " } else { "" };
+            format!("{}{}{}{}", head.trim_end(), names, gap, tail)
+        } else {
+            // entries before the bracket closing a multiline list, e.g. `  a b ];`
+            let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+            let gap = if parts.is_empty() || segment.inherit {
+                ""
+            } else {
+                " "
+            };
+            format!("{}{}{}{}", indent, parts.join(" "), gap, tail)
+        };
+    }
+
+    edit.commit(&lines.join("\n"))
+}
+
+/// Replaces `old` with `new` in the package block, in place: the entry keeps its position,
+/// indentation and trailing comment.
+pub fn replace_package_in_nix(
+    file_path: &Path,
+    old: &str,
+    new: &str,
+    scope: impl Into<Scope>,
+) -> Result<()> {
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+
+    // make backup (overwrite if already exists)
+    edit.backup()?;
+
+    let Some(segments) = find_package_block(&lines, &scope.into()) else {
+        return Err(DeclairError::BlockNotFound {
+            file: file_path.to_path_buf(),
+        });
+    };
+    if segments[0].inherit && !inheritable(new) {
+        return Err(DeclairError::NotInheritable {
+            pkg: new.to_string(),
+            file: file_path.to_path_buf(),
+        });
+    }
+    let start_idx = segments[0].open.0;
+    let end_idx = segments[segments.len() - 1].close.0;
+    let mut old_idx = None;
+    for (i, line) in lines[start_idx..=end_idx].iter().enumerate() {
+        if entries_of(line).any(|entry| entry == new) {
+            return Err(DeclairError::PackageExists {
+                pkg: new.to_string(),
+                file: file_path.to_path_buf(),
+                line: start_idx + i + 1,
+            });
+        }
+        if old_idx.is_none() && entries_of(line).any(|entry| entry == old) {
+            old_idx = Some(start_idx + i);
+        }
+    }
+    let idx = old_idx.ok_or_else(|| DeclairError::PackageNotFound {
+        pkg: old.to_string(),
+        file: file_path.to_path_buf(),
+    })?;
+
+    // only the code before a comment is rewritten, token by token
+    let line = &lines[idx];
+    let code_end = line.find('#').unwrap_or(line.len());
+    let (code, comment) = line.split_at(code_end);
+    let mut replaced = String::with_capacity(line.len());
+    let mut token = String::new();
+    let mut done = false;
+    for c in code.chars().chain(std::iter::once(' ')) {
+        if c.is_whitespace() || matches!(c, '[' | ']' | ';') {
+            if !done && token == old {
+                token = new.to_string();
+                done = true;
+            }
+            replaced.push_str(&token);
+            token.clear();
+            replaced.push(c);
+        } else {
+            token.push(c);
+        }
+    }
+    replaced.pop();
+    lines[idx] = format!("{}{}", replaced, comment);
+
+    edit.commit(&lines.join("\n"))
+}
+
+/// Removes a line like `programs.<program>.enable = true;` from the given Nix configuration file.
+pub fn remove_program_from_nix(file_path: &Path, pattern: &str) -> Result<()> {
+    // Read the file contents into a vector of lines.
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+
+    // Write a backup copy (overwrite if it already exists).
+    edit.backup()?;
+
+    // Try to find a line that matches the pattern.
+    let mut found_index: Option<usize> = None;
+    for (idx, raw_line) in lines.iter().enumerate() {
+        let trimmed = raw_line.trim();
+        // Skip empty lines and obvious comments.
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        // Check if the trimmed line starts with the token and contains '=' and ';'
+        if trimmed.starts_with(pattern) && trimmed.contains('=') {
+            // A simple sanity check: ensure it's likely an assignment like `... = true;`
+            // Accept variations of whitespace around '=' and an optional trailing semicolon.
+            // We'll treat this as a match and remove the entire line.
+            found_index = Some(idx);
+            break;
+        }
+    }
+
+    // If not found, return an error.
+    let remove_idx = match found_index {
+        Some(i) => i,
+        None => {
+            return Err(DeclairError::ProgramNotFound {
+                pattern: pattern.to_string(),
+                file: file_path.to_path_buf(),
+            });
+        }
+    };
+
+    // Remove the matching line.
+    lines.remove(remove_idx);
+
+    // Optionally, also remove an immediately following blank line to keep the file tidy.
+    if remove_idx < lines.len() && lines[remove_idx].trim().is_empty() {
+        lines.remove(remove_idx);
+    }
+
+    // Join lines back into a single string and write back to the file.
+    edit.commit(&lines.join("\n"))
+}
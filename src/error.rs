@@ -0,0 +1,273 @@
+use crate::t;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use thiserror::Error;
+
+/// All the ways a declair run can fail.
+#[derive(Debug, Error)]
+pub enum DeclairError {
+    #[error("{}", t!("error.block_not_found", file.display()))]
+    BlockNotFound { file: PathBuf },
+
+    #[error("{}", t!("error.malformed_block", file.display()))]
+    MalformedBlock { file: PathBuf },
+
+    #[error("{}", t!("error.not_inheritable", pkg, file.display()))]
+    NotInheritable { pkg: String, file: PathBuf },
+
+    #[error("{}", t!("error.package_exists", pkg, file.display(), line))]
+    PackageExists {
+        pkg: String,
+        file: PathBuf,
+        line: usize,
+    },
+
+    #[error("{}", t!("error.package_not_found", pkg, file.display()))]
+    PackageNotFound { pkg: String, file: PathBuf },
+
+    #[error("{}", t!("error.variable_not_found", name, file.display()))]
+    VariableNotFound { name: String, file: PathBuf },
+
+    #[error("{}", t!("error.option_not_found", option))]
+    OptionNotFound { option: String },
+
+    #[error("{}", t!("error.backup_not_found", id))]
+    BackupNotFound { id: String },
+
+    #[error("{}", t!("error.package_conflict", pkg, reason))]
+    PackageConflict { pkg: String, reason: String },
+
+    #[error("{}", t!("error.removed_package", pkg, message))]
+    RemovedPackage { pkg: String, message: String },
+
+    #[error("{}", t!("error.not_managed", pkg, file.display()))]
+    NotManaged { pkg: String, file: PathBuf },
+
+    #[error("{}", t!("error.pinned", pkg))]
+    Pinned { pkg: String },
+
+    #[error("{}", t!("error.program_exists", file.display(), pattern))]
+    ProgramExists { pattern: String, file: PathBuf },
+
+    #[error("{}", t!("error.program_not_found", pattern, file.display()))]
+    ProgramNotFound { pattern: String, file: PathBuf },
+
+    #[error("{}", t!("error.overlay_exists", name, file.display()))]
+    OverlayExists { name: String, file: PathBuf },
+
+    #[error("{}", t!("error.overlay_not_found", name, file.display()))]
+    OverlayNotFound { name: String, file: PathBuf },
+
+    #[error("{}", t!("error.import_exists", import, file.display()))]
+    ImportExists { import: String, file: PathBuf },
+
+    #[error("{}", t!("error.no_insertion_point", file.display()))]
+    NoInsertionPoint { file: PathBuf },
+
+    #[error("{}", t!("error.concurrent_modification", file.display()))]
+    ConcurrentModification { file: PathBuf },
+
+    #[error("{}", t!("error.file_locked", file.display()))]
+    FileLocked { file: PathBuf },
+
+    #[error("{}", t!("error.privileged_write", file.display(), status))]
+    PrivilegedWriteFailed { file: PathBuf, status: ExitStatus },
+
+    #[error("{}", t!("error.invalid_path", path.display(), reason))]
+    InvalidPath { path: PathBuf, reason: String },
+
+    #[error("{}", t!("error.search_failed", .0))]
+    SearchFailed(String),
+
+    #[error("{}", t!("error.invalid_regex", pattern, reason))]
+    InvalidRegex { pattern: String, reason: String },
+
+    #[error("{}", t!("error.store_query_failed", .0))]
+    StoreQueryFailed(String),
+
+    #[error("{}", t!("error.format_failed", formatter, reason))]
+    FormatFailed { formatter: String, reason: String },
+
+    #[error("{}", t!("error.invalid_group", name, reason))]
+    InvalidGroup { name: String, reason: String },
+
+    #[error("{}", t!("error.fetch_failed", source_url, reason))]
+    FetchFailed { source_url: String, reason: String },
+
+    #[error("{}", t!("error.rpc", .0))]
+    Rpc(String),
+
+    #[error("{}", t!("error.watch", .0))]
+    Watch(String),
+
+    #[error("{}", t!("error.rebuild_failed", status))]
+    RebuildFailed {
+        status: ExitStatus,
+        /// It failed evaluating the configuration, before building anything.
+        evaluation: bool,
+    },
+
+    #[error("{}", t!("error.update_failed", status))]
+    UpdateFailed { status: ExitStatus },
+
+    #[error("{}", t!("error.systemd", .0))]
+    Systemd(String),
+
+    #[error("{}", t!("error.channel", .0))]
+    Channel(String),
+
+    #[error("{}", t!("error.audit", .0))]
+    Audit(String),
+
+    #[error("{}", t!("error.eval", .0))]
+    Eval(String),
+
+    #[error("{}", t!("error.vulnix_missing"))]
+    VulnixMissing,
+
+    #[error("{}", t!("error.vulnerable", count))]
+    Vulnerable { count: usize },
+
+    #[error("{}", t!("error.unsupported_platform", pkg, system))]
+    UnsupportedPlatform { pkg: String, system: String },
+
+    #[error("{}", t!("error.unsupported_host", operation))]
+    UnsupportedHost { operation: String },
+
+    #[error("{}", t!("error.policy_violation", pkg, reason))]
+    PolicyViolation { pkg: String, reason: String },
+
+    #[error("{}", t!("error.read_only"))]
+    ReadOnly,
+
+    #[error("{}", t!("error.license_violations", count))]
+    LicenseViolations { count: usize },
+
+    #[error("{}", t!("error.hook", .0))]
+    Hook(String),
+
+    #[error("{}", t!("error.git", .0))]
+    Git(String),
+
+    #[error("{}", t!("error.plugin", name, reason))]
+    Plugin { name: String, reason: String },
+
+    #[error("{0}")]
+    Config(String),
+
+    #[error("{0}")]
+    Selection(String),
+
+    #[error("{}", t!("error.read", path.display(), source))]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{}", t!("error.backup", path.display(), source))]
+    Backup {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{}", t!("error.write", path.display(), source))]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{}", t!("error.no_home_dir"))]
+    NoHomeDir,
+
+    #[error("{}", t!("error.io", .0))]
+    Io(#[from] io::Error),
+
+    #[error("{}", t!("error.prompt", .0))]
+    Prompt(#[from] dialoguer::Error),
+
+    #[error("{}", t!("error.toml_de", .0))]
+    TomlDe(#[from] toml::de::Error),
+
+    #[error("{}", t!("error.toml_ser", .0))]
+    TomlSer(#[from] toml::ser::Error),
+}
+
+impl DeclairError {
+    /// Whether the user pressed Ctrl-C, in a prompt or while an external command ran.
+    pub fn is_interrupted(&self) -> bool {
+        match self {
+            DeclairError::Io(e) | DeclairError::Prompt(dialoguer::Error::IO(e)) => {
+                e.kind() == io::ErrorKind::Interrupted
+            }
+            _ => false,
+        }
+    }
+
+    /// Process exit code for this error, so scripts can tell failure modes apart.
+    pub fn exit_code(&self) -> i32 {
+        if self.is_interrupted() {
+            return 130;
+        }
+        match self {
+            DeclairError::Config(_)
+            | DeclairError::InvalidGroup { .. }
+            | DeclairError::TomlDe(_)
+            | DeclairError::TomlSer(_)
+            | DeclairError::NoHomeDir => 2,
+            DeclairError::InvalidPath { .. } => 3,
+            DeclairError::BlockNotFound { .. }
+            | DeclairError::MalformedBlock { .. }
+            | DeclairError::NotInheritable { .. }
+            | DeclairError::NoInsertionPoint { .. } => 4,
+            DeclairError::PackageExists { .. }
+            | DeclairError::ProgramExists { .. }
+            | DeclairError::ImportExists { .. }
+            | DeclairError::OverlayExists { .. }
+            | DeclairError::PackageConflict { .. }
+            | DeclairError::PolicyViolation { .. }
+            | DeclairError::Pinned { .. }
+            | DeclairError::UnsupportedPlatform { .. }
+            | DeclairError::UnsupportedHost { .. } => 5,
+            DeclairError::PackageNotFound { .. }
+            | DeclairError::ProgramNotFound { .. }
+            | DeclairError::OverlayNotFound { .. }
+            | DeclairError::VariableNotFound { .. }
+            | DeclairError::OptionNotFound { .. }
+            | DeclairError::BackupNotFound { .. }
+            | DeclairError::RemovedPackage { .. }
+            | DeclairError::NotManaged { .. } => 6,
+            DeclairError::SearchFailed(_)
+            | DeclairError::Eval(_)
+            | DeclairError::InvalidRegex { .. }
+            | DeclairError::StoreQueryFailed(_)
+            | DeclairError::Selection(_) => 7,
+            DeclairError::RebuildFailed { .. } | DeclairError::UpdateFailed { .. } => 8,
+            DeclairError::FormatFailed { .. } => 9,
+            DeclairError::ConcurrentModification { .. } | DeclairError::FileLocked { .. } => 10,
+            DeclairError::Vulnerable { .. } | DeclairError::LicenseViolations { .. } => 11,
+            DeclairError::ReadOnly => 12,
+            DeclairError::Read { .. }
+            | DeclairError::Backup { .. }
+            | DeclairError::Write { .. }
+            | DeclairError::PrivilegedWriteFailed { .. }
+            | DeclairError::Watch(_)
+            | DeclairError::Rpc(_)
+            | DeclairError::Systemd(_)
+            | DeclairError::Channel(_)
+            | DeclairError::Audit(_)
+            | DeclairError::VulnixMissing
+            | DeclairError::Hook(_)
+            | DeclairError::Git(_)
+            | DeclairError::Plugin { .. }
+            | DeclairError::FetchFailed { .. }
+            | DeclairError::Io(_)
+            | DeclairError::Prompt(_) => 1,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, DeclairError>;
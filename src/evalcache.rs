@@ -0,0 +1,165 @@
+//! Results of `nix eval` kept in the cache dir, keyed by the nixpkgs revision they were
+//! evaluated in, the query and the attribute. A locked revision never changes, so entries
+//! don't expire; `info` and license lookups of a package seen before skip the evaluator.
+
+use crate::atomic::write_atomic;
+use crate::audit::Nixpkgs;
+use crate::error::Result;
+use crate::paths::get_cache_dir;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Resolving a flake reference may have to fetch it.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Length of a git revision.
+const REV_LEN: usize = 40;
+
+/// Cached evaluations in one nixpkgs revision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalCache {
+    dir: PathBuf,
+}
+
+impl EvalCache {
+    /// The entries for `revision` below `root`.
+    pub fn new(root: &Path, revision: &str) -> Self {
+        EvalCache {
+            dir: root.join(file_name(revision)),
+        }
+    }
+
+    fn path(&self, query: &str, attr: &str) -> PathBuf {
+        self.dir
+            .join(file_name(query))
+            .join(format!("{}.json", file_name(attr)))
+    }
+
+    /// The cached result of `query` for `attr`.
+    pub fn get(&self, query: &str, attr: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(query, attr)).ok()
+    }
+
+    /// Remember `value` as the result of `query` for `attr`. Failing to write only costs
+    /// a later evaluation, so errors are ignored.
+    pub fn put(&self, query: &str, attr: &str, value: &[u8]) {
+        let path = self.path(query, attr);
+        if let Some(dir) = path.parent()
+            && fs::create_dir_all(dir).is_ok()
+        {
+            let _ = write_atomic(&path, value);
+        }
+    }
+
+    /// The cached result of `query` for `attr`, or `eval`'s, which is cached on success.
+    pub fn eval(
+        &self,
+        query: &str,
+        attr: &str,
+        eval: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        if let Some(value) = self.get(query, attr) {
+            return Ok(value);
+        }
+        let value = eval()?;
+        self.put(query, attr, &value);
+        Ok(value)
+    }
+}
+
+/// Where evaluations are cached.
+pub fn cache_root() -> Option<PathBuf> {
+    Some(get_cache_dir()?.join("eval"))
+}
+
+/// `nixpkgs` locked to a revision, with the cache for that revision. Without a known
+/// revision (a mutable path, a failed lookup) it is returned as is, without a cache.
+pub fn open(runner: &dyn CommandRunner, nixpkgs: &Nixpkgs) -> (Nixpkgs, Option<EvalCache>) {
+    match (cache_root(), lock(runner, nixpkgs)) {
+        (Some(root), Some((revision, locked))) => (locked, Some(EvalCache::new(&root, &revision))),
+        _ => (nixpkgs.clone(), None),
+    }
+}
+
+/// The revision `nixpkgs` evaluates to and a reference that keeps evaluating to it: a
+/// reference that names a revision already, the locked URL of one that doesn't (from
+/// `nix flake metadata`), or the store path of the `<nixpkgs>` channel.
+pub fn lock(runner: &dyn CommandRunner, nixpkgs: &Nixpkgs) -> Option<(String, Nixpkgs)> {
+    match nixpkgs {
+        Nixpkgs::Flake(reference) => match pinned_revision(reference) {
+            Some(rev) => Some((rev.to_string(), nixpkgs.clone())),
+            None => flake_metadata(runner, reference),
+        },
+        Nixpkgs::NixPath => channel_revision(runner).map(|rev| (rev, Nixpkgs::NixPath)),
+    }
+}
+
+/// The git revision a flake reference names, e.g. in `github:NixOS/nixpkgs/<rev>` or
+/// `git+https://...?rev=<rev>`.
+pub fn pinned_revision(reference: &str) -> Option<&str> {
+    reference
+        .split(['/', '?', '&', '='])
+        .find(|part| part.len() == REV_LEN && part.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// `nix flake metadata` of `reference`: its revision (or content hash) and locked URL.
+fn flake_metadata(runner: &dyn CommandRunner, reference: &str) -> Option<(String, Nixpkgs)> {
+    let cmd = CommandSpec::new(
+        "nix",
+        [
+            "flake",
+            "metadata",
+            "--json",
+            "--extra-experimental-features",
+            "nix-command flakes",
+            reference,
+        ],
+    )
+    .timeout(LOCK_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query();
+    let output = runner.output(&cmd).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let metadata: Value = serde_json::from_slice(&output.stdout).ok()?;
+    let locked = &metadata["locked"];
+    let revision = locked["rev"].as_str().or(locked["narHash"].as_str())?;
+    let url = metadata["lockedUrl"]
+        .as_str()
+        .or(metadata["url"].as_str())?;
+    Some((revision.to_string(), Nixpkgs::Flake(url.to_string())))
+}
+
+/// The store path `<nixpkgs>` resolves to, e.g. `<hash>-nixos-24.11`; `None` for a
+/// checkout outside the store, which may change.
+fn channel_revision(runner: &dyn CommandRunner) -> Option<String> {
+    let cmd = CommandSpec::new("nix-instantiate", ["--find-file", "nixpkgs"])
+        .timeout(LOCK_TIMEOUT)
+        .class(CommandClass::Eval)
+        .query();
+    let output = runner.output(&cmd).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let path = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    let path = fs::canonicalize(&path).unwrap_or(path);
+    let name = path.strip_prefix("/nix/store").ok()?.components().next()?;
+    Some(name.as_os_str().to_string_lossy().into_owned())
+}
+
+/// `text` usable as a single file name.
+fn file_name(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "._+-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,271 @@
+//! Machine-readable event stream (`--log-format json-lines`): one JSON object per line for
+//! each phase of a run (search, edit, rebuild), on stderr or the file descriptor given
+//! with `--log-fd`, so wrappers and GUIs can show their own progress around declair.
+//! Rebuild progress comes from Nix's `--log-format internal-json` log, which
+//! [`crate::progress`] also draws its display from.
+
+use crate::error::{DeclairError, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+/// Arguments making Nix log in the format [`NixLog`] reads.
+pub const NIX_LOG_ARGS: [&str; 2] = ["--log-format", "internal-json"];
+
+/// Nix activity type of copying several paths, e.g. from a binary cache.
+const ACT_COPY_PATHS: u64 = 103;
+/// Nix activity type of a build of several derivations, whose progress is reported.
+const ACT_BUILDS: u64 = 104;
+/// Nix activity type of the build of one derivation.
+const ACT_BUILD: u64 = 105;
+/// Nix result type of a line of a build's log.
+const RES_BUILD_LOG_LINE: u64 = 101;
+/// Nix result type of a progress update: done, expected, running and failed.
+const RES_PROGRESS: u64 = 105;
+/// Most verbose level of the messages Nix prints by default.
+const LVL_INFO: u64 = 3;
+
+/// What declair reports while it runs (`--log-format`).
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Only the usual messages.
+    #[default]
+    Text,
+    /// Also one JSON event per line.
+    JsonLines,
+}
+
+/// One event of the stream, e.g. `{"event":"searchStarted","query":"firefox"}`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(
+    tag = "event",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Event {
+    SearchStarted {
+        query: String,
+    },
+    SearchFinished {
+        results: usize,
+    },
+    /// A config file was edited; `action` is the one hooks get (`add`, `remove`, ...).
+    EditApplied {
+        action: String,
+        packages: Vec<String>,
+        file: PathBuf,
+    },
+    RebuildStarted {
+        command: String,
+    },
+    /// Derivations built so far, of those to build; only for NixOS rebuilds.
+    RebuildProgress {
+        done: u64,
+        expected: u64,
+        running: u64,
+        failed: u64,
+    },
+    RebuildFinished {
+        success: bool,
+        exit_code: Option<i32>,
+    },
+}
+
+impl Event {
+    /// The event as one line of JSON, without the newline.
+    pub fn to_line(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+static SINK: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Send events to `sink` for the rest of the process. Only the first call has an effect;
+/// without one, events are dropped.
+pub fn set_event_sink(sink: Box<dyn Write + Send>) {
+    let _ = SINK.set(Mutex::new(sink));
+}
+
+/// Whether events are written anywhere.
+pub fn events_enabled() -> bool {
+    SINK.get().is_some()
+}
+
+/// Write `event` to the sink, if there is one. A sink that went away is ignored.
+pub fn emit(event: &Event) {
+    if let Some(sink) = SINK.get()
+        && let Ok(mut sink) = sink.lock()
+    {
+        let _ = writeln!(sink, "{}", event.to_line());
+        let _ = sink.flush();
+    }
+}
+
+/// Where `--log-fd` sends events: file descriptor `fd`, stderr without one.
+pub fn event_sink(fd: Option<i32>) -> Result<Box<dyn Write + Send>> {
+    match fd {
+        Some(fd) => fd_sink(fd),
+        None => Ok(Box::new(io::stderr())),
+    }
+}
+
+#[cfg(unix)]
+fn fd_sink(fd: i32) -> Result<Box<dyn Write + Send>> {
+    use crate::t;
+    use std::fs::File;
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: fcntl(2) only queries the descriptor's flags.
+    if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(DeclairError::Config(t!("events.bad_fd", fd)));
+    }
+    // SAFETY: the descriptor is open, and the caller hands it to declair for the rest of
+    // the process.
+    Ok(Box::new(unsafe { File::from_raw_fd(fd) }))
+}
+
+/// File descriptors handed down by a wrapper are a Unix notion.
+#[cfg(not(unix))]
+fn fd_sink(_fd: i32) -> Result<Box<dyn Write + Send>> {
+    Err(DeclairError::UnsupportedHost {
+        operation: "--log-fd".to_string(),
+    })
+}
+
+/// Whether `args` ask Nix for its `internal-json` log.
+pub fn wants_nix_log(args: &[String]) -> bool {
+    args.windows(2)
+        .any(|pair| pair[0] == NIX_LOG_ARGS[0] && pair[1] == NIX_LOG_ARGS[1])
+}
+
+/// A line of Nix's `internal-json` log, read by [`NixLog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NixLine {
+    /// A message Nix would have printed, like an error or warning.
+    Text(String),
+    /// What Nix is doing now, e.g. `building '/nix/store/...drv'`, which Nix would have
+    /// printed too.
+    Activity(String),
+    /// A line of a derivation's build log as `nix build -L` shows it: `name> line`.
+    BuildLog(String),
+    Progress(Event),
+    /// Nothing to show.
+    Quiet,
+}
+
+/// Reads the `internal-json` log of a rebuild (`@nix {...}` lines), turning messages back
+/// into text and build progress into [`Event::RebuildProgress`], and keeping track of the
+/// downloads and the derivations being built.
+#[derive(Debug, Default)]
+pub struct NixLog {
+    /// Activities whose progress counts derivations.
+    builds: HashSet<u64>,
+    /// Activities whose progress counts copied paths.
+    copies: HashSet<u64>,
+    /// Paths copied so far, of those to copy.
+    downloads: Option<(u64, u64)>,
+    /// Names of the derivations being built, by activity.
+    building: BTreeMap<u64, String>,
+}
+
+impl NixLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Paths downloaded (or otherwise copied) so far and expected, once Nix reported any.
+    pub fn downloads(&self) -> Option<(u64, u64)> {
+        self.downloads
+    }
+
+    /// Names of the derivations being built now, oldest first.
+    pub fn building(&self) -> Vec<&str> {
+        self.building.values().map(String::as_str).collect()
+    }
+
+    /// Read one line; lines not from the JSON log are text.
+    pub fn line(&mut self, line: &str) -> NixLine {
+        let Some(json) = line.strip_prefix("@nix ") else {
+            return NixLine::Text(line.to_string());
+        };
+        let Ok(entry) = serde_json::from_str::<Value>(json) else {
+            return NixLine::Text(line.to_string());
+        };
+        let number = |key: &str| entry.get(key).and_then(Value::as_u64);
+        let text = |key: &str| entry.get(key).and_then(Value::as_str).unwrap_or_default();
+        let fields = entry
+            .get("fields")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let count = |idx: usize| fields.get(idx).and_then(Value::as_u64).unwrap_or(0);
+        let id = number("id").unwrap_or(0);
+        match text("action") {
+            "msg" if number("level").unwrap_or(0) <= LVL_INFO => {
+                NixLine::Text(text("msg").to_string())
+            }
+            "start" => {
+                match number("type") {
+                    Some(ACT_BUILDS) => {
+                        self.builds.insert(id);
+                    }
+                    Some(ACT_COPY_PATHS) => {
+                        self.copies.insert(id);
+                    }
+                    Some(ACT_BUILD) => {
+                        let drv = fields.first().and_then(Value::as_str).unwrap_or_default();
+                        self.building.insert(id, derivation_name(drv));
+                    }
+                    _ => {}
+                }
+                if number("level").unwrap_or(0) <= LVL_INFO && !text("text").is_empty() {
+                    NixLine::Activity(text("text").to_string())
+                } else {
+                    NixLine::Quiet
+                }
+            }
+            "result" => match number("type") {
+                Some(RES_PROGRESS) if self.builds.contains(&id) => {
+                    NixLine::Progress(Event::RebuildProgress {
+                        done: count(0),
+                        expected: count(1),
+                        running: count(2),
+                        failed: count(3),
+                    })
+                }
+                Some(RES_PROGRESS) if self.copies.contains(&id) => {
+                    self.downloads = Some((count(0), count(1)));
+                    NixLine::Quiet
+                }
+                Some(RES_BUILD_LOG_LINE) => {
+                    let line = fields.first().and_then(Value::as_str).unwrap_or_default();
+                    let name = self.building.get(&id).map_or("", String::as_str);
+                    NixLine::BuildLog(format!("{}> {}", name, line))
+                }
+                _ => NixLine::Quiet,
+            },
+            "stop" => {
+                self.builds.remove(&id);
+                self.copies.remove(&id);
+                self.building.remove(&id);
+                NixLine::Quiet
+            }
+            _ => NixLine::Quiet,
+        }
+    }
+}
+
+/// The name of the derivation at `path`: `hello-2.12` for
+/// `/nix/store/<hash>-hello-2.12.drv`.
+pub fn derivation_name(path: &str) -> String {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    let file = file.strip_suffix(".drv").unwrap_or(file);
+    match file.split_once('-') {
+        Some((hash, name)) if hash.len() == 32 => name.to_string(),
+        _ => file.to_string(),
+    }
+}
@@ -0,0 +1,595 @@
+//! Examples of declair's command lines: the "Examples" at the end of each subcommand's
+//! `--help`, the EXAMPLES section of the man pages `declair man` writes, and the
+//! workflows `declair examples` prints.
+
+use crate::t;
+use clap::Command;
+use roff::{Roff, bold, roman};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// One command line and what it does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Example {
+    pub command: &'static str,
+    pub about: &'static str,
+}
+
+const fn ex(command: &'static str, about: &'static str) -> Example {
+    Example { command, about }
+}
+
+/// Examples of running declair without a subcommand.
+pub const MAIN_EXAMPLES: &[Example] = &[
+    ex(
+        "declair-rs",
+        "Search for a package interactively and add it",
+    ),
+    ex(
+        "declair-rs -p ripgrep",
+        "Add `ripgrep` to the package list and rebuild",
+    ),
+    ex("declair-rs -r -p ripgrep", "Remove `ripgrep` and rebuild"),
+    ex(
+        "declair-rs --program -p git",
+        "Enable `programs.git.enable` instead of adding a list entry",
+    ),
+    ex(
+        "declair-rs --no-rebuild -p htop",
+        "Edit the config without rebuilding",
+    ),
+    ex(
+        "declair-rs --list --details",
+        "List the configured packages",
+    ),
+];
+
+/// Examples of each subcommand, by name.
+pub const COMMAND_EXAMPLES: &[(&str, &[Example])] = &[
+    (
+        "init-config-file",
+        &[ex(
+            "declair-rs init-config-file --insert-import",
+            "Create `declair-packages.nix` and import it from the config",
+        )],
+    ),
+    (
+        "watch",
+        &[ex(
+            "declair-rs watch",
+            "Rebuild whenever a managed Nix file is saved",
+        )],
+    ),
+    (
+        "daemon",
+        &[ex(
+            "declair-rs daemon --socket /tmp/declair.sock",
+            "Serve JSON-RPC requests on a socket of your choosing",
+        )],
+    ),
+    (
+        "add",
+        &[
+            ex(
+                "declair-rs add ripgrep fd bat",
+                "Add several packages, review the diff and rebuild once",
+            ),
+            ex(
+                "declair-rs add --no-interactive jq",
+                "Add without prompts, e.g. from a script",
+            ),
+        ],
+    ),
+    (
+        "replace",
+        &[ex(
+            "declair-rs replace exa eza",
+            "Swap a package for its successor in place",
+        )],
+    ),
+    (
+        "new-package",
+        &[ex(
+            "declair-rs new-package hello-tool",
+            "Create `pkgs/hello-tool/default.nix` and add it with `callPackage`",
+        )],
+    ),
+    (
+        "group",
+        &[
+            ex("declair-rs group list", "List the configured groups"),
+            ex(
+                "declair-rs group add dev",
+                "Add every package of the group `dev`",
+            ),
+            ex(
+                "declair-rs group import https://example.org/groups.toml",
+                "Fetch shared group definitions",
+            ),
+        ],
+    ),
+    (
+        "overlay",
+        &[
+            ex("declair-rs overlay list", "List the overlays"),
+            ex(
+                "declair-rs overlay add emacs github:nix-community/emacs-overlay",
+                "Add an overlay (a flake input in a flake config)",
+            ),
+            ex("declair-rs overlay remove emacs", "Remove it again"),
+        ],
+    ),
+    (
+        "env",
+        &[
+            ex(
+                "declair-rs env set EDITOR nvim",
+                "Set an environment variable",
+            ),
+            ex("declair-rs env unset EDITOR", "Remove its assignment"),
+            ex("declair-rs env list", "List the variables the config sets"),
+        ],
+    ),
+    (
+        "service",
+        &[
+            ex(
+                "declair-rs service enable openssh",
+                "Add `services.openssh.enable = true;`",
+            ),
+            ex("declair-rs service disable openssh", "Remove it again"),
+            ex("declair-rs service list", "List the enabled services"),
+        ],
+    ),
+    (
+        "options",
+        &[
+            ex(
+                "declair-rs options search firewall",
+                "Search the options and set one",
+            ),
+            ex(
+                "declair-rs options set services.openssh.ports '[ 2222 ]'",
+                "Assign a value to an option",
+            ),
+        ],
+    ),
+    (
+        "pin",
+        &[ex(
+            "declair-rs pin firefox",
+            "Make removing or replacing `firefox` need `--force`",
+        )],
+    ),
+    ("unpin", &[ex("declair-rs unpin firefox", "Unpin it again")]),
+    (
+        "list-pins",
+        &[ex("declair-rs list-pins", "List the pinned packages")],
+    ),
+    (
+        "prune",
+        &[
+            ex("declair-rs prune", "Pick packages declair added to remove"),
+            ex("declair-rs prune cowsay sl", "Remove the named ones"),
+        ],
+    ),
+    (
+        "why",
+        &[ex(
+            "declair-rs why openssl",
+            "Show why `openssl` is installed",
+        )],
+    ),
+    (
+        "drift",
+        &[ex(
+            "declair-rs drift",
+            "Compare the config with the running generation",
+        )],
+    ),
+    (
+        "rebuild",
+        &[
+            ex("declair-rs rebuild", "Rebuild after edits made by hand"),
+            ex(
+                "declair-rs rebuild --mode boot",
+                "Make the new generation the boot default without activating it",
+            ),
+        ],
+    ),
+    (
+        "update",
+        &[
+            ex(
+                "declair-rs update",
+                "Update the flake inputs (or channels) and rebuild",
+            ),
+            ex("declair-rs update --yes", "The same without asking"),
+        ],
+    ),
+    (
+        "channel",
+        &[
+            ex("declair-rs channel list", "List the subscribed channels"),
+            ex(
+                "declair-rs channel add https://nixos.org/channels/nixos-unstable nixos",
+                "Subscribe to a channel",
+            ),
+            ex("declair-rs channel update", "Download the latest channels"),
+        ],
+    ),
+    (
+        "schedule",
+        &[
+            ex(
+                "declair-rs schedule install --weekly",
+                "Update and rebuild every week",
+            ),
+            ex("declair-rs schedule status", "Show the timer's next run"),
+            ex("declair-rs schedule uninstall", "Remove the timer"),
+        ],
+    ),
+    (
+        "plugins",
+        &[ex("declair-rs plugins", "List the plugins found on PATH")],
+    ),
+    (
+        "grep",
+        &[ex(
+            "declair-rs grep 'nodejs_*'",
+            "Find every line referring to a Node.js package",
+        )],
+    ),
+    (
+        "audit",
+        &[ex(
+            "declair-rs audit",
+            "Check the configured packages for known vulnerabilities",
+        )],
+    ),
+    (
+        "graph",
+        &[
+            ex("declair-rs graph", "Show the module import tree"),
+            ex(
+                "declair-rs graph dot | dot -Tsvg > modules.svg",
+                "Draw it with Graphviz",
+            ),
+        ],
+    ),
+    (
+        "diff",
+        &[ex(
+            "declair-rs diff desktop.json",
+            "Compare with a `--list --output json` export of another machine",
+        )],
+    ),
+    (
+        "sync",
+        &[
+            ex(
+                "declair-rs sync desktop.json --dry-run",
+                "Preview what following a manifest would change",
+            ),
+            ex("declair-rs sync desktop.json --yes", "Follow it"),
+        ],
+    ),
+    (
+        "provides",
+        &[ex(
+            "declair-rs provides convert",
+            "Find the packages providing `convert` and add one",
+        )],
+    ),
+    (
+        "fix",
+        &[
+            ex(
+                "declair-rs fix",
+                "Add the package of the command that was just not found",
+            ),
+            ex(
+                "declair-rs fix \"command not found: rg\"",
+                "Take the command from an error message",
+            ),
+        ],
+    ),
+    (
+        "doctor",
+        &[ex("declair-rs doctor", "Check the Nix installation")],
+    ),
+    (
+        "paths",
+        &[ex("declair-rs paths", "Show where declair keeps its files")],
+    ),
+    (
+        "backups",
+        &[
+            ex(
+                "declair-rs backups list",
+                "List the earlier states of edited files",
+            ),
+            ex(
+                "declair-rs backups show 1760000000-1",
+                "Show how a file changed since a backup",
+            ),
+            ex("declair-rs backups prune", "Apply the retention now"),
+        ],
+    ),
+    (
+        "status",
+        &[ex(
+            "declair-rs status",
+            "Show the edits no rebuild has applied yet",
+        )],
+    ),
+    (
+        "run",
+        &[ex(
+            "declair-rs run cowsay hello",
+            "Run a package's program, installed or not",
+        )],
+    ),
+    (
+        "info",
+        &[ex(
+            "declair-rs info ripgrep",
+            "Show a package's version, description and license",
+        )],
+    ),
+    (
+        "search",
+        &[
+            ex("declair-rs search browser", "Search nixpkgs"),
+            ex(
+                "declair-rs search --exact firefox --output json",
+                "Look up one attribute as JSON",
+            ),
+        ],
+    ),
+    (
+        "completions",
+        &[
+            ex(
+                "declair-rs completions bash > ~/.local/share/bash-completion/completions/declair-rs",
+                "Install bash completions",
+            ),
+            ex(
+                "declair-rs completions fish > ~/.config/fish/completions/declair-rs.fish",
+                "Install fish completions",
+            ),
+        ],
+    ),
+    (
+        "rpc",
+        &[ex(
+            "declair-rs rpc search '{\"query\": \"ripgrep\"}'",
+            "Ask a running daemon to search",
+        )],
+    ),
+    (
+        "man",
+        &[ex(
+            "declair-rs man --dir ~/.local/share/man/man1",
+            "Write the man pages where `man` finds them",
+        )],
+    ),
+    (
+        "self",
+        &[
+            ex(
+                "declair-rs self check",
+                "Tell how declair was installed and update it",
+            ),
+            ex(
+                "declair-rs --dry-run self check",
+                "Only print the command updating it",
+            ),
+        ],
+    ),
+    (
+        "examples",
+        &[ex("declair-rs examples", "Print common workflows")],
+    ),
+];
+
+/// The examples of subcommand `name`.
+pub fn command_examples(name: &str) -> &'static [Example] {
+    COMMAND_EXAMPLES
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map_or(&[], |(_, examples)| *examples)
+}
+
+/// `examples` as the end of a `--help`.
+pub fn help_text(examples: &[Example]) -> String {
+    let mut text = String::from("Examples:");
+    for example in examples {
+        text.push_str(&format!("\n  # {}\n  $ {}", example.about, example.command));
+    }
+    text
+}
+
+/// `cmd` with its examples and those of its subcommands at the end of their `--help`.
+pub fn with_examples(cmd: Command) -> Command {
+    let mut cmd = cmd.after_long_help(help_text(MAIN_EXAMPLES));
+    for (name, examples) in COMMAND_EXAMPLES {
+        if cmd.find_subcommand(name).is_some() {
+            cmd = cmd.mut_subcommand(name, |sub| sub.after_long_help(help_text(examples)));
+        }
+    }
+    cmd
+}
+
+/// `examples` as the EXAMPLES section of a man page.
+fn examples_section(examples: &[Example]) -> String {
+    let mut roff = Roff::new();
+    roff.control("SH", ["EXAMPLES"]);
+    for example in examples {
+        roff.control("TP", None)
+            .text([bold(example.command)])
+            .text([roman(example.about)]);
+    }
+    roff.render()
+}
+
+/// Write the man page of `cmd`, a built command, with `examples` to `out`. Rendered
+/// section by section so that the examples get a section of their own instead of
+/// clap_mangen's EXTRA.
+fn render_page(cmd: &Command, examples: &[Example], out: &mut dyn Write) -> io::Result<()> {
+    let man = clap_mangen::Man::new(cmd.clone());
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+    if cmd.get_arguments().any(|arg| !arg.is_hide_set()) {
+        man.render_options_section(out)?;
+    }
+    if cmd.has_subcommands() {
+        man.render_subcommands_section(out)?;
+    }
+    if !examples.is_empty() {
+        out.write_all(examples_section(examples).as_bytes())?;
+    }
+    if cmd.get_version().is_some() {
+        man.render_version_section(out)?;
+    }
+    Ok(())
+}
+
+/// `cmd`, the main command, ready to render: built, without the `help` subcommand.
+fn built(cmd: Command) -> Command {
+    let mut cmd = cmd.disable_help_subcommand(true);
+    cmd.build();
+    cmd
+}
+
+/// The man page of `cmd`, the main command, with its examples.
+pub fn man_page(cmd: Command) -> String {
+    let mut page = Vec::new();
+    render_page(&built(cmd), MAIN_EXAMPLES, &mut page).expect("writing to a Vec always works");
+    String::from_utf8_lossy(&page).into_owned()
+}
+
+/// Write the man page of `cmd` with `examples` to `dir`, then those of its subcommands.
+fn write_pages(
+    cmd: &Command,
+    examples: &[Example],
+    dir: &Path,
+    written: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let name = cmd.get_display_name().unwrap_or_else(|| cmd.get_name());
+    let path = dir.join(format!("{}.1", name));
+    let mut page = Vec::new();
+    render_page(cmd, examples, &mut page)?;
+    fs::write(&path, page)?;
+    written.push(path);
+    // Nested subcommands are covered by their parent's examples.
+    for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        write_pages(sub, &[], dir, written)?;
+    }
+    Ok(())
+}
+
+/// Write the man pages of `cmd`, the main command, and of all its subcommands
+/// (`declair-rs.1`, `declair-rs-add.1`, `declair-rs-group-add.1`, ...) to `dir`; the files
+/// written.
+pub fn write_man_pages(cmd: Command, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let cmd = built(cmd);
+    let mut written = Vec::new();
+    let mut page = Vec::new();
+    render_page(&cmd, MAIN_EXAMPLES, &mut page)?;
+    let path = dir.join(format!("{}.1", cmd.get_name()));
+    fs::write(&path, page)?;
+    written.push(path);
+    for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        write_pages(sub, command_examples(sub.get_name()), dir, &mut written)?;
+    }
+    Ok(written)
+}
+
+/// A common task done with a few declair commands.
+pub struct Workflow {
+    /// Message key of the title.
+    pub title: &'static str,
+    pub commands: &'static [&'static str],
+}
+
+/// What `declair examples` prints.
+pub const WORKFLOWS: &[Workflow] = &[
+    Workflow {
+        title: "examples.try_then_install",
+        commands: &[
+            "declair-rs search ripgrep",
+            "declair-rs run ripgrep -- --version",
+            "declair-rs -p ripgrep",
+        ],
+    },
+    Workflow {
+        title: "examples.batch_edits",
+        commands: &[
+            "declair-rs --no-rebuild -p htop",
+            "declair-rs --no-rebuild -r -p nano",
+            "declair-rs status",
+            "declair-rs rebuild",
+        ],
+    },
+    Workflow {
+        title: "examples.update",
+        commands: &["declair-rs update", "declair-rs status"],
+    },
+    Workflow {
+        title: "examples.missing_command",
+        commands: &["declair-rs provides convert", "declair-rs fix"],
+    },
+    Workflow {
+        title: "examples.second_machine",
+        commands: &[
+            "declair-rs --list --output json > desktop.json",
+            "declair-rs diff desktop.json",
+            "declair-rs sync desktop.json --dry-run",
+            "declair-rs sync desktop.json",
+        ],
+    },
+    Workflow {
+        title: "examples.undo",
+        commands: &[
+            "declair-rs backups list",
+            "declair-rs backups show <ID>",
+            "sudo nixos-rebuild switch --rollback",
+        ],
+    },
+    Workflow {
+        title: "examples.cleanup",
+        commands: &[
+            "declair-rs --list --managed-only",
+            "declair-rs why openssl",
+            "declair-rs prune",
+        ],
+    },
+    Workflow {
+        title: "examples.scheduled_updates",
+        commands: &[
+            "declair-rs schedule install --weekly",
+            "declair-rs schedule status",
+        ],
+    },
+];
+
+/// The workflows as copy-pasteable shell, each under its title as a comment.
+pub fn render_workflows(workflows: &[Workflow]) -> String {
+    workflows
+        .iter()
+        .map(|workflow| {
+            let mut block = format!("# {}\n", t!(workflow.title));
+            for command in workflow.commands {
+                block.push_str(command);
+                block.push('\n');
+            }
+            block
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
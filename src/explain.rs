@@ -0,0 +1,77 @@
+//! Teaching mode (`--explain`): next to what declair does, a few sentences on what it
+//! changes in the Nix config and why, for people new to NixOS. Each topic is explained
+//! once per run.
+
+use crate::module::ModuleKind;
+use crate::t;
+use crate::theme::theme;
+use std::sync::{Mutex, OnceLock};
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+static SHOWN: Mutex<Vec<Topic>> = Mutex::new(Vec::new());
+
+/// Turn explanations on or off for the rest of the process. Only the first call has an
+/// effect; without one, nothing is explained.
+pub fn set_explain(enabled: bool) {
+    let _ = ENABLED.set(enabled);
+}
+
+/// What an explanation is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    /// Searching nixpkgs: attribute names versus package names.
+    Search,
+    /// A package added to a `with pkgs; [ ... ]` list.
+    PackageList,
+    /// A `programs.<name>.enable` option set instead of a list entry.
+    ProgramModule,
+    /// A package removed from the config, and what happens to it in the store.
+    Removal,
+    /// What the rebuild of a module of this kind does.
+    Rebuild(ModuleKind),
+    /// Flake rebuilds and `flake.lock`.
+    Flake,
+    /// Where the generation a rebuild made lives, and how to go back.
+    Generations(ModuleKind),
+}
+
+impl Topic {
+    /// The explanation, in the selected language.
+    pub fn text(self) -> String {
+        match self {
+            Topic::Search => t!("explain.search"),
+            Topic::PackageList => t!("explain.package_list"),
+            Topic::ProgramModule => t!("explain.program_module"),
+            Topic::Removal => t!("explain.removal"),
+            Topic::Rebuild(ModuleKind::Nixos) => t!("explain.rebuild_nixos"),
+            Topic::Rebuild(ModuleKind::HomeManager) => t!("explain.rebuild_home_manager"),
+            Topic::Flake => t!("explain.flake"),
+            Topic::Generations(ModuleKind::Nixos) => t!("explain.generations_nixos"),
+            Topic::Generations(ModuleKind::HomeManager) => {
+                t!("explain.generations_home_manager")
+            }
+        }
+    }
+}
+
+/// Whether `topic` is to be explained now: explanations are on and it wasn't yet.
+fn first_time(topic: Topic) -> bool {
+    if !ENABLED.get().copied().unwrap_or(false) {
+        return false;
+    }
+    let Ok(mut shown) = SHOWN.lock() else {
+        return false;
+    };
+    if shown.contains(&topic) {
+        return false;
+    }
+    shown.push(topic);
+    true
+}
+
+/// Print the explanation of `topic` with `--explain`, the first time it comes up.
+pub fn explain(topic: Topic) {
+    if first_time(topic) {
+        println!("{} {}", theme().header(t!("explain.label")), topic.text());
+    }
+}
@@ -0,0 +1,90 @@
+use crate::host::home_dir;
+use crate::provides::is_command_name;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Extract the missing command from a shell or build error, e.g.
+/// `zsh: command not found: rg`, `bash: rg: command not found`, `fish: Unknown command: rg`,
+/// `sh: 1: rg: not found` or `line 3: rg: command not found`. A bare word is taken as the
+/// command itself.
+pub fn missing_command(message: &str) -> Option<String> {
+    let message = message.trim();
+    if is_command_name(message) {
+        return Some(message.to_string());
+    }
+    for line in message.lines().rev() {
+        let line = line.trim();
+        let lower = line.to_lowercase();
+        // `command not found: rg`, `Unknown command: rg`
+        for marker in ["command not found:", "unknown command:"] {
+            if let Some(pos) = lower.find(marker) {
+                let rest = &line[pos + marker.len()..];
+                if let Some(cmd) = rest.split_whitespace().next().map(clean) {
+                    return Some(cmd);
+                }
+            }
+        }
+        // `bash: rg: command not found`, `sh: 1: rg: not found`
+        for marker in [": command not found", ": not found"] {
+            if let Some(pos) = lower.find(marker) {
+                let before = &line[..pos];
+                let cmd = before.rsplit(':').next().map(clean)?;
+                if !cmd.is_empty() {
+                    return Some(cmd);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Command name of the last command in a shell history file (bash, zsh extended history
+/// or fish), skipping declair's own invocations.
+pub fn last_history_command(contents: &str) -> Option<String> {
+    contents.lines().rev().find_map(|line| {
+        let line = line.trim();
+        let line = if let Some(cmd) = line.strip_prefix("- cmd: ") {
+            cmd
+        } else if line.starts_with(": ") {
+            // zsh: `: 1700000000:0;rg foo`
+            line.split_once(';').map_or(line, |(_, cmd)| cmd)
+        } else {
+            line
+        };
+        let cmd = line
+            .split_whitespace()
+            .find(|w| *w != "sudo" && !w.contains('='))?;
+        let name = cmd.rsplit('/').next().unwrap_or(cmd);
+        let is_declair = name == "declair" || name == "declair-rs";
+        (is_command_name(name) && !is_declair).then(|| name.to_string())
+    })
+}
+
+/// The most recently written shell history file: `$HISTFILE`, zsh, bash or fish history.
+pub fn history_file() -> Option<PathBuf> {
+    let home = home_dir()?;
+    let candidates = [
+        env::var_os("HISTFILE").map(PathBuf::from),
+        Some(home.join(".zsh_history")),
+        Some(home.join(".bash_history")),
+        Some(home.join(".local/share/fish/fish_history")),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .filter_map(|p| Some((fs::metadata(&p).ok()?.modified().ok()?, p)))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, p)| p)
+}
+
+/// Command name of the last command in the user's shell history.
+pub fn history_command() -> Option<String> {
+    // zsh history may contain metafied non-UTF-8 bytes.
+    let contents = fs::read(history_file()?).ok()?;
+    last_history_command(&String::from_utf8_lossy(&contents))
+}
+
+fn clean(s: &str) -> String {
+    s.trim().trim_matches(|c| "'\"`".contains(c)).to_string()
+}
@@ -0,0 +1,172 @@
+use crate::error::{DeclairError, Result};
+use crate::module::ModuleKind;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A `nixosConfigurations.<name>` or `homeConfigurations.<name>` output of a flake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlakeOutput {
+    pub kind: ModuleKind,
+    pub name: String,
+}
+
+/// Outputs declared in a `flake.nix` together with the local paths (`./home.nix`,
+/// `./hosts/laptop`) mentioned after each one. This is a line-based approximation of the
+/// flake's structure: good enough for the usual `modules = [ ./... ];` layouts.
+pub fn flake_outputs(contents: &str) -> Vec<(FlakeOutput, Vec<String>)> {
+    let mut outputs: Vec<(FlakeOutput, Vec<String>)> = Vec::new();
+    let mut section: Option<ModuleKind> = None;
+    for line in contents.lines() {
+        let code = line.split('#').next().unwrap_or("");
+        if let Some(output) = output_start(code, &mut section) {
+            outputs.push((output, Vec::new()));
+        }
+        if let Some((_, paths)) = outputs.last_mut() {
+            paths.extend(local_paths(code));
+        }
+    }
+    outputs
+}
+
+/// Recognize the start of an output on `line`, tracking `nixosConfigurations = {` sections.
+fn output_start(line: &str, section: &mut Option<ModuleKind>) -> Option<FlakeOutput> {
+    const SECTIONS: &[(&str, ModuleKind)] = &[
+        ("nixosConfigurations", ModuleKind::Nixos),
+        ("homeConfigurations", ModuleKind::HomeManager),
+    ];
+    let (lhs, rhs) = line.split_once('=')?;
+    let lhs = lhs.trim();
+    for (attr, kind) in SECTIONS {
+        if let Some(name) = lhs.strip_prefix(attr).and_then(|n| n.strip_prefix('.')) {
+            return Some(FlakeOutput {
+                kind: *kind,
+                name: name.trim_matches('"').to_string(),
+            });
+        }
+        if lhs.ends_with(attr) {
+            *section = Some(*kind);
+            return None;
+        }
+    }
+    let builder = rhs.contains("nixosSystem") || rhs.contains("homeManagerConfiguration");
+    match *section {
+        Some(kind) if builder && !lhs.contains(' ') => Some(FlakeOutput {
+            kind,
+            name: lhs.trim_matches('"').to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Relative paths such as `./home.nix` on `line`, without the leading `./`.
+fn local_paths(line: &str) -> impl Iterator<Item = String> + '_ {
+    line.split(|c: char| c.is_whitespace() || "[](){};".contains(c))
+        .filter_map(|tok| tok.strip_prefix("./"))
+        .filter(|p| !p.is_empty())
+        .map(|p| p.trim_end_matches('/').to_string())
+}
+
+/// The directory of the flake building `file` in the repository `repo`: the nearest
+/// directory holding a `flake.nix` from `file` up to `repo`, or else the only one within
+/// two levels below `repo` (e.g. `repo/nix/flake.nix` for modules in `repo/hosts`).
+pub fn find_flake_dir(repo: &Path, file: &Path) -> Option<PathBuf> {
+    let has_flake = |dir: &Path| dir.join("flake.nix").is_file();
+    let start = if file.is_dir() { file } else { file.parent()? };
+    if let Some(dir) = start
+        .ancestors()
+        .take_while(|dir| dir.starts_with(repo))
+        .find(|dir| has_flake(dir))
+    {
+        return Some(dir.to_path_buf());
+    }
+    if has_flake(repo) {
+        return Some(repo.to_path_buf());
+    }
+    let subdirs = |dir: &Path| -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.is_dir()
+                    && !p
+                        .file_name()
+                        .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+            })
+            .collect()
+    };
+    let mut found = subdirs(repo)
+        .into_iter()
+        .flat_map(|d| std::iter::once(d.clone()).chain(subdirs(&d)))
+        .filter(|d| has_flake(d));
+    match (found.next(), found.next()) {
+        (Some(dir), None) => Some(dir),
+        _ => None,
+    }
+}
+
+/// The `flake.nix` building `file` in `repo`, as found by [`find_flake_dir`];
+/// `repo/flake.nix` when there is none.
+pub fn flake_file(repo: &Path, file: &Path) -> PathBuf {
+    find_flake_dir(repo, file)
+        .unwrap_or_else(|| repo.to_path_buf())
+        .join("flake.nix")
+}
+
+/// The output of the flake in `flake_dir` that imports `file`, if it can be told.
+/// `None` when there is no `flake.nix`, the file is the flake itself, or no output mentions it.
+pub fn owning_output(flake_dir: &Path, file: &Path) -> Result<Option<FlakeOutput>> {
+    let flake = flake_dir.join("flake.nix");
+    if !flake.is_file() {
+        return Ok(None);
+    }
+    let canonical = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let (dir, file) = (canonical(flake_dir), canonical(file));
+    let Ok(rel) = file.strip_prefix(&dir) else {
+        return Ok(None);
+    };
+    if rel == Path::new("flake.nix") {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(&flake).map_err(|e| DeclairError::Read {
+        path: flake.clone(),
+        source: e,
+    })?;
+    Ok(flake_outputs(&contents)
+        .into_iter()
+        .find(|(_, paths)| paths.iter().any(|p| rel.starts_with(p)))
+        .map(|(output, _)| output))
+}
+
+/// A flake reference to the exact nixpkgs revision `flake.lock` in `flake_dir` pins for
+/// the flake's `nixpkgs` input, e.g. `github:NixOS/nixpkgs/<rev>`. `None` without a lock
+/// file, a `nixpkgs` input or a lock type that can't be written as a reference.
+pub fn locked_nixpkgs(flake_dir: &Path) -> Option<String> {
+    locked_input(flake_dir, "nixpkgs")
+}
+
+/// Like [`locked_nixpkgs`], for the flake's input `input`, e.g. `home-manager`.
+pub fn locked_input(flake_dir: &Path, input: &str) -> Option<String> {
+    let contents = fs::read_to_string(flake_dir.join("flake.lock")).ok()?;
+    let lock: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    let nodes = &lock["nodes"];
+    let root = lock["root"].as_str().unwrap_or("root");
+    let node = nodes[root]["inputs"][input].as_str()?;
+    let locked = &nodes[node]["locked"];
+    let field = |name: &str| locked[name].as_str();
+    let rev = field("rev");
+    match field("type")? {
+        kind @ ("github" | "gitlab" | "sourcehut") => Some(format!(
+            "{}:{}/{}/{}",
+            kind,
+            field("owner")?,
+            field("repo")?,
+            rev?
+        )),
+        "git" => Some(format!("git+{}?rev={}", field("url")?, rev?)),
+        "tarball" | "file" => field("url").map(str::to_string),
+        "path" => field("path").map(|path| format!("path:{}", path)),
+        _ => None,
+    }
+}
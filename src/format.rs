@@ -0,0 +1,67 @@
+use crate::error::{DeclairError, Result};
+use crate::runner::{CommandRunner, CommandSpec};
+use crate::timings::time;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+/// Formatting a single file should never take long.
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Nix formatter run on the edited file after declair changes it.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Formatter {
+    Nixfmt,
+    Alejandra,
+    NixpkgsFmt,
+    #[default]
+    None,
+}
+
+impl Formatter {
+    /// Command that formats `file` in place, or `None` when formatting is disabled.
+    pub fn command(self, file: &Path) -> Option<CommandSpec> {
+        let file = file.to_string_lossy().into_owned();
+        let cmd = match self {
+            Formatter::Nixfmt => CommandSpec::new("nixfmt", [file]),
+            Formatter::Alejandra => CommandSpec::new("alejandra", ["--quiet".to_string(), file]),
+            Formatter::NixpkgsFmt => CommandSpec::new("nixpkgs-fmt", [file]),
+            Formatter::None => return None,
+        };
+        Some(cmd.timeout(FORMAT_TIMEOUT))
+    }
+}
+
+impl fmt::Display for Formatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Formatter::Nixfmt => "nixfmt",
+            Formatter::Alejandra => "alejandra",
+            Formatter::NixpkgsFmt => "nixpkgs-fmt",
+            Formatter::None => "none",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Run the configured formatter on `file` (and only on `file`).
+/// Callers are expected to treat errors as warnings: the edit itself already succeeded.
+pub fn format_file(runner: &dyn CommandRunner, formatter: Formatter, file: &Path) -> Result<()> {
+    let Some(cmd) = formatter.command(file) else {
+        return Ok(());
+    };
+    let output =
+        time("format", || runner.output(&cmd)).map_err(|e| DeclairError::FormatFailed {
+            formatter: formatter.to_string(),
+            reason: e.to_string(),
+        })?;
+    if !output.status.success() {
+        return Err(DeclairError::FormatFailed {
+            formatter: formatter.to_string(),
+            reason: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(())
+}
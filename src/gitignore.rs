@@ -0,0 +1,72 @@
+//! Keeping declair's own files out of the configuration's git repository: backups written
+//! next to the edited file (when there is no state dir) and temporary files left behind by
+//! an interrupted write.
+
+use crate::atomic::write_atomic;
+use crate::edit::backup_path;
+use crate::error::{DeclairError, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `.gitignore` patterns matching the files declair may leave in a repository.
+pub const IGNORE_PATTERNS: &[&str] = &["*.declair.bak", ".*.declair.tmp"];
+
+/// Written above the patterns declair appends.
+const COMMENT: &str = "# declair backups and temporary files";
+
+/// The working directory of the git repository `path` is in, if any.
+pub fn git_workdir(path: &Path) -> Option<PathBuf> {
+    let repo = gix::discover(path).ok()?;
+    repo.workdir().map(Path::to_path_buf)
+}
+
+/// Whether editing `file` leaves a backup inside `repo`.
+pub fn writes_artifacts(repo: &Path, file: &Path) -> bool {
+    let repo = fs::canonicalize(repo).unwrap_or_else(|_| repo.to_path_buf());
+    let backup = backup_path(file);
+    let dir = backup
+        .parent()
+        .and_then(|dir| fs::canonicalize(dir).ok())
+        .unwrap_or(backup);
+    dir.starts_with(repo)
+}
+
+/// The patterns of [`IGNORE_PATTERNS`] that `repo`'s `.gitignore` doesn't list yet.
+pub fn missing_patterns(repo: &Path) -> Vec<&'static str> {
+    let contents = fs::read_to_string(repo.join(".gitignore")).unwrap_or_default();
+    IGNORE_PATTERNS
+        .iter()
+        .copied()
+        .filter(|pattern| !contents.lines().any(|line| line.trim() == *pattern))
+        .collect()
+}
+
+/// Append `patterns` to `repo`'s `.gitignore` under a comment, creating the file if needed.
+pub fn append_patterns(repo: &Path, patterns: &[&str]) -> Result<()> {
+    let path = repo.join(".gitignore");
+    let mut contents = fs::read_to_string(&path).unwrap_or_default();
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+    contents.push_str(COMMENT);
+    contents.push('\n');
+    for pattern in patterns {
+        contents.push_str(pattern);
+        contents.push('\n');
+    }
+    write_atomic(&path, contents.as_bytes()).map_err(|e| DeclairError::Write { path, source: e })
+}
+
+/// The repository editing `file` leaves backups in, with the patterns its `.gitignore`
+/// still lacks; `None` when the backups go elsewhere or are already ignored.
+pub fn unignored_artifacts(file: &Path) -> Option<(PathBuf, Vec<&'static str>)> {
+    let repo = git_workdir(file)?;
+    if !writes_artifacts(&repo, file) {
+        return None;
+    }
+    let missing = missing_patterns(&repo);
+    (!missing.is_empty()).then_some((repo, missing))
+}
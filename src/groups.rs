@@ -0,0 +1,163 @@
+use crate::config::Group;
+use crate::error::{DeclairError, Result};
+use crate::paths::expand_path;
+use crate::runner::{CommandRunner, CommandSpec};
+use crate::t;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Downloads and clones of shared group files.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+/// File read from a git repository when the source names no file (`repo.git#path`).
+const DEFAULT_GROUPS_FILE: &str = "groups.toml";
+
+/// A shared group file: either a `[groups.<name>]` table like declair's own config, or a
+/// single group's `packages = [...]` named after the file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SharedGroups {
+    #[serde(default)]
+    groups: BTreeMap<String, Group>,
+    packages: Option<Vec<String>>,
+}
+
+/// Parse and validate a shared group file. `default_name` names a single-group file.
+pub fn parse_shared_groups(contents: &str, default_name: &str) -> Result<BTreeMap<String, Group>> {
+    let shared: SharedGroups = toml::from_str(contents)?;
+    let mut groups = shared.groups;
+    if let Some(packages) = shared.packages {
+        groups.insert(
+            default_name.to_string(),
+            Group {
+                packages,
+                ..Default::default()
+            },
+        );
+    }
+    if groups.is_empty() {
+        return Err(DeclairError::InvalidGroup {
+            name: default_name.to_string(),
+            reason: t!("groups.no_groups"),
+        });
+    }
+    for (name, group) in &groups {
+        validate_group(name, group)?;
+    }
+    Ok(groups)
+}
+
+/// Check that a group has a usable name and only plausible package attribute paths, so a
+/// malicious or broken file can't inject Nix code into the config.
+pub fn validate_group(name: &str, group: &Group) -> Result<()> {
+    let invalid = |reason: String| DeclairError::InvalidGroup {
+        name: name.to_string(),
+        reason,
+    };
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_".contains(c))
+    {
+        return Err(invalid(
+            "group names may only contain letters, digits, `-` and `_`".into(),
+        ));
+    }
+    if group.packages.is_empty() {
+        return Err(invalid("the group has no packages".to_string()));
+    }
+    if let Some(pkg) = group.packages.iter().find(|p| !is_attr_path(p)) {
+        return Err(invalid(format!(
+            "`{}` is not a package attribute name",
+            pkg
+        )));
+    }
+    Ok(())
+}
+
+/// Whether `s` looks like a Nix attribute path such as `git` or `python3Packages.requests`.
+pub(crate) fn is_attr_path(s: &str) -> bool {
+    !s.is_empty()
+        && s.split('.').all(|segment| {
+            let mut chars = segment.chars();
+            chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || "_'-".contains(c))
+        })
+}
+
+/// Read a shared group file from an `http(s)://` URL (via `curl`), a git repository
+/// (`git+https://...` or `*.git`, optionally `#path/in/repo.toml`, via `git clone`) or a
+/// local path. Returns the contents and the name a single-group file gets (its file stem).
+pub fn fetch_groups_source(runner: &dyn CommandRunner, source: &str) -> Result<(String, String)> {
+    let fetch_error = |reason: String| DeclairError::FetchFailed {
+        source_url: source.to_string(),
+        reason,
+    };
+    let (repo, file) = match source.split_once('#') {
+        Some((repo, file)) => (repo, Some(file)),
+        None => (source, None),
+    };
+    let is_git = repo.starts_with("git+") || repo.ends_with(".git");
+
+    let (contents, file_name) = if is_git {
+        let url = repo.strip_prefix("git+").unwrap_or(repo);
+        let file = file.unwrap_or(DEFAULT_GROUPS_FILE);
+        let dir = tempfile::tempdir()?;
+        let checkout = dir.path().join("repo");
+        let cmd = CommandSpec::new(
+            "git",
+            [
+                "clone".to_string(),
+                "--depth=1".to_string(),
+                "--quiet".to_string(),
+                url.to_string(),
+                checkout.display().to_string(),
+            ],
+        )
+        .timeout(FETCH_TIMEOUT);
+        let output = runner
+            .output(&cmd)
+            .map_err(|e| fetch_error(e.to_string()))?;
+        if !output.status.success() {
+            return Err(fetch_error(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        let contents = fs::read_to_string(checkout.join(file))
+            .map_err(|e| fetch_error(format!("`{}`: {}", file, e)))?;
+        (contents, file.to_string())
+    } else if source.starts_with("http://") || source.starts_with("https://") {
+        let cmd = CommandSpec::new("curl", ["-fsSL", source])
+            .timeout(FETCH_TIMEOUT)
+            .retries(2);
+        let output = runner
+            .output(&cmd)
+            .map_err(|e| fetch_error(e.to_string()))?;
+        if !output.status.success() {
+            return Err(fetch_error(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        let contents = String::from_utf8(output.stdout)
+            .map_err(|_| fetch_error("response is not UTF-8 text".to_string()))?;
+        let file_name = source.rsplit('/').next().unwrap_or(source).to_string();
+        (contents, file_name)
+    } else {
+        let path = expand_path(source)?;
+        let contents = fs::read_to_string(&path).map_err(|e| DeclairError::Read {
+            path: path.clone(),
+            source: e,
+        })?;
+        (contents, path.display().to_string())
+    };
+
+    let stem = Path::new(&file_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok((contents, stem))
+}
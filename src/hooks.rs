@@ -0,0 +1,259 @@
+//! Commands run around edits and rebuilds and desktop notifications fired after a
+//! rebuild, from the `[hooks]` config table.
+
+use crate::error::{DeclairError, Result};
+use crate::runner::{CommandRunner, CommandSpec, shell_quote};
+use crate::t;
+use crate::timings::format_duration;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How long a hook may run before it is killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What to run around edits and rebuilds. `on_success`/`on_failure` are run with `sh -c`
+/// after replacing `{package}`, `{target}`, `{duration}` and `{status}` with shell-quoted
+/// values; the phase lists get their context as `DECLAIR_*` environment variables instead.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Hooks {
+    /// Run before a config file is edited; a failing command cancels the edit.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pre_edit: Vec<String>,
+    /// Run after the edited file has been written.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub post_edit: Vec<String>,
+    /// Run before a rebuild; a failing command cancels the rebuild.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pre_rebuild: Vec<String>,
+    /// Run after a rebuild, whether it succeeded or not.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub post_rebuild: Vec<String>,
+    /// Run after a successful rebuild, e.g. `"notify-send 'Installed {package}'"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_success: Option<String>,
+    /// Run after a failed rebuild.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+    /// Show a desktop notification (via `notify-send`) after each rebuild.
+    pub notify: bool,
+    /// Skip hooks and notifications for rebuilds shorter than this many seconds.
+    pub min_duration: u64,
+}
+
+impl Hooks {
+    pub fn commands(&self, phase: Phase) -> &[String] {
+        match phase {
+            Phase::PreEdit => &self.pre_edit,
+            Phase::PostEdit => &self.post_edit,
+            Phase::PreRebuild => &self.pre_rebuild,
+            Phase::PostRebuild => &self.post_rebuild,
+        }
+    }
+}
+
+/// When a list of phase hooks runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    PreEdit,
+    PostEdit,
+    PreRebuild,
+    PostRebuild,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::PreEdit => "pre_edit",
+            Phase::PostEdit => "post_edit",
+            Phase::PreRebuild => "pre_rebuild",
+            Phase::PostRebuild => "post_rebuild",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What phase hooks run for, passed to them as environment variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookContext {
+    /// `DECLAIR_ACTION`: `add`, `remove`, `update`, `watch` or `rebuild`.
+    pub action: &'static str,
+    /// `DECLAIR_PACKAGE`, space-separated.
+    pub packages: Vec<String>,
+    /// `DECLAIR_FILE`: the config file that is (or was) edited.
+    pub file: PathBuf,
+}
+
+impl HookContext {
+    pub fn new(action: &'static str, packages: Vec<String>, file: impl Into<PathBuf>) -> Self {
+        HookContext {
+            action,
+            packages,
+            file: file.into(),
+        }
+    }
+}
+
+/// `sh -c command` with the context in `DECLAIR_ACTION`, `DECLAIR_PACKAGE`,
+/// `DECLAIR_FILE` and `DECLAIR_PHASE`, plus `DECLAIR_STATUS` after a rebuild.
+pub fn phase_command(
+    command: &str,
+    phase: Phase,
+    ctx: &HookContext,
+    status: Option<&str>,
+) -> CommandSpec {
+    let cmd = CommandSpec::new("sh", ["-c", command])
+        .env("DECLAIR_ACTION", ctx.action)
+        .env("DECLAIR_PACKAGE", ctx.packages.join(" "))
+        .env("DECLAIR_FILE", ctx.file.display().to_string())
+        .env("DECLAIR_PHASE", phase.to_string())
+        .timeout(HOOK_TIMEOUT);
+    match status {
+        Some(status) => cmd.env("DECLAIR_STATUS", status),
+        None => cmd,
+    }
+}
+
+/// Run the commands configured for `phase` in order, stopping at the first failure.
+/// `status` is the rebuild's outcome for [`Phase::PostRebuild`].
+pub fn run_phase(
+    runner: &dyn CommandRunner,
+    hooks: &Hooks,
+    phase: Phase,
+    ctx: &HookContext,
+    status: Option<&str>,
+) -> Result<()> {
+    for command in hooks.commands(phase) {
+        let cmd = phase_command(command, phase, ctx, status);
+        let status = runner
+            .status(&cmd)
+            .map_err(|e| DeclairError::Hook(t!("hooks.spawn", phase, command, e)))?;
+        if !status.success() {
+            return Err(DeclairError::Hook(t!(
+                "hooks.failed",
+                phase,
+                command,
+                status
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// A finished rebuild, as seen by hooks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebuildEvent {
+    /// Packages the rebuild was started for; empty for a plain rebuild or update.
+    pub packages: Vec<String>,
+    /// `NixOS` or `Home Manager`, with the flake output when there is one.
+    pub target: String,
+    pub duration: Duration,
+    pub succeeded: bool,
+}
+
+impl RebuildEvent {
+    pub fn status(&self) -> &'static str {
+        if self.succeeded { "success" } else { "failure" }
+    }
+
+    /// Values of the template variables.
+    fn vars(&self) -> [(&'static str, String); 4] {
+        [
+            ("package", self.packages.join(" ")),
+            ("target", self.target.clone()),
+            ("duration", format_duration(self.duration)),
+            ("status", self.status().to_string()),
+        ]
+    }
+}
+
+/// Replace the `{name}` variables of `template` with shell-quoted values from `event`.
+/// Unknown variables are left as they are.
+pub fn expand(template: &str, event: &RebuildEvent) -> String {
+    event
+        .vars()
+        .iter()
+        .fold(template.to_string(), |command, (name, value)| {
+            command.replace(&format!("{{{}}}", name), &shell_quote(value))
+        })
+}
+
+/// Run the hook command and show the notification configured for `event`. Both are
+/// attempted; the first failure is returned.
+pub fn fire(runner: &dyn CommandRunner, hooks: &Hooks, event: &RebuildEvent) -> Result<()> {
+    if event.duration < Duration::from_secs(hooks.min_duration) {
+        return Ok(());
+    }
+    let hook = if event.succeeded {
+        &hooks.on_success
+    } else {
+        &hooks.on_failure
+    };
+    let command = match hook {
+        Some(template) => run_hook(runner, template, event),
+        None => Ok(()),
+    };
+    let notification = if hooks.notify {
+        notify(runner, event)
+    } else {
+        Ok(())
+    };
+    command.and(notification)
+}
+
+fn run_hook(runner: &dyn CommandRunner, template: &str, event: &RebuildEvent) -> Result<()> {
+    let cmd =
+        CommandSpec::new("sh", ["-c".to_string(), expand(template, event)]).timeout(HOOK_TIMEOUT);
+    let status = runner
+        .status(&cmd)
+        .map_err(|e| DeclairError::Hook(t!("command.spawn", template, e)))?;
+    if !status.success() {
+        return Err(DeclairError::Hook(t!(
+            "command.failed_status",
+            template,
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// The `notify-send` command announcing `event`.
+pub fn notify_command(event: &RebuildEvent) -> CommandSpec {
+    let (summary, urgency) = if event.succeeded {
+        (t!("hooks.rebuild_succeeded", event.target), "normal")
+    } else {
+        (t!("hooks.rebuild_failed", event.target), "critical")
+    };
+    let duration = format_duration(event.duration);
+    let body = if event.packages.is_empty() {
+        t!("hooks.took", duration)
+    } else {
+        t!("hooks.took_packages", event.packages.join(", "), duration)
+    };
+    CommandSpec::new(
+        "notify-send",
+        [
+            "--app-name=declair".to_string(),
+            format!("--urgency={}", urgency),
+            summary,
+            body,
+        ],
+    )
+    .timeout(HOOK_TIMEOUT)
+}
+
+fn notify(runner: &dyn CommandRunner, event: &RebuildEvent) -> Result<()> {
+    let status = runner
+        .status(&notify_command(event))
+        .map_err(|e| DeclairError::Hook(t!("command.spawn", "notify-send", e)))?;
+    if !status.success() {
+        return Err(DeclairError::Hook(t!(
+            "command.failed_status",
+            "notify-send",
+            status
+        )));
+    }
+    Ok(())
+}
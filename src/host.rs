@@ -0,0 +1,174 @@
+//! The operating system declair runs on. Rebuilds need Linux or macOS; on Windows a
+//! NixOS or Home Manager configuration lives inside WSL, where declair works as on Linux
+//! once it runs there too, and the distributions it could live in are listed with
+//! `wsl.exe`.
+
+use crate::error::{DeclairError, Result};
+use crate::runner::{CommandRunner, CommandSpec};
+use directories::BaseDirs;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Kernel release of a Linux host; WSL kernels carry `microsoft` in it.
+const OSRELEASE: &str = "/proc/sys/kernel/osrelease";
+
+/// How long `wsl.exe --list` may take; it starts the WSL service when it isn't running.
+const WSL_LIST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where declair is running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostOs {
+    Linux,
+    /// Linux under the Windows Subsystem for Linux, with the distribution's name if known.
+    Wsl {
+        distro: Option<String>,
+    },
+    MacOs,
+    Windows,
+    Other,
+}
+
+impl HostOs {
+    /// The host declair runs on.
+    pub fn detect() -> Self {
+        if cfg!(target_os = "macos") {
+            return HostOs::MacOs;
+        }
+        if cfg!(windows) {
+            return HostOs::Windows;
+        }
+        if !cfg!(target_os = "linux") {
+            return HostOs::Other;
+        }
+        let distro = env::var("WSL_DISTRO_NAME").ok().filter(|d| !d.is_empty());
+        let release = fs::read_to_string(OSRELEASE).unwrap_or_default();
+        if distro.is_some() || is_wsl_release(&release) {
+            HostOs::Wsl { distro }
+        } else {
+            HostOs::Linux
+        }
+    }
+
+    /// Whether it can run `nixos-rebuild`, `home-manager` and the other Nix tools.
+    pub fn is_unix(&self) -> bool {
+        !matches!(self, HostOs::Windows | HostOs::Other)
+    }
+}
+
+/// Whether `release` (the contents of `/proc/sys/kernel/osrelease`) is a WSL kernel's,
+/// e.g. `5.15.153.1-microsoft-standard-WSL2`.
+pub fn is_wsl_release(release: &str) -> bool {
+    let release = release.to_lowercase();
+    release.contains("microsoft") || release.contains("wsl")
+}
+
+/// The WSL distributions installed on a Windows host, which is where its NixOS or Home
+/// Manager configuration lives. Empty without WSL.
+pub fn wsl_distributions(runner: &dyn CommandRunner) -> Vec<String> {
+    let cmd = CommandSpec::new("wsl.exe", ["--list", "--quiet"])
+        .timeout(WSL_LIST_TIMEOUT)
+        .query();
+    match runner.output(&cmd) {
+        Ok(output) if output.status.success() => parse_wsl_list(&output.stdout),
+        _ => Vec::new(),
+    }
+}
+
+/// The distribution names in the output of `wsl.exe --list --quiet`: UTF-16 unless
+/// `WSL_UTF8` is set, one per (CRLF-terminated) line.
+pub fn parse_wsl_list(output: &[u8]) -> Vec<String> {
+    // UTF-8 text has no NUL bytes, while UTF-16 has one in every ASCII character.
+    let text = if output.contains(&0) {
+        let units: Vec<u16> = output
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(output).into_owned()
+    };
+    text.lines()
+        .map(|line| line.trim_matches(|c: char| c == '\u{feff}' || c.is_whitespace()))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether the WSL distribution `name` is NixOS-WSL, which is installed as `NixOS`.
+pub fn is_nixos_distribution(name: &str) -> bool {
+    name.to_lowercase().starts_with("nixos")
+}
+
+/// Fail with [`DeclairError::UnsupportedHost`] unless `operation` (e.g. `rebuild`) can run
+/// here, rather than failing to spawn its command.
+pub fn require_unix(operation: &str) -> Result<()> {
+    if HostOs::detect().is_unix() {
+        Ok(())
+    } else {
+        Err(DeclairError::UnsupportedHost {
+            operation: operation.to_string(),
+        })
+    }
+}
+
+/// The user's home directory.
+pub fn home_dir() -> Option<PathBuf> {
+    BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf())
+}
+
+/// Whether the running system is NixOS (including NixOS-WSL).
+pub fn is_nixos() -> bool {
+    Path::new("/etc/NIXOS").exists()
+}
+
+/// Whether systemd manages the running system. WSL distributions only boot with it when
+/// `/etc/wsl.conf` enables it.
+pub fn has_systemd() -> bool {
+    Path::new("/run/systemd/system").exists()
+}
+
+/// Whether declair runs as root (effective user id 0), e.g. under `sudo declair`.
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// Without Unix user ids declair never runs as root.
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
+
+/// Who declair runs as, which decides how commands needing (or not wanting) root run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Privilege {
+    /// An ordinary user: `nixos-rebuild` and writes to root's files go through sudo.
+    #[default]
+    User,
+    /// Root, with the user who ran sudo (`$SUDO_USER`) if it was sudo.
+    Root { invoker: Option<String> },
+}
+
+impl Privilege {
+    /// The privilege declair runs with.
+    pub fn current() -> Self {
+        if !is_root() {
+            return Privilege::User;
+        }
+        let invoker = env::var("SUDO_USER")
+            .ok()
+            .filter(|user| !user.is_empty() && user != "root");
+        Privilege::Root { invoker }
+    }
+
+    /// The user who ran sudo to become root.
+    pub fn invoker(&self) -> Option<&str> {
+        match self {
+            Privilege::Root { invoker } => invoker.as_deref(),
+            Privilege::User => None,
+        }
+    }
+}
@@ -0,0 +1,1965 @@
+//! Message catalog for user-facing text. Messages are looked up by key with [`t!`](crate::t);
+//! a key missing from the active language falls back to English, then to the key itself.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Ru,
+}
+
+impl Language {
+    /// Language of a POSIX locale name such as `ru_RU.UTF-8`; English unless it is Russian.
+    pub fn from_locale(locale: &str) -> Self {
+        if locale.starts_with("ru") {
+            Language::Ru
+        } else {
+            Language::En
+        }
+    }
+
+    /// Language of the first non-empty of `LC_ALL`, `LC_MESSAGES` and `LANG`.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .map(|value| Self::from_locale(&value))
+            .unwrap_or_default()
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::En => EN,
+            Language::Ru => RU,
+        }
+    }
+}
+
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+/// Select the language for the rest of the process. Only the first call has an effect.
+pub fn set_language(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+/// The selected language, English if none was set.
+pub fn language() -> Language {
+    LANGUAGE.get().copied().unwrap_or_default()
+}
+
+/// Message `key` in `language`, falling back to English; `None` if neither has it.
+pub fn find(language: Language, key: &str) -> Option<&'static str> {
+    let find_in = |catalog: &'static [(&'static str, &'static str)]| {
+        catalog.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+    };
+    find_in(language.catalog()).or_else(|| find_in(EN))
+}
+
+/// Message `key` in `language`, or the key itself if no catalog has it.
+pub fn lookup(language: Language, key: &'static str) -> &'static str {
+    find(language, key).unwrap_or(key)
+}
+
+/// Message `key` in the selected language.
+pub fn tr(key: &'static str) -> &'static str {
+    lookup(language(), key)
+}
+
+/// Replace each `{}` in `template` with the next of `args`.
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(pos) = rest.find("{}") {
+        out.push_str(&rest[..pos]);
+        match args.next() {
+            Some(arg) => out.push_str(&arg.to_string()),
+            None => out.push_str("{}"),
+        }
+        rest = &rest[pos + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Translated message `key`, with `{}` placeholders filled from the remaining arguments.
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::tr($key).to_string()
+    };
+    ($key:expr, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::fill($crate::i18n::tr($key), &[$(&$arg as &dyn ::std::fmt::Display),+])
+    };
+}
+
+pub const EN: &[(&str, &str)] = &[
+    // General
+    ("interrupted", "Interrupted"),
+    ("error", "Error: {}"),
+    ("warning", "Warning: {}"),
+    ("warning.label", "Warning:"),
+    (
+        "warning.ctrl_c_handler",
+        "Warning: failed to install Ctrl-C handler: {}",
+    ),
+    (
+        "warning.state_update",
+        "Warning: failed to update install state: {}",
+    ),
+    ("nothing_selected", "Nothing selected"),
+    ("done", "Done ({})"),
+    // Config
+    ("config.no_config_dir", "Failed to get config directory"),
+    (
+        "config.locked",
+        "`{}` is locked by the system config; the setting in your config.toml is ignored",
+    ),
+    (
+        "config.not_found_noninteractive",
+        "Config file not found and --no-interactive specified",
+    ),
+    (
+        "config.nix_path_prompt",
+        "Enter the path to your NixOS configuration file (with 'with pkgs; [')",
+    ),
+    (
+        "config.auto_rebuild_prompt",
+        "Automatically rebuild NixOS after adding a package?",
+    ),
+    ("config.recent_prompt", "Which configuration?"),
+    ("config.other_path", "Another path..."),
+    (
+        "config.pick_noninteractive",
+        "--config without a path asks which recent configuration to use, which --no-interactive rules out",
+    ),
+    (
+        "config.home_manager_prompt",
+        "Use Home Manager as a NixOS configuration?",
+    ),
+    (
+        "config.flake_prompt",
+        "Use a flake as a NixOS configuration?",
+    ),
+    // Package block selection
+    ("block.missing", "No `with pkgs; [...]` block found in `{}`"),
+    ("block.use_line", "Use line {}: {}"),
+    ("block.create", "Create `{} = with pkgs; [ ];`"),
+    ("block.abort", "Abort"),
+    ("segment.prompt", "Which list should {} go to?"),
+    (
+        "scope.invalid",
+        "invalid scope `{}`: expected `system`, `user:<name>`, `fonts` or `home`",
+    ),
+    (
+        "scope.mismatch",
+        "The `{}` scope doesn't apply to a {} module",
+    ),
+    ("segment.main", "The package list itself"),
+    ("segment.conditional", "The segment under `{}`"),
+    ("block.prompt", "Where should the package go?"),
+    // init-config-file
+    ("init.created", "Created `{}`"),
+    ("init.add_import_prompt", "Add `{}` to the imports of `{}`?"),
+    ("init.added_import", "Added `{}` to the imports of `{}`"),
+    (
+        "init.add_line",
+        "Add this line to `{}`:\n\n  imports = [ {} ];\n",
+    ),
+    ("init.import_it", "Import `{}` from your configuration."),
+    ("init.manages", "declair now manages `{}`"),
+    // watch
+    ("watch.watching", "Watching `{}`"),
+    ("watch.stop", "Press Ctrl-C to stop"),
+    (
+        "watch.changed_no_packages",
+        "`{}` changed (no package changes)",
+    ),
+    ("watch.changed", "`{}` changed: {}"),
+    ("watch.rebuild_prompt", "Rebuild now?"),
+    // Rebuild
+    ("rebuild.rebuilding", "Rebuilding {}..."),
+    ("rebuild.with_new", "Rebuilding {} with the new package..."),
+    ("rebuild.finished", "Rebuild finished"),
+    ("rebuild.summary", "The rebuild failed with:"),
+    (
+        "rebuild.trace_prompt",
+        "The configuration failed to evaluate. Evaluate it again with --show-trace?",
+    ),
+    ("rebuild.tracing", "Evaluating with --show-trace..."),
+    (
+        "rebuild.trace_passed",
+        "The configuration evaluates now; there is no trace to show",
+    ),
+    ("rebuild.progress_builds", "built"),
+    ("rebuild.progress_downloads", "downloaded"),
+    ("rebuild.progress_building", "building {}"),
+    ("rebuild.log_saved", "Full rebuild log: {}"),
+    ("rebuild.changes", "{} added, {} updated, {} removed:"),
+    ("rebuild.more_changes", "... and {} more"),
+    (
+        "rebuild.home_manager_mode",
+        "Home Manager only switches; `--mode {}` and `--profile` are for NixOS",
+    ),
+    (
+        "rebuild.deferred",
+        "Another declair run is rebuilding this configuration; it will include this change",
+    ),
+    (
+        "rebuild.coalesced",
+        "Rebuilt {} times to include edits other declair runs made meanwhile",
+    ),
+    ("hooks.rebuild_succeeded", "Rebuilt {}"),
+    ("hooks.rebuild_failed", "Rebuilding {} failed"),
+    ("hooks.took", "Took {}"),
+    ("hooks.took_packages", "{} — took {}"),
+    ("update.not_rebuilt", "Inputs updated; not rebuilding"),
+    ("schedule.installed", "Installed `{}` ({})"),
+    ("schedule.uninstalled", "Removed the declair update timer"),
+    (
+        "schedule.no_systemd",
+        "systemd isn't running, so there are no timers to schedule updates with",
+    ),
+    (
+        "schedule.no_systemd_wsl",
+        "systemd isn't running in this WSL distribution; enable it with `[boot]` `systemd=true` in /etc/wsl.conf and restart WSL",
+    ),
+    (
+        "rebuild.skipped",
+        "Skipping rebuild due to --no-rebuild flag",
+    ),
+    // Batch changes
+    ("review.apply_prompt", "Apply changes to `{}`?"),
+    (
+        "review.not_restored",
+        "`{}` was already written and could not be restored: {}",
+    ),
+    ("batch.checking", "Checking licenses and platforms"),
+    ("batch.skipping", "Skipping `{}`"),
+    ("batch.already_in", "`{}` is already in `{}`, skipping"),
+    ("batch.not_in", "`{}` is not in `{}`, skipping"),
+    ("batch.nothing", "Nothing to change"),
+    ("batch.updated", "Updated `{}`"),
+    ("batch.none_applied", "No changes applied"),
+    // Conflicts
+    (
+        "conflict.check_skipped",
+        "Warning: skipping command conflict check: {}",
+    ),
+    ("conflict.item", "`{}`: {}"),
+    ("conflict.use_force", "{} (use --force to add it anyway)"),
+    ("conflict.add_anyway_prompt", "Add `{}` anyway?"),
+    (
+        "conflict.program_enabled",
+        "already enabled as a program in `{}` (line {})",
+    ),
+    (
+        "conflict.program_package",
+        "already used as `programs.{}.package` in `{}` (line {})",
+    ),
+    ("conflict.listed_elsewhere", "already listed in `{}`"),
+    (
+        "conflict.same_command",
+        "`{}` also provides the `{}` command",
+    ),
+    // why
+    ("why.added", "`{}` was added to `{}` on {} by {}"),
+    ("why.reason", "Reason: {}"),
+    (
+        "why.no_record",
+        "No install record for `{}` (not added by declair)",
+    ),
+    ("why.listed", "Listed in `{}`"),
+    ("why.not_listed", "Not listed in `{}`"),
+    ("why.not_installed", "Not installed in `{}`"),
+    ("why.explicit", "  installed explicitly in the {} profile"),
+    ("why.required_by", "  required by {}"),
+    (
+        "why.still_needed",
+        "Other installed packages depend on `{}`; removing it from the config will not uninstall it",
+    ),
+    // prune
+    ("prune.none", "No packages managed by declair in `{}`"),
+    (
+        "prune.no_packages_noninteractive",
+        "No packages given and --no-interactive specified",
+    ),
+    ("prune.prompt", "Packages to remove"),
+    // Groups
+    ("group.not_found", "No group named `{}` in config"),
+    (
+        "group.exists",
+        "a group with this name already exists (use --force to replace it)",
+    ),
+    ("group.imported", "Imported group `{}`: {}"),
+    // provides / fix
+    ("provides.none", "No package provides `{}`"),
+    ("provides.prompt", "Packages providing `{}` (Esc to cancel)"),
+    ("fix.no_missing_command", "No missing command found in `{}`"),
+    ("fix.no_history", "No command found in the shell history"),
+    ("fix.looking", "Looking for `{}`"),
+    // doctor
+    ("doctor.no_nix", "`nix` not found or its version is unknown"),
+    ("doctor.wsl", "Running under WSL ({})"),
+    ("doctor.wsl_nixos", "Running under NixOS-WSL ({})"),
+    ("doctor.wsl_unknown_distro", "unknown distribution"),
+    (
+        "doctor.windows_no_wsl",
+        "Windows without WSL: declair needs Linux or macOS, or a WSL distribution with Nix",
+    ),
+    (
+        "doctor.windows_wsl",
+        "WSL distribution `{}` found; run declair inside it with `wsl -d {} declair`",
+    ),
+    (
+        "doctor.windows_wsl_nixos",
+        "NixOS-WSL distribution `{}` found; run declair inside it with `wsl -d {} declair`",
+    ),
+    (
+        "doctor.no_features",
+        "No experimental features enabled system-wide; declair passes `--extra-experimental-features \"nix-command flakes\"` itself",
+    ),
+    ("doctor.features", "Experimental features: {}"),
+    (
+        "doctor.old_nix",
+        "Nix older than {} has no flake-based `nix search`; searching with `nix-env -qa`",
+    ),
+    (
+        "doctor.nix_search",
+        "Searching with `nix search` (falls back to `nix-env -qa` if it fails)",
+    ),
+    // daemon
+    ("daemon.serving", "Serving `{}` on `{}`"),
+    ("daemon.invalid_params", "Invalid JSON parameters: {}"),
+    // list
+    (
+        "list.empty",
+        "No packages found in `with pkgs; [...]` block of {}",
+    ),
+    ("list.unassigned", "(line {})"),
+    (
+        "list.tree_empty",
+        "No `with pkgs; [...]` lists with packages in `{}`",
+    ),
+    ("header.file", "File"),
+    ("header.list", "List"),
+    ("header.status", "Status"),
+    ("diff.only_in", "Only in `{}` ({}):"),
+    ("diff.common", "In both ({}):"),
+    ("diff.in_sync", "In sync: both have the same {} packages"),
+    ("header.package", "Package"),
+    ("header.source", "Source"),
+    ("header.added", "Added"),
+    ("header.by", "By"),
+    ("header.reason", "Reason"),
+    ("header.group", "Group"),
+    ("header.packages", "Packages"),
+    ("header.name", "Name"),
+    ("header.version", "Version"),
+    ("header.description", "Description"),
+    ("header.attr", "Attribute"),
+    ("header.homepage", "Homepage"),
+    ("header.license", "License"),
+    ("header.pinned", "Pinned"),
+    // sudo
+    (
+        "sudo.prompt",
+        "`{}` is not writable by you. Write the change with sudo?",
+    ),
+    (
+        "sudo.not_writable",
+        "File is not writable by the current user",
+    ),
+    (
+        "root.home_manager",
+        "Running as root: `{}` is a user's Home Manager config, and `home-manager` run as root builds root's home. Run declair as that user, or through sudo",
+    ),
+    (
+        "root.owner",
+        "Running as root: `{}` belongs to user {}; backups and new files declair writes next to it will belong to root",
+    ),
+    (
+        "root.home",
+        "Running as root: `$HOME` is {}, but `~` in paths stands for {}, the home of {}, who ran sudo",
+    ),
+    ("root.prompt", "Edit `{}` as root anyway?"),
+    (
+        "root.noninteractive",
+        "Refusing to edit the config as root without asking; pass --allow-root to go ahead",
+    ),
+    ("root.declined", "Not editing the config as root"),
+    // Search and selection
+    (
+        "search.no_query_noninteractive",
+        "No query provided and --no-interactive specified",
+    ),
+    ("search.prompt", "Search for a package"),
+    ("search.spinner", "Searching nixpkgs..."),
+    ("search.no_results", "No results found"),
+    ("select.prompt", "Select a package:"),
+    ("select.fzf_failed", "Failed to configure fzf: {}"),
+    ("select.none", "No package selected"),
+    ("select.extract_failed", "Failed to extract package name"),
+    ("select.dry_run", "Selected package: {}"),
+    ("select.details_spinner", "Fetching package details..."),
+    ("select.use_prompt", "Use `{}`?"),
+    (
+        "select.sets",
+        "`{}` is in several package sets, listed first in this order: {}",
+    ),
+    ("select.top_level", "top level"),
+    ("info.homepage", "Homepage: {}"),
+    ("info.license", "License: {}"),
+    ("info.platforms", "Platforms: {}"),
+    ("info.broken", "Marked as broken in nixpkgs"),
+    (
+        "info.insecure",
+        "Marked as insecure in nixpkgs (known vulnerabilities)",
+    ),
+    ("select.installed", "[installed]"),
+    (
+        "rebuild.option_warnings",
+        "The rebuild warned about {} renamed or removed option(s); update your configuration:",
+    ),
+    ("rebuild.news", "Home Manager news ({} unread):"),
+    (
+        "rebuild.diagnosing",
+        "Checking whether the configuration builds without the change...",
+    ),
+    (
+        "rebuild.caused_by_edit",
+        "The configuration evaluates without this change, so the change caused the failure.",
+    ),
+    ("rebuild.revert_prompt", "Revert the change?"),
+    ("rebuild.reverted", "Reverted the change to {}."),
+    (
+        "resume.found",
+        "An earlier run didn't finish rebuilding these edits of {}; the system doesn't have them yet:",
+    ),
+    ("resume.prompt", "What should declair do with them?"),
+    ("resume.rebuild", "Rebuild now"),
+    ("resume.revert", "Revert them"),
+    ("resume.ignore", "Leave them (don't ask again)"),
+    ("self.version", "declair {} ({})"),
+    ("self.nix_profile", "Installed in your Nix profile as `{}`."),
+    (
+        "self.nix_env",
+        "Installed in your Nix profile with `nix-env`.",
+    ),
+    (
+        "self.flake_input",
+        "Installed by your configuration from its flake input `{}` ({}).",
+    ),
+    (
+        "self.nix_store",
+        "Installed from the Nix store ({}), but not by your Nix profile or an input of your configuration's flake. It is updated with whatever installed it, e.g. the nixpkgs of your configuration with `declair update`.",
+    ),
+    ("self.cargo", "Installed with `cargo install`."),
+    (
+        "self.unknown",
+        "Can't tell how declair was installed; update it the way you installed it.",
+    ),
+    ("self.update_prompt", "Update declair now?"),
+    (
+        "self.bump_prompt",
+        "Update the input `{}` in flake.lock now?",
+    ),
+    ("self.update_with", "Update it with: {}"),
+    (
+        "self.updated",
+        "declair updated; the next run uses the new version",
+    ),
+    ("self.bumped", "Updated the input `{}`"),
+    ("man.written", "Wrote {} man pages to {}"),
+    (
+        "examples.try_then_install",
+        "Try a program before installing it",
+    ),
+    (
+        "examples.batch_edits",
+        "Make several edits, then rebuild once",
+    ),
+    ("examples.update", "Update the system"),
+    (
+        "examples.missing_command",
+        "Install the package of a command that wasn't found",
+    ),
+    (
+        "examples.second_machine",
+        "Give a second machine the packages of the first",
+    ),
+    ("examples.undo", "Undo an edit or a rebuild"),
+    ("examples.cleanup", "Clean up packages you no longer need"),
+    (
+        "examples.scheduled_updates",
+        "Update automatically every week",
+    ),
+    ("explain.label", "Explained:"),
+    (
+        "explain.search",
+        "declair searches nixpkgs, the collection of packages your system is built from. A package is named by its attribute (like `python3Packages.requests`), which is what goes into the config; it can differ from the program's own name.",
+    ),
+    (
+        "explain.package_list",
+        "The package was added to a list like `environment.systemPackages = with pkgs; [ ... ];` (`home.packages` with Home Manager). `with pkgs;` brings the attributes of nixpkgs into scope, so the list can say `firefox` instead of `pkgs.firefox`. The file only describes the system: nothing is installed until it is rebuilt.",
+    ),
+    (
+        "explain.program_module",
+        "Instead of a list entry, the program's module was enabled with `programs.<name>.enable = true;`. Besides installing the package, a module sets up what the program needs around it, like its config files, services or shell integration.",
+    ),
+    (
+        "explain.removal",
+        "The package was taken out of the config. After the next rebuild it is no longer part of the system, but it stays in /nix/store as long as an older generation uses it; `nix-collect-garbage` removes what nothing uses any more.",
+    ),
+    (
+        "explain.rebuild_nixos",
+        "`nixos-rebuild` evaluates the whole configuration, builds or downloads everything it needs into /nix/store, and then activates the result: it becomes the new system generation, services are restarted as needed, and the boot menu gets an entry for it. Nothing already there is changed in place.",
+    ),
+    (
+        "explain.rebuild_home_manager",
+        "`home-manager switch` evaluates your home configuration, builds or downloads everything it needs into /nix/store, and then links the result into your home directory (packages, dotfiles) as a new Home Manager generation.",
+    ),
+    (
+        "explain.flake",
+        "The configuration is a flake: its inputs, nixpkgs among them, are pinned to exact revisions in `flake.lock`, so rebuilding gives the same packages until the inputs are updated (`declair update`).",
+    ),
+    (
+        "explain.generations_nixos",
+        "The rebuild made a new generation, kept as /nix/var/nix/profiles/system-<n>-link next to the earlier ones. Earlier generations can be booted from the boot menu, or switched back to with `nixos-rebuild switch --rollback`.",
+    ),
+    (
+        "explain.generations_home_manager",
+        "The rebuild made a new Home Manager generation, kept next to the earlier ones in your Nix profiles (`home-manager generations` lists them). Running an earlier generation's `activate` script switches back to it.",
+    ),
+    (
+        "resume.noninteractive",
+        "Run `declair rebuild` to apply them, or run declair without --no-interactive to revert them.",
+    ),
+    (
+        "rebuild.kept",
+        "Kept the change to {}; its previous version is in its backup (see `declair paths`).",
+    ),
+    (
+        "rebuild.pre_existing",
+        "The configuration fails without this change too: the failure predates it, so the change is kept.",
+    ),
+    (
+        "rebuild.cause_unknown",
+        "Couldn't check whether the change caused the failure; it is kept, with its previous version in its backup (see `declair paths`).",
+    ),
+    ("select.broken", "[broken]"),
+    ("select.insecure", "[insecure]"),
+    (
+        "select.more_results",
+        "{} more results not listed; refine your query to see them",
+    ),
+    // add / remove
+    ("program.found", "Found `{}` in `man configuration.nix`"),
+    ("program.prompt", "As a program?"),
+    ("add.declined", "Not adding `{}`"),
+    ("add.program", "Added `{}` as program to `{}`"),
+    ("add.package", "Added `{}` to `{}`"),
+    ("local.would_add", "Would add `{}` to `{}`"),
+    (
+        "local.scaffold_prompt",
+        "`{}` doesn't exist. Create it from a derivation template?",
+    ),
+    (
+        "local.missing",
+        "No such file; create the derivation first or let declair scaffold one",
+    ),
+    ("local.scaffolded", "Created a derivation template at `{}`"),
+    (
+        "local.fill_in",
+        "Fill in the source and metadata in `{}`, then rebuild",
+    ),
+    (
+        "new_package.bad_name",
+        "`{}` is not a package name; use a single name such as `my-tool`",
+    ),
+    (
+        "new_package.exists",
+        "The package already exists; add it with --local",
+    ),
+    ("store.spinner", "Looking up store paths..."),
+    (
+        "gitignore.prompt",
+        "declair keeps backups inside the repository at {}. Add `{}` to its .gitignore?",
+    ),
+    ("gitignore.added", "Updated {}"),
+    ("git.committed", "Committed: {}"),
+    ("status.config", "Configuration in {} (rebuilds {}):"),
+    ("status.last_rebuild", "Last rebuild by declair: {} ({})"),
+    (
+        "status.never_rebuilt",
+        "declair hasn't recorded a rebuild yet",
+    ),
+    (
+        "status.generation_matches",
+        "The running generation is the one that rebuild activated",
+    ),
+    (
+        "status.generation_switched",
+        "Another generation is running since that rebuild: {}",
+    ),
+    (
+        "status.generation_unknown",
+        "Couldn't tell whether the running generation is the one that rebuild activated",
+    ),
+    ("status.pending", "Edits not applied by a rebuild yet:"),
+    ("status.changed", "Files changed since the last rebuild:"),
+    ("status.rebuild_pending", "A rebuild is pending"),
+    (
+        "status.up_to_date",
+        "The system is up to date with the configuration",
+    ),
+    (
+        "drift.none",
+        "The running generation ({}) matches the configured packages",
+    ),
+    (
+        "drift.missing",
+        "Configured but not in the running generation ({}):",
+    ),
+    (
+        "drift.extra",
+        "In the running generation but no longer configured ({}):",
+    ),
+    (
+        "drift.rebuild_prompt",
+        "Rebuild to apply the configuration?",
+    ),
+    ("store.paths", "`{}` is in the Nix store at:"),
+    ("store.binary", "Run it with `{}` ({})"),
+    ("store.run_prompt", "Run `{}` now?"),
+    ("run.unknown", "No package `{}` in nixpkgs"),
+    (
+        "run.not_on_path",
+        "`{}` isn't on PATH although the configuration lists `{}`; rebuild it first",
+    ),
+    ("run.not_installed", "`{}` isn't in the configuration"),
+    ("run.choice_once", "Run it once with `nix run`"),
+    ("run.choice_add", "Add it to the configuration, then run it"),
+    ("run.once", "Running `{}` with `nix run`..."),
+    ("remove.program", "Removed `{}` as program from `{}`"),
+    ("remove.package", "Removed `{}` from `{}`"),
+    ("replace.done", "Replaced `{}` with `{}` in `{}`"),
+    (
+        "remove.still_referenced",
+        "`{}` is still referenced elsewhere; removing it may break these lines:",
+    ),
+    ("remove.reference", "  {}:{} ({}): {}"),
+    // Errors
+    (
+        "error.block_not_found",
+        "Failed to find `with pkgs; [...]` block in `{}`",
+    ),
+    (
+        "error.malformed_block",
+        "Malformed `with pkgs; [ ... ]` line in `{}`",
+    ),
+    (
+        "error.not_inheritable",
+        "`{}` can't go into the `inherit (pkgs) ...;` of `{}`: only top-level package names can be inherited",
+    ),
+    (
+        "error.package_exists",
+        "Package `{}` is already in `{}` (line {})",
+    ),
+    ("error.package_not_found", "Package `{}` not found in `{}`"),
+    (
+        "error.variable_not_found",
+        "Variable `{}` is not set in `{}`",
+    ),
+    (
+        "error.option_not_found",
+        "No option `{}` is declared (use --force to set it anyway)",
+    ),
+    (
+        "error.backup_not_found",
+        "No backup `{}` (see `declair backups list`)",
+    ),
+    (
+        "error.package_conflict",
+        "Package `{}` conflicts with the existing configuration: {}",
+    ),
+    (
+        "error.not_managed",
+        "Package `{}` in `{}` was not added by declair; refusing to remove it",
+    ),
+    (
+        "error.pinned",
+        "`{}` is pinned; unpin it with `declair unpin` or use --force",
+    ),
+    ("sync.in_sync", "The configuration already matches `{}`"),
+    ("sync.preview", "Syncing `{}` into `{}`:"),
+    (
+        "sync.added_locally",
+        "`{}` was added to the file by hand; the manifest doesn't have it",
+    ),
+    (
+        "sync.removed_locally",
+        "`{}` was removed from the file by hand; the manifest still has it",
+    ),
+    ("sync.conflict_prompt", "Conflict on `{}`:"),
+    ("sync.keep_local", "Keep the file as it is"),
+    ("sync.take_manifest", "Take the manifest's side"),
+    ("sync.apply_prompt", "Add {} and remove {} packages?"),
+    ("pin.pinned", "Pinned `{}`"),
+    ("pin.already_pinned", "`{}` is already pinned"),
+    ("pin.unpinned", "Unpinned `{}`"),
+    ("pin.not_pinned", "`{}` isn't pinned"),
+    ("pin.none", "No packages are pinned"),
+    ("backups.none", "No backups yet"),
+    ("backups.unchanged", "`{}` is unchanged since this backup"),
+    ("backups.pruned", "Removed {} backups"),
+    (
+        "prune.all_pinned",
+        "Every package declair added is pinned; prune them by name with --force",
+    ),
+    (
+        "error.removed_package",
+        "`{}` has been removed from nixpkgs: {}",
+    ),
+    ("alias.renamed", "`{}` has been renamed to `{}` in nixpkgs"),
+    ("alias.removed", "`{}` has been removed from nixpkgs: {}"),
+    ("alias.use_prompt", "Add `{}` instead?"),
+    ("alias.using", "Adding `{}` instead of `{}`"),
+    (
+        "alias.add_anyway_prompt",
+        "Add `{}` anyway? The rebuild will fail",
+    ),
+    (
+        "error.program_exists",
+        "Configuration `{}` already contains `{}`",
+    ),
+    ("error.program_not_found", "No entry `{}` found in `{}`"),
+    (
+        "error.overlay_exists",
+        "Overlay `{}` already exists in `{}`",
+    ),
+    ("error.overlay_not_found", "No overlay `{}` found in `{}`"),
+    (
+        "overlay.invalid_name",
+        "Invalid overlay name `{}`: use letters, digits, `-` and `_`",
+    ),
+    ("overlay.invalid_url", "Invalid overlay URL `{}`"),
+    ("overlay.added", "Added overlay `{}` to `{}`"),
+    ("overlay.removed", "Removed overlay `{}` from `{}`"),
+    ("overlay.none", "No overlays in `{}`"),
+    (
+        "env.invalid_name",
+        "Invalid variable name `{}`: use letters, digits and `_`, not starting with a digit",
+    ),
+    ("env.set", "Set `{}` in `{}`"),
+    ("env.updated", "Changed `{}` in `{}` (was {})"),
+    ("env.unset", "Removed `{}` from `{}`"),
+    ("env.none", "No variables set in `{}`"),
+    (
+        "service.invalid_name",
+        "Invalid service name `{}`: use an attribute path like `openssh` or `xserver.desktopManager.gnome`",
+    ),
+    ("service.enabled", "Enabled `{}` in `{}`"),
+    ("service.disabled", "Removed `{}` from `{}`"),
+    ("service.none", "No services enabled in `{}`"),
+    ("service.checking", "Checking that `{}` exists..."),
+    (
+        "service.check_skipped",
+        "Couldn't check that the option exists: {}",
+    ),
+    (
+        "option.invalid_name",
+        "Invalid option name `{}`: use a dotted attribute path like `networking.hostName`",
+    ),
+    ("option.empty_value", "No value given for `{}`"),
+    ("project.invalid", "Invalid project config `{}`: {}"),
+    (
+        "events.bad_fd",
+        "--log-fd {} is not an open file descriptor",
+    ),
+    (
+        "option.invalid_value",
+        "`{}` is not a single Nix expression",
+    ),
+    (
+        "option.wrong_type",
+        "`{}` doesn't fit `{}`, which takes {} (use --force to set it anyway)",
+    ),
+    ("option.evaluating", "Evaluating the {} options..."),
+    ("option.none_found", "No options match `{}`"),
+    (
+        "option.pick_prompt",
+        "Set one of these options? (Esc to skip)",
+    ),
+    ("option.name_prompt", "Option to set"),
+    (
+        "option.value_prompt",
+        "Value of `{}` (a Nix expression, type {})",
+    ),
+    ("option.set", "Set `{}` to `{}` in `{}`"),
+    ("option.updated", "Changed `{}` to `{}` in `{}` (was {})"),
+    (
+        "overlay.flake_hint",
+        "Pass `inputs` to your modules (e.g. `specialArgs = { inherit inputs; };`) so the overlay can refer to `inputs.{}`",
+    ),
+    ("error.import_exists", "`{}` is already imported in `{}`"),
+    (
+        "error.no_insertion_point",
+        "Failed to find a closing '}' in `{}`; cannot insert",
+    ),
+    (
+        "error.concurrent_modification",
+        "`{}` changed on disk while declair was working; not overwriting it",
+    ),
+    (
+        "error.file_locked",
+        "`{}` is locked by another declair process",
+    ),
+    (
+        "error.privileged_write",
+        "Writing `{}` with sudo failed ({})",
+    ),
+    ("error.invalid_path", "Failed to use path `{}`: {}"),
+    ("error.search_failed", "Package search failed: {}"),
+    ("error.invalid_regex", "Invalid regular expression '{}': {}"),
+    ("error.store_query_failed", "Nix store query failed: {}"),
+    ("error.format_failed", "Formatter `{}` failed: {}"),
+    ("error.invalid_group", "Invalid group `{}`: {}"),
+    ("error.fetch_failed", "Failed to fetch `{}`: {}"),
+    ("error.rpc", "Daemon request failed: {}"),
+    ("error.watch", "Failed to watch files: {}"),
+    ("error.rebuild_failed", "Rebuild failed ({})"),
+    ("error.update_failed", "Updating the inputs failed ({})"),
+    ("error.systemd", "Managing the systemd timer failed: {}"),
+    ("error.channel", "Managing the channels failed: {}"),
+    ("error.audit", "Checking for advisories failed: {}"),
+    ("error.eval", "`nix eval` failed: {}"),
+    ("eval.spawn", "could not run it: {}"),
+    ("eval.unexpected_output", "unexpected output: {}"),
+    ("command.spawn", "Failed to run `{}`: {}"),
+    ("command.failed", "`{}` failed ({}): {}"),
+    ("command.failed_status", "`{}` failed ({})"),
+    ("command.failed_output", "`{}` failed: {}"),
+    ("command.unexpected_output", "Unexpected `{}` output: {}"),
+    ("config.no_state_dir", "Failed to get state directory"),
+    ("state.invalid", "Invalid state file `{}`: {}"),
+    ("state.serialize", "Failed to serialize state: {}"),
+    (
+        "daemon.already_listening",
+        "Another declair daemon is already listening here",
+    ),
+    (
+        "daemon.invalid_response",
+        "Invalid response from daemon: {}",
+    ),
+    ("depends.no_collection", "`{}` has no package collection"),
+    ("groups.no_groups", "the file defines no groups"),
+    ("hooks.spawn", "{} `{}` could not be started: {}"),
+    ("hooks.failed", "{} `{}` ({})"),
+    ("info.no_package", "No package `{}` in nixpkgs"),
+    ("nur.index_error", "NUR index: {}"),
+    ("paths.no_user", "There is no user `{}`"),
+    (
+        "paths.unset_variable",
+        "Environment variable `{}` is not set",
+    ),
+    ("paths.missing", "Path does not exist"),
+    ("paths.no_workdir", "Repository has no working directory"),
+    ("paths.no_parent", "Cannot get parent directory"),
+    (
+        "paths.no_config_in_dir",
+        "The specified directory does not contain any of the expected files: {}",
+    ),
+    ("paths.not_found", "File or directory not found."),
+    ("policy.unchecked", "its license could not be checked: {}"),
+    (
+        "provides.invalid_command",
+        "`{}` is not a valid command name",
+    ),
+    (
+        "provides.no_database",
+        "Neither `nix-locate` (nix-index) nor a channel `programs.sqlite` is available",
+    ),
+    ("provides.query_failed", "Querying `{}` failed: {}"),
+    (
+        "scaffold.exists",
+        "File already exists; refusing to overwrite it",
+    ),
+    ("search.invalid_json", "JSON parsing error: {}"),
+    ("search.nix_too_old", "Nix {}, too old for `nix search`"),
+    ("search.nix_version", "Nix {}"),
+    ("search.unknown_nix_version", "unknown Nix version"),
+    (
+        "search.fallback_failed",
+        "{} ({}); fallback to `nix-env -qa` failed too: {}",
+    ),
+    (
+        "search.nonzero_exit",
+        "Error while running `{}` (non-zero exit code)",
+    ),
+    (
+        "error.vulnix_missing",
+        "`vulnix` is not installed; it is needed to check packages for advisories",
+    ),
+    (
+        "error.vulnerable",
+        "Packages with known vulnerabilities: {}",
+    ),
+    (
+        "error.policy_violation",
+        "`{}` violates the license policy: {} (use --override-policy to add it anyway)",
+    ),
+    (
+        "error.read_only",
+        "declair is read-only on this machine: packages can be searched, listed and shown, but not added or removed, and nothing is rebuilt. Ask your administrator to make the change",
+    ),
+    (
+        "error.license_violations",
+        "Packages violating the license policy: {}",
+    ),
+    (
+        "policy.overridden",
+        "Adding it despite the license policy: {}",
+    ),
+    ("policy.ok", "ok"),
+    (
+        "error.unsupported_platform",
+        "`{}` is not available on {} (use --force to add it anyway)",
+    ),
+    (
+        "error.unsupported_host",
+        "`{}` requires Linux or macOS; on Windows, run declair inside WSL next to your configuration",
+    ),
+    (
+        "platform.unsupported",
+        "`{}` is not available on {}; it builds for: {}",
+    ),
+    ("platform.none_listed", "no platforms listed"),
+    (
+        "platform.add_anyway_prompt",
+        "Add `{}` anyway? The rebuild will likely fail",
+    ),
+    ("platform.check_skipped", "Platform check skipped: {}"),
+    (
+        "audit.vulnerable",
+        "`{}` ({}) has known vulnerabilities, highest severity {}: {}",
+    ),
+    ("audit.more", "and {} more"),
+    ("audit.clean", "No known vulnerabilities in {} packages"),
+    ("audit.spinner", "Checking for advisories..."),
+    (
+        "flake.using_module",
+        "Editing `{}`, the module of `{}` with a package list",
+    ),
+    (
+        "flake.ambiguous_modules",
+        "`{}` has no package list, and several of its modules do: {}. Set `nix_path` to one of them",
+    ),
+    (
+        "flake.module_prompt",
+        "`{}` has no package list. Which module should declair edit?",
+    ),
+    (
+        "flake.remember_prompt",
+        "Make `{}` declair's config file from now on?",
+    ),
+    ("graph.target", "<- declair edits this file"),
+    ("grep.none", "No references to `{}` in `{}`"),
+    ("channel.none", "No channels subscribed"),
+    (
+        "channel.added",
+        "Added channel `{}`; run `declair channel update` to download it",
+    ),
+    ("error.hook", "Rebuild hook failed: {}"),
+    ("error.git", "Couldn't commit the change: {}"),
+    ("error.plugin", "Plugin `{}` failed: {}"),
+    ("plugin.not_found", "no `{}{}` executable on PATH"),
+    ("plugin.invalid_response", "invalid response: {}"),
+    (
+        "plugins.none",
+        "No plugins found (executables named `declair-<name>` on PATH)",
+    ),
+    (
+        "nur.missing_input",
+        "{} has no NUR input; `nur.repos` packages won't evaluate without it",
+    ),
+    ("nur.add_input_prompt", "Add the NUR flake input?"),
+    ("nur.input_added", "Added the `nur` input to {}"),
+    (
+        "nur.overlay_hint",
+        "Apply `inputs.nur.overlays.default` to `nixpkgs.overlays` in your configuration so `pkgs.nur.repos` exists",
+    ),
+    (
+        "nur.channel_hint",
+        "`nur.repos` packages need NUR in `pkgs`, e.g. `nixpkgs.config.packageOverrides = pkgs: { nur = import (builtins.fetchTarball \"https://github.com/nix-community/NUR/archive/main.tar.gz\") { inherit pkgs; }; };`",
+    ),
+    ("error.read", "Failed to read `{}`: {}"),
+    ("error.backup", "Failed to create backup `{}`: {}"),
+    (
+        "error.write",
+        "Failed to write updated configuration to `{}`: {}",
+    ),
+    ("error.no_home_dir", "Failed to get home directory"),
+    ("error.io", "I/O error: {}"),
+    ("error.prompt", "Prompt error: {}"),
+    ("error.toml_de", "Failed to parse config: {}"),
+    ("error.toml_ser", "Failed to serialize config: {}"),
+];
+
+pub const RU: &[(&str, &str)] = &[
+    // General
+    ("interrupted", "Прервано"),
+    ("error", "Ошибка: {}"),
+    ("warning", "Предупреждение: {}"),
+    ("warning.label", "Предупреждение:"),
+    (
+        "warning.ctrl_c_handler",
+        "Предупреждение: не удалось установить обработчик Ctrl-C: {}",
+    ),
+    (
+        "warning.state_update",
+        "Предупреждение: не удалось обновить журнал установок: {}",
+    ),
+    ("nothing_selected", "Ничего не выбрано"),
+    ("done", "Готово ({})"),
+    // Config
+    (
+        "config.no_config_dir",
+        "Не удалось определить каталог конфигурации",
+    ),
+    (
+        "config.locked",
+        "`{}` задан системным конфигом; значение из вашего config.toml не учитывается",
+    ),
+    (
+        "config.not_found_noninteractive",
+        "Файл конфигурации не найден, а задан --no-interactive",
+    ),
+    (
+        "config.nix_path_prompt",
+        "Введите путь к файлу конфигурации NixOS (с 'with pkgs; [')",
+    ),
+    ("config.recent_prompt", "Какую конфигурацию использовать?"),
+    ("config.other_path", "Другой путь..."),
+    (
+        "config.pick_noninteractive",
+        "--config без пути спрашивает, какую из недавних конфигураций использовать, а задан --no-interactive",
+    ),
+    (
+        "config.auto_rebuild_prompt",
+        "Автоматически пересобирать NixOS после добавления пакета?",
+    ),
+    (
+        "config.home_manager_prompt",
+        "Использовать Home Manager как конфигурацию NixOS?",
+    ),
+    (
+        "config.flake_prompt",
+        "Использовать флейк как конфигурацию NixOS?",
+    ),
+    // Package block selection
+    ("block.missing", "Блок `with pkgs; [...]` не найден в `{}`"),
+    ("block.use_line", "Использовать строку {}: {}"),
+    ("block.create", "Создать `{} = with pkgs; [ ];`"),
+    ("block.abort", "Отмена"),
+    ("segment.prompt", "В какой список добавить {}?"),
+    (
+        "scope.invalid",
+        "неверная область `{}`: ожидается `system`, `user:<имя>`, `fonts` или `home`",
+    ),
+    ("scope.mismatch", "Область `{}` неприменима к модулю {}"),
+    ("segment.main", "Основной список пакетов"),
+    ("segment.conditional", "Сегмент под условием `{}`"),
+    ("block.prompt", "Куда добавить пакет?"),
+    // init-config-file
+    ("init.created", "Создан `{}`"),
+    (
+        "init.add_import_prompt",
+        "Добавить `{}` в imports файла `{}`?",
+    ),
+    ("init.added_import", "`{}` добавлен в imports файла `{}`"),
+    (
+        "init.add_line",
+        "Добавьте эту строку в `{}`:\n\n  imports = [ {} ];\n",
+    ),
+    ("init.import_it", "Импортируйте `{}` из своей конфигурации."),
+    ("init.manages", "Теперь declair управляет `{}`"),
+    // watch
+    ("watch.watching", "Отслеживается `{}`"),
+    ("watch.stop", "Нажмите Ctrl-C для остановки"),
+    (
+        "watch.changed_no_packages",
+        "`{}` изменён (пакеты не изменились)",
+    ),
+    ("watch.changed", "`{}` изменён: {}"),
+    ("watch.rebuild_prompt", "Пересобрать сейчас?"),
+    // Rebuild
+    ("rebuild.rebuilding", "Пересборка {}..."),
+    ("rebuild.with_new", "Пересборка {} с новым пакетом..."),
+    ("rebuild.finished", "Пересборка завершена"),
+    ("rebuild.summary", "Пересборка завершилась с ошибками:"),
+    (
+        "rebuild.trace_prompt",
+        "Конфигурация не вычисляется. Вычислить её снова с --show-trace?",
+    ),
+    ("rebuild.tracing", "Вычисление с --show-trace..."),
+    (
+        "rebuild.trace_passed",
+        "Теперь конфигурация вычисляется; показывать нечего",
+    ),
+    ("rebuild.progress_builds", "собрано"),
+    ("rebuild.progress_downloads", "загружено"),
+    ("rebuild.progress_building", "сборка {}"),
+    ("rebuild.log_saved", "Полный журнал пересборки: {}"),
+    (
+        "rebuild.changes",
+        "Добавлено: {}, обновлено: {}, удалено: {}:",
+    ),
+    ("rebuild.more_changes", "... и ещё {}"),
+    (
+        "rebuild.home_manager_mode",
+        "Home Manager умеет только switch; `--mode {}` и `--profile` только для NixOS",
+    ),
+    (
+        "rebuild.deferred",
+        "Эту конфигурацию уже пересобирает другой запуск declair; изменение войдёт в его пересборку",
+    ),
+    (
+        "rebuild.coalesced",
+        "Пересборок: {}, чтобы включить изменения других запусков declair",
+    ),
+    ("hooks.rebuild_succeeded", "Пересборка {} завершена"),
+    ("hooks.rebuild_failed", "Не удалось пересобрать {}"),
+    ("hooks.took", "Заняло {}"),
+    ("hooks.took_packages", "{} — заняло {}"),
+    (
+        "update.not_rebuilt",
+        "Входы обновлены; пересборка не выполнялась",
+    ),
+    ("schedule.installed", "Установлен `{}` ({})"),
+    ("schedule.uninstalled", "Таймер обновления declair удалён"),
+    (
+        "schedule.no_systemd",
+        "systemd не запущен, поэтому таймеров для обновлений по расписанию нет",
+    ),
+    (
+        "schedule.no_systemd_wsl",
+        "В этом дистрибутиве WSL systemd не запущен; включите его через `[boot]` `systemd=true` в /etc/wsl.conf и перезапустите WSL",
+    ),
+    (
+        "rebuild.skipped",
+        "Пересборка пропущена из-за флага --no-rebuild",
+    ),
+    // Batch changes
+    ("review.apply_prompt", "Применить изменения к `{}`?"),
+    (
+        "review.not_restored",
+        "`{}` уже записан, и вернуть его не удалось: {}",
+    ),
+    ("batch.checking", "Проверка лицензий и платформ"),
+    ("batch.skipping", "Пропуск `{}`"),
+    ("batch.already_in", "`{}` уже есть в `{}`, пропуск"),
+    ("batch.not_in", "`{}` нет в `{}`, пропуск"),
+    ("batch.nothing", "Нечего менять"),
+    ("batch.updated", "Обновлён `{}`"),
+    ("batch.none_applied", "Изменения не применены"),
+    // Conflicts
+    (
+        "conflict.check_skipped",
+        "Предупреждение: проверка конфликтов команд пропущена: {}",
+    ),
+    ("conflict.item", "`{}`: {}"),
+    (
+        "conflict.use_force",
+        "{} (используйте --force, чтобы всё равно добавить)",
+    ),
+    ("conflict.add_anyway_prompt", "Всё равно добавить `{}`?"),
+    (
+        "conflict.program_enabled",
+        "уже включён как программа в `{}` (строка {})",
+    ),
+    (
+        "conflict.program_package",
+        "уже используется как `programs.{}.package` в `{}` (строка {})",
+    ),
+    ("conflict.listed_elsewhere", "уже указан в `{}`"),
+    (
+        "conflict.same_command",
+        "`{}` тоже предоставляет команду `{}`",
+    ),
+    // why
+    ("why.added", "`{}` добавлен в `{}` {} пользователем {}"),
+    ("why.reason", "Причина: {}"),
+    (
+        "why.no_record",
+        "Нет записи об установке `{}` (добавлен не через declair)",
+    ),
+    ("why.listed", "Указан в `{}`"),
+    ("why.not_listed", "Не указан в `{}`"),
+    ("why.not_installed", "Не установлен в `{}`"),
+    ("why.explicit", "  установлен явно в профиле {}"),
+    ("why.required_by", "  нужен для {}"),
+    (
+        "why.still_needed",
+        "От `{}` зависят другие установленные пакеты; удаление из конфигурации не удалит его из системы",
+    ),
+    // prune
+    ("prune.none", "В `{}` нет пакетов, добавленных declair"),
+    (
+        "prune.no_packages_noninteractive",
+        "Пакеты не указаны, а задан --no-interactive",
+    ),
+    ("prune.prompt", "Пакеты для удаления"),
+    // Groups
+    ("group.not_found", "В конфигурации нет группы `{}`"),
+    (
+        "group.exists",
+        "группа с таким именем уже существует (используйте --force для замены)",
+    ),
+    ("group.imported", "Импортирована группа `{}`: {}"),
+    // provides / fix
+    ("provides.none", "Ни один пакет не содержит `{}`"),
+    ("provides.prompt", "Пакеты, содержащие `{}` (Esc — отмена)"),
+    (
+        "fix.no_missing_command",
+        "В `{}` не найдена отсутствующая команда",
+    ),
+    ("fix.no_history", "В истории оболочки не найдено команд"),
+    ("fix.looking", "Поиск `{}`"),
+    // doctor
+    ("doctor.no_nix", "`nix` не найден или его версия неизвестна"),
+    ("doctor.wsl", "Запуск в WSL ({})"),
+    ("doctor.wsl_nixos", "Запуск в NixOS-WSL ({})"),
+    ("doctor.wsl_unknown_distro", "неизвестный дистрибутив"),
+    (
+        "doctor.windows_no_wsl",
+        "Windows без WSL: declair нужен Linux или macOS либо дистрибутив WSL с Nix",
+    ),
+    (
+        "doctor.windows_wsl",
+        "Найден дистрибутив WSL `{}`; запускайте declair в нём: `wsl -d {} declair`",
+    ),
+    (
+        "doctor.windows_wsl_nixos",
+        "Найден дистрибутив NixOS-WSL `{}`; запускайте declair в нём: `wsl -d {} declair`",
+    ),
+    (
+        "doctor.no_features",
+        "Экспериментальные возможности не включены в системе; declair сам передаёт `--extra-experimental-features \"nix-command flakes\"`",
+    ),
+    ("doctor.features", "Экспериментальные возможности: {}"),
+    (
+        "doctor.old_nix",
+        "В Nix до версии {} нет `nix search` на основе флейков; поиск через `nix-env -qa`",
+    ),
+    (
+        "doctor.nix_search",
+        "Поиск через `nix search` (при ошибке — через `nix-env -qa`)",
+    ),
+    // daemon
+    ("daemon.serving", "Обслуживается `{}` на `{}`"),
+    ("daemon.invalid_params", "Некорректные параметры JSON: {}"),
+    // list
+    (
+        "list.empty",
+        "В блоке `with pkgs; [...]` файла {} нет пакетов",
+    ),
+    ("list.unassigned", "(строка {})"),
+    (
+        "list.tree_empty",
+        "В `{}` нет списков `with pkgs; [...]` с пакетами",
+    ),
+    ("header.file", "Файл"),
+    ("header.list", "Список"),
+    ("header.status", "Статус"),
+    ("diff.only_in", "Только в `{}` ({}):"),
+    ("diff.common", "В обоих ({}):"),
+    ("diff.in_sync", "Наборы совпадают: {} пакетов в обоих"),
+    ("header.package", "Пакет"),
+    ("header.source", "Источник"),
+    ("header.added", "Добавлен"),
+    ("header.by", "Кем"),
+    ("header.reason", "Причина"),
+    ("header.group", "Группа"),
+    ("header.packages", "Пакеты"),
+    ("header.name", "Имя"),
+    ("header.version", "Версия"),
+    ("header.description", "Описание"),
+    ("header.attr", "Атрибут"),
+    ("header.homepage", "Сайт"),
+    ("header.license", "Лицензия"),
+    ("header.pinned", "Закреплён"),
+    // sudo
+    (
+        "sudo.prompt",
+        "У вас нет прав на запись в `{}`. Записать изменение через sudo?",
+    ),
+    (
+        "sudo.not_writable",
+        "Файл недоступен для записи текущему пользователю",
+    ),
+    (
+        "root.home_manager",
+        "Запуск от root: `{}` — конфигурация Home Manager пользователя, а `home-manager` от root собирает домашний каталог root. Запустите declair от этого пользователя или через sudo",
+    ),
+    (
+        "root.owner",
+        "Запуск от root: `{}` принадлежит пользователю {}; резервные копии и новые файлы declair рядом с ним будут принадлежать root",
+    ),
+    (
+        "root.home",
+        "Запуск от root: `$HOME` — {}, но `~` в путях означает {}, домашний каталог {}, запустившего sudo",
+    ),
+    ("root.prompt", "Всё равно изменить `{}` от root?"),
+    (
+        "root.noninteractive",
+        "Изменение конфигурации от root без подтверждения запрещено; передайте --allow-root, чтобы продолжить",
+    ),
+    ("root.declined", "Конфигурация от root не изменена"),
+    // Search and selection
+    (
+        "search.no_query_noninteractive",
+        "Запрос не указан, а задан --no-interactive",
+    ),
+    ("search.prompt", "Поиск пакета"),
+    ("search.spinner", "Поиск в nixpkgs..."),
+    ("search.no_results", "Ничего не найдено"),
+    ("select.prompt", "Выберите пакет:"),
+    ("select.fzf_failed", "Не удалось настроить fzf: {}"),
+    ("select.none", "Пакет не выбран"),
+    ("select.extract_failed", "Не удалось получить имя пакета"),
+    ("select.dry_run", "Выбран пакет: {}"),
+    ("select.details_spinner", "Получение сведений о пакете..."),
+    ("select.use_prompt", "Выбрать `{}`?"),
+    (
+        "select.sets",
+        "`{}` есть в нескольких наборах пакетов, они идут первыми в таком порядке: {}",
+    ),
+    ("select.top_level", "верхний уровень"),
+    ("info.homepage", "Сайт: {}"),
+    ("info.license", "Лицензия: {}"),
+    ("info.platforms", "Платформы: {}"),
+    ("info.broken", "Помечен в nixpkgs как сломанный"),
+    (
+        "info.insecure",
+        "Помечен в nixpkgs как небезопасный (известные уязвимости)",
+    ),
+    ("select.installed", "[установлен]"),
+    (
+        "rebuild.option_warnings",
+        "Пересборка предупредила о переименованных или удалённых опциях ({}); обновите конфигурацию:",
+    ),
+    ("rebuild.news", "Новости Home Manager (непрочитанных: {}):"),
+    (
+        "rebuild.diagnosing",
+        "Проверка, собирается ли конфигурация без изменения...",
+    ),
+    (
+        "rebuild.caused_by_edit",
+        "Без этого изменения конфигурация вычисляется, значит сбой вызван им.",
+    ),
+    ("rebuild.revert_prompt", "Отменить изменение?"),
+    ("rebuild.reverted", "Изменение в {} отменено."),
+    (
+        "resume.found",
+        "Предыдущий запуск не завершил пересборку после этих изменений {}; в системе их ещё нет:",
+    ),
+    ("resume.prompt", "Что с ними сделать?"),
+    ("resume.rebuild", "Пересобрать сейчас"),
+    ("resume.revert", "Отменить их"),
+    ("resume.ignore", "Оставить (больше не спрашивать)"),
+    ("self.version", "declair {} ({})"),
+    ("self.nix_profile", "Установлен в профиль Nix как `{}`."),
+    ("self.nix_env", "Установлен в профиль Nix через `nix-env`."),
+    (
+        "self.flake_input",
+        "Установлен вашей конфигурацией из входа flake `{}` ({}).",
+    ),
+    (
+        "self.nix_store",
+        "Установлен из хранилища Nix ({}), но не профилем Nix и не входом flake вашей конфигурации. Он обновляется тем, что его установило, например nixpkgs вашей конфигурации через `declair update`.",
+    ),
+    ("self.cargo", "Установлен через `cargo install`."),
+    (
+        "self.unknown",
+        "Не удалось определить, как установлен declair; обновите его тем же способом, каким устанавливали.",
+    ),
+    ("self.update_prompt", "Обновить declair сейчас?"),
+    (
+        "self.bump_prompt",
+        "Обновить вход `{}` в flake.lock сейчас?",
+    ),
+    ("self.update_with", "Обновить можно так: {}"),
+    (
+        "self.updated",
+        "declair обновлён; следующий запуск использует новую версию",
+    ),
+    ("self.bumped", "Вход `{}` обновлён"),
+    ("man.written", "Записано man-страниц: {} в {}"),
+    (
+        "examples.try_then_install",
+        "Попробовать программу перед установкой",
+    ),
+    (
+        "examples.batch_edits",
+        "Сделать несколько правок и пересобрать один раз",
+    ),
+    ("examples.update", "Обновить систему"),
+    (
+        "examples.missing_command",
+        "Установить пакет ненайденной команды",
+    ),
+    (
+        "examples.second_machine",
+        "Перенести пакеты одной машины на другую",
+    ),
+    ("examples.undo", "Отменить правку или пересборку"),
+    ("examples.cleanup", "Убрать ненужные пакеты"),
+    (
+        "examples.scheduled_updates",
+        "Обновляться автоматически каждую неделю",
+    ),
+    ("explain.label", "Пояснение:"),
+    (
+        "explain.search",
+        "declair ищет в nixpkgs, наборе пакетов, из которого собирается система. Пакет называется по атрибуту (например, `python3Packages.requests`): именно он записывается в конфигурацию и может отличаться от имени самой программы.",
+    ),
+    (
+        "explain.package_list",
+        "Пакет добавлен в список вроде `environment.systemPackages = with pkgs; [ ... ];` (`home.packages` в Home Manager). `with pkgs;` делает атрибуты nixpkgs доступными напрямую, поэтому в списке можно писать `firefox` вместо `pkgs.firefox`. Файл лишь описывает систему: ничего не установлено, пока она не пересобрана.",
+    ),
+    (
+        "explain.program_module",
+        "Вместо записи в списке включён модуль программы: `programs.<name>.enable = true;`. Помимо установки пакета, модуль настраивает то, что программе нужно вокруг неё: файлы конфигурации, службы, интеграцию с оболочкой.",
+    ),
+    (
+        "explain.removal",
+        "Пакет убран из конфигурации. После следующей пересборки он больше не входит в систему, но остаётся в /nix/store, пока его использует более старое поколение; `nix-collect-garbage` удаляет то, что уже ничем не используется.",
+    ),
+    (
+        "explain.rebuild_nixos",
+        "`nixos-rebuild` вычисляет всю конфигурацию, собирает или скачивает всё нужное в /nix/store и затем активирует результат: он становится новым поколением системы, службы перезапускаются по необходимости, а в меню загрузки появляется новый пункт. Ничто из уже имеющегося не меняется на месте.",
+    ),
+    (
+        "explain.rebuild_home_manager",
+        "`home-manager switch` вычисляет конфигурацию домашнего каталога, собирает или скачивает всё нужное в /nix/store и затем ссылается на результат из домашнего каталога (пакеты, dotfiles) как на новое поколение Home Manager.",
+    ),
+    (
+        "explain.flake",
+        "Конфигурация — flake: её входы, включая nixpkgs, закреплены на точных ревизиях в `flake.lock`, поэтому пересборка даёт те же пакеты, пока входы не обновлены (`declair update`).",
+    ),
+    (
+        "explain.generations_nixos",
+        "Пересборка создала новое поколение; оно хранится как /nix/var/nix/profiles/system-<n>-link рядом с предыдущими. Предыдущее поколение можно загрузить из меню загрузки или вернуть командой `nixos-rebuild switch --rollback`.",
+    ),
+    (
+        "explain.generations_home_manager",
+        "Пересборка создала новое поколение Home Manager; оно хранится рядом с предыдущими в профилях Nix (`home-manager generations` их перечисляет). Скрипт `activate` предыдущего поколения возвращает к нему.",
+    ),
+    (
+        "resume.noninteractive",
+        "Запустите `declair rebuild`, чтобы применить их, или declair без --no-interactive, чтобы их отменить.",
+    ),
+    (
+        "rebuild.kept",
+        "Изменение в {} сохранено; предыдущая версия лежит в резервной копии (см. `declair paths`).",
+    ),
+    (
+        "rebuild.pre_existing",
+        "Конфигурация не собирается и без этого изменения: сбой был и раньше, поэтому изменение сохранено.",
+    ),
+    (
+        "rebuild.cause_unknown",
+        "Не удалось проверить, вызван ли сбой изменением; оно сохранено, предыдущая версия лежит в резервной копии (см. `declair paths`).",
+    ),
+    ("select.broken", "[сломан]"),
+    ("select.insecure", "[небезопасен]"),
+    (
+        "select.more_results",
+        "Ещё {} результатов не показано; уточните запрос, чтобы их увидеть",
+    ),
+    // add / remove
+    ("program.found", "`{}` найден в `man configuration.nix`"),
+    ("program.prompt", "Как программу?"),
+    ("add.declined", "`{}` не добавлен"),
+    ("add.program", "`{}` добавлен как программа в `{}`"),
+    ("add.package", "`{}` добавлен в `{}`"),
+    ("local.would_add", "Будет добавлен `{}` в `{}`"),
+    (
+        "local.scaffold_prompt",
+        "`{}` не существует. Создать его из шаблона деривации?",
+    ),
+    (
+        "local.missing",
+        "Файл не найден; создайте деривацию или позвольте declair создать шаблон",
+    ),
+    ("local.scaffolded", "Шаблон деривации создан в `{}`"),
+    (
+        "local.fill_in",
+        "Заполните исходники и метаданные в `{}`, затем пересоберите",
+    ),
+    (
+        "new_package.bad_name",
+        "`{}` не является именем пакета; используйте одно имя, например `my-tool`",
+    ),
+    (
+        "new_package.exists",
+        "Пакет уже существует; добавьте его через --local",
+    ),
+    ("store.spinner", "Поиск путей в хранилище..."),
+    (
+        "gitignore.prompt",
+        "declair хранит резервные копии внутри репозитория {}. Добавить `{}` в его .gitignore?",
+    ),
+    ("gitignore.added", "Обновлён {}"),
+    ("git.committed", "Закоммичено: {}"),
+    ("status.config", "Конфигурация в {} (пересобирается {}):"),
+    (
+        "status.last_rebuild",
+        "Последняя пересборка через declair: {} ({})",
+    ),
+    (
+        "status.never_rebuilt",
+        "declair ещё не записал ни одной пересборки",
+    ),
+    (
+        "status.generation_matches",
+        "Запущено поколение, активированное этой пересборкой",
+    ),
+    (
+        "status.generation_switched",
+        "После этой пересборки запущено другое поколение: {}",
+    ),
+    (
+        "status.generation_unknown",
+        "Не удалось определить, запущено ли поколение, активированное этой пересборкой",
+    ),
+    (
+        "status.pending",
+        "Изменения, ещё не применённые пересборкой:",
+    ),
+    (
+        "status.changed",
+        "Файлы, изменённые после последней пересборки:",
+    ),
+    ("status.rebuild_pending", "Требуется пересборка"),
+    ("status.up_to_date", "Система соответствует конфигурации"),
+    (
+        "drift.none",
+        "Запущенное поколение ({}) совпадает с пакетами конфигурации",
+    ),
+    (
+        "drift.missing",
+        "Есть в конфигурации, но нет в запущенном поколении ({}):",
+    ),
+    (
+        "drift.extra",
+        "Есть в запущенном поколении, но больше нет в конфигурации ({}):",
+    ),
+    (
+        "drift.rebuild_prompt",
+        "Пересобрать, чтобы применить конфигурацию?",
+    ),
+    ("store.paths", "`{}` находится в хранилище Nix:"),
+    ("store.binary", "Запуск: `{}` ({})"),
+    ("store.run_prompt", "Запустить `{}` сейчас?"),
+    ("run.unknown", "Пакета `{}` нет в nixpkgs"),
+    (
+        "run.not_on_path",
+        "`{}` не найден в PATH, хотя `{}` есть в конфигурации; сначала пересоберите её",
+    ),
+    ("run.not_installed", "`{}` нет в конфигурации"),
+    ("run.choice_once", "Запустить один раз через `nix run`"),
+    ("run.choice_add", "Добавить в конфигурацию и запустить"),
+    ("run.once", "Запуск `{}` через `nix run`..."),
+    ("remove.program", "Программа `{}` удалена из `{}`"),
+    ("remove.package", "`{}` удалён из `{}`"),
+    ("replace.done", "`{}` заменён на `{}` в `{}`"),
+    (
+        "remove.still_referenced",
+        "На `{}` ещё есть ссылки; после удаления эти строки могут сломаться:",
+    ),
+    ("remove.reference", "  {}:{} ({}): {}"),
+    // Errors
+    (
+        "error.block_not_found",
+        "Не удалось найти блок `with pkgs; [...]` в `{}`",
+    ),
+    (
+        "error.malformed_block",
+        "Некорректная строка `with pkgs; [ ... ]` в `{}`",
+    ),
+    (
+        "error.not_inheritable",
+        "`{}` нельзя добавить в `inherit (pkgs) ...;` в `{}`: наследовать можно только пакеты верхнего уровня",
+    ),
+    (
+        "error.package_exists",
+        "Пакет `{}` уже есть в `{}` (строка {})",
+    ),
+    ("error.package_not_found", "Пакет `{}` не найден в `{}`"),
+    (
+        "error.variable_not_found",
+        "Переменная `{}` не задана в `{}`",
+    ),
+    (
+        "error.option_not_found",
+        "Опция `{}` не объявлена (используйте --force, чтобы задать её всё равно)",
+    ),
+    (
+        "error.backup_not_found",
+        "Резервной копии `{}` нет (см. `declair backups list`)",
+    ),
+    (
+        "error.package_conflict",
+        "Пакет `{}` конфликтует с текущей конфигурацией: {}",
+    ),
+    (
+        "error.not_managed",
+        "Пакет `{}` в `{}` добавлен не через declair; удаление отклонено",
+    ),
+    (
+        "error.pinned",
+        "`{}` закреплён; открепите его через `declair unpin` или используйте --force",
+    ),
+    ("sync.in_sync", "Конфигурация уже соответствует `{}`"),
+    ("sync.preview", "Синхронизация `{}` в `{}`:"),
+    (
+        "sync.added_locally",
+        "`{}` добавлен в файл вручную; в манифесте его нет",
+    ),
+    (
+        "sync.removed_locally",
+        "`{}` удалён из файла вручную; в манифесте он остался",
+    ),
+    ("sync.conflict_prompt", "Конфликт по `{}`:"),
+    ("sync.keep_local", "Оставить файл как есть"),
+    ("sync.take_manifest", "Принять версию манифеста"),
+    ("sync.apply_prompt", "Добавить {} и удалить {} пакетов?"),
+    ("pin.pinned", "`{}` закреплён"),
+    ("pin.already_pinned", "`{}` уже закреплён"),
+    ("pin.unpinned", "`{}` откреплён"),
+    ("pin.not_pinned", "`{}` не закреплён"),
+    ("pin.none", "Закреплённых пакетов нет"),
+    ("backups.none", "Резервных копий пока нет"),
+    (
+        "backups.unchanged",
+        "`{}` не изменился с этой резервной копии",
+    ),
+    ("backups.pruned", "Удалено резервных копий: {}"),
+    (
+        "prune.all_pinned",
+        "Все пакеты, добавленные declair, закреплены; удалите их по имени с --force",
+    ),
+    ("error.removed_package", "`{}` удалён из nixpkgs: {}"),
+    ("alias.renamed", "`{}` переименован в nixpkgs в `{}`"),
+    ("alias.removed", "`{}` удалён из nixpkgs: {}"),
+    ("alias.use_prompt", "Добавить `{}` вместо него?"),
+    ("alias.using", "Добавляется `{}` вместо `{}`"),
+    (
+        "alias.add_anyway_prompt",
+        "Всё равно добавить `{}`? Пересборка завершится ошибкой",
+    ),
+    (
+        "error.program_exists",
+        "Конфигурация `{}` уже содержит `{}`",
+    ),
+    ("error.program_not_found", "Запись `{}` не найдена в `{}`"),
+    ("error.overlay_exists", "Оверлей `{}` уже есть в `{}`"),
+    ("error.overlay_not_found", "Оверлей `{}` не найден в `{}`"),
+    (
+        "overlay.invalid_name",
+        "Недопустимое имя оверлея `{}`: используйте буквы, цифры, `-` и `_`",
+    ),
+    ("overlay.invalid_url", "Недопустимый URL оверлея `{}`"),
+    ("overlay.added", "Оверлей `{}` добавлен в `{}`"),
+    ("overlay.removed", "Оверлей `{}` удалён из `{}`"),
+    ("overlay.none", "В `{}` нет оверлеев"),
+    (
+        "env.invalid_name",
+        "Недопустимое имя переменной `{}`: используйте буквы, цифры и `_`, не начиная с цифры",
+    ),
+    ("env.set", "`{}` задана в `{}`"),
+    ("env.updated", "`{}` изменена в `{}` (было {})"),
+    ("env.unset", "`{}` удалена из `{}`"),
+    ("env.none", "В `{}` не задано переменных"),
+    (
+        "service.invalid_name",
+        "Недопустимое имя службы `{}`: используйте путь атрибута вроде `openssh` или `xserver.desktopManager.gnome`",
+    ),
+    ("service.enabled", "`{}` включена в `{}`"),
+    ("service.disabled", "`{}` удалена из `{}`"),
+    ("service.none", "В `{}` не включено служб"),
+    ("service.checking", "Проверка наличия `{}`..."),
+    (
+        "service.check_skipped",
+        "Не удалось проверить наличие опции: {}",
+    ),
+    (
+        "option.invalid_name",
+        "Недопустимое имя опции `{}`: используйте путь атрибута через точку вроде `networking.hostName`",
+    ),
+    ("option.empty_value", "Не задано значение `{}`"),
+    ("project.invalid", "Некорректный конфиг проекта `{}`: {}"),
+    (
+        "events.bad_fd",
+        "--log-fd {} не является открытым файловым дескриптором",
+    ),
+    (
+        "option.invalid_value",
+        "`{}` не является одним выражением Nix",
+    ),
+    (
+        "option.wrong_type",
+        "`{}` не подходит для `{}`, которая принимает {} (используйте --force, чтобы задать всё равно)",
+    ),
+    ("option.evaluating", "Вычисление опций {}..."),
+    ("option.none_found", "Нет опций, подходящих под `{}`"),
+    (
+        "option.pick_prompt",
+        "Задать одну из этих опций? (Esc — пропустить)",
+    ),
+    ("option.name_prompt", "Задаваемая опция"),
+    (
+        "option.value_prompt",
+        "Значение `{}` (выражение Nix, тип {})",
+    ),
+    ("option.set", "`{}` = `{}` задана в `{}`"),
+    ("option.updated", "`{}` изменена на `{}` в `{}` (было {})"),
+    (
+        "overlay.flake_hint",
+        "Передайте `inputs` в модули (например, `specialArgs = { inherit inputs; };`), чтобы оверлей мог ссылаться на `inputs.{}`",
+    ),
+    ("error.import_exists", "`{}` уже импортирован в `{}`"),
+    (
+        "error.no_insertion_point",
+        "Не найдена закрывающая '}' в `{}`; вставка невозможна",
+    ),
+    (
+        "error.concurrent_modification",
+        "`{}` изменился на диске во время работы declair; перезапись отменена",
+    ),
+    (
+        "error.file_locked",
+        "`{}` заблокирован другим процессом declair",
+    ),
+    (
+        "error.privileged_write",
+        "Запись `{}` через sudo не удалась ({})",
+    ),
+    (
+        "error.invalid_path",
+        "Не удалось использовать путь `{}`: {}",
+    ),
+    ("error.search_failed", "Поиск пакетов не удался: {}"),
+    (
+        "error.invalid_regex",
+        "Некорректное регулярное выражение '{}': {}",
+    ),
+    (
+        "error.store_query_failed",
+        "Запрос к хранилищу Nix не удался: {}",
+    ),
+    (
+        "error.format_failed",
+        "Форматтер `{}` завершился с ошибкой: {}",
+    ),
+    ("error.invalid_group", "Некорректная группа `{}`: {}"),
+    ("error.fetch_failed", "Не удалось загрузить `{}`: {}"),
+    ("error.rpc", "Запрос к демону не удался: {}"),
+    ("error.watch", "Не удалось отслеживать файлы: {}"),
+    ("error.rebuild_failed", "Пересборка не удалась ({})"),
+    ("error.update_failed", "Не удалось обновить входы ({})"),
+    ("error.systemd", "Не удалось настроить таймер systemd: {}"),
+    ("error.channel", "Не удалось настроить каналы: {}"),
+    ("error.audit", "Не удалось проверить уязвимости: {}"),
+    ("error.eval", "`nix eval` завершился с ошибкой: {}"),
+    ("eval.spawn", "не удалось его запустить: {}"),
+    ("eval.unexpected_output", "неожиданный вывод: {}"),
+    ("command.spawn", "Не удалось запустить `{}`: {}"),
+    ("command.failed", "`{}` завершился с ошибкой ({}): {}"),
+    ("command.failed_status", "`{}` завершился с ошибкой ({})"),
+    ("command.failed_output", "`{}` завершился с ошибкой: {}"),
+    ("command.unexpected_output", "Неожиданный вывод `{}`: {}"),
+    (
+        "config.no_state_dir",
+        "Не удалось определить каталог состояния",
+    ),
+    ("state.invalid", "Некорректный файл состояния `{}`: {}"),
+    ("state.serialize", "Не удалось сериализовать состояние: {}"),
+    (
+        "daemon.already_listening",
+        "Здесь уже слушает другой демон declair",
+    ),
+    ("daemon.invalid_response", "Некорректный ответ демона: {}"),
+    ("depends.no_collection", "В `{}` нет набора пакетов"),
+    ("groups.no_groups", "в файле не определено ни одной группы"),
+    ("hooks.spawn", "Не удалось запустить {} `{}`: {}"),
+    ("hooks.failed", "{} `{}` ({})"),
+    ("info.no_package", "В nixpkgs нет пакета `{}`"),
+    ("nur.index_error", "Индекс NUR: {}"),
+    ("paths.no_user", "Пользователя `{}` не существует"),
+    (
+        "paths.unset_variable",
+        "Переменная окружения `{}` не задана",
+    ),
+    ("paths.missing", "Путь не существует"),
+    ("paths.no_workdir", "У репозитория нет рабочего каталога"),
+    (
+        "paths.no_parent",
+        "Не удалось определить родительский каталог",
+    ),
+    (
+        "paths.no_config_in_dir",
+        "В указанном каталоге нет ни одного из ожидаемых файлов: {}",
+    ),
+    ("paths.not_found", "Файл или каталог не найден."),
+    ("policy.unchecked", "не удалось проверить его лицензию: {}"),
+    (
+        "provides.invalid_command",
+        "`{}` — недопустимое имя команды",
+    ),
+    (
+        "provides.no_database",
+        "Нет ни `nix-locate` (nix-index), ни базы `programs.sqlite` канала",
+    ),
+    (
+        "provides.query_failed",
+        "Запрос к `{}` завершился с ошибкой: {}",
+    ),
+    (
+        "scaffold.exists",
+        "Файл уже существует; перезаписывать его нельзя",
+    ),
+    ("search.invalid_json", "Ошибка разбора JSON: {}"),
+    ("search.nix_too_old", "Nix {} слишком стар для `nix search`"),
+    ("search.nix_version", "Nix {}"),
+    ("search.unknown_nix_version", "версия Nix неизвестна"),
+    (
+        "search.fallback_failed",
+        "{} ({}); запасной `nix-env -qa` тоже завершился с ошибкой: {}",
+    ),
+    (
+        "search.nonzero_exit",
+        "Ошибка при выполнении `{}` (ненулевой код возврата)",
+    ),
+    (
+        "error.vulnix_missing",
+        "`vulnix` не установлен; он нужен для проверки пакетов на уязвимости",
+    ),
+    ("error.vulnerable", "Пакетов с известными уязвимостями: {}"),
+    (
+        "error.policy_violation",
+        "`{}` нарушает лицензионную политику: {} (--override-policy, чтобы всё равно добавить)",
+    ),
+    (
+        "error.read_only",
+        "declair на этой машине только для чтения: пакеты можно искать, просматривать и выводить списком, но не добавлять и не удалять, и ничего не пересобирается. Обратитесь к администратору",
+    ),
+    (
+        "error.license_violations",
+        "Пакетов, нарушающих лицензионную политику: {}",
+    ),
+    (
+        "policy.overridden",
+        "Добавляется вопреки лицензионной политике: {}",
+    ),
+    ("policy.ok", "ok"),
+    (
+        "error.unsupported_platform",
+        "`{}` недоступен на {} (--force, чтобы всё равно добавить)",
+    ),
+    (
+        "error.unsupported_host",
+        "`{}` требует Linux или macOS; в Windows запускайте declair внутри WSL, рядом с конфигурацией",
+    ),
+    (
+        "platform.unsupported",
+        "`{}` недоступен на {}; поддерживаемые системы: {}",
+    ),
+    ("platform.none_listed", "не указаны"),
+    (
+        "platform.add_anyway_prompt",
+        "Всё равно добавить `{}`? Пересборка, скорее всего, завершится ошибкой",
+    ),
+    ("platform.check_skipped", "Проверка платформы пропущена: {}"),
+    (
+        "audit.vulnerable",
+        "У `{}` ({}) есть известные уязвимости, наивысшая критичность {}: {}",
+    ),
+    ("audit.more", "и ещё {}"),
+    ("audit.clean", "Известных уязвимостей в {} пакетах нет"),
+    ("audit.spinner", "Проверка уязвимостей..."),
+    (
+        "flake.using_module",
+        "Редактируется `{}` — модуль `{}` со списком пакетов",
+    ),
+    (
+        "flake.ambiguous_modules",
+        "В `{}` нет списка пакетов, а в нескольких его модулях есть: {}. Укажите один из них в `nix_path`",
+    ),
+    (
+        "flake.module_prompt",
+        "В `{}` нет списка пакетов. Какой модуль редактировать?",
+    ),
+    (
+        "flake.remember_prompt",
+        "Сделать `{}` файлом конфигурации declair?",
+    ),
+    ("graph.target", "<- этот файл редактирует declair"),
+    ("grep.none", "Ссылок на `{}` в `{}` нет"),
+    ("channel.none", "Нет подписок на каналы"),
+    (
+        "channel.added",
+        "Канал `{}` добавлен; выполните `declair channel update`, чтобы загрузить его",
+    ),
+    ("error.hook", "Хук пересборки завершился ошибкой: {}"),
+    ("error.git", "Не удалось закоммитить изменение: {}"),
+    ("error.plugin", "Ошибка плагина `{}`: {}"),
+    (
+        "plugin.not_found",
+        "исполняемый файл `{}{}` не найден в PATH",
+    ),
+    ("plugin.invalid_response", "некорректный ответ: {}"),
+    (
+        "plugins.none",
+        "Плагины не найдены (исполняемые файлы `declair-<имя>` в PATH)",
+    ),
+    (
+        "nur.missing_input",
+        "В {} нет входа NUR; пакеты `nur.repos` без него не вычислятся",
+    ),
+    ("nur.add_input_prompt", "Добавить вход NUR во флейк?"),
+    ("nur.input_added", "Вход `nur` добавлен в {}"),
+    (
+        "nur.overlay_hint",
+        "Добавьте `inputs.nur.overlays.default` в `nixpkgs.overlays` вашей конфигурации, чтобы появился `pkgs.nur.repos`",
+    ),
+    (
+        "nur.channel_hint",
+        "Пакетам `nur.repos` нужен NUR в `pkgs`, например `nixpkgs.config.packageOverrides = pkgs: { nur = import (builtins.fetchTarball \"https://github.com/nix-community/NUR/archive/main.tar.gz\") { inherit pkgs; }; };`",
+    ),
+    ("error.read", "Не удалось прочитать `{}`: {}"),
+    (
+        "error.backup",
+        "Не удалось создать резервную копию `{}`: {}",
+    ),
+    (
+        "error.write",
+        "Не удалось записать обновлённую конфигурацию в `{}`: {}",
+    ),
+    (
+        "error.no_home_dir",
+        "Не удалось определить домашний каталог",
+    ),
+    ("error.io", "Ошибка ввода-вывода: {}"),
+    ("error.prompt", "Ошибка запроса: {}"),
+    ("error.toml_de", "Не удалось разобрать конфигурацию: {}"),
+    ("error.toml_ser", "Не удалось сохранить конфигурацию: {}"),
+];
@@ -0,0 +1,244 @@
+//! Local imports between the files of a configuration: `./hosts/laptop`, `../common.nix`
+//! and the like, found in `imports = [ ... ]` lists and a flake's `modules = [ ... ]`.
+
+use crate::edit::{find_candidate_lists, package_lists};
+use crate::error::{DeclairError, Result};
+use crate::output::TreeNode;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The local Nix files `file` refers to, in order of appearance: relative paths to `.nix`
+/// files, and to directories holding a `default.nix`. Paths that don't exist are skipped.
+pub fn local_imports(file: &Path) -> Result<Vec<PathBuf>> {
+    let contents = fs::read_to_string(file).map_err(|e| DeclairError::Read {
+        path: file.to_path_buf(),
+        source: e,
+    })?;
+    let dir = file.parent().unwrap_or(Path::new("."));
+    let mut imports = Vec::new();
+    for line in contents.lines() {
+        let code = line.split('#').next().unwrap_or("");
+        for token in code.split(|c: char| c.is_whitespace() || "[](){};\"".contains(c)) {
+            if !(token.starts_with("./") || token.starts_with("../")) {
+                continue;
+            }
+            let path = normalize(&dir.join(token.trim_end_matches('/')));
+            let module = if path.is_dir() {
+                path.join("default.nix")
+            } else {
+                path
+            };
+            let is_nix = module.extension().is_some_and(|e| e == "nix");
+            if is_nix && module.is_file() && module != file && !imports.contains(&module) {
+                imports.push(module);
+            }
+        }
+    }
+    Ok(imports)
+}
+
+/// Resolve `.` and `..` components without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if out.file_name().is_some() => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b
+        || fs::canonicalize(a)
+            .ok()
+            .is_some_and(|a| fs::canonicalize(b).ok() == Some(a))
+}
+
+/// A file of the import graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleNode {
+    pub path: PathBuf,
+    /// Entries of the file's `with pkgs; [ ... ]` blocks.
+    pub packages: usize,
+    /// Indices into [`ModuleGraph::nodes`] of the files it imports.
+    pub imports: Vec<usize>,
+}
+
+/// The files reachable from a root file through [`local_imports`], root first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleGraph {
+    pub nodes: Vec<ModuleNode>,
+}
+
+/// Build the import graph of `root`, breadth first. Each file appears once, however many
+/// files import it; cycles become edges back to an earlier node.
+pub fn module_graph(root: &Path) -> Result<ModuleGraph> {
+    let canonical = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let mut index = HashMap::from([(canonical(root), 0)]);
+    let mut nodes = vec![ModuleNode {
+        path: root.to_path_buf(),
+        packages: 0,
+        imports: Vec::new(),
+    }];
+    let mut next = 0;
+    while next < nodes.len() {
+        let path = nodes[next].path.clone();
+        nodes[next].packages = package_lists(&path)?
+            .iter()
+            .map(|list| list.entries.len())
+            .sum();
+        for import in local_imports(&path)? {
+            let len = nodes.len();
+            let i = *index.entry(canonical(&import)).or_insert(len);
+            if i == len {
+                nodes.push(ModuleNode {
+                    path: import,
+                    packages: 0,
+                    imports: Vec::new(),
+                });
+            }
+            nodes[next].imports.push(i);
+        }
+        next += 1;
+    }
+    Ok(ModuleGraph { nodes })
+}
+
+/// `root` and every file reachable from it through [`local_imports`], breadth first.
+/// Each file appears once, however many files import it.
+pub fn module_tree(root: &Path) -> Result<Vec<PathBuf>> {
+    Ok(module_graph(root)?
+        .nodes
+        .into_iter()
+        .map(|node| node.path)
+        .collect())
+}
+
+impl ModuleGraph {
+    fn label(&self, i: usize, base: &Path) -> String {
+        let path = &self.nodes[i].path;
+        path.strip_prefix(base)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// The graph as a tree from the root, each file labelled with its path relative to
+    /// `base`, its package count and, for `target`, `target_note`. A file imported again
+    /// is shown without its imports.
+    pub fn tree(&self, base: &Path, target: &Path, target_note: &str) -> TreeNode {
+        let mut expanded = HashSet::new();
+        self.subtree(0, base, target, target_note, &mut expanded)
+    }
+
+    fn subtree(
+        &self,
+        i: usize,
+        base: &Path,
+        target: &Path,
+        target_note: &str,
+        expanded: &mut HashSet<usize>,
+    ) -> TreeNode {
+        let node = &self.nodes[i];
+        let mut label = format!("{} ({})", self.label(i, base), node.packages);
+        if same_file(&node.path, target) {
+            label = format!("{} {}", label, target_note);
+        }
+        if !expanded.insert(i) {
+            return TreeNode::leaf(if node.imports.is_empty() {
+                label
+            } else {
+                format!("{} ...", label)
+            });
+        }
+        let children = node
+            .imports
+            .iter()
+            .map(|&child| self.subtree(child, base, target, target_note, expanded))
+            .collect();
+        TreeNode::new(label, children)
+    }
+
+    /// Graphviz DOT, with `target` drawn bold.
+    pub fn to_dot(&self, base: &Path, target: &Path) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let quote = |s: &str| format!("\"{}\"", escape(s));
+        let mut out = vec![
+            "digraph modules {".to_string(),
+            "  node [shape=box];".to_string(),
+        ];
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = self.label(i, base);
+            let style = if same_file(&node.path, target) {
+                ", style=bold"
+            } else {
+                ""
+            };
+            out.push(format!(
+                "  {} [label=\"{}\\n{} packages\"{}];",
+                quote(&label),
+                escape(&label),
+                node.packages,
+                style
+            ));
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            for &child in &node.imports {
+                out.push(format!(
+                    "  {} -> {};",
+                    quote(&self.label(i, base)),
+                    quote(&self.label(child, base))
+                ));
+            }
+        }
+        out.push("}".to_string());
+        out.join("\n")
+    }
+
+    /// A Mermaid flowchart, with `target` drawn with a thick border.
+    pub fn to_mermaid(&self, base: &Path, target: &Path) -> String {
+        let mut out = vec!["graph TD".to_string()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = self.label(i, base).replace('"', "#quot;");
+            out.push(format!("  m{}[\"{} ({})\"]", i, label, node.packages));
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            out.extend(
+                node.imports
+                    .iter()
+                    .map(|child| format!("  m{} --> m{}", i, child)),
+            );
+        }
+        if let Some(i) = self.nodes.iter().position(|n| same_file(&n.path, target)) {
+            out.push(format!("  style m{} stroke-width:3px", i));
+        }
+        out.join("\n")
+    }
+}
+
+/// The files under `root` that declair could add packages to: those with a
+/// `with pkgs; [ ... ]` block or, when none has one, those with a package list it could
+/// adopt. `root` itself is not included.
+pub fn package_targets(root: &Path) -> Result<Vec<PathBuf>> {
+    let modules = module_tree(root)?.split_off(1);
+    let mut with_block = Vec::new();
+    let mut with_candidates = Vec::new();
+    for module in modules {
+        if !package_lists(&module)?.is_empty() {
+            with_block.push(module);
+        } else if !find_candidate_lists(&module)?.is_empty() {
+            with_candidates.push(module);
+        }
+    }
+    Ok(if with_block.is_empty() {
+        with_candidates
+    } else {
+        with_block
+    })
+}
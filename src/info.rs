@@ -0,0 +1,188 @@
+//! Package metadata beyond what `nix search` reports, for `declair info` and the
+//! selector's details pane.
+
+use crate::audit::Nixpkgs;
+use crate::error::{DeclairError, Result};
+use crate::evalcache::EvalCache;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec, eval_output};
+use crate::search::PackageInfo;
+use crate::selector::wrap;
+use crate::syntax::nix_string;
+use crate::t;
+use crate::theme::theme;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Evaluating one attribute of nixpkgs; may have to fetch the flake first.
+const EVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageDetails {
+    /// Attribute path in nixpkgs, e.g. `python3Packages.requests`.
+    pub attr: String,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub long_description: Option<String>,
+    pub homepage: Vec<String>,
+    /// SPDX identifiers where nixpkgs has them, other license names otherwise.
+    pub license: Vec<String>,
+    /// Systems named in `meta.platforms`.
+    pub platforms: Vec<String>,
+    pub broken: bool,
+    /// Whether nixpkgs lists known vulnerabilities.
+    pub insecure: bool,
+}
+
+impl PackageDetails {
+    /// What a search result already says about the package.
+    pub fn from_info(info: &PackageInfo) -> Self {
+        PackageDetails {
+            attr: info.name().to_string(),
+            version: Some(info.version.clone()).filter(|v| !v.is_empty()),
+            description: info.description.clone(),
+            long_description: None,
+            homepage: info.homepage.clone(),
+            license: info.license.iter().map(|l| l.name().to_string()).collect(),
+            platforms: info.platforms.clone(),
+            broken: info.broken,
+            insecure: info.insecure,
+        }
+    }
+
+    /// Parse the JSON printed by the expression [`package_details`] evaluates;
+    /// `None` when the attribute doesn't exist.
+    pub fn parse(attr: &str, json: &[u8]) -> Result<Option<Self>> {
+        let value: Value = serde_json::from_slice(json)
+            .map_err(|e| DeclairError::Eval(t!("eval.unexpected_output", e)))?;
+        if value.is_null() {
+            return Ok(None);
+        }
+        let text = |key: &str| value[key].as_str().map(str::to_string);
+        Ok(Some(PackageDetails {
+            attr: attr.to_string(),
+            version: text("version"),
+            description: text("description"),
+            long_description: text("longDescription"),
+            homepage: strings(&value["homepage"]),
+            license: licenses(&value["license"]),
+            platforms: strings(&value["platforms"]),
+            broken: value["broken"].as_bool().unwrap_or(false),
+            insecure: value["insecure"].as_bool().unwrap_or(false),
+        }))
+    }
+
+    /// Human-readable summary with descriptions wrapped to `width` columns.
+    pub fn render(&self, width: usize) -> String {
+        let mut lines = vec![match &self.version {
+            Some(v) => format!("{} {}", theme().package(&self.attr), theme().version(v)),
+            None => theme().package(&self.attr).to_string(),
+        }];
+        for text in [&self.description, &self.long_description]
+            .into_iter()
+            .flatten()
+        {
+            lines.push(String::new());
+            lines.extend(wrap(text, width));
+        }
+        if !self.homepage.is_empty()
+            || !self.license.is_empty()
+            || !self.platforms.is_empty()
+            || self.broken
+            || self.insecure
+        {
+            lines.push(String::new());
+        }
+        if !self.homepage.is_empty() {
+            lines.push(t!("info.homepage", self.homepage.join(" ")));
+        }
+        if !self.license.is_empty() {
+            lines.push(t!("info.license", self.license.join(", ")));
+        }
+        if !self.platforms.is_empty() {
+            let platforms = t!("info.platforms", self.platforms.join(" "));
+            lines.extend(wrap(&platforms, width));
+        }
+        for (flag, key) in [
+            (self.broken, "info.broken"),
+            (self.insecure, "info.insecure"),
+        ] {
+            if flag {
+                lines.push(theme().warning(t!(key)).to_string());
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// A string, or a list of strings.
+pub(crate) fn strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(items) => items.iter().flat_map(strings).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `meta.license`: a license attrset, a list of them, or a bare string.
+fn licenses(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(items) => items.iter().flat_map(licenses).collect(),
+        Value::Object(license) => ["spdxId", "shortName", "fullName"]
+            .iter()
+            .find_map(|key| license.get(*key).and_then(Value::as_str))
+            .map(|name| vec![name.to_string()])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Look up `attr` in `nixpkgs` with `nix eval`, or in `cache` if it was looked up before.
+pub fn package_details(
+    runner: &dyn CommandRunner,
+    nixpkgs: &Nixpkgs,
+    cache: Option<&EvalCache>,
+    attr: &str,
+) -> Result<PackageDetails> {
+    let json = match cache {
+        Some(cache) => cache.eval("details", attr, || evaluate_details(runner, nixpkgs, attr))?,
+        None => evaluate_details(runner, nixpkgs, attr)?,
+    };
+    PackageDetails::parse(attr, &json)?
+        .ok_or_else(|| DeclairError::SearchFailed(t!("info.no_package", attr)))
+}
+
+/// The JSON [`PackageDetails::parse`] reads, from `nix eval`.
+fn evaluate_details(runner: &dyn CommandRunner, nixpkgs: &Nixpkgs, attr: &str) -> Result<Vec<u8>> {
+    let expr = format!(
+        "let pkgs = {}; \
+         p = pkgs.lib.attrByPath (pkgs.lib.splitString \".\" {}) null pkgs; \
+         in if p == null then null else {{ \
+         version = p.version or null; \
+         description = p.meta.description or null; \
+         longDescription = p.meta.longDescription or null; \
+         homepage = p.meta.homepage or null; \
+         license = p.meta.license or null; \
+         platforms = builtins.filter builtins.isString (p.meta.platforms or [ ]); \
+         broken = p.meta.broken or false; \
+         insecure = (p.meta.knownVulnerabilities or [ ]) != [ ]; }}",
+        nixpkgs.expr(),
+        nix_string(attr)
+    );
+    let cmd = CommandSpec::new(
+        "nix",
+        [
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+            &expr,
+            "--extra-experimental-features",
+            "nix-command flakes",
+        ],
+    )
+    .timeout(EVAL_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query();
+    eval_output(runner, &cmd)
+}
@@ -0,0 +1,69 @@
+//! Library side of declair: config handling, Nix file editing, package search and rebuilds.
+
+pub mod aliases;
+pub mod atomic;
+pub mod audit;
+pub mod backups;
+pub mod channel;
+pub mod cli;
+pub mod closures;
+pub mod commit;
+pub mod completion;
+pub mod config;
+pub mod conflicts;
+pub mod daemon;
+pub mod depends;
+pub mod drift;
+pub mod edit;
+pub mod error;
+pub mod evalcache;
+pub mod events;
+pub mod examples;
+pub mod explain;
+pub mod fix;
+pub mod flake;
+pub mod format;
+pub mod gitignore;
+pub mod groups;
+pub mod hooks;
+pub mod host;
+pub mod i18n;
+pub mod imports;
+pub mod info;
+pub mod lock;
+pub mod manifest;
+pub mod module;
+pub mod notices;
+pub mod nur;
+pub mod options;
+pub mod output;
+pub mod overlay;
+pub mod pager;
+pub mod paths;
+pub mod platform;
+pub mod plugin;
+pub mod policy;
+pub mod pool;
+pub mod privileged;
+pub mod progress;
+pub mod project;
+pub mod provides;
+pub mod queue;
+pub mod rebuild;
+pub mod references;
+pub mod review;
+pub mod runner;
+pub mod scaffold;
+pub mod schedule;
+pub mod search;
+pub mod selector;
+pub mod selfupdate;
+pub mod services;
+pub mod state;
+pub mod status;
+pub mod store;
+pub mod syntax;
+pub mod theme;
+pub mod timings;
+pub mod variables;
+pub mod watch;
@@ -0,0 +1,72 @@
+use crate::error::{DeclairError, Result};
+use std::fs::{self, File, TryLockError};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Fingerprint of a file's contents, used to detect edits made behind declair's back
+/// (another declair instance, an editor autosave) between reading and writing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSnapshot {
+    path: PathBuf,
+    hash: u64,
+}
+
+impl FileSnapshot {
+    /// Read `path` and remember its current contents.
+    pub fn capture(path: &Path) -> Result<Self> {
+        let contents = fs::read(path).map_err(|e| DeclairError::Read {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        Ok(Self::of_contents(path, &contents))
+    }
+
+    /// Snapshot of `path` as if it contained `contents`.
+    pub fn of_contents(path: &Path, contents: &[u8]) -> Self {
+        FileSnapshot {
+            path: path.to_path_buf(),
+            hash: hash_bytes(contents),
+        }
+    }
+
+    /// Fail with [`DeclairError::ConcurrentModification`] if the file changed since the snapshot.
+    pub fn verify(&self) -> Result<()> {
+        let current = Self::capture(&self.path)?;
+        if current.hash != self.hash {
+            return Err(DeclairError::ConcurrentModification {
+                file: self.path.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Exclusive advisory lock on a file, released when dropped.
+/// Keeps two declair instances from editing the same file at once.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Lock `path`, failing immediately with [`DeclairError::FileLocked`] if someone else holds it.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| DeclairError::Read {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        match file.try_lock() {
+            Ok(()) => Ok(FileLock { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(DeclairError::FileLocked {
+                file: path.to_path_buf(),
+            }),
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
@@ -1,728 +1,4023 @@
-use clap::Parser;
-use dialoguer::{Completion, Confirm, Input, Select};
-use directories::ProjectDirs;
-use gix::discover;
-use serde::{Deserialize, Serialize};
-use serde_json::from_slice;
-use std::collections::HashMap;
+use clap::FromArgMatches;
+use clap_complete::{Shell, generate};
+use clap_complete_nushell::Nushell;
+use console::Term;
+use declair_rs::aliases::{Alias, Aliases, nixpkgs_dir};
+use declair_rs::atomic::can_replace;
+use declair_rs::audit::{Nixpkgs, advisories_on_add, audit_packages};
+use declair_rs::backups::{
+    BackupSettings, find_backup, list_backups, prune_backups, set_retention,
+};
+use declair_rs::channel::{add_channel, channel_update_command, list_channels, update_channels};
+use declair_rs::cli::{
+    self, Args, BackupsAction, ChannelAction, Commands, CompletionShell, EnvAction, GraphStyle,
+    GroupAction, OptionsAction, OverlayAction, ScheduleAction, SelfAction, ServiceAction,
+    StatusArgs,
+};
+use declair_rs::commit::commit_files;
+use declair_rs::config::{
+    Config, config_file_path, prompt_nix_path, read_config, read_or_create_config, save_config,
+    system_config_path,
+};
+use declair_rs::conflicts::{conflict_error, nix_files, package_conflicts};
+use declair_rs::daemon::{Daemon, call_daemon, default_socket_path};
+use declair_rs::depends::{
+    profile_packages, profile_root, reverse_dependencies, store_name, why_depends,
+};
+use declair_rs::drift::package_name;
+use declair_rs::edit::{
+    MissingBlock, add_flake_input, add_import, add_package_resolving_block, add_package_to_segment,
+    add_program_to_nix, call_package_entry, find_candidate_lists, installed_packages,
+    list_managed_packages, list_packages, list_segments, package_lists, package_scopes,
+    remove_flake_input, remove_program_from_nix, restore_backup,
+};
+use declair_rs::error::{DeclairError, Result};
+use declair_rs::evalcache::{self, cache_root};
+use declair_rs::events::{Event, LogFormat, emit, event_sink, set_event_sink};
+use declair_rs::examples::{WORKFLOWS, man_page, render_workflows, write_man_pages};
+use declair_rs::explain::{Topic, explain, set_explain};
+use declair_rs::fix::{history_command, missing_command};
+use declair_rs::flake::{find_flake_dir, flake_file};
+use declair_rs::format::{Formatter, format_file};
+use declair_rs::gitignore::{append_patterns, unignored_artifacts};
+use declair_rs::groups::{fetch_groups_source, parse_shared_groups};
+use declair_rs::hooks::{HookContext, Phase, run_phase};
+use declair_rs::host::{
+    HostOs, Privilege, is_nixos, is_nixos_distribution, is_root, require_unix, wsl_distributions,
+};
+use declair_rs::i18n::{Language, set_language};
+use declair_rs::imports::{module_graph, module_tree, package_targets};
+use declair_rs::info::{PackageDetails, package_details};
+use declair_rs::lock::FileSnapshot;
+use declair_rs::manifest::{SetDiff, SyncConflict, SyncPlan, read_manifest};
+use declair_rs::module::{ModuleKind, Scope, edit_scope, is_font, module_kind};
+use declair_rs::nur::{NUR_FLAKE_URL, flake_has_nur, index_cache_path, is_nur_package};
+use declair_rs::options::{
+    OptionInfo, OptionTree, check_value, choices, find_option, list_options, option_value,
+    set_option, validate_option_name,
+};
+use declair_rs::output::{OutputFormat, TreeNode, render, render_tree};
+use declair_rs::overlay::{
+    add_overlay, list_overlays, overlay_expr, remove_overlay, validate_overlay,
+};
+use declair_rs::pager::{page, set_pager};
+use declair_rs::paths::{
+    contract_home, expand_path, get_backups_dir, get_git_repo_or_parent_directory, get_history_dir,
+    get_logs_dir, resolve_nix_config,
+};
+use declair_rs::platform::{PlatformSupport, platform_support, target_system};
+use declair_rs::plugin::{discover, find_plugin};
+use declair_rs::policy::{License, check_licenses, check_policy, package_licenses};
+use declair_rs::pool::{default_jobs, map_bounded};
+use declair_rs::privileged::{RootRisk, ScratchCopy, root_risks};
+use declair_rs::progress::with_spinner;
+use declair_rs::project::{find_project_config, load_project_config};
+use declair_rs::provides::providers;
+use declair_rs::queue::{Queued, RebuildQueue};
+use declair_rs::rebuild::{
+    FailureCause, RebuildMode, RebuildTarget, check_target, diagnose_failure, rebuild_command_as,
+    rebuild_target, rebuild_with_hooks, trace_command, update_command, update_inputs,
+};
+use declair_rs::references::{find_references, references_elsewhere};
+use declair_rs::review::{ChangeSet, FileChange};
+use declair_rs::runner::{CommandRunner, CommandSpec, SystemRunner, install_interrupt_handler};
+use declair_rs::scaffold::{
+    PACKAGES_MODULE, Template, import_path, new_package_path, write_derivation,
+    write_packages_module,
+};
+use declair_rs::schedule::{self, TIMER_NAME, user_unit_dir};
+use declair_rs::search::{
+    FLAKE_SEARCH_VERSION, PackageInfo, Query, SearchMode, experimental_features, mark_installed,
+    nix_version, nixos_options_manual, search_configured,
+};
+use declair_rs::selector::{
+    MAX_RESULTS, best_results, group_by_set, option_lines, page_size, wrap,
+};
+use declair_rs::selfupdate::{
+    InstallMethod, cargo_home, detect, self_update_command, user_profiles,
+};
+use declair_rs::services::{
+    disable_service, enable_option, enable_service, list_services, service_exists, validate_service,
+};
+use declair_rs::state::{
+    RebuildRecord, State, Transaction, format_time, recent_configs, remember_config,
+    state_file_path, unix_now, update_state,
+};
+use declair_rs::status::{GenerationStatus, current_generation};
+use declair_rs::store::{nix_run_command, store_paths};
+use declair_rs::t;
+use declair_rs::theme::{self, prompt_theme, theme};
+use declair_rs::timings::{self, run_timings};
+use declair_rs::variables::{list_variables, set_variable, unset_variable, validate_variable};
+use declair_rs::watch::{PackageDiff, watch_files};
+use dialoguer::{Confirm, Input, MultiSelect, Select};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::env;
-use std::env::home_dir;
-use std::error::Error;
+use std::ffi::OsStr;
 use std::fs;
-use std::io::{BufRead, BufReader};
+use std::io;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use std::process::exit;
+use std::time::{Duration, Instant};
 
-/// A command-line tool to search, add, and manage NixOS or Home Manager packages with optional automatic rebuilds.
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// Set config file (path to your NixOS configuration file or directory)
-    #[arg(short = 'c', long = "config", value_name = "FILE")]
-    config: Option<PathBuf>,
-
-    /// Package name to add (used as search query in interactive mode or as the
-    /// literal package name in --no-interactive mode)
-    #[arg(short = 'p', long = "package", value_name = "PACKAGE")]
-    package: Option<String>,
-
-    /// Use fzf for package selection (Needs fzf installed)
-    #[arg(short = 'f', long = "fzf")]
-    fzf: bool,
-
-    /// Do not prompt interactively; fail if necessary information is missing
-    #[arg(long = "no-interactive")]
-    no_interactive: bool,
-
-    /// Don't perform rebuild even if config requests it
-    #[arg(long = "no-rebuild")]
-    no_rebuild: bool,
-
-    /// Perform a dry-run (Only print selected package)
-    #[arg(short = 'd', long = "dry-run")]
-    dry_run: bool,
-
-    /// Use `program.{package}.enable` = true instead of adding pkg to `with pkgs; [...]` (if available)
-    #[arg(long = "program")]
-    program: bool,
-
-    /// Remove package from NixOS config
-    #[arg(short = 'r', long = "remove")]
-    remove: bool,
-    /// List currently configured packages
-    #[arg(short = 'l', long = "list")]
-    list: bool,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Config {
-    nix_path: String,
-    auto_rebuild: bool,
-    home_manager: bool,
-    flake: bool,
-}
-
-#[derive(Default)]
-struct FileCompletion;
-
-impl Completion for FileCompletion {
-    fn get(&self, input: &str) -> Option<String> {
-        // 1) First, expand possible tilde at the beginning.
-        let expanded = match expand_tilde(input) {
-            Ok(p) => p,
-            Err(_) => return None, // if error expanding – no suggestion
+fn main() {
+    let args = match Args::from_arg_matches(&cli::command().get_matches()) {
+        Ok(args) => args,
+        Err(e) => e.exit(),
+    };
+    if let Err(e) = install_interrupt_handler() {
+        eprintln!("{}", t!("warning.ctrl_c_handler", e));
+    }
+
+    let print_timings = args.timings;
+    let result = run(args);
+    if print_timings {
+        eprintln!("{}", run_timings().to_json());
+    }
+
+    // top-level error handling
+    if let Err(e) = result {
+        // A prompt interrupted by Ctrl-C leaves the cursor hidden.
+        let _ = Term::stderr().show_cursor();
+        if e.is_interrupted() {
+            eprintln!("{}", t!("interrupted"));
+        } else {
+            eprintln!("{}", theme().error(t!("error", e)).for_stderr());
+        }
+        exit(e.exit_code());
+    }
+}
+
+/// The file declair edits: the configured one, except that a `flake.nix` without a
+/// package list of its own stands for the modules it imports. One module with a package
+/// list is used directly; between several, the user picks one and may keep it as the
+/// configured file. Without prompts, several candidates are an error.
+fn target_nix_file(config: &Config, no_interactive: bool) -> Result<PathBuf> {
+    let nix_file = resolve_nix_config(&expand_path(config.nix_path.trim())?)?;
+    if nix_file.file_name() != Some(OsStr::new("flake.nix"))
+        || !package_lists(&nix_file)?.is_empty()
+    {
+        return Ok(nix_file);
+    }
+    let targets = package_targets(&nix_file)?;
+    let chosen = match targets.as_slice() {
+        [] => return Ok(nix_file),
+        [only] => {
+            println!(
+                "{}",
+                t!("flake.using_module", only.display(), nix_file.display())
+            );
+            return Ok(only.clone());
+        }
+        _ if no_interactive => {
+            let list: Vec<String> = targets.iter().map(|t| t.display().to_string()).collect();
+            return Err(DeclairError::Config(t!(
+                "flake.ambiguous_modules",
+                nix_file.display(),
+                list.join(", ")
+            )));
+        }
+        _ => {
+            let items: Vec<String> = targets.iter().map(|t| t.display().to_string()).collect();
+            let selection = Select::with_theme(prompt_theme())
+                .with_prompt(t!("flake.module_prompt", nix_file.display()))
+                .items(&items)
+                .default(0)
+                .interact()?;
+            targets[selection].clone()
+        }
+    };
+    // Only offer to remember the choice for the stored config, not a `--config` override.
+    let mut stored = read_or_create_config(true)?;
+    if stored.nix_path == config.nix_path
+        && Confirm::with_theme(prompt_theme())
+            .with_prompt(t!("flake.remember_prompt", chosen.display()))
+            .default(true)
+            .interact()?
+    {
+        stored.nix_path = contract_home(&chosen);
+        save_config(&stored)?;
+    }
+    Ok(chosen)
+}
+
+/// Ask how to proceed when the config file has no `with pkgs; [...]` block:
+/// adopt one of the lists the parser found, create a new block, or give up.
+fn prompt_missing_block(nix_file: &Path, scope: &Scope) -> Result<MissingBlock> {
+    let candidates = find_candidate_lists(nix_file)?;
+    let attr = scope.packages_attr();
+
+    println!("{}", t!("block.missing", nix_file.display()));
+    let mut items: Vec<String> = candidates
+        .iter()
+        .map(|c| t!("block.use_line", c.line + 1, c.text))
+        .collect();
+    items.push(t!("block.create", attr));
+    items.push(t!("block.abort"));
+
+    let selection = Select::with_theme(prompt_theme())
+        .with_prompt(t!("block.prompt"))
+        .items(&items)
+        .default(0)
+        .interact()?;
+    if let Some(candidate) = candidates.get(selection) {
+        Ok(MissingBlock::Adopt(candidate.clone()))
+    } else if selection == candidates.len() {
+        Ok(MissingBlock::Create(attr))
+    } else {
+        Err(DeclairError::BlockNotFound {
+            file: nix_file.to_path_buf(),
+        })
+    }
+}
+
+/// Which list of the package block in `nix_file` new packages go to, as an index into
+/// [`list_segments`]: asked when the block has conditional segments such as
+/// `++ lib.optionals stdenv.isLinux [ ... ]`, otherwise the `with pkgs; [` list itself.
+/// An editor plugin always gets the list itself.
+fn prompt_segment(
+    config: &Config,
+    args: &Args,
+    nix_file: &Path,
+    scope: &Scope,
+    packages: &[String],
+) -> Result<usize> {
+    if args.no_interactive || config.plugins.editor.is_some() || packages.is_empty() {
+        return Ok(0);
+    }
+    let Ok(segments) = list_segments(nix_file, scope) else {
+        return Ok(0);
+    };
+    if segments.len() < 2 {
+        return Ok(0);
+    }
+    let items: Vec<String> = segments
+        .iter()
+        .map(|segment| match &segment.condition {
+            Some(condition) => t!("segment.conditional", condition),
+            None => t!("segment.main"),
+        })
+        .collect();
+    Ok(Select::with_theme(prompt_theme())
+        .with_prompt(t!("segment.prompt", packages.join(", ")))
+        .items(&items)
+        .default(0)
+        .interact()?)
+}
+
+/// Add `pkg` to the `segment`th list of the package block (see [`prompt_segment`]).
+fn add_to_segment(
+    config: &Config,
+    runner: &dyn CommandRunner,
+    file: &Path,
+    pkg: &str,
+    scope: &Scope,
+    segment: usize,
+) -> Result<()> {
+    match segment {
+        0 => match config.plugins.add_package(runner, file, pkg, scope) {
+            // The fonts list is created along with the first font.
+            Err(DeclairError::BlockNotFound { .. })
+                if *scope == Scope::Fonts && config.plugins.editor.is_none() =>
+            {
+                let create = MissingBlock::Create(scope.packages_attr());
+                add_package_resolving_block(file, pkg, scope, &create)
+            }
+            result => result,
+        },
+        n => add_package_to_segment(file, pkg, scope, n),
+    }
+}
+
+/// The scope `pkg` is edited in: `scope`, except that without `--scope` a font in a NixOS
+/// module goes to `fonts.packages`, unless `file` lists it among the system packages.
+fn package_scope(args: &Args, scope: &Scope, file: &Path, pkg: &str) -> Scope {
+    if args.scope.is_some() || *scope != Scope::System {
+        return scope.clone();
+    }
+    let listed =
+        |scope: &Scope| list_packages(file, scope).is_ok_and(|p| p.iter().any(|p| p == pkg));
+    if listed(&Scope::Fonts) || (is_font(pkg) && !listed(scope)) {
+        Scope::Fonts
+    } else {
+        scope.clone()
+    }
+}
+
+/// `declair init-config-file`: scaffold a packages module that declair fully owns,
+/// wire it into the current config's imports and make it declair's target.
+fn init_config_file(
+    mut config: Config,
+    args: &Args,
+    path: Option<&Path>,
+    insert_import: bool,
+) -> Result<()> {
+    let current = resolve_nix_config(&expand_path(config.nix_path.trim())?).ok();
+    let module = match (path, &current) {
+        (Some(p), _) => expand_path(&p.to_string_lossy())?,
+        (None, Some(cur)) => cur.parent().unwrap_or(Path::new(".")).join(PACKAGES_MODULE),
+        (None, None) => PathBuf::from(PACKAGES_MODULE),
+    };
+
+    write_packages_module(&module, config.home_manager)?;
+    println!("{}", t!("init.created", module.display()));
+
+    if let Some(current) = &current {
+        let import = import_path(current, &module);
+        let insert = insert_import
+            || (!args.no_interactive
+                && Confirm::with_theme(prompt_theme())
+                    .with_prompt(t!("init.add_import_prompt", import, current.display()))
+                    .default(true)
+                    .interact()?);
+        if insert {
+            add_import(current, &import)?;
+            let runner = SystemRunner {
+                print_only: args.print_commands,
+                ..Default::default()
+            };
+            if let Err(e) = format_file(&runner, config.formatter, current) {
+                eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+            }
+            println!("{}", t!("init.added_import", import, current.display()));
+        } else {
+            println!("{}", t!("init.add_line", current.display(), import));
+        }
+    } else {
+        println!("{}", t!("init.import_it", module.display()));
+    }
+
+    let module = std::path::absolute(&module).unwrap_or(module);
+    config.nix_path = contract_home(&module);
+    save_config(&config)?;
+    println!("{}", t!("init.manages", module.display()));
+    Ok(())
+}
+
+/// [`PackageDiff::summary`] with additions and removals in the theme's diff colours.
+fn styled_summary(diff: &PackageDiff) -> String {
+    diff.added
+        .iter()
+        .map(|p| theme().added(format!("+{}", p)).to_string())
+        .chain(
+            diff.removed
+                .iter()
+                .map(|p| theme().removed(format!("-{}", p)).to_string()),
+        )
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A file watched by `declair watch`, with what it looked like at the last change.
+struct WatchedFile {
+    contents: String,
+    packages: Vec<String>,
+    target: RebuildTarget,
+    dir: PathBuf,
+}
+
+impl WatchedFile {
+    fn read(config: &Config, file: &PathBuf) -> Result<Self> {
+        let contents = fs::read_to_string(file).map_err(|e| DeclairError::Read {
+            path: file.clone(),
+            source: e,
+        })?;
+        let dir = get_git_repo_or_parent_directory(file)?;
+        let target = rebuild_target(config, file, &dir)?;
+        // A file without a package list can still change; it just has no packages to diff.
+        let packages = list_packages(file, target.kind).unwrap_or_default();
+        Ok(WatchedFile {
+            contents,
+            packages,
+            target,
+            dir,
+        })
+    }
+}
+
+/// `declair watch`: rebuild when the managed files change, e.g. after editing them by hand,
+/// and show which packages the edit added or removed.
+fn watch(config: &Config, runner: &SystemRunner, debounce: Duration, confirm: bool) -> Result<()> {
+    let mut files = vec![resolve_nix_config(&expand_path(config.nix_path.trim())?)?];
+    for path in config.modules.keys() {
+        let path = expand_path(path)?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    let mut files: Vec<PathBuf> = files
+        .into_iter()
+        .map(|f| fs::canonicalize(&f).unwrap_or(f))
+        .collect();
+    files.sort();
+    files.dedup();
+
+    let mut known = HashMap::new();
+    for file in &files {
+        known.insert(file.clone(), WatchedFile::read(config, file)?);
+    }
+
+    for file in &files {
+        println!("{}", t!("watch.watching", file.display()));
+    }
+    println!("{}", t!("watch.stop"));
+
+    watch_files(&files, debounce, |changed| {
+        // What to rebuild, where, and the first changed file that asked for it.
+        let mut targets: Vec<(RebuildTarget, PathBuf, PathBuf)> = Vec::new();
+        let mut packages = Vec::new();
+        for file in changed {
+            let Ok(current) = WatchedFile::read(config, file) else {
+                // Mid-save or deleted; the next event will tell.
+                continue;
+            };
+            let previous = known.insert(file.clone(), current);
+            let current = &known[file];
+            if previous
+                .as_ref()
+                .is_some_and(|p| p.contents == current.contents)
+            {
+                continue;
+            }
+            let diff = PackageDiff::between(
+                previous.as_ref().map_or(&[][..], |p| &p.packages),
+                &current.packages,
+            );
+            if diff.is_empty() {
+                println!("{}", t!("watch.changed_no_packages", file.display()));
+            } else {
+                println!(
+                    "{}",
+                    t!("watch.changed", file.display(), styled_summary(&diff))
+                );
+            }
+            packages.extend(diff.added.iter().chain(&diff.removed).cloned());
+            if !targets
+                .iter()
+                .any(|(t, dir, _)| *t == current.target && *dir == current.dir)
+            {
+                targets.push((current.target.clone(), current.dir.clone(), file.clone()));
+            }
+        }
+        if targets.is_empty() {
+            return Ok(());
+        }
+        if confirm
+            && !Confirm::with_theme(prompt_theme())
+                .with_prompt(t!("watch.rebuild_prompt"))
+                .default(true)
+                .interact()?
+        {
+            return Ok(());
+        }
+        for (target, dir, file) in &targets {
+            println!("{}", t!("rebuild.rebuilding", target.kind));
+            let ctx = HookContext::new("watch", packages.clone(), file);
+            // Keep watching after a failed rebuild; the next edit may fix it.
+            match rebuild_with_hooks(runner, config, target, dir, &ctx) {
+                Ok(()) => {
+                    if !runner.print_only {
+                        record_rebuild(target);
+                    }
+                    println!("{}", t!("rebuild.finished"))
+                }
+                Err(e) => eprintln!("{}", theme().error(t!("error", e)).for_stderr()),
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Run the `post_edit` hooks. The edit is already written, so a failure only warns.
+/// Commit `files` in `repo` when `[git] commit` is on or `--commit-message` was given. A
+/// failed commit only warns, since the change itself went through.
+fn commit_edit(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    repo: &Path,
+    ctx: &HookContext,
+    files: &[PathBuf],
+) {
+    if args.dry_run || files.is_empty() || !(config.git.commit || args.commit_message.is_some()) {
+        return;
+    }
+    let message = config.git.message(ctx, args.commit_message.as_deref());
+    match commit_files(runner, repo, files, &message) {
+        Ok(()) => println!("{}", t!("git.committed", message)),
+        Err(e) => eprintln!("{}", theme().warning(t!("warning", e)).for_stderr()),
+    }
+}
+
+/// Offer to add the patterns of declair's backups and temporary files to the `.gitignore` of
+/// each repository the backups of `files` were written into. Only asks interactively.
+fn offer_gitignore(args: &Args, files: &[PathBuf]) {
+    if args.no_interactive || args.dry_run {
+        return;
+    }
+    let mut asked = HashSet::new();
+    for (repo, patterns) in files.iter().filter_map(|f| unignored_artifacts(f)) {
+        if !asked.insert(repo.clone()) {
+            continue;
+        }
+        let add = Confirm::with_theme(prompt_theme())
+            .with_prompt(t!("gitignore.prompt", repo.display(), patterns.join(" ")))
+            .default(true)
+            .interact()
+            .unwrap_or(false);
+        if !add {
+            continue;
+        }
+        match append_patterns(&repo, &patterns) {
+            Ok(()) => println!(
+                "{}",
+                t!("gitignore.added", repo.join(".gitignore").display())
+            ),
+            Err(e) => eprintln!("{}", theme().warning(t!("warning", e)).for_stderr()),
+        }
+    }
+}
+
+fn post_edit_hooks(runner: &SystemRunner, config: &Config, ctx: &HookContext) {
+    if let Err(e) = run_phase(runner, &config.hooks, Phase::PostEdit, ctx, None) {
+        eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+    }
+}
+
+/// Whether the invocation may edit the config, as opposed to only reading it or
+/// rebuilding.
+fn edits_config(args: &Args) -> bool {
+    if args.dry_run {
+        return false;
+    }
+    let Some(command) = &args.command else {
+        return !args.list;
+    };
+    matches!(
+        command,
+        Commands::InitConfigFile { .. }
+            | Commands::Daemon { .. }
+            | Commands::Add { .. }
+            | Commands::Replace { .. }
+            | Commands::NewPackage { .. }
+            | Commands::Drift { .. }
+            | Commands::Sync { .. }
+            | Commands::Fix { .. }
+            | Commands::Prune { .. }
+            | Commands::Update { .. }
+            | Commands::Group {
+                action: GroupAction::Add { .. }
+                    | GroupAction::Remove { .. }
+                    | GroupAction::Import { .. },
+            }
+            | Commands::Overlay {
+                action: OverlayAction::Add { .. } | OverlayAction::Remove { .. },
+            }
+            | Commands::Env {
+                action: EnvAction::Set { .. } | EnvAction::Unset { .. },
+            }
+            | Commands::Service {
+                action: ServiceAction::Enable { .. } | ServiceAction::Disable { .. },
+            }
+            | Commands::Options {
+                action: OptionsAction::Set { .. },
+            }
+    )
+}
+
+/// Whether `args` edit the configuration or change the system otherwise (rebuilds,
+/// watching, channels, scheduled updates), which read-only mode refuses.
+fn changes_system(args: &Args) -> bool {
+    edits_config(args)
+        || !args.dry_run
+            && matches!(
+                &args.command,
+                Some(
+                    Commands::Rebuild { .. }
+                        | Commands::Watch { .. }
+                        | Commands::Channel {
+                            action: ChannelAction::Add { .. } | ChannelAction::Update { .. },
+                        }
+                        | Commands::Schedule {
+                            action: ScheduleAction::Install { .. },
+                        }
+                )
+            )
+}
+
+/// Running as root, say what editing the config as root gets wrong and ask before going
+/// on; without prompts only `--allow-root` goes on.
+fn confirm_root_edit(config: &Config, args: &Args) -> Result<()> {
+    if !is_root() || !edits_config(args) {
+        return Ok(());
+    }
+    let nix_file = resolve_nix_config(&expand_path(config.nix_path.trim())?)?;
+    let home_manager = module_kind(config, &nix_file)? == ModuleKind::HomeManager;
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let risks = root_risks(
+        &nix_file,
+        home_manager,
+        &Privilege::current(),
+        home.as_deref(),
+    );
+    for risk in &risks {
+        let warning = match risk {
+            RootRisk::HomeManager => t!("root.home_manager", nix_file.display()),
+            RootRisk::Owner { uid } => t!("root.owner", nix_file.display(), uid),
+            RootRisk::Home {
+                home,
+                user,
+                user_home,
+            } => t!("root.home", home.display(), user_home.display(), user),
+        };
+        eprintln!("{}", theme().warning(warning).for_stderr());
+    }
+    if args.allow_root {
+        return Ok(());
+    }
+    if args.no_interactive {
+        return Err(DeclairError::Config(t!("root.noninteractive")));
+    }
+    let confirmed = Confirm::with_theme(prompt_theme())
+        .with_prompt(t!("root.prompt", nix_file.display()))
+        .default(risks.is_empty())
+        .interact()?;
+    if !confirmed {
+        return Err(DeclairError::Config(t!("root.declined")));
+    }
+    Ok(())
+}
+
+/// Show each pending change as a coloured diff and ask whether to apply it.
+/// With `--no-interactive` everything is accepted.
+fn review_changes(changes: &[FileChange], no_interactive: bool) -> Result<Vec<PathBuf>> {
+    let mut accepted = Vec::new();
+    for change in changes {
+        if no_interactive {
+            accepted.push(change.path.clone());
+            continue;
+        }
+        println!("{}", theme().diff(&change.diff()));
+        if Confirm::with_theme(prompt_theme())
+            .with_prompt(t!("review.apply_prompt", change.path.display()))
+            .default(true)
+            .interact()?
+        {
+            accepted.push(change.path.clone());
+        }
+    }
+    Ok(accepted)
+}
+
+/// Whether a batch operation adds or removes its packages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BatchAction {
+    Add,
+    Remove,
+}
+
+/// `declair add` and `declair group add/remove`: stage all packages, review the resulting
+/// diffs, write the accepted files and rebuild once.
+fn edit_packages(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    packages: &[String],
+    action: BatchAction,
+) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(config, &nix_file, &git_repo)?;
+    let action_name = match action {
+        BatchAction::Add => "add",
+        BatchAction::Remove => "remove",
+    };
+    let scope = edit_scope(args.scope.as_ref(), target.kind)?;
+    if action == BatchAction::Remove {
+        check_pins(args, packages.iter().map(String::as_str))?;
+    }
+    let ctx = HookContext::new(action_name, packages.to_vec(), &nix_file);
+    run_phase(runner, &config.hooks, Phase::PreEdit, &ctx, None)?;
+
+    if action == BatchAction::Add && packages.iter().any(|p| is_nur_package(p)) {
+        offer_nur_setup(config, args, &git_repo, &nix_file)?;
+    }
+
+    let mut resolved = Vec::new();
+    for pkg in packages {
+        match action {
+            BatchAction::Add => match resolve_alias(args, pkg)? {
+                Some(pkg) => resolved.push(pkg),
+                None => println!("{}", t!("batch.skipping", pkg)),
+            },
+            BatchAction::Remove => resolved.push(pkg.clone()),
+        }
+    }
+    let prechecks = match action {
+        BatchAction::Add => timings::time("precheck", || {
+            precheck_packages(config, runner, &git_repo, &nix_file, &resolved)
+        }),
+        BatchAction::Remove => resolved.iter().map(|_| None).collect(),
+    };
+
+    let segment = match action {
+        BatchAction::Add => {
+            let in_scope: Vec<String> = resolved
+                .iter()
+                .filter(|pkg| package_scope(args, &scope, &nix_file, pkg) == scope)
+                .cloned()
+                .collect();
+            prompt_segment(config, args, &nix_file, &scope, &in_scope)?
+        }
+        BatchAction::Remove => 0,
+    };
+
+    let edit_start = Instant::now();
+    let mut changes = ChangeSet::new();
+    let mut applied = Vec::new();
+    for (pkg, precheck) in resolved.iter().zip(prechecks) {
+        if let Some(precheck) = precheck {
+            override_policy(args, precheck.policy)?;
+            if !confirm_support(args, pkg, precheck.platform)? {
+                println!("{}", t!("batch.skipping", pkg));
+                continue;
+            }
+        }
+        if action == BatchAction::Add
+            && !confirm_conflicts(args, runner, &git_repo, &nix_file, pkg, target.kind)?
+        {
+            println!("{}", t!("batch.skipping", pkg));
+            continue;
+        }
+        if action == BatchAction::Add {
+            warn_advisories(config, runner, &git_repo, &nix_file, pkg);
+        }
+        if action == BatchAction::Remove {
+            warn_references(&git_repo, &nix_file, pkg);
+        }
+        let staged = changes.stage(&nix_file)?;
+        let pkg_scope = package_scope(args, &scope, staged, pkg);
+        let result = match action {
+            BatchAction::Add if pkg_scope != scope => {
+                add_to_segment(config, runner, staged, pkg, &pkg_scope, 0)
+            }
+            BatchAction::Add => add_to_segment(config, runner, staged, pkg, &scope, segment),
+            BatchAction::Remove => config
+                .plugins
+                .remove_package(runner, staged, pkg, &pkg_scope),
         };
-        // 2) Work with str representation of expanded path
-        let expanded_str = expanded.to_string_lossy();
-        // 3) Split into "directory" and "prefix" (filename to complete)
-        let (dir_str, prefix) = match expanded_str.rfind('/') {
-            Some(pos) => {
-                let (d, p) = expanded_str.split_at(pos + 1); // d includes the separator
-                (d.to_string(), p.to_string())
-            }
-            None => ("".to_string(), expanded_str.to_string()),
+        match result {
+            Ok(()) => applied.push(pkg.clone()),
+            Err(DeclairError::PackageExists { .. }) => {
+                println!("{}", t!("batch.already_in", pkg, nix_file.display()));
+            }
+            Err(DeclairError::PackageNotFound { .. }) => {
+                println!("{}", t!("batch.not_in", pkg, nix_file.display()));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    timings::record("edit", edit_start.elapsed());
+    if config.formatter != Formatter::None {
+        let staged = changes.stage(&nix_file)?;
+        if let Err(e) = format_file(runner, config.formatter, staged) {
+            eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+        }
+    }
+
+    let pending = changes.changes()?;
+    if pending.is_empty() {
+        println!("{}", t!("batch.nothing"));
+        return Ok(());
+    }
+    let accepted = review_changes(&pending, args.no_interactive)?;
+    let written = changes.commit(runner, |path| accepted.iter().any(|a| a == path))?;
+    for file in &written {
+        println!("{}", t!("batch.updated", file.display()));
+    }
+    if !written.is_empty() {
+        explain(match action {
+            BatchAction::Add => Topic::PackageList,
+            BatchAction::Remove => Topic::Removal,
+        });
+    }
+    let ctx = HookContext::new(action_name, applied.clone(), &nix_file);
+    if !written.is_empty() {
+        post_edit_hooks(runner, config, &ctx);
+        offer_gitignore(args, &written);
+        log_transaction(&ctx, rebuilding(config, args, runner, &written));
+    }
+    if written.contains(&nix_file) {
+        update_install_state(|state| {
+            for pkg in &applied {
+                match action {
+                    BatchAction::Add => state.record_added(pkg, &nix_file, args.reason.clone()),
+                    BatchAction::Remove => state.forget(pkg),
+                }
+            }
+        });
+    }
+
+    if written.is_empty() {
+        println!("{}", t!("batch.none_applied"));
+    } else if config.auto_rebuild && !args.no_rebuild {
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        rebuild_edited(config, args, runner, &target, &git_repo, &ctx, &written)?;
+    }
+    commit_edit(config, args, runner, &git_repo, &ctx, &written);
+    Ok(())
+}
+
+/// `declair replace`: swap `old` for `new` in the package list as one edit, review the
+/// diff and rebuild once.
+fn replace(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    old: &str,
+    new: &str,
+) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(config, &nix_file, &git_repo)?;
+    let scope = edit_scope(args.scope.as_ref(), target.kind)?;
+    check_pins(args, [old])?;
+    let Some(new) = resolve_alias(args, new)? else {
+        return Ok(());
+    };
+    let new = new.as_str();
+    let ctx = HookContext::new("replace", vec![old.to_string(), new.to_string()], &nix_file);
+    run_phase(runner, &config.hooks, Phase::PreEdit, &ctx, None)?;
+
+    if is_nur_package(new) {
+        offer_nur_setup(config, args, &git_repo, &nix_file)?;
+    }
+    enforce_policy(config, args, runner, &git_repo, &nix_file, new, None)?;
+    if !confirm_platform(config, args, runner, &git_repo, &nix_file, new)? {
+        return Ok(());
+    }
+    if !confirm_conflicts(args, runner, &git_repo, &nix_file, new, target.kind)? {
+        return Ok(());
+    }
+    warn_advisories(config, runner, &git_repo, &nix_file, new);
+    warn_references(&git_repo, &nix_file, old);
+
+    let mut changes = ChangeSet::new();
+    let staged = changes.stage(&nix_file)?;
+    timings::time("edit", || {
+        config
+            .plugins
+            .replace_package(runner, staged, old, new, &scope)
+    })?;
+    if config.formatter != Formatter::None {
+        let staged = changes.stage(&nix_file)?;
+        if let Err(e) = format_file(runner, config.formatter, staged) {
+            eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+        }
+    }
+
+    let pending = changes.changes()?;
+    let accepted = review_changes(&pending, args.no_interactive)?;
+    let written = changes.commit(runner, |path| accepted.iter().any(|a| a == path))?;
+    if !written.contains(&nix_file) {
+        println!("{}", t!("batch.none_applied"));
+        return Ok(());
+    }
+    println!("{}", t!("replace.done", old, new, nix_file.display()));
+    post_edit_hooks(runner, config, &ctx);
+    offer_gitignore(args, &written);
+    log_transaction(&ctx, rebuilding(config, args, runner, &written));
+    update_install_state(|state| {
+        state.forget(old);
+        state.record_added(new, &nix_file, args.reason.clone());
+    });
+
+    if config.auto_rebuild && !args.no_rebuild {
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        rebuild_edited(config, args, runner, &target, &git_repo, &ctx, &written)?;
+    }
+    commit_edit(config, args, runner, &git_repo, &ctx, &written);
+    Ok(())
+}
+
+/// `declair --local FILE`: add `(callPackage FILE { })` to the package list, with FILE
+/// relative to the edited file. A missing FILE can be created from a template, in which
+/// case there is nothing to build yet and the rebuild is left for later.
+fn add_local(config: &Config, args: &Args, runner: &SystemRunner, local: &str) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let derivation = expand_path(local)?;
+    let derivation = std::path::absolute(&derivation).unwrap_or(derivation);
+    add_derivation(config, args, runner, &nix_file, &git_repo, derivation, None)
+}
+
+/// `declair new-package NAME`: write a `template` derivation to `pkgs/NAME/default.nix`
+/// in the config repository and add it like `--local` does.
+fn new_package(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    name: &str,
+    template: Template,
+) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let derivation = new_package_path(&git_repo, name);
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(DeclairError::InvalidPath {
+            path: derivation,
+            reason: t!("new_package.bad_name", name),
+        });
+    }
+    if derivation.exists() {
+        return Err(DeclairError::InvalidPath {
+            path: derivation,
+            reason: t!("new_package.exists"),
+        });
+    }
+    add_derivation(
+        config,
+        args,
+        runner,
+        &nix_file,
+        &git_repo,
+        derivation,
+        Some(template),
+    )
+}
+
+/// Add `(callPackage DERIVATION { })` to `nix_file`. With a `template` the derivation is
+/// written first; without one, a missing derivation can be scaffolded after asking.
+fn add_derivation(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    nix_file: &Path,
+    git_repo: &Path,
+    derivation: PathBuf,
+    template: Option<Template>,
+) -> Result<()> {
+    let target = rebuild_target(config, nix_file, git_repo)?;
+    let scope = edit_scope(args.scope.as_ref(), target.kind)?;
+    let entry = call_package_entry(nix_file, &derivation);
+    if args.dry_run {
+        println!("{}", t!("local.would_add", entry, nix_file.display()));
+        return Ok(());
+    }
+    let scaffolded = !derivation.exists();
+    if scaffolded {
+        let create = template.is_some()
+            || !args.no_interactive
+                && Confirm::with_theme(prompt_theme())
+                    .with_prompt(t!("local.scaffold_prompt", derivation.display()))
+                    .default(true)
+                    .interact()?;
+        if !create {
+            return Err(DeclairError::InvalidPath {
+                path: derivation,
+                reason: t!("local.missing"),
+            });
+        }
+        write_derivation(&derivation, template.unwrap_or_default())?;
+        println!("{}", t!("local.scaffolded", derivation.display()));
+    }
+    let nix_file = nix_file.to_path_buf();
+    let ctx = HookContext::new("add", vec![entry.clone()], &nix_file);
+    run_phase(runner, &config.hooks, Phase::PreEdit, &ctx, None)?;
+
+    let mut changes = ChangeSet::new();
+    let staged = changes.stage(&nix_file)?;
+    config.plugins.add_package(runner, staged, &entry, &scope)?;
+    if config.formatter != Formatter::None {
+        let staged = changes.stage(&nix_file)?;
+        if let Err(e) = format_file(runner, config.formatter, staged) {
+            eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+        }
+    }
+
+    let pending = changes.changes()?;
+    let accepted = review_changes(&pending, args.no_interactive)?;
+    let written = changes.commit(runner, |path| accepted.iter().any(|a| a == path))?;
+    if !written.contains(&nix_file) {
+        println!("{}", t!("batch.none_applied"));
+        return Ok(());
+    }
+    println!("{}", t!("add.package", entry, nix_file.display()));
+    post_edit_hooks(runner, config, &ctx);
+    offer_gitignore(args, &written);
+    log_transaction(&ctx, rebuilding(config, args, runner, &written));
+    update_install_state(|state| state.record_added(&entry, &nix_file, args.reason.clone()));
+
+    if scaffolded {
+        println!("{}", t!("local.fill_in", derivation.display()));
+    } else if config.auto_rebuild && !args.no_rebuild {
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        rebuild_edited(config, args, runner, &target, git_repo, &ctx, &written)?;
+    }
+    let mut committed = written;
+    if scaffolded {
+        committed.push(derivation);
+    }
+    commit_edit(config, args, runner, git_repo, &ctx, &committed);
+    Ok(())
+}
+
+/// `nur.repos.*` packages need NUR in the configuration. In a flake without a NUR input,
+/// offer to add one; without a flake, explain how to add NUR when the config doesn't
+/// mention it.
+fn offer_nur_setup(config: &Config, args: &Args, repo: &Path, nix_file: &Path) -> Result<()> {
+    if config.flake {
+        let flake = flake_file(repo, nix_file);
+        let Ok(contents) = fs::read_to_string(&flake) else {
+            return Ok(());
+        };
+        if flake_has_nur(&contents) {
+            return Ok(());
+        }
+        println!("{}", t!("nur.missing_input", flake.display()));
+        if !args.no_interactive
+            && Confirm::with_theme(prompt_theme())
+                .with_prompt(t!("nur.add_input_prompt"))
+                .default(true)
+                .interact()?
+        {
+            add_flake_input(&flake, "nur", NUR_FLAKE_URL)?;
+            println!("{}", t!("nur.input_added", flake.display()));
+        }
+        println!("{}", t!("nur.overlay_hint"));
+    } else if !fs::read_to_string(nix_file)?.contains("nur") {
+        println!("{}", t!("nur.channel_hint"));
+    }
+    Ok(())
+}
+
+/// Warn about settings under `repo` that already install `pkg` (or a configured package
+/// providing the same command) and ask whether to add it anyway. Without prompts the
+/// conflicts are an error unless `--force` is given.
+fn confirm_conflicts(
+    args: &Args,
+    runner: &SystemRunner,
+    repo: &Path,
+    nix_file: &Path,
+    pkg: &str,
+    kind: ModuleKind,
+) -> Result<bool> {
+    if args.force {
+        return Ok(true);
+    }
+    let (conflicts, skipped) = package_conflicts(runner, repo, nix_file, pkg, kind)?;
+    if let Some(e) = skipped {
+        eprintln!(
+            "{}",
+            theme()
+                .warning(t!("conflict.check_skipped", e))
+                .for_stderr()
+        );
+    }
+    if conflicts.is_empty() {
+        return Ok(true);
+    }
+    for conflict in &conflicts {
+        eprintln!(
+            "{} {}",
+            theme().warning(t!("warning.label")).for_stderr(),
+            t!("conflict.item", pkg, conflict)
+        );
+    }
+    if args.no_interactive {
+        return Err(conflict_error(pkg, &conflicts));
+    }
+    Ok(Confirm::with_theme(prompt_theme())
+        .with_prompt(t!("conflict.add_anyway_prompt", pkg))
+        .default(false)
+        .interact()?)
+}
+
+/// The package to add for `pkg`: itself, unless nixpkgs renamed or removed it. A renamed
+/// package is replaced by its new name (after asking, when prompts are allowed); a
+/// removed one by the package its removal message suggests. `None` means the user chose
+/// not to add it. Without prompts, a removed package with no suggestion is an error
+/// unless `--force` is given.
+fn resolve_alias(args: &Args, pkg: &str) -> Result<Option<String>> {
+    let nix_path = std::env::var("NIX_PATH").unwrap_or_default();
+    let aliases = Aliases::load(nixpkgs_dir(&nix_path).as_deref());
+    let Some(alias) = aliases.get(pkg) else {
+        return Ok(Some(pkg.to_string()));
+    };
+    let note = match alias {
+        Alias::Renamed { new } => t!("alias.renamed", pkg, new),
+        Alias::Removed { message, .. } => t!("alias.removed", pkg, message),
+    };
+    eprintln!(
+        "{} {}",
+        theme().warning(t!("warning.label")).for_stderr(),
+        note
+    );
+    if let Some(new) = alias.replacement() {
+        if args.no_interactive
+            || Confirm::with_theme(prompt_theme())
+                .with_prompt(t!("alias.use_prompt", new))
+                .default(true)
+                .interact()?
+        {
+            println!("{}", t!("alias.using", new, pkg));
+            return Ok(Some(new.to_string()));
+        }
+        return Ok(matches!(alias, Alias::Renamed { .. }).then(|| pkg.to_string()));
+    }
+    if args.force
+        || (!args.no_interactive
+            && Confirm::with_theme(prompt_theme())
+                .with_prompt(t!("alias.add_anyway_prompt", pkg))
+                .default(false)
+                .interact()?)
+    {
+        return Ok(Some(pkg.to_string()));
+    }
+    match alias {
+        Alias::Removed { message, .. } if args.no_interactive => {
+            Err(DeclairError::RemovedPackage {
+                pkg: pkg.to_string(),
+                message: message.clone(),
+            })
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Fail when `pkg`'s license breaks the `[licenses]` policy, or only warn with
+/// `--override-policy`. `known` are its licenses when a search result already gave them;
+/// otherwise they are looked up.
+fn enforce_policy(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    repo: &Path,
+    nix_file: &Path,
+    pkg: &str,
+    known: Option<&[License]>,
+) -> Result<()> {
+    if config.licenses.is_empty() {
+        return Ok(());
+    }
+    let result = match known {
+        Some(licenses) => check_licenses(&config.licenses, pkg, licenses),
+        None => {
+            let (nixpkgs, cache) =
+                evalcache::open(runner, &Nixpkgs::for_config(config, repo, nix_file));
+            check_policy(runner, &config.licenses, &nixpkgs, cache.as_ref(), pkg)
+        }
+    };
+    override_policy(args, result)
+}
+
+/// `result` of a license check, with a violation only warned about under
+/// `--override-policy`.
+fn override_policy(args: &Args, result: Result<()>) -> Result<()> {
+    match result {
+        Err(DeclairError::PolicyViolation { reason, .. }) if args.override_policy => {
+            eprintln!(
+                "{} {}",
+                theme().warning(t!("warning.label")).for_stderr(),
+                t!("policy.overridden", reason)
+            );
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Warn when `pkg` isn't available on the system the configuration targets (the one named
+/// in `nix_file`, its `hardware-configuration.nix` or the flake, else this machine's) and
+/// ask whether to add it anyway. Without prompts that is an error unless `--force` is
+/// given. A failed lookup only warns.
+fn confirm_platform(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    repo: &Path,
+    nix_file: &Path,
+    pkg: &str,
+) -> Result<bool> {
+    let system = configured_system(config, repo, nix_file);
+    let nixpkgs = Nixpkgs::for_config(config, repo, nix_file);
+    confirm_support(
+        args,
+        pkg,
+        platform_support(runner, &nixpkgs, pkg, system.as_deref()),
+    )
+}
+
+/// The system the configuration targets, as named in `nix_file`, its
+/// `hardware-configuration.nix` or the flake.
+fn configured_system(config: &Config, repo: &Path, nix_file: &Path) -> Option<String> {
+    let mut files = vec![nix_file.to_path_buf()];
+    if let Some(dir) = nix_file.parent() {
+        files.push(dir.join("hardware-configuration.nix"));
+    }
+    if config.flake {
+        files.push(flake_file(repo, nix_file));
+    }
+    target_system(&files)
+}
+
+/// The prompt of [`confirm_platform`] for the looked-up `support` of `pkg`.
+fn confirm_support(
+    args: &Args,
+    pkg: &str,
+    support: Result<Option<PlatformSupport>>,
+) -> Result<bool> {
+    let support = match support {
+        Ok(Some(support)) if !support.available => support,
+        Ok(_) => return Ok(true),
+        Err(e) => {
+            eprintln!(
+                "{}",
+                theme()
+                    .warning(t!("platform.check_skipped", e))
+                    .for_stderr()
+            );
+            return Ok(true);
+        }
+    };
+    let platforms = if support.platforms.is_empty() {
+        t!("platform.none_listed")
+    } else {
+        support.platforms.join(", ")
+    };
+    eprintln!(
+        "{} {}",
+        theme().warning(t!("warning.label")).for_stderr(),
+        t!("platform.unsupported", pkg, support.system, platforms)
+    );
+    if args.force {
+        return Ok(true);
+    }
+    if args.no_interactive {
+        return Err(DeclairError::UnsupportedPlatform {
+            pkg: pkg.to_string(),
+            system: support.system,
+        });
+    }
+    Ok(Confirm::with_theme(prompt_theme())
+        .with_prompt(t!("platform.add_anyway_prompt", pkg))
+        .default(false)
+        .interact()?)
+}
+
+/// What adding a package looks up before editing: its license check and platform support.
+struct Precheck {
+    policy: Result<()>,
+    platform: Result<Option<PlatformSupport>>,
+}
+
+/// The [`Precheck`]s of `pkgs`, looked up in parallel so a large batch doesn't evaluate
+/// nixpkgs once per package in turn.
+fn precheck_packages(
+    config: &Config,
+    runner: &SystemRunner,
+    repo: &Path,
+    nix_file: &Path,
+    pkgs: &[String],
+) -> Vec<Option<Precheck>> {
+    let system = configured_system(config, repo, nix_file);
+    let (nixpkgs, cache) = evalcache::open(runner, &Nixpkgs::for_config(config, repo, nix_file));
+    evaluate_each(config, &t!("batch.checking"), pkgs, |pkg| {
+        let policy = check_policy(runner, &config.licenses, &nixpkgs, cache.as_ref(), pkg);
+        Some(Precheck {
+            policy,
+            platform: platform_support(runner, &nixpkgs, pkg, system.as_deref()),
+        })
+    })
+}
+
+/// `f` for each of `items` on `[jobs]` threads, with a progress bar showing `message`.
+fn evaluate_each<T: Sync, R: Send>(
+    config: &Config,
+    message: &str,
+    items: &[T],
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let bar = ProgressBar::new(items.len() as u64);
+    bar.set_style(
+        ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len} [{elapsed}]")
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+    );
+    bar.set_message(message.to_string());
+    let jobs = config.jobs.unwrap_or_else(default_jobs);
+    let results = map_bounded(items, jobs, || bar.inc(1), f);
+    bar.finish_and_clear();
+    results
+}
+
+/// Warn when `pkg` has known vulnerabilities. Skipped when vulnix isn't installed or
+/// `[audit] on_add` is off; other failures only warn.
+fn warn_advisories(
+    config: &Config,
+    runner: &SystemRunner,
+    repo: &Path,
+    nix_file: &Path,
+    pkg: &str,
+) {
+    if !config.audit.on_add {
+        return;
+    }
+    let findings = timings::time_with_spinner("audit", &t!("audit.spinner"), || {
+        advisories_on_add(runner, config, repo, nix_file, pkg)
+    });
+    match findings {
+        Ok(findings) => {
+            for finding in findings {
+                eprintln!(
+                    "{} {}",
+                    theme().warning(t!("warning.label")).for_stderr(),
+                    finding.summary()
+                );
+            }
+        }
+        Err(e) => eprintln!("{}", theme().warning(t!("warning", e)).for_stderr()),
+    }
+}
+
+/// After `pkg` was added and the configuration rebuilt, print where it landed in the store
+/// and how to run its main program, offering to run it right away. Outputs the rebuild
+/// didn't realise, e.g. when the configuration pins another nixpkgs, aren't shown.
+fn show_store_paths(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    repo: &Path,
+    nix_file: &Path,
+    pkg: &str,
+) {
+    let nixpkgs = Nixpkgs::for_config(config, repo, nix_file);
+    let paths = timings::time_with_spinner("store", &t!("store.spinner"), || {
+        store_paths(runner, &nixpkgs, pkg)
+    });
+    let paths = match paths {
+        Ok(Some(paths)) => paths,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+            return;
+        }
+    };
+    let built: Vec<(&String, &String)> = paths
+        .outputs
+        .iter()
+        .filter(|(_, path)| Path::new(path).exists())
+        .collect();
+    if built.is_empty() {
+        return;
+    }
+    println!("{}", t!("store.paths", pkg));
+    for (output, path) in built {
+        println!("  {}: {}", output, path);
+    }
+    let Some(binary) = paths.binary().filter(|b| b.exists()) else {
+        return;
+    };
+    let program = paths.main_program.as_deref().unwrap_or(pkg);
+    println!("{}", t!("store.binary", program, binary.display()));
+    if args.no_interactive {
+        return;
+    }
+    let run = Confirm::with_theme(prompt_theme())
+        .with_prompt(t!("store.run_prompt", program))
+        .default(false)
+        .interact()
+        .unwrap_or(false);
+    if run {
+        let cmd = CommandSpec::new(&binary.to_string_lossy(), Vec::<String>::new());
+        if let Err(e) = runner.status(&cmd) {
+            eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+        }
+    }
+}
+
+/// Warn about the places under `repo` still referring to `pkg` before it is removed
+/// from `nix_file`, since removing it may break them.
+fn warn_references(repo: &Path, nix_file: &Path, pkg: &str) {
+    let references = references_elsewhere(repo, nix_file, pkg);
+    if references.is_empty() {
+        return;
+    }
+    eprintln!(
+        "{} {}",
+        theme().warning(t!("warning.label")).for_stderr(),
+        t!("remove.still_referenced", pkg)
+    );
+    for r in references {
+        let file = r.file.strip_prefix(repo).unwrap_or(&r.file);
+        eprintln!(
+            "{}",
+            t!("remove.reference", file.display(), r.line, r.kind, r.text)
+        );
+    }
+}
+
+/// `declair rebuild`: queue a rebuild of the configuration with no edit of its own, in
+/// `mode` and into `profile` (the config's when not given).
+fn rebuild(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    mode: Option<RebuildMode>,
+    profile: Option<String>,
+) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let mut target = rebuild_target(config, &nix_file, &git_repo)?;
+    if let Some(mode) = mode {
+        target.mode = mode;
+    }
+    if profile.is_some() {
+        target.profile = profile;
+    }
+    check_target(&target)?;
+    if args.dry_run {
+        let cmd = rebuild_command_as(config, &target, &git_repo, &Privilege::current());
+        println!("{}", cmd.shell_line());
+        return Ok(());
+    }
+    require_unix("rebuild")?;
+    let ctx = HookContext::new("rebuild", Vec::new(), &nix_file);
+    println!("{}", t!("rebuild.rebuilding", target.kind));
+    rebuild_edited(config, args, runner, &target, &git_repo, &ctx, &[])
+}
+
+/// Rebuild after declair edited `files`, through the repository's [`RebuildQueue`]: when
+/// another run is rebuilding, it picks the edit up instead.
+fn rebuild_edited(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    target: &RebuildTarget,
+    repo: &Path,
+    ctx: &HookContext,
+    files: &[PathBuf],
+) -> Result<()> {
+    // Until the rebuild finishes or fails, the edit is one the next run offers to finish.
+    if !files.is_empty() && !runner.print_only {
+        update_install_state(|state| state.rebuild_started(&ctx.file, files));
+    }
+    let stopped = |err: DeclairError| {
+        if !err.is_interrupted() {
+            update_install_state(|state| state.rebuild_stopped(repo));
+        }
+        err
+    };
+    let Some(queue) = RebuildQueue::for_repo(repo) else {
+        return rebuild_once(config, args, runner, target, repo, ctx, files).map_err(stopped);
+    };
+    let debounce = Duration::from_millis(config.rebuild_debounce_ms.unwrap_or(0));
+    let mut edited = files;
+    let queued = queue
+        .run(debounce, || {
+            // Later rounds rebuild other runs' edits; this run's files are already built.
+            let files = std::mem::take(&mut edited);
+            rebuild_once(config, args, runner, target, repo, ctx, files)
+        })
+        .map_err(stopped)?;
+    match queued {
+        Queued::Deferred => println!("{}", t!("rebuild.deferred")),
+        Queued::Rebuilt(1) => {}
+        Queued::Rebuilt(n) => println!("{}", t!("rebuild.coalesced", n)),
+    }
+    Ok(())
+}
+
+/// Undo an edit of `file` declair made: put the edited `files` back from their backups
+/// and the install state of `packages` back to what it was before `action`.
+fn revert_edit(
+    args: &Args,
+    action: &str,
+    packages: &[String],
+    file: &Path,
+    files: &[PathBuf],
+) -> Result<()> {
+    for edited in files {
+        restore_backup(edited)?;
+    }
+    update_install_state(|state| {
+        state.unlog(file);
+        match (action, packages) {
+            ("add", pkgs) => pkgs.iter().for_each(|pkg| state.forget(pkg)),
+            ("remove", pkgs) => pkgs
+                .iter()
+                .for_each(|pkg| state.record_added(pkg, file, args.reason.clone())),
+            ("replace", [old, new]) => {
+                state.forget(new);
+                state.record_added(old, file, args.reason.clone());
+            }
+            _ => {}
+        }
+    });
+    Ok(())
+}
+
+/// Offer to deal with the edits an earlier run made but didn't finish rebuilding, e.g.
+/// because it was interrupted, so the system doesn't silently stay behind the config:
+/// rebuild now, revert them or leave them. Without prompts it only warns. Rebuilds still
+/// running in another run are left to it.
+fn resume_interrupted(config: &Config, args: &Args, runner: &SystemRunner) -> Result<()> {
+    let Ok(state) = state_file_path().and_then(|path| State::load(&path)) else {
+        return Ok(());
+    };
+    let mut by_repo: BTreeMap<PathBuf, Vec<Transaction>> = BTreeMap::new();
+    for transaction in state.interrupted() {
+        let Ok(repo) = get_git_repo_or_parent_directory(&transaction.file) else {
+            continue;
+        };
+        by_repo.entry(repo).or_default().push(transaction.clone());
+    }
+    for (repo, edits) in by_repo {
+        let running =
+            RebuildQueue::for_repo(&repo).is_some_and(|queue| matches!(queue.try_lock(), Ok(None)));
+        if running {
+            continue;
+        }
+        eprintln!(
+            "{}",
+            theme()
+                .warning(t!("resume.found", repo.display()))
+                .for_stderr()
+        );
+        for edit in &edits {
+            eprintln!(
+                "  {} {} ({}, {})",
+                edit.action,
+                edit.packages.join(" "),
+                contract_home(&edit.file),
+                edit.at_display()
+            );
+        }
+        if args.no_interactive {
+            eprintln!("{}", t!("resume.noninteractive"));
+            continue;
+        }
+        let choice = Select::with_theme(prompt_theme())
+            .with_prompt(t!("resume.prompt"))
+            .items([
+                t!("resume.rebuild"),
+                t!("resume.revert"),
+                t!("resume.ignore"),
+            ])
+            .default(0)
+            .interact()?;
+        match choice {
+            0 => {
+                let latest = &edits[edits.len() - 1];
+                let target = rebuild_target(config, &latest.file, &repo)?;
+                check_target(&target)?;
+                let files: Vec<PathBuf> = edits
+                    .iter()
+                    .flat_map(|edit| edit.rebuilding.iter().cloned())
+                    .collect();
+                let ctx = HookContext::new("rebuild", latest.packages.clone(), &latest.file);
+                println!("{}", t!("rebuild.rebuilding", target.kind));
+                rebuild_edited(config, args, runner, &target, &repo, &ctx, &files)?;
+            }
+            1 => {
+                for edit in edits.iter().rev() {
+                    revert_edit(
+                        args,
+                        &edit.action,
+                        &edit.packages,
+                        &edit.file,
+                        &edit.rebuilding,
+                    )?;
+                    let shown: Vec<String> = edit
+                        .rebuilding
+                        .iter()
+                        .map(|f| f.display().to_string())
+                        .collect();
+                    println!("{}", t!("rebuild.reverted", shown.join(", ")));
+                }
+            }
+            _ => update_install_state(|state| state.rebuild_stopped(&repo)),
+        }
+    }
+    Ok(())
+}
+
+/// After a rebuild failed with an evaluation error, evaluate the configuration again with
+/// `--show-trace` and show the trace in the pager: right away without prompts, otherwise
+/// after asking. Nothing to do when the rebuild had `--show-trace` already.
+fn show_trace(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    target: &RebuildTarget,
+    repo: &Path,
+) -> Result<()> {
+    if config.rebuild_args.iter().any(|arg| arg == "--show-trace") {
+        return Ok(());
+    }
+    let wanted = args.no_interactive
+        || Confirm::with_theme(prompt_theme())
+            .with_prompt(t!("rebuild.trace_prompt"))
+            .default(true)
+            .interact()?;
+    if !wanted {
+        return Ok(());
+    }
+    let cmd = trace_command(config, target, repo);
+    let output =
+        timings::time_with_spinner("trace", &t!("rebuild.tracing"), || runner.output(&cmd))?;
+    let mut trace = String::from_utf8_lossy(&output.stdout).into_owned();
+    trace.push_str(&String::from_utf8_lossy(&output.stderr));
+    if output.status.success() {
+        // Evaluation errors can be flaky, e.g. with `--impure` inputs.
+        println!("{}", t!("rebuild.trace_passed"));
+    } else {
+        page(&trace);
+    }
+    Ok(())
+}
+
+/// [`rebuild_with_hooks`] after declair edited `files`. When the rebuild fails, the
+/// configuration is checked without the edit to report whether the edit caused the failure;
+/// if it did, the edit is reverted (after asking, unless `--no-interactive`) along with its
+/// install state. A failure that predates the edit keeps it. An evaluation error first
+/// gets its trace shown (see [`show_trace`]). The rebuild's error is returned either way.
+fn rebuild_once(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    target: &RebuildTarget,
+    repo: &Path,
+    ctx: &HookContext,
+    files: &[PathBuf],
+) -> Result<()> {
+    let err = match rebuild_with_hooks(runner, config, target, repo, ctx) {
+        Ok(()) => {
+            // Printed commands activated nothing.
+            if !runner.print_only {
+                record_rebuild(target);
+            }
+            return Ok(());
+        }
+        Err(e) => e,
+    };
+    if let DeclairError::RebuildFailed {
+        evaluation: true, ..
+    } = err
+    {
+        show_trace(config, args, runner, target, repo)?;
+    }
+    if !matches!(err, DeclairError::RebuildFailed { .. }) || files.is_empty() {
+        return Err(err);
+    }
+    let cause = timings::time_with_spinner("diagnose", &t!("rebuild.diagnosing"), || {
+        diagnose_failure(runner, config, target, repo, files)
+    })?;
+    let shown: Vec<String> = files.iter().map(|f| f.display().to_string()).collect();
+    let warn = |msg: String| {
+        eprintln!(
+            "{} {}",
+            theme().warning(t!("warning.label")).for_stderr(),
+            msg
+        )
+    };
+    match cause {
+        FailureCause::Edit => {
+            warn(t!("rebuild.caused_by_edit"));
+            let revert = args.no_interactive
+                || Confirm::with_theme(prompt_theme())
+                    .with_prompt(t!("rebuild.revert_prompt"))
+                    .default(true)
+                    .interact()?;
+            if revert {
+                revert_edit(args, ctx.action, &ctx.packages, &ctx.file, files)?;
+                println!("{}", t!("rebuild.reverted", shown.join(", ")));
+            } else {
+                println!("{}", t!("rebuild.kept", shown.join(", ")));
+            }
+        }
+        FailureCause::PreExisting => warn(t!("rebuild.pre_existing")),
+        FailureCause::Unknown => warn(t!("rebuild.cause_unknown")),
+    }
+    Err(err)
+}
+
+/// Log the edit described by `ctx` for `declair status`. When a rebuild of the edited
+/// `rebuilding` files follows, it counts as started from here on, so an edit interrupted
+/// before its rebuild got going is offered to finish too (see [`resume_interrupted`]).
+fn log_transaction(ctx: &HookContext, rebuilding: &[PathBuf]) {
+    emit(&Event::EditApplied {
+        action: ctx.action.to_string(),
+        packages: ctx.packages.clone(),
+        file: ctx.file.clone(),
+    });
+    update_install_state(|state| {
+        state.log(Transaction {
+            rebuilding: rebuilding.to_vec(),
+            ..Transaction::now(ctx.action, ctx.packages.clone(), &ctx.file)
+        })
+    });
+}
+
+/// The edited `files` a rebuild follows right away: all of them with `auto_rebuild` and
+/// without `--no-rebuild`, otherwise none. Printed rebuilds leave nothing to finish.
+fn rebuilding<'a>(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    files: &'a [PathBuf],
+) -> &'a [PathBuf] {
+    if config.auto_rebuild && !args.no_rebuild && !runner.print_only {
+        files
+    } else {
+        &[]
+    }
+}
+
+/// Remember that `target` was just rebuilt successfully, with the generation it activated.
+fn record_rebuild(target: &RebuildTarget) {
+    let record = RebuildRecord {
+        at: unix_now(),
+        target: target.to_string(),
+        generation: current_generation(target.kind),
+    };
+    update_install_state(|state| state.record_rebuild(record));
+}
+
+/// Apply `f` to the install state. The config edit already happened, so a failure here
+/// only warns.
+fn update_install_state(f: impl FnOnce(&mut State)) {
+    if let Err(e) = state_file_path().and_then(|path| update_state(&path, f)) {
+        eprintln!(
+            "{}",
+            theme().warning(t!("warning.state_update", e)).for_stderr()
+        );
+    }
+}
+
+/// Remember the config path `nix_path` for the path prompts, absolute and with `~/` for
+/// the home directory so it means the same from any working directory.
+fn remember_nix_path(nix_path: &str) {
+    let Ok(path) = expand_path(nix_path.trim()) else {
+        return;
+    };
+    let path = std::path::absolute(&path).unwrap_or(path);
+    if let Err(e) = remember_config(&contract_home(&path)) {
+        eprintln!(
+            "{}",
+            theme().warning(t!("warning.state_update", e)).for_stderr()
+        );
+    }
+}
+
+/// `declair update`: update the flake inputs or channels, then rebuild when `yes` is set
+/// or the user agrees.
+fn update(config: &Config, args: &Args, runner: &SystemRunner, yes: bool) -> Result<()> {
+    let nix_file = resolve_nix_config(&expand_path(config.nix_path.trim())?)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(config, &nix_file, &git_repo)?;
+    if args.dry_run {
+        for cmd in [
+            update_command(config, &target, &git_repo),
+            rebuild_command_as(config, &target, &git_repo, &Privilege::current()),
+        ] {
+            println!("{}", cmd.shell_line());
+        }
+        return Ok(());
+    }
+    update_inputs(runner, config, &target, &git_repo)?;
+    rebuild_updated(config, args, runner, &nix_file, &git_repo, &target, yes)
+}
+
+/// After updating inputs of the configuration at `nix_file`, rebuild it: right away with
+/// `yes`, otherwise after asking; without prompts not at all.
+fn rebuild_updated(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    nix_file: &Path,
+    git_repo: &Path,
+    target: &RebuildTarget,
+    yes: bool,
+) -> Result<()> {
+    let rebuild_now = yes
+        || (!args.no_interactive
+            && Confirm::with_theme(prompt_theme())
+                .with_prompt(t!("watch.rebuild_prompt"))
+                .default(true)
+                .interact()?);
+    if rebuild_now {
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        let ctx = HookContext::new("update", Vec::new(), nix_file);
+        rebuild_with_hooks(runner, config, target, git_repo, &ctx)?;
+        record_rebuild(target);
+    } else {
+        println!("{}", t!("update.not_rebuilt"));
+    }
+    Ok(())
+}
+
+/// The configuration's file, repository and rebuild target when it is a flake; `None`
+/// without a configuration or when it can't be resolved.
+fn configured_flake(config: &Config) -> Option<(PathBuf, PathBuf, RebuildTarget)> {
+    if !config.flake || config.nix_path.trim().is_empty() {
+        return None;
+    }
+    let nix_file = resolve_nix_config(&expand_path(config.nix_path.trim()).ok()?).ok()?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file).ok()?;
+    let target = rebuild_target(config, &nix_file, &git_repo).ok()?;
+    Some((nix_file, git_repo, target))
+}
+
+/// `declair self check`: say how the running declair was installed, then update it (after
+/// asking, unless `yes`) or print the command that does. A declair installed by the
+/// configuration's flake is updated by bumping its input and rebuilding.
+fn self_check(config: &Config, args: &Args, runner: &SystemRunner, yes: bool) -> Result<()> {
+    let exe = env::current_exe()
+        .and_then(fs::canonicalize)
+        .unwrap_or_default();
+    let configured = configured_flake(config);
+    let flake_dir = configured
+        .as_ref()
+        .and_then(|(nix_file, repo, _)| find_flake_dir(repo, nix_file));
+    let method = detect(
+        &exe,
+        &user_profiles(),
+        cargo_home().as_deref(),
+        flake_dir.as_deref(),
+    );
+    println!(
+        "{}",
+        t!("self.version", env!("CARGO_PKG_VERSION"), exe.display())
+    );
+    let (described, prompt) = match &method {
+        InstallMethod::NixProfile { element } => {
+            (t!("self.nix_profile", element), t!("self.update_prompt"))
+        }
+        InstallMethod::NixEnv => (t!("self.nix_env"), t!("self.update_prompt")),
+        InstallMethod::FlakeInput { input, flake_dir } => (
+            t!("self.flake_input", input, contract_home(flake_dir)),
+            t!("self.bump_prompt", input),
+        ),
+        InstallMethod::NixStore { store_path } => {
+            (t!("self.nix_store", store_path.display()), String::new())
+        }
+        InstallMethod::Cargo => (t!("self.cargo"), t!("self.update_prompt")),
+        InstallMethod::Unknown => (t!("self.unknown"), String::new()),
+    };
+    println!("{}", described);
+    let Some(cmd) = self_update_command(&method) else {
+        return Ok(());
+    };
+    // Bumping the input edits `flake.lock`, which read-only mode leaves alone.
+    let print_only =
+        args.dry_run || (config.read_only && matches!(method, InstallMethod::FlakeInput { .. }));
+    let update_now = !print_only
+        && (yes
+            || (!args.no_interactive
+                && Confirm::with_theme(prompt_theme())
+                    .with_prompt(prompt)
+                    .default(true)
+                    .interact()?));
+    if !update_now {
+        println!("{}", t!("self.update_with", cmd.shell_line()));
+        return Ok(());
+    }
+    let status = runner.status(&cmd)?;
+    if !status.success() {
+        return Err(DeclairError::UpdateFailed { status });
+    }
+    match (&method, configured) {
+        (InstallMethod::FlakeInput { input, .. }, Some((nix_file, git_repo, target))) => {
+            println!("{}", t!("self.bumped", input));
+            rebuild_updated(config, args, runner, &nix_file, &git_repo, &target, yes)
+        }
+        _ => {
+            println!("{}", t!("self.updated"));
+            Ok(())
+        }
+    }
+}
+
+/// `declair channel`: list, add or update the channels the configuration is built from.
+fn channel(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    action: &ChannelAction,
+) -> Result<()> {
+    require_unix("channel")?;
+    let kind = ModuleKind::from_home_manager(config.home_manager);
+    match action {
+        ChannelAction::List => {
+            let channels = list_channels(runner, kind)?;
+            if channels.is_empty() && args.output == OutputFormat::Table {
+                println!("{}", t!("channel.none"));
+                return Ok(());
+            }
+            let rows: Vec<Vec<String>> =
+                channels.into_iter().map(|c| vec![c.name, c.url]).collect();
+            println!("{}", render(args.output, &["name", "url"], &rows));
+            Ok(())
+        }
+        ChannelAction::Add { url, name } => {
+            add_channel(runner, kind, url, name.as_deref())?;
+            println!("{}", t!("channel.added", name.as_deref().unwrap_or(url)));
+            Ok(())
+        }
+        ChannelAction::Update { names } if args.dry_run => {
+            let cmd = channel_update_command(kind, names);
+            println!("{}", cmd.shell_line());
+            Ok(())
+        }
+        ChannelAction::Update { names } => update_channels(runner, kind, names),
+    }
+}
+
+/// `declair schedule`: manage the user systemd timer running `declair update`.
+fn schedule(runner: &SystemRunner, action: &ScheduleAction) -> Result<()> {
+    let unit_dir = user_unit_dir()?;
+    match action {
+        ScheduleAction::Install {
+            daily,
+            monthly,
+            on_calendar,
+            ..
+        } => {
+            let on_calendar = match (on_calendar, daily, monthly) {
+                (Some(spec), _, _) => spec.as_str(),
+                (None, true, _) => "daily",
+                (None, _, true) => "monthly",
+                _ => "weekly",
+            };
+            schedule::require_systemd()?;
+            let mut profiles = user_profiles();
+            profiles.push(PathBuf::from("/run/current-system/sw"));
+            let exe = schedule::stable_exe(
+                &env::current_exe()?,
+                &profiles,
+                env::var_os("PATH").as_deref(),
+            );
+            schedule::install(runner, &unit_dir, &exe, on_calendar)?;
+            println!(
+                "{}",
+                t!(
+                    "schedule.installed",
+                    unit_dir.join(TIMER_NAME).display(),
+                    on_calendar
+                )
+            );
+        }
+        ScheduleAction::Status => schedule::status(runner)?,
+        ScheduleAction::Uninstall => {
+            schedule::uninstall(runner, &unit_dir)?;
+            println!("{}", t!("schedule.uninstalled"));
+        }
+    }
+    Ok(())
+}
+
+/// `declair plugins`: the plugins found on `PATH`.
+fn plugins(output: OutputFormat) -> Result<()> {
+    let found = discover();
+    if found.is_empty() && output == OutputFormat::Table {
+        println!("{}", t!("plugins.none"));
+        return Ok(());
+    }
+    let rows: Vec<Vec<String>> = found
+        .into_iter()
+        .map(|p| vec![p.name, p.path.display().to_string()])
+        .collect();
+    println!("{}", render(output, &["name", "path"], &rows));
+    Ok(())
+}
+
+/// `declair <name> [ARGS]...`: run the plugin `declair-<name>`, like cargo runs its
+/// subcommands, and exit with its exit code.
+fn external(runner: &SystemRunner, argv: &[String]) -> Result<()> {
+    let (name, rest) = argv
+        .split_first()
+        .ok_or_else(|| DeclairError::Config(t!("plugins.none")))?;
+    let plugin = find_plugin(name)?;
+    run_to_exit(
+        runner,
+        &CommandSpec::new(&plugin.path.to_string_lossy(), rest),
+    )
+}
+
+/// Run `cmd` in the foreground, exiting with its code when it fails.
+fn run_to_exit(runner: &SystemRunner, cmd: &CommandSpec) -> Result<()> {
+    let status = runner.status(cmd)?;
+    if !status.success() {
+        exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// `declair run`: run the main program of `pkg` if the configuration lists it, otherwise
+/// offer to run it once with `nix run` (the default with `--no-interactive`) or to add it
+/// and then run it.
+fn run_package(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    pkg: &str,
+    argv: &[String],
+) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let nixpkgs = Nixpkgs::for_config(config, &git_repo, &nix_file);
+    let paths = with_spinner(&t!("store.spinner"), || store_paths(runner, &nixpkgs, pkg))?
+        .ok_or_else(|| DeclairError::SearchFailed(t!("run.unknown", pkg)))?;
+    let program = paths
+        .main_program
+        .clone()
+        .unwrap_or_else(|| pkg.rsplit('.').next().unwrap_or(pkg).to_string());
+    let installed = CommandSpec::new(&program, argv);
+
+    if installed_packages(&git_repo).contains(pkg) {
+        return match run_to_exit(runner, &installed) {
+            Err(DeclairError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Err(
+                DeclairError::SearchFailed(t!("run.not_on_path", program, pkg)),
+            ),
+            result => result,
+        };
+    }
+
+    let once = args.no_interactive || {
+        let choices = [t!("run.choice_once"), t!("run.choice_add")];
+        let Some(choice) = Select::with_theme(prompt_theme())
+            .with_prompt(t!("run.not_installed", pkg))
+            .items(&choices)
+            .default(0)
+            .interact_opt()?
+        else {
+            println!("{}", t!("nothing_selected"));
+            return Ok(());
+        };
+        choice == 0
+    };
+    if once {
+        println!("{}", t!("run.once", pkg));
+        return run_to_exit(runner, &nix_run_command(&nixpkgs, pkg, argv));
+    }
+    edit_packages(config, args, runner, &[pkg.to_string()], BatchAction::Add)?;
+    if config.auto_rebuild && !args.no_rebuild && !args.dry_run {
+        run_to_exit(runner, &installed)?;
+    }
+    Ok(())
+}
+
+/// `declair diff`: packages only the local config has, only `other` has, and both have.
+fn diff(config: &Config, args: &Args, other: &Path) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let kind = rebuild_target(config, &nix_file, &git_repo)?.kind;
+    let local: BTreeSet<String> = list_packages(&nix_file, kind)?.into_iter().collect();
+    let diff = SetDiff::between(&local, &read_manifest(other)?);
+
+    if args.output != OutputFormat::Table {
+        let rows: Vec<Vec<String>> = [
+            ("local", &diff.only_local),
+            ("other", &diff.only_other),
+            ("common", &diff.common),
+        ]
+        .into_iter()
+        .flat_map(|(status, pkgs)| {
+            pkgs.iter()
+                .map(move |p| vec![p.clone(), status.to_string()])
+        })
+        .collect();
+        println!("{}", render(args.output, &["package", "status"], &rows));
+        return Ok(());
+    }
+    let width = usize::from(Term::stdout().size().1).saturating_sub(2);
+    let mut lines = Vec::new();
+    if !diff.only_local.is_empty() {
+        lines.push(t!(
+            "diff.only_in",
+            nix_file.display(),
+            diff.only_local.len()
+        ));
+        for pkg in &diff.only_local {
+            lines.push(format!("  {}", theme().added(format!("+ {}", pkg))));
+        }
+    }
+    if !diff.only_other.is_empty() {
+        lines.push(t!("diff.only_in", other.display(), diff.only_other.len()));
+        for pkg in &diff.only_other {
+            lines.push(format!("  {}", theme().removed(format!("- {}", pkg))));
+        }
+    }
+    if diff.is_in_sync() {
+        lines.push(t!("diff.in_sync", diff.common.len()));
+    } else if !diff.common.is_empty() {
+        lines.push(t!("diff.common", diff.common.len()));
+        for line in wrap(&diff.common.join(" "), width) {
+            lines.push(format!("  {}", line));
+        }
+    }
+    page(&format!("{}\n", lines.join("\n")));
+    Ok(())
+}
+
+/// `declair sync`: merge `manifest` into the package list, with the manifest as last
+/// synced as the base, preview the result, resolve conflicts and apply it with one
+/// rebuild.
+fn sync(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    manifest: &Path,
+    yes: bool,
+) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(config, &nix_file, &git_repo)?;
+    let scope = edit_scope(args.scope.as_ref(), target.kind)?;
+    let wanted = read_manifest(manifest)?;
+    let current: BTreeSet<String> = list_packages(&nix_file, &scope)?.into_iter().collect();
+    let key = std::path::absolute(manifest)
+        .unwrap_or_else(|_| manifest.to_path_buf())
+        .display()
+        .to_string();
+    let state = State::load(&state_file_path()?)?;
+    let mut plan = SyncPlan::merge(state.synced.get(&key), &wanted, &current);
+
+    if plan.is_empty() {
+        println!("{}", t!("sync.in_sync", manifest.display()));
+        update_install_state(|state| {
+            state.synced.insert(key, wanted);
+        });
+        return Ok(());
+    }
+    println!(
+        "{}",
+        t!("sync.preview", manifest.display(), nix_file.display())
+    );
+    for pkg in &plan.add {
+        println!("  {}", theme().added(format!("+ {}", pkg)));
+    }
+    for pkg in &plan.remove {
+        println!("  {}", theme().removed(format!("- {}", pkg)));
+    }
+    for conflict in &plan.conflicts {
+        let reason = match conflict {
+            SyncConflict::AddedLocally(pkg) => t!("sync.added_locally", pkg),
+            SyncConflict::RemovedLocally(pkg) => t!("sync.removed_locally", pkg),
         };
-        // 4) Select directory to search in ("." if no / in path)
-        let dir_path = if dir_str.is_empty() {
-            Path::new(".")
+        println!("  {} {}", theme().warning("!"), reason);
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+
+    for conflict in plan.conflicts.clone() {
+        let overwrite = if args.no_interactive || yes {
+            args.force
         } else {
-            Path::new(&dir_str)
+            let items = [t!("sync.keep_local"), t!("sync.take_manifest")];
+            Select::with_theme(prompt_theme())
+                .with_prompt(t!("sync.conflict_prompt", conflict.package()))
+                .items(&items)
+                .default(0)
+                .interact()?
+                == 1
         };
-        let read = fs::read_dir(dir_path).ok()?; // stop if cannot open
-        // 5) Find first file/folder whose name starts with prefix
-        for entry in read.filter_map(Result::ok) {
-            let name = entry.file_name();
-            let name_s = name.to_string_lossy();
-            if name_s.starts_with(&prefix) {
-                // 6) Build completion string: dir + name (and add / if it is a directory)
-                let mut completed = String::new();
-                if !dir_str.is_empty() {
-                    completed.push_str(&dir_str);
-                }
-                completed.push_str(&name_s);
-                if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
-                    completed.push('/');
+        if overwrite {
+            plan.overwrite(&conflict);
+        }
+    }
+    if plan.add.is_empty() && plan.remove.is_empty() {
+        println!("{}", t!("batch.nothing"));
+        update_install_state(|state| {
+            state.synced.insert(key, wanted);
+        });
+        return Ok(());
+    }
+    check_pins(args, plan.remove.iter().map(String::as_str))?;
+    if !args.no_interactive
+        && !yes
+        && !Confirm::with_theme(prompt_theme())
+            .with_prompt(t!("sync.apply_prompt", plan.add.len(), plan.remove.len()))
+            .default(true)
+            .interact()?
+    {
+        return Ok(());
+    }
+
+    let packages: Vec<String> = plan.add.iter().chain(&plan.remove).cloned().collect();
+    let ctx = HookContext::new("sync", packages, &nix_file);
+    run_phase(runner, &config.hooks, Phase::PreEdit, &ctx, None)?;
+    let prechecks = precheck_packages(config, runner, &git_repo, &nix_file, &plan.add);
+    let mut changes = ChangeSet::new();
+    let mut added = Vec::new();
+    for (pkg, precheck) in plan.add.iter().zip(prechecks) {
+        if let Some(precheck) = precheck {
+            override_policy(args, precheck.policy)?;
+            if !confirm_support(args, pkg, precheck.platform)? {
+                println!("{}", t!("batch.skipping", pkg));
+                continue;
+            }
+        }
+        let staged = changes.stage(&nix_file)?;
+        let pkg_scope = package_scope(args, &scope, staged, pkg);
+        match add_to_segment(config, runner, staged, pkg, &pkg_scope, 0) {
+            Ok(()) | Err(DeclairError::PackageExists { .. }) => added.push(pkg.clone()),
+            Err(e) => return Err(e),
+        }
+    }
+    for pkg in &plan.remove {
+        warn_references(&git_repo, &nix_file, pkg);
+        let staged = changes.stage(&nix_file)?;
+        let pkg_scope = package_scope(args, &scope, staged, pkg);
+        match config
+            .plugins
+            .remove_package(runner, staged, pkg, &pkg_scope)
+        {
+            Ok(()) | Err(DeclairError::PackageNotFound { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if config.formatter != Formatter::None {
+        let staged = changes.stage(&nix_file)?;
+        if let Err(e) = format_file(runner, config.formatter, staged) {
+            eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+        }
+    }
+
+    let pending = changes.changes()?;
+    let accepted = review_changes(&pending, args.no_interactive)?;
+    let written = changes.commit(runner, |path| accepted.iter().any(|a| a == path))?;
+    if !written.contains(&nix_file) {
+        println!("{}", t!("batch.none_applied"));
+        return Ok(());
+    }
+    println!("{}", t!("batch.updated", nix_file.display()));
+    post_edit_hooks(runner, config, &ctx);
+    offer_gitignore(args, &written);
+    log_transaction(&ctx, rebuilding(config, args, runner, &written));
+    update_install_state(|state| {
+        for pkg in &added {
+            state.record_added(pkg, &nix_file, args.reason.clone());
+        }
+        for pkg in &plan.remove {
+            state.forget(pkg);
+        }
+        state.synced.insert(key, wanted);
+    });
+
+    if config.auto_rebuild && !args.no_rebuild {
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        rebuild_edited(config, args, runner, &target, &git_repo, &ctx, &written)?;
+    }
+    commit_edit(config, args, runner, &git_repo, &ctx, &written);
+    Ok(())
+}
+
+/// `declair grep`: every reference to a package in the repository, as `file:line`.
+fn grep(config: &Config, args: &Args, pattern: &str) -> Result<()> {
+    let nix_file = resolve_nix_config(&expand_path(config.nix_path.trim())?)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let references = find_references(&git_repo, pattern);
+    if references.is_empty() {
+        return Err(DeclairError::SearchFailed(t!(
+            "grep.none",
+            pattern,
+            git_repo.display()
+        )));
+    }
+    let rows: Vec<Vec<String>> = references
+        .into_iter()
+        .map(|r| {
+            let file = r.file.strip_prefix(&git_repo).unwrap_or(&r.file);
+            vec![
+                format!("{}:{}", file.display(), r.line),
+                r.kind.to_string(),
+                r.text,
+            ]
+        })
+        .collect();
+    println!(
+        "{}",
+        render(args.output, &["location", "kind", "line"], &rows)
+    );
+    Ok(())
+}
+
+/// `declair audit`: check every package of the target file for known vulnerabilities.
+/// Fails with exit code 11 when some have advisories, so it can gate CI.
+fn audit(config: &Config, args: &Args, runner: &SystemRunner) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let kind = rebuild_target(config, &nix_file, &git_repo)?.kind;
+    let packages = list_packages(&nix_file, kind)?;
+    let nixpkgs = Nixpkgs::for_config(config, &git_repo, &nix_file);
+    let findings = with_spinner(&t!("audit.spinner"), || {
+        audit_packages(runner, &nixpkgs, &packages)
+    })?;
+    if findings.is_empty() {
+        if args.output == OutputFormat::Table {
+            println!("{}", t!("audit.clean", packages.len()));
+        }
+        return Ok(());
+    }
+    let rows: Vec<Vec<String>> = findings
+        .iter()
+        .map(|f| {
+            let ids: Vec<String> = f.advisories.iter().map(ToString::to_string).collect();
+            vec![
+                f.package.clone(),
+                f.name.clone(),
+                f.severity().to_string(),
+                ids.join(", "),
+            ]
+        })
+        .collect();
+    let headers = ["package", "derivation", "severity", "advisories"];
+    println!("{}", render(args.output, &headers, &rows));
+    Err(DeclairError::Vulnerable {
+        count: findings.len(),
+    })
+}
+
+/// `declair audit --licenses`: list the licenses of every package of the target file
+/// with the `[licenses]` policy's verdict. Fails with exit code 11 when some break it.
+fn audit_licenses(config: &Config, args: &Args, runner: &SystemRunner) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let kind = rebuild_target(config, &nix_file, &git_repo)?.kind;
+    let packages = list_packages(&nix_file, kind)?;
+    let licenses = with_spinner(&t!("audit.spinner"), || {
+        let nixpkgs = Nixpkgs::for_config(config, &git_repo, &nix_file);
+        let (nixpkgs, cache) = evalcache::open(runner, &nixpkgs);
+        package_licenses(runner, &nixpkgs, cache.as_ref(), &packages)
+    })?;
+    let mut violations = 0;
+    let rows: Vec<Vec<String>> = packages
+        .iter()
+        .map(|pkg| {
+            let found = licenses.get(pkg).map_or(&[][..], Vec::as_slice);
+            let reasons = config.licenses.violations(found);
+            if !reasons.is_empty() {
+                violations += 1;
+            }
+            let names: Vec<String> = found.iter().map(ToString::to_string).collect();
+            let verdict = if reasons.is_empty() {
+                theme().success(t!("policy.ok")).to_string()
+            } else {
+                theme().error(reasons.join("; ")).to_string()
+            };
+            vec![pkg.clone(), names.join(", "), verdict]
+        })
+        .collect();
+    println!(
+        "{}",
+        render(args.output, &["package", "licenses", "policy"], &rows)
+    );
+    if violations > 0 {
+        return Err(DeclairError::LicenseViolations { count: violations });
+    }
+    Ok(())
+}
+
+/// `declair graph`: the modules reachable from the flake (or the config file without
+/// one) through local imports, with the file declair edits marked.
+fn graph(config: &Config, args: &Args, style: GraphStyle) -> Result<()> {
+    let nix_file = resolve_nix_config(&expand_path(config.nix_path.trim())?)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let flake = flake_file(&git_repo, &nix_file);
+    let root = if config.flake && flake.is_file() {
+        flake
+    } else {
+        nix_file.clone()
+    };
+    let graph = module_graph(&root)?;
+    let shown = |path: &Path| {
+        path.strip_prefix(&git_repo)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    if args.output != OutputFormat::Table {
+        let rows: Vec<Vec<String>> = graph
+            .nodes
+            .iter()
+            .map(|node| {
+                let imports: Vec<String> = node
+                    .imports
+                    .iter()
+                    .map(|&i| shown(&graph.nodes[i].path))
+                    .collect();
+                vec![
+                    shown(&node.path),
+                    node.packages.to_string(),
+                    imports.join(" "),
+                ]
+            })
+            .collect();
+        println!(
+            "{}",
+            render(args.output, &["module", "packages", "imports"], &rows)
+        );
+        return Ok(());
+    }
+    let drawn = match style {
+        GraphStyle::Tree => render_tree(&[graph.tree(&git_repo, &nix_file, &t!("graph.target"))]),
+        GraphStyle::Dot => graph.to_dot(&git_repo, &nix_file),
+        GraphStyle::Mermaid => graph.to_mermaid(&git_repo, &nix_file),
+    };
+    println!("{}", drawn);
+    Ok(())
+}
+
+/// `declair why`: print the install record of a package, whether the config lists it and
+/// what else in the installed profile depends on it.
+fn why(config: &Config, runner: &SystemRunner, pkg: &str) -> Result<()> {
+    let nix_file = resolve_nix_config(&expand_path(config.nix_path.trim())?)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let kind = rebuild_target(config, &nix_file, &git_repo)?.kind;
+    let in_config = list_packages(&nix_file, kind)?.iter().any(|p| p == pkg);
+
+    let state = State::load(&state_file_path()?)?;
+    match state.get(pkg) {
+        Some(record) => {
+            println!(
+                "{}",
+                t!(
+                    "why.added",
+                    pkg,
+                    record.file.display(),
+                    record.added_at_display(),
+                    record.added_by()
+                )
+            );
+            if let Some(reason) = &record.reason {
+                println!("{}", t!("why.reason", reason));
+            }
+        }
+        None => println!("{}", t!("why.no_record", pkg)),
+    }
+    if in_config {
+        println!("{}", t!("why.listed", nix_file.display()));
+    } else {
+        println!("{}", t!("why.not_listed", nix_file.display()));
+    }
+
+    let root = profile_root(kind)?;
+    let installed = reverse_dependencies(runner, &root, pkg)?;
+    if installed.is_empty() {
+        println!("{}", t!("why.not_installed", root.display()));
+        return Ok(());
+    }
+    let mut required = false;
+    for dep in &installed {
+        println!("{}:", store_name(&dep.path));
+        if dep.in_profile {
+            println!("{}", t!("why.explicit", kind));
+        }
+        if !dep.required_by.is_empty() {
+            required = true;
+            println!("{}", t!("why.required_by", dep.required_by.join(", ")));
+        }
+        println!("{}", why_depends(runner, &root, &dep.path)?);
+    }
+    if in_config && required {
+        println!("{}", t!("why.still_needed", pkg));
+    }
+    Ok(())
+}
+
+/// `declair prune`: remove packages declair added, refusing any entry without the marker.
+fn prune(config: &Config, args: &Args, runner: &SystemRunner, packages: &[String]) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let kind = rebuild_target(config, &nix_file, &git_repo)?.kind;
+    let scope = edit_scope(args.scope.as_ref(), kind)?;
+    let mut managed = list_managed_packages(&nix_file, &scope)?;
+
+    let selected: Vec<String> = if !packages.is_empty() {
+        if let Some(pkg) = packages.iter().find(|p| !managed.contains(p)) {
+            return Err(DeclairError::NotManaged {
+                pkg: pkg.clone(),
+                file: nix_file,
+            });
+        }
+        packages.to_vec()
+    } else if managed.is_empty() {
+        println!("{}", t!("prune.none", nix_file.display()));
+        return Ok(());
+    } else if args.no_interactive {
+        return Err(DeclairError::Config(t!("prune.no_packages_noninteractive")));
+    } else {
+        // Pinned packages are only pruned by name, with `--force`.
+        let state = State::load(&state_file_path()?)?;
+        managed.retain(|p| !state.is_pinned(p));
+        if managed.is_empty() {
+            println!("{}", t!("prune.all_pinned"));
+            return Ok(());
+        }
+        let picked = MultiSelect::with_theme(prompt_theme())
+            .with_prompt(t!("prune.prompt"))
+            .items(&managed)
+            .interact()?;
+        picked.into_iter().map(|i| managed[i].clone()).collect()
+    };
+    if selected.is_empty() {
+        println!("{}", t!("nothing_selected"));
+        return Ok(());
+    }
+    edit_packages(config, args, runner, &selected, BatchAction::Remove)
+}
+
+/// `declair group`: expand a named group into a batch add or remove, or list the groups.
+fn group(config: &Config, args: &Args, runner: &SystemRunner, action: &GroupAction) -> Result<()> {
+    let packages_of = |name: &str| {
+        config
+            .groups
+            .get(name)
+            .map(|g| g.packages.clone())
+            .ok_or_else(|| DeclairError::Config(t!("group.not_found", name)))
+    };
+    match action {
+        GroupAction::Add { name } => {
+            edit_packages(config, args, runner, &packages_of(name)?, BatchAction::Add)
+        }
+        GroupAction::Remove { name } => edit_packages(
+            config,
+            args,
+            runner,
+            &packages_of(name)?,
+            BatchAction::Remove,
+        ),
+        GroupAction::Import {
+            source,
+            name,
+            force,
+        } => import_groups(runner, source, name.as_deref(), *force),
+        GroupAction::List => {
+            let rows: Vec<Vec<String>> = config
+                .groups
+                .iter()
+                .map(|(name, g)| vec![name.clone(), g.packages.join(" ")])
+                .collect();
+            println!("{}", render(args.output, &["group", "packages"], &rows));
+            Ok(())
+        }
+    }
+}
+
+/// `declair overlay`: list, add or remove overlays. In a flake config the overlay comes
+/// from a flake input of the same name, which is added or removed along with it.
+fn overlay(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    action: &OverlayAction,
+) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let flake = flake_file(&git_repo, &nix_file);
+    let name = match action {
+        OverlayAction::List => {
+            let overlays = list_overlays(&nix_file)?;
+            if overlays.is_empty() && args.output == OutputFormat::Table {
+                println!("{}", t!("overlay.none", nix_file.display()));
+                return Ok(());
+            }
+            let rows: Vec<Vec<String>> =
+                overlays.into_iter().map(|o| vec![o.name, o.expr]).collect();
+            println!("{}", render(args.output, &["name", "overlay"], &rows));
+            return Ok(());
+        }
+        OverlayAction::Add { name, .. } | OverlayAction::Remove { name } => name,
+    };
+    let ctx = HookContext::new("overlay", vec![name.clone()], &nix_file);
+    run_phase(runner, &config.hooks, Phase::PreEdit, &ctx, None)?;
+
+    let mut changes = ChangeSet::new();
+    match action {
+        OverlayAction::Add { name, url } => {
+            validate_overlay(name, url)?;
+            let expr = overlay_expr(name, url, config.flake);
+            add_overlay(changes.stage(&nix_file)?, name, &expr)?;
+            if config.flake {
+                add_flake_input(changes.stage(&flake)?, name, url)?;
+            }
+        }
+        OverlayAction::Remove { name } => {
+            remove_overlay(changes.stage(&nix_file)?, name)?;
+            if config.flake && flake.exists() {
+                remove_flake_input(changes.stage(&flake)?, name)?;
+            }
+        }
+        OverlayAction::List => unreachable!(),
+    }
+
+    let pending = changes.changes()?;
+    let accepted = review_changes(&pending, args.no_interactive)?;
+    let written = changes.commit(runner, |path| accepted.iter().any(|a| a == path))?;
+    if !written.contains(&nix_file) {
+        println!("{}", t!("batch.none_applied"));
+        return Ok(());
+    }
+    match action {
+        OverlayAction::Add { name, .. } => {
+            println!("{}", t!("overlay.added", name, nix_file.display()));
+            if config.flake {
+                println!("{}", t!("overlay.flake_hint", name));
+            }
+        }
+        _ => println!("{}", t!("overlay.removed", name, nix_file.display())),
+    }
+    post_edit_hooks(runner, config, &ctx);
+    offer_gitignore(args, &written);
+    log_transaction(&ctx, rebuilding(config, args, runner, &written));
+    if config.auto_rebuild && !args.no_rebuild {
+        let target = rebuild_target(config, &nix_file, &git_repo)?;
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        rebuild_edited(config, args, runner, &target, &git_repo, &ctx, &written)?;
+    }
+    commit_edit(config, args, runner, &git_repo, &ctx, &written);
+    Ok(())
+}
+
+/// `declair env`: list, set or unset the environment variables of the config file's kind.
+fn env_vars(config: &Config, args: &Args, runner: &SystemRunner, action: &EnvAction) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(config, &nix_file, &git_repo)?;
+    let name = match action {
+        EnvAction::List => {
+            let variables = list_variables(&nix_file, target.kind)?;
+            if variables.is_empty() && args.output == OutputFormat::Table {
+                println!("{}", t!("env.none", nix_file.display()));
+                return Ok(());
+            }
+            let rows: Vec<Vec<String>> = variables
+                .into_iter()
+                .map(|v| vec![v.name, v.value])
+                .collect();
+            println!("{}", render(args.output, &["name", "value"], &rows));
+            return Ok(());
+        }
+        EnvAction::Set { name, .. } | EnvAction::Unset { name } => name,
+    };
+    validate_variable(name)?;
+    let ctx = HookContext::new("env", vec![name.clone()], &nix_file);
+    run_phase(runner, &config.hooks, Phase::PreEdit, &ctx, None)?;
+
+    let mut changes = ChangeSet::new();
+    let previous = match action {
+        EnvAction::Set { name, value } => {
+            set_variable(changes.stage(&nix_file)?, target.kind, name, value)?
+        }
+        EnvAction::Unset { name } => {
+            unset_variable(changes.stage(&nix_file)?, target.kind, name)?;
+            None
+        }
+        EnvAction::List => unreachable!(),
+    };
+
+    let pending = changes.changes()?;
+    let accepted = review_changes(&pending, args.no_interactive)?;
+    let written = changes.commit(runner, |path| accepted.iter().any(|a| a == path))?;
+    if !written.contains(&nix_file) {
+        println!("{}", t!("batch.none_applied"));
+        return Ok(());
+    }
+    match (action, previous) {
+        (EnvAction::Unset { .. }, _) => println!("{}", t!("env.unset", name, nix_file.display())),
+        (_, Some(previous)) => {
+            println!("{}", t!("env.updated", name, nix_file.display(), previous));
+        }
+        (_, None) => println!("{}", t!("env.set", name, nix_file.display())),
+    }
+    post_edit_hooks(runner, config, &ctx);
+    offer_gitignore(args, &written);
+    log_transaction(&ctx, rebuilding(config, args, runner, &written));
+    if config.auto_rebuild && !args.no_rebuild {
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        rebuild_edited(config, args, runner, &target, &git_repo, &ctx, &written)?;
+    }
+    commit_edit(config, args, runner, &git_repo, &ctx, &written);
+    Ok(())
+}
+
+/// `declair service`: enable or disable a service, or list the enabled ones. Enabling a
+/// service NixOS has no option for is an error unless `--force` is given; Home Manager's
+/// options are not checked.
+fn service(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    action: &ServiceAction,
+) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(config, &nix_file, &git_repo)?;
+    let name = match action {
+        ServiceAction::List => {
+            let services = list_services(&nix_file)?;
+            if services.is_empty() && args.output == OutputFormat::Table {
+                println!("{}", t!("service.none", nix_file.display()));
+                return Ok(());
+            }
+            let rows: Vec<Vec<String>> = services
+                .into_iter()
+                .map(|s| vec![s.name, s.value])
+                .collect();
+            println!("{}", render(args.output, &["name", "enable"], &rows));
+            return Ok(());
+        }
+        ServiceAction::Enable { name } | ServiceAction::Disable { name } => name,
+    };
+    validate_service(name)?;
+    let option = enable_option(name);
+    if matches!(action, ServiceAction::Enable { .. })
+        && target.kind == ModuleKind::Nixos
+        && !args.force
+    {
+        println!("{}", t!("service.checking", option));
+        let nixpkgs = Nixpkgs::for_config(config, &git_repo, &nix_file);
+        match service_exists(runner, &nixpkgs, name) {
+            Ok(true) => {}
+            Ok(false) => return Err(DeclairError::OptionNotFound { option }),
+            Err(e) => eprintln!(
+                "{}",
+                theme().warning(t!("service.check_skipped", e)).for_stderr()
+            ),
+        }
+    }
+    let ctx = HookContext::new("service", vec![name.clone()], &nix_file);
+    run_phase(runner, &config.hooks, Phase::PreEdit, &ctx, None)?;
+
+    let mut changes = ChangeSet::new();
+    match action {
+        ServiceAction::Enable { name } => enable_service(changes.stage(&nix_file)?, name)?,
+        ServiceAction::Disable { name } => disable_service(changes.stage(&nix_file)?, name)?,
+        ServiceAction::List => unreachable!(),
+    }
+
+    let pending = changes.changes()?;
+    let accepted = review_changes(&pending, args.no_interactive)?;
+    let written = changes.commit(runner, |path| accepted.iter().any(|a| a == path))?;
+    if !written.contains(&nix_file) {
+        println!("{}", t!("batch.none_applied"));
+        return Ok(());
+    }
+    match action {
+        ServiceAction::Disable { .. } => {
+            println!("{}", t!("service.disabled", option, nix_file.display()));
+        }
+        _ => println!("{}", t!("service.enabled", option, nix_file.display())),
+    }
+    post_edit_hooks(runner, config, &ctx);
+    offer_gitignore(args, &written);
+    log_transaction(&ctx, rebuilding(config, args, runner, &written));
+    if config.auto_rebuild && !args.no_rebuild {
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        rebuild_edited(config, args, runner, &target, &git_repo, &ctx, &written)?;
+    }
+    commit_edit(config, args, runner, &git_repo, &ctx, &written);
+    Ok(())
+}
+
+/// Ask for the value of `option` (as `name`, with its placeholders filled in): a choice
+/// for booleans and enums, otherwise a Nix expression checked against the option's type.
+fn prompt_option_value(name: &str, option: &OptionInfo, current: Option<String>) -> Result<String> {
+    let prompt = t!("option.value_prompt", name, option.type_name);
+    if let Some(choices) = choices(&option.type_name) {
+        let selected = current
+            .as_deref()
+            .and_then(|current| choices.iter().position(|c| c == current.trim()))
+            .unwrap_or(0);
+        let choice = Select::with_theme(prompt_theme())
+            .with_prompt(prompt)
+            .items(&choices)
+            .default(selected)
+            .interact()?;
+        return Ok(choices[choice].clone());
+    }
+    Ok(Input::with_theme(prompt_theme())
+        .with_prompt(prompt)
+        .with_initial_text(current.unwrap_or_default())
+        .validate_with(|value: &String| {
+            check_value(name, value, Some(&option.type_name)).map_err(|e| e.to_string())
+        })
+        .interact_text()?)
+}
+
+/// `declair options`: search the options of the configuration's modules and offer to set
+/// one of the results, or set one directly. Setting an option that isn't declared is an
+/// error unless `--force` is given; a failed evaluation only warns.
+fn options(
+    config: &Config,
+    args: &Args,
+    runner: &SystemRunner,
+    action: &OptionsAction,
+) -> Result<()> {
+    let nix_file = target_nix_file(config, args.no_interactive)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(config, &nix_file, &git_repo)?;
+    let tree = OptionTree::for_config(config, &git_repo, &nix_file, target.kind);
+    let (name, value) = match action {
+        OptionsAction::Search { query, matching } => {
+            let query = Query::new(query, matching.mode())?;
+            if args.output == OutputFormat::Table {
+                println!("{}", t!("option.evaluating", target.kind));
+            }
+            let mut found: Vec<OptionInfo> = list_options(runner, &tree)?
+                .into_iter()
+                .filter(|o| {
+                    let last = o.name.rsplit('.').next().unwrap_or(&o.name);
+                    query.matches_names(&[&o.name, last], o.description.as_deref())
+                })
+                .collect();
+            found.sort_by(|a, b| a.name.cmp(&b.name));
+            if found.is_empty() && args.output == OutputFormat::Table {
+                println!("{}", t!("option.none_found", query.text));
+                return Ok(());
+            }
+            let one_line = |text: &Option<String>| {
+                text.as_deref()
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            let rows: Vec<Vec<String>> = found
+                .iter()
+                .map(|o| {
+                    vec![
+                        o.name.clone(),
+                        o.type_name.clone(),
+                        one_line(&o.default),
+                        one_line(&o.description),
+                    ]
+                })
+                .collect();
+            println!(
+                "{}",
+                render(
+                    args.output,
+                    &["name", "type", "default", "description"],
+                    &rows
+                )
+            );
+            if args.no_interactive || args.output != OutputFormat::Table {
+                return Ok(());
+            }
+            let names: Vec<&str> = found.iter().map(|o| o.name.as_str()).collect();
+            let Some(choice) = Select::with_theme(prompt_theme())
+                .with_prompt(t!("option.pick_prompt"))
+                .items(&names)
+                .default(0)
+                .interact_opt()?
+            else {
+                println!("{}", t!("nothing_selected"));
+                return Ok(());
+            };
+            let option = &found[choice];
+            // Placeholders like `<name>` have to be filled in.
+            let name: String = Input::with_theme(prompt_theme())
+                .with_prompt(t!("option.name_prompt"))
+                .with_initial_text(option.name.clone())
+                .validate_with(|name: &String| {
+                    validate_option_name(name).map_err(|e| e.to_string())
+                })
+                .interact_text()?;
+            let current = option_value(&nix_file, &name)?.or(option.default.clone());
+            let value = prompt_option_value(&name, option, current)?;
+            (name, value)
+        }
+        OptionsAction::Set { name, value } => {
+            validate_option_name(name)?;
+            check_value(name, value, None)?;
+            if !args.force {
+                match list_options(runner, &tree) {
+                    Ok(options) => {
+                        let option = find_option(&options, name).ok_or_else(|| {
+                            DeclairError::OptionNotFound {
+                                option: name.clone(),
+                            }
+                        })?;
+                        check_value(name, value, Some(&option.type_name))?;
+                    }
+                    Err(e) => eprintln!(
+                        "{}",
+                        theme().warning(t!("service.check_skipped", e)).for_stderr()
+                    ),
                 }
-                return Some(completed);
             }
+            (name.clone(), value.clone())
         }
-        None
+    };
+
+    let ctx = HookContext::new("options", vec![name.clone()], &nix_file);
+    run_phase(runner, &config.hooks, Phase::PreEdit, &ctx, None)?;
+    let mut changes = ChangeSet::new();
+    let previous = set_option(changes.stage(&nix_file)?, &name, &value)?;
+    let pending = changes.changes()?;
+    let accepted = review_changes(&pending, args.no_interactive)?;
+    let written = changes.commit(runner, |path| accepted.iter().any(|a| a == path))?;
+    if !written.contains(&nix_file) {
+        println!("{}", t!("batch.none_applied"));
+        return Ok(());
+    }
+    let value = value.trim();
+    match previous {
+        Some(previous) => println!(
+            "{}",
+            t!("option.updated", name, value, nix_file.display(), previous)
+        ),
+        None => println!("{}", t!("option.set", name, value, nix_file.display())),
+    }
+    post_edit_hooks(runner, config, &ctx);
+    offer_gitignore(args, &written);
+    log_transaction(&ctx, rebuilding(config, args, runner, &written));
+    if config.auto_rebuild && !args.no_rebuild {
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        rebuild_edited(config, args, runner, &target, &git_repo, &ctx, &written)?;
+    }
+    commit_edit(config, args, runner, &git_repo, &ctx, &written);
+    Ok(())
+}
+
+/// `declair group import`: fetch, validate and store shared groups in declair's config.
+fn import_groups(
+    runner: &SystemRunner,
+    source: &str,
+    name: Option<&str>,
+    force: bool,
+) -> Result<()> {
+    let (contents, stem) = fetch_groups_source(runner, source)?;
+    let groups = parse_shared_groups(&contents, name.unwrap_or(&stem))?;
+    // Re-read the stored config so a `--config` override isn't saved along with the groups.
+    let mut config = read_or_create_config(true)?;
+    if !force && let Some(existing) = groups.keys().find(|n| config.groups.contains_key(*n)) {
+        return Err(DeclairError::InvalidGroup {
+            name: existing.clone(),
+            reason: t!("group.exists"),
+        });
+    }
+
+    for (name, mut group) in groups {
+        println!("{}", t!("group.imported", name, group.packages.join(" ")));
+        group.source = Some(source.to_string());
+        config.groups.insert(name, group);
+    }
+    save_config(&config)
+}
+
+/// `declair search`: print matching packages, sorted by name, for reading or piping.
+/// The `installed` column shows whether the configuration (when one is set up) already
+/// lists each package.
+fn search(
+    runner: &SystemRunner,
+    config: &Config,
+    query: &Query,
+    status: StatusArgs,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut results = search_configured(runner, config, query)?;
+    let repo = expand_path(config.nix_path.trim())
+        .and_then(|path| resolve_nix_config(&path))
+        .and_then(|file| get_git_repo_or_parent_directory(&file));
+    match repo {
+        Ok(repo) => mark_installed(&mut results, &installed_packages(&repo)),
+        Err(e) if status.is_set() => return Err(e),
+        Err(_) => {}
+    }
+    let mut pkgs: Vec<PackageInfo> = results.into_values().filter(|p| status.keep(p)).collect();
+    pkgs.sort_by(|a, b| a.name().cmp(b.name()));
+    let rows: Vec<Vec<String>> = pkgs
+        .into_iter()
+        .map(|p| {
+            vec![
+                p.name().to_string(),
+                p.version.clone(),
+                p.description.clone().unwrap_or_default(),
+                if p.installed { "yes" } else { "no" }.to_string(),
+            ]
+        })
+        .collect();
+    page(&format!(
+        "{}\n",
+        render(
+            output,
+            &["name", "version", "description", "installed"],
+            &rows
+        )
+    ));
+    Ok(())
+}
+
+/// `declair provides`: list the packages providing `command` and offer to add one.
+/// Only prints the list with `--no-interactive`, `--dry-run` or a machine-readable `--output`.
+fn provides(config: &Config, args: &Args, runner: &SystemRunner, command: &str) -> Result<()> {
+    let found = providers(runner, command)?;
+    if found.is_empty() {
+        return Err(DeclairError::SearchFailed(t!("provides.none", command)));
+    }
+    if args.no_interactive || args.dry_run || args.output != OutputFormat::Table {
+        let rows: Vec<Vec<String>> = found.into_iter().map(|p| vec![p]).collect();
+        println!("{}", render(args.output, &["package"], &rows));
+        return Ok(());
+    }
+    let Some(choice) = Select::with_theme(prompt_theme())
+        .with_prompt(t!("provides.prompt", command))
+        .items(&found)
+        .default(0)
+        .interact_opt()?
+    else {
+        println!("{}", t!("nothing_selected"));
+        return Ok(());
+    };
+    edit_packages(
+        config,
+        args,
+        runner,
+        std::slice::from_ref(&found[choice]),
+        BatchAction::Add,
+    )
+}
+
+/// `declair fix`: find the command missing from `message` (or the shell history) and
+/// offer the packages providing it, best match first.
+fn fix(config: &Config, args: &Args, runner: &SystemRunner, message: Option<&str>) -> Result<()> {
+    let command = match message {
+        Some(message) => missing_command(message)
+            .ok_or_else(|| DeclairError::Selection(t!("fix.no_missing_command", message)))?,
+        None => history_command().ok_or_else(|| DeclairError::Selection(t!("fix.no_history")))?,
+    };
+    println!("{}", t!("fix.looking", command));
+    provides(config, args, runner, &command)
+}
+
+/// `declair doctor`: report what declair found out about the Nix installation.
+fn doctor(runner: &SystemRunner) -> Result<()> {
+    match HostOs::detect() {
+        HostOs::Wsl { distro } => {
+            let distro = distro.unwrap_or_else(|| t!("doctor.wsl_unknown_distro"));
+            let key = if is_nixos() {
+                "doctor.wsl_nixos"
+            } else {
+                "doctor.wsl"
+            };
+            println!("{} {}", theme().success("✓"), t!(key, distro));
+        }
+        HostOs::Windows => {
+            let distros = wsl_distributions(runner);
+            if distros.is_empty() {
+                println!("{} {}", theme().error("✗"), t!("doctor.windows_no_wsl"));
+            }
+            for distro in distros {
+                let key = if is_nixos_distribution(&distro) {
+                    "doctor.windows_wsl_nixos"
+                } else {
+                    "doctor.windows_wsl"
+                };
+                println!("{} {}", theme().warning("!"), t!(key, distro, distro));
+            }
+        }
+        _ => {}
+    }
+    let Some(version) = nix_version(runner) else {
+        println!("{} {}", theme().error("✗"), t!("doctor.no_nix"));
+        return Ok(());
+    };
+    println!("{} Nix {}", theme().success("✓"), version);
+
+    let features = experimental_features(runner)?;
+    if features.is_empty() {
+        println!("{} {}", theme().warning("!"), t!("doctor.no_features"));
+    } else {
+        println!(
+            "{} {}",
+            theme().success("✓"),
+            t!("doctor.features", features.join(" "))
+        );
     }
-}
 
-/// Expand leading "~" in a path (if present).
-fn expand_tilde(path: &str) -> Result<PathBuf, Box<dyn Error>> {
-    if path.starts_with("~/") {
-        let home_dir = home_dir().ok_or("Failed to get home directory")?;
-        let rest_of_path = path.trim_start_matches("~/");
-        let expanded_path = home_dir.join(rest_of_path);
-        Ok(expanded_path)
+    if version < FLAKE_SEARCH_VERSION {
+        println!(
+            "{} Nix older than {} has no flake-based `nix search`; searching with `nix-env -qa`",
+            theme().warning("!"),
+            FLAKE_SEARCH_VERSION
+        );
     } else {
-        Ok(PathBuf::from(path))
+        println!(
+            "{} Searching with `nix search` (falls back to `nix-env -qa` if it fails)",
+            theme().success("✓")
+        );
     }
+    Ok(())
 }
 
-fn get_git_repo_or_parent_directory(path: &PathBuf) -> Result<PathBuf, Box<dyn Error>> {
-    // Check if path exists
-    if !path.exists() {
-        return Err("Path does not exist".into());
+/// `declair status`: whether the system runs what the configuration says, from declair's
+/// transaction log, the files changed since the last rebuild and the active generation.
+fn status(config: &Config) -> Result<()> {
+    let nix_file = resolve_nix_config(&expand_path(config.nix_path.trim())?)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(config, &nix_file, &git_repo)?;
+    let state = State::load(&state_file_path()?)?;
+    let status = declair_rs::status::status(&state, &git_repo, current_generation(target.kind));
+    let shown = |path: &Path| {
+        path.strip_prefix(&git_repo)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+
+    println!("{}", t!("status.config", git_repo.display(), target));
+    for file in module_tree(&nix_file)? {
+        println!("  {}", shown(&file));
     }
-    // Try to find a repository
-    match discover(path) {
-        Ok(repo) => {
-            // Found repository - return its working directory
-            let workdir = repo
-                .workdir()
-                .ok_or("Repository has no working directory")?;
-            Ok(workdir.to_path_buf())
+    match &status.last_rebuild {
+        Some(rebuild) => println!(
+            "{}",
+            t!("status.last_rebuild", rebuild.at_display(), rebuild.target)
+        ),
+        None => println!("{}", t!("status.never_rebuilt")),
+    }
+    match &status.generation {
+        GenerationStatus::Matches => {
+            println!(
+                "{} {}",
+                theme().success("✓"),
+                t!("status.generation_matches")
+            )
         }
-        Err(_) => {
-            // Repository not found
-            if path.is_dir() {
-                // Return the path itself (directory)
-                Ok(path.to_path_buf())
-            } else {
-                // It's a file - return its parent directory
-                let parent = path.parent().ok_or("Cannot get parent directory")?;
-                Ok(parent.to_path_buf())
-            }
+        GenerationStatus::Switched { current } => println!(
+            "{} {}",
+            theme().warning("!"),
+            t!("status.generation_switched", current.display())
+        ),
+        GenerationStatus::Unknown => println!("{}", t!("status.generation_unknown")),
+    }
+    if !status.pending.is_empty() {
+        println!("{}", t!("status.pending"));
+        for edit in &status.pending {
+            println!(
+                "  {}  {} {} ({})",
+                edit.at_display(),
+                edit.action,
+                edit.packages.join(" "),
+                shown(&edit.file)
+            );
         }
     }
-}
-
-/// If given path is a directory, try to find a likely NixOS config file inside it.
-/// Returns an error if nothing suitable is found.
-fn resolve_nix_config(path: &Path) -> Result<PathBuf, String> {
-    if path.exists() && path.is_file() {
-        return Ok(path.to_path_buf());
-    } else if path.exists() && path.is_dir() {
-        let candidates = [
-            "configuration.nix",
-            "flake.nix",
-            "default.nix",
-            "home.nix",
-            "pkgs.nix",
-        ];
-        for cand in &candidates {
-            let p = path.join(cand);
-            if p.exists() && p.is_file() {
-                return Ok(p);
-            }
+    if !status.changed_files.is_empty() {
+        println!("{}", t!("status.changed"));
+        for file in &status.changed_files {
+            println!("  {}", shown(file));
         }
-        return Err(format!(
-            "The specified directory `{}` does not contain any of the expected files: {}",
-            path.display(),
-            candidates.join(", ")
-        ));
     }
-    Err(format!("File or directory `{}` not found.", path.display()))
-}
-
-fn get_config_dir() -> Option<PathBuf> {
-    let proj_dirs = ProjectDirs::from("com", "timasoft", "declair")?;
-    Some(proj_dirs.config_dir().to_path_buf())
-}
-
-/// Read existing program config or interactively create it.
-/// Respects `--no-interactive` from Args.
-fn read_or_create_config(args: &Args) -> Result<Config, Box<dyn Error>> {
-    let config_dir = get_config_dir().ok_or("Failed to get config directory")?;
-    let config_path = config_dir.join("config.toml");
-    if config_path.exists() {
-        let contents = fs::read_to_string(&config_path)?;
-        let cfg: Config = toml::from_str(&contents)?;
-        Ok(cfg)
+    if status.rebuild_pending() {
+        println!("{} {}", theme().warning("!"), t!("status.rebuild_pending"));
     } else {
-        if args.no_interactive {
-            return Err("Config file not found and --no-interactive specified".into());
-        }
-        fs::create_dir_all(&config_dir)?;
-        let completion = FileCompletion;
-        let nix_path: String = Input::new()
-            .with_prompt("Enter the path to your NixOS configuration file (with 'with pkgs; [')")
-            .completion_with(&completion)
-            .interact_text()?;
-        let auto_rebuild: bool = Confirm::new()
-            .with_prompt("Automatically rebuild NixOS after adding a package?")
-            .default(false)
-            .interact()?;
-        let (home_manager, flake) = if auto_rebuild {
-            (
-                Confirm::new()
-                    .with_prompt("Use Home Manager as a NixOS configuration?")
-                    .default(false)
-                    .interact()?,
-                Confirm::new()
-                    .with_prompt("Use a flake as a NixOS configuration?")
-                    .default(false)
-                    .interact()?,
-            )
-        } else {
-            (false, false)
-        };
-        let cfg = Config {
-            nix_path,
-            auto_rebuild,
-            home_manager,
-            flake,
-        };
-        fs::write(&config_path, toml::to_string(&cfg)?)?;
-        Ok(cfg)
+        println!("{} {}", theme().success("✓"), t!("status.up_to_date"));
     }
+    Ok(())
 }
 
-#[derive(Deserialize)]
-struct PackageInfo {
-    pname: String,
-    version: String,
-    description: Option<String>,
-}
-
-/// Search for a package via `nix search`
-fn search_packages(query: &str) -> Result<HashMap<String, PackageInfo>, String> {
-    let output = Command::new("nix")
-        .args([
-            "search",
-            "nixpkgs",
-            query,
-            "--json",
-            "--extra-experimental-features",
-            "nix-command flakes",
-        ])
-        .output()
-        .map_err(|e| format!("Failed to run `nix search`: {}", e))?;
-    if !output.status.success() {
-        return Err("Error while running `nix search` (non-zero exit code)".to_string());
-    }
-    from_slice(&output.stdout).map_err(|e| format!("JSON parsing error: {}", e))
-}
-
-/// Add a package to NixOS config (input — already valid file path)
-fn add_package_to_nix(file_path: &Path, pkg: &str) -> Result<(), Box<dyn Error>> {
-    let file = fs::File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let mut lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
-    // make backup (overwrite if already exists)
-    fs::copy(file_path, file_path.with_extension("declair.bak"))?;
-    // find start and end of "with pkgs; [" block
-    if let Some(start_idx) = lines
+/// `declair drift`: the configured packages missing from the running generation and, with
+/// `all` or among the packages declair has seen, the ones it has that the configuration
+/// no longer lists. Offers to rebuild when there are any.
+fn drift(config: &Config, args: &Args, runner: &SystemRunner, all: bool, yes: bool) -> Result<()> {
+    let nix_file = resolve_nix_config(&expand_path(config.nix_path.trim())?)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(config, &nix_file, &git_repo)?;
+    let configured: BTreeSet<String> = module_tree(&nix_file)?
         .iter()
-        .position(|l: &String| l.contains("with pkgs; ["))
-        && let Some(end_idx_rel) = lines[start_idx..]
-            .iter()
-            .position(|l: &String| l.contains(']'))
-    {
-        let end_idx = start_idx + end_idx_rel;
-        // find line with pkg
-        for line in lines[start_idx..end_idx].iter() {
-            if line.contains(pkg) {
-                return Err(format!("Package `{}` is already in the config", pkg).into());
-            }
-        }
-        // clone the line and indentation BEFORE mutations, to avoid borrow issues
-        let end_line = lines[end_idx].clone();
-        // three cases (simplified but reliable logic)
-        if start_idx == end_idx {
-            // everything in one line, e.g.: with pkgs; []
-            if end_line.contains("[]") {
-                lines[start_idx] = end_line.replace("[]", &format!("[ {} ]", pkg));
-            } else if end_line.contains(" ]") {
-                lines[start_idx] = end_line.replace("]", &format!("{} ]", pkg));
-            } else {
-                lines[start_idx] = end_line.replace("]", &format!(" {} ]", pkg));
+        .filter_map(|file| list_packages(file, target.kind).ok())
+        .flatten()
+        .collect();
+    let root = profile_root(target.kind)?;
+    let installed: BTreeSet<String> = profile_packages(runner, &root)?
+        .iter()
+        .map(|path| package_name(store_name(path)).to_string())
+        .collect();
+    let tracked = (!all).then(|| {
+        let state = state_file_path()
+            .and_then(|path| State::load(&path))
+            .unwrap_or_default();
+        state
+            .packages
+            .keys()
+            .cloned()
+            .chain(state.transactions.into_iter().flat_map(|t| t.packages))
+            .collect::<BTreeSet<String>>()
+    });
+    let drift = declair_rs::drift::drift(&configured, &installed, tracked.as_ref());
+
+    if args.output != OutputFormat::Table {
+        let rows: Vec<Vec<String>> = [("config", &drift.missing), ("system", &drift.extra)]
+            .into_iter()
+            .flat_map(|(side, pkgs)| pkgs.iter().map(move |p| vec![p.clone(), side.to_string()]))
+            .collect();
+        println!("{}", render(args.output, &["package", "only_in"], &rows));
+    } else if drift.is_empty() {
+        println!(
+            "{} {}",
+            theme().success("✓"),
+            t!("drift.none", root.display())
+        );
+    } else {
+        if !drift.missing.is_empty() {
+            println!("{}", t!("drift.missing", drift.missing.len()));
+            for pkg in &drift.missing {
+                println!("  {}", theme().added(format!("+ {}", pkg)));
             }
-        } else {
-            // multiline case
-            let indent: String = end_line.chars().take_while(|c| c.is_whitespace()).collect();
-            lines.insert(end_idx, format!("{}{}{}", indent, indent, pkg));
         }
-    } else {
-        return Err("Failed to find `with pkgs; [...]` block in the given file.".into());
+        if !drift.extra.is_empty() {
+            println!("{}", t!("drift.extra", drift.extra.len()));
+            for pkg in &drift.extra {
+                println!("  {}", theme().removed(format!("- {}", pkg)));
+            }
+        }
+    }
+    if drift.is_empty() || args.dry_run {
+        return Ok(());
+    }
+    let rebuild_now = yes
+        || (!args.no_interactive
+            && Confirm::with_theme(prompt_theme())
+                .with_prompt(t!("drift.rebuild_prompt"))
+                .default(true)
+                .interact()?);
+    if rebuild_now {
+        require_unix("rebuild")?;
+        println!("{}", t!("rebuild.rebuilding", target.kind));
+        let ctx = HookContext::new("rebuild", Vec::new(), &nix_file);
+        rebuild_with_hooks(runner, config, &target, &git_repo, &ctx)?;
+        record_rebuild(&target);
     }
-    fs::write(file_path, lines.join("\n"))?;
     Ok(())
 }
 
-/// Adds `programs.<program>.enable = true;` into the given Nix configuration file.
-fn add_program_to_nix(file_path: &Path, pattern: &str) -> Result<(), Box<dyn Error>> {
-    // Read the whole file into a string.
-    let mut contents = fs::read_to_string(file_path)
-        .map_err(|e| format!("Failed to read {}: {}", file_path.display(), e))?;
-
-    // Write a backup copy (overwrite if already exists).
-    fs::write(file_path.with_extension("declair.bak"), &contents).map_err(|e| {
-        format!(
-            "Failed to create backup `{}`: {}",
-            file_path.with_extension("declair.bak").display(),
-            e
-        )
-    })?;
-
-    if contents.contains(pattern) {
-        return Err(format!("Configuration already contains `{}`", pattern).into());
-    }
-
-    // Try to locate the insertion point. We will insert before the last '}' in the file.
-    let insert_pos = contents
-        .rfind('}')
-        .ok_or("Failed to find a closing '}' in the configuration file; cannot insert")?;
-
-    // Determine the indentation of the line containing the chosen '}'.
-    // Find the start of that line (last newline before insert_pos).
-    let line_start = contents[..insert_pos]
-        .rfind('\n')
-        .map(|i| i + 1)
-        .unwrap_or(0);
-
-    // Collect leading whitespace from the line to preserve indentation style.
-    let mut indent = String::new();
-    for ch in contents[line_start..insert_pos].chars() {
-        if ch.is_whitespace() {
-            indent.push(ch);
-        } else {
-            break;
+/// `declair pin` and `declair unpin`: add `packages` to the pinned set, or take them out.
+fn pin(packages: &[String], pinned: bool) -> Result<()> {
+    let mut changed = Vec::new();
+    update_state(&state_file_path()?, |state| {
+        for pkg in packages {
+            let done = if pinned {
+                state.pin(pkg)
+            } else {
+                state.unpin(pkg)
+            };
+            changed.push(done);
         }
+    })?;
+    for (pkg, changed) in packages.iter().zip(changed) {
+        let key = match (pinned, changed) {
+            (true, true) => "pin.pinned",
+            (true, false) => "pin.already_pinned",
+            (false, true) => "pin.unpinned",
+            (false, false) => "pin.not_pinned",
+        };
+        println!("{}", t!(key, pkg));
     }
+    Ok(())
+}
 
-    // If indentation couldn't be inferred, fall back to two spaces.
-    if indent.is_empty() {
-        indent = "  ".to_string();
+/// `declair list-pins`: the pinned packages, one per row.
+fn list_pins(output: OutputFormat) -> Result<()> {
+    let state = State::load(&state_file_path()?)?;
+    if state.pinned.is_empty() && output == OutputFormat::Table {
+        println!("{}", t!("pin.none"));
+        return Ok(());
     }
-
-    // Build the insertion line. Use the inferred indent level.
-    // The inserted line will be placed directly before the final '}'.
-    let insertion = format!("{}{} = true;\n", indent, pattern);
-
-    // Insert the text at the computed position.
-    contents.insert_str(insert_pos, &insertion);
-
-    // Write the modified contents back to the file.
-    fs::write(file_path, contents).map_err(|e| {
-        format!(
-            "Failed to write updated configuration to `{}`: {}",
-            file_path.display(),
-            e
-        )
-    })?;
-
+    let rows: Vec<Vec<String>> = state.pinned.into_iter().map(|p| vec![p]).collect();
+    println!("{}", render(output, &["package"], &rows));
     Ok(())
 }
 
-/// List packages found in `with pkgs; [ ... ]` block of given file.
-fn list_packages(file_path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
-    let file = fs::File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
+/// Refuse to remove or replace pinned `packages` unless `--force` is given.
+fn check_pins<'a>(args: &Args, packages: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    if args.force {
+        return Ok(());
+    }
+    State::load(&state_file_path()?)?.ensure_unpinned(packages)
+}
 
-    if let Some(start_idx) = lines
-        .iter()
-        .position(|l: &String| l.contains("with pkgs; ["))
-        && let Some(end_idx_rel) = lines[start_idx..]
-            .iter()
-            .position(|l: &String| l.contains(']'))
-    {
-        let end_idx = start_idx + end_idx_rel;
-        let mut packages: Vec<String> = Vec::new();
-
-        if start_idx == end_idx {
-            // single-line case
-            let line = &lines[start_idx];
-            if let Some(lbr) = line.find('[')
-                && let Some(rbr) = line.rfind(']')
-            {
-                let inside = &line[lbr + 1..rbr];
-                for token in inside.split_whitespace() {
-                    if !token.trim().is_empty() {
-                        packages.push(token.trim().to_string());
-                    }
-                }
+/// `declair backups`: list, show or prune the backup history.
+fn backups(config: &Config, action: &BackupsAction, output: OutputFormat) -> Result<()> {
+    let history = get_history_dir().ok_or(DeclairError::NoHomeDir)?;
+    match action {
+        BackupsAction::List => {
+            let entries = list_backups(&history)?;
+            if entries.is_empty() && output == OutputFormat::Table {
+                println!("{}", t!("backups.none"));
+                return Ok(());
             }
-        } else {
-            // multiline case: lines between start_idx+1 .. end_idx-1
-            for l in &lines[start_idx + 1..end_idx] {
-                let trimmed = l.trim();
-                if trimmed.is_empty() {
-                    continue;
-                }
-                // take the first token on the line as package name
-                if let Some(tok) = trimmed.split_whitespace().next() {
-                    // skip lines that are just comments
-                    if tok.starts_with('#') || tok.starts_with("//") {
-                        continue;
+            let rows: Vec<Vec<String>> = entries
+                .iter()
+                .map(|entry| {
+                    vec![
+                        entry.id.clone(),
+                        entry.file.display().to_string(),
+                        format_time(entry.at),
+                        entry.size().to_string(),
+                    ]
+                })
+                .collect();
+            page(&format!(
+                "{}\n",
+                render(output, &["id", "file", "saved", "bytes"], &rows)
+            ));
+        }
+        BackupsAction::Show { id, contents } => {
+            let entry = find_backup(&history, id)?;
+            let before = entry.contents()?;
+            let current = fs::read_to_string(&entry.file);
+            match current {
+                Ok(after) if !contents => {
+                    if before == after {
+                        println!("{}", t!("backups.unchanged", entry.file.display()));
+                    } else {
+                        let change = FileChange {
+                            path: entry.file.clone(),
+                            before,
+                            after,
+                        };
+                        page(&format!("{}\n", theme().diff(&change.diff())));
                     }
-                    packages.push(tok.to_string());
                 }
+                _ => page(&before),
             }
         }
-        Ok(packages)
-    } else {
-        Err("Failed to find `with pkgs; [...]` block in the given file.".into())
+        BackupsAction::Prune {
+            max_backups,
+            max_age_days,
+        } => {
+            let settings = BackupSettings {
+                max_backups: max_backups.unwrap_or(config.backups.max_backups),
+                max_age_days: max_age_days.unwrap_or(config.backups.max_age_days),
+            };
+            let removed = prune_backups(&history, &settings, unix_now())?;
+            println!("{}", t!("backups.pruned", removed.len()));
+        }
     }
+    Ok(())
 }
 
-/// Remove a package from NixOS config (with backup). Does not perform rebuild itself.
-fn remove_package_from_nix(file_path: &Path, pkg: &str) -> Result<(), Box<dyn Error>> {
-    let file = fs::File::open(file_path)?;
-    let reader = BufReader::new(&file);
-    let mut lines: Vec<String> = reader.lines().collect::<Result<_, _>>()?;
-
-    // make backup (overwrite if already exists)
-    fs::copy(file_path, file_path.with_extension("declair.bak"))?;
+/// `declair paths`: where everything declair writes outside the configuration lives.
+fn paths(output: OutputFormat) -> Result<()> {
+    let rows: Vec<Vec<String>> = [
+        ("config", config_file_path().ok()),
+        ("system-config", Some(system_config_path())),
+        (
+            "project-config",
+            env::current_dir()
+                .ok()
+                .and_then(|cwd| find_project_config(&cwd)),
+        ),
+        ("install-records", state_file_path().ok()),
+        ("backups", get_backups_dir()),
+        ("backup-history", get_history_dir()),
+        ("rebuild-logs", get_logs_dir()),
+        ("nur-index", index_cache_path()),
+        ("eval-cache", cache_root()),
+        ("socket", default_socket_path().ok()),
+    ]
+    .into_iter()
+    .filter_map(|(name, path)| Some(vec![name.to_string(), path?.display().to_string()]))
+    .collect();
+    println!("{}", render(output, &["name", "path"], &rows));
+    Ok(())
+}
 
-    // find start and end of "with pkgs; [" block
-    if let Some(start_idx) = lines.iter().position(|l| l.contains("with pkgs; ["))
-        && let Some(end_idx_rel) = lines[start_idx..]
-            .iter()
-            .position(|l: &String| l.contains(']'))
-    {
-        let end_idx = start_idx + end_idx_rel;
-
-        if start_idx == end_idx {
-            // single-line case
-            let line = &lines[start_idx];
-            let lbr = line
-                .find('[')
-                .ok_or("Malformed `with pkgs; [ ... ]` line")?;
-            let rbr = line
-                .rfind(']')
-                .ok_or("Malformed `with pkgs; [ ... ]` line")?;
-            let inside = &line[lbr + 1..rbr];
-            let parts: Vec<&str> = inside
-                .split_whitespace()
-                .filter(|s| !s.is_empty())
-                .collect();
-            if !parts.contains(&pkg) {
-                return Err(format!("Package `{}` not found in the configuration", pkg).into());
-            }
-            let new_parts: Vec<&str> = parts.into_iter().filter(|&p| p != pkg).collect();
-            let new_inside = new_parts.join(" ");
-            let new_line = format!("{}[ {} ]", &line[..lbr], new_inside);
-            lines[start_idx] = new_line;
-        } else {
-            // multiline case
-            // find the index of the line that contains the package (first token matches)
-            let mut found_idx: Option<usize> = None;
-            for (i, l) in lines[start_idx + 1..end_idx].iter().enumerate() {
-                let trimmed = l.trim();
-                if trimmed.is_empty() {
-                    continue;
-                }
-                if let Some(first) = trimmed.split_whitespace().next()
-                    && first == pkg
-                {
-                    found_idx = Some(start_idx + 1 + i);
-                    break;
-                }
-            }
-            if found_idx.is_none() {
-                return Err(format!("Package `{}` not found in the configuration", pkg).into());
-            }
-            let remove_idx = found_idx.unwrap();
-            lines.remove(remove_idx);
-        }
-    } else {
-        return Err("Failed to find `with pkgs; [...]` block in the given file.".into());
+/// `declair completions`: write the completion script for `shell` to stdout.
+fn completions(shell: CompletionShell) {
+    let mut cmd = cli::command();
+    let name = cmd.get_name().to_string();
+    let mut out = io::stdout();
+    match shell {
+        CompletionShell::Bash => generate(Shell::Bash, &mut cmd, name, &mut out),
+        CompletionShell::Zsh => generate(Shell::Zsh, &mut cmd, name, &mut out),
+        CompletionShell::Fish => generate(Shell::Fish, &mut cmd, name, &mut out),
+        CompletionShell::Elvish => generate(Shell::Elvish, &mut cmd, name, &mut out),
+        CompletionShell::Powershell => generate(Shell::PowerShell, &mut cmd, name, &mut out),
+        CompletionShell::Nu => generate(Nushell, &mut cmd, name, &mut out),
     }
+}
 
-    fs::write(file_path, lines.join("\n"))?;
+/// `declair man`: print the man page, or with `dir` write those of every subcommand there.
+fn man(dir: Option<&Path>) -> Result<()> {
+    let Some(dir) = dir else {
+        print!("{}", man_page(cli::command()));
+        return Ok(());
+    };
+    let written = write_man_pages(cli::command(), dir).map_err(|e| DeclairError::Write {
+        path: dir.to_path_buf(),
+        source: e,
+    })?;
+    println!("{}", t!("man.written", written.len(), dir.display()));
     Ok(())
 }
 
-/// Removes a line like `programs.<program>.enable = true;` from the given Nix configuration file.
-fn remove_program_from_nix(file_path: &Path, pattern: &str) -> Result<(), Box<dyn Error>> {
-    // Read the file contents into a vector of lines.
-    let content = fs::read_to_string(file_path)
-        .map_err(|e| format!("Failed to read {}: {}", file_path.display(), e))?;
-    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+/// `declair daemon`: keep declair warm behind a socket for GUIs, bars and editor plugins.
+fn daemon(config: Config, runner: &SystemRunner, socket: Option<PathBuf>) -> Result<()> {
+    let socket = match socket {
+        Some(socket) => socket,
+        None => default_socket_path()?,
+    };
+    let nix_file = target_nix_file(&config, true)?;
+    let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    let target = rebuild_target(&config, &nix_file, &git_repo)?;
+    println!(
+        "{}",
+        t!("daemon.serving", nix_file.display(), socket.display())
+    );
+    Daemon::new(runner, config, nix_file, target, git_repo).serve(&socket)
+}
 
-    // Write a backup copy (overwrite if it already exists).
-    fs::write(file_path.with_extension("declair.bak"), &content).map_err(|e| {
-        format!(
-            "Failed to create backup `{}`: {}",
-            file_path.with_extension("declair.bak").display(),
-            e
-        )
-    })?;
+/// `declair rpc`: one-shot client for `declair daemon`.
+fn rpc(method: &str, params: Option<&str>, socket: Option<PathBuf>) -> Result<()> {
+    let socket = match socket {
+        Some(socket) => socket,
+        None => default_socket_path()?,
+    };
+    let params = match params {
+        Some(params) => serde_json::from_str(params)
+            .map_err(|e| DeclairError::Config(t!("daemon.invalid_params", e)))?,
+        None => serde_json::Value::Null,
+    };
+    let result = call_daemon(&socket, method, params)?;
+    println!("{}", result);
+    Ok(())
+}
 
-    // Try to find a line that matches the pattern.
-    let mut found_index: Option<usize> = None;
-    for (idx, raw_line) in lines.iter().enumerate() {
-        let trimmed = raw_line.trim();
-        // Skip empty lines and obvious comments.
-        if trimmed.is_empty() || trimmed.starts_with('#') {
+/// `declair --list --tree`: the package lists of every file in `repo`, grouped by file
+/// and by list. Machine-readable formats get one `file`, `list`, `package` row per entry.
+fn list_tree(repo: &Path, args: &Args) -> Result<()> {
+    let mut rows = Vec::new();
+    let mut files = Vec::new();
+    for file in nix_files(repo) {
+        let Ok(lists) = package_lists(&file) else {
             continue;
+        };
+        let shown = file
+            .strip_prefix(repo)
+            .unwrap_or(&file)
+            .display()
+            .to_string();
+        let mut list_nodes = Vec::new();
+        let mut count = 0;
+        for list in lists {
+            let pkgs: Vec<String> = list
+                .entries
+                .into_iter()
+                .filter(|(_, managed)| *managed || !args.managed_only)
+                .map(|(pkg, _)| pkg)
+                .collect();
+            if pkgs.is_empty() {
+                continue;
+            }
+            let name = if list.attr.is_empty() {
+                t!("list.unassigned", list.line)
+            } else {
+                list.attr
+            };
+            rows.extend(
+                pkgs.iter()
+                    .map(|p| vec![shown.clone(), name.clone(), p.clone()]),
+            );
+            count += pkgs.len();
+            let leaves = pkgs
+                .iter()
+                .map(|p| TreeNode::leaf(theme().package(p).to_string()))
+                .collect();
+            list_nodes.push(TreeNode::new(format!("{} ({})", name, pkgs.len()), leaves));
         }
-        // Check if the trimmed line starts with the token and contains '=' and ';'
-        if trimmed.starts_with(pattern) && trimmed.contains('=') {
-            // A simple sanity check: ensure it's likely an assignment like `... = true;`
-            // Accept variations of whitespace around '=' and an optional trailing semicolon.
-            // We'll treat this as a match and remove the entire line.
-            found_index = Some(idx);
-            break;
+        if !list_nodes.is_empty() {
+            let label = format!("{} ({})", theme().header(&shown), count);
+            files.push(TreeNode::new(label, list_nodes));
         }
     }
+    if args.output != OutputFormat::Table {
+        println!(
+            "{}",
+            render(args.output, &["file", "list", "package"], &rows)
+        );
+    } else if files.is_empty() {
+        println!("{}", t!("list.tree_empty", repo.display()));
+    } else {
+        page(&format!("{}\n", render_tree(&files)));
+    }
+    Ok(())
+}
 
-    // If not found, return an error.
-    let remove_idx = match found_index {
-        Some(i) => i,
-        None => {
-            return Err(format!(
-                "No entry `programs.{}.enable` found in `{}`",
-                pattern,
-                file_path.display()
-            )
-            .into());
-        }
-    };
+/// Terminal width from which fzf shows the details preview beside the result list.
+const FZF_SIDE_PREVIEW_WIDTH: u16 = 100;
+
+/// fzf `--preview` command showing `declair info` for the highlighted line's package.
+fn info_preview_command() -> String {
+    let exe = std::env::current_exe()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| "declair-rs".to_string());
+    format!("'{}' --color always info {{1}}", exe.replace('\'', "'\\''"))
+}
 
-    // Remove the matching line.
-    lines.remove(remove_idx);
+/// Search results sorted by name, so the selector lists them in a stable order.
+fn sorted_results(pkg_map: HashMap<String, PackageInfo>) -> Vec<PackageInfo> {
+    let mut pkgs: Vec<PackageInfo> = pkg_map.into_values().collect();
+    pkgs.sort_by(|a, b| a.name().cmp(b.name()));
+    pkgs
+}
 
-    // Optionally, also remove an immediately following blank line to keep the file tidy.
-    if remove_idx < lines.len() && lines[remove_idx].trim().is_empty() {
-        lines.remove(remove_idx);
+/// Tell which package sets have a package named `query`, in the order the selector lists
+/// them (see [`group_by_set`]).
+fn print_sets(sets: &[String], query: &str) {
+    if sets.is_empty() {
+        return;
     }
+    let names: Vec<String> = sets
+        .iter()
+        .map(|set| {
+            if set.is_empty() {
+                t!("select.top_level")
+            } else {
+                set.clone()
+            }
+        })
+        .collect();
+    eprintln!("{}", t!("select.sets", query, names.join(", ")));
+}
 
-    // Join lines back into a single string and write back to the file.
-    let new_content = lines.join("\n");
-    fs::write(file_path, &new_content).map_err(|e| {
-        format!(
-            "Failed to write updated configuration to `{}`: {}",
-            file_path.display(),
-            e
-        )
-    })?;
+/// [`package_details`] of `attr` in the registry's nixpkgs, which searches use too.
+fn registry_details(runner: &SystemRunner, attr: &str) -> Result<PackageDetails> {
+    let (nixpkgs, cache) = evalcache::open(runner, &Nixpkgs::Flake("nixpkgs".to_string()));
+    package_details(runner, &nixpkgs, cache.as_ref(), attr)
+}
 
+/// `declair info`: the package's details, wrapped to the terminal (or fzf preview) width.
+fn info(runner: &SystemRunner, attr: &str, output: OutputFormat) -> Result<()> {
+    let details = registry_details(runner, attr)?;
+    if output != OutputFormat::Table {
+        let row = vec![
+            details.attr,
+            details.version.unwrap_or_default(),
+            details.description.unwrap_or_default(),
+            details.homepage.join(" "),
+            details.license.join(", "),
+        ];
+        let headers = ["attr", "version", "description", "homepage", "license"];
+        println!("{}", render(output, &headers, &[row]));
+        return Ok(());
+    }
+    let width = std::env::var("FZF_PREVIEW_COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or_else(|| Term::stdout().size().1.into());
+    println!("{}", details.render(width));
     Ok(())
 }
 
-fn main() {
-    let args = Args::parse();
+fn run(args: Args) -> Result<()> {
+    // Even commands that don't need the config honour its `[timeouts]` and `language`
+    // when it exists.
+    let mut stored = read_config().ok().flatten().unwrap_or_default();
+    // A `.declair.toml` of the project declair runs in overrides the global config, and
+    // `--nix-arg`/`--rebuild-arg`/`--show-trace` add to both.
+    let project = match env::current_dir() {
+        Ok(cwd) => load_project_config(&cwd)?,
+        Err(_) => None,
+    };
+    let apply_overrides = |config: &mut Config| {
+        if let Some((path, project)) = &project {
+            project.apply(config, path.parent().unwrap_or(Path::new(".")));
+        }
+        config.nix_args.extend(args.nix_args.iter().cloned());
+        config
+            .rebuild_args
+            .extend(args.rebuild_args.iter().cloned());
+        if args.show_trace {
+            config.nix_args.push("--show-trace".to_string());
+            config.rebuild_args.push("--show-trace".to_string());
+        }
+    };
+    apply_overrides(&mut stored);
+    set_language(stored.language.unwrap_or_else(Language::from_env));
+    set_retention(stored.backups);
+    set_pager(!args.no_pager);
+    set_explain(args.explain);
+    if args.log_format == LogFormat::JsonLines {
+        set_event_sink(event_sink(args.log_fd)?);
+    }
+    theme::init(args.color, stored.theme.clone());
+    let runner = SystemRunner {
+        print_only: args.print_commands,
+        nix_args: stored.nix_args.clone(),
+        rebuild_logs: get_logs_dir(),
+        ..SystemRunner::with_timeouts(stored.timeouts)
+    };
+    if stored.read_only && changes_system(&args) {
+        return Err(DeclairError::ReadOnly);
+    }
 
-    // top-level error handling
-    if let Err(e) = run(args) {
-        eprintln!("Error: {}", e);
-        exit(1);
+    // Commands that don't touch the Nix config must not trigger the first-run wizard.
+    match &args.command {
+        Some(Commands::Completions { shell }) => {
+            completions(*shell);
+            return Ok(());
+        }
+        Some(Commands::Man { dir }) => return man(dir.as_deref()),
+        Some(Commands::Examples) => {
+            println!("{}", render_workflows(WORKFLOWS));
+            return Ok(());
+        }
+        Some(Commands::Search {
+            query,
+            matching,
+            status,
+        }) => {
+            let query = Query::new(query, matching.mode())?;
+            return search(&runner, &stored, &query, *status, args.output);
+        }
+        Some(Commands::Plugins) => return plugins(args.output),
+        Some(Commands::External(argv)) => return external(&runner, argv),
+        Some(Commands::Doctor) => return doctor(&runner),
+        Some(Commands::Paths) => return paths(args.output),
+        Some(Commands::Backups { action }) => return backups(&stored, action, args.output),
+        Some(Commands::Pin { packages }) => return pin(packages, true),
+        Some(Commands::Unpin { packages }) => return pin(packages, false),
+        Some(Commands::ListPins) => return list_pins(args.output),
+        Some(Commands::Info { package }) => return info(&runner, package, args.output),
+        Some(Commands::Schedule { action }) => return schedule(&runner, action),
+        Some(Commands::SelfManage {
+            action: SelfAction::Check { yes },
+        }) => return self_check(&stored, &args, &runner, *yes),
+        Some(Commands::Rpc {
+            method,
+            params,
+            socket,
+        }) => return rpc(method, params.as_deref(), socket.clone()),
+        _ => {}
     }
-}
 
-fn run(args: Args) -> Result<(), Box<dyn Error>> {
-    let mut config = read_or_create_config(&args)?;
+    // A project naming its own file needs no global config, so no first-run wizard.
+    let mut config = match &project {
+        Some((_, project)) if project.nix_path.is_some() => read_config()?.unwrap_or_default(),
+        _ => read_or_create_config(args.no_interactive)?,
+    };
+    apply_overrides(&mut config);
 
     // If user passed --config, override the nix_path from the stored config.
     if let Some(cfg_path) = &args.config {
-        config.nix_path = cfg_path.to_string_lossy().to_string();
+        config.nix_path = if !cfg_path.as_os_str().is_empty() {
+            cfg_path.to_string_lossy().to_string()
+        } else if args.no_interactive {
+            return Err(DeclairError::Config(t!("config.pick_noninteractive")));
+        } else {
+            prompt_nix_path(&recent_configs())?
+        };
+    }
+    confirm_root_edit(&config, &args)?;
+    remember_nix_path(&config.nix_path);
+    if edits_config(&args) {
+        resume_interrupted(&config, &args, &runner)?;
+    }
+
+    if let Some(command) = &args.command {
+        return match command {
+            Commands::InitConfigFile {
+                path,
+                insert_import,
+            } => init_config_file(config, &args, path.as_deref(), *insert_import),
+            Commands::Watch { debounce, confirm } => {
+                watch(&config, &runner, Duration::from_millis(*debounce), *confirm)
+            }
+            Commands::Add { packages } => {
+                edit_packages(&config, &args, &runner, packages, BatchAction::Add)
+            }
+            Commands::Replace { old, new } => replace(&config, &args, &runner, old, new),
+            Commands::NewPackage { name, template } => {
+                new_package(&config, &args, &runner, name, *template)
+            }
+            Commands::Why { package } => why(&config, &runner, package),
+            Commands::Drift { all, yes } => drift(&config, &args, &runner, *all, *yes),
+            Commands::Run {
+                package,
+                args: argv,
+            } => run_package(&config, &args, &runner, package, argv),
+            Commands::Diff { other } => diff(&config, &args, other),
+            Commands::Sync { manifest, yes } => sync(&config, &args, &runner, manifest, *yes),
+            Commands::Graph { style } => graph(&config, &args, *style),
+            Commands::Status => status(&config),
+            Commands::Grep { pattern } => grep(&config, &args, pattern),
+            Commands::Audit { licenses: false } => audit(&config, &args, &runner),
+            Commands::Audit { licenses: true } => audit_licenses(&config, &args, &runner),
+            Commands::Rebuild { mode, profile } => {
+                rebuild(&config, &args, &runner, *mode, profile.clone())
+            }
+            Commands::Update { yes } => update(&config, &args, &runner, *yes),
+            Commands::Channel { action } => channel(&config, &args, &runner, action),
+            Commands::Provides { command } => provides(&config, &args, &runner, command),
+            Commands::Fix { message } => fix(&config, &args, &runner, message.as_deref()),
+            Commands::Prune { packages } => prune(&config, &args, &runner, packages),
+            Commands::Group { action } => group(&config, &args, &runner, action),
+            Commands::Overlay { action } => overlay(&config, &args, &runner, action),
+            Commands::Env { action } => env_vars(&config, &args, &runner, action),
+            Commands::Service { action } => service(&config, &args, &runner, action),
+            Commands::Options { action } => options(&config, &args, &runner, action),
+            Commands::Daemon { socket } => daemon(config, &runner, socket.clone()),
+            Commands::Search { .. }
+            | Commands::Info { .. }
+            | Commands::Schedule { .. }
+            | Commands::Plugins
+            | Commands::External(_)
+            | Commands::Completions { .. }
+            | Commands::Man { .. }
+            | Commands::Examples
+            | Commands::SelfManage { .. }
+            | Commands::Rpc { .. }
+            | Commands::Paths
+            | Commands::Backups { .. }
+            | Commands::Pin { .. }
+            | Commands::Unpin { .. }
+            | Commands::ListPins
+            | Commands::Doctor => {
+                unreachable!("handled before reading the config")
+            }
+        };
+    }
+    if let Some(local) = &args.local {
+        return add_local(&config, &args, &runner, local);
     }
 
-    let man_output = Command::new("sh")
-        .arg("-c")
-        .arg("man configuration.nix | col -bx")
-        .output()?;
-    let man_text = String::from_utf8_lossy(&man_output.stdout);
+    let man_text = timings::time("options", || nixos_options_manual(&runner))?;
 
-    // expand and resolve nix config path
-    let raw = config.nix_path.trim();
-    let expanded = expand_tilde(raw)?;
-    let nix_file = resolve_nix_config(&expanded)
-        .map_err(|s| format!("Failed to use path `{}`: {}", expanded.display(), s))?;
+    // expand and resolve nix config path, following a bare flake into its modules
+    let nix_file = target_nix_file(&config, args.no_interactive)?;
     let git_repo = get_git_repo_or_parent_directory(&nix_file)?;
+    // Remember the file as it was before searching and prompting, so an edit made
+    // meanwhile (e.g. by another declair run) is not silently overwritten.
+    let snapshot = FileSnapshot::capture(&nix_file)?;
+    // In a flake, the output importing the file decides its kind and what gets rebuilt.
+    let rebuild_target = rebuild_target(&config, &nix_file, &git_repo)?;
+    let kind = rebuild_target.kind;
+    let scope = edit_scope(args.scope.as_ref(), kind)?;
 
     // Handle --list first: just list packages and exit
+    if args.list && args.tree {
+        return list_tree(&git_repo, &args);
+    }
     if args.list {
-        match list_packages(&nix_file) {
-            Ok(pkgs) => {
-                if pkgs.is_empty() {
-                    println!(
-                        "No packages found in `with pkgs; [...]` block of {}",
-                        nix_file.display()
-                    );
-                } else {
-                    let header_pkg = "Package";
-                    let header_src = "Source";
-
-                    let w1 = pkgs
-                        .iter()
-                        .map(|s| s.len())
-                        .max()
-                        .unwrap_or(0)
-                        .max(header_pkg.len());
-
-                    let source = format!("{}", nix_file.display());
-                    let w2 = source.len().max(header_src.len());
-
-                    println!(
-                        "{:<w1$} | {:<w2$}",
-                        header_pkg,
-                        header_src,
-                        w1 = w1,
-                        w2 = w2
-                    );
-
-                    println!("{}-+-{}", "-".repeat(w1), "-".repeat(w2));
-
-                    for p in pkgs {
-                        println!("{:<w1$} | {:<w2$}", p, source, w1 = w1, w2 = w2);
-                    }
-                }
-                return Ok(());
+        // Every scope's packages unless --scope picks one, each package with its scope
+        // and the condition of the list it is in, if conditional.
+        let scopes = match &args.scope {
+            Some(_) => vec![scope.clone()],
+            None => package_scopes(&nix_file, kind)?,
+        };
+        if scopes.is_empty() {
+            return Err(DeclairError::BlockNotFound { file: nix_file });
+        }
+        let mut pkgs: Vec<(String, String, String)> = Vec::new();
+        for scope in &scopes {
+            for segment in list_segments(&nix_file, scope)? {
+                let condition = segment.condition.unwrap_or_default();
+                pkgs.extend(
+                    segment
+                        .entries
+                        .into_iter()
+                        .filter(|(_, managed)| *managed || !args.managed_only)
+                        .map(|(pkg, _)| (pkg, scope.to_string(), condition.clone())),
+                );
             }
-            Err(e) => {
-                return Err(format!("Failed to list packages: {}", e).into());
+        }
+        if pkgs.is_empty() && args.output == OutputFormat::Table {
+            println!("{}", t!("list.empty", nix_file.display()));
+        } else {
+            let source = nix_file.display().to_string();
+            let state = State::load(&state_file_path()?)?;
+            let pinned = |p: &str| if state.is_pinned(p) { "yes" } else { "no" }.to_string();
+            if args.details {
+                let rows: Vec<Vec<String>> = pkgs
+                    .into_iter()
+                    .map(|(p, scope, condition)| {
+                        let record = state.get(&p);
+                        vec![
+                            p.clone(),
+                            source.clone(),
+                            scope,
+                            condition,
+                            pinned(&p),
+                            record.map(|r| r.added_at_display()).unwrap_or_default(),
+                            record.map(|r| r.added_by()).unwrap_or_default(),
+                            record.and_then(|r| r.reason.clone()).unwrap_or_default(),
+                        ]
+                    })
+                    .collect();
+                let headers = [
+                    "package",
+                    "source",
+                    "scope",
+                    "condition",
+                    "pinned",
+                    "added",
+                    "by",
+                    "reason",
+                ];
+                page(&format!("{}\n", render(args.output, &headers, &rows)));
+            } else {
+                let rows: Vec<Vec<String>> = pkgs
+                    .into_iter()
+                    .map(|(p, scope, condition)| {
+                        vec![p.clone(), source.clone(), scope, condition, pinned(&p)]
+                    })
+                    .collect();
+                let headers = ["package", "source", "scope", "condition", "pinned"];
+                page(&format!("{}\n", render(args.output, &headers, &rows)));
             }
         }
+        return Ok(());
     }
 
+    // Ask about root-owned files now rather than failing after search and selection.
+    let use_sudo = if args.dry_run || can_replace(&nix_file) {
+        false
+    } else if !args.no_interactive
+        && Confirm::with_theme(prompt_theme())
+            .with_prompt(t!("sudo.prompt", nix_file.display()))
+            .default(true)
+            .interact()?
+    {
+        true
+    } else {
+        return Err(DeclairError::InvalidPath {
+            path: nix_file,
+            reason: t!("sudo.not_writable"),
+        });
+    };
+
     // obtain query: from CLI or interactively (existing add-package flow)
     let query: String = if let Some(q) = args.package.clone() {
         q
     } else if args.no_interactive {
-        return Err("No query provided and --no-interactive specified".into());
+        return Err(DeclairError::Config(t!("search.no_query_noninteractive")));
     } else if args.fzf {
         "^".to_string()
     } else {
-        Input::new()
-            .with_prompt("Search for a package")
+        Input::with_theme(prompt_theme())
+            .with_prompt(t!("search.prompt"))
             .interact_text()?
     };
 
-    let mut options = Vec::new();
+    // fzf without a query lists every package (`^`) for fzf to filter.
+    let mode = if args.fzf && args.package.is_none() {
+        SearchMode::Regex
+    } else {
+        args.matching.mode()
+    };
+    let search_query = Query::new(&query, mode)?;
 
-    let selected_pkg = if args.no_interactive {
-        query
+    // The search result the user picked, if any: its metadata spares some lookups below.
+    let (selected_pkg, mut selected_info) = if args.no_interactive {
+        (query, None)
     } else if args.fzf {
         let fzf = fzf_wrapped::Fzf::builder()
-            .prompt("Select a package: ")
+            .prompt(format!("{} ", t!("select.prompt")))
             .custom_args(vec![
                 "--select-1".to_string(),
                 "--height=40%".to_string(),
                 "--border".to_string(),
                 "--margin=0,1".to_string(),
+                format!("--preview={}", info_preview_command()),
+                format!(
+                    "--preview-window=right,50%,wrap,<{}(down,50%,wrap)",
+                    FZF_SIDE_PREVIEW_WIDTH
+                ),
             ])
             .build()
-            .map_err(|e| format!("Failed to configure fzf: {}", e))?;
+            .map_err(|e| DeclairError::Selection(t!("select.fzf_failed", e)))?;
 
-        let pkg_map: HashMap<String, PackageInfo> =
-            search_packages(&query).map_err(|s| format!("Package search failed: {}", s))?;
+        let mut pkg_map: HashMap<String, PackageInfo> =
+            timings::time_with_spinner("search", &t!("search.spinner"), || {
+                search_configured(&runner, &config, &search_query)
+            })?;
+        mark_installed(&mut pkg_map, &installed_packages(&git_repo));
+        pkg_map.retain(|_, p| args.status.keep(p));
         if pkg_map.is_empty() {
-            println!("No results found");
+            println!("{}", t!("search.no_results"));
             return Ok(());
         }
-        for pkg in pkg_map.values() {
-            let desc = pkg.description.as_deref().unwrap_or("");
-            options.push(format!("{} {}: {}", pkg.pname, pkg.version, desc));
-        }
+        let mut pkgs = sorted_results(pkg_map);
+        print_sets(
+            &group_by_set(&mut pkgs, &query, &config.prefer_sets),
+            &query,
+        );
+        // With room for it, fzf puts the preview beside the list instead of below.
+        let (_, cols) = Term::stdout().size();
+        let list_width = if cols >= FZF_SIDE_PREVIEW_WIDTH {
+            cols / 2
+        } else {
+            cols
+        };
+        let options = option_lines(&pkgs, usize::from(list_width.saturating_sub(6)));
 
-        let selected_line =
-            fzf_wrapped::run_with_output(fzf, &options).ok_or("No package selected")?;
+        let selected_line = fzf_wrapped::run_with_output(fzf, &options)
+            .ok_or_else(|| DeclairError::Selection(t!("select.none")))?;
 
-        selected_line
+        let attr = selected_line
             .split_whitespace()
             .next()
-            .ok_or("Failed to extract package name")?
-            .to_string()
+            .ok_or_else(|| DeclairError::Selection(t!("select.extract_failed")))?
+            .to_string();
+        let info = pkgs.into_iter().find(|p| p.name() == attr);
+        (attr, info)
     } else {
-        let pkg_map: HashMap<String, PackageInfo> =
-            search_packages(&query).map_err(|s| format!("Package search failed: {}", s))?;
+        let mut pkg_map: HashMap<String, PackageInfo> =
+            timings::time_with_spinner("search", &t!("search.spinner"), || {
+                search_configured(&runner, &config, &search_query)
+            })?;
+        mark_installed(&mut pkg_map, &installed_packages(&git_repo));
+        pkg_map.retain(|_, p| args.status.keep(p));
         if pkg_map.is_empty() {
-            println!("No results found");
+            println!("{}", t!("search.no_results"));
             return Ok(());
         }
-        for pkg in pkg_map.values() {
-            let desc = pkg.description.as_deref().unwrap_or("");
-            options.push(format!("{} {}: {}", pkg.pname, pkg.version, desc));
+        let (mut pkgs, more) = best_results(
+            pkg_map.into_values().collect(),
+            &query,
+            MAX_RESULTS,
+            &config.prefer_sets,
+        );
+        if more > 0 {
+            eprintln!(
+                "{}",
+                theme()
+                    .warning(t!("select.more_results", more))
+                    .for_stderr()
+            );
+        }
+        print_sets(
+            &group_by_set(&mut pkgs, &query, &config.prefer_sets),
+            &query,
+        );
+        let (rows, cols) = Term::stderr().size();
+        let options = option_lines(&pkgs, usize::from(cols).saturating_sub(4));
+        let mut cursor = 0;
+        // Show the chosen package's details and go back to the list unless it's confirmed.
+        loop {
+            cursor = Select::with_theme(prompt_theme())
+                .with_prompt(t!("select.prompt"))
+                .items(&options)
+                .default(cursor)
+                .max_length(page_size(rows.into()))
+                .interact()?;
+            let pkg = &pkgs[cursor];
+            let details =
+                timings::time_with_spinner("details", &t!("select.details_spinner"), || {
+                    registry_details(&runner, pkg.name())
+                })
+                .unwrap_or_else(|_| PackageDetails::from_info(pkg));
+            println!("{}\n", details.render(cols.into()));
+            if Confirm::with_theme(prompt_theme())
+                .with_prompt(t!("select.use_prompt", pkg.name()))
+                .default(true)
+                .interact()?
+            {
+                break (pkg.name().to_string(), Some(pkg.clone()));
+            }
         }
+    };
 
-        let selection = Select::new()
-            .with_prompt("Select a package:")
-            .items(&options)
-            .default(0)
-            .interact()?;
-        let selected_line = &options[selection];
-        // safer to extract and own the package name
-        selected_line
-            .split_whitespace()
-            .next()
-            .ok_or("Failed to extract package name")?
-            .to_string()
+    let selected_pkg = if args.remove {
+        check_pins(&args, [selected_pkg.as_str()])?;
+        selected_pkg
+    } else {
+        match resolve_alias(&args, &selected_pkg)? {
+            Some(pkg) if pkg != selected_pkg => {
+                selected_info = None;
+                pkg
+            }
+            Some(pkg) => pkg,
+            None => {
+                println!("{}", t!("add.declined", selected_pkg));
+                return Ok(());
+            }
+        }
     };
 
     if args.dry_run {
-        println!("Selected package: {}", selected_pkg);
+        println!("{}", t!("select.dry_run", selected_pkg));
         return Ok(());
     }
 
     let pattern = format!("programs.{}.enable", selected_pkg);
 
-    let programs = if man_text.contains(&pattern) && !config.home_manager {
-        println!("Found `{pattern}` in `man configuration.nix`");
+    let programs = if man_text.contains(&pattern) && kind == ModuleKind::Nixos {
+        println!("{}", t!("program.found", pattern));
         if args.program {
             true
         } else if args.no_interactive {
             false
         } else {
-            Confirm::new()
-                .with_prompt("As a program?")
+            Confirm::with_theme(prompt_theme())
+                .with_prompt(t!("program.prompt"))
                 .default(true)
                 .interact()?
         }
@@ -730,58 +4025,145 @@ fn run(args: Args) -> Result<(), Box<dyn Error>> {
         false
     };
 
+    if !args.remove && !programs {
+        let known_licenses = selected_info
+            .as_ref()
+            .map(|info| info.license.as_slice())
+            .filter(|licenses| !licenses.is_empty());
+        enforce_policy(
+            &config,
+            &args,
+            &runner,
+            &git_repo,
+            &nix_file,
+            &selected_pkg,
+            known_licenses,
+        )?;
+        if !confirm_platform(&config, &args, &runner, &git_repo, &nix_file, &selected_pkg)? {
+            println!("{}", t!("add.declined", selected_pkg));
+            return Ok(());
+        }
+        let proceed = timings::time("conflicts", || {
+            confirm_conflicts(&args, &runner, &git_repo, &nix_file, &selected_pkg, kind)
+        })?;
+        if !proceed {
+            println!("{}", t!("add.declined", selected_pkg));
+            return Ok(());
+        }
+        warn_advisories(&config, &runner, &git_repo, &nix_file, &selected_pkg);
+        if is_nur_package(&selected_pkg) {
+            offer_nur_setup(&config, &args, &git_repo, &nix_file)?;
+        }
+    }
+
+    if args.remove && !programs {
+        warn_references(&git_repo, &nix_file, &selected_pkg);
+    }
+    let ctx = HookContext::new(
+        if args.remove { "remove" } else { "add" },
+        vec![selected_pkg.clone()],
+        &nix_file,
+    );
+    run_phase(&runner, &config.hooks, Phase::PreEdit, &ctx, None)?;
+
+    let edit_start = Instant::now();
+    snapshot.verify()?;
+    let scratch = if use_sudo {
+        Some(ScratchCopy::new(&nix_file)?)
+    } else {
+        None
+    };
+    // The file the edit functions work on: the config itself, or its scratch copy.
+    let target = scratch
+        .as_ref()
+        .map_or(nix_file.as_path(), ScratchCopy::path);
+    let scope = package_scope(&args, &scope, target, &selected_pkg);
     if args.remove {
         if programs {
-            remove_program_from_nix(&nix_file, &pattern)?;
-            println!(
-                "Removed `{}` as program from `{}`",
-                selected_pkg,
-                nix_file.display()
-            );
+            remove_program_from_nix(target, &pattern)?;
+            println!("{}", t!("remove.program", selected_pkg, nix_file.display()));
+            explain(Topic::ProgramModule);
         } else {
-            remove_package_from_nix(&nix_file, &selected_pkg)?;
-            println!("Removed `{}` to `{}`", selected_pkg, nix_file.display());
+            config
+                .plugins
+                .remove_package(&runner, target, &selected_pkg, &scope)?;
+            println!("{}", t!("remove.package", selected_pkg, nix_file.display()));
+            explain(Topic::Removal);
         }
     } else if programs {
-        add_program_to_nix(&nix_file, &pattern)?;
-        println!(
-            "Added `{}` as program to `{}`",
-            selected_pkg,
-            nix_file.display()
-        );
+        add_program_to_nix(target, &pattern)?;
+        println!("{}", t!("add.program", selected_pkg, nix_file.display()));
+        explain(Topic::ProgramModule);
+    } else {
+        let segment = prompt_segment(
+            &config,
+            &args,
+            target,
+            &scope,
+            std::slice::from_ref(&selected_pkg),
+        )?;
+        match add_to_segment(&config, &runner, target, &selected_pkg, &scope, segment) {
+            Err(DeclairError::BlockNotFound { .. }) if !args.no_interactive => {
+                let resolution = prompt_missing_block(&nix_file, &scope)?;
+                add_package_resolving_block(target, &selected_pkg, &scope, &resolution)?;
+            }
+            result => result?,
+        }
+        println!("{}", t!("add.package", selected_pkg, nix_file.display()));
+        explain(Topic::PackageList);
+    }
+    timings::record("edit", edit_start.elapsed());
+
+    if config.formatter != Formatter::None
+        && let Err(e) = format_file(&runner, config.formatter, target)
+    {
+        eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+    }
+
+    if let Some(scratch) = scratch {
+        scratch.write_back(&runner)?;
+    }
+    post_edit_hooks(&runner, &config, &ctx);
+    offer_gitignore(&args, std::slice::from_ref(&nix_file));
+    log_transaction(
+        &ctx,
+        rebuilding(&config, &args, &runner, std::slice::from_ref(&nix_file)),
+    );
+    if args.remove {
+        update_install_state(|state| state.forget(&selected_pkg));
     } else {
-        add_package_to_nix(&nix_file, &selected_pkg)?;
-        println!("Added `{}` to `{}`", selected_pkg, nix_file.display());
+        update_install_state(|state| {
+            state.record_added(&selected_pkg, &nix_file, args.reason.clone())
+        });
     }
 
     // Respect --no-rebuild flag
     if config.auto_rebuild && !args.no_rebuild {
-        println!("Rebuilding NixOS with the new package...");
-        env::set_current_dir(&git_repo)?;
-        let status = if config.flake {
-            if config.home_manager {
-                Command::new("home-manager")
-                    .args(["switch", "--flake", "."])
-                    .status()?
-            } else {
-                Command::new("sudo")
-                    .args(["nixos-rebuild", "switch", "--flake", "."])
-                    .status()?
-            }
-        } else if config.home_manager {
-            Command::new("home-manager").args(["switch"]).status()?
-        } else {
-            Command::new("sudo")
-                .args(["nixos-rebuild", "switch"])
-                .status()?
-        };
-        if !status.success() {
-            eprintln!("Error while running nixos-rebuild (exit code != 0)");
+        println!("{}", t!("rebuild.with_new", kind));
+        rebuild_edited(
+            &config,
+            &args,
+            &runner,
+            &rebuild_target,
+            &git_repo,
+            &ctx,
+            std::slice::from_ref(&nix_file),
+        )?;
+        if !args.remove && !programs {
+            show_store_paths(&config, &args, &runner, &git_repo, &nix_file, &selected_pkg);
         }
     } else if config.auto_rebuild && args.no_rebuild {
-        println!("Skipping rebuild due to --no-rebuild flag");
+        println!("{}", t!("rebuild.skipped"));
     }
+    commit_edit(
+        &config,
+        &args,
+        &runner,
+        &git_repo,
+        &ctx,
+        std::slice::from_ref(&nix_file),
+    );
 
-    println!("Done");
+    println!("{}", t!("done", run_timings().summary()));
     Ok(())
 }
@@ -0,0 +1,189 @@
+//! Package sets read from another machine's config or an exported `--list` manifest,
+//! for `declair diff` and `declair sync`.
+
+use crate::edit::package_lists;
+use crate::error::{DeclairError, Result};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// Columns that hold the package name in exported manifests.
+const PACKAGE_COLUMNS: [&str; 3] = ["package", "name", "attr"];
+
+/// Packages of `path`: every `with pkgs; [ ... ]` list of a `.nix` file (including a
+/// `--output nix` export), or a `--output json|csv|tsv` export of `--list` or `search`.
+/// Any other file is read as one package per line, `#` starting a comment.
+pub fn read_manifest(path: &Path) -> Result<BTreeSet<String>> {
+    let invalid = |reason: String| DeclairError::InvalidPath {
+        path: path.to_path_buf(),
+        reason,
+    };
+    if path.extension().is_some_and(|e| e == "nix") {
+        return Ok(package_lists(path)?
+            .into_iter()
+            .flat_map(|list| list.entries)
+            .map(|(pkg, _)| pkg)
+            .collect());
+    }
+    let contents = fs::read_to_string(path).map_err(|e| DeclairError::Read {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    if contents.trim_start().starts_with('[') {
+        let value: Value = serde_json::from_str(&contents)
+            .map_err(|e| invalid(format!("Not a JSON package list: {}", e)))?;
+        return json_packages(&value).ok_or_else(|| {
+            invalid(
+                "Expected an array of package names or of objects with a `package` field"
+                    .to_string(),
+            )
+        });
+    }
+    Ok(text_packages(&contents))
+}
+
+/// `["git", ...]` or `[{"package": "git", ...}, ...]`.
+fn json_packages(value: &Value) -> Option<BTreeSet<String>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|item| match item {
+            Value::String(pkg) => Some(pkg.clone()),
+            Value::Object(fields) => PACKAGE_COLUMNS
+                .iter()
+                .find_map(|c| fields.get(*c).and_then(Value::as_str))
+                .map(str::to_string),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A CSV/TSV export with a header line naming the package column, or a plain list.
+fn text_packages(contents: &str) -> BTreeSet<String> {
+    let mut lines = contents
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .filter(|l| !l.is_empty())
+        .peekable();
+    let header = lines.peek().and_then(|first| {
+        let separator = if first.contains('\t') { '\t' } else { ',' };
+        first
+            .split(separator)
+            .position(|c| PACKAGE_COLUMNS.contains(&c.trim()))
+            .map(|column| (separator, column))
+    });
+    match header {
+        Some((separator, column)) => lines
+            .skip(1)
+            .filter_map(|l| l.split(separator).nth(column))
+            .map(|c| c.trim().trim_matches('"').to_string())
+            .filter(|c| !c.is_empty())
+            .collect(),
+        None => lines
+            .filter_map(|l| l.split_whitespace().next())
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// How two package sets differ.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetDiff {
+    /// Packages only the local config has.
+    pub only_local: Vec<String>,
+    /// Packages only the other set has.
+    pub only_other: Vec<String>,
+    pub common: Vec<String>,
+}
+
+impl SetDiff {
+    pub fn between(local: &BTreeSet<String>, other: &BTreeSet<String>) -> Self {
+        SetDiff {
+            only_local: local.difference(other).cloned().collect(),
+            only_other: other.difference(local).cloned().collect(),
+            common: local.intersection(other).cloned().collect(),
+        }
+    }
+
+    pub fn is_in_sync(&self) -> bool {
+        self.only_local.is_empty() && self.only_other.is_empty()
+    }
+}
+
+/// A package the config file changed since the last sync and applying the manifest would
+/// undo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncConflict {
+    /// The file lists the package, the manifest doesn't: it was added by hand since the
+    /// last sync, or there was no sync yet to tell.
+    AddedLocally(String),
+    /// The package was removed from the file since the last sync; the manifest still has
+    /// it.
+    RemovedLocally(String),
+}
+
+impl SyncConflict {
+    pub fn package(&self) -> &str {
+        match self {
+            SyncConflict::AddedLocally(pkg) | SyncConflict::RemovedLocally(pkg) => pkg,
+        }
+    }
+}
+
+/// What `declair sync` does to the config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncPlan {
+    pub add: Vec<String>,
+    pub remove: Vec<String>,
+    /// Changes to the file that are kept unless resolved in the manifest's favour.
+    pub conflicts: Vec<SyncConflict>,
+}
+
+impl SyncPlan {
+    /// Three-way merge of the `manifest` into the `current` packages of the file, with
+    /// `base` the manifest as last synced. Changes of the manifest since `base` are
+    /// applied; changes of the file are kept and reported as conflicts. Without a base,
+    /// only additions are applied.
+    pub fn merge(
+        base: Option<&BTreeSet<String>>,
+        manifest: &BTreeSet<String>,
+        current: &BTreeSet<String>,
+    ) -> Self {
+        let mut plan = SyncPlan::default();
+        for pkg in manifest.union(current) {
+            let in_manifest = manifest.contains(pkg);
+            let in_current = current.contains(pkg);
+            if in_manifest == in_current {
+                continue;
+            }
+            // Without a base, a package missing from the file was never there.
+            let changed_locally = match base {
+                Some(base) => base.contains(pkg) != in_current,
+                None => in_current,
+            };
+            let pkg = pkg.clone();
+            match (changed_locally, in_manifest) {
+                (false, true) => plan.add.push(pkg),
+                (false, false) => plan.remove.push(pkg),
+                (true, true) => plan.conflicts.push(SyncConflict::RemovedLocally(pkg)),
+                (true, false) => plan.conflicts.push(SyncConflict::AddedLocally(pkg)),
+            }
+        }
+        plan
+    }
+
+    /// Resolve `conflict` in the manifest's favour.
+    pub fn overwrite(&mut self, conflict: &SyncConflict) {
+        self.conflicts.retain(|c| c != conflict);
+        match conflict {
+            SyncConflict::AddedLocally(pkg) => self.remove.push(pkg.clone()),
+            SyncConflict::RemovedLocally(pkg) => self.add.push(pkg.clone()),
+        }
+    }
+
+    /// Whether the file already matches the manifest.
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.remove.is_empty() && self.conflicts.is_empty()
+    }
+}
@@ -0,0 +1,226 @@
+use crate::config::Config;
+use crate::error::{DeclairError, Result};
+use crate::paths::expand_path;
+use crate::t;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Attributes that only appear in Home Manager modules.
+const HOME_MANAGER_MARKERS: &[&str] = &[
+    "home.packages",
+    "home.stateVersion",
+    "home.username",
+    "home.homeDirectory",
+    "programs.home-manager",
+];
+
+/// Attributes that only appear in NixOS modules.
+const NIXOS_MARKERS: &[&str] = &[
+    "environment.systemPackages",
+    "system.stateVersion",
+    "boot.loader",
+    "networking.hostName",
+    "users.users",
+    "nixosConfigurations",
+];
+
+/// Whether a config file is a NixOS or a Home Manager module. Decides which package list
+/// declair edits and which rebuild command applies to the file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModuleKind {
+    Nixos,
+    HomeManager,
+}
+
+impl ModuleKind {
+    /// The kind implied by the global `home_manager` config flag.
+    pub fn from_home_manager(home_manager: bool) -> Self {
+        if home_manager {
+            ModuleKind::HomeManager
+        } else {
+            ModuleKind::Nixos
+        }
+    }
+
+    /// Attribute holding the package list in this kind of module.
+    pub fn packages_attr(self) -> &'static str {
+        match self {
+            ModuleKind::Nixos => "environment.systemPackages",
+            ModuleKind::HomeManager => "home.packages",
+        }
+    }
+
+    /// Guess the kind from the attributes a module sets.
+    /// Returns `None` when there are no markers or both kinds are equally likely.
+    pub fn detect(contents: &str) -> Option<Self> {
+        let count = |markers: &[&str]| markers.iter().filter(|m| contents.contains(*m)).count();
+        let hm = count(HOME_MANAGER_MARKERS);
+        let nixos = count(NIXOS_MARKERS);
+        match hm.cmp(&nixos) {
+            std::cmp::Ordering::Greater => Some(ModuleKind::HomeManager),
+            std::cmp::Ordering::Less => Some(ModuleKind::Nixos),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+impl fmt::Display for ModuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ModuleKind::Nixos => "NixOS",
+            ModuleKind::HomeManager => "Home Manager",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Fonts whose attribute name doesn't say so.
+const FONT_PACKAGES: &[&str] = &[
+    "cascadia-code",
+    "fira",
+    "fira-code",
+    "fira-mono",
+    "hack-font",
+    "ibm-plex",
+    "inter",
+    "iosevka",
+    "jetbrains-mono",
+    "liberation_ttf",
+    "roboto",
+    "roboto-mono",
+    "source-code-pro",
+    "source-han-sans",
+    "source-sans",
+    "source-serif",
+    "twemoji-color-font",
+];
+
+/// Packages with "font" in their name that are tools rather than fonts.
+const FONT_TOOLS: &[&str] = &["fontconfig", "fontforge", "font-manager", "fontmatrix"];
+
+/// Which package list of a module declair edits: the system's, one user's
+/// (`users.users.<name>.packages`), the system fonts (`fonts.packages`) or Home
+/// Manager's.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Scope {
+    System,
+    User(String),
+    Fonts,
+    Home,
+}
+
+impl Scope {
+    /// Attribute holding the package list of this scope.
+    pub fn packages_attr(&self) -> String {
+        match self {
+            Scope::System => ModuleKind::Nixos.packages_attr().to_string(),
+            Scope::User(name) => format!("users.users.{}.packages", name),
+            Scope::Fonts => "fonts.packages".to_string(),
+            Scope::Home => ModuleKind::HomeManager.packages_attr().to_string(),
+        }
+    }
+
+    /// The kind of module this scope's list is in.
+    pub fn kind(&self) -> ModuleKind {
+        match self {
+            Scope::System | Scope::User(_) | Scope::Fonts => ModuleKind::Nixos,
+            Scope::Home => ModuleKind::HomeManager,
+        }
+    }
+}
+
+impl From<ModuleKind> for Scope {
+    fn from(kind: ModuleKind) -> Self {
+        match kind {
+            ModuleKind::Nixos => Scope::System,
+            ModuleKind::HomeManager => Scope::Home,
+        }
+    }
+}
+
+impl From<&Scope> for Scope {
+    fn from(scope: &Scope) -> Self {
+        scope.clone()
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    /// `system`, `user:<name>`, `fonts` or `home`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "system" => Ok(Scope::System),
+            None if s == "fonts" => Ok(Scope::Fonts),
+            None if s == "home" => Ok(Scope::Home),
+            Some(("user", name))
+                if !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "_-".contains(c)) =>
+            {
+                Ok(Scope::User(name.to_string()))
+            }
+            _ => Err(t!("scope.invalid", s)),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::System => f.write_str("system"),
+            Scope::User(name) => write!(f, "user:{}", name),
+            Scope::Fonts => f.write_str("fonts"),
+            Scope::Home => f.write_str("home"),
+        }
+    }
+}
+
+/// Whether the attribute `attr` is most likely a font: `noto-fonts`, `nerd-fonts.hack`,
+/// `dejavu_fonts`, or one of a few well-known fonts named without "font".
+pub fn is_font(attr: &str) -> bool {
+    let attr = attr.to_lowercase();
+    (attr.contains("font") && !FONT_TOOLS.contains(&attr.as_str()))
+        || FONT_PACKAGES.contains(&attr.as_str())
+}
+
+/// The scope edits go to: `requested` (from `--scope`) if it fits a module of `kind`,
+/// otherwise `kind`'s own.
+pub fn edit_scope(requested: Option<&Scope>, kind: ModuleKind) -> Result<Scope> {
+    match requested {
+        None => Ok(kind.into()),
+        Some(scope) if scope.kind() == kind => Ok(scope.clone()),
+        Some(scope) => Err(DeclairError::Config(t!("scope.mismatch", scope, kind))),
+    }
+}
+
+/// Kind assigned to `file` in the config's `[modules]` table, if any.
+pub fn configured_kind(config: &Config, file: &Path) -> Result<Option<ModuleKind>> {
+    let canonical = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    for (path, kind) in &config.modules {
+        let path = expand_path(path)?;
+        if fs::canonicalize(&path).unwrap_or(path) == canonical {
+            return Ok(Some(*kind));
+        }
+    }
+    Ok(None)
+}
+
+/// Kind of `file`: an entry in the config's `[modules]` table wins, then detection from
+/// the file's contents, then the global `home_manager` flag.
+pub fn module_kind(config: &Config, file: &Path) -> Result<ModuleKind> {
+    if let Some(kind) = configured_kind(config, file)? {
+        return Ok(kind);
+    }
+    let contents = fs::read_to_string(file).map_err(|e| DeclairError::Read {
+        path: file.to_path_buf(),
+        source: e,
+    })?;
+    Ok(ModuleKind::detect(&contents)
+        .unwrap_or_else(|| ModuleKind::from_home_manager(config.home_manager)))
+}
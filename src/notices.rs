@@ -0,0 +1,92 @@
+//! Migration notices buried in rebuild output: the option renames and removals Nix warns
+//! about while evaluating, and Home Manager's unread news.
+
+use crate::config::Config;
+use crate::module::ModuleKind;
+use crate::rebuild::{RebuildTarget, flake_args};
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use std::path::Path;
+use std::time::Duration;
+
+/// `home-manager news` only reads local files.
+const NEWS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Words marking a warning as one about migrating an option.
+const MIGRATION_WORDS: &[&str] = &[
+    "renamed",
+    "removed",
+    "deprecated",
+    "obsolete",
+    "no longer has any effect",
+];
+
+/// Evaluation warnings about options that were renamed, removed or deprecated, in order,
+/// without repeats: `trace: warning: The option `a' ... has been renamed to `b'.` (older
+/// Nix) or `evaluation warning: ...` (newer).
+pub fn option_warnings(output: &str) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+    for line in output.lines() {
+        let Some((_, message)) = line.split_once("warning: ") else {
+            continue;
+        };
+        let message = message.trim();
+        let lower = message.to_lowercase();
+        if lower.contains("option")
+            && MIGRATION_WORDS.iter().any(|w| lower.contains(w))
+            && !warnings.iter().any(|w| w == message)
+        {
+            warnings.push(message.to_string());
+        }
+    }
+    warnings
+}
+
+/// How many unread news items `home-manager switch` announced, if any:
+/// `There are 3 unread and relevant news items.` or `There is an unread and relevant news
+/// item.`
+pub fn unread_news(output: &str) -> Option<usize> {
+    output.lines().find_map(|line| {
+        let line = line.trim();
+        if line.starts_with("There is an unread and relevant news item") {
+            return Some(1);
+        }
+        let rest = line.strip_prefix("There are ")?;
+        let (count, rest) = rest.split_once(' ')?;
+        if !rest.starts_with("unread and relevant news items") {
+            return None;
+        }
+        count.parse().ok().filter(|&n| n > 0)
+    })
+}
+
+/// `home-manager news` for the configuration in `dir`, printing the unread items (and
+/// marking them read) instead of opening a pager.
+pub fn news_command(config: &Config, target: &RebuildTarget, dir: &Path) -> CommandSpec {
+    let args = std::iter::once("news".to_string()).chain(flake_args(config, target));
+    CommandSpec::new("home-manager", args)
+        .env("PAGER", "cat")
+        .current_dir(dir)
+        .timeout(NEWS_TIMEOUT)
+        .class(CommandClass::Eval)
+}
+
+/// The unread news after a Home Manager switch that printed `output`, or `None` when there
+/// is none, the target isn't Home Manager or `home-manager news` fails.
+pub fn read_news(
+    runner: &dyn CommandRunner,
+    config: &Config,
+    target: &RebuildTarget,
+    dir: &Path,
+    output: &str,
+) -> Option<(usize, String)> {
+    if target.kind != ModuleKind::HomeManager {
+        return None;
+    }
+    let count = unread_news(output)?;
+    let news = runner.output(&news_command(config, target, dir)).ok()?;
+    if !news.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&news.stdout).trim().to_string();
+    (!text.is_empty()).then_some((count, text))
+}
@@ -0,0 +1,168 @@
+//! Packages of the Nix User Repository, found in NUR's combined package index and
+//! installed as `nur.repos.<owner>.<package>`.
+
+use crate::atomic::write_atomic;
+use crate::error::{DeclairError, Result};
+use crate::paths::get_cache_dir;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use crate::search::{PackageInfo, parse_entries, split_name};
+use crate::t;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const DEFAULT_INDEX_URL: &str =
+    "https://raw.githubusercontent.com/nix-community/nur-search/master/data/packages.json";
+/// The flake input providing NUR.
+pub const NUR_FLAKE_URL: &str = "github:nix-community/NUR";
+/// Attribute path prefix of NUR packages.
+pub const NUR_PREFIX: &str = "nur.repos.";
+/// The index is tens of megabytes.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// NUR search settings, from the `[nur]` config table.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Nur {
+    /// Search NUR next to nixpkgs.
+    pub enable: bool,
+    /// Combined package index: a JSON object mapping `nur.repos.<owner>.<package>` to
+    /// `nix-env --json --meta` style entries.
+    pub index_url: String,
+    /// How long a downloaded index is used before it is fetched again, in hours.
+    pub cache_hours: u64,
+}
+
+impl Default for Nur {
+    fn default() -> Self {
+        Nur {
+            enable: false,
+            index_url: DEFAULT_INDEX_URL.to_string(),
+            cache_hours: 24,
+        }
+    }
+}
+
+/// Whether `pkg` is a NUR attribute path.
+pub fn is_nur_package(pkg: &str) -> bool {
+    pkg.starts_with(NUR_PREFIX)
+}
+
+#[derive(Deserialize)]
+struct IndexEntry {
+    name: Option<String>,
+    version: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    meta: Value,
+}
+
+/// Parse the index. Each package's `pname` is its full attribute path, which is what
+/// goes into the package list.
+pub fn parse_index(json: &[u8]) -> Result<HashMap<String, PackageInfo>> {
+    parse_entries(json, |attr, entry: IndexEntry| {
+        if !is_nur_package(&attr) {
+            return None;
+        }
+        let name_version = entry.name.as_deref().map(|n| split_name(n).1);
+        let mut info = PackageInfo {
+            attr: attr.clone(),
+            pname: attr.clone(),
+            version: entry.version.or(name_version).unwrap_or_default(),
+            description: entry.description,
+            ..Default::default()
+        };
+        info.apply_meta(&entry.meta);
+        Some((attr, info))
+    })
+    .map_err(|e| match e {
+        DeclairError::SearchFailed(reason) => {
+            DeclairError::SearchFailed(t!("nur.index_error", reason))
+        }
+        e => e,
+    })
+}
+
+/// Packages whose attribute path or description contains every word of `query`,
+/// ignoring case.
+pub fn search_index(
+    index: &HashMap<String, PackageInfo>,
+    query: &str,
+) -> HashMap<String, PackageInfo> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    index
+        .iter()
+        .filter(|(attr, info)| {
+            let haystack = format!(
+                "{} {}",
+                attr.to_lowercase(),
+                info.description.as_deref().unwrap_or("").to_lowercase()
+            );
+            words.iter().all(|w| haystack.contains(w.as_str()))
+        })
+        .map(|(attr, info)| (attr.clone(), info.clone()))
+        .collect()
+}
+
+/// Where the downloaded index is kept.
+pub fn index_cache_path() -> Option<PathBuf> {
+    Some(get_cache_dir()?.join("nur-packages.json"))
+}
+
+fn is_fresh(path: &Path, max_age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < max_age)
+}
+
+/// The index from `cache` while it is younger than `cache_hours`, otherwise downloaded
+/// with `curl` and cached. A stale cache is used when the download fails.
+pub fn load_index(runner: &dyn CommandRunner, nur: &Nur, cache: &Path) -> Result<Vec<u8>> {
+    if is_fresh(cache, Duration::from_secs(nur.cache_hours * 3600)) {
+        return Ok(fs::read(cache)?);
+    }
+    let cmd = CommandSpec::new("curl", ["-fsSL", nur.index_url.as_str()])
+        .timeout(FETCH_TIMEOUT)
+        .retries(2)
+        .class(CommandClass::Search)
+        .query();
+    let fetched = match runner.output(&cmd) {
+        Ok(output) if output.status.success() => Ok(output.stdout),
+        Ok(output) => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match fetched {
+        Ok(index) => {
+            if let Some(dir) = cache.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            write_atomic(cache, &index)?;
+            Ok(index)
+        }
+        Err(reason) => fs::read(cache).map_err(|_| DeclairError::FetchFailed {
+            source_url: nur.index_url.clone(),
+            reason,
+        }),
+    }
+}
+
+/// Search NUR's index for `query`.
+pub fn nur_search(
+    runner: &dyn CommandRunner,
+    nur: &Nur,
+    query: &str,
+) -> Result<HashMap<String, PackageInfo>> {
+    let cache = index_cache_path().ok_or(DeclairError::NoHomeDir)?;
+    let index = parse_index(&load_index(runner, nur, &cache)?)?;
+    Ok(search_index(&index, query))
+}
+
+/// Whether a `flake.nix` already has a NUR input.
+pub fn flake_has_nur(contents: &str) -> bool {
+    contents.contains("nix-community/NUR")
+}
@@ -0,0 +1,525 @@
+//! NixOS and Home Manager options, for `declair options`: the declarations of an option
+//! tree as `lib.optionAttrSetToDocList` lists them for the manual and search.nixos.org,
+//! values checked against the declared types, and `<option> = <value>;` assignments in a
+//! config file.
+
+use crate::audit::Nixpkgs;
+use crate::config::Config;
+use crate::edit::Edit;
+use crate::error::{DeclairError, Result};
+use crate::evalcache::{self, pinned_revision};
+use crate::flake::{find_flake_dir, locked_input};
+use crate::module::ModuleKind;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec, eval_output};
+use crate::syntax::{
+    AttrSet, Place, Token, TokenKind, find_place, insert_binding, is_expression, module_body,
+    nix_string, tokenize,
+};
+use crate::t;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+/// Evaluating every option declaration takes a while.
+const EVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// One declared option.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OptionInfo {
+    /// Dotted name, with `<name>` and `*` for the attributes of attribute sets and lists,
+    /// e.g. `services.nginx.virtualHosts.<name>.root`.
+    pub name: String,
+    /// Description of the type, e.g. `boolean` or `list of package`.
+    #[serde(rename = "type")]
+    pub type_name: String,
+    /// The default as a Nix expression.
+    pub default: Option<String>,
+    /// Markdown description.
+    pub description: Option<String>,
+}
+
+/// Where Home Manager's modules come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HomeManager {
+    /// The revision of the configuration flake's `home-manager` input.
+    Flake(String),
+    /// `<home-manager>` from `NIX_PATH`.
+    NixPath,
+}
+
+/// The option tree a config file is evaluated in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionTree {
+    Nixos(Nixpkgs),
+    HomeManager {
+        nixpkgs: Nixpkgs,
+        home_manager: HomeManager,
+    },
+}
+
+impl OptionTree {
+    /// The options of `kind`'s modules in the nixpkgs (and Home Manager) `nix_file` in
+    /// `repo` is built with.
+    pub fn for_config(config: &Config, repo: &Path, nix_file: &Path, kind: ModuleKind) -> Self {
+        let nixpkgs = Nixpkgs::for_config(config, repo, nix_file);
+        match kind {
+            ModuleKind::Nixos => OptionTree::Nixos(nixpkgs),
+            ModuleKind::HomeManager => {
+                let locked = config
+                    .flake
+                    .then(|| find_flake_dir(repo, nix_file))
+                    .flatten()
+                    .and_then(|dir| locked_input(&dir, "home-manager"));
+                OptionTree::HomeManager {
+                    nixpkgs,
+                    home_manager: locked.map_or(HomeManager::NixPath, HomeManager::Flake),
+                }
+            }
+        }
+    }
+
+    pub fn nixpkgs(&self) -> &Nixpkgs {
+        match self {
+            OptionTree::Nixos(nixpkgs) | OptionTree::HomeManager { nixpkgs, .. } => nixpkgs,
+        }
+    }
+
+    fn with_nixpkgs(&self, nixpkgs: Nixpkgs) -> Self {
+        match self {
+            OptionTree::Nixos(_) => OptionTree::Nixos(nixpkgs),
+            OptionTree::HomeManager { home_manager, .. } => OptionTree::HomeManager {
+                nixpkgs,
+                home_manager: home_manager.clone(),
+            },
+        }
+    }
+
+    /// A Nix expression for the tree, with `src` bound to the nixpkgs source.
+    fn expr(&self) -> String {
+        match self {
+            OptionTree::Nixos(_) => {
+                "(import (src + \"/nixos/lib/eval-config.nix\") { modules = [ ]; }).options"
+                    .to_string()
+            }
+            OptionTree::HomeManager { home_manager, .. } => {
+                let source = match home_manager {
+                    HomeManager::Flake(reference) => {
+                        format!("(builtins.getFlake {}).outPath", nix_string(reference))
+                    }
+                    HomeManager::NixPath => "<home-manager>".to_string(),
+                };
+                format!(
+                    "(import ({} + \"/modules\") {{ configuration = {{ }}; \
+                     pkgs = import src {{ }}; check = false; }}).options",
+                    source
+                )
+            }
+        }
+    }
+
+    /// What the evaluated options are cached under for a nixpkgs revision; `None` when
+    /// the Home Manager revision isn't known.
+    fn cache_key(&self) -> Option<String> {
+        match self {
+            OptionTree::Nixos(_) => Some("nixos".to_string()),
+            OptionTree::HomeManager {
+                home_manager: HomeManager::Flake(reference),
+                ..
+            } => pinned_revision(reference).map(|rev| format!("home-manager-{}", rev)),
+            OptionTree::HomeManager { .. } => None,
+        }
+    }
+}
+
+/// `nix eval` of the visible options of `tree`. Defaults and descriptions that fail to
+/// evaluate are `null`.
+pub fn options_command(tree: &OptionTree) -> CommandSpec {
+    let expr = format!(
+        "let src = {}; lib = import (src + \"/lib\"); options = {}; \
+         text = v: if builtins.isString v then v else v.text or (builtins.toJSON v); \
+         safe = v: let r = builtins.tryEval (text v); in if r.success then r.value else null; \
+         visible = o: (o.visible or true) != false && !(o.internal or false); \
+         in map (o: {{ inherit (o) name; type = o.type or \"unspecified\"; \
+         default = if o ? default then safe o.default else null; \
+         description = if o.description or null == null then null else safe o.description; }}) \
+         (builtins.filter visible (lib.optionAttrSetToDocList options))",
+        tree.nixpkgs().source(),
+        tree.expr()
+    );
+    CommandSpec::new(
+        "nix",
+        [
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+            &expr,
+            "--extra-experimental-features",
+            "nix-command flakes",
+        ],
+    )
+    .timeout(EVAL_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query()
+}
+
+/// The options in the output of [`options_command`].
+pub fn parse_options(json: &[u8]) -> Result<Vec<OptionInfo>> {
+    serde_json::from_slice(json).map_err(|e| DeclairError::Eval(t!("eval.unexpected_output", e)))
+}
+
+/// The options of `tree`, from the eval cache when it has them for the revision.
+pub fn list_options(runner: &dyn CommandRunner, tree: &OptionTree) -> Result<Vec<OptionInfo>> {
+    let (nixpkgs, cache) = evalcache::open(runner, tree.nixpkgs());
+    let tree = tree.with_nixpkgs(nixpkgs);
+    let eval = || eval_output(runner, &options_command(&tree));
+    let json = match (cache, tree.cache_key()) {
+        (Some(cache), Some(key)) => cache.eval("options", &key, eval)?,
+        _ => eval()?,
+    };
+    parse_options(&json)
+}
+
+/// The declaration of `name` in `options`, with their `<name>` and `*` parts matching any
+/// attribute.
+pub fn find_option<'a>(options: &'a [OptionInfo], name: &str) -> Option<&'a OptionInfo> {
+    let parts: Vec<&str> = name.split('.').collect();
+    options.iter().find(|option| {
+        let declared: Vec<&str> = option.name.split('.').collect();
+        declared.len() == parts.len()
+            && declared.iter().zip(&parts).all(|(declared, part)| {
+                declared == part || *declared == "*" || declared.starts_with('<')
+            })
+    })
+}
+
+/// Whether `name` is one of `options` (see [`find_option`]).
+pub fn is_declared(options: &[OptionInfo], name: &str) -> bool {
+    find_option(options, name).is_some()
+}
+
+/// Check that `name` is a dotted attribute path, like `networking.hostName`.
+pub fn validate_option_name(name: &str) -> Result<()> {
+    let valid = name.split('.').all(|part| {
+        let mut chars = part.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '\'')
+    });
+    if !valid {
+        return Err(DeclairError::Config(t!("option.invalid_name", name)));
+    }
+    Ok(())
+}
+
+/// What a value is, as far as can be told without evaluating it.
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Null,
+    Bool,
+    Int(i64),
+    Float,
+    Str,
+    Path,
+    List(Vec<Literal>),
+    Set,
+    /// Anything else: a variable, a function call, `lib.mkForce ...`.
+    Expr,
+}
+
+fn literal(text: &str) -> Literal {
+    let Some(tokens) = tokenize(text) else {
+        return Literal::Expr;
+    };
+    let texts: Vec<&str> = tokens.iter().map(|t| t.text(text)).collect();
+    match (tokens.as_slice(), texts.as_slice()) {
+        ([token], [word]) => match (token.kind, *word) {
+            (TokenKind::Ident, "null") => Literal::Null,
+            (TokenKind::Ident, "true" | "false") => Literal::Bool,
+            (TokenKind::Str, _) => Literal::Str,
+            (TokenKind::Other, word) if word.contains('/') => Literal::Path,
+            (TokenKind::Other, word) => match word.parse::<i64>() {
+                Ok(n) => Literal::Int(n),
+                Err(_) if word.parse::<f64>().is_ok() => Literal::Float,
+                Err(_) => Literal::Expr,
+            },
+            _ => Literal::Expr,
+        },
+        ([_, number], ["-", digits]) if number.kind == TokenKind::Other => {
+            match digits.parse::<i64>() {
+                Ok(n) => Literal::Int(-n),
+                Err(_) if digits.parse::<f64>().is_ok() => Literal::Float,
+                Err(_) => Literal::Expr,
+            }
+        }
+        ([first, .., last], _)
+            if first.kind == TokenKind::Punct(b'[') && last.kind == TokenKind::Punct(b']') =>
+        {
+            match list_items(text, &tokens[1..tokens.len() - 1]) {
+                Some(items) => Literal::List(items.iter().map(|item| literal(item)).collect()),
+                None => Literal::Expr,
+            }
+        }
+        ([first, .., last], _)
+            if (first.kind == TokenKind::Punct(b'{') || texts[0] == "rec")
+                && last.kind == TokenKind::Punct(b'}') =>
+        {
+            Literal::Set
+        }
+        _ => Literal::Expr,
+    }
+}
+
+/// The elements of a list whose tokens between the brackets are `tokens`: bracketed
+/// groups, selections like `pkgs.hello`, negative numbers or single tokens. `None` when
+/// the brackets don't close the list as a whole, as in `[ 1 ] ++ [ 2 ]`.
+fn list_items<'a>(text: &'a str, tokens: &[Token]) -> Option<Vec<&'a str>> {
+    let mut items = Vec::new();
+    let mut idx = 0;
+    while idx < tokens.len() {
+        let start = idx;
+        match tokens[idx].kind {
+            TokenKind::Punct(b'[' | b'{' | b'(') => {
+                let mut depth = 0;
+                loop {
+                    match tokens.get(idx)?.kind {
+                        TokenKind::Punct(b'[' | b'{' | b'(') => depth += 1,
+                        TokenKind::Punct(b']' | b'}' | b')') => depth -= 1,
+                        _ => {}
+                    }
+                    idx += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            TokenKind::Punct(b']' | b'}' | b')') => return None,
+            TokenKind::Op if tokens[idx].text(text) == "-" => idx += 2,
+            _ => {
+                idx += 1;
+                while tokens
+                    .get(idx)
+                    .is_some_and(|t| t.kind == TokenKind::Punct(b'.'))
+                {
+                    idx += 2;
+                }
+            }
+        }
+        let end = tokens.get(idx - 1)?.end;
+        items.push(&text[tokens[start].start..end]);
+    }
+    Some(items)
+}
+
+/// `text` split at `separator` outside parentheses and quotes.
+fn split_top<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut quoted, mut start) = (0, false, 0);
+    let mut idx = 0;
+    while idx < text.len() {
+        match text.as_bytes()[idx] {
+            b'"' => quoted = !quoted,
+            b'(' if !quoted => depth += 1,
+            b')' if !quoted => depth -= 1,
+            _ if !quoted && depth == 0 && text[idx..].starts_with(separator) => {
+                parts.push(&text[start..idx]);
+                idx += separator.len();
+                start = idx;
+                continue;
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// `ty` without parentheses around all of it.
+fn unwrap_parens(ty: &str) -> &str {
+    let ty = ty.trim();
+    if let Some(inner) = ty.strip_prefix('(').and_then(|t| t.strip_suffix(')'))
+        && split_top(ty, ")").len() == 2
+    {
+        return unwrap_parens(inner);
+    }
+    ty
+}
+
+/// The values an option of type `ty` can take when there are few: `true` and `false`,
+/// or the choices of `one of ...`, with `null` for `null or ...`.
+pub fn choices(ty: &str) -> Option<Vec<String>> {
+    let branches = split_top(unwrap_parens(ty), " or ");
+    let mut values = Vec::new();
+    for branch in branches {
+        let branch = unwrap_parens(branch);
+        match branch {
+            "null" => values.push("null".to_string()),
+            "boolean" => values.extend(["true".to_string(), "false".to_string()]),
+            _ => values.extend(
+                split_top(branch.strip_prefix("one of ")?, ", ")
+                    .into_iter()
+                    .map(|choice| choice.trim().to_string()),
+            ),
+        }
+    }
+    Some(values)
+}
+
+/// The bounds of an integer type like `16 bit unsigned integer; between 0 and 65535 (both
+/// inclusive)` or `positive integer, meaning >0`.
+fn integer_bounds(ty: &str) -> (Option<i64>, Option<i64>) {
+    if let Some((_, range)) = ty.split_once("between ") {
+        let mut bounds = range
+            .split(|c: char| !c.is_ascii_digit() && c != '-')
+            .filter_map(|n| n.parse().ok());
+        return (bounds.next(), bounds.next());
+    }
+    if ty.contains(">0") && !ty.contains(">=0") {
+        return (Some(1), None);
+    }
+    if ty.contains(">=0") || ty.contains("unsigned") {
+        return (Some(0), None);
+    }
+    (None, None)
+}
+
+/// Whether `value` can be of type `ty`, the description `nix eval` gave for the option.
+/// Only literals are checked; other expressions and unknown types are accepted.
+fn accepts(ty: &str, value: &Literal, raw: &str) -> bool {
+    if *value == Literal::Expr {
+        return true;
+    }
+    let ty = unwrap_parens(ty);
+    let branches = split_top(ty, " or ");
+    if branches.len() > 1 {
+        return branches.iter().any(|branch| accepts(branch, value, raw));
+    }
+    if let Some(choices) = ty.strip_prefix("one of ") {
+        return split_top(choices, ", ")
+            .iter()
+            .any(|choice| choice.trim() == raw.trim());
+    }
+    if let Some(element) = ty
+        .strip_prefix("list of ")
+        .or(ty.strip_prefix("non-empty list of "))
+    {
+        let Literal::List(items) = value else {
+            return false;
+        };
+        let raw_items = tokenize(raw)
+            .and_then(|tokens| list_items(raw, &tokens[1..tokens.len() - 1]))
+            .unwrap_or_default();
+        return !(items.is_empty() && ty.starts_with("non-empty"))
+            && items
+                .iter()
+                .zip(raw_items)
+                .all(|(item, raw)| accepts(element, item, raw));
+    }
+    let known = [
+        "boolean",
+        "integer",
+        "floating point",
+        "number",
+        "string",
+        "path",
+    ]
+    .iter()
+    .any(|word| ty.contains(word))
+        || ["null", "package"].contains(&ty)
+        || ty.contains("attribute set")
+        || ty.starts_with("submodule");
+    if !known {
+        return true;
+    }
+    match value {
+        _ if ty == "null" => *value == Literal::Null,
+        _ if ty.contains("attribute set") || ty.starts_with("submodule") => *value == Literal::Set,
+        Literal::Bool => ty == "boolean",
+        Literal::Int(n) if ty.contains("integer") => {
+            let (min, max) = integer_bounds(ty);
+            min.is_none_or(|min| *n >= min) && max.is_none_or(|max| *n <= max)
+        }
+        Literal::Int(_) | Literal::Float => ty.contains("floating point") || ty.contains("number"),
+        Literal::Str => ty.contains("string") || ty.contains("path") || ty == "package",
+        Literal::Path => ty.contains("path") || ty == "package",
+        Literal::Set => ty == "package",
+        Literal::Null | Literal::List(_) => false,
+        Literal::Expr => true,
+    }
+}
+
+/// Check that `value` is a single Nix expression and, when it is a literal, that an
+/// option of type `ty` accepts it.
+pub fn check_value(name: &str, value: &str, ty: Option<&str>) -> Result<()> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(DeclairError::Config(t!("option.empty_value", name)));
+    }
+    if !is_expression(value) {
+        return Err(DeclairError::Config(t!("option.invalid_value", value)));
+    }
+    if let Some(ty) = ty
+        && !accepts(ty, &literal(value), value)
+    {
+        return Err(DeclairError::Config(t!(
+            "option.wrong_type",
+            value,
+            name,
+            ty
+        )));
+    }
+    Ok(())
+}
+
+fn module(file_path: &Path, contents: &str) -> Result<AttrSet> {
+    module_body(contents).ok_or_else(|| DeclairError::NoInsertionPoint {
+        file: file_path.to_path_buf(),
+    })
+}
+
+/// The value `file_path` assigns to `name`, as written, also when it's nested like
+/// `services.openssh = { ports = ...; };`.
+pub fn option_value(file_path: &Path, name: &str) -> Result<Option<String>> {
+    let edit = Edit::begin(file_path)?;
+    let body = module(file_path, edit.contents())?;
+    let path: Vec<String> = name.split('.').map(str::to_string).collect();
+    Ok(match find_place(&body, &path) {
+        Place::Assigned(binding) => Some(edit.contents()[binding.value.clone()].to_string()),
+        Place::Missing { .. } => None,
+    })
+}
+
+/// Assign the Nix expression `value` to `name`. An existing assignment, also one nested
+/// in set literals like `services.openssh = { ... };` or spanning several lines, gets the
+/// new value in place; otherwise the rest of the path is assigned in the innermost set
+/// that `name` leads into, or the module's. Returns the previous value, as written.
+pub fn set_option(file_path: &Path, name: &str, value: &str) -> Result<Option<String>> {
+    validate_option_name(name)?;
+    check_value(name, value, None)?;
+    let value = value.trim();
+    let edit = Edit::begin(file_path)?;
+    let contents = edit.contents();
+    let body = module(file_path, contents)?;
+    let path: Vec<String> = name.split('.').map(str::to_string).collect();
+    edit.backup()?;
+    let (updated, previous) = match find_place(&body, &path) {
+        Place::Assigned(binding) => {
+            let range = binding.value.clone();
+            let updated = format!(
+                "{}{}{}",
+                &contents[..range.start],
+                value,
+                &contents[range.end..]
+            );
+            (updated, Some(contents[range].to_string()))
+        }
+        Place::Missing { set, rest } => {
+            let binding = format!("{} = {};", rest.join("."), value);
+            (insert_binding(contents, set, &binding), None)
+        }
+    };
+    edit.commit(&updated)?;
+    Ok(previous)
+}
@@ -0,0 +1,248 @@
+use crate::i18n;
+use crate::theme::theme;
+use clap::ValueEnum;
+
+/// How `--list`, `search` and `info` print their rows.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Aligned columns for reading
+    #[default]
+    Table,
+    /// Tab-separated values with a header line
+    Tsv,
+    /// Nushell object notation (a table literal), for `from nuon`
+    Nuon,
+    /// A `with pkgs; [ ... ]` block of the package column, to paste into a config
+    Nix,
+    /// An array of objects keyed by column name
+    Json,
+    /// Comma-separated values with a header line (RFC 4180 quoting)
+    Csv,
+    /// A Markdown table
+    #[value(name = "md", alias = "markdown")]
+    Markdown,
+}
+
+/// Render `rows` under `headers` in `format`. Headers should be lowercase identifiers;
+/// the table format capitalizes them.
+pub fn render(format: OutputFormat, headers: &[&str], rows: &[Vec<String>]) -> String {
+    match format {
+        OutputFormat::Table => render_table(headers, rows),
+        OutputFormat::Tsv => render_tsv(headers, rows),
+        OutputFormat::Nuon => render_nuon(headers, rows),
+        OutputFormat::Nix => render_nix(headers, rows),
+        OutputFormat::Json => render_json(headers, rows),
+        OutputFormat::Csv => render_csv(headers, rows),
+        OutputFormat::Markdown => render_markdown(headers, rows),
+    }
+}
+
+/// Column title for the table and Markdown formats: the translated `header.<key>`
+/// message, or the capitalized key.
+fn header_label(key: &str) -> String {
+    match i18n::find(i18n::language(), &format!("header.{}", key)) {
+        Some(label) => label.to_string(),
+        None => capitalize(key),
+    }
+}
+
+fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let theme = theme();
+    let keys = headers;
+    let headers: Vec<String> = headers.iter().map(|h| header_label(h)).collect();
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            rows.iter()
+                .filter_map(|r| r.get(i))
+                .map(|c| c.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(h.chars().count())
+        })
+        .collect();
+    // Pad before styling so escape codes don't count towards the width; the last column
+    // isn't padded so lines carry no trailing spaces.
+    let pad = |i: usize, c: &str| {
+        if i + 1 == widths.len() {
+            c.trim_end().to_string()
+        } else {
+            format!("{:<w$}", c, w = widths[i])
+        }
+    };
+    let line = |cells: &[String], style: &dyn Fn(usize, String) -> String| {
+        cells
+            .iter()
+            .enumerate()
+            .map(|(i, c)| style(i, pad(i, c)))
+            .collect::<Vec<_>>()
+            .join(" | ")
+            .trim_end()
+            .to_string()
+    };
+    let header_style = |_: usize, c: String| theme.header(c).to_string();
+    let cell_style = |i: usize, c: String| match keys.get(i) {
+        Some(&"package") | Some(&"name") => theme.package(c).to_string(),
+        Some(&"version") => theme.version(c).to_string(),
+        _ => c,
+    };
+    let mut out = vec![line(&headers, &header_style)];
+    out.push(
+        widths
+            .iter()
+            .map(|w| "-".repeat(*w))
+            .collect::<Vec<_>>()
+            .join("-+-"),
+    );
+    out.extend(rows.iter().map(|r| line(r, &cell_style)));
+    out.join("\n")
+}
+
+fn render_tsv(headers: &[&str], rows: &[Vec<String>]) -> String {
+    // Tabs and newlines inside a field would break the columns.
+    let clean = |c: &str| c.replace(['\t', '\n', '\r'], " ");
+    std::iter::once(headers.join("\t"))
+        .chain(
+            rows.iter()
+                .map(|r| r.iter().map(|c| clean(c)).collect::<Vec<_>>().join("\t")),
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_nuon(headers: &[&str], rows: &[Vec<String>]) -> String {
+    // NUON strings use the same double-quoted escapes as JSON.
+    let quote = |c: &str| serde_json::to_string(c).unwrap_or_else(|_| "\"\"".to_string());
+    let rows: Vec<String> = rows
+        .iter()
+        .map(|r| {
+            format!(
+                "[{}]",
+                r.iter().map(|c| quote(c)).collect::<Vec<_>>().join(", ")
+            )
+        })
+        .collect();
+    let mut out = format!("[[{}];", headers.join(", "));
+    for row in rows {
+        out.push_str(&format!(" {}", row));
+    }
+    out.push(']');
+    out
+}
+
+/// A labelled node of a tree printed by [`render_tree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeNode {
+    pub label: String,
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    pub fn new(label: impl Into<String>, children: Vec<TreeNode>) -> Self {
+        TreeNode {
+            label: label.into(),
+            children,
+        }
+    }
+
+    pub fn leaf(label: impl Into<String>) -> Self {
+        Self::new(label, Vec::new())
+    }
+}
+
+/// Draw each root flush left with its descendants below it on box-drawing branches.
+pub fn render_tree(roots: &[TreeNode]) -> String {
+    let mut lines = Vec::new();
+    for root in roots {
+        lines.push(root.label.clone());
+        push_children(&mut lines, &root.children, "");
+    }
+    lines.join("\n")
+}
+
+fn push_children(lines: &mut Vec<String>, children: &[TreeNode], prefix: &str) {
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        lines.push(format!("{}{}{}", prefix, branch, child.label));
+        push_children(lines, &child.children, &format!("{}{}", prefix, indent));
+    }
+}
+
+/// The `package` (or `name`, `attr`) column as a `with pkgs; [ ... ]` block, one entry
+/// per line; other columns are dropped.
+fn render_nix(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let column = headers
+        .iter()
+        .position(|h| matches!(*h, "package" | "name" | "attr"))
+        .unwrap_or(0);
+    let mut out = vec!["with pkgs; [".to_string()];
+    out.extend(
+        rows.iter()
+            .filter_map(|r| r.get(column))
+            .map(|p| format!("  {}", p)),
+    );
+    out.push("]".to_string());
+    out.join("\n")
+}
+
+fn render_json(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let objects: Vec<serde_json::Map<String, serde_json::Value>> = rows
+        .iter()
+        .map(|r| {
+            headers
+                .iter()
+                .zip(r)
+                .map(|(h, c)| (h.to_string(), serde_json::Value::String(c.clone())))
+                .collect()
+        })
+        .collect();
+    serde_json::to_string_pretty(&objects).unwrap_or_else(|_| "[]".to_string())
+}
+
+fn render_csv(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let quote = |c: &str| {
+        if c.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", c.replace('"', "\"\""))
+        } else {
+            c.to_string()
+        }
+    };
+    std::iter::once(headers.join(","))
+        .chain(
+            rows.iter()
+                .map(|r| r.iter().map(|c| quote(c)).collect::<Vec<_>>().join(",")),
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_markdown(headers: &[&str], rows: &[Vec<String>]) -> String {
+    // Pipes would end a cell early and newlines the row.
+    let clean = |c: &str| c.replace('|', "\\|").replace(['\n', '\r'], " ");
+    let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+    std::iter::once(line(headers.iter().map(|h| header_label(h)).collect()))
+        .chain(std::iter::once(line(vec![
+            "---".to_string();
+            headers.len()
+        ])))
+        .chain(
+            rows.iter()
+                .map(|r| line(r.iter().map(|c| clean(c)).collect())),
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
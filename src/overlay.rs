@@ -0,0 +1,244 @@
+//! The `nixpkgs.overlays = [ ... ];` list of a config file, for `declair overlay`.
+
+use crate::edit::Edit;
+use crate::error::{DeclairError, Result};
+use crate::syntax::{nix_string, split_comment};
+use crate::t;
+use std::path::Path;
+
+/// Trailing comment naming an overlay declair added.
+pub const OVERLAY_MARKER: &str = "# overlay:";
+
+/// One entry of the overlays list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlay {
+    /// The `# overlay: <name>` marker, the input of `inputs.<name>.overlays.*`, or the
+    /// expression itself.
+    pub name: String,
+    pub expr: String,
+    /// 1-based line of the entry.
+    pub line: usize,
+}
+
+/// Check that `name` can be a flake input name and `url` can't break out of the Nix
+/// string it is written into.
+pub fn validate_overlay(name: &str, url: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid_name = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "-_".contains(c));
+    if !valid_name {
+        return Err(DeclairError::Config(t!("overlay.invalid_name", name)));
+    }
+    if url.is_empty() || url.contains(['"', '\\', '\n']) || url.contains("${") {
+        return Err(DeclairError::Config(t!("overlay.invalid_url", url)));
+    }
+    Ok(())
+}
+
+/// The list entry for overlay `name`: the default overlay of the flake input `name`, or
+/// the tarball at `url` imported as an overlay.
+pub fn overlay_expr(name: &str, url: &str, flake: bool) -> String {
+    if flake {
+        format!("inputs.{}.overlays.default", name)
+    } else {
+        format!("(import (builtins.fetchTarball {}))", nix_string(url))
+    }
+}
+
+/// Split the inside of a single-line list into entries, keeping parenthesised
+/// expressions and strings whole.
+fn split_entries(inner: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0i32;
+    let mut in_string = false;
+    for c in inner.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' | '[' | '{' if !in_string => depth += 1,
+            ')' | ']' | '}' if !in_string => depth -= 1,
+            c if c.is_whitespace() && depth == 0 && !in_string => {
+                if !current.is_empty() {
+                    entries.push(std::mem::take(&mut current));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        entries.push(current);
+    }
+    entries
+}
+
+fn name_of(expr: &str, comment: Option<&str>) -> String {
+    if let Some(name) = comment
+        .and_then(|c| c.strip_prefix(OVERLAY_MARKER))
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        return name.to_string();
+    }
+    let path = expr.strip_prefix("inputs.").unwrap_or(expr);
+    match path.split_once('.') {
+        Some((input, rest)) if rest.starts_with("overlays") => input.to_string(),
+        _ => expr.to_string(),
+    }
+}
+
+/// Where the overlays list is: its first and last line (equal for a single-line list).
+fn find_list(lines: &[String]) -> Option<(usize, usize)> {
+    let start = lines.iter().position(|l| {
+        let t = l.trim_start();
+        t.starts_with("nixpkgs.overlays") && t.contains('[')
+    })?;
+    let after_open = &lines[start][lines[start].find('[')? + 1..];
+    if split_comment(after_open).0.contains(']') {
+        return Some((start, start));
+    }
+    let end = lines[start + 1..]
+        .iter()
+        .position(|l| l.trim_start().starts_with(']'))?;
+    Some((start, start + 1 + end))
+}
+
+fn parse(lines: &[String]) -> Vec<Overlay> {
+    let Some((start, end)) = find_list(lines) else {
+        return Vec::new();
+    };
+    if start == end {
+        let line = &lines[start];
+        let inner = line
+            .find('[')
+            .and_then(|open| Some((open, line.rfind(']')?)))
+            .map_or("", |(open, close)| &line[open + 1..close]);
+        return split_entries(inner)
+            .into_iter()
+            .map(|expr| Overlay {
+                name: name_of(&expr, None),
+                expr,
+                line: start + 1,
+            })
+            .collect();
+    }
+    lines[start + 1..end]
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let (code, comment) = split_comment(line.trim());
+            (!code.is_empty()).then(|| Overlay {
+                name: name_of(code, comment),
+                expr: code.to_string(),
+                line: start + 2 + i,
+            })
+        })
+        .collect()
+}
+
+/// Entries of the file's `nixpkgs.overlays` list; empty without one.
+pub fn list_overlays(file_path: &Path) -> Result<Vec<Overlay>> {
+    let edit = Edit::begin(file_path)?;
+    Ok(parse(&edit.lines()))
+}
+
+/// Add `expr` to the overlays list as `name`, creating the list before the file's closing
+/// brace when there is none. A single-line list is rewritten as a multi-line one.
+pub fn add_overlay(file_path: &Path, name: &str, expr: &str) -> Result<()> {
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+    if parse(&lines)
+        .iter()
+        .any(|o| o.name == name || o.expr == expr)
+    {
+        return Err(DeclairError::OverlayExists {
+            name: name.to_string(),
+            file: file_path.to_path_buf(),
+        });
+    }
+    edit.backup()?;
+    let indent_of = |l: &str| -> String { l.chars().take_while(|c| c.is_whitespace()).collect() };
+    let entry = |indent: &str| format!("{}{} {} {}", indent, expr, OVERLAY_MARKER, name);
+
+    match find_list(&lines) {
+        Some((start, end)) if start == end => {
+            let existing = parse(&lines);
+            let outer = indent_of(&lines[start]);
+            let line = &lines[start];
+            let head = line[..line.find('[').unwrap_or(line.len())].trim_end();
+            let tail = line[line.rfind(']').map_or(line.len(), |i| i + 1)..].to_string();
+            let mut block = vec![format!("{} [", head)];
+            block.extend(existing.iter().map(|o| format!("{}  {}", outer, o.expr)));
+            block.push(entry(&format!("{}  ", outer)));
+            block.push(format!("{}]{}", outer, tail));
+            lines.splice(start..=start, block);
+        }
+        Some((start, end)) => {
+            let indent = if end - start > 1 {
+                indent_of(&lines[end - 1])
+            } else {
+                format!("{}  ", indent_of(&lines[end]))
+            };
+            lines.insert(end, entry(&indent));
+        }
+        None => {
+            let close_idx = lines
+                .iter()
+                .rposition(|l| l.trim_start().starts_with('}'))
+                .ok_or_else(|| DeclairError::NoInsertionPoint {
+                    file: file_path.to_path_buf(),
+                })?;
+            let outer = indent_of(&lines[close_idx]);
+            let block = [
+                format!("{}  nixpkgs.overlays = [", outer),
+                entry(&format!("{}    ", outer)),
+                format!("{}  ];", outer),
+            ];
+            lines.splice(close_idx..close_idx, block);
+        }
+    }
+    edit.commit(&lines.join("\n"))
+}
+
+/// Remove the overlay `name` from the overlays list.
+pub fn remove_overlay(file_path: &Path, name: &str) -> Result<()> {
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+    let overlays = parse(&lines);
+    let overlay =
+        overlays
+            .iter()
+            .find(|o| o.name == name)
+            .ok_or_else(|| DeclairError::OverlayNotFound {
+                name: name.to_string(),
+                file: file_path.to_path_buf(),
+            })?;
+    edit.backup()?;
+    let idx = overlay.line - 1;
+    if overlays.iter().filter(|o| o.line == overlay.line).count() > 1 {
+        // An entry of a single-line list.
+        let line = &lines[idx];
+        let open = line.find('[').unwrap_or(0);
+        let close = line.rfind(']').unwrap_or(line.len());
+        let rest: Vec<&str> = overlays
+            .iter()
+            .filter(|o| o.line == overlay.line && o.name != name)
+            .map(|o| o.expr.as_str())
+            .collect();
+        lines[idx] = format!(
+            "{}[ {} ]{}",
+            &line[..open],
+            rest.join(" "),
+            &line[close + 1..]
+        );
+    } else if find_list(&lines).is_some_and(|(start, end)| start == end) {
+        let line = &lines[idx];
+        let open = line.find('[').unwrap_or(0);
+        let close = line.rfind(']').unwrap_or(line.len());
+        lines[idx] = format!("{}[ ]{}", &line[..open], &line[close + 1..]);
+    } else {
+        lines.remove(idx);
+    }
+    edit.commit(&lines.join("\n"))
+}
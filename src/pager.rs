@@ -0,0 +1,66 @@
+//! Long outputs (search results, package lists, diffs, the backup history, evaluation
+//! traces) go through a pager when stdout is a terminal, as git's do: `$DECLAIR_PAGER`,
+//! `$PAGER` or `less`, with `LESS=FRX` unless `LESS` is set, so that output fitting on one
+//! screen is printed as usual and colours survive.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// `less` options used unless `LESS` is set: quit if the output fits on one screen, pass
+/// colours through, and don't clear the screen on exit.
+const LESS_OPTIONS: &str = "FRX";
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// Turn paging on or off for the rest of the process (`--no-pager` turns it off). Only
+/// the first call has an effect; without one, output is never paged.
+pub fn set_pager(enabled: bool) {
+    let _ = ENABLED.set(enabled);
+}
+
+/// The pager command given `$DECLAIR_PAGER` and `$PAGER`: the first one set, `less`
+/// without either. An empty one or `cat` means no pager.
+pub fn pager_command(declair_pager: Option<String>, pager: Option<String>) -> Option<String> {
+    let command = declair_pager
+        .or(pager)
+        .unwrap_or_else(|| "less".to_string());
+    let command = command.trim();
+    if command.is_empty() || command == "cat" {
+        None
+    } else {
+        Some(command.to_string())
+    }
+}
+
+/// Print `text` through the pager when paging is on and stdout is a terminal, otherwise
+/// (or when the pager can't run) directly.
+pub fn page(text: &str) {
+    if ENABLED.get().copied().unwrap_or(false)
+        && io::stdout().is_terminal()
+        && let Some(pager) = pager_command(env::var("DECLAIR_PAGER").ok(), env::var("PAGER").ok())
+        && run_pager(&pager, text)
+    {
+        return;
+    }
+    print!("{}", text);
+    let _ = io::stdout().flush();
+}
+
+/// Feed `text` to `pager`, run by the shell so it may have arguments; whether it ran.
+fn run_pager(pager: &str, text: &str) -> bool {
+    let mut command = Command::new("sh");
+    command.args(["-c", pager]).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", LESS_OPTIONS);
+    }
+    let Ok(mut child) = command.spawn() else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager closing early (`q`) is a broken pipe, not a failure.
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait().is_ok()
+}
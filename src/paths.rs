@@ -0,0 +1,267 @@
+use crate::error::{DeclairError, Result};
+use crate::host::{Privilege, home_dir};
+use crate::t;
+use directories::ProjectDirs;
+use gix::discover;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Expand a path the way a shell would: a leading `~` (the home directory, see
+/// [`tilde_home`]) or `~user` (that user's), then `$VAR` and `${VAR}` anywhere, and
+/// `%VAR%` on Windows only. An unset variable is an error rather than an empty string,
+/// except `HOME` and `USERPROFILE`, which fall back to the home directory.
+pub fn expand_path(path: &str) -> Result<PathBuf> {
+    let Some(tilde) = path.strip_prefix('~') else {
+        return Ok(PathBuf::from(expand_variables(path)?));
+    };
+    let (user, rest) = tilde.split_at(tilde.find('/').unwrap_or(tilde.len()));
+    let home = if user.is_empty() {
+        tilde_home().ok_or(DeclairError::NoHomeDir)?
+    } else {
+        user_home(user).ok_or_else(|| DeclairError::InvalidPath {
+            path: PathBuf::from(path),
+            reason: t!("paths.no_user", user),
+        })?
+    };
+    let rest = expand_variables(rest.trim_start_matches('/'))?;
+    Ok(if rest.is_empty() {
+        home
+    } else {
+        home.join(rest)
+    })
+}
+
+/// Characters starting a variable reference: `%` only on Windows, where `%VAR%` is how
+/// paths name variables; elsewhere it is an ordinary file name character.
+const REFERENCE_STARTS: &[char] = if cfg!(windows) { &['$', '%'] } else { &['$'] };
+
+/// `text` with its `$VAR` and `${VAR}` references replaced, and `%VAR%` ones on Windows.
+/// A `$` or `%` not starting one is kept as it is.
+fn expand_variables(text: &str) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find(REFERENCE_STARTS) {
+        expanded.push_str(&rest[..pos]);
+        let reference = &rest[pos..];
+        let after = &reference[1..];
+        let found = if let Some(braced) = reference.strip_prefix("${") {
+            braced.find('}').map(|end| (&braced[..end], end + 3))
+        } else if reference.starts_with('$') {
+            let len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            Some((&after[..len], len + 1))
+        } else {
+            after.find('%').map(|end| (&after[..end], end + 2))
+        };
+        match found {
+            Some((name, len)) if is_variable_name(name) => {
+                expanded.push_str(&variable(name, text)?);
+                rest = &reference[len..];
+            }
+            _ => {
+                expanded.push_str(&reference[..1]);
+                rest = after;
+            }
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The value of the environment variable `name`, referenced in `path`.
+fn variable(name: &str, path: &str) -> Result<String> {
+    if let Ok(value) = env::var(name) {
+        return Ok(value);
+    }
+    if matches!(name, "HOME" | "USERPROFILE") {
+        let home = home_dir().ok_or(DeclairError::NoHomeDir)?;
+        return Ok(home.to_string_lossy().into_owned());
+    }
+    Err(DeclairError::InvalidPath {
+        path: PathBuf::from(path),
+        reason: t!("paths.unset_variable", name),
+    })
+}
+
+/// The home directory `~` stands for: the user's, except under sudo, where it is the home
+/// of the user who ran sudo rather than root's, since that is whose files they mean.
+pub fn tilde_home() -> Option<PathBuf> {
+    Privilege::current()
+        .invoker()
+        .and_then(user_home)
+        .or_else(home_dir)
+}
+
+/// Home directory of the user `name`, from the password database.
+#[cfg(unix)]
+pub fn user_home(name: &str) -> Option<PathBuf> {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = CString::new(name).ok()?;
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: an all-zero `passwd` is a valid out-parameter; getpwnam_r fills it with
+    // pointers into `buf`, which outlives their use below.
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() || pwd.pw_dir.is_null() {
+        return None;
+    }
+    // SAFETY: getpwnam_r succeeded, so `pw_dir` is a NUL-terminated string in `buf`.
+    let dir = unsafe { CStr::from_ptr(pwd.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+/// Other users' home directories are only known from a Unix password database.
+#[cfg(not(unix))]
+pub fn user_home(_name: &str) -> Option<PathBuf> {
+    None
+}
+
+/// `path` as stored in declair's config: under the home directory it is written with `~/`,
+/// also when reached through a symlink to the home directory, so the config keeps working
+/// when the home directory moves. Other paths are kept as they are.
+pub fn contract_home(path: &Path) -> String {
+    let Some(home) = tilde_home() else {
+        return path.display().to_string();
+    };
+    let canonical_home = fs::canonicalize(&home).ok();
+    let canonical_path = fs::canonicalize(path).ok();
+    let homes = [Some(home), canonical_home];
+    let paths = [Some(path.to_path_buf()), canonical_path];
+    for home in homes.iter().flatten() {
+        for candidate in paths.iter().flatten() {
+            if let Ok(rest) = candidate.strip_prefix(home) {
+                return if rest.as_os_str().is_empty() {
+                    "~".to_string()
+                } else {
+                    format!("~/{}", rest.display())
+                };
+            }
+        }
+    }
+    path.display().to_string()
+}
+
+pub fn get_git_repo_or_parent_directory(path: &PathBuf) -> Result<PathBuf> {
+    // Check if path exists
+    if !path.exists() {
+        return Err(DeclairError::InvalidPath {
+            path: path.clone(),
+            reason: t!("paths.missing"),
+        });
+    }
+    // Try to find a repository
+    match discover(path) {
+        Ok(repo) => {
+            // Found repository - return its working directory
+            let workdir = repo.workdir().ok_or_else(|| DeclairError::InvalidPath {
+                path: path.clone(),
+                reason: t!("paths.no_workdir"),
+            })?;
+            Ok(workdir.to_path_buf())
+        }
+        Err(_) => {
+            // Repository not found
+            if path.is_dir() {
+                // Return the path itself (directory)
+                Ok(path.to_path_buf())
+            } else {
+                // It's a file - return its parent directory
+                let parent = path.parent().ok_or_else(|| DeclairError::InvalidPath {
+                    path: path.clone(),
+                    reason: t!("paths.no_parent"),
+                })?;
+                Ok(parent.to_path_buf())
+            }
+        }
+    }
+}
+
+/// If given path is a directory, try to find a likely NixOS config file inside it.
+/// Returns an error if nothing suitable is found.
+pub fn resolve_nix_config(path: &Path) -> Result<PathBuf> {
+    if path.exists() && path.is_file() {
+        return Ok(path.to_path_buf());
+    } else if path.exists() && path.is_dir() {
+        let candidates = [
+            "configuration.nix",
+            "flake.nix",
+            "default.nix",
+            "home.nix",
+            "pkgs.nix",
+        ];
+        for cand in &candidates {
+            let p = path.join(cand);
+            if p.exists() && p.is_file() {
+                return Ok(p);
+            }
+        }
+        return Err(DeclairError::InvalidPath {
+            path: path.to_path_buf(),
+            reason: t!("paths.no_config_in_dir", candidates.join(", ")),
+        });
+    }
+    Err(DeclairError::InvalidPath {
+        path: path.to_path_buf(),
+        reason: t!("paths.not_found"),
+    })
+}
+
+pub fn get_config_dir() -> Option<PathBuf> {
+    let proj_dirs = ProjectDirs::from("com", "timasoft", "declair")?;
+    Some(proj_dirs.config_dir().to_path_buf())
+}
+
+pub fn get_cache_dir() -> Option<PathBuf> {
+    let proj_dirs = ProjectDirs::from("com", "timasoft", "declair")?;
+    Some(proj_dirs.cache_dir().to_path_buf())
+}
+
+pub fn get_data_dir() -> Option<PathBuf> {
+    let proj_dirs = ProjectDirs::from("com", "timasoft", "declair")?;
+    Some(proj_dirs.data_dir().to_path_buf())
+}
+
+/// Where declair keeps what piles up while it works: install records and backups of edited
+/// files. `$XDG_STATE_HOME/declair` on Linux, the data dir elsewhere.
+pub fn get_state_dir() -> Option<PathBuf> {
+    let proj_dirs = ProjectDirs::from("com", "timasoft", "declair")?;
+    Some(
+        proj_dirs
+            .state_dir()
+            .unwrap_or(proj_dirs.data_dir())
+            .to_path_buf(),
+    )
+}
+
+/// Where backups of edited files are kept (see [`crate::edit::backup_path`]).
+pub fn get_backups_dir() -> Option<PathBuf> {
+    Some(get_state_dir()?.join("backups"))
+}
+
+/// Where earlier states of edited files are kept (see [`crate::backups`]).
+pub fn get_history_dir() -> Option<PathBuf> {
+    Some(get_state_dir()?.join("history"))
+}
+
+/// Where the full logs of the latest rebuilds are kept.
+pub fn get_logs_dir() -> Option<PathBuf> {
+    Some(get_state_dir()?.join("logs"))
+}
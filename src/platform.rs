@@ -0,0 +1,108 @@
+//! Whether a package builds for the system a configuration targets, from its
+//! `meta.platforms` and `meta.badPlatforms`.
+
+use crate::audit::Nixpkgs;
+use crate::error::Result;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec, eval_json};
+use crate::syntax::nix_string;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const EVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Kernels of Nix system doubles such as `x86_64-linux` or `aarch64-darwin`.
+const KERNELS: &[&str] = &["linux", "darwin", "freebsd", "openbsd", "netbsd", "cygwin"];
+
+/// Whether `s` looks like a Nix system double.
+pub fn is_system(s: &str) -> bool {
+    match s.split_once('-') {
+        Some((arch, kernel)) => {
+            !arch.is_empty()
+                && arch.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && KERNELS.contains(&kernel)
+        }
+        None => false,
+    }
+}
+
+/// The system set on `line`: `system = "x86_64-linux";`,
+/// `nixpkgs.hostPlatform = lib.mkDefault "aarch64-linux";` or
+/// `nixpkgs.legacyPackages.x86_64-linux`.
+pub fn system_on_line(line: &str) -> Option<String> {
+    let code = line.split('#').next().unwrap_or("");
+    if code.contains("system") || code.contains("hostPlatform") {
+        let quoted = code.split('"').skip(1).step_by(2).find(|s| is_system(s));
+        if let Some(system) = quoted {
+            return Some(system.to_string());
+        }
+    }
+    code.split(|c: char| c.is_whitespace() || ";()[]{}".contains(c))
+        .filter_map(|token| token.split_once("legacyPackages."))
+        .map(|(_, rest)| rest.split('.').next().unwrap_or(""))
+        .find(|system| is_system(system))
+        .map(str::to_string)
+}
+
+/// The first system set in `files`, looked at in order (say the edited module, its
+/// `hardware-configuration.nix` and the flake). `None` when none names one.
+pub fn target_system(files: &[PathBuf]) -> Option<String> {
+    files
+        .iter()
+        .filter_map(|file| fs::read_to_string(file).ok())
+        .find_map(|contents| contents.lines().find_map(system_on_line))
+}
+
+/// How a package fares on a system.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PlatformSupport {
+    pub system: String,
+    /// Whether `meta.platforms` includes the system and `meta.badPlatforms` doesn't.
+    pub available: bool,
+    /// The systems `meta.platforms` lists by name, for the message.
+    pub platforms: Vec<String>,
+}
+
+/// `nix eval` of how `pkg` in `nixpkgs` fares on `system`, or the evaluating machine's
+/// system when it is `None`. Prints `null` for a missing package.
+pub fn platform_command(nixpkgs: &Nixpkgs, pkg: &str, system: Option<&str>) -> CommandSpec {
+    let system = system.map_or("builtins.currentSystem".to_string(), nix_string);
+    let expr = format!(
+        "let pkgs = {}; system = {}; \
+         p = pkgs.lib.attrByPath (pkgs.lib.splitString \".\" {}) null pkgs; \
+         in if p == null then null else {{ \
+         inherit system; \
+         available = pkgs.lib.meta.availableOn (pkgs.lib.systems.elaborate system) p; \
+         platforms = builtins.filter builtins.isString (p.meta.platforms or [ ]); }}",
+        nixpkgs.expr(),
+        system,
+        nix_string(pkg)
+    );
+    CommandSpec::new(
+        "nix",
+        [
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+            &expr,
+            "--extra-experimental-features",
+            "nix-command flakes",
+        ],
+    )
+    .timeout(EVAL_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query()
+}
+
+/// How `pkg` in `nixpkgs` fares on `system` (see [`platform_command`]); `None` when
+/// nixpkgs has no such package.
+pub fn platform_support(
+    runner: &dyn CommandRunner,
+    nixpkgs: &Nixpkgs,
+    pkg: &str,
+    system: Option<&str>,
+) -> Result<Option<PlatformSupport>> {
+    eval_json(runner, &platform_command(nixpkgs, pkg, system))
+}
@@ -0,0 +1,230 @@
+//! External plugins: executables named `declair-<name>` on `PATH`, like cargo subcommands.
+//! declair writes one JSON-RPC 2.0 request to a plugin's stdin and reads one response from
+//! its stdout. A search backend answers `search {"query"}` with an object mapping attribute
+//! paths to `{"pname", "version", "description"}`, as `nix search --json` does; an editor
+//! answers `add`/`remove {"file", "package", "kind", "scope"}` by editing `file` in place,
+//! with `scope` `system`, `user:<name>` or `home`.
+
+use crate::edit::{add_package_to_nix, remove_package_from_nix, replace_package_in_nix};
+use crate::error::{DeclairError, Result};
+use crate::module::Scope;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use crate::search::PackageInfo;
+use crate::t;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi::OsStr;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const PLUGIN_PREFIX: &str = "declair-";
+/// Searching may evaluate a whole package set, like `nix search`.
+const PLUGIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Plugins to use, from the `[plugins]` config table.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Plugins {
+    /// Search backends queried next to nixpkgs, e.g. `["nur"]` for `declair-nur`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub search: Vec<String>,
+    /// Plugin adding and removing packages instead of declair's own editor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+}
+
+/// An installed plugin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plugin {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// Windows has no executable bit; any file named `declair-<name>` runs.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Every `declair-<name>` executable in the directories of `search_path` (a `PATH` value),
+/// sorted by name. The first directory providing a name wins, as it does for the shell.
+/// declair's own binary, `declair-rs`, is not a plugin.
+pub fn discover_in(search_path: &OsStr) -> Vec<Plugin> {
+    let mut plugins = BTreeMap::new();
+    for dir in env::split_paths(search_path) {
+        let Ok(entries) = dir.read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(name) = file_name
+                .to_str()
+                .and_then(|n| n.strip_prefix(PLUGIN_PREFIX))
+            else {
+                continue;
+            };
+            if name.is_empty()
+                || file_name == env!("CARGO_PKG_NAME")
+                || plugins.contains_key(name)
+                || !is_executable(&entry.path())
+            {
+                continue;
+            }
+            plugins.insert(name.to_string(), entry.path());
+        }
+    }
+    plugins
+        .into_iter()
+        .map(|(name, path)| Plugin { name, path })
+        .collect()
+}
+
+/// Plugins installed on `PATH`.
+pub fn discover() -> Vec<Plugin> {
+    discover_in(&env::var_os("PATH").unwrap_or_default())
+}
+
+/// The plugin called `name`, looked up on `PATH`.
+pub fn find_plugin(name: &str) -> Result<Plugin> {
+    discover()
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| DeclairError::Plugin {
+            name: name.to_string(),
+            reason: t!("plugin.not_found", PLUGIN_PREFIX, name),
+        })
+}
+
+impl Plugin {
+    /// The command sending `method` with `params` to the plugin.
+    pub fn request(&self, method: &str, params: Value) -> CommandSpec {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        CommandSpec::new(&self.path.to_string_lossy(), Vec::<String>::new())
+            .stdin(request.to_string())
+            .timeout(PLUGIN_TIMEOUT)
+            .class(CommandClass::Search)
+    }
+
+    /// Send one request and return the response's `result`.
+    pub fn call(&self, runner: &dyn CommandRunner, method: &str, params: Value) -> Result<Value> {
+        let fail = |reason: String| DeclairError::Plugin {
+            name: self.name.clone(),
+            reason,
+        };
+        let output = runner
+            .output(&self.request(method, params))
+            .map_err(|e| fail(e.to_string()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(fail(format!("{} {}", output.status, stderr.trim())));
+        }
+        let mut response: Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| fail(t!("plugin.invalid_response", e)))?;
+        if let Some(error) = response.get("error") {
+            let message = error
+                .get("message")
+                .and_then(Value::as_str)
+                .map_or_else(|| error.to_string(), str::to_string);
+            return Err(fail(message));
+        }
+        match response.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(fail(t!("plugin.invalid_response", "missing `result`"))),
+        }
+    }
+
+    /// Ask a search backend for packages matching `query`.
+    pub fn search(
+        &self,
+        runner: &dyn CommandRunner,
+        query: &str,
+    ) -> Result<HashMap<String, PackageInfo>> {
+        let result = self.call(runner, "search", json!({ "query": query }))?;
+        serde_json::from_value(result).map_err(|e| DeclairError::Plugin {
+            name: self.name.clone(),
+            reason: t!("plugin.invalid_response", e),
+        })
+    }
+
+    /// Ask an editor to add (`"add"`) or remove (`"remove"`) `pkg` in `file`.
+    pub fn edit(
+        &self,
+        runner: &dyn CommandRunner,
+        method: &str,
+        file: &Path,
+        pkg: &str,
+        scope: impl Into<Scope>,
+    ) -> Result<()> {
+        let scope = scope.into();
+        let params = json!({
+            "file": file,
+            "package": pkg,
+            "kind": scope.kind(),
+            "scope": scope.to_string(),
+        });
+        self.call(runner, method, params).map(|_| ())
+    }
+}
+
+impl Plugins {
+    /// Add `pkg` to `file` with the editor plugin, or declair's own editor without one.
+    pub fn add_package(
+        &self,
+        runner: &dyn CommandRunner,
+        file: &Path,
+        pkg: &str,
+        scope: impl Into<Scope>,
+    ) -> Result<()> {
+        let scope = scope.into();
+        match &self.editor {
+            Some(name) => find_plugin(name)?.edit(runner, "add", file, pkg, &scope),
+            None => add_package_to_nix(file, pkg, scope),
+        }
+    }
+
+    /// Remove `pkg` from `file` with the editor plugin, or declair's own editor without one.
+    pub fn remove_package(
+        &self,
+        runner: &dyn CommandRunner,
+        file: &Path,
+        pkg: &str,
+        scope: impl Into<Scope>,
+    ) -> Result<()> {
+        let scope = scope.into();
+        match &self.editor {
+            Some(name) => find_plugin(name)?.edit(runner, "remove", file, pkg, &scope),
+            None => remove_package_from_nix(file, pkg, scope),
+        }
+    }
+
+    /// Replace `old` with `new` in `file`. The editor plugin removes one and adds the
+    /// other; declair's own editor swaps the entry in place.
+    pub fn replace_package(
+        &self,
+        runner: &dyn CommandRunner,
+        file: &Path,
+        old: &str,
+        new: &str,
+        scope: impl Into<Scope>,
+    ) -> Result<()> {
+        let scope = scope.into();
+        match &self.editor {
+            Some(name) => {
+                let plugin = find_plugin(name)?;
+                plugin.edit(runner, "remove", file, old, &scope)?;
+                plugin.edit(runner, "add", file, new, &scope)
+            }
+            None => replace_package_in_nix(file, old, new, scope),
+        }
+    }
+}
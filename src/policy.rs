@@ -0,0 +1,227 @@
+//! License policy: `[licenses]` allow and deny lists checked against the `meta.license`
+//! of packages before they are added, and by `declair audit --licenses`.
+
+use crate::audit::Nixpkgs;
+use crate::error::{DeclairError, Result};
+use crate::evalcache::EvalCache;
+use crate::references::matches_pattern;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec, eval_json};
+use crate::syntax::nix_string;
+use crate::t;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+const EVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Query name of cached license lookups.
+const CACHE_QUERY: &str = "licenses";
+
+/// Allowed and denied licenses, from the `[licenses]` config table. Entries are SPDX IDs
+/// or nixpkgs short names with optional `*` wildcards (`AGPL-*`, `agpl3*`), or `unfree`
+/// for any license nixpkgs doesn't consider free.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct LicensePolicy {
+    /// When not empty, every license of a package must match one of these.
+    pub allow: Vec<String>,
+    /// No license of a package may match any of these.
+    pub deny: Vec<String>,
+}
+
+/// One license of a package.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct License {
+    pub spdx_id: Option<String>,
+    pub short_name: Option<String>,
+    pub free: bool,
+}
+
+impl License {
+    /// The licenses of a `meta.license` value: a license attribute set, a list of them,
+    /// or a bare name (taken as free).
+    pub fn from_meta(value: &Value) -> Vec<License> {
+        match value {
+            Value::String(name) => vec![License {
+                spdx_id: None,
+                short_name: Some(name.clone()),
+                free: true,
+            }],
+            Value::Array(items) => items.iter().flat_map(License::from_meta).collect(),
+            Value::Object(license) => {
+                let text = |key: &str| license.get(key).and_then(Value::as_str).map(str::to_string);
+                vec![License {
+                    spdx_id: text("spdxId"),
+                    short_name: text("shortName").or_else(|| text("fullName")),
+                    free: license.get("free").and_then(Value::as_bool).unwrap_or(true),
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The SPDX ID, or the nixpkgs short name without one.
+    pub fn name(&self) -> &str {
+        self.spdx_id
+            .as_deref()
+            .or(self.short_name.as_deref())
+            .unwrap_or("?")
+    }
+
+    fn matches(&self, pattern: &str) -> bool {
+        if pattern == "unfree" {
+            return !self.free;
+        }
+        [&self.spdx_id, &self.short_name]
+            .into_iter()
+            .flatten()
+            .any(|name| matches_pattern(name, pattern))
+    }
+}
+
+impl fmt::Display for License {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.free {
+            f.write_str(self.name())
+        } else {
+            write!(f, "{} (unfree)", self.name())
+        }
+    }
+}
+
+impl LicensePolicy {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Why `licenses` break the policy, one reason per offending license; empty when
+    /// they comply. A package without license information only complies without an
+    /// allow list.
+    pub fn violations(&self, licenses: &[License]) -> Vec<String> {
+        if licenses.is_empty() && !self.allow.is_empty() {
+            return vec!["no license information".to_string()];
+        }
+        licenses
+            .iter()
+            .filter_map(|license| {
+                if let Some(rule) = self.deny.iter().find(|rule| license.matches(rule)) {
+                    Some(format!("{} is denied by `{}`", license, rule))
+                } else if !self.allow.is_empty()
+                    && !self.allow.iter().any(|rule| license.matches(rule))
+                {
+                    Some(format!("{} is not allowed", license))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// `nix eval` of the `meta.license` of `packages` in `nixpkgs`, normalized to a list of
+/// `{ spdxId, shortName, free }` per package. Missing packages get an empty list.
+pub fn licenses_command(nixpkgs: &Nixpkgs, packages: &[String]) -> CommandSpec {
+    let names: Vec<String> = packages.iter().map(|p| nix_string(p)).collect();
+    let expr = format!(
+        "let pkgs = {}; \
+         norm = l: if builtins.isAttrs l \
+         then {{ spdxId = l.spdxId or null; shortName = l.shortName or null; free = l.free or true; }} \
+         else {{ spdxId = null; shortName = toString l; free = true; }}; \
+         licenses = n: let p = pkgs.lib.attrByPath (pkgs.lib.splitString \".\" n) null pkgs; \
+         l = if p == null then [ ] else p.meta.license or [ ]; \
+         in map norm (if builtins.isList l then l else [ l ]); \
+         in builtins.listToAttrs (map (n: {{ name = n; value = licenses n; }}) [ {} ])",
+        nixpkgs.expr(),
+        names.join(" ")
+    );
+    CommandSpec::new(
+        "nix",
+        [
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+            &expr,
+            "--extra-experimental-features",
+            "nix-command flakes",
+        ],
+    )
+    .timeout(EVAL_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query()
+}
+
+/// The licenses of `packages` in `nixpkgs`, by package. With a `cache`, only the packages
+/// it doesn't know yet are evaluated.
+pub fn package_licenses(
+    runner: &dyn CommandRunner,
+    nixpkgs: &Nixpkgs,
+    cache: Option<&EvalCache>,
+    packages: &[String],
+) -> Result<BTreeMap<String, Vec<License>>> {
+    let mut licenses = BTreeMap::new();
+    let mut missing = Vec::new();
+    for pkg in packages {
+        let cached = cache
+            .and_then(|cache| cache.get(CACHE_QUERY, pkg))
+            .and_then(|json| serde_json::from_slice(&json).ok());
+        match cached {
+            Some(cached) => {
+                licenses.insert(pkg.clone(), cached);
+            }
+            None => missing.push(pkg.clone()),
+        }
+    }
+    if missing.is_empty() {
+        return Ok(licenses);
+    }
+    let evaluated: BTreeMap<String, Vec<License>> =
+        eval_json(runner, &licenses_command(nixpkgs, &missing))?;
+    if let Some(cache) = cache {
+        for (pkg, pkg_licenses) in &evaluated {
+            if let Ok(json) = serde_json::to_vec(pkg_licenses) {
+                cache.put(CACHE_QUERY, pkg, &json);
+            }
+        }
+    }
+    licenses.extend(evaluated);
+    Ok(licenses)
+}
+
+/// Fail with [`DeclairError::PolicyViolation`] unless `pkg` complies with `policy`. A
+/// package whose licenses can't be looked up doesn't comply either.
+pub fn check_policy(
+    runner: &dyn CommandRunner,
+    policy: &LicensePolicy,
+    nixpkgs: &Nixpkgs,
+    cache: Option<&EvalCache>,
+    pkg: &str,
+) -> Result<()> {
+    if policy.is_empty() {
+        return Ok(());
+    }
+    let licenses = package_licenses(runner, nixpkgs, cache, &[pkg.to_string()]).map_err(|e| {
+        DeclairError::PolicyViolation {
+            pkg: pkg.to_string(),
+            reason: t!("policy.unchecked", e),
+        }
+    })?;
+    check_licenses(policy, pkg, licenses.get(pkg).map_or(&[], Vec::as_slice))
+}
+
+/// Fail with [`DeclairError::PolicyViolation`] unless `licenses`, already known for `pkg`
+/// (say from a search result), comply with `policy`.
+pub fn check_licenses(policy: &LicensePolicy, pkg: &str, licenses: &[License]) -> Result<()> {
+    let reasons = policy.violations(licenses);
+    if reasons.is_empty() {
+        Ok(())
+    } else {
+        Err(DeclairError::PolicyViolation {
+            pkg: pkg.to_string(),
+            reason: reasons.join("; "),
+        })
+    }
+}
@@ -0,0 +1,52 @@
+//! Running many independent lookups, such as one `nix eval` per package, on a bounded
+//! number of threads instead of one after another.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Upper bound of [`default_jobs`]: every worker runs a Nix evaluator of its own.
+pub const MAX_DEFAULT_JOBS: usize = 8;
+
+/// Workers to use when the config doesn't say: the available CPUs, at most
+/// [`MAX_DEFAULT_JOBS`].
+pub fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_DEFAULT_JOBS)
+}
+
+/// `f` applied to every item on at most `jobs` threads, with the results in the order of
+/// `items`. `done` is called after each item, by the worker that finished it, e.g. to
+/// advance a progress bar.
+pub fn map_bounded<T, R, F>(items: &[T], jobs: usize, done: impl Fn() + Sync, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    let workers = jobs.clamp(1, items.len().max(1));
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    let result = f(item);
+                    results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(result);
+                    done();
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .map(|r| r.expect("every item is mapped once the workers are done"))
+        .collect()
+}
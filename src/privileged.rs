@@ -0,0 +1,212 @@
+use crate::atomic::{can_replace, temp_path, write_atomic};
+use crate::backups::record_backup;
+use crate::edit::{backup_path, create_backup_dir};
+use crate::error::{DeclairError, Result};
+use crate::host::Privilege;
+use crate::lock::FileSnapshot;
+use crate::paths::user_home;
+use crate::runner::{CommandRunner, CommandSpec};
+use crate::timings::time;
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// Backs up the target, then replaces it atomically with stdin, keeping its mode and owner.
+/// The backup goes to the user running sudo, whose state dir it is usually in.
+/// Arguments: `$1` temporary file, `$2` target, `$3` backup.
+const SUDO_WRITE_SCRIPT: &str = r#"set -e
+cp -p -- "$2" "$3"
+if [ -n "$SUDO_UID" ]; then chown -- "$SUDO_UID:$SUDO_GID" "$3"; fi
+cat > "$1"
+chmod --reference="$2" -- "$1"
+chown --reference="$2" -- "$1"
+mv -f -- "$1" "$2""#;
+
+/// Command that writes `contents` to `target` as root, leaving a backup of the old
+/// contents at [`backup_path`] like the unprivileged edits do. Its directory must exist.
+pub fn sudo_write_command(target: &Path, contents: &[u8]) -> CommandSpec {
+    let target = fs::canonicalize(target).unwrap_or_else(|_| target.to_path_buf());
+    let tmp = temp_path(&target).unwrap_or_else(|_| target.with_extension("declair.tmp"));
+    let backup = backup_path(&target);
+    CommandSpec::new(
+        "sudo",
+        [
+            "sh".to_string(),
+            "-c".to_string(),
+            SUDO_WRITE_SCRIPT.to_string(),
+            "sh".to_string(),
+            tmp.display().to_string(),
+            target.display().to_string(),
+            backup.display().to_string(),
+        ],
+    )
+    .stdin(contents)
+}
+
+/// A user-writable copy of a config file the current user can't write (e.g. a root-owned
+/// `/etc/nixos/configuration.nix`). Edits are made to the copy and written back with sudo.
+pub struct ScratchCopy {
+    _dir: TempDir,
+    path: PathBuf,
+    original: PathBuf,
+    /// The original's contents when the copy was taken, for the backup history.
+    before: Vec<u8>,
+    snapshot: FileSnapshot,
+}
+
+impl ScratchCopy {
+    pub fn new(original: &Path) -> Result<Self> {
+        let contents = fs::read(original).map_err(|e| DeclairError::Read {
+            path: original.to_path_buf(),
+            source: e,
+        })?;
+        let dir = tempfile::tempdir()?;
+        // Keep the file name so anything keyed on it (backups, formatters) behaves the same.
+        let name = original.file_name().unwrap_or("configuration.nix".as_ref());
+        let path = dir.path().join(name);
+        fs::write(&path, &contents).map_err(|e| DeclairError::Write {
+            path: path.clone(),
+            source: e,
+        })?;
+        Ok(ScratchCopy {
+            _dir: dir,
+            path,
+            original: original.to_path_buf(),
+            snapshot: FileSnapshot::of_contents(original, &contents),
+            before: contents,
+        })
+    }
+
+    /// The copy to edit in place of the original.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file this is a copy of.
+    pub fn original(&self) -> &Path {
+        &self.original
+    }
+
+    /// Fail with [`DeclairError::ConcurrentModification`] if the original changed since
+    /// the copy was taken.
+    pub fn verify(&self) -> Result<()> {
+        self.snapshot.verify()
+    }
+
+    /// Write the edited copy over the original: directly if the current user can replace
+    /// it, otherwise with sudo. Either way a backup is left at [`backup_path`].
+    pub fn apply(self, runner: &dyn CommandRunner) -> Result<()> {
+        if !can_replace(&self.original) {
+            return self.write_back(runner);
+        }
+        self.snapshot.verify()?;
+        let contents = fs::read(&self.path).map_err(|e| DeclairError::Read {
+            path: self.path.clone(),
+            source: e,
+        })?;
+        if FileSnapshot::of_contents(&self.original, &contents) == self.snapshot {
+            return Ok(());
+        }
+        let backup = backup_path(&self.original);
+        create_backup_dir(&backup)?;
+        fs::copy(&self.original, &backup).map_err(|e| DeclairError::Backup {
+            path: backup,
+            source: e,
+        })?;
+        record_backup(&self.original, &self.before)?;
+        write_atomic(&self.original, &contents).map_err(|e| DeclairError::Write {
+            path: self.original.clone(),
+            source: e,
+        })
+    }
+
+    /// Write the edited copy over the original with sudo. Refuses if the original changed
+    /// since the copy was taken, and does nothing if the copy wasn't modified.
+    pub fn write_back(self, runner: &dyn CommandRunner) -> Result<()> {
+        time("write", || self.copy_back(runner))
+    }
+
+    fn copy_back(self, runner: &dyn CommandRunner) -> Result<()> {
+        self.snapshot.verify()?;
+        let contents = fs::read(&self.path).map_err(|e| DeclairError::Read {
+            path: self.path.clone(),
+            source: e,
+        })?;
+        if FileSnapshot::of_contents(&self.original, &contents) == self.snapshot {
+            return Ok(());
+        }
+        create_backup_dir(&backup_path(&self.original))?;
+        let output = runner.output(&sudo_write_command(&self.original, &contents))?;
+        if !output.status.success() {
+            return Err(DeclairError::PrivilegedWriteFailed {
+                file: self.original,
+                status: output.status,
+            });
+        }
+        record_backup(&self.original, &self.before)
+    }
+}
+
+/// What may go wrong editing a config file as root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootRisk {
+    /// A Home Manager config belongs to a user, but without sudo there is no user to
+    /// rebuild it as: `home-manager` run as root builds root's home.
+    HomeManager,
+    /// The file belongs to the user with id `uid`; the backups and new files declair
+    /// writes next to it will belong to root.
+    Owner { uid: u32 },
+    /// `$HOME` is `home` (root's), while `~` in paths stands for `user_home`, the home of
+    /// `user`, who ran sudo.
+    Home {
+        home: PathBuf,
+        user: String,
+        user_home: PathBuf,
+    },
+}
+
+/// What may go wrong editing `file`, a Home Manager config if `home_manager`, with
+/// `privilege`, given `$HOME`. Nothing for an ordinary user.
+pub fn root_risks(
+    file: &Path,
+    home_manager: bool,
+    privilege: &Privilege,
+    home: Option<&Path>,
+) -> Vec<RootRisk> {
+    let Privilege::Root { invoker } = privilege else {
+        return Vec::new();
+    };
+    let mut risks = Vec::new();
+    if home_manager && invoker.is_none() {
+        risks.push(RootRisk::HomeManager);
+    }
+    if let Some(uid) = owner(file)
+        && uid != 0
+    {
+        risks.push(RootRisk::Owner { uid });
+    }
+    if let (Some(user), Some(home)) = (invoker, home)
+        && let Some(user_home) = user_home(user)
+        && home != user_home
+    {
+        risks.push(RootRisk::Home {
+            home: home.to_path_buf(),
+            user: user.clone(),
+            user_home,
+        });
+    }
+    risks
+}
+
+/// User id owning `file`.
+#[cfg(unix)]
+fn owner(file: &Path) -> Option<u32> {
+    fs::metadata(file).ok().map(|meta| meta.uid())
+}
+
+#[cfg(not(unix))]
+fn owner(_file: &Path) -> Option<u32> {
+    None
+}
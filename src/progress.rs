@@ -0,0 +1,198 @@
+//! Progress display of NixOS rebuilds, drawn from Nix's `internal-json` log (see
+//! [`crate::events::NixLog`]): how many derivations are built and paths downloaded, and
+//! which derivations are being built, with Nix's messages printed above. Without a
+//! terminal, the log is printed as plain lines instead. The full log, build logs
+//! included, is saved to a file either way. Also the spinner shown while declair waits
+//! for a command whose output it captures.
+
+use crate::events::{Event, NixLine, NixLog, emit};
+use crate::t;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Rebuild logs kept by [`save_log`]; older ones are removed.
+pub const MAX_LOGS: usize = 10;
+
+/// A spinner on stderr saying `message`, drawn only on a terminal.
+pub fn spinner(message: &str) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::with_template("{spinner} {msg} [{elapsed}]")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    spinner.set_message(message.to_string());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
+
+/// Run `f` with a [`spinner`] saying `message`, cleared when it is done.
+pub fn with_spinner<T>(message: &str, f: impl FnOnce() -> T) -> T {
+    let spinner = spinner(message);
+    let result = f();
+    spinner.finish_and_clear();
+    result
+}
+
+/// The bars of a [`RebuildDisplay`] drawn on a terminal.
+struct Bars {
+    multi: MultiProgress,
+    current: ProgressBar,
+    builds: Option<ProgressBar>,
+    downloads: Option<ProgressBar>,
+}
+
+impl Bars {
+    fn new() -> Self {
+        let multi = MultiProgress::with_draw_target(ProgressDrawTarget::stderr());
+        let current = multi.add(ProgressBar::new_spinner());
+        current.set_style(
+            ProgressStyle::with_template("{spinner} {wide_msg} [{elapsed}]")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        );
+        current.enable_steady_tick(Duration::from_millis(100));
+        Bars {
+            multi,
+            current,
+            builds: None,
+            downloads: None,
+        }
+    }
+
+    /// A bar counting `label`, added above the spinner.
+    fn counter(multi: &MultiProgress, current: &ProgressBar, label: String) -> ProgressBar {
+        let bar = multi.insert_before(current, ProgressBar::new(0));
+        bar.set_style(
+            ProgressStyle::with_template("{prefix:>12} [{bar:30}] {pos}/{len}")
+                .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+        bar.set_prefix(label);
+        bar
+    }
+
+    fn update(bar: &ProgressBar, done: u64, expected: u64) {
+        bar.set_length(expected);
+        bar.set_position(done);
+    }
+}
+
+/// Shows a rebuild's `internal-json` log as it is read, line by line.
+pub struct RebuildDisplay {
+    log: NixLog,
+    bars: Option<Bars>,
+}
+
+impl RebuildDisplay {
+    /// Draw progress bars when `terminal`, otherwise print the log as plain lines.
+    pub fn new(terminal: bool) -> Self {
+        RebuildDisplay {
+            log: NixLog::new(),
+            bars: terminal.then(Bars::new),
+        }
+    }
+
+    /// Read one line of the log, updating the display and emitting progress events.
+    /// Returns the line as text for the rebuild's log, unless it has none.
+    pub fn line(&mut self, line: &str) -> Option<String> {
+        let text = match self.log.line(line) {
+            NixLine::Text(text) => {
+                self.show(&text);
+                Some(text)
+            }
+            NixLine::Activity(text) => {
+                if self.bars.is_none() {
+                    eprintln!("{}", text);
+                } else if self.log.building().is_empty() {
+                    // Downloads and evaluation have no bar of their own; the spinner says
+                    // what Nix is up to.
+                    self.set_current(&text);
+                }
+                Some(text)
+            }
+            NixLine::BuildLog(text) => Some(text),
+            NixLine::Progress(event) => {
+                if let Some(bars) = &mut self.bars
+                    && let Event::RebuildProgress { done, expected, .. } = event
+                {
+                    let bar = bars.builds.get_or_insert_with(|| {
+                        Bars::counter(&bars.multi, &bars.current, t!("rebuild.progress_builds"))
+                    });
+                    Bars::update(bar, done, expected);
+                }
+                emit(&event);
+                None
+            }
+            NixLine::Quiet => None,
+        };
+        if let Some(bars) = &mut self.bars {
+            if let Some((done, expected)) = self.log.downloads() {
+                let bar = bars.downloads.get_or_insert_with(|| {
+                    Bars::counter(&bars.multi, &bars.current, t!("rebuild.progress_downloads"))
+                });
+                Bars::update(bar, done, expected);
+            }
+            let building = self.log.building();
+            if !building.is_empty() {
+                bars.current
+                    .set_message(t!("rebuild.progress_building", building.join(", ")));
+            }
+        }
+        text
+    }
+
+    /// Clear the bars, leaving the printed messages.
+    pub fn finish(self) {
+        if let Some(bars) = self.bars {
+            for bar in [bars.builds, bars.downloads].into_iter().flatten() {
+                bar.finish_and_clear();
+            }
+            bars.current.finish_and_clear();
+            let _ = bars.multi.clear();
+        }
+    }
+
+    /// Print a message of Nix, above the bars when there are any.
+    fn show(&self, text: &str) {
+        match &self.bars {
+            Some(bars) => {
+                let _ = bars.multi.println(text);
+            }
+            None => eprintln!("{}", text),
+        }
+    }
+
+    fn set_current(&self, text: &str) {
+        if let Some(bars) = &self.bars {
+            bars.current.set_message(text.to_string());
+        }
+    }
+}
+
+/// Save the log of a rebuild at `at` (seconds since the epoch) in `dir`, removing all but
+/// the newest [`MAX_LOGS`] logs; where it went.
+pub fn save_log(dir: &Path, log: &[u8], at: u64) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("rebuild-{}.log", at));
+    fs::write(&path, log)?;
+    let mut logs: Vec<(u64, PathBuf)> = fs::read_dir(dir)?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let secs = name
+                .to_str()?
+                .strip_prefix("rebuild-")?
+                .strip_suffix(".log")?
+                .parse()
+                .ok()?;
+            Some((secs, entry.path()))
+        })
+        .collect();
+    logs.sort();
+    let old = logs.len().saturating_sub(MAX_LOGS);
+    for (_, old) in logs.drain(..old) {
+        fs::remove_file(old)?;
+    }
+    Ok(path)
+}
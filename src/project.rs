@@ -0,0 +1,93 @@
+//! Per-project settings: a `.declair.toml` in the working directory or one of its
+//! parents overrides the global config, so that declair run inside a project edits the
+//! project's `shell.nix` (or flake) rather than the system configuration.
+
+use crate::config::Config;
+use crate::error::{DeclairError, Result};
+use crate::rebuild::RebuildMode;
+use crate::t;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the per-project config file.
+pub const PROJECT_FILE: &str = ".declair.toml";
+
+/// The settings a `.declair.toml` may override; unset ones keep the global config's.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectConfig {
+    /// File (or directory) to edit, relative to the directory of `.declair.toml`.
+    pub nix_path: Option<String>,
+    pub auto_rebuild: Option<bool>,
+    pub home_manager: Option<bool>,
+    pub flake: Option<bool>,
+    pub rebuild_mode: Option<RebuildMode>,
+    pub profile: Option<String>,
+    /// Order and filtering of search results.
+    pub prefer_sets: Option<Vec<String>>,
+    pub exclude_sets: Option<Vec<String>>,
+}
+
+impl ProjectConfig {
+    /// Override the settings of `config` this file sets; `dir` is the file's directory.
+    pub fn apply(&self, config: &mut Config, dir: &Path) {
+        if let Some(path) = &self.nix_path {
+            config.nix_path = if path.starts_with('~') || Path::new(path).is_absolute() {
+                path.clone()
+            } else {
+                dir.join(path).to_string_lossy().into_owned()
+            };
+        }
+        let flags = [
+            (self.auto_rebuild, &mut config.auto_rebuild),
+            (self.home_manager, &mut config.home_manager),
+            (self.flake, &mut config.flake),
+        ];
+        for (value, setting) in flags {
+            if let Some(value) = value {
+                *setting = value;
+            }
+        }
+        if self.rebuild_mode.is_some() {
+            config.rebuild_mode = self.rebuild_mode;
+        }
+        if self.profile.is_some() {
+            config.profile.clone_from(&self.profile);
+        }
+        if let Some(sets) = &self.prefer_sets {
+            config.prefer_sets.clone_from(sets);
+        }
+        if let Some(sets) = &self.exclude_sets {
+            config.exclude_sets.clone_from(sets);
+        }
+    }
+}
+
+/// The nearest `.declair.toml` in `start` or its parents.
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Read the project config at `path`.
+pub fn read_project_config(path: &Path) -> Result<ProjectConfig> {
+    let contents = fs::read_to_string(path).map_err(|e| DeclairError::Read {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    toml::from_str(&contents)
+        .map_err(|e| DeclairError::Config(t!("project.invalid", path.display(), e)))
+}
+
+/// The nearest project config from `start` and where it is, if there is one.
+pub fn load_project_config(start: &Path) -> Result<Option<(PathBuf, ProjectConfig)>> {
+    match find_project_config(start) {
+        Some(path) => {
+            let project = read_project_config(&path)?;
+            Ok(Some((path, project)))
+        }
+        None => Ok(None),
+    }
+}
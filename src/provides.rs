@@ -0,0 +1,145 @@
+use crate::error::{DeclairError, Result};
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use crate::t;
+use std::collections::BTreeSet;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Both lookups read a local database.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where NixOS channels ship the `programs.sqlite` database used by `command-not-found`.
+pub const PROGRAMS_DB_PATHS: &[&str] = &[
+    "/nix/var/nix/profiles/per-user/root/channels/nixos/programs.sqlite",
+    "/nix/var/nix/profiles/per-user/root/channels/nixpkgs/programs.sqlite",
+];
+
+/// Attribute names of the packages providing `command` in their `bin/`, best match first
+/// (see [`rank_providers`]).
+///
+/// Uses `nix-locate` (from nix-index) when it is installed and has a database, and falls
+/// back to the `programs.sqlite` database of the system channel (queried with `sqlite3`).
+pub fn providers(runner: &dyn CommandRunner, command: &str) -> Result<Vec<String>> {
+    if !is_command_name(command) {
+        return Err(DeclairError::SearchFailed(t!(
+            "provides.invalid_command",
+            command
+        )));
+    }
+    if let Some(found) = nix_locate(runner, command)? {
+        return Ok(rank_providers(command, found));
+    }
+    for db in PROGRAMS_DB_PATHS.iter().map(Path::new) {
+        if db.exists() {
+            return Ok(rank_providers(
+                command,
+                programs_db_providers(runner, db, command)?,
+            ));
+        }
+    }
+    Err(DeclairError::SearchFailed(t!("provides.no_database")))
+}
+
+/// `nix-locate` results, or `None` when it isn't installed or has no index to search.
+fn nix_locate(runner: &dyn CommandRunner, command: &str) -> Result<Option<Vec<String>>> {
+    let bin = format!("/bin/{}", command);
+    let cmd = CommandSpec::new(
+        "nix-locate",
+        [
+            "--minimal",
+            "--top-level",
+            "--whole-name",
+            "--at-root",
+            "--type",
+            "x",
+            "--type",
+            "s",
+            &bin,
+        ],
+    )
+    .timeout(LOOKUP_TIMEOUT)
+    .class(CommandClass::Search)
+    .query();
+    let output = match runner.output(&cmd) {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let packages = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|l| strip_output(l).to_string())
+        .collect::<BTreeSet<_>>();
+    Ok(Some(packages.into_iter().collect()))
+}
+
+/// Packages providing `command` according to a `programs.sqlite` database.
+pub fn programs_db_providers(
+    runner: &dyn CommandRunner,
+    db: &Path,
+    command: &str,
+) -> Result<Vec<String>> {
+    // `command` only holds characters valid in a command name, so quoting it is safe.
+    let query = format!(
+        "SELECT DISTINCT package FROM Programs WHERE name = '{}' ORDER BY package",
+        command
+    );
+    let cmd = CommandSpec::new("sqlite3", ["-readonly", &db.to_string_lossy(), &query])
+        .timeout(LOOKUP_TIMEOUT)
+        .class(CommandClass::Search)
+        .query();
+    let output = runner
+        .output(&cmd)
+        .map_err(|e| DeclairError::SearchFailed(t!("command.spawn", "sqlite3", e)))?;
+    if !output.status.success() {
+        return Err(DeclairError::SearchFailed(t!(
+            "provides.query_failed",
+            db.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Order providers by how likely they are what the user wants: a package named like the
+/// command first, then packages whose name contains it, then the rest, shorter names first.
+pub fn rank_providers(command: &str, mut packages: Vec<String>) -> Vec<String> {
+    packages.sort_by_key(|p| {
+        let name = p.rsplit('.').next().unwrap_or(p);
+        let tier = if name == command {
+            0
+        } else if name.contains(command) {
+            1
+        } else {
+            2
+        };
+        (tier, p.len(), p.clone())
+    });
+    packages.dedup();
+    packages
+}
+
+/// `imagemagick.out` -> `imagemagick`: nix-locate names the output holding the file.
+fn strip_output(attr: &str) -> &str {
+    ["out", "bin"]
+        .iter()
+        .find_map(|o| attr.strip_suffix(&format!(".{}", o)))
+        .unwrap_or(attr)
+}
+
+pub(crate) fn is_command_name(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with('-')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._+-".contains(c))
+}
@@ -0,0 +1,138 @@
+//! Rebuilds shared by declair runs that finish close together. Each edit marks its
+//! repository dirty; the run holding the repository's rebuild lock keeps rebuilding until
+//! no other run has marked it dirty since, and the others leave their edits to it. Five
+//! quick `add`s in parallel cause one or two switches instead of five.
+
+use crate::error::{DeclairError, Result};
+use crate::lock::FileLock;
+use crate::paths::get_state_dir;
+use crate::state::unix_now;
+use std::fs::{self, OpenOptions};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// What became of a queued rebuild.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Queued {
+    /// This run rebuilt, this many times, including edits other runs queued meanwhile.
+    Rebuilt(usize),
+    /// Another run is rebuilding and will pick the edit up.
+    Deferred,
+}
+
+/// The rebuild queue of one configuration repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebuildQueue {
+    dir: PathBuf,
+}
+
+impl RebuildQueue {
+    /// The queue kept in `dir`.
+    pub fn new(dir: &Path) -> Self {
+        RebuildQueue {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// The queue of the repository `repo`, in declair's state dir.
+    pub fn for_repo(repo: &Path) -> Option<Self> {
+        let mut hasher = DefaultHasher::new();
+        repo.hash(&mut hasher);
+        let dir = get_state_dir()?
+            .join("rebuild")
+            .join(format!("{:016x}", hasher.finish()));
+        Some(Self::new(&dir))
+    }
+
+    fn dirty_file(&self) -> PathBuf {
+        self.dir.join("dirty")
+    }
+
+    /// Note that the configuration changed since the last rebuild.
+    pub fn mark_dirty(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dirty_file();
+        fs::write(&path, unix_now().to_string())
+            .map_err(|e| DeclairError::Write { path, source: e })
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_file().exists()
+    }
+
+    /// Clear the dirty mark. Returns whether it was set.
+    pub fn take_dirty(&self) -> bool {
+        fs::remove_file(self.dirty_file()).is_ok()
+    }
+
+    /// How long ago the configuration was last marked dirty.
+    fn dirty_age(&self) -> Option<Duration> {
+        let modified = fs::metadata(self.dirty_file()).ok()?.modified().ok()?;
+        Some(
+            SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// The rebuild lock, or `None` while another run holds it.
+    pub fn try_lock(&self) -> Result<Option<FileLock>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join("lock");
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| DeclairError::Write {
+                path: path.clone(),
+                source: e,
+            })?;
+        match FileLock::acquire(&path) {
+            Ok(lock) => Ok(Some(lock)),
+            Err(DeclairError::FileLocked { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Mark the configuration dirty and `rebuild` unless another run is rebuilding.
+    /// The lock holder waits until the configuration has not been marked dirty for
+    /// `debounce`, and rebuilds again as long as other runs mark it dirty meanwhile.
+    pub fn run(
+        &self,
+        debounce: Duration,
+        mut rebuild: impl FnMut() -> Result<()>,
+    ) -> Result<Queued> {
+        self.mark_dirty()?;
+        let mut rebuilds = 0;
+        // A run marking the queue dirty just before the lock is released would find it
+        // locked and leave the edit to us, so look again once the lock is released.
+        while self.is_dirty() {
+            let Some(lock) = self.try_lock()? else {
+                break;
+            };
+            loop {
+                while let Some(age) = self.dirty_age()
+                    && age < debounce
+                {
+                    thread::sleep(debounce - age);
+                }
+                if !self.take_dirty() {
+                    break;
+                }
+                if let Err(e) = rebuild() {
+                    // Left dirty, so that the next rebuild retries.
+                    let _ = self.mark_dirty();
+                    return Err(e);
+                }
+                rebuilds += 1;
+            }
+            drop(lock);
+        }
+        Ok(match rebuilds {
+            0 => Queued::Deferred,
+            n => Queued::Rebuilt(n),
+        })
+    }
+}
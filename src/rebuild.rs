@@ -0,0 +1,522 @@
+use crate::atomic::{can_replace, write_atomic};
+use crate::channel::channel_update_command;
+use crate::closures::{ClosureChange, diff_closures};
+use crate::config::Config;
+use crate::edit::backup_path;
+use crate::error::{DeclairError, Result};
+use crate::events::{Event, NIX_LOG_ARGS, emit};
+use crate::explain::{Topic, explain};
+use crate::flake::{FlakeOutput, find_flake_dir, owning_output};
+use crate::hooks::{HookContext, Phase, RebuildEvent, fire, run_phase};
+use crate::host::{Privilege, require_unix};
+use crate::module::{ModuleKind, configured_kind, module_kind};
+use crate::notices::{option_warnings, read_news};
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use crate::status::current_generation;
+use crate::t;
+use crate::theme::theme;
+use crate::timings::record;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Most lines of a failed rebuild's output repeated in its summary.
+const MAX_SUMMARY_LINES: usize = 10;
+
+/// Most package changes listed after a rebuild; the rest are only counted.
+const MAX_CHANGES_SHOWN: usize = 30;
+
+/// What `nixos-rebuild` does with the new generation. Home Manager only switches.
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RebuildMode {
+    /// Activate it and make it the boot default
+    #[default]
+    Switch,
+    /// Activate it without making it the boot default
+    Test,
+    /// Make it the boot default without activating it
+    Boot,
+}
+
+impl fmt::Display for RebuildMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RebuildMode::Switch => "switch",
+            RebuildMode::Test => "test",
+            RebuildMode::Boot => "boot",
+        })
+    }
+}
+
+/// What to rebuild after editing a file: the tool matching its module kind and, in a
+/// flake, the output that imports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebuildTarget {
+    pub kind: ModuleKind,
+    pub flake_output: Option<String>,
+    /// Directory of the `flake.nix`, relative to the directory the rebuild runs in, when
+    /// the flake isn't at the repository root (`nix` for `--flake ./nix`).
+    pub flake_dir: Option<PathBuf>,
+    pub mode: RebuildMode,
+    /// System profile to build into instead of the default one (`--profile-name`).
+    pub profile: Option<String>,
+}
+
+impl RebuildTarget {
+    pub fn new(kind: ModuleKind) -> Self {
+        RebuildTarget {
+            kind,
+            flake_output: None,
+            flake_dir: None,
+            mode: RebuildMode::default(),
+            profile: None,
+        }
+    }
+}
+
+impl fmt::Display for RebuildTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.flake_output {
+            Some(output) => write!(f, "{} (.#{})", self.kind, output),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl From<FlakeOutput> for RebuildTarget {
+    fn from(output: FlakeOutput) -> Self {
+        RebuildTarget {
+            flake_output: Some(output.name),
+            ..RebuildTarget::new(output.kind)
+        }
+    }
+}
+
+/// Work out what to rebuild after `file` changes. In a flake (found under `repo`, see
+/// [`find_flake_dir`]), the output importing the file decides its kind unless the config's
+/// `[modules]` table says otherwise.
+pub fn rebuild_target(config: &Config, file: &Path, repo: &Path) -> Result<RebuildTarget> {
+    let flake_dir = if config.flake {
+        find_flake_dir(repo, file)
+    } else {
+        None
+    };
+    let owner = match &flake_dir {
+        Some(dir) => owning_output(dir, file)?,
+        None => None,
+    };
+    let kind = match (&owner, configured_kind(config, file)?) {
+        (_, Some(kind)) => kind,
+        (Some(output), None) => output.kind,
+        (None, None) => module_kind(config, file)?,
+    };
+    let mut target = match owner {
+        Some(output) if output.kind == kind => RebuildTarget::from(output),
+        _ => RebuildTarget::new(kind),
+    };
+    target.flake_dir = flake_dir
+        .and_then(|dir| dir.strip_prefix(repo).ok().map(Path::to_path_buf))
+        .filter(|rel| !rel.as_os_str().is_empty());
+    target.mode = config.rebuild_mode.unwrap_or_default();
+    target.profile.clone_from(&config.profile);
+    Ok(target)
+}
+
+/// `--flake <ref>` naming `target` in a flake, relative to the repository root; nothing
+/// without a flake.
+pub(crate) fn flake_args(config: &Config, target: &RebuildTarget) -> Vec<String> {
+    if !config.flake {
+        return Vec::new();
+    }
+    let flake = match &target.flake_dir {
+        Some(rel) => format!("./{}", rel.display()),
+        None => ".".to_string(),
+    };
+    let reference = match &target.flake_output {
+        Some(name) => format!("{}#{}", flake, name),
+        None => flake,
+    };
+    vec!["--flake".to_string(), reference]
+}
+
+/// Build the rebuild command for `target`, run from `dir` by an ordinary user, with the
+/// config's `rebuild_args`. `nixos-rebuild` runs through the config's `sudo` command.
+pub fn rebuild_command(config: &Config, target: &RebuildTarget, dir: &Path) -> CommandSpec {
+    rebuild_command_as(config, target, dir, &Privilege::User)
+}
+
+/// [`rebuild_command`] for declair running with `privilege`. As root, `nixos-rebuild` runs
+/// directly, and `home-manager` runs as the user who ran sudo, so that it builds their
+/// home rather than root's.
+pub fn rebuild_command_as(
+    config: &Config,
+    target: &RebuildTarget,
+    dir: &Path,
+    privilege: &Privilege,
+) -> CommandSpec {
+    let sudo = config.sudo.as_deref().unwrap_or("sudo");
+    let mut args = vec![target.mode.to_string()];
+    args.extend(flake_args(config, target));
+    args.extend(config.rebuild_args.iter().cloned());
+    let cmd = match (target.kind, privilege) {
+        (
+            ModuleKind::HomeManager,
+            Privilege::Root {
+                invoker: Some(user),
+            },
+        ) => CommandSpec::new(
+            sudo,
+            ["-u", user, "home-manager"]
+                .map(String::from)
+                .into_iter()
+                .chain(args),
+        ),
+        (ModuleKind::HomeManager, _) => CommandSpec::new("home-manager", args),
+        (ModuleKind::Nixos, _) => {
+            if let Some(profile) = &target.profile {
+                args.extend(["--profile-name".to_string(), profile.clone()]);
+            }
+            match privilege {
+                Privilege::Root { .. } => CommandSpec::new("nixos-rebuild", args),
+                Privilege::User => {
+                    CommandSpec::new(sudo, ["nixos-rebuild".to_string()].into_iter().chain(args))
+                }
+            }
+        }
+    };
+    cmd.current_dir(dir).class(CommandClass::Rebuild)
+}
+
+/// Fail for a mode or profile Home Manager has no equivalent of.
+pub fn check_target(target: &RebuildTarget) -> Result<()> {
+    if target.kind == ModuleKind::HomeManager
+        && (target.mode != RebuildMode::Switch || target.profile.is_some())
+    {
+        return Err(DeclairError::Config(t!(
+            "rebuild.home_manager_mode",
+            target.mode
+        )));
+    }
+    Ok(())
+}
+
+/// The `error:` lines of a failed rebuild's `output`, without repeats, at most
+/// [`MAX_SUMMARY_LINES`] of the last ones.
+pub fn failure_summary(output: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in output.lines().map(str::trim) {
+        if line.starts_with("error:") && !lines.iter().any(|l| l == line) {
+            lines.push(line.to_string());
+        }
+    }
+    let skip = lines.len().saturating_sub(MAX_SUMMARY_LINES);
+    lines.split_off(skip)
+}
+
+/// Run `nixos-rebuild` or `home-manager switch` in `dir`, returning what it printed.
+pub fn rebuild(
+    runner: &dyn CommandRunner,
+    config: &Config,
+    target: &RebuildTarget,
+    dir: &Path,
+) -> Result<String> {
+    require_unix("rebuild")?;
+    check_target(target)?;
+    let mut cmd = rebuild_command_as(config, target, dir, &Privilege::current());
+    // Nix's structured log drives the progress display; only nixos-rebuild passes
+    // `--log-format` on to Nix.
+    if target.kind == ModuleKind::Nixos {
+        cmd = cmd.extra_args(&NIX_LOG_ARGS.map(String::from));
+    }
+    emit(&Event::RebuildStarted {
+        command: cmd.shell_line(),
+    });
+    let output = runner.status_captured(&cmd);
+    emit(&Event::RebuildFinished {
+        success: output.as_ref().is_ok_and(|out| out.status.success()),
+        exit_code: output.as_ref().ok().and_then(|out| out.status.code()),
+    });
+    let output = output?;
+    let mut printed = String::from_utf8_lossy(&output.stdout).into_owned();
+    printed.push_str(&String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        // The errors scroll by among the build logs; repeat them at the end.
+        let summary = failure_summary(&printed);
+        if !summary.is_empty() {
+            eprintln!("\n{}", theme().error(t!("rebuild.summary")).for_stderr());
+            for line in summary {
+                eprintln!("  {}", line);
+            }
+        }
+        return Err(DeclairError::RebuildFailed {
+            status: output.status,
+            evaluation: is_evaluation_error(&printed),
+        });
+    }
+    Ok(printed)
+}
+
+/// Repeat the option migration warnings and Home Manager news a rebuild printed, so they
+/// aren't lost in its log.
+fn report_notices(
+    runner: &dyn CommandRunner,
+    config: &Config,
+    target: &RebuildTarget,
+    dir: &Path,
+    output: &str,
+) {
+    let warnings = option_warnings(output);
+    if !warnings.is_empty() {
+        eprintln!(
+            "\n{}",
+            theme()
+                .warning(t!("rebuild.option_warnings", warnings.len()))
+                .for_stderr()
+        );
+        for warning in warnings {
+            eprintln!("  - {}", warning);
+        }
+    }
+    if let Some((count, news)) = read_news(runner, config, target, dir, output) {
+        println!("\n{}\n{}", theme().header(t!("rebuild.news", count)), news);
+    }
+}
+
+/// Print the packages a rebuild added, updated and removed, going from the generation
+/// `before` to the one active now.
+fn report_changes(runner: &dyn CommandRunner, kind: ModuleKind, before: Option<&Path>) {
+    let (Some(before), Some(after)) = (before, current_generation(kind)) else {
+        return;
+    };
+    if before == after {
+        return;
+    }
+    let Some(changes) = diff_closures(runner, before, &after) else {
+        return;
+    };
+    if changes.is_empty() {
+        return;
+    }
+    let count = |f: fn(&ClosureChange) -> bool| changes.iter().filter(|c| f(c)).count();
+    println!(
+        "\n{}",
+        theme().header(t!(
+            "rebuild.changes",
+            count(|c| matches!(c, ClosureChange::Added { .. })),
+            count(|c| matches!(c, ClosureChange::Updated { .. })),
+            count(|c| matches!(c, ClosureChange::Removed { .. }))
+        ))
+    );
+    for change in changes.iter().take(MAX_CHANGES_SHOWN) {
+        let line = match change {
+            ClosureChange::Added { name, version } => {
+                theme().added(format!("+ {} {}", name, version)).to_string()
+            }
+            ClosureChange::Updated { name, from, to } => {
+                format!("~ {} {} → {}", name, from, to)
+            }
+            ClosureChange::Removed { name, version } => theme()
+                .removed(format!("- {} {}", name, version))
+                .to_string(),
+        };
+        println!("  {}", line);
+    }
+    if changes.len() > MAX_CHANGES_SHOWN {
+        println!(
+            "  {}",
+            t!("rebuild.more_changes", changes.len() - MAX_CHANGES_SHOWN)
+        );
+    }
+}
+
+/// [`rebuild`] between the config's `pre_rebuild` and `post_rebuild` hooks, then fire
+/// `on_success`/`on_failure` and the notification. A failing `pre_rebuild` hook cancels the
+/// rebuild; later hooks only print a warning when they fail.
+pub fn rebuild_with_hooks(
+    runner: &dyn CommandRunner,
+    config: &Config,
+    target: &RebuildTarget,
+    dir: &Path,
+    ctx: &HookContext,
+) -> Result<()> {
+    run_phase(runner, &config.hooks, Phase::PreRebuild, ctx, None)?;
+    explain(Topic::Rebuild(target.kind));
+    if target.flake_output.is_some() {
+        explain(Topic::Flake);
+    }
+    let before = current_generation(target.kind);
+    let start = Instant::now();
+    let result = rebuild(runner, config, target, dir);
+    let duration = start.elapsed();
+    record("rebuild", duration);
+    if let Ok(output) = &result {
+        report_notices(runner, config, target, dir, output);
+        report_changes(runner, target.kind, before.as_deref());
+        // `test` activates the build without making it a generation.
+        if target.mode != RebuildMode::Test {
+            explain(Topic::Generations(target.kind));
+        }
+    }
+    let event = RebuildEvent {
+        packages: ctx.packages.clone(),
+        target: target.to_string(),
+        duration,
+        succeeded: result.is_ok(),
+    };
+    let post = run_phase(
+        runner,
+        &config.hooks,
+        Phase::PostRebuild,
+        ctx,
+        Some(event.status()),
+    );
+    for hook_result in [post, fire(runner, &config.hooks, &event)] {
+        if let Err(e) = hook_result {
+            eprintln!("{}", theme().warning(t!("warning", e)).for_stderr());
+        }
+    }
+    result.map(|_| ())
+}
+
+/// Evaluate the configuration and work out what would be built, without building or
+/// activating anything: `nixos-rebuild dry-build`, `home-manager build --dry-run`. The
+/// config's `rebuild_args` apply as to the rebuild.
+pub fn check_command(config: &Config, target: &RebuildTarget, dir: &Path) -> CommandSpec {
+    let extra = flake_args(config, target)
+        .into_iter()
+        .chain(config.rebuild_args.iter().cloned());
+    let cmd = match target.kind {
+        ModuleKind::HomeManager => CommandSpec::new(
+            "home-manager",
+            ["build", "--dry-run", "--no-out-link"]
+                .map(String::from)
+                .into_iter()
+                .chain(extra),
+        ),
+        ModuleKind::Nixos => CommandSpec::new(
+            "nixos-rebuild",
+            std::iter::once("dry-build".to_string()).chain(extra),
+        ),
+    };
+    cmd.current_dir(dir).class(CommandClass::Eval)
+}
+
+/// Nix's hint below an evaluation error printed without its trace.
+const TRACE_HINT: &str = "use '--show-trace' to show detailed location information";
+
+/// Whether a failed rebuild's output is an evaluation error whose trace wasn't shown.
+pub fn is_evaluation_error(output: &str) -> bool {
+    output.contains(TRACE_HINT)
+}
+
+/// [`check_command`] with `--show-trace`: evaluates the configuration again to print the
+/// full trace of an evaluation error.
+pub fn trace_command(config: &Config, target: &RebuildTarget, dir: &Path) -> CommandSpec {
+    let mut cmd = check_command(config, target, dir);
+    if !cmd.args.iter().any(|arg| arg == "--show-trace") {
+        cmd.args.push("--show-trace".to_string());
+    }
+    cmd
+}
+
+/// Whether a failed rebuild was caused by declair's edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureCause {
+    /// The configuration evaluates without the edit.
+    Edit,
+    /// It fails without the edit too.
+    PreExisting,
+    /// The check couldn't run: a file has no backup or isn't writable, or the check command
+    /// couldn't be started.
+    Unknown,
+}
+
+/// Find out whether the failed rebuild of `target` is due to the edits of `files`: put
+/// their backups (see [`backup_path`]) in place, check the configuration with
+/// [`check_command`], and put the edited contents back.
+pub fn diagnose_failure(
+    runner: &dyn CommandRunner,
+    config: &Config,
+    target: &RebuildTarget,
+    dir: &Path,
+    files: &[PathBuf],
+) -> Result<FailureCause> {
+    let mut swaps = Vec::new();
+    for file in files {
+        let (Ok(edited), Ok(original)) = (fs::read(file), fs::read(backup_path(file))) else {
+            return Ok(FailureCause::Unknown);
+        };
+        if !can_replace(file) {
+            return Ok(FailureCause::Unknown);
+        }
+        swaps.push((file, edited, original));
+    }
+    let mut swapped = Vec::new();
+    let mut result = Ok(FailureCause::Unknown);
+    for (file, edited, original) in &swaps {
+        if let Err(e) = write_atomic(file, original) {
+            result = Err(DeclairError::Write {
+                path: file.to_path_buf(),
+                source: e,
+            });
+            break;
+        }
+        swapped.push((file, edited));
+    }
+    if swapped.len() == swaps.len() {
+        result = Ok(match runner.output(&check_command(config, target, dir)) {
+            Ok(output) if output.status.success() => FailureCause::Edit,
+            Ok(_) => FailureCause::PreExisting,
+            Err(_) => FailureCause::Unknown,
+        });
+    }
+    for (file, edited) in swapped {
+        write_atomic(file, edited).map_err(|e| DeclairError::Write {
+            path: file.to_path_buf(),
+            source: e,
+        })?;
+    }
+    result
+}
+
+/// Build the command refreshing the config's inputs: `nix flake update` in the flake's
+/// directory, `nix-channel --update` (as root for NixOS) otherwise.
+pub fn update_command(config: &Config, target: &RebuildTarget, dir: &Path) -> CommandSpec {
+    let (cmd, dir) = if config.flake {
+        let cmd = CommandSpec::new(
+            "nix",
+            [
+                "flake",
+                "update",
+                "--extra-experimental-features",
+                "nix-command flakes",
+            ],
+        );
+        match &target.flake_dir {
+            Some(rel) => (cmd, dir.join(rel)),
+            None => (cmd, dir.to_path_buf()),
+        }
+    } else {
+        (channel_update_command(target.kind, &[]), dir.to_path_buf())
+    };
+    cmd.current_dir(dir).class(CommandClass::Rebuild)
+}
+
+/// Update the flake inputs or channels the config in `dir` is built from.
+pub fn update_inputs(
+    runner: &dyn CommandRunner,
+    config: &Config,
+    target: &RebuildTarget,
+    dir: &Path,
+) -> Result<()> {
+    require_unix("update")?;
+    let status = runner.status(&update_command(config, target, dir))?;
+    if !status.success() {
+        return Err(DeclairError::UpdateFailed { status });
+    }
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! Places in a configuration repository that refer to a package: package list entries,
+//! `pkgs.<name>` attributes in overrides and wrappers, module options such as
+//! `programs.<name>.enable`, and other mentions.
+
+use crate::conflicts::nix_files;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Roots of attribute paths that name packages rather than module options.
+const PACKAGE_SETS: &[&str] = &["pkgs", "prev", "final", "super", "self"];
+
+/// How a line refers to the package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    /// An entry of a `with pkgs; [ ... ]` list.
+    List,
+    /// An attribute of a package set: `pkgs.<name>`, `prev.<name>`, ...
+    Attribute,
+    /// Part of a module option path: `programs.<name>.enable`.
+    Option,
+    /// Any other occurrence of the name, e.g. in a string or a `let` binding.
+    Mention,
+}
+
+impl fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ReferenceKind::List => "list",
+            ReferenceKind::Attribute => "attribute",
+            ReferenceKind::Option => "option",
+            ReferenceKind::Mention => "mention",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One line referring to the package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub file: PathBuf,
+    /// 1-based.
+    pub line: usize,
+    pub kind: ReferenceKind,
+    /// The line, trimmed.
+    pub text: String,
+}
+
+/// Whether `word` matches `pattern`: equal, or with `*` in `pattern` standing for any
+/// run of characters (`nodejs_*`, `*-unwrapped`).
+pub fn matches_pattern(word: &str, pattern: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return word == pattern;
+    };
+    let Some(mut remaining) = word.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or("");
+    for part in parts {
+        match remaining.find(part) {
+            Some(i) => remaining = &remaining[i + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_-'".contains(c)
+}
+
+/// Identifiers of `code` with their byte offsets.
+fn identifiers(code: &str) -> Vec<(usize, &str)> {
+    let mut idents = Vec::new();
+    let mut start = None;
+    for (i, c) in code
+        .char_indices()
+        .chain(std::iter::once((code.len(), ' ')))
+    {
+        match (start, is_ident_char(c)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                idents.push((s, &code[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    idents
+}
+
+/// The kind of the identifier at `idx`, from the dotted path it is part of.
+fn classify(idents: &[(usize, &str)], code: &str, idx: usize, in_list: bool) -> ReferenceKind {
+    let joined = |a: usize, b: usize| {
+        let end = idents[a].0 + idents[a].1.len();
+        code[end..idents[b].0].trim() == "."
+    };
+    let mut root = idx;
+    while root > 0 && joined(root - 1, root) {
+        root -= 1;
+    }
+    let continues = idx + 1 < idents.len() && joined(idx, idx + 1);
+    if root == idx && !continues {
+        return if in_list {
+            ReferenceKind::List
+        } else {
+            ReferenceKind::Mention
+        };
+    }
+    if PACKAGE_SETS.contains(&idents[root].1) || (root == idx && in_list) {
+        ReferenceKind::Attribute
+    } else {
+        ReferenceKind::Option
+    }
+}
+
+/// Lines of `contents` referring to identifiers matching `pattern`, ignoring comments.
+pub fn references_in(file: &Path, contents: &str, pattern: &str) -> Vec<Reference> {
+    let mut references = Vec::new();
+    let mut in_list = false;
+    for (i, line) in contents.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or("");
+        let list_start = code.find("with pkgs; [").map(|p| p + "with pkgs; [".len());
+        let idents = identifiers(code);
+        let found = idents
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, word))| matches_pattern(word, pattern))
+            .map(|(idx, (pos, _))| {
+                let listed = in_list || list_start.is_some_and(|start| *pos >= start);
+                classify(&idents, code, idx, listed)
+            })
+            .min_by_key(|kind| *kind as u8);
+        if let Some(kind) = found {
+            references.push(Reference {
+                file: file.to_path_buf(),
+                line: i + 1,
+                kind,
+                text: line.trim().to_string(),
+            });
+        }
+        if list_start.is_some() {
+            in_list = true;
+        }
+        if in_list && code.contains(']') {
+            in_list = false;
+        }
+    }
+    references
+}
+
+/// Every reference to `pattern` in the `.nix` files under `repo`, by file and line.
+pub fn find_references(repo: &Path, pattern: &str) -> Vec<Reference> {
+    nix_files(repo)
+        .into_iter()
+        .filter_map(|file| {
+            let contents = fs::read_to_string(&file).ok()?;
+            Some(references_in(&file, &contents, pattern))
+        })
+        .flatten()
+        .collect()
+}
+
+/// References to `pkg` other than its entries in `file`'s package lists: overrides,
+/// wrappers, module options and other modules. Removing `pkg` from `file` may break
+/// these expressions.
+pub fn references_elsewhere(repo: &Path, file: &Path, pkg: &str) -> Vec<Reference> {
+    let file = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    find_references(repo, pkg)
+        .into_iter()
+        .filter(|r| {
+            r.kind != ReferenceKind::List
+                || fs::canonicalize(&r.file).map_or(r.file != file, |f| f != file)
+        })
+        .collect()
+}
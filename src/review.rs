@@ -0,0 +1,149 @@
+use crate::atomic::{can_replace, write_atomic};
+use crate::edit::backup_path;
+use crate::error::{DeclairError, Result};
+use crate::privileged::ScratchCopy;
+use crate::runner::{CommandRunner, CommandSpec};
+use crate::t;
+use crate::theme::theme;
+use crate::timings::record;
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// One file's pending change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub before: String,
+    pub after: String,
+}
+
+impl FileChange {
+    /// Unified diff of the change with three lines of context.
+    pub fn diff(&self) -> String {
+        let name = self.path.display().to_string();
+        TextDiff::from_lines(&self.before, &self.after)
+            .unified_diff()
+            .context_radius(3)
+            .header(&name, &name)
+            .to_string()
+    }
+}
+
+/// Edits to several files made on scratch copies, so they can be reviewed together and
+/// only the accepted ones written.
+#[derive(Default)]
+pub struct ChangeSet {
+    staged: Vec<(String, ScratchCopy)>,
+}
+
+impl ChangeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Path to edit in place of `file`. Staging the same file again returns the same copy,
+    /// so several edits to one file accumulate.
+    pub fn stage(&mut self, file: &Path) -> Result<&Path> {
+        let canonical = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        let idx = match self
+            .staged
+            .iter()
+            .position(|(_, s)| s.original() == canonical)
+        {
+            Some(idx) => idx,
+            None => {
+                let before = fs::read_to_string(&canonical).map_err(|e| DeclairError::Read {
+                    path: canonical.clone(),
+                    source: e,
+                })?;
+                self.staged.push((before, ScratchCopy::new(&canonical)?));
+                self.staged.len() - 1
+            }
+        };
+        Ok(self.staged[idx].1.path())
+    }
+
+    /// Staged files whose copy now differs from the original.
+    pub fn changes(&self) -> Result<Vec<FileChange>> {
+        let mut changes = Vec::new();
+        for (before, scratch) in &self.staged {
+            let after = fs::read_to_string(scratch.path()).map_err(|e| DeclairError::Read {
+                path: scratch.path().to_path_buf(),
+                source: e,
+            })?;
+            if &after != before {
+                changes.push(FileChange {
+                    path: scratch.original().to_path_buf(),
+                    before: before.clone(),
+                    after,
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Write the changes to the files `accept` approves and drop the rest. Every accepted
+    /// file is checked for concurrent modification before the first one is written, and
+    /// when writing one fails, the ones already written get their old contents back.
+    /// Returns the files written.
+    pub fn commit(
+        self,
+        runner: &dyn CommandRunner,
+        accept: impl Fn(&Path) -> bool,
+    ) -> Result<Vec<PathBuf>> {
+        let changed: Vec<PathBuf> = self.changes()?.into_iter().map(|c| c.path).collect();
+        let accepted: Vec<(String, ScratchCopy)> = self
+            .staged
+            .into_iter()
+            .filter(|(_, s)| changed.iter().any(|c| c == s.original()) && accept(s.original()))
+            .collect();
+        for (_, scratch) in &accepted {
+            scratch.verify()?;
+        }
+        let start = Instant::now();
+        let mut written: Vec<(PathBuf, String)> = Vec::new();
+        for (before, scratch) in accepted {
+            let path = scratch.original().to_path_buf();
+            if let Err(e) = scratch.apply(runner) {
+                for (path, before) in written.iter().rev() {
+                    if let Err(e) = restore(runner, path, before) {
+                        eprintln!(
+                            "{}",
+                            theme()
+                                .warning(t!("review.not_restored", path.display(), e))
+                                .for_stderr()
+                        );
+                    }
+                }
+                return Err(e);
+            }
+            written.push((path, before));
+        }
+        record("write", start.elapsed());
+        Ok(written.into_iter().map(|(path, _)| path).collect())
+    }
+}
+
+/// Put `before` back into `file`, which a failed [`ChangeSet::commit`] already wrote. A
+/// file the current user can't replace gets its backup copied back with sudo.
+fn restore(runner: &dyn CommandRunner, file: &Path, before: &str) -> Result<()> {
+    if can_replace(file) {
+        return write_atomic(file, before.as_bytes()).map_err(|e| DeclairError::Write {
+            path: file.to_path_buf(),
+            source: e,
+        });
+    }
+    let backup = backup_path(file).display().to_string();
+    let target = file.display().to_string();
+    let cmd = CommandSpec::new("sudo", ["cp", "--", backup.as_str(), target.as_str()]);
+    let output = runner.output(&cmd)?;
+    if !output.status.success() {
+        return Err(DeclairError::PrivilegedWriteFailed {
+            file: file.to_path_buf(),
+            status: output.status,
+        });
+    }
+    Ok(())
+}
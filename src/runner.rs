@@ -0,0 +1,642 @@
+use crate::error::{DeclairError, Result};
+use crate::events::wants_nix_log;
+use crate::progress::{RebuildDisplay, save_log};
+use crate::state::unix_now;
+use crate::t;
+use crate::theme::theme;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use signal_hook::consts::SIGINT;
+use signal_hook::flag;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(windows)]
+use std::os::windows::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Environment variables stripped from every spawned command. They can inject foreign
+/// libraries into `nix`/`sudo` or change how their output looks.
+const SANITIZED_ENV_VARS: &[&str] = &[
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "DYLD_INSERT_LIBRARIES",
+    "DYLD_LIBRARY_PATH",
+    "NIX_GET_COMPLETIONS",
+];
+
+/// Fragments of `nix` stderr output that indicate a network hiccup worth retrying.
+const TRANSIENT_ERRORS: &[&str] = &[
+    "unable to download",
+    "Could not resolve host",
+    "Couldn't resolve host",
+    "Connection reset",
+    "Connection refused",
+    "Timeout was reached",
+    "timed out",
+    "HTTP error 5",
+    "error 502",
+    "error 503",
+    "error 504",
+];
+
+/// How often a running child is polled while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long an interrupted child gets to exit after SIGINT before it is killed.
+const INTERRUPT_GRACE: Duration = Duration::from_secs(10);
+
+/// What a command is for, so its timeout can be configured in `[timeouts]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommandClass {
+    #[default]
+    Other,
+    /// Package searches: `nix search`, `nix-env -qa`, `nix-locate`.
+    Search,
+    /// Evaluations and store queries: `nix eval`, `nix-store`, `nix why-depends`.
+    Eval,
+    /// `nixos-rebuild` and `home-manager switch`.
+    Rebuild,
+}
+
+/// Timeouts in seconds overriding declair's defaults per [`CommandClass`]; `0` disables
+/// the timeout.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rebuild: Option<u64>,
+}
+
+impl Timeouts {
+    /// Timeout for a command of `class` whose own default is `default`.
+    pub fn resolve(&self, class: CommandClass, default: Option<Duration>) -> Option<Duration> {
+        let configured = match class {
+            CommandClass::Other => None,
+            CommandClass::Search => self.search,
+            CommandClass::Eval => self.eval,
+            CommandClass::Rebuild => self.rebuild,
+        };
+        match configured {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => default,
+        }
+    }
+}
+
+/// An external command declair wants to execute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub current_dir: Option<PathBuf>,
+    /// Variables added to the inherited environment.
+    pub env: Vec<(String, String)>,
+    /// Kill the command if it runs longer than this.
+    pub timeout: Option<Duration>,
+    /// How many times to retry after a transient failure (network error or timeout).
+    pub retries: u32,
+    /// Bytes fed to the command's stdin by [`CommandRunner::output`].
+    pub stdin: Option<Vec<u8>>,
+    pub class: CommandClass,
+    /// Only reads (searches, evaluations, probes of the installed Nix), so
+    /// `--print-commands` runs it for real rather than printing it.
+    pub query: bool,
+}
+
+impl CommandSpec {
+    pub fn new<I, S>(program: &str, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        CommandSpec {
+            program: program.to_string(),
+            args: args.into_iter().map(Into::into).collect(),
+            current_dir: None,
+            env: Vec::new(),
+            timeout: None,
+            retries: 0,
+            stdin: None,
+            class: CommandClass::Other,
+            query: false,
+        }
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn stdin(mut self, input: impl Into<Vec<u8>>) -> Self {
+        self.stdin = Some(input.into());
+        self
+    }
+
+    pub fn class(mut self, class: CommandClass) -> Self {
+        self.class = class;
+        self
+    }
+
+    /// Mark the command as one that only reads.
+    pub fn query(mut self) -> Self {
+        self.query = true;
+        self
+    }
+
+    /// Add `extra` to the command's options: before a `--` that ends them, otherwise at the
+    /// end.
+    pub fn extra_args(mut self, extra: &[String]) -> Self {
+        let at = self
+            .args
+            .iter()
+            .position(|arg| arg == "--")
+            .unwrap_or(self.args.len());
+        self.args.splice(at..at, extra.iter().cloned());
+        self
+    }
+
+    /// The command as POSIX shell, with every word quoted as needed: `cd DIR &&` and
+    /// `VAR=value` in front when it sets them, and its stdin as a here-document.
+    pub fn shell_line(&self) -> String {
+        let mut words = Vec::new();
+        if let Some(dir) = &self.current_dir {
+            words.push(format!("cd {} &&", shell_quote(&dir.to_string_lossy())));
+        }
+        words.extend(
+            self.env
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, shell_quote(value))),
+        );
+        words.push(shell_quote(&self.program));
+        words.extend(self.args.iter().map(|arg| shell_quote(arg)));
+        let mut line = words.join(" ");
+        if let Some(stdin) = &self.stdin {
+            let input = String::from_utf8_lossy(stdin);
+            // A delimiter no line of the input equals.
+            let mut delimiter = "DECLAIR_EOF".to_string();
+            while input.lines().any(|l| l == delimiter) {
+                delimiter.push('_');
+            }
+            line.push_str(&format!(" <<'{}'\n{}", delimiter, input));
+            if !input.ends_with('\n') {
+                line.push('\n');
+            }
+            line.push_str(&delimiter);
+        }
+        line
+    }
+}
+
+/// `word` quoted for a POSIX shell: left alone if it only has characters no shell treats
+/// specially, otherwise in single quotes.
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Executes external commands. Lets search and rebuild be tested without nix installed.
+pub trait CommandRunner {
+    /// Run the command and capture its stdout/stderr.
+    fn output(&self, cmd: &CommandSpec) -> io::Result<Output>;
+
+    /// Run the command with inherited stdio and wait for it to finish.
+    fn status(&self, cmd: &CommandSpec) -> io::Result<ExitStatus>;
+
+    /// Like [`status`](Self::status), but also capture what the command prints while it
+    /// is shown. Runners that can't do both capture nothing.
+    fn status_captured(&self, cmd: &CommandSpec) -> io::Result<Output> {
+        Ok(Output {
+            status: self.status(cmd)?,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        })
+    }
+}
+
+/// What the `nix eval` `cmd` printed. Failing to start or exit successfully is a
+/// [`DeclairError::Eval`], except for Ctrl-C.
+pub fn eval_output(runner: &dyn CommandRunner, cmd: &CommandSpec) -> Result<Vec<u8>> {
+    let output = runner.output(cmd).map_err(|e| {
+        if e.kind() == io::ErrorKind::Interrupted {
+            DeclairError::Io(e)
+        } else {
+            DeclairError::Eval(t!("eval.spawn", e))
+        }
+    })?;
+    if !output.status.success() {
+        return Err(DeclairError::Eval(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// The JSON the `nix eval --json` `cmd` printed, see [`eval_output`].
+pub fn eval_json<T: DeserializeOwned>(runner: &dyn CommandRunner, cmd: &CommandSpec) -> Result<T> {
+    serde_json::from_slice(&eval_output(runner, cmd)?)
+        .map_err(|e| DeclairError::Eval(t!("eval.unexpected_output", e)))
+}
+
+/// Whether a failed command's stderr looks like a temporary network problem.
+pub fn is_transient_failure(stderr: &[u8]) -> bool {
+    let stderr = String::from_utf8_lossy(stderr);
+    TRANSIENT_ERRORS.iter().any(|e| stderr.contains(e))
+}
+
+/// Set by SIGINT while declair waits for a child (see [`install_interrupt_handler`]).
+static INTERRUPTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+/// Whether SIGINT should terminate declair as usual, i.e. no child is being waited for.
+static DEFAULT_SIGINT: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+/// Catch Ctrl-C while a child runs, so [`SystemRunner`] can stop the child and return an
+/// [`io::ErrorKind::Interrupted`] error instead of declair dying with the child still
+/// running. Outside of child waits, SIGINT terminates declair as before.
+pub fn install_interrupt_handler() -> io::Result<()> {
+    let interrupted = INTERRUPTED.get_or_init(Default::default);
+    let default = DEFAULT_SIGINT.get_or_init(|| Arc::new(AtomicBool::new(true)));
+    flag::register_conditional_default(SIGINT, Arc::clone(default))?;
+    flag::register(SIGINT, Arc::clone(interrupted))?;
+    Ok(())
+}
+
+/// Routes SIGINT to [`INTERRUPTED`] while alive.
+struct InterruptScope;
+
+impl InterruptScope {
+    fn enter() -> Self {
+        if let Some(interrupted) = INTERRUPTED.get() {
+            interrupted.store(false, Ordering::SeqCst);
+        }
+        if let Some(default) = DEFAULT_SIGINT.get() {
+            default.store(false, Ordering::SeqCst);
+        }
+        InterruptScope
+    }
+
+    fn interrupted(&self) -> bool {
+        INTERRUPTED.get().is_some_and(|i| i.load(Ordering::SeqCst))
+    }
+}
+
+impl Drop for InterruptScope {
+    fn drop(&mut self) {
+        if let Some(default) = DEFAULT_SIGINT.get() {
+            default.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Runs commands on the host system with a sanitized environment, enforcing timeouts,
+/// stopping children on Ctrl-C and retrying transient failures with exponential backoff.
+pub struct SystemRunner {
+    /// Delay before the first retry; doubled after every attempt.
+    pub initial_backoff: Duration,
+    /// Configured timeouts, overriding the ones commands ask for.
+    pub timeouts: Timeouts,
+    /// Print the commands instead of running them (`--print-commands`). Each one succeeds
+    /// without output, except queries, which run anyway.
+    pub print_only: bool,
+    /// Arguments added to every `nix` command (`nix_args`, `--nix-arg`).
+    pub nix_args: Vec<String>,
+    /// Where the output of rebuilds is saved, if anywhere.
+    pub rebuild_logs: Option<PathBuf>,
+}
+
+impl Default for SystemRunner {
+    fn default() -> Self {
+        SystemRunner {
+            initial_backoff: Duration::from_millis(500),
+            timeouts: Timeouts::default(),
+            print_only: false,
+            nix_args: Vec::new(),
+            rebuild_logs: None,
+        }
+    }
+}
+
+impl SystemRunner {
+    pub fn with_timeouts(timeouts: Timeouts) -> Self {
+        SystemRunner {
+            timeouts,
+            ..Default::default()
+        }
+    }
+
+    /// `cmd` with the configured extra arguments.
+    fn prepare(&self, cmd: &CommandSpec) -> CommandSpec {
+        let cmd = cmd.clone();
+        if cmd.program == "nix" && !self.nix_args.is_empty() {
+            cmd.extra_args(&self.nix_args)
+        } else {
+            cmd
+        }
+    }
+
+    /// Print `cmd` in place of running it.
+    fn print(cmd: &CommandSpec) -> Output {
+        println!("{}", cmd.shell_line());
+        Output {
+            status: ExitStatus::from_raw(0),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }
+    }
+
+    /// Save what a rebuild printed to [`SystemRunner::rebuild_logs`], pointing to it when
+    /// the rebuild failed. A log that can't be saved only warns.
+    fn save_rebuild_log(&self, output: &Output) {
+        let Some(dir) = &self.rebuild_logs else {
+            return;
+        };
+        let log = [output.stdout.as_slice(), &output.stderr].concat();
+        match save_log(dir, &log, unix_now()) {
+            Ok(path) if !output.status.success() => {
+                eprintln!("{}", t!("rebuild.log_saved", path.display()));
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("{}", theme().warning(t!("warning", e)).for_stderr()),
+        }
+    }
+
+    fn timeout(&self, cmd: &CommandSpec) -> Option<Duration> {
+        self.timeouts.resolve(cmd.class, cmd.timeout)
+    }
+
+    fn command(cmd: &CommandSpec) -> Command {
+        let mut command = Command::new(&cmd.program);
+        command.args(&cmd.args);
+        if let Some(dir) = &cmd.current_dir {
+            command.current_dir(dir);
+        }
+        for var in SANITIZED_ENV_VARS {
+            command.env_remove(var);
+        }
+        command.envs(cmd.env.iter().map(|(k, v)| (k, v)));
+        command
+    }
+
+    fn output_once(&self, cmd: &CommandSpec) -> io::Result<Output> {
+        let stdin = if cmd.stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        };
+        let mut child = Self::command(cmd)
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let writer = write_in_background(child.stdin.take(), cmd.stdin.clone());
+        // Drain the pipes on separate threads so a chatty child can't block on a full pipe.
+        let stdout = read_in_background(child.stdout.take());
+        let stderr = read_in_background(child.stderr.take());
+        let status = wait(&mut child, self.timeout(cmd), &cmd.program)?;
+        let _ = writer.join();
+        Ok(Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandRunner for SystemRunner {
+    fn output(&self, cmd: &CommandSpec) -> io::Result<Output> {
+        let cmd = &self.prepare(cmd);
+        if self.print_only && !cmd.query {
+            return Ok(Self::print(cmd));
+        }
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            let result = self.output_once(cmd);
+            let transient = match &result {
+                Ok(out) => !out.status.success() && is_transient_failure(&out.stderr),
+                Err(e) => e.kind() == io::ErrorKind::TimedOut,
+            };
+            if !transient || attempt >= cmd.retries {
+                return result;
+            }
+            attempt += 1;
+            thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+
+    fn status(&self, cmd: &CommandSpec) -> io::Result<ExitStatus> {
+        let cmd = &self.prepare(cmd);
+        if self.print_only {
+            return Ok(Self::print(cmd).status);
+        }
+        let scope = InterruptScope::enter();
+        let mut child = Self::command(cmd).spawn()?;
+        wait_in_scope(&scope, &mut child, self.timeout(cmd), &cmd.program)
+    }
+
+    /// The output goes through pipes, so Nix prints plain log lines rather than its
+    /// progress bar.
+    fn status_captured(&self, cmd: &CommandSpec) -> io::Result<Output> {
+        let cmd = &self.prepare(cmd);
+        if self.print_only {
+            return Ok(Self::print(cmd));
+        }
+        let scope = InterruptScope::enter();
+        let mut child = Self::command(cmd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = tee_in_background(child.stdout.take(), io::stdout);
+        let stderr = if wants_nix_log(&cmd.args) {
+            translate_in_background(child.stderr.take())
+        } else {
+            tee_in_background(child.stderr.take(), io::stderr)
+        };
+        let status = wait_in_scope(&scope, &mut child, self.timeout(cmd), &cmd.program)?;
+        let output = Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        };
+        if cmd.class == CommandClass::Rebuild {
+            self.save_rebuild_log(&output);
+        }
+        Ok(output)
+    }
+}
+
+fn write_in_background<W: Write + Send + 'static>(
+    pipe: Option<W>,
+    input: Option<Vec<u8>>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        if let (Some(mut pipe), Some(input)) = (pipe, input) {
+            // A child that exits without reading everything closes the pipe; that's its call.
+            let _ = pipe.write_all(&input);
+        }
+    })
+}
+
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Copy `pipe` to `sink` as it is read, keeping a copy.
+fn tee_in_background<R, W>(pipe: Option<R>, sink: fn() -> W) -> thread::JoinHandle<Vec<u8>>
+where
+    R: Read + Send + 'static,
+    W: Write + 'static,
+{
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let Some(mut pipe) = pipe else {
+            return buf;
+        };
+        let mut chunk = [0; 8192];
+        while let Ok(n) = pipe.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            let mut out = sink();
+            let _ = out.write_all(&chunk[..n]);
+            let _ = out.flush();
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        buf
+    })
+}
+
+/// Like [`tee_in_background`] to stderr for Nix's `internal-json` log: it is shown by a
+/// [`RebuildDisplay`] and kept as plain text, build logs included. The pipe is read to the
+/// end whatever it holds; bytes that aren't UTF-8 become replacement characters.
+fn translate_in_background<R: Read + Send + 'static>(
+    pipe: Option<R>,
+) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let Some(pipe) = pipe else {
+            return buf;
+        };
+        let mut display = RebuildDisplay::new(io::stderr().is_terminal());
+        for line in BufReader::new(pipe).split(b'\n') {
+            // Only a failing read ends it early, and then there is nothing left to drain.
+            let Ok(line) = line else {
+                break;
+            };
+            let line = String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(&line));
+            if let Some(text) = display.line(&line) {
+                buf.extend_from_slice(text.as_bytes());
+                buf.push(b'\n');
+            }
+        }
+        display.finish();
+        buf
+    })
+}
+
+fn wait(child: &mut Child, timeout: Option<Duration>, program: &str) -> io::Result<ExitStatus> {
+    let scope = InterruptScope::enter();
+    wait_in_scope(&scope, child, timeout, program)
+}
+
+/// Wait for `child`. Once `timeout` elapses it is killed and a `TimedOut` error returned;
+/// on Ctrl-C it gets SIGINT and [`INTERRUPT_GRACE`] to exit before being killed, and an
+/// `Interrupted` error is returned.
+fn wait_in_scope(
+    scope: &InterruptScope,
+    child: &mut Child,
+    timeout: Option<Duration>,
+    program: &str,
+) -> io::Result<ExitStatus> {
+    let deadline = timeout.map(|t| Instant::now() + t);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if scope.interrupted() {
+            stop_after_interrupt(child);
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                format!("`{}` was interrupted", program),
+            ));
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "`{}` timed out after {}s",
+                    program,
+                    timeout.unwrap_or_default().as_secs_f32()
+                ),
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Forward SIGINT to `child` (it may not share declair's process group), give it
+/// [`INTERRUPT_GRACE`] to clean up, then kill it.
+fn stop_after_interrupt(child: &mut Child) {
+    forward_interrupt(child);
+    let deadline = Instant::now() + INTERRUPT_GRACE;
+    while Instant::now() < deadline {
+        if let Ok(Some(_)) = child.try_wait() {
+            return;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(unix)]
+fn forward_interrupt(child: &Child) {
+    if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: plain kill(2) on the pid of a child we haven't reaped yet.
+        unsafe {
+            libc::kill(pid, libc::SIGINT);
+        }
+    }
+}
+
+/// Without Unix signals the child only gets the console's Ctrl-C, if it shares it.
+#[cfg(not(unix))]
+fn forward_interrupt(_child: &Child) {}
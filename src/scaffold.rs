@@ -0,0 +1,211 @@
+use crate::error::{DeclairError, Result};
+use crate::t;
+use clap::ValueEnum;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the module generated by `declair init-config-file`.
+pub const PACKAGES_MODULE: &str = "declair-packages.nix";
+
+/// Contents of a fresh declair-owned packages module.
+pub fn packages_module_template(home_manager: bool) -> String {
+    let attr = if home_manager {
+        "home.packages"
+    } else {
+        "environment.systemPackages"
+    };
+    format!(
+        "# Packages managed by declair-rs.\n\
+         # Import this file from your configuration and let declair add/remove entries.\n\
+         {{ pkgs, ... }}:\n\
+         \n\
+         {{\n  {} = with pkgs; [\n  ];\n}}\n",
+        attr
+    )
+}
+
+/// Write the packages module to `path`, refusing to overwrite an existing file.
+pub fn write_packages_module(path: &Path, home_manager: bool) -> Result<()> {
+    if path.exists() {
+        return Err(DeclairError::InvalidPath {
+            path: path.to_path_buf(),
+            reason: t!("scaffold.exists"),
+        });
+    }
+    fs::write(path, packages_module_template(home_manager)).map_err(|e| DeclairError::Write {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+/// Path of `module` as written in an `imports` list of `importer`:
+/// `./name.nix` when both live in the same directory, otherwise absolute.
+pub fn import_path(importer: &Path, module: &Path) -> String {
+    let module_dir = module.parent().map(Path::to_path_buf);
+    let importer_dir = importer.parent().map(Path::to_path_buf);
+    match (module_dir, module.file_name()) {
+        (Some(m), Some(name)) if Some(&m) == importer_dir.as_ref() => {
+            format!("./{}", name.to_string_lossy())
+        }
+        _ => absolute(module).display().to_string(),
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Kind of starter derivation for a new package.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Template {
+    /// `stdenv.mkDerivation`, e.g. for C, C++ or a Makefile
+    #[default]
+    Stdenv,
+    /// `rustPlatform.buildRustPackage`
+    Rust,
+    /// `python3Packages.buildPythonApplication`
+    Python,
+    /// `buildGoModule`
+    Go,
+}
+
+const HEADER: &str = "\
+# Built with `callPackage` by the entry declair-rs added to your package list.
+# Fill in the source and metadata, then rebuild.
+";
+
+const STDENV: &str = r#"{ lib, stdenv, fetchFromGitHub }:
+
+stdenv.mkDerivation (finalAttrs: {
+  pname = "@name@";
+  version = "0.1.0";
+
+  src = fetchFromGitHub {
+    owner = "";
+    repo = "@name@";
+    rev = "v${finalAttrs.version}";
+    hash = lib.fakeHash;
+  };
+
+  meta = {
+    description = "";
+    mainProgram = "@name@";
+  };
+})
+"#;
+
+const RUST: &str = r#"{ lib, rustPlatform, fetchFromGitHub }:
+
+rustPlatform.buildRustPackage (finalAttrs: {
+  pname = "@name@";
+  version = "0.1.0";
+
+  src = fetchFromGitHub {
+    owner = "";
+    repo = "@name@";
+    rev = "v${finalAttrs.version}";
+    hash = lib.fakeHash;
+  };
+
+  cargoHash = lib.fakeHash;
+
+  meta = {
+    description = "";
+    mainProgram = "@name@";
+  };
+})
+"#;
+
+const PYTHON: &str = r#"{ lib, python3Packages, fetchFromGitHub }:
+
+python3Packages.buildPythonApplication rec {
+  pname = "@name@";
+  version = "0.1.0";
+  pyproject = true;
+
+  src = fetchFromGitHub {
+    owner = "";
+    repo = "@name@";
+    rev = "v${version}";
+    hash = lib.fakeHash;
+  };
+
+  build-system = [ python3Packages.setuptools ];
+
+  dependencies = with python3Packages; [ ];
+
+  meta = {
+    description = "";
+    mainProgram = "@name@";
+  };
+}
+"#;
+
+const GO: &str = r#"{ lib, buildGoModule, fetchFromGitHub }:
+
+buildGoModule (finalAttrs: {
+  pname = "@name@";
+  version = "0.1.0";
+
+  src = fetchFromGitHub {
+    owner = "";
+    repo = "@name@";
+    rev = "v${finalAttrs.version}";
+    hash = lib.fakeHash;
+  };
+
+  vendorHash = lib.fakeHash;
+
+  meta = {
+    description = "";
+    mainProgram = "@name@";
+  };
+})
+"#;
+
+/// A `callPackage`-style derivation of the package `name` built with `template`, to be
+/// filled in: its source, hashes and metadata.
+pub fn derivation_template(name: &str, template: Template) -> String {
+    let body = match template {
+        Template::Stdenv => STDENV,
+        Template::Rust => RUST,
+        Template::Python => PYTHON,
+        Template::Go => GO,
+    };
+    format!("{}{}", HEADER, body.replace("@name@", name))
+}
+
+/// Write [`derivation_template`] to `path`, named after the file (or its directory for a
+/// `default.nix`), creating missing directories and refusing to overwrite a file.
+pub fn write_derivation(path: &Path, template: Template) -> Result<()> {
+    if path.exists() {
+        return Err(DeclairError::InvalidPath {
+            path: path.to_path_buf(),
+            reason: t!("scaffold.exists"),
+        });
+    }
+    let name = match path.file_stem().and_then(|s| s.to_str()) {
+        Some("default") => path
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .and_then(|s| s.to_str()),
+        stem => stem,
+    }
+    .unwrap_or("package");
+    let write = || -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, derivation_template(name, template))
+    };
+    write().map_err(|e| DeclairError::Write {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+/// Where `declair new-package` puts the derivation of `name`: `pkgs/<name>/default.nix`
+/// in `repo`.
+pub fn new_package_path(repo: &Path, name: &str) -> PathBuf {
+    repo.join("pkgs").join(name).join("default.nix")
+}
@@ -0,0 +1,157 @@
+//! User systemd units running `declair update` on a schedule.
+
+use crate::error::{DeclairError, Result};
+use crate::host::{HostOs, has_systemd};
+use crate::runner::{CommandRunner, CommandSpec};
+use crate::selfupdate::store_path;
+use crate::t;
+use directories::BaseDirs;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const SERVICE_NAME: &str = "declair-update.service";
+pub const TIMER_NAME: &str = "declair-update.timer";
+
+/// Directory of the user's own systemd units, `~/.config/systemd/user`.
+pub fn user_unit_dir() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().ok_or(DeclairError::NoHomeDir)?;
+    Ok(dirs.config_dir().join("systemd").join("user"))
+}
+
+/// The declair for the service to run: `exe`, unless it is in the Nix store, where the
+/// garbage collection after the next update removes it. Then it is the binary of that
+/// name in the `bin` directory of one of `profiles` (e.g. `~/.nix-profile`), or else in
+/// `path` (a `$PATH`), which follow updates; `exe` when none has it.
+pub fn stable_exe(exe: &Path, profiles: &[PathBuf], path: Option<&OsStr>) -> PathBuf {
+    let Some(name) = exe.file_name().filter(|_| store_path(exe).is_some()) else {
+        return exe.to_path_buf();
+    };
+    profiles
+        .iter()
+        .map(|profile| profile.join("bin"))
+        .chain(path.map(env::split_paths).into_iter().flatten())
+        .filter(|dir| store_path(dir).is_none())
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+        .unwrap_or_else(|| exe.to_path_buf())
+}
+
+/// Quote `arg` for an `ExecStart=` line when it contains spaces or quotes.
+fn exec_arg(arg: &str) -> String {
+    if arg.contains([' ', '"', '\\', '\'']) {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
+
+/// Oneshot service running `exe update --yes --no-interactive`.
+pub fn service_unit(exe: &Path) -> String {
+    format!(
+        "[Unit]\n\
+         Description=Update and rebuild the Nix configuration with declair\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={} update --yes --no-interactive\n",
+        exec_arg(&exe.display().to_string())
+    )
+}
+
+/// Timer starting the service at `on_calendar` (a systemd calendar expression such as
+/// `weekly`); runs missed while the machine was off are caught up on the next boot.
+pub fn timer_unit(on_calendar: &str) -> String {
+    format!(
+        "[Unit]\n\
+         Description=Scheduled declair update\n\
+         \n\
+         [Timer]\n\
+         OnCalendar={}\n\
+         Persistent=true\n\
+         RandomizedDelaySec=1h\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        on_calendar
+    )
+}
+
+fn systemctl(runner: &dyn CommandRunner, args: &[&str]) -> Result<()> {
+    let cmd = CommandSpec::new(
+        "systemctl",
+        std::iter::once("--user").chain(args.iter().copied()),
+    );
+    let status = runner
+        .status(&cmd)
+        .map_err(|e| DeclairError::Systemd(t!("command.spawn", "systemctl", e)))?;
+    if !status.success() {
+        return Err(DeclairError::Systemd(t!(
+            "command.failed_status",
+            format!("systemctl --user {}", args.join(" ")),
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// Fail with [`DeclairError::Systemd`] unless systemd runs the system, telling WSL users
+/// how to turn it on.
+pub fn require_systemd() -> Result<()> {
+    if has_systemd() {
+        return Ok(());
+    }
+    Err(DeclairError::Systemd(match HostOs::detect() {
+        HostOs::Wsl { .. } => t!("schedule.no_systemd_wsl"),
+        _ => t!("schedule.no_systemd"),
+    }))
+}
+
+/// Write the units to `unit_dir` and enable and start the timer.
+pub fn install(
+    runner: &dyn CommandRunner,
+    unit_dir: &Path,
+    exe: &Path,
+    on_calendar: &str,
+) -> Result<()> {
+    fs::create_dir_all(unit_dir)?;
+    for (name, contents) in [
+        (SERVICE_NAME, service_unit(exe)),
+        (TIMER_NAME, timer_unit(on_calendar)),
+    ] {
+        let path = unit_dir.join(name);
+        fs::write(&path, contents).map_err(|e| DeclairError::Write { path, source: e })?;
+    }
+    systemctl(runner, &["daemon-reload"])?;
+    systemctl(runner, &["enable", "--now", TIMER_NAME])
+}
+
+/// Stop and disable the timer and delete the units from `unit_dir`.
+/// Missing units are not an error, so uninstalling twice is harmless.
+pub fn uninstall(runner: &dyn CommandRunner, unit_dir: &Path) -> Result<()> {
+    let timer = unit_dir.join(TIMER_NAME);
+    if timer.exists() {
+        systemctl(runner, &["disable", "--now", TIMER_NAME])?;
+    }
+    for name in [TIMER_NAME, SERVICE_NAME] {
+        match fs::remove_file(unit_dir.join(name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    systemctl(runner, &["daemon-reload"])
+}
+
+/// Print systemd's view of the timer and the last run of the service. systemctl exits
+/// non-zero for inactive units, which is a normal answer here rather than a failure.
+pub fn status(runner: &dyn CommandRunner) -> Result<()> {
+    let cmd = CommandSpec::new(
+        "systemctl",
+        ["--user", "status", "--no-pager", TIMER_NAME, SERVICE_NAME],
+    );
+    runner
+        .status(&cmd)
+        .map_err(|e| DeclairError::Systemd(t!("command.spawn", "systemctl", e)))?;
+    Ok(())
+}
@@ -0,0 +1,533 @@
+use crate::channel::nix_path_has_nixpkgs;
+use crate::config::Config;
+use crate::error::{DeclairError, Result};
+use crate::events::{Event, emit};
+use crate::explain::{Topic, explain};
+use crate::info::strings;
+use crate::nur::nur_search;
+use crate::plugin::find_plugin;
+use crate::policy::License;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec};
+use crate::selector::in_set;
+use crate::t;
+use crate::theme::theme;
+use regex::{Regex, RegexBuilder};
+use serde::de::{DeserializeOwned, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// `nix search` may have to download and evaluate nixpkgs, so give it plenty of time.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(300);
+/// Retries for `nix search` after transient network failures.
+const SEARCH_RETRIES: u32 = 3;
+/// Rendering the options manual is local and quick.
+const MANUAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A search result. `nix search` only reports `pname`, `version` and `description`; the
+/// `nix-env` fallback and NUR's index also fill in the `meta` fields.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PackageInfo {
+    /// Attribute path to put in a package list, e.g. `python3Packages.requests`.
+    #[serde(default)]
+    pub attr: String,
+    pub pname: String,
+    pub version: String,
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub license: Vec<License>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub homepage: Vec<String>,
+    /// Systems named in `meta.platforms`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub platforms: Vec<String>,
+    /// `meta.broken`.
+    #[serde(default)]
+    pub broken: bool,
+    /// Whether `meta.knownVulnerabilities` is non-empty.
+    #[serde(default)]
+    pub insecure: bool,
+    /// Whether the configuration already lists it; see [`mark_installed`].
+    #[serde(default)]
+    pub installed: bool,
+}
+
+impl PackageInfo {
+    /// The attribute path, or `pname` for results that don't know it.
+    pub fn name(&self) -> &str {
+        if self.attr.is_empty() {
+            &self.pname
+        } else {
+            &self.attr
+        }
+    }
+
+    /// Fill in what a `meta` attribute set says about the package.
+    pub(crate) fn apply_meta(&mut self, meta: &Value) {
+        if self.description.is_none() {
+            self.description = meta["description"].as_str().map(str::to_string);
+        }
+        self.license = License::from_meta(&meta["license"]);
+        self.homepage = strings(&meta["homepage"]);
+        self.platforms = strings(&meta["platforms"]);
+        self.broken = meta["broken"].as_bool().unwrap_or(false);
+        self.insecure = meta["knownVulnerabilities"]
+            .as_array()
+            .is_some_and(|v| !v.is_empty());
+    }
+}
+
+/// The attribute path of a `nix search --json` key:
+/// `legacyPackages.x86_64-linux.ripgrep` is `ripgrep`.
+pub fn attr_path(key: &str) -> &str {
+    for output in ["legacyPackages.", "packages."] {
+        if let Some(rest) = key.strip_prefix(output) {
+            return rest.split_once('.').map_or(rest, |(_, attr)| attr);
+        }
+    }
+    key
+}
+
+/// Flag the results whose attribute path is one of `installed`.
+pub fn mark_installed(results: &mut HashMap<String, PackageInfo>, installed: &HashSet<String>) {
+    for info in results.values_mut() {
+        info.installed = installed.contains(info.name());
+    }
+}
+
+/// Set the attribute path of results that don't have one from their key.
+fn fill_attrs(results: &mut HashMap<String, PackageInfo>) {
+    for (key, info) in results.iter_mut() {
+        if info.attr.is_empty() {
+            info.attr = attr_path(key).to_string();
+        }
+    }
+}
+
+/// Read a JSON object of search results one entry at a time, keeping what `convert` makes
+/// of each. Broad queries match tens of thousands of packages; converting as they are read
+/// never holds more than one raw entry (with its whole `meta`) in memory.
+pub(crate) fn parse_entries<T, F>(json: &[u8], convert: F) -> Result<HashMap<String, PackageInfo>>
+where
+    T: DeserializeOwned,
+    F: FnMut(String, T) -> Option<(String, PackageInfo)>,
+{
+    struct Entries<T, F>(F, PhantomData<T>);
+
+    impl<'de, T, F> Visitor<'de> for Entries<T, F>
+    where
+        T: DeserializeOwned,
+        F: FnMut(String, T) -> Option<(String, PackageInfo)>,
+    {
+        type Value = HashMap<String, PackageInfo>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("an object of packages")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(
+            mut self,
+            mut map: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            let mut results = HashMap::with_capacity(map.size_hint().unwrap_or(0));
+            while let Some((key, entry)) = map.next_entry::<String, T>()? {
+                results.extend((self.0)(key, entry));
+            }
+            Ok(results)
+        }
+    }
+
+    let mut de = serde_json::Deserializer::from_slice(json);
+    de.deserialize_map(Entries(convert, PhantomData))
+        .and_then(|results| de.end().map(|()| results))
+        .map_err(|e| DeclairError::SearchFailed(t!("search.invalid_json", e)))
+}
+
+/// Parse `nix search --json` output.
+pub fn parse_search_results(json: &[u8]) -> Result<HashMap<String, PackageInfo>> {
+    parse_entries(json, |key, info: PackageInfo| Some((key, info)))
+}
+
+/// How a search query matches packages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SearchMode {
+    /// The query appears in the attribute path, `pname` or description.
+    #[default]
+    Substring,
+    /// The attribute path, its last part or `pname` starts with the query.
+    Prefix,
+    /// The attribute path, its last part or `pname` is the query.
+    Exact,
+    /// The query is a regular expression, matched like `nix search` does.
+    Regex,
+}
+
+impl SearchMode {
+    /// The mode named `name`: `substring`, `prefix`, `exact` or `regex`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "substring" => Some(SearchMode::Substring),
+            "prefix" => Some(SearchMode::Prefix),
+            "exact" => Some(SearchMode::Exact),
+            "regex" => Some(SearchMode::Regex),
+            _ => None,
+        }
+    }
+}
+
+/// `text` as a regular expression matching it literally, in the POSIX extended syntax Nix
+/// understands (which escapes fewer characters than the `regex` crate would).
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if r".[]{}()\*+?^$|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// A search query and how it matches. `nix search` and `nix-env` only take regular
+/// expressions, so the other modes escape the query for them and narrow their results
+/// here; backends that can't search by regular expression are filtered here too.
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub text: String,
+    pub mode: SearchMode,
+    regex: Regex,
+}
+
+impl Query {
+    pub fn new(text: &str, mode: SearchMode) -> Result<Self> {
+        let pattern = match mode {
+            SearchMode::Regex => text.to_string(),
+            _ => escape(text),
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| DeclairError::InvalidRegex {
+                pattern: text.to_string(),
+                reason: e.to_string(),
+            })?;
+        Ok(Query {
+            text: text.to_string(),
+            mode,
+            regex,
+        })
+    }
+
+    /// A query in the default mode, which can't fail: escaped text always compiles.
+    pub fn substring(text: &str) -> Self {
+        Query::new(text, SearchMode::Substring).expect("escaped query is a valid regex")
+    }
+
+    /// The regular expression to hand to `nix search` and `nix-env`.
+    pub fn nix_regex(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// Whether `pkg` matches.
+    pub fn matches(&self, pkg: &PackageInfo) -> bool {
+        let name = pkg.name();
+        let last = name.rsplit('.').next().unwrap_or(name);
+        self.matches_names(&[name, last, &pkg.pname], pkg.description.as_deref())
+    }
+
+    /// Whether something called any of `names` and described by `description` matches;
+    /// the description only counts for substring and regex queries.
+    pub fn matches_names(&self, names: &[&str], description: Option<&str>) -> bool {
+        let text = self.text.to_lowercase();
+        match self.mode {
+            SearchMode::Exact => names.iter().any(|n| n.to_lowercase() == text),
+            SearchMode::Prefix => names.iter().any(|n| n.to_lowercase().starts_with(&text)),
+            SearchMode::Substring | SearchMode::Regex => names
+                .iter()
+                .copied()
+                .chain(description)
+                .any(|s| self.regex.is_match(s)),
+        }
+    }
+}
+
+/// First Nix release with the flake-based `nix search`.
+pub const FLAKE_SEARCH_VERSION: NixVersion = NixVersion(2, 4, 0);
+/// How long to wait for `nix --version` and `nix show-config`.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A Nix release, e.g. `NixVersion(2, 18, 1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NixVersion(pub u32, pub u32, pub u32);
+
+impl fmt::Display for NixVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// Parse the output of `nix --version`, e.g. `nix (Nix) 2.18.1` or `nix (Lix, like Nix) 2.91.0`.
+pub fn parse_nix_version(output: &str) -> Option<NixVersion> {
+    let version = output.split_whitespace().last()?;
+    let mut parts = version
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|p| p.parse::<u32>().ok());
+    Some(NixVersion(
+        parts.next()??,
+        parts.next()??,
+        parts.next().flatten().unwrap_or(0),
+    ))
+}
+
+/// Installed Nix version, or `None` if `nix` is missing or its version can't be parsed.
+pub fn nix_version(runner: &dyn CommandRunner) -> Option<NixVersion> {
+    let cmd = CommandSpec::new("nix", ["--version"])
+        .timeout(PROBE_TIMEOUT)
+        .query();
+    let output = runner.output(&cmd).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_nix_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Experimental features enabled in the system-wide/user Nix configuration
+/// (without the ones declair passes on the command line).
+pub fn experimental_features(runner: &dyn CommandRunner) -> Result<Vec<String>> {
+    let cmd = CommandSpec::new("nix", ["show-config"])
+        .timeout(PROBE_TIMEOUT)
+        .query();
+    let output = runner
+        .output(&cmd)
+        .map_err(|e| DeclairError::SearchFailed(t!("command.spawn", "nix show-config", e)))?;
+    if !output.status.success() {
+        // Without `nix-command`, `nix show-config` itself is unavailable.
+        return Ok(Vec::new());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .find(|(key, _)| key.trim() == "experimental-features")
+        .map(|(_, value)| value.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default())
+}
+
+/// Which nixpkgs `nix search` looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NixpkgsSource {
+    /// `nixpkgs` from the flake registry.
+    Registry,
+    /// `<nixpkgs>` from `NIX_PATH`, i.e. the channel a configuration without a flake is
+    /// built from.
+    NixPath,
+}
+
+impl NixpkgsSource {
+    /// `<nixpkgs>` for configurations without a flake when `NIX_PATH` provides it, so
+    /// versions match the channel; the registry otherwise.
+    pub fn for_config(config: &Config) -> Self {
+        let nix_path = env::var("NIX_PATH").unwrap_or_default();
+        if !config.flake && nix_path_has_nixpkgs(&nix_path) {
+            NixpkgsSource::NixPath
+        } else {
+            NixpkgsSource::Registry
+        }
+    }
+
+    /// `nix search` arguments selecting this nixpkgs.
+    fn installable(self) -> &'static [&'static str] {
+        match self {
+            NixpkgsSource::Registry => &["nixpkgs"],
+            NixpkgsSource::NixPath => &["--file", "<nixpkgs>", ""],
+        }
+    }
+}
+
+/// Search for a package via `nix search`, falling back to `nix-env -qa` on installations
+/// where the flake-based search is unavailable (Nix older than 2.4, or a failing
+/// `nix search`, e.g. because experimental features are locked down).
+pub fn search_packages(
+    runner: &dyn CommandRunner,
+    query: &str,
+) -> Result<HashMap<String, PackageInfo>> {
+    search_packages_in(runner, NixpkgsSource::Registry, query)
+}
+
+/// [`search_packages`] in the nixpkgs of `source`.
+pub fn search_packages_in(
+    runner: &dyn CommandRunner,
+    source: NixpkgsSource,
+    query: &str,
+) -> Result<HashMap<String, PackageInfo>> {
+    match nix_search(runner, source, query) {
+        Err(DeclairError::SearchFailed(reason)) => {
+            nix_env_search(runner, query).map_err(|fallback| {
+                let version = match nix_version(runner) {
+                    Some(v) if v < FLAKE_SEARCH_VERSION => {
+                        t!("search.nix_too_old", v)
+                    }
+                    Some(v) => t!("search.nix_version", v),
+                    None => t!("search.unknown_nix_version"),
+                };
+                DeclairError::SearchFailed(t!("search.fallback_failed", reason, version, fallback))
+            })
+        }
+        result => result,
+    }
+    .map(|mut results| {
+        fill_attrs(&mut results);
+        results
+    })
+}
+
+/// The `nix search` command looking for `query` in `source`.
+pub fn nix_search_command(source: NixpkgsSource, query: &str) -> CommandSpec {
+    let args = std::iter::once("search")
+        .chain(source.installable().iter().copied())
+        .chain([
+            query,
+            "--json",
+            "--extra-experimental-features",
+            "nix-command flakes",
+        ]);
+    CommandSpec::new("nix", args)
+        .timeout(SEARCH_TIMEOUT)
+        .retries(SEARCH_RETRIES)
+        .class(CommandClass::Search)
+        .query()
+}
+
+fn nix_search(
+    runner: &dyn CommandRunner,
+    source: NixpkgsSource,
+    query: &str,
+) -> Result<HashMap<String, PackageInfo>> {
+    let output = runner
+        .output(&nix_search_command(source, query))
+        .map_err(|e| DeclairError::SearchFailed(t!("command.spawn", "nix search", e)))?;
+    if !output.status.success() {
+        return Err(DeclairError::SearchFailed(t!(
+            "search.nonzero_exit",
+            "nix search"
+        )));
+    }
+    parse_search_results(&output.stdout)
+}
+
+/// One entry of `nix-env -qa --json --meta`. Older Nix releases only report `name`.
+#[derive(Deserialize)]
+struct NixEnvPackage {
+    name: String,
+    pname: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    meta: Value,
+}
+
+/// Search the channels with `nix-env`, which works without any experimental feature.
+fn nix_env_search(runner: &dyn CommandRunner, query: &str) -> Result<HashMap<String, PackageInfo>> {
+    let pattern = format!(".*{}.*", query);
+    let cmd = CommandSpec::new("nix-env", ["-qaP", "--json", "--meta", &pattern])
+        .timeout(SEARCH_TIMEOUT)
+        .retries(SEARCH_RETRIES)
+        .class(CommandClass::Search)
+        .query();
+    let output = runner
+        .output(&cmd)
+        .map_err(|e| DeclairError::SearchFailed(t!("command.spawn", "nix-env", e)))?;
+    if !output.status.success() {
+        return Err(DeclairError::SearchFailed(t!(
+            "search.nonzero_exit",
+            "nix-env -qa"
+        )));
+    }
+    parse_entries(&output.stdout, |key, p: NixEnvPackage| {
+        let (name_pname, name_version) = split_name(&p.name);
+        // `nix-env -qaP` prefixes attribute paths with the channel: `nixos.ripgrep`.
+        let attr = key.split_once('.').map_or(key.as_str(), |(_, a)| a);
+        let mut info = PackageInfo {
+            attr: attr.to_string(),
+            pname: p.pname.unwrap_or(name_pname),
+            version: p.version.unwrap_or(name_version),
+            ..Default::default()
+        };
+        info.apply_meta(&p.meta);
+        Some((key, info))
+    })
+}
+
+/// Search nixpkgs (the channel's, see [`NixpkgsSource::for_config`]) and the extra sources `config` enables: NUR and plugin backends.
+/// A failing extra source only prints a warning.
+pub fn search_configured(
+    runner: &dyn CommandRunner,
+    config: &Config,
+    query: &Query,
+) -> Result<HashMap<String, PackageInfo>> {
+    emit(&Event::SearchStarted {
+        query: query.text.clone(),
+    });
+    explain(Topic::Search);
+    let mut results =
+        search_packages_in(runner, NixpkgsSource::for_config(config), query.nix_regex())?;
+    if matches!(query.mode, SearchMode::Exact | SearchMode::Prefix) {
+        results.retain(|_, info| query.matches(info));
+    }
+    let mut extra = Vec::new();
+    if config.nur.enable {
+        // NUR's index is searched by words; take all of it and match a regex here.
+        let words = match query.mode {
+            SearchMode::Regex => "",
+            _ => query.text.as_str(),
+        };
+        extra.push(nur_search(runner, &config.nur, words));
+    }
+    for name in &config.plugins.search {
+        extra.push(find_plugin(name).and_then(|p| p.search(runner, &query.text)));
+    }
+    for found in extra {
+        match found {
+            Ok(mut found) => {
+                fill_attrs(&mut found);
+                found.retain(|_, info| query.matches(info));
+                results.extend(found);
+            }
+            Err(e) => eprintln!("{}", theme().warning(t!("warning", e)).for_stderr()),
+        }
+    }
+    // A query naming an excluded set, like `haskellPackages.pandoc`, still finds it.
+    let excluded: Vec<&String> = config
+        .exclude_sets
+        .iter()
+        .filter(|set| !in_set(&query.text, set))
+        .collect();
+    results.retain(|_, info| !excluded.iter().any(|set| in_set(info.name(), set)));
+    emit(&Event::SearchFinished {
+        results: results.len(),
+    });
+    Ok(results)
+}
+
+/// Split a derivation name like `ripgrep-14.1.0` into `ripgrep` and `14.1.0`.
+pub(crate) fn split_name(name: &str) -> (String, String) {
+    let split = name
+        .match_indices('-')
+        .find(|(i, _)| name[i + 1..].starts_with(|c: char| c.is_ascii_digit()));
+    match split {
+        Some((i, _)) => (name[..i].to_string(), name[i + 1..].to_string()),
+        None => (name.to_string(), String::new()),
+    }
+}
+
+/// Read `man configuration.nix` so we can tell whether `programs.<name>.enable` exists.
+pub fn nixos_options_manual(runner: &dyn CommandRunner) -> Result<String> {
+    let cmd = CommandSpec::new("sh", ["-c", "man configuration.nix | col -bx"])
+        .timeout(MANUAL_TIMEOUT)
+        .query();
+    let output = runner.output(&cmd)?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,170 @@
+//! Layout of search results in the interactive selector.
+
+use crate::search::PackageInfo;
+use crate::t;
+use console::{measure_text_width, truncate_str};
+
+/// Widest name column before long names stop pushing the versions right.
+const MAX_NAME_WIDTH: usize = 32;
+/// Widest version column.
+const MAX_VERSION_WIDTH: usize = 16;
+/// Most results the selector lists; broad queries like `lib` match tens of thousands.
+pub const MAX_RESULTS: usize = 500;
+
+/// How well `pkg`'s name matches `query`: an exact match (of the whole attribute path or
+/// its last part) first, then prefixes, then names containing it, then the rest.
+fn rank(pkg: &PackageInfo, query: &str) -> u8 {
+    let name = pkg.name().to_lowercase();
+    let last = name.rsplit('.').next().unwrap_or(&name);
+    if name == query || last == query {
+        0
+    } else if name.starts_with(query) || last.starts_with(query) {
+        1
+    } else if name.contains(query) {
+        2
+    } else {
+        3
+    }
+}
+
+/// The results to list for `query`, sorted by name, and how many others were left out.
+/// Past `limit`, the results whose names match `query` best are kept; among equally good
+/// matches, those in the `prefer`red package sets, then top-level ones.
+pub fn best_results(
+    mut pkgs: Vec<PackageInfo>,
+    query: &str,
+    limit: usize,
+    prefer: &[String],
+) -> (Vec<PackageInfo>, usize) {
+    let more = pkgs.len().saturating_sub(limit);
+    if more > 0 {
+        let query = query.to_lowercase();
+        pkgs.sort_by_cached_key(|p| {
+            let set = if prefer.iter().any(|set| in_set(p.name(), set)) {
+                0
+            } else if package_set(p.name()).is_empty() {
+                1
+            } else {
+                2
+            };
+            (rank(p, &query), set, p.name().to_string())
+        });
+        pkgs.truncate(limit);
+    }
+    pkgs.sort_by(|a, b| a.name().cmp(b.name()));
+    (pkgs, more)
+}
+
+/// The package set holding `attr`, e.g. `python312Packages` for
+/// `python312Packages.requests`; empty for a top-level attribute.
+pub fn package_set(attr: &str) -> &str {
+    attr.rsplit_once('.').map_or("", |(set, _)| set)
+}
+
+/// Whether `attr` is in the package set `set` or one nested in it, e.g.
+/// `nodePackages.typescript` in `nodePackages`.
+pub fn in_set(attr: &str, set: &str) -> bool {
+    attr.strip_prefix(set)
+        .is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Puts results named exactly `query` in several package sets (`requests`,
+/// `python311Packages.requests`, `python312Packages.requests`) first, one set after the
+/// other: the sets in `prefer`, then the top level, then the rest by name, so the default
+/// selection is the preferred one. Returns those sets in order, or nothing (leaving `pkgs`
+/// as is) when at most one set has the package.
+pub fn group_by_set(pkgs: &mut Vec<PackageInfo>, query: &str, prefer: &[String]) -> Vec<String> {
+    let query = query.to_lowercase();
+    let is_exact =
+        |p: &PackageInfo| p.name().to_lowercase().rsplit('.').next() == Some(query.as_str());
+    let rank = |set: &str| match prefer.iter().position(|p| set == p || in_set(set, p)) {
+        Some(i) => (0, i, String::new()),
+        None if set.is_empty() => (1, 0, String::new()),
+        None => (2, 0, set.to_string()),
+    };
+    let mut sets: Vec<&str> = pkgs
+        .iter()
+        .filter(|p| is_exact(p))
+        .map(|p| package_set(p.name()))
+        .collect();
+    sets.sort_by_cached_key(|set| rank(set));
+    sets.dedup();
+    if sets.len() < 2 {
+        return Vec::new();
+    }
+    let sets: Vec<String> = sets.into_iter().map(str::to_string).collect();
+    let (mut exact, rest): (Vec<PackageInfo>, Vec<PackageInfo>) =
+        std::mem::take(pkgs).into_iter().partition(is_exact);
+    exact.sort_by_cached_key(|p| rank(package_set(p.name())));
+    *pkgs = exact.into_iter().chain(rest).collect();
+    sets
+}
+
+/// One selector line per package, cut to `width` columns: attribute path and version in
+/// aligned columns, then `[installed]`/`[broken]`/`[insecure]` flags and as much of the
+/// description as fits, ending in `…` when cut. The attribute path stays the line's first
+/// word.
+pub fn option_lines(pkgs: &[PackageInfo], width: usize) -> Vec<String> {
+    let name_width = pkgs
+        .iter()
+        .map(|p| measure_text_width(p.name()))
+        .max()
+        .unwrap_or(0)
+        .min(MAX_NAME_WIDTH.min(width / 3));
+    let version_width = pkgs
+        .iter()
+        .map(|p| measure_text_width(&p.version))
+        .max()
+        .unwrap_or(0)
+        .min(MAX_VERSION_WIDTH);
+    pkgs.iter()
+        .map(|p| {
+            let mut about: Vec<String> = [
+                (p.installed, "select.installed"),
+                (p.broken, "select.broken"),
+                (p.insecure, "select.insecure"),
+            ]
+            .into_iter()
+            .filter(|(flag, _)| *flag)
+            .map(|(_, key)| t!(key))
+            .collect();
+            about.extend(p.description.as_deref().map(|d| d.replace('\n', " ")));
+            let line = format!(
+                "{:<nw$}  {:<vw$}  {}",
+                p.name(),
+                p.version,
+                about.join(" "),
+                nw = name_width,
+                vw = version_width
+            );
+            truncate_str(line.trim_end(), width, "…").into_owned()
+        })
+        .collect()
+}
+
+/// Number of selector rows to show at once on a terminal `height` rows tall; longer result
+/// lists scroll page by page.
+pub fn page_size(height: usize) -> usize {
+    height.saturating_sub(4).max(5)
+}
+
+/// Break `text` into lines of at most `width` columns at spaces, keeping paragraph breaks.
+/// Words longer than `width` get a line of their own.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.trim().split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && measure_text_width(&line) + 1 + measure_text_width(word) > width
+            {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
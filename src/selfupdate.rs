@@ -0,0 +1,179 @@
+//! How declair itself was installed, for `declair self check`: from the path of the
+//! running executable, the Nix profile's manifest, the configuration's `flake.lock` and
+//! the cargo home, and the command updating it.
+
+use crate::host::home_dir;
+use crate::runner::{CommandClass, CommandSpec};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Name of declair's repository, as a flake input's `repo` or the end of its URL.
+const REPO_NAME: &str = "declair-rs";
+
+/// How the running declair was installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallMethod {
+    /// An element of a profile managed with `nix profile`, by name (or index with older
+    /// Nix versions).
+    NixProfile {
+        element: String,
+    },
+    /// A profile managed with `nix-env`.
+    NixEnv,
+    /// The input `input` of the configuration's flake in `flake_dir`, so rebuilding the
+    /// configuration installs it.
+    FlakeInput {
+        input: String,
+        flake_dir: PathBuf,
+    },
+    /// Somewhere else in the Nix store, e.g. nixpkgs' package in the configuration; it is
+    /// updated with whatever installed it.
+    NixStore {
+        store_path: PathBuf,
+    },
+    /// `cargo install`.
+    Cargo,
+    Unknown,
+}
+
+/// The store path `exe` is in: `/nix/store/<hash>-declair-rs-0.3.0` for
+/// `/nix/store/<hash>-declair-rs-0.3.0/bin/declair-rs`.
+pub fn store_path(exe: &Path) -> Option<PathBuf> {
+    let rest = exe.strip_prefix("/nix/store").ok()?;
+    match rest.components().next()? {
+        Component::Normal(name) => Some(Path::new("/nix/store").join(name)),
+        _ => None,
+    }
+}
+
+/// The element of a `nix profile` manifest (`manifest.json`) holding `store_path`: its
+/// name in version 3 manifests, its index in older ones.
+pub fn profile_element(manifest: &str, store_path: &Path) -> Option<String> {
+    let manifest: Value = serde_json::from_str(manifest).ok()?;
+    let holds = |element: &Value| {
+        element["storePaths"]
+            .as_array()
+            .is_some_and(|paths| paths.iter().any(|p| p.as_str() == store_path.to_str()))
+    };
+    match &manifest["elements"] {
+        Value::Object(elements) => elements
+            .iter()
+            .find(|(_, element)| holds(element))
+            .map(|(name, _)| name.clone()),
+        Value::Array(elements) => elements.iter().position(holds).map(|idx| idx.to_string()),
+        _ => None,
+    }
+}
+
+/// The input of the flake whose `flake.lock` is `lock` that is declair's repository, by
+/// the reference it was locked from.
+pub fn declair_input(lock: &str) -> Option<String> {
+    let lock: Value = serde_json::from_str(lock).ok()?;
+    let nodes = &lock["nodes"];
+    let root = lock["root"].as_str().unwrap_or("root");
+    let inputs = nodes[root]["inputs"].as_object()?;
+    inputs.iter().find_map(|(input, node)| {
+        let original = &nodes[node.as_str()?]["original"];
+        let repo = original["repo"].as_str().or_else(|| {
+            original["url"]
+                .as_str()
+                .map(|url| url.trim_end_matches('/').trim_end_matches(".git"))
+                .and_then(|url| url.rsplit('/').next())
+        })?;
+        repo.eq_ignore_ascii_case(REPO_NAME).then(|| input.clone())
+    })
+}
+
+/// The user's Nix profile: `~/.nix-profile`, or in the XDG state dir with
+/// `use-xdg-base-directories`.
+pub fn user_profiles() -> Vec<PathBuf> {
+    let state = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".local/state")));
+    home_dir()
+        .map(|home| home.join(".nix-profile"))
+        .into_iter()
+        .chain(state.map(|dir| dir.join("nix/profile")))
+        .collect()
+}
+
+/// Where `cargo install` puts its `bin` directory: `$CARGO_HOME`, or `~/.cargo`.
+pub fn cargo_home() -> Option<PathBuf> {
+    env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".cargo")))
+}
+
+/// How the declair at `exe` was installed, looking at the Nix `profiles` (like
+/// `~/.nix-profile`), the cargo home and the directory of the configuration's flake.
+pub fn detect(
+    exe: &Path,
+    profiles: &[PathBuf],
+    cargo_home: Option<&Path>,
+    flake_dir: Option<&Path>,
+) -> InstallMethod {
+    if let Some(cargo_home) = cargo_home
+        && exe.starts_with(cargo_home.join("bin"))
+    {
+        return InstallMethod::Cargo;
+    }
+    let Some(store_path) = store_path(exe) else {
+        return InstallMethod::Unknown;
+    };
+    for profile in profiles {
+        if let Ok(manifest) = fs::read_to_string(profile.join("manifest.json")) {
+            if let Some(element) = profile_element(&manifest, &store_path) {
+                return InstallMethod::NixProfile { element };
+            }
+        } else if let Ok(manifest) = fs::read_to_string(profile.join("manifest.nix"))
+            && manifest.contains(&*store_path.to_string_lossy())
+        {
+            return InstallMethod::NixEnv;
+        }
+    }
+    if let Some(flake_dir) = flake_dir
+        && let Ok(lock) = fs::read_to_string(flake_dir.join("flake.lock"))
+        && let Some(input) = declair_input(&lock)
+    {
+        return InstallMethod::FlakeInput {
+            input,
+            flake_dir: flake_dir.to_path_buf(),
+        };
+    }
+    InstallMethod::NixStore { store_path }
+}
+
+/// The command updating a declair installed by `method`; `None` when declair can't
+/// update it itself. For a flake input it only updates the lock; a rebuild installs it.
+pub fn self_update_command(method: &InstallMethod) -> Option<CommandSpec> {
+    let cmd = match method {
+        InstallMethod::NixProfile { element } => CommandSpec::new(
+            "nix",
+            [
+                "profile",
+                "upgrade",
+                element.as_str(),
+                "--extra-experimental-features",
+                "nix-command flakes",
+            ],
+        ),
+        InstallMethod::NixEnv => CommandSpec::new("nix-env", ["--upgrade", REPO_NAME]),
+        InstallMethod::FlakeInput { input, flake_dir } => CommandSpec::new(
+            "nix",
+            [
+                "flake",
+                "update",
+                input.as_str(),
+                "--extra-experimental-features",
+                "nix-command flakes",
+            ],
+        )
+        .current_dir(flake_dir),
+        InstallMethod::Cargo => CommandSpec::new("cargo", ["install", "--locked", REPO_NAME]),
+        InstallMethod::NixStore { .. } | InstallMethod::Unknown => return None,
+    };
+    Some(cmd.class(CommandClass::Rebuild))
+}
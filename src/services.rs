@@ -0,0 +1,145 @@
+//! Services enabled in a config file, for `declair service`: one-line
+//! `services.<name>.enable = ...;` assignments, added and removed like the
+//! `programs.<name>.enable` lines of `declair add --program`, and whether the NixOS option
+//! tree has such a service, asked of `nix eval`.
+
+use crate::audit::Nixpkgs;
+use crate::edit::{Edit, add_program_to_nix, remove_program_from_nix};
+use crate::error::{DeclairError, Result};
+use crate::runner::{CommandClass, CommandRunner, CommandSpec, eval_json};
+use crate::syntax::nix_string;
+use crate::t;
+use std::path::Path;
+use std::time::Duration;
+
+/// Evaluating the NixOS module system takes a while on first use.
+const EVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// One `services.<name>.enable` assignment of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Service {
+    /// The service's attribute path below `services`, e.g. `openssh` or
+    /// `xserver.desktopManager.gnome`.
+    pub name: String,
+    /// The value as written, e.g. `true` or `lib.mkDefault true`.
+    pub value: String,
+    /// 1-based line of the assignment.
+    pub line: usize,
+}
+
+/// Check that `name` is an attribute path of plain identifiers, like `openssh` or
+/// `xserver.desktopManager.gnome`.
+pub fn validate_service(name: &str) -> Result<()> {
+    let valid = name.split('.').all(|part| {
+        let mut chars = part.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    });
+    if !valid {
+        return Err(DeclairError::Config(t!("service.invalid_name", name)));
+    }
+    Ok(())
+}
+
+/// The option enabling `name`, e.g. `services.openssh.enable`.
+pub fn enable_option(name: &str) -> String {
+    format!("services.{}.enable", name)
+}
+
+fn parse(lines: &[String]) -> Vec<Service> {
+    lines
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let (lhs, rhs) = line.trim().split_once('=')?;
+            let name = lhs
+                .trim()
+                .strip_prefix("services.")?
+                .strip_suffix(".enable")?;
+            let value = rhs.split(';').next()?.trim();
+            if validate_service(name).is_err() || value.is_empty() {
+                return None;
+            }
+            Some(Service {
+                name: name.to_string(),
+                value: value.to_string(),
+                line: idx + 1,
+            })
+        })
+        .collect()
+}
+
+/// The services `file_path` enables or disables with a one-line assignment.
+pub fn list_services(file_path: &Path) -> Result<Vec<Service>> {
+    let edit = Edit::begin(file_path)?;
+    Ok(parse(&edit.lines()))
+}
+
+/// Enable `name`: an existing assignment of another value is set to `true` in place,
+/// otherwise `services.<name>.enable = true;` goes before the file's closing brace.
+/// Fails with [`DeclairError::ProgramExists`] when the service is enabled already.
+pub fn enable_service(file_path: &Path, name: &str) -> Result<()> {
+    validate_service(name)?;
+    let option = enable_option(name);
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+    let Some(existing) = parse(&lines).into_iter().find(|s| s.name == name) else {
+        drop(edit);
+        return add_program_to_nix(file_path, &option);
+    };
+    if existing.value == "true" {
+        return Err(DeclairError::ProgramExists {
+            pattern: option,
+            file: file_path.to_path_buf(),
+        });
+    }
+    edit.backup()?;
+    let idx = existing.line - 1;
+    let line = &lines[idx];
+    let eq = line.find('=').unwrap_or(line.len());
+    let tail = line[eq..].split_once(';').map_or("", |(_, tail)| tail);
+    lines[idx] = format!("{}= true;{}", &line[..eq], tail);
+    edit.commit(&lines.join("\n"))
+}
+
+/// Remove the assignment enabling `name`. Fails with [`DeclairError::ProgramNotFound`]
+/// when there is none.
+pub fn disable_service(file_path: &Path, name: &str) -> Result<()> {
+    validate_service(name)?;
+    remove_program_from_nix(file_path, &enable_option(name))
+}
+
+/// `nix eval` of whether the NixOS option tree of `nixpkgs` has `services.<name>.enable`.
+pub fn service_command(nixpkgs: &Nixpkgs, name: &str) -> CommandSpec {
+    let path: Vec<String> = enable_option(name).split('.').map(nix_string).collect();
+    let expr = format!(
+        "let src = {}; lib = import (src + \"/lib\"); \
+         options = (import (src + \"/nixos/lib/eval-config.nix\") {{ modules = [ ]; }}).options; \
+         in lib.hasAttrByPath [ {} ] options",
+        nixpkgs.source(),
+        path.join(" ")
+    );
+    CommandSpec::new(
+        "nix",
+        [
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+            &expr,
+            "--extra-experimental-features",
+            "nix-command flakes",
+        ],
+    )
+    .timeout(EVAL_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query()
+}
+
+/// Whether NixOS in `nixpkgs` has a `services.<name>.enable` option (see
+/// [`service_command`]).
+pub fn service_exists(runner: &dyn CommandRunner, nixpkgs: &Nixpkgs, name: &str) -> Result<bool> {
+    eval_json(runner, &service_command(nixpkgs, name))
+}
@@ -0,0 +1,339 @@
+use crate::atomic::write_atomic;
+use crate::error::{DeclairError, Result};
+use crate::paths::{get_data_dir, get_state_dir};
+use crate::t;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Why and when declair added a package, kept outside the Nix config.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PackageRecord {
+    /// Nix file the package was added to.
+    pub file: PathBuf,
+    /// Seconds since the Unix epoch.
+    pub added_at: u64,
+    pub user: String,
+    pub host: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl PackageRecord {
+    /// Record for a package added to `file` right now by the current user on this host.
+    pub fn now(file: &Path, reason: Option<String>) -> Self {
+        PackageRecord {
+            file: file.to_path_buf(),
+            added_at: unix_now(),
+            user: current_user(),
+            host: current_host(),
+            reason,
+        }
+    }
+
+    /// `added_at` as an RFC 3339 timestamp, e.g. `2025-03-01T12:00:00Z`.
+    pub fn added_at_display(&self) -> String {
+        format_time(self.added_at)
+    }
+
+    /// `user@host`.
+    pub fn added_by(&self) -> String {
+        format!("{}@{}", self.user, self.host)
+    }
+}
+
+/// How many recently used config paths are remembered.
+pub const MAX_RECENT_CONFIGS: usize = 10;
+
+/// How many edits the transaction log keeps; older ones are dropped.
+pub const MAX_TRANSACTIONS: usize = 200;
+
+/// One edit declair made to the configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    /// `add`, `remove`, `replace`, `overlay-add`, ...
+    pub action: String,
+    pub packages: Vec<String>,
+    /// Nix file the edit was made to.
+    pub file: PathBuf,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    /// Whether a successful rebuild has applied the edit since.
+    #[serde(default)]
+    pub applied: bool,
+    /// The files edited, while a rebuild of the edit runs. A rebuild that neither
+    /// finished nor failed, e.g. because declair was interrupted, leaves them set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rebuilding: Vec<PathBuf>,
+}
+
+impl Transaction {
+    /// An edit of `file` made right now.
+    pub fn now(action: &str, packages: Vec<String>, file: &Path) -> Self {
+        Transaction {
+            action: action.to_string(),
+            packages,
+            file: file.to_path_buf(),
+            at: unix_now(),
+            applied: false,
+            rebuilding: Vec::new(),
+        }
+    }
+
+    /// `at` as an RFC 3339 timestamp.
+    pub fn at_display(&self) -> String {
+        format_time(self.at)
+    }
+}
+
+/// The last successful rebuild declair ran.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RebuildRecord {
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    /// What was rebuilt, e.g. `NixOS (.#laptop)`.
+    pub target: String,
+    /// Store path of the generation that was active right after it, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<PathBuf>,
+}
+
+impl RebuildRecord {
+    /// `at` as an RFC 3339 timestamp.
+    pub fn at_display(&self) -> String {
+        format_time(self.at)
+    }
+}
+
+/// Install history of the packages declair added, stored as JSON in declair's state dir,
+/// the config paths used recently and a log of edits and rebuilds.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct State {
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageRecord>,
+    /// Config paths declair worked on, most recent first, as offered by the path prompts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent_configs: Vec<String>,
+    /// Edits declair made, oldest first, at most [`MAX_TRANSACTIONS`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transactions: Vec<Transaction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_rebuild: Option<RebuildRecord>,
+    /// Packages that are only removed or replaced with `--force`.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub pinned: BTreeSet<String>,
+    /// The packages of each manifest as `declair sync` last applied it, by the manifest's
+    /// absolute path.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub synced: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl State {
+    /// Load the state at `path`; a missing file is an empty state.
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| DeclairError::Config(t!("state.invalid", path.display(), e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(DeclairError::Read {
+                path: path.to_path_buf(),
+                source: e,
+            }),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| DeclairError::Config(t!("state.serialize", e)))?;
+        write_atomic(path, json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Remember that `pkg` was just added to `file`, replacing any older record.
+    pub fn record_added(&mut self, pkg: &str, file: &Path, reason: Option<String>) {
+        self.packages
+            .insert(pkg.to_string(), PackageRecord::now(file, reason));
+    }
+
+    /// Drop the record of `pkg`, e.g. after it was removed from the config.
+    pub fn forget(&mut self, pkg: &str) {
+        self.packages.remove(pkg);
+    }
+
+    pub fn get(&self, pkg: &str) -> Option<&PackageRecord> {
+        self.packages.get(pkg)
+    }
+
+    /// Append `transaction` to the log, dropping the oldest entries beyond
+    /// [`MAX_TRANSACTIONS`].
+    pub fn log(&mut self, transaction: Transaction) {
+        self.transactions.push(transaction);
+        let excess = self.transactions.len().saturating_sub(MAX_TRANSACTIONS);
+        self.transactions.drain(..excess);
+    }
+
+    /// Drop the last logged edit of `file`, e.g. after it was reverted.
+    pub fn unlog(&mut self, file: &Path) {
+        if let Some(pos) = self.transactions.iter().rposition(|t| t.file == file) {
+            self.transactions.remove(pos);
+        }
+    }
+
+    /// Remember a successful rebuild, which applied every edit logged so far.
+    pub fn record_rebuild(&mut self, record: RebuildRecord) {
+        for transaction in &mut self.transactions {
+            transaction.applied = true;
+            transaction.rebuilding.clear();
+        }
+        self.last_rebuild = Some(record);
+    }
+
+    /// The logged edits no rebuild has applied yet, oldest first.
+    pub fn pending(&self) -> Vec<&Transaction> {
+        self.transactions.iter().filter(|t| !t.applied).collect()
+    }
+
+    /// Note that a rebuild of the last edit of `file`, which changed `files`, started.
+    pub fn rebuild_started(&mut self, file: &Path, files: &[PathBuf]) {
+        if let Some(transaction) = self
+            .transactions
+            .iter_mut()
+            .rev()
+            .find(|t| t.file == file && !t.applied)
+        {
+            transaction.rebuilding = files.to_vec();
+        }
+    }
+
+    /// Note that the rebuilds of the edits in `repo` stopped without applying them, e.g.
+    /// because they failed.
+    pub fn rebuild_stopped(&mut self, repo: &Path) {
+        for transaction in &mut self.transactions {
+            if transaction.file.starts_with(repo) {
+                transaction.rebuilding.clear();
+            }
+        }
+    }
+
+    /// The edits whose rebuild neither finished nor failed, oldest first.
+    pub fn interrupted(&self) -> Vec<&Transaction> {
+        self.transactions
+            .iter()
+            .filter(|t| !t.applied && !t.rebuilding.is_empty())
+            .collect()
+    }
+
+    /// Pin `pkg`. Returns whether it wasn't pinned before.
+    pub fn pin(&mut self, pkg: &str) -> bool {
+        self.pinned.insert(pkg.to_string())
+    }
+
+    /// Unpin `pkg`. Returns whether it was pinned.
+    pub fn unpin(&mut self, pkg: &str) -> bool {
+        self.pinned.remove(pkg)
+    }
+
+    pub fn is_pinned(&self, pkg: &str) -> bool {
+        self.pinned.contains(pkg)
+    }
+
+    /// Fail on the first pinned package among `packages`, which are about to be removed.
+    pub fn ensure_unpinned<'a>(&self, packages: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        match packages.into_iter().find(|pkg| self.is_pinned(pkg)) {
+            Some(pkg) => Err(DeclairError::Pinned {
+                pkg: pkg.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Put `path` first among the recent config paths, keeping at most
+    /// [`MAX_RECENT_CONFIGS`]. Returns whether anything changed.
+    pub fn remember_config(&mut self, path: &str) -> bool {
+        if self.recent_configs.first().is_some_and(|p| p == path) {
+            return false;
+        }
+        self.recent_configs.retain(|p| p != path);
+        self.recent_configs.insert(0, path.to_string());
+        self.recent_configs.truncate(MAX_RECENT_CONFIGS);
+        true
+    }
+}
+
+/// The config paths used recently, most recent first; none when the state can't be read.
+pub fn recent_configs() -> Vec<String> {
+    state_file_path()
+        .and_then(|path| State::load(&path))
+        .map(|state| state.recent_configs)
+        .unwrap_or_default()
+}
+
+/// Remember `path` as the most recently used config path, writing the state only when
+/// that changes it.
+pub fn remember_config(path: &str) -> Result<()> {
+    let state_path = state_file_path()?;
+    let mut state = State::load(&state_path)?;
+    if state.remember_config(path) {
+        state.save(&state_path)?;
+    }
+    Ok(())
+}
+
+/// Location of declair's state file, in the state dir. One that older versions left in the
+/// data dir is moved there first.
+pub fn state_file_path() -> Result<PathBuf> {
+    let state_dir =
+        get_state_dir().ok_or_else(|| DeclairError::Config(t!("config.no_state_dir")))?;
+    let path = state_dir.join("state.json");
+    if let Some(old) = get_data_dir().map(|dir| dir.join("state.json"))
+        && old != path
+        && old.exists()
+        && !path.exists()
+    {
+        fs::create_dir_all(&state_dir)?;
+        fs::rename(&old, &path)?;
+    }
+    Ok(path)
+}
+
+/// Load the state, apply `f` and save it again.
+pub fn update_state(path: &Path, f: impl FnOnce(&mut State)) -> Result<()> {
+    let mut state = State::load(path)?;
+    f(&mut state);
+    state.save(path)
+}
+
+/// Seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `secs` since the epoch as an RFC 3339 timestamp.
+pub fn format_time(secs: u64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(secs)).to_string()
+}
+
+fn current_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("LOGNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+fn current_host() -> String {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|p| fs::read_to_string(p).ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
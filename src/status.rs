@@ -0,0 +1,124 @@
+//! `declair status`: whether the configuration on disk is what the system runs. Edits are
+//! taken from the transaction log in the state file, other changes from the modification
+//! times of the repository's Nix files, and the running generation is compared with the
+//! one the last rebuild activated.
+
+use crate::conflicts::nix_files;
+use crate::host::home_dir;
+use crate::module::ModuleKind;
+use crate::state::{RebuildRecord, State, Transaction};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The running NixOS (or nix-darwin) system.
+const CURRENT_SYSTEM: &str = "/run/current-system";
+
+/// How the running generation relates to the last rebuild declair ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenerationStatus {
+    /// The generation the last rebuild activated is still the active one.
+    Matches,
+    /// Another generation became active since, e.g. after a rollback or a rebuild
+    /// outside declair.
+    Switched { current: PathBuf },
+    /// No rebuild was recorded, or the active generation can't be found.
+    Unknown,
+}
+
+/// What changed since the last successful rebuild.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub last_rebuild: Option<RebuildRecord>,
+    /// Edits declair made that no rebuild applied yet.
+    pub pending: Vec<Transaction>,
+    /// Nix files and `flake.lock`s in the repository modified after the last rebuild.
+    pub changed_files: Vec<PathBuf>,
+    pub generation: GenerationStatus,
+}
+
+impl Status {
+    /// Whether a rebuild is needed for the system to match the configuration: there are
+    /// unapplied edits or changed files, or no rebuild was ever recorded.
+    pub fn rebuild_pending(&self) -> bool {
+        self.last_rebuild.is_none() || !self.pending.is_empty() || !self.changed_files.is_empty()
+    }
+}
+
+/// The status of the configuration in `repo` from `state`, with `current` the running
+/// generation of the kind being rebuilt (see [`current_generation`]).
+pub fn status(state: &State, repo: &Path, current: Option<PathBuf>) -> Status {
+    let changed_files = match &state.last_rebuild {
+        Some(rebuild) => changed_since(repo, rebuild.at),
+        None => Vec::new(),
+    };
+    let generation = match (state.last_rebuild.as_ref(), current) {
+        (
+            Some(RebuildRecord {
+                generation: Some(built),
+                ..
+            }),
+            Some(current),
+        ) => {
+            if *built == current {
+                GenerationStatus::Matches
+            } else {
+                GenerationStatus::Switched { current }
+            }
+        }
+        _ => GenerationStatus::Unknown,
+    };
+    Status {
+        last_rebuild: state.last_rebuild.clone(),
+        pending: state.pending().into_iter().cloned().collect(),
+        changed_files,
+        generation,
+    }
+}
+
+/// The Nix files and `flake.lock`s below `repo` modified after `since` (seconds since the
+/// Unix epoch).
+pub fn changed_since(repo: &Path, since: u64) -> Vec<PathBuf> {
+    let lock = repo.join("flake.lock");
+    nix_files(repo)
+        .into_iter()
+        .chain(lock.exists().then_some(lock))
+        .filter(|file| modified_at(file).is_some_and(|at| at > since))
+        .collect()
+}
+
+fn modified_at(file: &Path) -> Option<u64> {
+    let modified = fs::metadata(file).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// Store path of the active generation of `kind`: `/run/current-system` for NixOS, the
+/// `home-manager` profile for Home Manager.
+pub fn current_generation(kind: ModuleKind) -> Option<PathBuf> {
+    let candidates = match kind {
+        ModuleKind::Nixos => vec![PathBuf::from(CURRENT_SYSTEM)],
+        ModuleKind::HomeManager => home_manager_profiles(),
+    };
+    candidates
+        .iter()
+        .find_map(|profile| fs::canonicalize(profile).ok())
+}
+
+/// Where Home Manager keeps its profile: in the XDG state dir since 23.05, under the
+/// per-user profiles before.
+fn home_manager_profiles() -> Vec<PathBuf> {
+    let state = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".local/state")));
+    let user = env::var("USER").unwrap_or_default();
+    state
+        .map(|dir| dir.join("nix/profiles/home-manager"))
+        .into_iter()
+        .chain([PathBuf::from(format!(
+            "/nix/var/nix/profiles/per-user/{}/home-manager",
+            user
+        ))])
+        .collect()
+}
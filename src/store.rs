@@ -0,0 +1,98 @@
+//! Where an added package ends up: its store paths and the program it installs, so declair
+//! can say how to run it after a rebuild.
+
+use crate::audit::Nixpkgs;
+use crate::error::Result;
+use crate::runner::{CommandClass, CommandRunner, CommandSpec, eval_json};
+use crate::syntax::nix_string;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Evaluating nixpkgs can take a while on a cold cache.
+const EVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The outputs of a package and its main program.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct StorePaths {
+    /// Store path by output name: `out`, `bin`, `man`, ...
+    pub outputs: BTreeMap<String, String>,
+    /// `meta.mainProgram`, or the package's `pname` the way `lib.getExe` falls back to it.
+    #[serde(rename = "mainProgram")]
+    pub main_program: Option<String>,
+}
+
+impl StorePaths {
+    /// Where the main program would be: `bin/<mainProgram>` in the `bin` output, or in
+    /// `out` for packages without one.
+    pub fn binary(&self) -> Option<PathBuf> {
+        let program = self.main_program.as_deref()?;
+        let output = self
+            .outputs
+            .get("bin")
+            .or_else(|| self.outputs.get("out"))?;
+        Some(PathBuf::from(output).join("bin").join(program))
+    }
+}
+
+/// `nix eval` of the outputs and main program of `pkg` in `nixpkgs`. Prints `null` for a
+/// missing package.
+pub fn store_paths_command(nixpkgs: &Nixpkgs, pkg: &str) -> CommandSpec {
+    let expr = format!(
+        "let pkgs = {}; \
+         p = pkgs.lib.attrByPath (pkgs.lib.splitString \".\" {}) null pkgs; \
+         in if p == null then null else {{ \
+         outputs = builtins.listToAttrs (map (o: {{ name = o; value = p.${{o}}.outPath; }}) \
+         (p.outputs or [ \"out\" ])); \
+         mainProgram = p.meta.mainProgram or p.pname or null; }}",
+        nixpkgs.expr(),
+        nix_string(pkg)
+    );
+    CommandSpec::new(
+        "nix",
+        [
+            "eval",
+            "--json",
+            "--impure",
+            "--expr",
+            &expr,
+            "--extra-experimental-features",
+            "nix-command flakes",
+        ],
+    )
+    .timeout(EVAL_TIMEOUT)
+    .class(CommandClass::Eval)
+    .query()
+}
+
+/// The store paths of `pkg` in `nixpkgs` (see [`store_paths_command`]); `None` when
+/// nixpkgs has no such package.
+pub fn store_paths(
+    runner: &dyn CommandRunner,
+    nixpkgs: &Nixpkgs,
+    pkg: &str,
+) -> Result<Option<StorePaths>> {
+    eval_json(runner, &store_paths_command(nixpkgs, pkg))
+}
+
+/// `nix run` of `pkg` from `nixpkgs` with `args`, running it once without adding it to the
+/// configuration.
+pub fn nix_run_command(nixpkgs: &Nixpkgs, pkg: &str, args: &[String]) -> CommandSpec {
+    let mut argv = vec![
+        "run".to_string(),
+        "--extra-experimental-features".to_string(),
+        "nix-command flakes".to_string(),
+    ];
+    match nixpkgs {
+        Nixpkgs::Flake(reference) => argv.push(format!("{}#{}", reference, pkg)),
+        Nixpkgs::NixPath => argv.extend([
+            "--file".to_string(),
+            "<nixpkgs>".to_string(),
+            pkg.to_string(),
+        ]),
+    }
+    argv.push("--".to_string());
+    argv.extend(args.iter().cloned());
+    CommandSpec::new("nix", argv)
+}
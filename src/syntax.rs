@@ -0,0 +1,464 @@
+//! Just enough of Nix's syntax to edit bindings safely: a lexer that skips comments and
+//! keeps strings (with their interpolations) whole, and the bindings of attribute sets,
+//! so that an option is found whether it is written `a.b.c = ...;` or nested as
+//! `a = { b.c = ...; };`, and a value spanning several lines is replaced as a whole.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A name or keyword.
+    Ident,
+    /// A `"..."` or `''...''` string, including its quotes.
+    Str,
+    /// One of `{ } [ ] ( ) ; = . , : ? @`.
+    Punct(u8),
+    /// Any other operator, e.g. `==`, `++` or `//`.
+    Op,
+    /// A number, path or URI.
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token {
+    pub fn text<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.start..self.end]
+    }
+
+    fn is(&self, c: u8) -> bool {
+        self.kind == TokenKind::Punct(c)
+    }
+}
+
+fn is_ident_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_'
+}
+
+fn is_ident(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'\'' | b'-')
+}
+
+fn is_path(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'.' | b'_' | b'-' | b'+' | b'/' | b'~')
+}
+
+/// The tokens of `text`, or `None` when a string or comment doesn't end.
+pub fn tokenize(text: &str) -> Option<Vec<Token>> {
+    let (tokens, end) = lex(text.as_bytes(), 0, false)?;
+    (end == text.len()).then_some(tokens)
+}
+
+/// Lex from `i`; with `interpolation`, stop after the `}` closing it.
+fn lex(bytes: &[u8], mut i: usize, interpolation: bool) -> Option<(Vec<Token>, usize)> {
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let next = bytes.get(i + 1).copied();
+        let start = i;
+        let kind = match c {
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                i += 1;
+                continue;
+            }
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if next == Some(b'*') => {
+                let close = find(bytes, i + 2, b"*/")?;
+                i = close + 2;
+                continue;
+            }
+            b'"' => {
+                i = string_end(bytes, i + 1)?;
+                TokenKind::Str
+            }
+            b'\'' if next == Some(b'\'') => {
+                i = indented_string_end(bytes, i + 2)?;
+                TokenKind::Str
+            }
+            b'}' if interpolation && depth == 0 => return Some((tokens, i + 1)),
+            b'{' | b'[' | b'(' => {
+                if c == b'{' {
+                    depth += 1;
+                }
+                i += 1;
+                TokenKind::Punct(c)
+            }
+            b'}' => {
+                depth = depth.saturating_sub(1);
+                i += 1;
+                TokenKind::Punct(c)
+            }
+            b'=' if next == Some(b'=') => {
+                i += 2;
+                TokenKind::Op
+            }
+            b'<' | b'>' | b'!' if next == Some(b'=') => {
+                i += 2;
+                TokenKind::Op
+            }
+            b'.' if next == Some(b'/') || next == Some(b'.') && bytes.get(i + 2) == Some(&b'/') => {
+                while i < bytes.len() && is_path(bytes[i]) {
+                    i += 1;
+                }
+                TokenKind::Other
+            }
+            b'~' if next == Some(b'/') => {
+                while i < bytes.len() && is_path(bytes[i]) {
+                    i += 1;
+                }
+                TokenKind::Other
+            }
+            b'/' if next.is_some_and(|n| n != b'/' && is_path(n)) => {
+                while i < bytes.len() && is_path(bytes[i]) {
+                    i += 1;
+                }
+                TokenKind::Other
+            }
+            b']' | b')' | b';' | b'=' | b'.' | b',' | b':' | b'?' | b'@' => {
+                i += 1;
+                TokenKind::Punct(c)
+            }
+            b'$' if next == Some(b'{') => {
+                // `${...}` in attribute names
+                let (_, end) = lex(bytes, i + 2, true)?;
+                i = end;
+                TokenKind::Other
+            }
+            c if c.is_ascii_digit() => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                    i += 1;
+                }
+                TokenKind::Other
+            }
+            c if is_ident_start(c) => {
+                while i < bytes.len() && is_ident(bytes[i]) {
+                    i += 1;
+                }
+                TokenKind::Ident
+            }
+            _ => {
+                // `-` stays alone, so that `-1` reads as a negative number.
+                i += 1;
+                while c != b'-' && i < bytes.len() && b"+*/&|<>".contains(&bytes[i]) {
+                    i += 1;
+                }
+                TokenKind::Op
+            }
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: i,
+        });
+    }
+    (!interpolation).then_some((tokens, i))
+}
+
+fn find(bytes: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    bytes[from..]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| from + p)
+}
+
+/// The end of a `"..."` string whose contents start at `i`.
+fn string_end(bytes: &[u8], mut i: usize) -> Option<usize> {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            b'$' if bytes.get(i + 1) == Some(&b'{') => i = lex(bytes, i + 2, true)?.1,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// The end of a `''...''` string whose contents start at `i`.
+fn indented_string_end(bytes: &[u8], mut i: usize) -> Option<usize> {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' if bytes.get(i + 1) == Some(&b'\'') => match bytes.get(i + 2) {
+                Some(b'\'') | Some(b'$') => i += 3,
+                Some(b'\\') => i += 4,
+                _ => return Some(i + 2),
+            },
+            b'$' if bytes.get(i + 1) == Some(&b'{') => i = lex(bytes, i + 2, true)?.1,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// For each bracket token the index of its partner, or `None` when they don't balance.
+fn partners(tokens: &[Token]) -> Option<Vec<Option<usize>>> {
+    let mut partners = vec![None; tokens.len()];
+    let mut open: Vec<usize> = Vec::new();
+    for (idx, token) in tokens.iter().enumerate() {
+        let TokenKind::Punct(c) = token.kind else {
+            continue;
+        };
+        let opening = match c {
+            b'{' | b'[' | b'(' => {
+                open.push(idx);
+                continue;
+            }
+            b'}' => b'{',
+            b']' => b'[',
+            b')' => b'(',
+            _ => continue,
+        };
+        let o = open.pop()?;
+        if !tokens[o].is(opening) {
+            return None;
+        }
+        partners[o] = Some(idx);
+        partners[idx] = Some(o);
+    }
+    open.is_empty().then_some(partners)
+}
+
+/// One `path = value;` of an attribute set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    /// The attribute path, quotes removed, e.g. `["services", "openssh", "enable"]`.
+    pub path: Vec<String>,
+    /// Offset of the binding's first token.
+    pub start: usize,
+    /// Byte range of the value, from its first token to the end of its last.
+    pub value: Range<usize>,
+    /// The value when it is an attribute set itself.
+    pub set: Option<AttrSet>,
+}
+
+/// An attribute set literal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttrSet {
+    /// Offset of the `{`.
+    pub open: usize,
+    /// Offset of the `}`.
+    pub close: usize,
+    pub bindings: Vec<Binding>,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    partners: Vec<Option<usize>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Option<Self> {
+        let tokens = tokenize(text)?;
+        let partners = partners(&tokens)?;
+        Some(Parser {
+            text,
+            tokens,
+            partners,
+        })
+    }
+
+    fn ident(&self, idx: usize, name: &str) -> bool {
+        self.tokens
+            .get(idx)
+            .is_some_and(|t| t.kind == TokenKind::Ident && t.text(self.text) == name)
+    }
+
+    /// The index of the `;` ending the expression starting at `idx`: `with e;`,
+    /// `assert e;` and `let ... in` inside it are skipped.
+    fn expression_end(&self, mut idx: usize) -> Option<usize> {
+        let mut lets = 0;
+        let mut prefixes = 0;
+        while let Some(token) = self.tokens.get(idx) {
+            match token.kind {
+                TokenKind::Punct(b'{' | b'[' | b'(') => idx = self.partners[idx]?,
+                TokenKind::Punct(b'}' | b']' | b')') => return None,
+                TokenKind::Punct(b';') if lets > 0 => {}
+                TokenKind::Punct(b';') if prefixes > 0 => prefixes -= 1,
+                TokenKind::Punct(b';') => return Some(idx),
+                TokenKind::Ident => match token.text(self.text) {
+                    "let" => lets += 1,
+                    "in" => lets = usize::saturating_sub(lets, 1),
+                    "with" | "assert" if lets == 0 => prefixes += 1,
+                    _ => {}
+                },
+                _ => {}
+            }
+            idx += 1;
+        }
+        None
+    }
+
+    /// The attribute set whose `{` is token `open`.
+    fn set(&self, open: usize) -> Option<AttrSet> {
+        let close = self.partners[open]?;
+        let mut bindings = Vec::new();
+        let mut idx = open + 1;
+        while idx < close {
+            if self.ident(idx, "inherit") {
+                idx = self.expression_end(idx)? + 1;
+                continue;
+            }
+            let start = self.tokens[idx].start;
+            let mut path = Vec::new();
+            let mut plain = true;
+            while idx < close && !self.tokens[idx].is(b'=') {
+                let token = self.tokens[idx];
+                let text = token.text(self.text);
+                match token.kind {
+                    TokenKind::Ident => path.push(text.to_string()),
+                    TokenKind::Str if text.starts_with('"') && !text.contains("${") => {
+                        path.push(text[1..text.len() - 1].to_string())
+                    }
+                    TokenKind::Punct(b'.') => {}
+                    _ => plain = false,
+                }
+                idx += 1;
+            }
+            let value_start = idx + 1;
+            let end = self.expression_end(value_start)?;
+            if end > close || value_start >= end {
+                return None;
+            }
+            let first = self.tokens[value_start];
+            let last = self.tokens[end - 1];
+            let set_open = if self.ident(value_start, "rec") {
+                value_start + 1
+            } else {
+                value_start
+            };
+            let set = (self.tokens[set_open].is(b'{') && self.partners[set_open] == Some(end - 1))
+                .then(|| self.set(set_open))
+                .flatten();
+            if plain {
+                bindings.push(Binding {
+                    path,
+                    start,
+                    value: first.start..last.end,
+                    set,
+                });
+            }
+            idx = end + 1;
+        }
+        Some(AttrSet {
+            open: self.tokens[open].start,
+            close: self.tokens[close].start,
+            bindings,
+        })
+    }
+}
+
+/// The attribute set a module evaluates to: the one closed by the file's last `}`, as
+/// in `{ config, pkgs, ... }: { ... }` or `args: let ... in { ... }`. `None` when the
+/// file doesn't end in a set or isn't valid Nix as far as this lexer can tell.
+pub fn module_body(text: &str) -> Option<AttrSet> {
+    let parser = Parser::new(text)?;
+    let last = parser.tokens.len().checked_sub(1)?;
+    if !parser.tokens[last].is(b'}') {
+        return None;
+    }
+    parser.set(parser.partners[last]?)
+}
+
+/// `value` as a Nix string literal: quotes, backslashes, `${` and control characters are
+/// escaped, so it is read verbatim whatever a package name, URL or value holds.
+pub fn nix_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+/// Split `line` into code and a trailing `#` comment outside `"..."` strings.
+pub fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut in_string = false;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' if prev != '\\' => in_string = !in_string,
+            '#' if !in_string => return (line[..i].trim_end(), Some(&line[i..])),
+            _ => {}
+        }
+        prev = c;
+    }
+    (line.trim_end(), None)
+}
+
+/// Whether `value` is one complete expression that can stand as `name = value;`.
+pub fn is_expression(value: &str) -> bool {
+    let wrapped = format!("{{ x = {}; }}", value);
+    module_body(&wrapped).is_some_and(|set| {
+        set.bindings.len() == 1 && set.bindings[0].path == ["x"] && set.close == wrapped.len() - 1
+    })
+}
+
+/// Where `path` is assigned in `set`, or the innermost set literal it would go into
+/// and the part of the path left to write there.
+pub enum Place<'a> {
+    Assigned(&'a Binding),
+    Missing {
+        set: &'a AttrSet,
+        rest: &'a [String],
+    },
+}
+
+/// Look `path` up in `set`, following nested set literals like `services = { ... };`.
+pub fn find_place<'a>(set: &'a AttrSet, path: &'a [String]) -> Place<'a> {
+    if let Some(binding) = set.bindings.iter().find(|b| b.path == path) {
+        return Place::Assigned(binding);
+    }
+    for binding in &set.bindings {
+        if binding.path.len() < path.len()
+            && path.starts_with(&binding.path)
+            && let Some(inner) = &binding.set
+        {
+            return find_place(inner, &path[binding.path.len()..]);
+        }
+    }
+    Place::Missing { set, rest: path }
+}
+
+/// `text` with `binding` (like `ports = [ 22 ];`) added to `set`: on its own line before
+/// the closing brace, indented like the set's last binding, or inline when the brace
+/// shares its line with other code.
+pub fn insert_binding(text: &str, set: &AttrSet, binding: &str) -> String {
+    let line_start = text[..set.close].rfind('\n').map_or(0, |i| i + 1);
+    let before_close = &text[line_start..set.close];
+    if !before_close.trim().is_empty() || !text[set.open..set.close].contains('\n') {
+        let head = text[..set.close].trim_end();
+        return format!("{} {} {}", head, binding, &text[set.close..]);
+    }
+    let indent_of = |at: usize| -> String {
+        let start = text[..at].rfind('\n').map_or(0, |i| i + 1);
+        text[start..]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect()
+    };
+    let indent = match set.bindings.last() {
+        Some(last) => indent_of(last.start),
+        None => format!("{}  ", before_close),
+    };
+    format!(
+        "{}{}{}\n{}",
+        &text[..line_start],
+        indent,
+        binding,
+        &text[line_start..]
+    )
+}
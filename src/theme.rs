@@ -0,0 +1,155 @@
+//! Colours of declair's output: when to use them (`--color`, `NO_COLOR`) and which ones
+//! (the `[theme]` config table).
+
+use clap::ValueEnum;
+use console::{Style, StyledObject};
+use dialoguer::theme::{ColorfulTheme, SimpleTheme};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// When to colour output.
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Colour when writing to a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to colour a stream, given whether `NO_COLOR` is set and the stream is a terminal.
+    pub fn enabled(self, no_color: bool, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => !no_color && is_terminal,
+        }
+    }
+}
+
+/// Style of each kind of output, written as `console` style names joined by dots,
+/// e.g. `"green"`, `"cyan.bold"` or `"#ff8800"`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Theme {
+    /// Package names in tables and prompts.
+    pub package: String,
+    pub version: String,
+    /// Table column headers.
+    pub header: String,
+    /// Added lines of diffs.
+    pub added: String,
+    /// Removed lines of diffs.
+    pub removed: String,
+    /// Diff hunk headers.
+    pub hunk: String,
+    pub error: String,
+    pub warning: String,
+    pub success: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            package: "cyan".to_string(),
+            version: "green".to_string(),
+            header: "bold".to_string(),
+            added: "green".to_string(),
+            removed: "red".to_string(),
+            hunk: "cyan".to_string(),
+            error: "red.bold".to_string(),
+            warning: "yellow".to_string(),
+            success: "green".to_string(),
+        }
+    }
+}
+
+impl Theme {
+    pub fn package<D>(&self, text: D) -> StyledObject<D> {
+        Style::from_dotted_str(&self.package).apply_to(text)
+    }
+
+    pub fn version<D>(&self, text: D) -> StyledObject<D> {
+        Style::from_dotted_str(&self.version).apply_to(text)
+    }
+
+    pub fn header<D>(&self, text: D) -> StyledObject<D> {
+        Style::from_dotted_str(&self.header).apply_to(text)
+    }
+
+    pub fn added<D>(&self, text: D) -> StyledObject<D> {
+        Style::from_dotted_str(&self.added).apply_to(text)
+    }
+
+    pub fn removed<D>(&self, text: D) -> StyledObject<D> {
+        Style::from_dotted_str(&self.removed).apply_to(text)
+    }
+
+    pub fn hunk<D>(&self, text: D) -> StyledObject<D> {
+        Style::from_dotted_str(&self.hunk).apply_to(text)
+    }
+
+    pub fn error<D>(&self, text: D) -> StyledObject<D> {
+        Style::from_dotted_str(&self.error).apply_to(text)
+    }
+
+    pub fn warning<D>(&self, text: D) -> StyledObject<D> {
+        Style::from_dotted_str(&self.warning).apply_to(text)
+    }
+
+    pub fn success<D>(&self, text: D) -> StyledObject<D> {
+        Style::from_dotted_str(&self.success).apply_to(text)
+    }
+
+    /// Style a unified diff line by line: additions, removals and hunk headers.
+    pub fn diff(&self, diff: &str) -> String {
+        diff.lines()
+            .map(|line| match line.chars().next() {
+                Some('+') if !line.starts_with("+++") => self.added(line).to_string(),
+                Some('-') if !line.starts_with("---") => self.removed(line).to_string(),
+                Some('@') => self.hunk(line).to_string(),
+                _ => line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Prompt theme using these colours.
+    fn prompts(&self) -> ColorfulTheme {
+        let for_stderr = |spec: &str| Style::from_dotted_str(spec).for_stderr();
+        ColorfulTheme {
+            values_style: for_stderr(&self.package),
+            active_item_style: for_stderr(&self.package),
+            error_style: for_stderr(&self.error),
+            ..ColorfulTheme::default()
+        }
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+static PROMPTS: OnceLock<ColorfulTheme> = OnceLock::new();
+
+/// Decide whether to colour stdout and stderr and select the theme for the rest of the
+/// process. Only the first call selects the theme.
+pub fn init(choice: ColorChoice, theme: Theme) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    console::set_colors_enabled(choice.enabled(no_color, console::user_attended()));
+    console::set_colors_enabled_stderr(choice.enabled(no_color, console::user_attended_stderr()));
+    let _ = THEME.set(theme);
+}
+
+/// The selected theme, the default one if none was selected.
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+/// Theme for dialoguer prompts: coloured when stderr is, plain otherwise.
+pub fn prompt_theme() -> &'static dyn dialoguer::theme::Theme {
+    if console::colors_enabled_stderr() {
+        PROMPTS.get_or_init(|| theme().prompts())
+    } else {
+        &SimpleTheme
+    }
+}
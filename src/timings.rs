@@ -0,0 +1,106 @@
+//! Where the time of a run went: each phase (search, edit, format, write, rebuild, ...)
+//! is recorded as it finishes, for the summary of the legacy flow and `--timings`.
+
+use crate::progress::spinner;
+use indicatif::ProgressStyle;
+use serde_json::{Value, json};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static RUN: Mutex<Timings> = Mutex::new(Timings::new());
+
+/// Elapsed time of each phase (search, edit, rebuild, ...) of a run.
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    phases: Vec<(String, Duration)>,
+}
+
+/// Record that the phase `name` of this run took `elapsed`.
+pub fn record(name: &str, elapsed: Duration) {
+    if let Ok(mut run) = RUN.lock() {
+        run.record(name, elapsed);
+    }
+}
+
+/// Run `f` and record how long it took as the phase `name` of this run.
+pub fn time<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(name, start.elapsed());
+    result
+}
+
+/// Like [`time`], with a bar of its own for the phase: a spinner with `message` while `f`
+/// runs, then the phase's name and how long it took, so the run's phases stay listed.
+/// Only use it for phases whose child process output is captured.
+pub fn time_with_spinner<T>(name: &str, message: &str, f: impl FnOnce() -> T) -> T {
+    let bar = spinner(message);
+    bar.set_style(
+        ProgressStyle::with_template("{prefix:>12} {spinner} {msg} [{elapsed}]")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    bar.set_prefix(name.to_string());
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    record(name, elapsed);
+    bar.set_style(
+        ProgressStyle::with_template("{prefix:>12} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    bar.finish_with_message(format_duration(elapsed));
+    result
+}
+
+/// The phases recorded so far this run.
+pub fn run_timings() -> Timings {
+    RUN.lock().map(|run| run.clone()).unwrap_or_default()
+}
+
+impl Timings {
+    pub const fn new() -> Self {
+        Timings { phases: Vec::new() }
+    }
+
+    pub fn record(&mut self, name: &str, elapsed: Duration) {
+        self.phases.push((name.to_string(), elapsed));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.phases.is_empty()
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|(_, d)| *d).sum()
+    }
+
+    /// Human-readable summary, e.g. `search 3.2s, edit 0.0s, rebuild 4m12s`.
+    pub fn summary(&self) -> String {
+        self.phases
+            .iter()
+            .map(|(name, d)| format!("{} {}", name, format_duration(*d)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn to_json(&self) -> Value {
+        let phases: Vec<Value> = self
+            .phases
+            .iter()
+            .map(|(name, d)| json!({ "phase": name, "seconds": d.as_secs_f64() }))
+            .collect();
+        json!({ "phases": phases, "total_seconds": self.total().as_secs_f64() })
+    }
+}
+
+/// Format a duration compactly: `0.4s`, `3.2s`, `4m12s`, `1h03m`.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{:.1}s", d.as_secs_f64())
+    }
+}
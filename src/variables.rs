@@ -0,0 +1,265 @@
+//! Environment variables set in a config file, for `declair env`: the
+//! `environment.variables = { ... };` set of a NixOS module, `home.sessionVariables` of a
+//! Home Manager one, or single `environment.variables.<NAME> = ...;` assignments.
+
+use crate::edit::Edit;
+use crate::error::{DeclairError, Result};
+use crate::module::ModuleKind;
+use crate::syntax::{nix_string, split_comment};
+use crate::t;
+use std::path::Path;
+
+/// One variable of the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    /// The value as written, e.g. `"vim"` or `"${pkgs.vim}/bin/vim"`.
+    pub value: String,
+    /// 1-based line of the assignment.
+    pub line: usize,
+}
+
+/// Attribute holding the variables in `kind`'s modules.
+pub fn variables_attr(kind: ModuleKind) -> &'static str {
+    match kind {
+        ModuleKind::Nixos => "environment.variables",
+        ModuleKind::HomeManager => "home.sessionVariables",
+    }
+}
+
+/// Check that `name` is a shell variable name, which also makes it a valid attribute
+/// name.
+pub fn validate_variable(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(DeclairError::Config(t!("env.invalid_name", name)));
+    }
+    Ok(())
+}
+
+/// Split `code` at the `;`s outside string literals; the part after the last one is
+/// dropped.
+fn split_statements(code: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut in_string = false;
+    let mut prev = ' ';
+    let mut start = 0;
+    for (i, c) in code.char_indices() {
+        match c {
+            '"' if prev != '\\' => in_string = !in_string,
+            ';' if !in_string => {
+                statements.push(code[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    statements
+}
+
+/// `NAME = value` as a name (unquoted) and value.
+fn assignment(statement: &str) -> Option<(String, String)> {
+    let (name, value) = statement.split_once('=')?;
+    let name = name.trim().trim_matches('"');
+    if name.is_empty() || name.contains(['.', ' ']) {
+        return None;
+    }
+    Some((name.to_string(), value.trim().to_string()))
+}
+
+/// Where the set assigned to `attr` is: its first and last line (equal for a single-line
+/// set).
+fn find_set(lines: &[String], attr: &str) -> Option<(usize, usize)> {
+    let start = lines.iter().position(|l| {
+        let (code, _) = split_comment(l.trim());
+        code.split_once('=')
+            .is_some_and(|(lhs, rhs)| lhs.trim() == attr && rhs.trim_start().starts_with('{'))
+    })?;
+    let (code, _) = split_comment(&lines[start]);
+    if code.contains('}') {
+        return Some((start, start));
+    }
+    let end = lines[start + 1..]
+        .iter()
+        .position(|l| l.trim_start().starts_with('}'))?;
+    Some((start, start + 1 + end))
+}
+
+fn parse(lines: &[String], attr: &str) -> Vec<Variable> {
+    let mut variables = Vec::new();
+    let set = find_set(lines, attr);
+    if let Some((start, end)) = set {
+        if start == end {
+            let (code, _) = split_comment(&lines[start]);
+            let inner = code
+                .find('{')
+                .and_then(|open| Some(&code[open + 1..code.rfind('}')?]))
+                .unwrap_or("");
+            variables.extend(split_statements(inner).into_iter().filter_map(|s| {
+                let (name, value) = assignment(s)?;
+                Some(Variable {
+                    name,
+                    value,
+                    line: start + 1,
+                })
+            }));
+        } else {
+            for (idx, line) in lines.iter().enumerate().take(end).skip(start + 1) {
+                let (code, _) = split_comment(line.trim());
+                if let Some(statement) = split_statements(code).first()
+                    && let Some((name, value)) = assignment(statement)
+                {
+                    variables.push(Variable {
+                        name,
+                        value,
+                        line: idx + 1,
+                    });
+                }
+            }
+        }
+    }
+    // `environment.variables.EDITOR = "vim";` anywhere else in the file
+    let prefix = format!("{}.", attr);
+    for (idx, line) in lines.iter().enumerate() {
+        let (code, _) = split_comment(line.trim());
+        if let Some(rest) = code.strip_prefix(&prefix)
+            && let Some(statement) = split_statements(rest).first()
+            && let Some((name, value)) = assignment(statement)
+        {
+            variables.push(Variable {
+                name,
+                value,
+                line: idx + 1,
+            });
+        }
+    }
+    variables.sort_by_key(|v| v.line);
+    variables
+}
+
+/// The variables `file_path` sets for a module of `kind`.
+pub fn list_variables(file_path: &Path, kind: ModuleKind) -> Result<Vec<Variable>> {
+    let edit = Edit::begin(file_path)?;
+    Ok(parse(&edit.lines(), variables_attr(kind)))
+}
+
+/// Set `name` to the string `value`. An existing assignment gets the new value in place;
+/// otherwise the variable is added to the set, which is created before the file's closing
+/// brace when there is none. A single-line set is rewritten as a multi-line one. Returns
+/// the previous value, as written.
+pub fn set_variable(
+    file_path: &Path,
+    kind: ModuleKind,
+    name: &str,
+    value: &str,
+) -> Result<Option<String>> {
+    validate_variable(name)?;
+    let attr = variables_attr(kind);
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+    edit.backup()?;
+    let value = nix_string(value);
+    let indent_of = |l: &str| -> String { l.chars().take_while(|c| c.is_whitespace()).collect() };
+    let variables = parse(&lines, attr);
+    let single_line = find_set(&lines, attr)
+        .filter(|(start, end)| start == end)
+        .map(|(start, _)| start + 1);
+
+    if let Some(existing) = variables.iter().find(|v| v.name == name)
+        && Some(existing.line) != single_line
+    {
+        // one assignment on its line: swap the value, keeping the comment
+        let idx = existing.line - 1;
+        let line = &lines[idx];
+        let (code, comment) = split_comment(line);
+        let eq = code.find('=').unwrap_or(code.len());
+        let comment = comment.map(|c| format!(" {}", c)).unwrap_or_default();
+        lines[idx] = format!("{}= {};{}", &code[..eq], value, comment);
+        edit.commit(&lines.join("\n"))?;
+        return Ok(Some(existing.value.clone()));
+    }
+
+    let previous = variables
+        .iter()
+        .find(|v| v.name == name)
+        .map(|v| v.value.clone());
+    match find_set(&lines, attr) {
+        Some((start, end)) if start == end => {
+            let outer = indent_of(&lines[start]);
+            let line = &lines[start];
+            let head = line[..line.find('{').unwrap_or(line.len())].trim_end();
+            let tail = line[line.rfind('}').map_or(line.len(), |i| i + 1)..].to_string();
+            let mut block = vec![format!("{} {{", head)];
+            block.extend(
+                variables
+                    .iter()
+                    .filter(|v| v.line == start + 1 && v.name != name)
+                    .map(|v| format!("{}  {} = {};", outer, v.name, v.value)),
+            );
+            block.push(format!("{}  {} = {};", outer, name, value));
+            block.push(format!("{}}}{}", outer, tail));
+            lines.splice(start..=start, block);
+        }
+        Some((start, end)) => {
+            let indent = if end - start > 1 {
+                indent_of(&lines[end - 1])
+            } else {
+                format!("{}  ", indent_of(&lines[end]))
+            };
+            lines.insert(end, format!("{}{} = {};", indent, name, value));
+        }
+        None => {
+            let close_idx = lines
+                .iter()
+                .rposition(|l| l.trim_start().starts_with('}'))
+                .ok_or_else(|| DeclairError::NoInsertionPoint {
+                    file: file_path.to_path_buf(),
+                })?;
+            let outer = indent_of(&lines[close_idx]);
+            let block = [
+                format!("{}  {} = {{", outer, attr),
+                format!("{}    {} = {};", outer, name, value),
+                format!("{}  }};", outer),
+            ];
+            lines.splice(close_idx..close_idx, block);
+        }
+    }
+    edit.commit(&lines.join("\n"))?;
+    Ok(previous)
+}
+
+/// Remove the assignment of `name`.
+pub fn unset_variable(file_path: &Path, kind: ModuleKind, name: &str) -> Result<()> {
+    let attr = variables_attr(kind);
+    let edit = Edit::begin(file_path)?;
+    let mut lines = edit.lines();
+    let variables = parse(&lines, attr);
+    let variable = variables.iter().find(|v| v.name == name).ok_or_else(|| {
+        DeclairError::VariableNotFound {
+            name: name.to_string(),
+            file: file_path.to_path_buf(),
+        }
+    })?;
+    edit.backup()?;
+    let idx = variable.line - 1;
+    if find_set(&lines, attr).is_some_and(|(start, end)| start == end && start == idx) {
+        // An entry of a single-line set.
+        let line = &lines[idx];
+        let open = line.find('{').unwrap_or(0);
+        let close = line.rfind('}').unwrap_or(line.len());
+        let rest: String = variables
+            .iter()
+            .filter(|v| v.line == variable.line && v.name != name)
+            .map(|v| format!("{} = {}; ", v.name, v.value))
+            .collect();
+        lines[idx] = format!("{}{{ {}}}{}", &line[..open], rest, &line[close + 1..]);
+    } else {
+        lines.remove(idx);
+    }
+    edit.commit(&lines.join("\n"))
+}
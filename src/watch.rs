@@ -0,0 +1,91 @@
+use crate::error::{DeclairError, Result};
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Packages added to and removed from a package list between two readings.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PackageDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl PackageDiff {
+    pub fn between(before: &[String], after: &[String]) -> Self {
+        PackageDiff {
+            added: after
+                .iter()
+                .filter(|p| !before.contains(p))
+                .cloned()
+                .collect(),
+            removed: before
+                .iter()
+                .filter(|p| !after.contains(p))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// One-line summary such as `+ripgrep +fd -htop`.
+    pub fn summary(&self) -> String {
+        self.added
+            .iter()
+            .map(|p| format!("+{}", p))
+            .chain(self.removed.iter().map(|p| format!("-{}", p)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Watch `files` (absolute paths) and call `on_change` with the files that changed, once no further change
+/// arrived for `debounce`. Runs until `on_change` fails or the watcher shuts down.
+///
+/// The parent directories are watched rather than the files themselves, since most
+/// editors save by writing a new file and renaming it over the old one.
+pub fn watch_files(
+    files: &[PathBuf],
+    debounce: Duration,
+    mut on_change: impl FnMut(&[PathBuf]) -> Result<()>,
+) -> Result<()> {
+    let watch_error = |e: notify::Error| DeclairError::Watch(e.to_string());
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(watch_error)?;
+    let dirs: HashSet<&Path> = files.iter().filter_map(|f| f.parent()).collect();
+    for dir in dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+    }
+
+    let matching = |event: notify::Result<notify::Event>| -> Vec<PathBuf> {
+        let Ok(event) = event else {
+            return Vec::new();
+        };
+        event
+            .paths
+            .into_iter()
+            .filter(|p| files.contains(p))
+            .collect()
+    };
+
+    while let Ok(event) = rx.recv() {
+        let mut changed = matching(event);
+        if changed.is_empty() {
+            continue;
+        }
+        // Collect the burst of events a single save produces.
+        while let Ok(event) = rx.recv_timeout(debounce) {
+            changed.extend(matching(event));
+        }
+        changed.sort();
+        changed.dedup();
+        on_change(&changed)?;
+    }
+    Ok(())
+}
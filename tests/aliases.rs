@@ -0,0 +1,94 @@
+use declair_rs::aliases::{Alias, Aliases, nixpkgs_dir, parse_aliases};
+use std::fs;
+
+const ALIASES: &str = r#"
+lib: self: super:
+
+mapAliases {
+  # renamed
+  du-dust = dust; # Added 2025-03-08
+  vscode-fhs = vscode.fhs; # Added 2023-04-26
+  exa = throw "'exa' has been removed because it is unmaintained upstream. Consider using 'eza', a maintained fork"; # Added 2023-09-07
+  teams = throw "'teams' has been removed as Microsoft no longer maintains the Linux client"; # Added 2023-12-29
+  "7z2hashcat" = throw "'7z2hashcat' has been renamed"; # not an attribute name
+  gnome-passwordsafe = lib.warnOnInstantiate "renamed" gnome-secrets; # not understood
+  inherit (libsForQt5.mauiPackages) index;
+}
+"#;
+
+#[test]
+fn renames_and_removals_are_parsed() {
+    let aliases = parse_aliases(ALIASES);
+    assert_eq!(
+        aliases.get("du-dust"),
+        Some(&Alias::Renamed {
+            new: "dust".to_string()
+        })
+    );
+    assert_eq!(
+        aliases.get("vscode-fhs").and_then(Alias::replacement),
+        Some("vscode.fhs")
+    );
+    assert_eq!(aliases.get("exa").and_then(Alias::replacement), Some("eza"));
+    match aliases.get("teams") {
+        Some(Alias::Removed {
+            message,
+            suggestion,
+        }) => {
+            assert!(message.starts_with("'teams' has been removed"));
+            assert_eq!(*suggestion, None);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(aliases.get("7z2hashcat"), None);
+    assert_eq!(aliases.get("gnome-passwordsafe"), None);
+    assert_eq!(aliases.get("index"), None);
+    assert_eq!(aliases.get("ripgrep"), None);
+}
+
+#[test]
+fn bundled_aliases_cover_common_migrations() {
+    let aliases = Aliases::bundled();
+    assert_eq!(aliases.get("exa").and_then(Alias::replacement), Some("eza"));
+    assert_eq!(
+        aliases.get("nodejs_18").and_then(Alias::replacement),
+        Some("nodejs_20")
+    );
+    assert!(matches!(
+        aliases.get("nerdfonts"),
+        Some(Alias::Removed {
+            suggestion: None,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn local_nixpkgs_aliases_are_preferred() {
+    let dir = tempfile::tempdir().unwrap();
+    let nixpkgs = dir.path().join("nixpkgs");
+    fs::create_dir_all(nixpkgs.join("pkgs/top-level")).unwrap();
+    fs::write(
+        nixpkgs.join("pkgs/top-level/aliases.nix"),
+        "mapAliases {\n  foo = bar;\n}\n",
+    )
+    .unwrap();
+
+    let nix_path = format!("nixpkgs={}", nixpkgs.display());
+    assert_eq!(nixpkgs_dir(&nix_path), Some(nixpkgs.clone()));
+    assert_eq!(
+        nixpkgs_dir(&dir.path().display().to_string()),
+        Some(nixpkgs.clone())
+    );
+    assert_eq!(
+        nixpkgs_dir("nixos-config=/etc/nixos/configuration.nix"),
+        None
+    );
+
+    let local = Aliases::load(Some(&nixpkgs));
+    assert_eq!(local.get("foo").and_then(Alias::replacement), Some("bar"));
+    assert_eq!(local.get("exa"), None);
+
+    let fallback = Aliases::load(Some(dir.path()));
+    assert_eq!(fallback, Aliases::bundled());
+}
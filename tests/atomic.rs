@@ -0,0 +1,49 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::copy_fixture;
+use declair_rs::atomic::write_atomic;
+use declair_rs::edit::add_package_to_nix;
+use declair_rs::module::ModuleKind;
+use std::fs;
+use std::os::unix::fs::{PermissionsExt, symlink};
+
+#[test]
+fn replaces_contents_without_leaving_temp_files() {
+    let (dir, file) = copy_fixture("multiline.nix");
+    write_atomic(&file, b"{ }").unwrap();
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), "{ }");
+    let names: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["multiline.nix"]);
+}
+
+#[test]
+fn preserves_permissions() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+    add_package_to_nix(&file, "ripgrep", ModuleKind::Nixos).unwrap();
+
+    let mode = fs::metadata(&file).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+}
+
+#[test]
+fn writes_through_symlinks() {
+    let (dir, file) = copy_fixture("multiline.nix");
+    let link = dir.path().join("configuration.nix");
+    symlink(&file, &link).unwrap();
+    write_atomic(&link, b"{ }").unwrap();
+
+    assert!(
+        fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), "{ }");
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::audit::{
+    Advisory, Nixpkgs, Severity, audit_packages, derivations_command, parse_vulnix,
+};
+use declair_rs::error::DeclairError;
+
+const EVAL_JSON: &str = r#"{
+  "openssl_1_1": "/nix/store/aaa-openssl-1.1.1w.drv",
+  "ripgrep": "/nix/store/bbb-ripgrep-14.1.0.drv",
+  "unfree-thing": null
+}"#;
+
+const VULNIX_JSON: &str = r#"[
+  {
+    "name": "openssl-1.1.1w",
+    "pname": "openssl",
+    "version": "1.1.1w",
+    "derivation": "/nix/store/aaa-openssl-1.1.1w.drv",
+    "affected_by": ["CVE-2023-5678", "CVE-2024-0727", "CVE-2023-6129"],
+    "whitelisted": [],
+    "cvssv3_basescore": { "CVE-2023-5678": 5.3, "CVE-2023-6129": 6.5 },
+    "description": {}
+  },
+  {
+    "name": "left-pad-1.0",
+    "derivation": "/nix/store/ccc-left-pad-1.0.drv",
+    "affected_by": []
+  }
+]"#;
+
+fn packages() -> Vec<String> {
+    ["openssl_1_1", "ripgrep", "unfree-thing"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// Answers `nix eval` with [`EVAL_JSON`]; how `vulnix` answers is up to the test.
+fn audit_runner() -> FakeRunner {
+    FakeRunner::new().answer("nix", &[], 0, EVAL_JSON)
+}
+
+#[test]
+fn severity_follows_cvss_ratings() {
+    assert_eq!(Severity::from_score(None), Severity::Unknown);
+    assert_eq!(Severity::from_score(Some(3.9)), Severity::Low);
+    assert_eq!(Severity::from_score(Some(4.0)), Severity::Medium);
+    assert_eq!(Severity::from_score(Some(7.5)), Severity::High);
+    assert_eq!(Severity::from_score(Some(9.8)), Severity::Critical);
+    assert!(Severity::Critical > Severity::Unknown);
+}
+
+#[test]
+fn vulnix_output_is_parsed_worst_first() {
+    let findings = parse_vulnix(VULNIX_JSON.as_bytes()).unwrap();
+    assert_eq!(findings.len(), 1);
+    let (name, advisories) = &findings["/nix/store/aaa-openssl-1.1.1w.drv"];
+    assert_eq!(name, "openssl-1.1.1w");
+    let shown: Vec<String> = advisories.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        shown,
+        [
+            "CVE-2023-6129 (6.5)",
+            "CVE-2023-5678 (5.3)",
+            "CVE-2024-0727"
+        ]
+    );
+    assert!(parse_vulnix(b"not json").is_err());
+}
+
+#[test]
+fn findings_name_the_configured_attribute() {
+    let runner = audit_runner().answer("vulnix", &[], 2, VULNIX_JSON);
+    let nixpkgs = Nixpkgs::Flake("github:NixOS/nixpkgs/abc123".to_string());
+    let findings = audit_packages(&runner, &nixpkgs, &packages()).unwrap();
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].package, "openssl_1_1");
+    assert_eq!(findings[0].severity(), Severity::Medium);
+    assert_eq!(
+        findings[0].advisories[0],
+        Advisory {
+            id: "CVE-2023-6129".to_string(),
+            score: Some(6.5)
+        }
+    );
+
+    let clean = audit_runner().answer("vulnix", &[], 0, "[]");
+    assert!(
+        audit_packages(&clean, &nixpkgs, &packages())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn missing_vulnix_and_failures_are_errors() {
+    let missing = audit_runner().missing("vulnix");
+    let err = audit_packages(&missing, &Nixpkgs::NixPath, &packages()).unwrap_err();
+    assert!(matches!(err, DeclairError::VulnixMissing));
+
+    let failing = audit_runner().answer("vulnix", &[], 1, "");
+    let err = audit_packages(&failing, &Nixpkgs::NixPath, &packages()).unwrap_err();
+    assert!(matches!(err, DeclairError::Audit(_)));
+}
+
+#[test]
+fn derivations_are_evaluated_in_the_pinned_nixpkgs() {
+    let cmd = derivations_command(
+        &Nixpkgs::Flake("github:NixOS/nixpkgs/abc123".to_string()),
+        &packages(),
+    );
+    let expr = &cmd.args[cmd.args.iter().position(|a| a == "--expr").unwrap() + 1];
+    assert!(expr.contains("builtins.getFlake \"github:NixOS/nixpkgs/abc123\""));
+    assert!(expr.contains("[ \"openssl_1_1\" \"ripgrep\" \"unfree-thing\" ]"));
+
+    let cmd = derivations_command(&Nixpkgs::NixPath, &packages());
+    assert!(cmd.args.iter().any(|a| a.contains("import <nixpkgs> { }")));
+}
@@ -0,0 +1,125 @@
+use declair_rs::backups::{BackupSettings, find_backup, list_backups, prune_backups, record};
+use declair_rs::config::Config;
+use declair_rs::error::DeclairError;
+use std::fs;
+
+const DAY: u64 = 24 * 60 * 60;
+
+#[test]
+fn records_are_listed_oldest_first() {
+    let history = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(&file, "{ }").unwrap();
+
+    let first = record(history.path(), &file, b"{ a }", 1000).unwrap();
+    let second = record(history.path(), &file, b"{ b }", 1000).unwrap();
+    let third = record(history.path(), &file, b"{ c }", 900).unwrap();
+    assert_eq!(first.id, "1000-1");
+    assert_eq!(second.id, "1000-2");
+    assert_eq!(third.id, "900-1");
+
+    let entries = list_backups(history.path()).unwrap();
+    let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, ["900-1", "1000-1", "1000-2"]);
+    assert_eq!(entries[0].file, fs::canonicalize(&file).unwrap());
+    assert_eq!(entries[0].contents().unwrap(), "{ c }");
+    assert_eq!(entries[0].size(), 5);
+
+    let found = find_backup(history.path(), "1000-2").unwrap();
+    assert_eq!(found.contents().unwrap(), "{ b }");
+}
+
+#[test]
+fn a_missing_history_is_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(
+        list_backups(&dir.path().join("history"))
+            .unwrap()
+            .is_empty()
+    );
+    let err = find_backup(&dir.path().join("history"), "1-1").unwrap_err();
+    assert!(matches!(err, DeclairError::BackupNotFound { .. }));
+    assert_eq!(err.exit_code(), 6);
+}
+
+#[test]
+fn pruning_keeps_the_newest_backups_of_each_file() {
+    let history = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let system = dir.path().join("configuration.nix");
+    let home = dir.path().join("home.nix");
+    for path in [&system, &home] {
+        fs::write(path, "{ }").unwrap();
+    }
+    for at in [100, 200, 300] {
+        record(history.path(), &system, b"{ }", at).unwrap();
+    }
+    record(history.path(), &home, b"{ }", 100).unwrap();
+
+    let settings = BackupSettings {
+        max_backups: 2,
+        max_age_days: 0,
+    };
+    let removed = prune_backups(history.path(), &settings, 400).unwrap();
+    let removed: Vec<&str> = removed.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(removed, ["100-1"]);
+    let left: Vec<String> = list_backups(history.path())
+        .unwrap()
+        .into_iter()
+        .map(|e| format!("{} {}", e.id, e.file.file_name().unwrap().to_string_lossy()))
+        .collect();
+    assert_eq!(
+        left,
+        [
+            "100-2 home.nix",
+            "200-1 configuration.nix",
+            "300-1 configuration.nix"
+        ]
+    );
+}
+
+#[test]
+fn pruning_removes_old_backups() {
+    let history = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(&file, "{ }").unwrap();
+    record(history.path(), &file, b"{ }", 0).unwrap();
+    record(history.path(), &file, b"{ }", 10 * DAY).unwrap();
+
+    let settings = BackupSettings {
+        max_backups: 0,
+        max_age_days: 5,
+    };
+    let removed = prune_backups(history.path(), &settings, 12 * DAY).unwrap();
+    assert_eq!(removed.len(), 1);
+    assert_eq!(removed[0].at, 0);
+    assert_eq!(list_backups(history.path()).unwrap().len(), 1);
+
+    // Without limits everything stays.
+    let settings = BackupSettings {
+        max_backups: 0,
+        max_age_days: 0,
+    };
+    assert!(
+        prune_backups(history.path(), &settings, 1000 * DAY)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn retention_is_read_from_the_config() {
+    let config: Config = toml::from_str(
+        "nix_path = \"/etc/nixos/configuration.nix\"\n\
+         auto_rebuild = false\nhome_manager = false\nflake = false\n\
+         [backups]\nmax_backups = 5\n",
+    )
+    .unwrap();
+    assert_eq!(config.backups.max_backups, 5);
+    assert_eq!(
+        config.backups.max_age_days,
+        BackupSettings::default().max_age_days
+    );
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::channel::{
+    add_channel, channel_update_command, list_channels, nix_channel, nix_path_has_nixpkgs,
+    parse_channels, update_channels,
+};
+use declair_rs::error::DeclairError;
+use declair_rs::module::ModuleKind;
+use declair_rs::search::{NixpkgsSource, nix_search_command};
+use std::fs;
+
+#[test]
+fn channels_are_parsed_from_nix_channel_list() {
+    let channels = parse_channels(
+        "home-manager https://github.com/nix-community/home-manager/archive/master.tar.gz\n\
+         nixos https://nixos.org/channels/nixos-24.05\n\n",
+    );
+    let pairs: Vec<(&str, &str)> = channels
+        .iter()
+        .map(|c| (c.name.as_str(), c.url.as_str()))
+        .collect();
+    assert_eq!(
+        pairs,
+        [
+            (
+                "home-manager",
+                "https://github.com/nix-community/home-manager/archive/master.tar.gz"
+            ),
+            ("nixos", "https://nixos.org/channels/nixos-24.05"),
+        ]
+    );
+}
+
+#[test]
+fn nixos_channels_are_managed_as_root() {
+    let cmd = nix_channel(ModuleKind::Nixos, ["--list"]);
+    assert_eq!(cmd.program, "sudo");
+    assert_eq!(cmd.args, ["nix-channel", "--list"]);
+    let cmd = nix_channel(ModuleKind::HomeManager, ["--list"]);
+    assert_eq!(cmd.program, "nix-channel");
+    assert_eq!(cmd.args, ["--list"]);
+}
+
+#[test]
+fn list_and_add_run_nix_channel() {
+    let runner = FakeRunner::new().answer(
+        "nix-channel",
+        &[],
+        0,
+        "nixos https://nixos.org/channels/nixos-unstable\n",
+    );
+    let channels = list_channels(&runner, ModuleKind::HomeManager).unwrap();
+    assert_eq!(channels[0].name, "nixos");
+
+    add_channel(
+        &runner,
+        ModuleKind::HomeManager,
+        "https://nixos.org/channels/nixos-24.05",
+        Some("stable"),
+    )
+    .unwrap();
+    assert_eq!(
+        runner.calls.borrow()[1].args,
+        ["--add", "https://nixos.org/channels/nixos-24.05", "stable"]
+    );
+}
+
+#[test]
+fn failing_nix_channel_is_reported() {
+    let runner = FakeRunner::new().answer("sudo", &[], 1, "");
+    let err = list_channels(&runner, ModuleKind::Nixos).unwrap_err();
+    assert!(matches!(err, DeclairError::Channel(_)));
+    let err = update_channels(&runner, ModuleKind::Nixos, &[]).unwrap_err();
+    assert!(matches!(err, DeclairError::UpdateFailed { .. }));
+    assert_eq!(err.exit_code(), 8);
+}
+
+#[test]
+fn update_names_channels() {
+    let cmd = channel_update_command(ModuleKind::HomeManager, &["nixos".to_string()]);
+    assert_eq!(cmd.args, ["--update", "nixos"]);
+}
+
+#[test]
+fn nixpkgs_is_found_in_nix_path() {
+    assert!(nix_path_has_nixpkgs(
+        "nixpkgs=/nix/var/nix/profiles/per-user/root/channels/nixos"
+    ));
+    assert!(nix_path_has_nixpkgs(
+        "nixos-config=/etc/nixos/configuration.nix:nixpkgs=https://example.com/nixpkgs.tar.gz"
+    ));
+    assert!(!nix_path_has_nixpkgs(""));
+    assert!(!nix_path_has_nixpkgs(
+        "nixos-config=/etc/nixos/configuration.nix"
+    ));
+
+    let channels = tempfile::tempdir().unwrap();
+    let nix_path = channels.path().display().to_string();
+    assert!(!nix_path_has_nixpkgs(&nix_path));
+    fs::create_dir(channels.path().join("nixpkgs")).unwrap();
+    assert!(nix_path_has_nixpkgs(&nix_path));
+}
+
+#[test]
+fn channel_search_uses_nix_path() {
+    let cmd = nix_search_command(NixpkgsSource::NixPath, "ripgrep");
+    assert_eq!(
+        cmd.args[..5],
+        ["search", "--file", "<nixpkgs>", "", "ripgrep"]
+    );
+    let cmd = nix_search_command(NixpkgsSource::Registry, "ripgrep");
+    assert_eq!(cmd.args[..3], ["search", "nixpkgs", "ripgrep"]);
+}
@@ -0,0 +1,44 @@
+use declair_rs::closures::{ClosureChange, parse_diff_closures};
+
+#[test]
+fn diff_closures_output_is_summarized() {
+    let output = "\
+firefox: 120.0 → 121.0, +3.2 MiB
+ripgrep: ∅ → 14.1.0, +4.5 MiB
+\x1b[1mhtop\x1b[0m: 3.2.2 → ∅, -512.0 KiB
+glibc: +12.0 KiB
+";
+    let changes = parse_diff_closures(output);
+    assert_eq!(
+        changes,
+        [
+            ClosureChange::Updated {
+                name: "firefox".to_string(),
+                from: "120.0".to_string(),
+                to: "121.0".to_string(),
+            },
+            ClosureChange::Added {
+                name: "ripgrep".to_string(),
+                version: "14.1.0".to_string(),
+            },
+            ClosureChange::Removed {
+                name: "htop".to_string(),
+                version: "3.2.2".to_string(),
+            },
+        ]
+    );
+    assert_eq!(changes[2].name(), "htop");
+}
+
+#[test]
+fn several_versions_stay_together() {
+    let changes = parse_diff_closures("python3: 3.11.9, 3.12.4 → 3.12.5\n");
+    assert_eq!(
+        changes,
+        [ClosureChange::Updated {
+            name: "python3".to_string(),
+            from: "3.11.9, 3.12.4".to_string(),
+            to: "3.12.5".to_string(),
+        }]
+    );
+}
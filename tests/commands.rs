@@ -0,0 +1,784 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::config::Config;
+use declair_rs::edit::{add_package_to_nix, backup_path, restore_backup};
+use declair_rs::error::DeclairError;
+use declair_rs::flake::FlakeOutput;
+use declair_rs::format::{Formatter, format_file};
+use declair_rs::host::Privilege;
+use declair_rs::module::ModuleKind;
+use declair_rs::privileged::{RootRisk, ScratchCopy, root_risks};
+use declair_rs::rebuild::{
+    FailureCause, RebuildMode, RebuildTarget, check_command, check_target, diagnose_failure,
+    failure_summary, is_evaluation_error, rebuild, rebuild_command, rebuild_command_as,
+    trace_command, update_command, update_inputs,
+};
+use declair_rs::runner::{
+    CommandClass, CommandRunner, CommandSpec, SystemRunner, Timeouts, eval_json,
+    is_transient_failure, shell_quote,
+};
+use declair_rs::search::{NixpkgsSource, nix_search_command, search_packages};
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+fn config(home_manager: bool, flake: bool) -> Config {
+    Config {
+        nix_path: "/etc/nixos".to_string(),
+        auto_rebuild: true,
+        home_manager,
+        flake,
+        ..Default::default()
+    }
+}
+
+const SEARCH_JSON: &str = r#"{
+  "legacyPackages.x86_64-linux.ripgrep": {
+    "pname": "ripgrep",
+    "version": "14.1.0",
+    "description": "A utility that combines the usability of The Silver Searcher with the raw speed of grep"
+  },
+  "legacyPackages.x86_64-linux.ripgrep-all": {
+    "pname": "ripgrep-all",
+    "version": "0.10.6"
+  }
+}"#;
+
+#[test]
+fn search_parses_nix_json() {
+    let runner = FakeRunner::new().answer("nix", &[], 0, SEARCH_JSON);
+    let results = search_packages(&runner, "ripgrep").unwrap();
+    assert_eq!(results.len(), 2);
+    let rg = &results["legacyPackages.x86_64-linux.ripgrep"];
+    assert_eq!(rg.pname, "ripgrep");
+    assert_eq!(rg.version, "14.1.0");
+    assert!(
+        results["legacyPackages.x86_64-linux.ripgrep-all"]
+            .description
+            .is_none()
+    );
+
+    let calls = runner.calls.borrow();
+    assert_eq!(calls[0].program, "nix");
+    assert_eq!(calls[0].args[..3], ["search", "nixpkgs", "ripgrep"]);
+}
+
+#[test]
+fn search_non_zero_exit_fails() {
+    let runner = FakeRunner::new()
+        .answer("nix", &[], 1, "")
+        .answer("nix-env", &[], 1, "");
+    let err = search_packages(&runner, "ripgrep").unwrap_err();
+    assert!(matches!(err, DeclairError::SearchFailed(_)));
+}
+
+#[test]
+fn search_invalid_json_fails() {
+    let runner =
+        FakeRunner::new()
+            .answer("nix", &[], 0, "not json")
+            .answer("nix-env", &[], 0, "not json");
+    let err = search_packages(&runner, "ripgrep").unwrap_err();
+    assert!(matches!(err, DeclairError::SearchFailed(_)));
+}
+
+#[test]
+fn evaluations_fail_with_eval_errors() {
+    let cmd = CommandSpec::new("nix", ["eval", "--json", "--expr", "1"]);
+    let value: u32 = eval_json(&FakeRunner::new().answer("nix", &[], 0, "1"), &cmd).unwrap();
+    assert_eq!(value, 1);
+    let err = eval_json::<u32>(&FakeRunner::new().answer("nix", &[], 1, ""), &cmd).unwrap_err();
+    assert!(matches!(err, DeclairError::Eval(_)));
+    let err =
+        eval_json::<u32>(&FakeRunner::new().answer("nix", &[], 0, "not json"), &cmd).unwrap_err();
+    assert!(matches!(err, DeclairError::Eval(_)));
+    assert_eq!(err.exit_code(), 7);
+}
+
+#[test]
+fn rebuild_commands_match_config() {
+    let dir = Path::new("/etc/nixos");
+    let cases = [
+        (false, false, "sudo", vec!["nixos-rebuild", "switch"]),
+        (
+            false,
+            true,
+            "sudo",
+            vec!["nixos-rebuild", "switch", "--flake", "."],
+        ),
+        (true, false, "home-manager", vec!["switch"]),
+        (true, true, "home-manager", vec!["switch", "--flake", "."]),
+    ];
+    for (home_manager, flake, program, args) in cases {
+        let target = RebuildTarget::new(ModuleKind::from_home_manager(home_manager));
+        let cmd = rebuild_command(&config(home_manager, flake), &target, dir);
+        assert_eq!(cmd.program, program);
+        assert_eq!(cmd.args, args);
+        assert_eq!(cmd.current_dir.as_deref(), Some(dir));
+    }
+}
+
+#[test]
+fn rebuild_targets_flake_output() {
+    let dir = Path::new("/home/user/nixos");
+    let cases = [
+        (
+            ModuleKind::Nixos,
+            "laptop",
+            "sudo",
+            "nixos-rebuild switch --flake .#laptop",
+        ),
+        (
+            ModuleKind::HomeManager,
+            "alice@laptop",
+            "home-manager",
+            "switch --flake .#alice@laptop",
+        ),
+    ];
+    for (kind, name, program, args) in cases {
+        let target = RebuildTarget::from(FlakeOutput {
+            kind,
+            name: name.to_string(),
+        });
+        let cmd = rebuild_command(&config(false, true), &target, dir);
+        assert_eq!(cmd.program, program);
+        assert_eq!(cmd.args.join(" "), args);
+    }
+}
+
+#[test]
+fn rebuild_failure_is_reported() {
+    let runner = FakeRunner::new().answer("nixos-rebuild", &[], 1, "");
+    let err = rebuild(
+        &runner,
+        &config(false, false),
+        &RebuildTarget::new(ModuleKind::Nixos),
+        Path::new("/etc/nixos"),
+    )
+    .unwrap_err();
+    assert!(matches!(err, DeclairError::RebuildFailed { .. }));
+}
+
+#[test]
+fn evaluation_errors_are_told_apart() {
+    let eval_error = "error: undefined variable 'pkgs'\n       \
+                      at /etc/nixos/configuration.nix:3:5:\n       \
+                      (use '--show-trace' to show detailed location information)\n";
+    assert!(is_evaluation_error(eval_error));
+    assert!(!is_evaluation_error(
+        "error: builder for '/nix/store/abc-hello.drv' failed with exit code 1"
+    ));
+
+    let runner = FakeRunner::new().answer("nixos-rebuild", &[], 1, eval_error);
+    let err = rebuild(
+        &runner,
+        &config(false, false),
+        &RebuildTarget::new(ModuleKind::Nixos),
+        Path::new("/etc/nixos"),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        DeclairError::RebuildFailed {
+            evaluation: true,
+            ..
+        }
+    ));
+    let runner = FakeRunner::new().answer("nixos-rebuild", &[], 1, "error: build failed");
+    let err = rebuild(
+        &runner,
+        &config(false, false),
+        &RebuildTarget::new(ModuleKind::Nixos),
+        Path::new("/etc/nixos"),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        DeclairError::RebuildFailed {
+            evaluation: false,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn traces_evaluate_with_show_trace_once() {
+    let dir = Path::new("/etc/nixos");
+    let target = RebuildTarget::new(ModuleKind::Nixos);
+    let cmd = trace_command(&config(false, true), &target, dir);
+    assert_eq!(cmd.program, "nixos-rebuild");
+    assert_eq!(cmd.args.join(" "), "dry-build --flake . --show-trace");
+
+    let mut config = config(false, true);
+    config.rebuild_args = vec!["--show-trace".to_string()];
+    let cmd = trace_command(&config, &target, dir);
+    assert_eq!(cmd.args.join(" "), "dry-build --flake . --show-trace");
+}
+
+#[test]
+fn rebuild_modes_and_profiles() {
+    let dir = Path::new("/etc/nixos");
+    let mut config = config(false, true);
+    config.sudo = Some("doas".to_string());
+    let mut target = RebuildTarget::new(ModuleKind::Nixos);
+    target.mode = RebuildMode::Boot;
+    target.profile = Some("work".to_string());
+    let cmd = rebuild_command(&config, &target, dir);
+    assert_eq!(cmd.program, "doas");
+    assert_eq!(
+        cmd.args.join(" "),
+        "nixos-rebuild boot --flake . --profile-name work"
+    );
+    assert!(check_target(&target).is_ok());
+
+    let mut target = RebuildTarget::new(ModuleKind::HomeManager);
+    assert!(check_target(&target).is_ok());
+    target.mode = RebuildMode::Test;
+    let err = check_target(&target).unwrap_err();
+    assert!(matches!(err, DeclairError::Config(_)));
+}
+
+#[test]
+fn root_rebuilds_skip_sudo_and_hand_home_manager_back() {
+    let dir = Path::new("/home/alice/nixos");
+    let root = Privilege::Root { invoker: None };
+    let sudo = Privilege::Root {
+        invoker: Some("alice".to_string()),
+    };
+    let cases = [
+        (
+            ModuleKind::Nixos,
+            &root,
+            "nixos-rebuild",
+            "switch --flake .",
+        ),
+        (
+            ModuleKind::Nixos,
+            &sudo,
+            "nixos-rebuild",
+            "switch --flake .",
+        ),
+        (
+            ModuleKind::HomeManager,
+            &root,
+            "home-manager",
+            "switch --flake .",
+        ),
+        (
+            ModuleKind::HomeManager,
+            &sudo,
+            "sudo",
+            "-u alice home-manager switch --flake .",
+        ),
+    ];
+    for (kind, privilege, program, args) in cases {
+        let target = RebuildTarget::new(kind);
+        let cmd = rebuild_command_as(&config(false, true), &target, dir, privilege);
+        assert_eq!(cmd.program, program);
+        assert_eq!(cmd.args.join(" "), args);
+    }
+}
+
+#[test]
+fn root_edits_of_home_manager_configs_are_risky() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("home.nix");
+    fs::write(&file, "{ }").unwrap();
+    let root = Privilege::Root { invoker: None };
+    assert!(root_risks(&file, true, &Privilege::User, None).is_empty());
+    let risks = root_risks(&file, true, &root, None);
+    assert!(risks.contains(&RootRisk::HomeManager));
+    // Run through sudo, `home-manager` runs as the user who ran it.
+    let sudo = Privilege::Root {
+        invoker: Some("alice".to_string()),
+    };
+    assert!(!root_risks(&file, true, &sudo, None).contains(&RootRisk::HomeManager));
+    assert!(!root_risks(&file, false, &root, None).contains(&RootRisk::HomeManager));
+}
+
+#[test]
+fn failure_summary_repeats_the_errors() {
+    let output = "\
+building '/nix/store/abc-foo.drv'...
+error: builder for '/nix/store/abc-foo.drv' failed with exit code 2
+       last 10 log lines:
+       > make: *** [all] Error 1
+error: 1 dependencies of derivation '/nix/store/def-system.drv' failed to build
+error: builder for '/nix/store/abc-foo.drv' failed with exit code 2
+";
+    assert_eq!(
+        failure_summary(output),
+        [
+            "error: builder for '/nix/store/abc-foo.drv' failed with exit code 2",
+            "error: 1 dependencies of derivation '/nix/store/def-system.drv' failed to build",
+        ]
+    );
+    assert!(failure_summary("building...\n").is_empty());
+}
+
+#[test]
+fn rebuild_success() {
+    let runner = FakeRunner::new().answer("home-manager", &[], 0, "");
+    rebuild(
+        &runner,
+        &config(true, true),
+        &RebuildTarget::new(ModuleKind::HomeManager),
+        Path::new("/home/user"),
+    )
+    .unwrap();
+    assert_eq!(runner.count(), 1);
+}
+
+#[test]
+fn update_commands_match_config() {
+    let dir = Path::new("/etc/nixos");
+    let cases = [
+        (false, false, "sudo", "nix-channel --update"),
+        (true, false, "nix-channel", "--update"),
+        (
+            false,
+            true,
+            "nix",
+            "flake update --extra-experimental-features nix-command flakes",
+        ),
+    ];
+    for (home_manager, flake, program, args) in cases {
+        let target = RebuildTarget::new(ModuleKind::from_home_manager(home_manager));
+        let cmd = update_command(&config(home_manager, flake), &target, dir);
+        assert_eq!(cmd.program, program);
+        assert_eq!(cmd.args.join(" "), args);
+        assert_eq!(cmd.current_dir.as_deref(), Some(dir));
+    }
+}
+
+#[test]
+fn update_failure_is_reported() {
+    let runner = FakeRunner::new().answer("nix", &[], 1, "");
+    let err = update_inputs(
+        &runner,
+        &config(false, true),
+        &RebuildTarget::new(ModuleKind::Nixos),
+        Path::new("/etc/nixos"),
+    )
+    .unwrap_err();
+    assert!(matches!(err, DeclairError::UpdateFailed { .. }));
+}
+
+#[test]
+fn transient_failures_are_detected() {
+    assert!(is_transient_failure(
+        b"error: unable to download 'https://channels.nixos.org/...': Couldn't resolve host name"
+    ));
+    assert!(is_transient_failure(b"error: HTTP error 503"));
+    assert!(!is_transient_failure(
+        b"error: flake 'nixpkgs' does not provide attribute"
+    ));
+}
+
+#[test]
+fn system_runner_kills_on_timeout() {
+    let runner = SystemRunner::default();
+    let cmd = CommandSpec::new("sleep", ["5"]).timeout(Duration::from_millis(100));
+    let err = runner.output(&cmd).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn system_runner_passes_extra_environment() {
+    let runner = SystemRunner::default();
+    let cmd = CommandSpec::new("sh", ["-c", r#"test "$DECLAIR_ACTION" = add"#])
+        .env("DECLAIR_ACTION", "add");
+    assert!(runner.status(&cmd).unwrap().success());
+}
+
+#[test]
+fn configured_timeouts_override_command_defaults() {
+    let timeouts = Timeouts {
+        search: Some(1),
+        rebuild: Some(0),
+        ..Default::default()
+    };
+    let default = Some(Duration::from_secs(300));
+    assert_eq!(
+        timeouts.resolve(CommandClass::Search, default),
+        Some(Duration::from_secs(1))
+    );
+    assert_eq!(timeouts.resolve(CommandClass::Rebuild, default), None);
+    assert_eq!(timeouts.resolve(CommandClass::Eval, default), default);
+    assert_eq!(timeouts.resolve(CommandClass::Other, None), None);
+}
+
+#[test]
+fn system_runner_applies_configured_timeout() {
+    let runner = SystemRunner::with_timeouts(Timeouts {
+        eval: Some(1),
+        ..Default::default()
+    });
+    let cmd = CommandSpec::new("sleep", ["5"]).class(CommandClass::Eval);
+    let err = runner.status(&cmd).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn rebuild_commands_are_rebuild_class() {
+    let cmd = rebuild_command(
+        &config(false, false),
+        &RebuildTarget::new(ModuleKind::Nixos),
+        Path::new("/etc/nixos"),
+    );
+    assert_eq!(cmd.class, CommandClass::Rebuild);
+}
+
+#[test]
+fn system_runner_retries_transient_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let runner = SystemRunner {
+        initial_backoff: Duration::from_millis(1),
+        ..Default::default()
+    };
+    // Fails with a network-looking error on the first call only.
+    let script = "if [ -f attempted ]; then echo ok; \
+                  else touch attempted; echo 'unable to download' >&2; exit 1; fi";
+    let cmd = CommandSpec::new("sh", ["-c", script])
+        .current_dir(dir.path())
+        .retries(2);
+    let out = runner.output(&cmd).unwrap();
+    assert!(out.status.success());
+    assert_eq!(out.stdout, b"ok\n");
+}
+
+#[test]
+fn commands_are_quoted_for_the_shell() {
+    assert_eq!(shell_quote("nixos-rebuild"), "nixos-rebuild");
+    assert_eq!(
+        shell_quote("/etc/nixos/configuration.nix"),
+        "/etc/nixos/configuration.nix"
+    );
+    assert_eq!(shell_quote(".#laptop"), "'.#laptop'");
+    assert_eq!(shell_quote("nix-command flakes"), "'nix-command flakes'");
+    assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    assert_eq!(shell_quote(""), "''");
+
+    let cmd = CommandSpec::new(
+        "git",
+        ["commit", "-m", "add ripgrep", "--", "/etc/nixos/a b.nix"],
+    )
+    .current_dir("/etc/nixos")
+    .env("GIT_AUTHOR_NAME", "declair bot");
+    assert_eq!(
+        cmd.shell_line(),
+        "cd /etc/nixos && GIT_AUTHOR_NAME='declair bot' git commit -m 'add ripgrep' -- '/etc/nixos/a b.nix'"
+    );
+
+    let cmd = CommandSpec::new("sudo", ["tee", "/etc/nixos/configuration.nix"]).stdin("{ }");
+    assert_eq!(
+        cmd.shell_line(),
+        "sudo tee /etc/nixos/configuration.nix <<'DECLAIR_EOF'\n{ }\nDECLAIR_EOF"
+    );
+}
+
+#[test]
+fn extra_args_go_before_the_end_of_options() {
+    let extra = ["--show-trace".to_string(), "--impure".to_string()];
+    let cmd = CommandSpec::new("nix", ["eval", "--expr", "1"]).extra_args(&extra);
+    assert_eq!(cmd.args.join(" "), "eval --expr 1 --show-trace --impure");
+    let cmd = CommandSpec::new("nix", ["run", "nixpkgs#hello", "--", "-g"]).extra_args(&extra);
+    assert_eq!(
+        cmd.args.join(" "),
+        "run nixpkgs#hello --show-trace --impure -- -g"
+    );
+}
+
+#[test]
+fn nix_args_only_reach_nix() {
+    let runner = SystemRunner {
+        nix_args: vec!["--version".to_string()],
+        ..Default::default()
+    };
+    // `nix` may not be installed here, so check with `sh`, which gets nothing added.
+    let out = runner
+        .output(&CommandSpec::new("sh", ["-c", "echo \"$#\"", "sh"]))
+        .unwrap();
+    assert_eq!(out.stdout, b"0\n");
+}
+
+#[test]
+fn rebuild_args_reach_rebuilds_and_checks() {
+    let dir = Path::new("/etc/nixos");
+    let mut config = config(false, true);
+    config.rebuild_args = vec!["--impure".to_string(), "--show-trace".to_string()];
+    let target = RebuildTarget::new(ModuleKind::Nixos);
+    let cmd = rebuild_command(&config, &target, dir);
+    assert_eq!(
+        cmd.args.join(" "),
+        "nixos-rebuild switch --flake . --impure --show-trace"
+    );
+    let cmd = check_command(&config, &target, dir);
+    assert_eq!(
+        cmd.args.join(" "),
+        "dry-build --flake . --impure --show-trace"
+    );
+}
+
+#[test]
+fn printing_runner_runs_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let runner = SystemRunner {
+        print_only: true,
+        ..Default::default()
+    };
+    let cmd = CommandSpec::new("sh", ["-c", "touch ran; exit 1"]).current_dir(dir.path());
+    let out = runner.output(&cmd).unwrap();
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty());
+    assert!(runner.status(&cmd).unwrap().success());
+    assert!(!dir.path().join("ran").exists());
+}
+
+#[test]
+fn nix_logs_are_read_past_lines_that_are_not_utf8() {
+    let script = r"printf 'bad \377 line\nlast line\n' >&2";
+    let cmd = CommandSpec::new("sh", ["-c", script, "sh", "--log-format", "internal-json"]);
+    let out = SystemRunner::default().status_captured(&cmd).unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "bad \u{fffd} line\nlast line\n"
+    );
+}
+
+#[test]
+fn printing_runner_still_runs_queries() {
+    let runner = SystemRunner {
+        print_only: true,
+        ..Default::default()
+    };
+    let cmd = CommandSpec::new("sh", ["-c", "echo found"]).query();
+    assert_eq!(runner.output(&cmd).unwrap().stdout, b"found\n");
+    assert!(nix_search_command(NixpkgsSource::Registry, "ripgrep").query);
+}
+
+#[test]
+fn system_runner_does_not_retry_real_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let runner = SystemRunner {
+        initial_backoff: Duration::from_millis(1),
+        ..Default::default()
+    };
+    let script = "echo x >> attempts; echo 'error: attribute missing' >&2; exit 1";
+    let cmd = CommandSpec::new("sh", ["-c", script])
+        .current_dir(dir.path())
+        .retries(3);
+    let out = runner.output(&cmd).unwrap();
+    assert!(!out.status.success());
+    let attempts = std::fs::read_to_string(dir.path().join("attempts")).unwrap();
+    assert_eq!(attempts.lines().count(), 1);
+}
+
+#[test]
+fn formatter_runs_only_on_edited_file() {
+    let runner = FakeRunner::new().answer("alejandra", &[], 0, "");
+    let file = Path::new("/etc/nixos/configuration.nix");
+    format_file(&runner, Formatter::Alejandra, file).unwrap();
+    let calls = runner.calls.borrow();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].program, "alejandra");
+    assert_eq!(calls[0].args, ["--quiet", "/etc/nixos/configuration.nix"]);
+}
+
+#[test]
+fn formatter_none_runs_nothing() {
+    let runner = FakeRunner::new();
+    format_file(
+        &runner,
+        Formatter::None,
+        Path::new("/etc/nixos/configuration.nix"),
+    )
+    .unwrap();
+    assert!(runner.calls.borrow().is_empty());
+}
+
+#[test]
+fn formatter_failure_is_reported() {
+    let runner = FakeRunner::new().answer("nixfmt", &[], 1, "");
+    let err = format_file(&runner, Formatter::Nixfmt, Path::new("a.nix")).unwrap_err();
+    assert!(matches!(err, DeclairError::FormatFailed { .. }));
+}
+
+#[test]
+fn formatter_config_values() {
+    let cfg: Config = toml::from_str(
+        r#"
+nix_path = "~/nixos"
+auto_rebuild = false
+home_manager = false
+flake = true
+formatter = "nixpkgs-fmt"
+"#,
+    )
+    .unwrap();
+    assert_eq!(cfg.formatter, Formatter::NixpkgsFmt);
+
+    let legacy: Config = toml::from_str(
+        r#"
+nix_path = "~/nixos"
+auto_rebuild = false
+home_manager = false
+flake = true
+"#,
+    )
+    .unwrap();
+    assert_eq!(legacy.formatter, Formatter::None);
+}
+
+#[test]
+fn groups_config_values() {
+    let cfg: Config = toml::from_str(
+        r#"
+nix_path = "~/nixos"
+auto_rebuild = false
+home_manager = false
+flake = false
+
+[groups.dev]
+packages = ["git", "gcc", "gdb"]
+
+[groups.media]
+packages = ["mpv"]
+"#,
+    )
+    .unwrap();
+    assert_eq!(cfg.groups["dev"].packages, ["git", "gcc", "gdb"]);
+    assert_eq!(cfg.groups["media"].packages, ["mpv"]);
+    assert_eq!(cfg.groups.keys().collect::<Vec<_>>(), ["dev", "media"]);
+}
+
+#[test]
+fn system_runner_feeds_stdin() {
+    let out = SystemRunner::default()
+        .output(&CommandSpec::new("cat", Vec::<String>::new()).stdin("with pkgs; [ ]"))
+        .unwrap();
+    assert_eq!(out.stdout, b"with pkgs; [ ]");
+}
+
+#[test]
+fn scratch_copy_writes_back_with_sudo() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    std::fs::write(
+        &file,
+        "{\n  environment.systemPackages = with pkgs; [\n  ];\n}",
+    )
+    .unwrap();
+
+    let scratch = ScratchCopy::new(&file).unwrap();
+    add_package_to_nix(scratch.path(), "git", ModuleKind::Nixos).unwrap();
+    let edited = std::fs::read(scratch.path()).unwrap();
+    let runner = FakeRunner::new().answer("sudo", &[], 0, "");
+    scratch.write_back(&runner).unwrap();
+
+    let calls = runner.calls.borrow();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].program, "sudo");
+    assert!(
+        calls[0]
+            .args
+            .contains(&file.canonicalize().unwrap().display().to_string())
+    );
+    assert_eq!(calls[0].stdin.as_deref(), Some(edited.as_slice()));
+}
+
+#[test]
+fn unchanged_scratch_copy_is_not_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    std::fs::write(&file, "{ }").unwrap();
+
+    let runner = FakeRunner::new();
+    ScratchCopy::new(&file)
+        .unwrap()
+        .write_back(&runner)
+        .unwrap();
+    assert!(runner.calls.borrow().is_empty());
+}
+
+#[test]
+fn failed_sudo_write_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    std::fs::write(&file, "{ environment.systemPackages = with pkgs; [ ]; }").unwrap();
+
+    let scratch = ScratchCopy::new(&file).unwrap();
+    add_package_to_nix(scratch.path(), "git", ModuleKind::Nixos).unwrap();
+    let err = scratch
+        .write_back(&FakeRunner::new().answer("sudo", &[], 1, ""))
+        .unwrap_err();
+    assert!(matches!(err, DeclairError::PrivilegedWriteFailed { .. }));
+}
+
+#[test]
+fn check_commands_only_evaluate() {
+    let dir = Path::new("/etc/nixos");
+    let mut target = RebuildTarget::new(ModuleKind::Nixos);
+    target.flake_output = Some("laptop".to_string());
+    let cmd = check_command(&config(false, true), &target, dir);
+    assert_eq!(cmd.program, "nixos-rebuild");
+    assert_eq!(cmd.args, ["dry-build", "--flake", ".#laptop"]);
+    assert_eq!(cmd.class, CommandClass::Eval);
+
+    let hm = RebuildTarget::new(ModuleKind::HomeManager);
+    let cmd = check_command(&config(true, false), &hm, dir);
+    assert_eq!(cmd.program, "home-manager");
+    assert_eq!(cmd.args, ["build", "--dry-run", "--no-out-link"]);
+}
+
+#[test]
+fn failures_are_blamed_on_the_edit_only_when_the_backup_evaluates() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(&file, "edited").unwrap();
+    fs::write(backup_path(&file), "original").unwrap();
+    let target = RebuildTarget::new(ModuleKind::Nixos);
+    let config = config(false, false);
+    let files = [file.clone()];
+
+    for (code, cause) in [(0, FailureCause::Edit), (1, FailureCause::PreExisting)] {
+        // Record what the file held whenever a command runs.
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let runner = FakeRunner::new()
+            .answer("nixos-rebuild", &[], code, "")
+            .on_call({
+                let (file, seen) = (file.clone(), seen.clone());
+                move |_| seen.borrow_mut().push(fs::read_to_string(&file).unwrap())
+            });
+        let found = diagnose_failure(&runner, &config, &target, dir.path(), &files).unwrap();
+        assert_eq!(found, cause);
+        assert_eq!(*seen.borrow(), ["original"]);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "edited");
+    }
+
+    restore_backup(&file).unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "original");
+}
+
+#[test]
+fn failures_without_a_backup_have_unknown_cause() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(&file, "edited").unwrap();
+    let runner = FakeRunner::new();
+    let cause = diagnose_failure(
+        &runner,
+        &config(false, false),
+        &RebuildTarget::new(ModuleKind::Nixos),
+        dir.path(),
+        &[file],
+    )
+    .unwrap();
+    assert_eq!(cause, FailureCause::Unknown);
+    assert!(runner.calls.borrow().is_empty());
+}
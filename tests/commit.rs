@@ -0,0 +1,93 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::commit::{GitSettings, commit_commands, commit_files, expand};
+use declair_rs::hooks::HookContext;
+use std::path::{Path, PathBuf};
+
+fn ctx(action: &'static str) -> HookContext {
+    HookContext::new(
+        action,
+        vec!["ripgrep".to_string(), "fd".to_string()],
+        "/etc/nixos/configuration.nix",
+    )
+}
+
+#[test]
+fn templates_expand_the_change() {
+    assert_eq!(
+        expand("{action} {packages} in {file}", &ctx("add")),
+        "add ripgrep fd in /etc/nixos/configuration.nix"
+    );
+    assert_eq!(
+        GitSettings::default().message(&ctx("remove"), None),
+        "remove ripgrep fd via declair"
+    );
+}
+
+#[test]
+fn conventional_messages_get_a_type_and_scope() {
+    let settings = GitSettings {
+        conventional: true,
+        ..GitSettings::default()
+    };
+    assert_eq!(
+        settings.message(&ctx("add"), None),
+        "feat(packages): add ripgrep fd via declair"
+    );
+    assert_eq!(
+        settings.message(&ctx("update"), Some("bump inputs")),
+        "build(packages): bump inputs"
+    );
+    let unscoped = GitSettings {
+        scope: String::new(),
+        ..settings
+    };
+    assert_eq!(
+        unscoped.message(&ctx("remove"), None),
+        "chore: remove ripgrep fd via declair"
+    );
+}
+
+#[test]
+fn commit_message_overrides_the_template() {
+    let settings = GitSettings {
+        message: "{packages}".to_string(),
+        ..GitSettings::default()
+    };
+    assert_eq!(settings.message(&ctx("add"), Some("tools")), "tools");
+}
+
+#[test]
+fn only_the_edited_files_are_committed() {
+    let files = [PathBuf::from("/repo/home.nix")];
+    let [add, commit] = commit_commands(Path::new("/repo"), &files, "add fd");
+    assert_eq!(add.program, "git");
+    assert_eq!(add.args, ["add", "--", "/repo/home.nix"]);
+    assert_eq!(
+        commit.args,
+        ["commit", "-m", "add fd", "--", "/repo/home.nix"]
+    );
+    assert_eq!(commit.current_dir.as_deref(), Some(Path::new("/repo")));
+}
+
+#[test]
+fn failed_commits_are_errors() {
+    let files = [PathBuf::from("/repo/home.nix")];
+    let failing = FakeRunner::new()
+        .answer("git", &[], 1, "")
+        .stderr("nothing to commit");
+    let err = commit_files(&failing, Path::new("/repo"), &files, "m")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("nothing to commit"), "{err}");
+    assert!(
+        commit_files(
+            &FakeRunner::new().answer("git", &[], 0, ""),
+            Path::new("/repo"),
+            &files,
+            "m"
+        )
+        .is_ok()
+    );
+}
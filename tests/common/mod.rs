@@ -0,0 +1,180 @@
+#![allow(dead_code)]
+
+use declair_rs::runner::{CommandRunner, CommandSpec};
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io;
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(windows)]
+use std::os::windows::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output};
+use tempfile::TempDir;
+
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Copy a fixture into a fresh temp dir so edits (and backups) never touch the originals.
+pub fn copy_fixture(name: &str) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let dest = dir.path().join(name);
+    fs::copy(fixtures_dir().join(name), &dest).expect("failed to copy fixture");
+    (dir, dest)
+}
+
+/// Write `contents` to a `configuration.nix` in a fresh temp dir.
+pub fn write_config(contents: &str) -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let file = dir.path().join("configuration.nix");
+    fs::write(&file, contents).expect("failed to write config");
+    (dir, file)
+}
+
+/// Compare `actual` against `tests/fixtures/expected/<golden>`.
+/// Set `UPDATE_GOLDEN=1` to rewrite the golden file instead.
+pub fn assert_golden(golden: &str, actual: &str) {
+    let path = fixtures_dir().join("expected").join(golden);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).expect("failed to write golden file");
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read golden file {}: {}", path.display(), e));
+    assert_eq!(
+        expected,
+        actual,
+        "output differs from golden file {}",
+        path.display()
+    );
+}
+
+/// What [`FakeRunner`] answers a command with; `None` when the program isn't installed.
+type Answer = Option<(i32, Vec<u8>, Vec<u8>)>;
+
+/// Called with every command [`FakeRunner`] runs.
+type OnCall = Box<dyn Fn(&CommandSpec)>;
+
+/// Stands in for the Nix tools and everything else declair runs: answers each command
+/// with the exit code and output set for its program and leading arguments, and records
+/// the calls. A command without an answer fails the test, unless the test opted into a
+/// [`FakeRunner::fallback`].
+pub struct FakeRunner {
+    fallback: Option<Answer>,
+    answers: Vec<(String, Vec<String>, Answer)>,
+    on_call: Option<OnCall>,
+    pub calls: RefCell<Vec<CommandSpec>>,
+}
+
+impl FakeRunner {
+    /// Answers nothing yet.
+    pub fn new() -> Self {
+        FakeRunner {
+            fallback: None,
+            answers: Vec::new(),
+            on_call: None,
+            calls: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Answer `program` run with arguments starting with `args` with `code` and `stdout`.
+    /// The answer with the most arguments that fit wins.
+    pub fn answer(mut self, program: &str, args: &[&str], code: i32, stdout: &str) -> Self {
+        let answer = Some((code, stdout.as_bytes().to_vec(), Vec::new()));
+        self.answers.push((
+            program.to_string(),
+            args.iter().map(|a| a.to_string()).collect(),
+            answer,
+        ));
+        self
+    }
+
+    /// Also print `stderr` with the answer set last.
+    pub fn stderr(mut self, stderr: &str) -> Self {
+        let last = self.answers.last_mut().map(|(_, _, answer)| answer);
+        if let Some(Some((_, _, output))) = last.or(self.fallback.as_mut()) {
+            *output = stderr.as_bytes().to_vec();
+        }
+        self
+    }
+
+    /// Fail to start `program`, as if it weren't installed.
+    pub fn missing(mut self, program: &str) -> Self {
+        self.answers.push((program.to_string(), Vec::new(), None));
+        self
+    }
+
+    /// Answer the commands without an answer of their own with `code` and `stdout`,
+    /// instead of failing the test.
+    pub fn fallback(mut self, code: i32, stdout: &str) -> Self {
+        self.fallback = Some(Some((code, stdout.as_bytes().to_vec(), Vec::new())));
+        self
+    }
+
+    /// Call `f` with every command before answering it.
+    pub fn on_call(mut self, f: impl Fn(&CommandSpec) + 'static) -> Self {
+        self.on_call = Some(Box::new(f));
+        self
+    }
+
+    /// How many commands ran.
+    pub fn count(&self) -> usize {
+        self.calls.borrow().len()
+    }
+
+    /// The commands that ran, as `program arg...`.
+    pub fn lines(&self) -> Vec<String> {
+        self.calls
+            .borrow()
+            .iter()
+            .map(|cmd| format!("{} {}", cmd.program, cmd.args.join(" ")))
+            .collect()
+    }
+}
+
+impl CommandRunner for FakeRunner {
+    fn output(&self, cmd: &CommandSpec) -> io::Result<Output> {
+        self.calls.borrow_mut().push(cmd.clone());
+        if let Some(on_call) = &self.on_call {
+            on_call(cmd);
+        }
+        let answer = self
+            .answers
+            .iter()
+            .filter(|(program, args, _)| *program == cmd.program && cmd.args.starts_with(args))
+            .max_by_key(|(_, args, _)| args.len())
+            .map(|(_, _, answer)| answer)
+            .or(self.fallback.as_ref())
+            .unwrap_or_else(|| {
+                panic!("unexpected command: {} {}", cmd.program, cmd.args.join(" "))
+            });
+        let (code, stdout, stderr) = answer
+            .clone()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        Ok(Output {
+            status: exit_status(code),
+            stdout,
+            stderr,
+        })
+    }
+
+    fn status(&self, cmd: &CommandSpec) -> io::Result<ExitStatus> {
+        self.output(cmd).map(|o| o.status)
+    }
+
+    fn status_captured(&self, cmd: &CommandSpec) -> io::Result<Output> {
+        self.output(cmd)
+    }
+}
+
+#[cfg(unix)]
+fn exit_status(code: i32) -> ExitStatus {
+    ExitStatus::from_raw(code << 8)
+}
+
+#[cfg(windows)]
+fn exit_status(code: i32) -> ExitStatus {
+    ExitStatus::from_raw(code as u32)
+}
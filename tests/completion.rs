@@ -0,0 +1,66 @@
+use declair_rs::completion::FileCompletion;
+use dialoguer::Completion;
+use std::fs;
+
+#[test]
+fn candidates_are_case_insensitive_with_directories_first() {
+    let dir = tempfile::tempdir().unwrap();
+    for file in ["home.nix", "Hardware.nix"] {
+        fs::write(dir.path().join(file), "{ }").unwrap();
+    }
+    fs::create_dir(dir.path().join("hosts")).unwrap();
+    fs::write(dir.path().join("flake.nix"), "{ }").unwrap();
+    let base = dir.path().display();
+    assert_eq!(
+        FileCompletion::candidates(&format!("{}/h", base)),
+        [
+            format!("{}/hosts/", base),
+            format!("{}/Hardware.nix", base),
+            format!("{}/home.nix", base),
+        ]
+    );
+}
+
+#[test]
+fn repeated_tabs_cycle_through_the_candidates() {
+    let dir = tempfile::tempdir().unwrap();
+    for file in ["a1.nix", "a2.nix", "b.nix"] {
+        fs::write(dir.path().join(file), "{ }").unwrap();
+    }
+    let base = dir.path().display();
+    let completion = FileCompletion::new();
+    let first = completion.get(&format!("{}/a", base)).unwrap();
+    assert_eq!(first, format!("{}/a1.nix", base));
+    let second = completion.get(&first).unwrap();
+    assert_eq!(second, format!("{}/a2.nix", base));
+    assert_eq!(completion.get(&second).unwrap(), first);
+    // Typing something else starts over.
+    assert_eq!(
+        completion.get(&format!("{}/b", base)),
+        Some(format!("{}/b.nix", base))
+    );
+}
+
+#[test]
+fn paths_with_spaces_complete() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("my config")).unwrap();
+    fs::write(dir.path().join("my config/home.nix"), "{ }").unwrap();
+    let base = dir.path().display();
+    assert_eq!(
+        FileCompletion::candidates(&format!("{}/my", base)),
+        [format!("{}/my config/", base)]
+    );
+    assert_eq!(
+        FileCompletion::candidates(&format!("{}/my config/h", base)),
+        [format!("{}/my config/home.nix", base)]
+    );
+    assert_eq!(
+        FileCompletion::candidates(&format!("{}/my\\ c", base)),
+        [format!("{}/my\\ config/", base)]
+    );
+    assert_eq!(
+        FileCompletion::candidates(&format!("{}/my\\ config/h", base)),
+        [format!("{}/my\\ config/home.nix", base)]
+    );
+}
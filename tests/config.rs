@@ -0,0 +1,209 @@
+use declair_rs::config::{
+    layer_configs, locked_keys, merge_tables, read_config_from, save_config_to, strip_inherited,
+};
+use declair_rs::error::DeclairError;
+use declair_rs::rebuild::RebuildMode;
+use toml::Table;
+
+fn table(toml: &str) -> Table {
+    toml::from_str(toml).unwrap()
+}
+
+const SYSTEM: &str = r#"
+nix_path = "/etc/nixos/configuration.nix"
+auto_rebuild = true
+home_manager = false
+flake = true
+profile = "shared"
+exclude_sets = ["haskellPackages"]
+locked = ["licenses"]
+
+[licenses]
+deny = ["unfree"]
+
+[git]
+commit = true
+"#;
+
+#[test]
+fn user_settings_override_the_system_config() {
+    let user = table(
+        r#"
+        profile = "alice"
+        exclude_sets = []
+
+        [git]
+        conventional = true
+        "#,
+    );
+    let config = layer_configs(Some(table(SYSTEM)), Some(user))
+        .unwrap()
+        .unwrap();
+    assert_eq!(config.nix_path, "/etc/nixos/configuration.nix");
+    assert!(config.flake);
+    assert_eq!(config.profile.as_deref(), Some("alice"));
+    // Arrays are replaced, tables merged.
+    assert!(config.exclude_sets.is_empty());
+    assert!(config.git.commit);
+    assert!(config.git.conventional);
+}
+
+#[test]
+fn locked_settings_keep_the_system_value() {
+    let system = table(SYSTEM);
+    let user = table("[licenses]\ndeny = []\n");
+    assert_eq!(locked_keys(&system, &user), ["licenses"]);
+    let config = layer_configs(Some(system), Some(user)).unwrap().unwrap();
+    assert_eq!(config.licenses.deny, ["unfree"]);
+}
+
+#[test]
+fn either_config_alone_is_enough() {
+    assert!(layer_configs(None, None).unwrap().is_none());
+    let config = layer_configs(Some(table(SYSTEM)), None).unwrap().unwrap();
+    assert_eq!(config.profile.as_deref(), Some("shared"));
+    let user = table(
+        "nix_path = \"~/nixos\"\nauto_rebuild = false\nhome_manager = true\nflake = false\n\
+         rebuild_mode = \"boot\"\n",
+    );
+    let config = layer_configs(None, Some(user)).unwrap().unwrap();
+    assert_eq!(config.rebuild_mode, Some(RebuildMode::Boot));
+}
+
+#[test]
+fn saved_configs_leave_out_inherited_settings() {
+    let system = table(SYSTEM);
+    let mut merged = system.clone();
+    merged.remove("locked");
+    merge_tables(
+        &mut merged,
+        table("profile = \"alice\"\n[git]\nconventional = true\n"),
+    );
+
+    strip_inherited(&mut merged, &system);
+    assert_eq!(
+        merged,
+        table(
+            r#"
+            nix_path = "/etc/nixos/configuration.nix"
+            auto_rebuild = true
+            home_manager = false
+            flake = true
+            profile = "alice"
+
+            [git]
+            conventional = true
+            "#
+        )
+    );
+}
+
+#[test]
+fn configs_are_read_and_saved_against_the_given_system_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let system = dir.path().join("system.toml");
+    let user = dir.path().join("user/config.toml");
+    assert!(read_config_from(&system, &user).unwrap().is_none());
+
+    std::fs::write(&system, SYSTEM).unwrap();
+    let mut config = read_config_from(&system, &user).unwrap().unwrap();
+    assert_eq!(config.profile.as_deref(), Some("shared"));
+    config.profile = Some("alice".to_string());
+    save_config_to(&config, &system, &user).unwrap();
+    let saved = std::fs::read_to_string(&user).unwrap();
+    assert!(saved.contains("profile = \"alice\""));
+    assert!(!saved.contains("exclude_sets"));
+    let config = read_config_from(&system, &user).unwrap().unwrap();
+    assert_eq!(config.profile.as_deref(), Some("alice"));
+    assert_eq!(config.exclude_sets, ["haskellPackages"]);
+}
+
+#[test]
+fn read_only_set_by_the_system_config_sticks() {
+    // Top-level keys go before the tables.
+    let system = table(&format!("read_only = true\n{}", SYSTEM));
+    let user = table("read_only = false\n");
+    assert_eq!(locked_keys(&system, &user), ["read_only"]);
+    // Listed and implied at once, it is locked once.
+    let mut system = table(&format!("read_only = true\n{}", SYSTEM));
+    system.insert(
+        "locked".to_string(),
+        toml::Value::Array(vec!["read_only".into(), "licenses".into()]),
+    );
+    let user = table("read_only = false\n[licenses]\ndeny = []\n");
+    assert_eq!(locked_keys(&system, &user), ["licenses", "read_only"]);
+    let config = layer_configs(Some(system), Some(user)).unwrap().unwrap();
+    assert!(config.read_only);
+    assert_eq!(DeclairError::ReadOnly.exit_code(), 12);
+
+    // Without the system config saying so, users decide.
+    let user = table(
+        "nix_path = \"~/nixos\"\nauto_rebuild = false\nhome_manager = true\nflake = false\n\
+         read_only = true\n",
+    );
+    assert!(layer_configs(None, Some(user)).unwrap().unwrap().read_only);
+}
+
+#[test]
+fn read_only_refuses_every_command_that_changes_the_system() {
+    let home = tempfile::tempdir().unwrap();
+    let config_dir = home.path().join("config/declair");
+    std::fs::create_dir_all(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("config.toml"),
+        "nix_path = \"/nonexistent/configuration.nix\"\nauto_rebuild = true\n\
+         home_manager = false\nflake = false\nread_only = true\n",
+    )
+    .unwrap();
+    let commands: &[&[&str]] = &[
+        &["-p", "ripgrep"],
+        &["-r", "-p", "ripgrep"],
+        &["init-config-file"],
+        &["watch"],
+        &["daemon"],
+        &["add", "ripgrep"],
+        &["replace", "vim", "neovim"],
+        &["new-package", "hello"],
+        &["group", "add", "dev"],
+        &["group", "remove", "dev"],
+        &["group", "import", "groups.toml"],
+        &["overlay", "add", "rust", "github:oxalica/rust-overlay"],
+        &["overlay", "remove", "rust"],
+        &["env", "set", "EDITOR", "vim"],
+        &["env", "unset", "EDITOR"],
+        &["service", "enable", "openssh"],
+        &["service", "disable", "openssh"],
+        &["options", "set", "networking.hostName", "\"box\""],
+        &["prune", "ripgrep"],
+        &["drift"],
+        &["rebuild"],
+        &["update"],
+        &[
+            "channel",
+            "add",
+            "https://nixos.org/channels/nixos-unstable",
+        ],
+        &["channel", "update"],
+        &["schedule", "install"],
+        &["sync", "declair.lock"],
+        &["fix"],
+    ];
+    for command in commands {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_declair-rs"))
+            .arg("--no-interactive")
+            .args(*command)
+            .env("HOME", home.path())
+            .env("XDG_CONFIG_HOME", home.path().join("config"))
+            .env("LANG", "en_US.UTF-8")
+            .env_remove("LC_ALL")
+            .env_remove("LC_MESSAGES")
+            .output()
+            .unwrap();
+        assert_eq!(
+            output.status.code(),
+            Some(12),
+            "{:?} wasn't refused",
+            command
+        );
+    }
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use common::{FakeRunner, fixtures_dir};
+use declair_rs::conflicts::{Conflict, command_conflicts, find_conflicts, nix_files};
+use declair_rs::module::ModuleKind;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A config repo: `configuration.nix` (the edited file), `programs.nix` and a `.git` dir.
+fn repo() -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("configuration.nix");
+    fs::copy(fixtures_dir().join("multiline.nix"), &target).unwrap();
+    let modules = dir.path().join("modules");
+    fs::create_dir(&modules).unwrap();
+    fs::copy(
+        fixtures_dir().join("programs.nix"),
+        modules.join("programs.nix"),
+    )
+    .unwrap();
+    fs::create_dir(dir.path().join(".git")).unwrap();
+    fs::write(
+        dir.path().join(".git/ignored.nix"),
+        "programs.htop.enable = true;",
+    )
+    .unwrap();
+    (dir, target)
+}
+
+fn conflicts(dir: &Path, target: &Path, pkg: &str) -> Vec<Conflict> {
+    find_conflicts(dir, target, pkg, ModuleKind::Nixos).unwrap()
+}
+
+#[test]
+fn nix_files_skip_hidden_directories() {
+    let (dir, target) = repo();
+    assert_eq!(
+        nix_files(dir.path()),
+        [target, dir.path().join("modules/programs.nix")]
+    );
+}
+
+#[test]
+fn enabled_program_is_a_conflict() {
+    let (dir, target) = repo();
+    assert_eq!(
+        conflicts(dir.path(), &target, "firefox"),
+        [Conflict::ProgramEnabled {
+            file: dir.path().join("modules/programs.nix"),
+            line: 4,
+        }]
+    );
+}
+
+#[test]
+fn package_listed_in_another_file_is_a_conflict() {
+    let (dir, target) = repo();
+    let found = conflicts(dir.path(), &target, "git");
+    assert_eq!(
+        found,
+        [Conflict::ListedElsewhere {
+            file: dir.path().join("modules/programs.nix"),
+        }]
+    );
+}
+
+#[test]
+fn program_package_override_is_a_conflict() {
+    let (dir, target) = repo();
+    fs::write(
+        dir.path().join("editor.nix"),
+        "{ pkgs, ... }: {\n  programs.neovim.package = pkgs.neovim-unwrapped;\n}\n",
+    )
+    .unwrap();
+    assert_eq!(
+        conflicts(dir.path(), &target, "neovim-unwrapped"),
+        [Conflict::ProgramPackage {
+            program: "neovim".to_string(),
+            file: dir.path().join("editor.nix"),
+            line: 2,
+        }]
+    );
+}
+
+#[test]
+fn commented_out_settings_and_hidden_dirs_are_ignored() {
+    let (dir, target) = repo();
+    fs::write(
+        dir.path().join("old.nix"),
+        "{\n  # programs.htop.enable = true;\n}\n",
+    )
+    .unwrap();
+    assert!(conflicts(dir.path(), &target, "htop").is_empty());
+}
+
+#[test]
+fn same_main_program_is_a_conflict() {
+    let runner = FakeRunner::new().answer("nix", &[], 0, r#"["vim", "vim", null]"#);
+    let installed = ["vim".to_string(), "git".to_string()];
+    // The new package comes first in the query, followed by the installed ones.
+    let found = command_conflicts(&runner, "vim-full", &installed).unwrap();
+    assert_eq!(
+        found,
+        [Conflict::SameCommand {
+            command: "vim".to_string(),
+            package: "vim".to_string(),
+        }]
+    );
+    let calls = runner.calls.borrow();
+    let expr = &calls[0].args[4];
+    assert!(expr.contains(r#"[ "vim-full" "vim" "git" ]"#));
+}
+
+#[test]
+fn package_without_main_program_has_no_command_conflicts() {
+    let runner = FakeRunner::new().answer("nix", &[], 0, "[null, \"git\"]");
+    let found = command_conflicts(&runner, "foo", &["git".to_string()]).unwrap();
+    assert!(found.is_empty());
+}
+
+#[test]
+fn nothing_installed_skips_the_evaluation() {
+    let runner = FakeRunner::new();
+    assert!(
+        command_conflicts(&runner, "git", &["git".to_string()])
+            .unwrap()
+            .is_empty()
+    );
+    assert!(runner.calls.borrow().is_empty());
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use common::{FakeRunner, copy_fixture};
+use declair_rs::config::Config;
+use declair_rs::daemon::{Daemon, call_daemon};
+use declair_rs::module::ModuleKind;
+use declair_rs::rebuild::RebuildTarget;
+use declair_rs::state::State;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+
+const SEARCH_JSON: &str = r#"{
+  "legacyPackages.x86_64-linux.ripgrep": { "pname": "ripgrep", "version": "14.1.0" },
+  "legacyPackages.x86_64-linux.fd": { "pname": "fd", "version": "10.2.0" }
+}"#;
+
+/// Answers `nix search` with [`SEARCH_JSON`] and fails the other `nix` commands and
+/// rebuilds.
+fn search_runner() -> FakeRunner {
+    FakeRunner::new()
+        .answer("nix", &["search"], 0, SEARCH_JSON)
+        .answer("nix", &[], 1, "")
+        .answer("nixos-rebuild", &[], 1, "")
+}
+
+/// How often `runner` was asked to search.
+fn searches(runner: &FakeRunner) -> usize {
+    runner
+        .calls
+        .borrow()
+        .iter()
+        .filter(|cmd| cmd.args.first().is_some_and(|arg| arg == "search"))
+        .count()
+}
+
+fn request(method: &str, params: Value) -> String {
+    json!({ "jsonrpc": "2.0", "id": 7, "method": method, "params": params }).to_string()
+}
+
+fn daemon<'a>(runner: &'a FakeRunner, file: &Path) -> Daemon<'a> {
+    Daemon::new(
+        runner,
+        Config::default(),
+        file.to_path_buf(),
+        RebuildTarget::new(ModuleKind::Nixos),
+        file.parent().unwrap().to_path_buf(),
+    )
+    .state_file(file.with_file_name("state.json"))
+}
+
+#[test]
+fn search_results_are_cached() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let runner = search_runner();
+    let mut daemon = daemon(&runner, &file);
+
+    let first = daemon.handle_line(&request("search", json!({ "query": "rg" })));
+    let second = daemon.handle_line(&request("search", json!({ "query": "rg" })));
+    assert_eq!(first, second);
+    assert_eq!(first["id"], 7);
+    assert_eq!(first["result"][0]["pname"], "fd");
+    assert_eq!(first["result"][1]["pname"], "ripgrep");
+    assert_eq!(first["result"][1]["installed"], false);
+    assert_eq!(searches(&runner), 1);
+
+    // Installed status follows edits even for cached results.
+    daemon.handle_line(&request("add", json!({ "package": "ripgrep" })));
+    let third = daemon.handle_line(&request("search", json!({ "query": "rg" })));
+    assert_eq!(third["result"][1]["installed"], true);
+    assert_eq!(searches(&runner), 1);
+
+    let exact = daemon.handle_line(&request(
+        "search",
+        json!({ "query": "rg", "mode": "exact" }),
+    ));
+    assert_eq!(exact["result"], json!([]));
+    assert_eq!(searches(&runner), 2);
+    let bad = daemon.handle_line(&request(
+        "search",
+        json!({ "query": "rg", "mode": "fuzzy" }),
+    ));
+    assert_eq!(bad["error"]["code"], -32602);
+    let invalid = daemon.handle_line(&request("search", json!({ "query": "(", "mode": "regex" })));
+    assert_eq!(invalid["error"]["code"], 7);
+}
+
+#[test]
+fn add_remove_and_list() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let runner = search_runner();
+    let mut daemon = daemon(&runner, &file);
+
+    let added = daemon.handle_line(&request("add", json!({ "package": "htop" })));
+    assert_eq!(added["result"]["package"], "htop");
+    let list = daemon.handle_line(&request("list", Value::Null));
+    assert!(list["result"].as_array().unwrap().contains(&json!("htop")));
+
+    let removed = daemon.handle_line(&request("remove", json!({ "package": "htop" })));
+    assert_eq!(removed["result"]["references"], json!([]));
+    let list = daemon.handle_line(&request("list", Value::Null));
+    assert!(!list["result"].as_array().unwrap().contains(&json!("htop")));
+}
+
+#[test]
+fn adds_and_removes_are_recorded_in_the_state() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let state = file.with_file_name("state.json");
+    let runner = search_runner();
+    let mut daemon = daemon(&runner, &file);
+
+    let added = daemon.handle_line(&request(
+        "add",
+        json!({ "package": "htop", "reason": "from the editor" }),
+    ));
+    assert_eq!(added["result"]["package"], "htop");
+    let record = &State::load(&state).unwrap().packages["htop"];
+    assert_eq!(record.reason.as_deref(), Some("from the editor"));
+
+    daemon.handle_line(&request("remove", json!({ "package": "htop" })));
+    assert!(!State::load(&state).unwrap().packages.contains_key("htop"));
+}
+
+#[test]
+fn add_refuses_conflicts_unless_forced() {
+    let (dir, file) = copy_fixture("multiline.nix");
+    fs::write(
+        dir.path().join("other.nix"),
+        "{ pkgs, ... }: { environment.systemPackages = with pkgs; [ htop ]; }\n",
+    )
+    .unwrap();
+    let runner = search_runner();
+    let mut daemon = daemon(&runner, &file);
+
+    let refused = daemon.handle_line(&request("add", json!({ "package": "htop" })));
+    assert_eq!(refused["error"]["code"], 5);
+    assert!(!fs::read_to_string(&file).unwrap().contains("htop"));
+
+    let forced = daemon.handle_line(&request("add", json!({ "package": "htop", "force": true })));
+    assert_eq!(forced["result"]["package"], "htop");
+    assert!(fs::read_to_string(&file).unwrap().contains("htop"));
+}
+
+#[test]
+fn remove_reports_references_elsewhere() {
+    let (dir, file) = copy_fixture("multiline.nix");
+    fs::write(
+        dir.path().join("aliases.nix"),
+        "{ pkgs, ... }: { environment.shellAliases.top = \"${pkgs.git}/bin/git\"; }\n",
+    )
+    .unwrap();
+    let runner = search_runner();
+    let mut daemon = daemon(&runner, &file);
+
+    let removed = daemon.handle_line(&request("remove", json!({ "package": "git" })));
+    let references = removed["result"]["references"].as_array().unwrap();
+    assert_eq!(references.len(), 1);
+    assert_eq!(references[0]["line"], 1);
+    assert_eq!(references[0]["kind"], "attribute");
+}
+
+#[test]
+fn add_substitutes_renamed_and_rejects_removed_packages() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let runner = search_runner();
+    let mut daemon = daemon(&runner, &file);
+
+    let added = daemon.handle_line(&request("add", json!({ "package": "exa" })));
+    assert_eq!(added["result"]["package"], "eza");
+    assert_eq!(added["result"]["replaces"], "exa");
+    let list = daemon.handle_line(&request("list", Value::Null));
+    assert!(list["result"].as_array().unwrap().contains(&json!("eza")));
+
+    let removed = daemon.handle_line(&request("add", json!({ "package": "nerdfonts" })));
+    assert_eq!(removed["error"]["code"], 6);
+}
+
+#[test]
+fn errors_use_exit_codes_and_json_rpc_codes() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let runner = search_runner();
+    let mut daemon = daemon(&runner, &file);
+
+    let exists = daemon.handle_line(&request("add", json!({ "package": "git" })));
+    assert_eq!(exists["error"]["code"], 5);
+    let unknown = daemon.handle_line(&request("frobnicate", Value::Null));
+    assert_eq!(unknown["error"]["code"], -32601);
+    let missing = daemon.handle_line(&request("add", json!({})));
+    assert_eq!(missing["error"]["code"], -32602);
+    let garbage = daemon.handle_line("{ not json");
+    assert_eq!(garbage["error"]["code"], -32700);
+}
+
+#[test]
+fn rebuild_status_tracks_last_rebuild() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let runner = search_runner();
+    let mut daemon = daemon(&runner, &file);
+
+    let status = daemon.handle_line(&request("rebuild-status", Value::Null));
+    assert_eq!(status["result"]["state"], "never");
+    let rebuild = daemon.handle_line(&request("rebuild", Value::Null));
+    assert_eq!(rebuild["error"]["code"], 8);
+    let status = daemon.handle_line(&request("rebuild-status", Value::Null));
+    assert_eq!(status["result"]["state"], "failed");
+}
+
+#[cfg(unix)]
+#[test]
+fn client_talks_to_daemon_over_socket() {
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+    use std::time::Duration;
+
+    let (dir, file) = copy_fixture("multiline.nix");
+    let socket = dir.path().join("declair.sock");
+    {
+        let (socket, file) = (socket.clone(), file.clone());
+        thread::spawn(move || {
+            let runner = search_runner();
+            daemon(&runner, &file).serve(&socket)
+        });
+    }
+    while UnixStream::connect(&socket).is_err() {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let list = call_daemon(&socket, "list", Value::Null).unwrap();
+    assert_eq!(list, json!(["git", "vim", "wget"]));
+    let err = call_daemon(&socket, "remove", json!({ "package": "nope" })).unwrap_err();
+    assert!(err.to_string().contains("nope"));
+}
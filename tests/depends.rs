@@ -0,0 +1,119 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::depends::{
+    ReverseDependencies, profile_packages, reverse_dependencies, store_name, why_depends,
+};
+use declair_rs::error::DeclairError;
+use std::path::Path;
+
+const ROOT: &str = "/nix/store/aaaa-nixos-system-host-25.05";
+const SYSTEM_PATH: &str = "/nix/store/bbbb-system-path";
+const RIPGREP: &str = "/nix/store/cccc-ripgrep-14.1.0";
+const RIPGREP_ALL: &str = "/nix/store/dddd-ripgrep-all-0.10.6";
+const OUTSIDE: &str = "/nix/store/eeee-unrelated-1.0";
+
+/// Answers `nix-store` queries about a small fake closure in which ripgrep is both in the
+/// system profile and a dependency of ripgrep-all.
+fn store_runner() -> FakeRunner {
+    let query = |what, path| ["--query", what, path];
+    FakeRunner::new()
+        .answer(
+            "nix-store",
+            &query("--requisites", ROOT),
+            0,
+            &[ROOT, SYSTEM_PATH, RIPGREP, RIPGREP_ALL].join("\n"),
+        )
+        .answer(
+            "nix-store",
+            &query("--referrers", RIPGREP),
+            0,
+            &[SYSTEM_PATH, RIPGREP_ALL, OUTSIDE].join("\n"),
+        )
+        .answer(
+            "nix-store",
+            &query("--referrers", RIPGREP_ALL),
+            0,
+            SYSTEM_PATH,
+        )
+        .answer(
+            "nix-store",
+            &query("--references", ROOT),
+            0,
+            &[OUTSIDE, SYSTEM_PATH].join("\n"),
+        )
+        .answer(
+            "nix-store",
+            &query("--references", SYSTEM_PATH),
+            0,
+            &[SYSTEM_PATH, RIPGREP, RIPGREP_ALL].join("\n"),
+        )
+        .answer(
+            "nix",
+            &["why-depends", ROOT, RIPGREP],
+            0,
+            &format!("{}\n└───{}\n    └───{}\n", ROOT, SYSTEM_PATH, RIPGREP),
+        )
+        .answer("nix-store", &[], 1, "")
+        .stderr("unknown store path")
+}
+
+#[test]
+fn store_names_drop_the_hash() {
+    assert_eq!(store_name(RIPGREP), "ripgrep-14.1.0");
+    assert_eq!(store_name(SYSTEM_PATH), "system-path");
+}
+
+#[test]
+fn profile_packages_are_what_the_package_collection_references() {
+    assert_eq!(
+        profile_packages(&store_runner(), Path::new(ROOT)).unwrap(),
+        [RIPGREP, RIPGREP_ALL]
+    );
+    assert!(profile_packages(&store_runner(), Path::new(OUTSIDE)).is_err());
+}
+
+#[test]
+fn explicit_package_that_is_also_a_dependency() {
+    let deps = reverse_dependencies(&store_runner(), Path::new(ROOT), "ripgrep").unwrap();
+    assert_eq!(
+        deps,
+        [ReverseDependencies {
+            path: RIPGREP.to_string(),
+            in_profile: true,
+            required_by: vec!["ripgrep-all-0.10.6".to_string()],
+        }]
+    );
+}
+
+#[test]
+fn package_names_match_whole_names_only() {
+    let deps = reverse_dependencies(&store_runner(), Path::new(ROOT), "ripgrep-all").unwrap();
+    assert_eq!(deps.len(), 1);
+    assert!(deps[0].in_profile);
+    assert!(deps[0].required_by.is_empty());
+}
+
+#[test]
+fn missing_package_has_no_paths() {
+    let deps = reverse_dependencies(&store_runner(), Path::new(ROOT), "htop").unwrap();
+    assert!(deps.is_empty());
+}
+
+#[test]
+fn why_depends_returns_the_chain() {
+    let chain = why_depends(&store_runner(), Path::new(ROOT), RIPGREP).unwrap();
+    assert!(chain.ends_with("cccc-ripgrep-14.1.0"));
+}
+
+#[test]
+fn failed_query_is_reported() {
+    let err = reverse_dependencies(
+        &store_runner(),
+        Path::new("/nix/store/ffff-other"),
+        "ripgrep",
+    )
+    .unwrap_err();
+    assert!(matches!(err, DeclairError::StoreQueryFailed(_)));
+    assert_eq!(err.exit_code(), 7);
+}
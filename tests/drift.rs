@@ -0,0 +1,44 @@
+use declair_rs::drift::{Drift, drift, package_name, provides};
+use std::collections::BTreeSet;
+
+fn set(items: &[&str]) -> BTreeSet<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn store_names_lose_version_and_output() {
+    assert_eq!(package_name("ripgrep-14.1.0"), "ripgrep");
+    assert_eq!(package_name("curl-8.9.1-bin"), "curl");
+    assert_eq!(package_name("python3-3.12.4"), "python3");
+    assert_eq!(package_name("ripgrep-all-0.10.6"), "ripgrep-all");
+    assert_eq!(package_name("system-path"), "system-path");
+}
+
+#[test]
+fn attributes_match_their_package_names() {
+    assert!(provides("ripgrep", "ripgrep"));
+    assert!(provides("gnome.nautilus", "nautilus"));
+    assert!(provides("nodejs_20", "nodejs"));
+    assert!(provides("python312", "python"));
+    assert!(!provides("ripgrep", "ripgrep-all"));
+    assert!(!provides("gitFull", "git"));
+}
+
+#[test]
+fn drift_in_both_directions() {
+    let configured = set(&["ripgrep", "fd", "nodejs_20"]);
+    let installed = set(&["ripgrep", "nodejs", "htop", "coreutils"]);
+    assert_eq!(
+        drift(&configured, &installed, None),
+        Drift {
+            missing: vec!["fd".to_string()],
+            extra: vec!["coreutils".to_string(), "htop".to_string()],
+        }
+    );
+    let tracked = set(&["htop"]);
+    let tracked_only = drift(&configured, &installed, Some(&tracked));
+    assert_eq!(tracked_only.extra, ["htop"]);
+
+    let in_sync = drift(&set(&["ripgrep"]), &set(&["ripgrep"]), None);
+    assert!(in_sync.is_empty());
+}
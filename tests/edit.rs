@@ -0,0 +1,740 @@
+mod common;
+
+use common::{assert_golden, copy_fixture};
+use declair_rs::edit::{
+    ListSegment, MissingBlock, PackageList, add_flake_input, add_import,
+    add_package_resolving_block, add_package_to_nix, add_package_to_segment, add_program_to_nix,
+    backup_path, call_package_entry, find_candidate_lists, installed_packages,
+    list_managed_packages, list_packages, list_segments, package_lists, package_scopes,
+    relative_nix_path, remove_flake_input, remove_package_from_nix, remove_program_from_nix,
+    replace_package_in_nix,
+};
+use declair_rs::error::DeclairError;
+use declair_rs::module::{ModuleKind, Scope};
+use declair_rs::paths::get_backups_dir;
+use std::fs;
+use std::path::Path;
+
+fn add_golden(fixture: &str, pkg: &str, golden: &str) {
+    let (_dir, file) = copy_fixture(fixture);
+    add_package_to_nix(&file, pkg, ModuleKind::Nixos).unwrap();
+    assert_golden(golden, &fs::read_to_string(&file).unwrap());
+}
+
+fn remove_golden(fixture: &str, pkg: &str, golden: &str) {
+    let (_dir, file) = copy_fixture(fixture);
+    remove_package_from_nix(&file, pkg, ModuleKind::Nixos).unwrap();
+    assert_golden(golden, &fs::read_to_string(&file).unwrap());
+}
+
+fn replace_golden(fixture: &str, old: &str, new: &str, golden: &str) {
+    let (_dir, file) = copy_fixture(fixture);
+    replace_package_in_nix(&file, old, new, ModuleKind::Nixos).unwrap();
+    assert_golden(golden, &fs::read_to_string(&file).unwrap());
+}
+
+fn list(fixture: &str) -> Vec<String> {
+    let (_dir, file) = copy_fixture(fixture);
+    list_packages(&file, ModuleKind::Nixos).unwrap()
+}
+
+#[test]
+fn add_single_line() {
+    add_golden("single_line.nix", "htop", "single_line.add.nix");
+}
+
+#[test]
+fn add_empty_single_line() {
+    add_golden("empty_single_line.nix", "htop", "empty_single_line.add.nix");
+}
+
+#[test]
+fn add_multiline() {
+    add_golden("multiline.nix", "htop", "multiline.add.nix");
+}
+
+#[test]
+fn add_with_comments() {
+    add_golden("comments.nix", "fd", "comments.add.nix");
+}
+
+#[test]
+fn add_to_first_of_multiple_lists() {
+    add_golden("multiple_lists.nix", "htop", "multiple_lists.add.nix");
+}
+
+#[test]
+fn add_flake() {
+    add_golden("flake.nix", "htop", "flake.add.nix");
+}
+
+#[test]
+fn add_home() {
+    add_golden("home.nix", "ripgrep", "home.add.nix");
+}
+
+#[test]
+fn add_existing_package_fails() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let err = add_package_to_nix(&file, "vim", ModuleKind::Nixos).unwrap_err();
+    assert!(matches!(err, DeclairError::PackageExists { line: 8, .. }));
+}
+
+#[test]
+fn add_without_block_fails() {
+    let (_dir, file) = copy_fixture("no_block.nix");
+    let err = add_package_to_nix(&file, "git", ModuleKind::Nixos).unwrap_err();
+    assert!(matches!(err, DeclairError::BlockNotFound { .. }));
+}
+
+#[test]
+fn add_writes_backup() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let original = fs::read_to_string(&file).unwrap();
+    add_package_to_nix(&file, "htop", ModuleKind::Nixos).unwrap();
+    let backup = fs::read_to_string(file.with_extension("declair.bak")).unwrap();
+    assert_eq!(original, backup);
+}
+
+#[test]
+fn backups_are_kept_out_of_the_configuration() {
+    let system = Path::new("/etc/nixos/configuration.nix");
+    assert_eq!(
+        backup_path(system),
+        get_backups_dir()
+            .unwrap()
+            .join("etc/nixos/configuration.nix.bak")
+    );
+    // Scratch copies in the temp dir keep theirs beside them.
+    let (_dir, file) = copy_fixture("multiline.nix");
+    assert_eq!(backup_path(&file), file.with_extension("declair.bak"));
+}
+
+#[test]
+fn remove_single_line() {
+    remove_golden("single_line.nix", "git", "single_line.remove.nix");
+}
+
+#[test]
+fn remove_multiline() {
+    remove_golden("multiline.nix", "vim", "multiline.remove.nix");
+}
+
+#[test]
+fn remove_with_comments() {
+    remove_golden("comments.nix", "git", "comments.remove.nix");
+}
+
+#[test]
+fn remove_home() {
+    remove_golden("home.nix", "firefox", "home.remove.nix");
+}
+
+#[test]
+fn remove_missing_package_fails() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let err = remove_package_from_nix(&file, "htop", ModuleKind::Nixos).unwrap_err();
+    assert!(matches!(err, DeclairError::PackageNotFound { .. }));
+}
+
+#[test]
+fn replace_single_line() {
+    replace_golden(
+        "single_line.nix",
+        "git",
+        "gitFull",
+        "single_line.replace.nix",
+    );
+}
+
+#[test]
+fn replace_keeps_position_and_comment() {
+    replace_golden("comments.nix", "git", "gitFull", "comments.replace.nix");
+}
+
+#[test]
+fn replace_with_listed_package_fails() {
+    let (_dir, file) = copy_fixture("comments.nix");
+    let err = replace_package_in_nix(&file, "git", "vim", ModuleKind::Nixos).unwrap_err();
+    assert!(matches!(err, DeclairError::PackageExists { line: 6, .. }));
+}
+
+#[test]
+fn replace_missing_package_fails() {
+    // `# htop` is commented out, not listed
+    let (_dir, file) = copy_fixture("comments.nix");
+    let err = replace_package_in_nix(&file, "htop", "btop", ModuleKind::Nixos).unwrap_err();
+    assert!(matches!(err, DeclairError::PackageNotFound { .. }));
+}
+
+#[test]
+fn list_single_line() {
+    assert_eq!(list("single_line.nix"), ["git", "vim"]);
+}
+
+#[test]
+fn list_empty_single_line() {
+    assert!(list("empty_single_line.nix").is_empty());
+}
+
+#[test]
+fn list_multiline() {
+    assert_eq!(list("multiline.nix"), ["git", "vim", "wget"]);
+}
+
+#[test]
+fn list_with_comments() {
+    assert_eq!(list("comments.nix"), ["vim", "git", "ripgrep"]);
+}
+
+#[test]
+fn list_multiple_lists_reads_first() {
+    assert_eq!(list("multiple_lists.nix"), ["git", "curl"]);
+}
+
+#[test]
+fn package_lists_finds_every_block() {
+    let (_dir, file) = copy_fixture("mixed.nix");
+    add_package_to_nix(&file, "ripgrep", ModuleKind::HomeManager).unwrap();
+    let entry = |pkg: &str, managed: bool| (pkg.to_string(), managed);
+    assert_eq!(
+        package_lists(&file).unwrap(),
+        vec![
+            PackageList {
+                attr: "home.packages".to_string(),
+                line: 5,
+                entries: vec![entry("firefox", false), entry("ripgrep", true)],
+            },
+            PackageList {
+                attr: "environment.systemPackages".to_string(),
+                line: 11,
+                entries: vec![entry("git", false)],
+            },
+        ]
+    );
+}
+
+#[test]
+fn package_lists_of_single_line_blocks() {
+    let (_dir, file) = copy_fixture("single_line.nix");
+    let lists = package_lists(&file).unwrap();
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0].attr, "environment.systemPackages");
+    assert!(lists[0].entries.iter().all(|(_, managed)| !managed));
+}
+
+#[test]
+fn list_flake() {
+    assert_eq!(list("flake.nix"), ["git"]);
+}
+
+#[test]
+fn list_home() {
+    assert_eq!(list("home.nix"), ["firefox", "neovim"]);
+}
+
+#[test]
+fn add_program() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    add_program_to_nix(&file, "programs.htop.enable").unwrap();
+    assert_golden(
+        "multiline.add_program.nix",
+        &fs::read_to_string(&file).unwrap(),
+    );
+}
+
+#[test]
+fn add_existing_program_fails() {
+    let (_dir, file) = copy_fixture("programs.nix");
+    let err = add_program_to_nix(&file, "programs.firefox.enable").unwrap_err();
+    assert!(matches!(err, DeclairError::ProgramExists { .. }));
+}
+
+#[test]
+fn remove_program() {
+    let (_dir, file) = copy_fixture("programs.nix");
+    remove_program_from_nix(&file, "programs.firefox.enable").unwrap();
+    assert_golden(
+        "programs.remove_program.nix",
+        &fs::read_to_string(&file).unwrap(),
+    );
+}
+
+#[test]
+fn remove_missing_program_fails() {
+    let (_dir, file) = copy_fixture("programs.nix");
+    let err = remove_program_from_nix(&file, "programs.htop.enable").unwrap_err();
+    assert!(matches!(err, DeclairError::ProgramNotFound { .. }));
+}
+
+#[test]
+fn candidate_lists_are_found() {
+    let (_dir, file) = copy_fixture("plain_list.nix");
+    let candidates = find_candidate_lists(&file).unwrap();
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].line, 3);
+    assert_eq!(candidates[0].text, "home.packages = [");
+}
+
+#[test]
+fn add_adopting_candidate_list() {
+    let (_dir, file) = copy_fixture("plain_list.nix");
+    let candidate = find_candidate_lists(&file).unwrap().remove(0);
+    add_package_resolving_block(
+        &file,
+        "htop",
+        ModuleKind::Nixos,
+        &MissingBlock::Adopt(candidate),
+    )
+    .unwrap();
+    assert_golden("plain_list.adopt.nix", &fs::read_to_string(&file).unwrap());
+}
+
+#[test]
+fn adopting_keeps_what_the_list_means() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("home.nix");
+    fs::write(
+        &file,
+        "{ lib, pkgs, ... }:\n{\n  home.packages = with lib; [\n    pkgs.firefox\n  ];\n  \
+         environment.systemPackages = lib.optionals true [ pkgs.git ];\n  \
+         extraPackages = extra ++ [ pkgs.vim ];\n}\n",
+    )
+    .unwrap();
+    let candidates = find_candidate_lists(&file).unwrap();
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].text, "home.packages = with lib; [");
+
+    add_package_resolving_block(
+        &file,
+        "htop",
+        ModuleKind::Nixos,
+        &MissingBlock::Adopt(candidates[0].clone()),
+    )
+    .unwrap();
+    let contents = fs::read_to_string(&file).unwrap();
+    assert!(contents.contains("home.packages = with lib; with pkgs; [\n"));
+    assert!(contents.contains("lib.optionals true [ pkgs.git ]"));
+    assert!(contents.contains("extra ++ [ pkgs.vim ]"));
+    assert!(contents.contains("pkgs.firefox\n    htop"));
+}
+
+#[test]
+fn add_creating_block() {
+    let (_dir, file) = copy_fixture("no_block.nix");
+    let original = fs::read_to_string(&file).unwrap();
+    let resolution = MissingBlock::Create("environment.systemPackages".to_string());
+    add_package_resolving_block(&file, "htop", ModuleKind::Nixos, &resolution).unwrap();
+    assert_golden("no_block.create.nix", &fs::read_to_string(&file).unwrap());
+    let backup = fs::read_to_string(file.with_extension("declair.bak")).unwrap();
+    assert_eq!(original, backup);
+}
+
+#[test]
+fn add_import_to_existing_list() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    add_import(&file, "./declair-packages.nix").unwrap();
+    assert_golden(
+        "multiline.add_import.nix",
+        &fs::read_to_string(&file).unwrap(),
+    );
+}
+
+#[test]
+fn add_import_creates_list() {
+    let (_dir, file) = copy_fixture("no_block.nix");
+    add_import(&file, "./declair-packages.nix").unwrap();
+    assert_golden(
+        "no_block.add_import.nix",
+        &fs::read_to_string(&file).unwrap(),
+    );
+}
+
+#[test]
+fn add_flake_input_to_inputs_block() {
+    let (_dir, file) = copy_fixture("mixed_flake.nix");
+    add_flake_input(&file, "nur", "github:nix-community/NUR").unwrap();
+    assert_golden(
+        "mixed_flake.add_input.nix",
+        &fs::read_to_string(&file).unwrap(),
+    );
+}
+
+#[test]
+fn add_flake_input_after_dotted_inputs() {
+    let (_dir, file) = copy_fixture("flake.nix");
+    add_flake_input(&file, "nur", "github:nix-community/NUR").unwrap();
+    assert_golden("flake.add_input.nix", &fs::read_to_string(&file).unwrap());
+}
+
+#[test]
+fn remove_flake_input_undoes_add() {
+    for fixture in ["flake.nix", "mixed_flake.nix"] {
+        let (_dir, file) = copy_fixture(fixture);
+        let original = fs::read_to_string(&file).unwrap();
+        add_flake_input(&file, "nur", "github:nix-community/NUR").unwrap();
+        assert!(remove_flake_input(&file, "nur").unwrap());
+        assert_eq!(
+            fs::read_to_string(&file).unwrap().trim_end(),
+            original.trim_end()
+        );
+        assert!(!remove_flake_input(&file, "nur").unwrap());
+    }
+}
+
+#[test]
+fn add_existing_import_fails() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let err = add_import(&file, "./hardware-configuration.nix").unwrap_err();
+    assert!(matches!(err, DeclairError::ImportExists { .. }));
+}
+
+#[test]
+fn add_matches_sibling_indentation() {
+    add_golden("four_spaces.nix", "htop", "four_spaces.add.nix");
+}
+
+#[test]
+fn add_to_empty_list_with_tabs() {
+    add_golden("tabs.nix", "htop", "tabs.add.nix");
+}
+
+#[test]
+fn add_picks_list_of_module_kind() {
+    let (_dir, file) = copy_fixture("mixed.nix");
+    add_package_to_nix(&file, "htop", ModuleKind::Nixos).unwrap();
+    add_package_to_nix(&file, "ripgrep", ModuleKind::HomeManager).unwrap();
+    assert_golden("mixed.add.nix", &fs::read_to_string(&file).unwrap());
+}
+
+#[test]
+fn list_picks_list_of_module_kind() {
+    let (_dir, file) = copy_fixture("mixed.nix");
+    assert_eq!(list_packages(&file, ModuleKind::Nixos).unwrap(), ["git"]);
+    assert_eq!(
+        list_packages(&file, ModuleKind::HomeManager).unwrap(),
+        ["firefox"]
+    );
+}
+
+#[test]
+fn managed_packages_are_only_those_declair_added() {
+    let (_dir, file) = copy_fixture("comments.nix");
+    assert!(
+        list_managed_packages(&file, ModuleKind::Nixos)
+            .unwrap()
+            .is_empty()
+    );
+    add_package_to_nix(&file, "fd", ModuleKind::Nixos).unwrap();
+    assert_eq!(
+        list_managed_packages(&file, ModuleKind::Nixos).unwrap(),
+        ["fd"]
+    );
+    assert!(
+        list_packages(&file, ModuleKind::Nixos)
+            .unwrap()
+            .contains(&"fd".to_string())
+    );
+}
+
+#[test]
+fn managed_entry_can_be_removed() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let original = list_packages(&file, ModuleKind::Nixos).unwrap();
+    add_package_to_nix(&file, "htop", ModuleKind::Nixos).unwrap();
+    remove_package_from_nix(&file, "htop", ModuleKind::Nixos).unwrap();
+    assert_eq!(list_packages(&file, ModuleKind::Nixos).unwrap(), original);
+    assert!(!fs::read_to_string(&file).unwrap().contains("htop"));
+}
+
+#[test]
+fn commented_out_package_is_not_present() {
+    let (_dir, file) = copy_fixture("comments.nix");
+    add_package_to_nix(&file, "htop", ModuleKind::Nixos).unwrap();
+    assert_eq!(
+        list_managed_packages(&file, ModuleKind::Nixos).unwrap(),
+        ["htop"]
+    );
+}
+
+#[test]
+fn installed_packages_span_every_file() {
+    let (dir, _file) = copy_fixture("multiline.nix");
+    fs::create_dir_all(dir.path().join("home")).unwrap();
+    fs::write(
+        dir.path().join("home/default.nix"),
+        "{ pkgs, ... }: {\n  home.packages = with pkgs; [\n    ripgrep # managed by declair\n    fd\n  ];\n}\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("broken.nix"), "{ ").unwrap();
+    let mut installed: Vec<String> = installed_packages(dir.path()).into_iter().collect();
+    installed.sort();
+    assert_eq!(installed, ["fd", "git", "ripgrep", "vim", "wget"]);
+}
+
+#[test]
+fn local_derivations_are_relative_to_the_edited_file() {
+    let host = Path::new("/etc/nixos/hosts/laptop.nix");
+    assert_eq!(
+        relative_nix_path(host, Path::new("/etc/nixos/hosts/pkgs/foo.nix")),
+        "./pkgs/foo.nix"
+    );
+    assert_eq!(
+        relative_nix_path(host, Path::new("/etc/nixos/pkgs/foo.nix")),
+        "../pkgs/foo.nix"
+    );
+    assert_eq!(
+        call_package_entry(host, Path::new("/etc/nixos/pkgs/foo.nix")),
+        "(callPackage ../pkgs/foo.nix { })"
+    );
+}
+
+#[test]
+fn call_package_entries_are_single_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    let entry = "(callPackage ./pkgs/foo.nix { })";
+    fs::write(
+        &file,
+        "{ pkgs, ... }:\n{\n  environment.systemPackages = with pkgs; [\n    git\n  ];\n}\n",
+    )
+    .unwrap();
+    add_package_to_nix(&file, entry, ModuleKind::Nixos).unwrap();
+    assert_eq!(
+        list_packages(&file, ModuleKind::Nixos).unwrap(),
+        ["git", entry]
+    );
+    assert!(matches!(
+        add_package_to_nix(&file, entry, ModuleKind::Nixos),
+        Err(DeclairError::PackageExists { .. })
+    ));
+    remove_package_from_nix(&file, entry, ModuleKind::Nixos).unwrap();
+    assert_eq!(list_packages(&file, ModuleKind::Nixos).unwrap(), ["git"]);
+
+    fs::write(
+        &file,
+        "{ pkgs, ... }:\n{\n  environment.systemPackages = with pkgs; [ git (callPackage ./a.nix { }) ];\n}\n",
+    )
+    .unwrap();
+    assert_eq!(
+        list_packages(&file, ModuleKind::Nixos).unwrap(),
+        ["git", "(callPackage ./a.nix { })"]
+    );
+}
+
+#[test]
+fn conditional_segments_on_one_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(
+        &file,
+        "{ pkgs, lib, ... }:\n{\n  environment.systemPackages = with pkgs; [ git ] ++ lib.optionals stdenv.isLinux [ strace ];\n}\n",
+    )
+    .unwrap();
+    assert_eq!(
+        list_packages(&file, ModuleKind::Nixos).unwrap(),
+        ["git", "strace"]
+    );
+    add_package_to_nix(&file, "htop", ModuleKind::Nixos).unwrap();
+    add_package_to_segment(&file, "perf", ModuleKind::Nixos, 1).unwrap();
+    assert!(
+        fs::read_to_string(&file)
+            .unwrap()
+            .contains("with pkgs; [ git htop ] ++ lib.optionals stdenv.isLinux [ strace perf ];")
+    );
+    assert!(matches!(
+        add_package_to_nix(&file, "strace", ModuleKind::Nixos),
+        Err(DeclairError::PackageExists { .. })
+    ));
+    remove_package_from_nix(&file, "strace", ModuleKind::Nixos).unwrap();
+    assert!(
+        fs::read_to_string(&file)
+            .unwrap()
+            .contains("with pkgs; [ git htop ] ++ lib.optionals stdenv.isLinux [ perf ];")
+    );
+}
+
+#[test]
+fn conditional_segments_over_several_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(
+        &file,
+        "{ pkgs, lib, ... }:\n{\n  environment.systemPackages =\n    with pkgs; [\n      git\n      (python3.withPackages (ps: [ ps.requests ]))\n    ]\n    ++ lib.optionals (!stdenv.isDarwin) [\n      strace # tracing\n    ];\n}\n",
+    )
+    .unwrap();
+    assert_eq!(
+        list_segments(&file, ModuleKind::Nixos).unwrap(),
+        [
+            ListSegment {
+                condition: None,
+                entries: vec![
+                    ("git".to_string(), false),
+                    (
+                        "(python3.withPackages (ps: [ ps.requests ]))".to_string(),
+                        false
+                    ),
+                ],
+            },
+            ListSegment {
+                condition: Some("lib.optionals (!stdenv.isDarwin)".to_string()),
+                entries: vec![("strace".to_string(), false)],
+            },
+        ]
+    );
+    add_package_to_segment(&file, "ltrace", ModuleKind::Nixos, 1).unwrap();
+    add_package_to_nix(&file, "htop", ModuleKind::Nixos).unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{ pkgs, lib, ... }:\n{\n  environment.systemPackages =\n    with pkgs; [\n      git\n      (python3.withPackages (ps: [ ps.requests ]))\n      htop # managed by declair\n    ]\n    ++ lib.optionals (!stdenv.isDarwin) [\n      strace # tracing\n      ltrace # managed by declair\n    ];\n}"
+    );
+    remove_package_from_nix(&file, "ltrace", ModuleKind::Nixos).unwrap();
+    assert_eq!(
+        list_managed_packages(&file, ModuleKind::Nixos).unwrap(),
+        ["htop"]
+    );
+}
+
+#[test]
+fn inherit_from_pkgs_on_one_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(
+        &file,
+        "{ pkgs, ... }:\n{\n  environment.systemPackages = builtins.attrValues { inherit (pkgs) git ripgrep; };\n}\n",
+    )
+    .unwrap();
+    assert_eq!(
+        list_packages(&file, ModuleKind::Nixos).unwrap(),
+        ["git", "ripgrep"]
+    );
+    add_package_to_nix(&file, "htop", ModuleKind::Nixos).unwrap();
+    replace_package_in_nix(&file, "git", "gitFull", ModuleKind::Nixos).unwrap();
+    remove_package_from_nix(&file, "ripgrep", ModuleKind::Nixos).unwrap();
+    assert!(
+        fs::read_to_string(&file)
+            .unwrap()
+            .contains("builtins.attrValues { inherit (pkgs) gitFull htop; };")
+    );
+    assert!(matches!(
+        add_package_to_nix(&file, "htop", ModuleKind::Nixos),
+        Err(DeclairError::PackageExists { .. })
+    ));
+    assert!(matches!(
+        add_package_to_nix(&file, "python3Packages.requests", ModuleKind::Nixos),
+        Err(DeclairError::NotInheritable { .. })
+    ));
+}
+
+#[test]
+fn inherit_from_pkgs_over_several_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("home.nix");
+    fs::write(
+        &file,
+        "{ pkgs, ... }:\n{\n  devShell = with pkgs; [ cargo ];\n  home.packages = builtins.attrValues {\n    inherit (pkgs)\n      git\n      ripgrep;\n  };\n}\n",
+    )
+    .unwrap();
+    assert_eq!(
+        list_packages(&file, ModuleKind::HomeManager).unwrap(),
+        ["git", "ripgrep"]
+    );
+    add_package_to_nix(&file, "fd", ModuleKind::HomeManager).unwrap();
+    remove_package_from_nix(&file, "ripgrep", ModuleKind::HomeManager).unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{ pkgs, ... }:\n{\n  devShell = with pkgs; [ cargo ];\n  home.packages = builtins.attrValues {\n    inherit (pkgs)\n      git\n      fd # managed by declair\n      ;\n  };\n}"
+    );
+    assert_eq!(
+        package_lists(&file)
+            .unwrap()
+            .into_iter()
+            .map(|list| list.attr)
+            .collect::<Vec<_>>(),
+        ["devShell", "home.packages"]
+    );
+}
+
+#[test]
+fn user_package_lists() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(
+        &file,
+        "{ pkgs, ... }:\n{\n  users.users.alice = {\n    isNormalUser = true;\n    packages = with pkgs; [\n      firefox\n    ];\n  };\n  users.users.\"bob\".packages = with pkgs; [ tmux ];\n  environment.systemPackages = with pkgs; [ git ];\n}\n",
+    )
+    .unwrap();
+    let alice = Scope::User("alice".to_string());
+    let bob = Scope::User("bob".to_string());
+    assert_eq!(
+        package_scopes(&file, ModuleKind::Nixos).unwrap(),
+        [Scope::System, alice.clone(), bob.clone()]
+    );
+    assert_eq!(list_packages(&file, &alice).unwrap(), ["firefox"]);
+    assert_eq!(list_packages(&file, &bob).unwrap(), ["tmux"]);
+    assert_eq!(list_packages(&file, ModuleKind::Nixos).unwrap(), ["git"]);
+
+    add_package_to_nix(&file, "thunderbird", &alice).unwrap();
+    remove_package_from_nix(&file, "tmux", &bob).unwrap();
+    assert_eq!(
+        list_packages(&file, &alice).unwrap(),
+        ["firefox", "thunderbird"]
+    );
+    assert!(list_packages(&file, &bob).unwrap().is_empty());
+    assert!(matches!(
+        list_packages(&file, Scope::User("carol".to_string())),
+        Err(DeclairError::BlockNotFound { .. })
+    ));
+}
+
+#[test]
+fn system_scope_leaves_user_lists_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(
+        &file,
+        "{ pkgs, ... }:\n{\n  users.users.alice = {\n    packages = with pkgs; [ firefox ];\n  };\n}\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        add_package_to_nix(&file, "git", ModuleKind::Nixos),
+        Err(DeclairError::BlockNotFound { .. })
+    ));
+    assert_eq!(
+        package_scopes(&file, ModuleKind::Nixos).unwrap(),
+        [Scope::User("alice".to_string())]
+    );
+}
+
+#[test]
+fn fonts_list_is_its_own_scope() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(
+        &file,
+        "{ pkgs, ... }:\n{\n  fonts.packages = with pkgs; [ noto-fonts ];\n  environment.systemPackages = with pkgs; [ git ];\n}\n",
+    )
+    .unwrap();
+    assert_eq!(list_packages(&file, Scope::Fonts).unwrap(), ["noto-fonts"]);
+    assert_eq!(list_packages(&file, Scope::System).unwrap(), ["git"]);
+    assert_eq!(
+        package_scopes(&file, ModuleKind::Nixos).unwrap(),
+        [Scope::System, Scope::Fonts]
+    );
+
+    // without a systemPackages list, the fonts list is not a fallback for it
+    fs::write(
+        &file,
+        "{ pkgs, ... }:\n{\n  fonts.packages = with pkgs; [ noto-fonts ];\n}\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        add_package_to_nix(&file, "git", ModuleKind::Nixos),
+        Err(DeclairError::BlockNotFound { .. })
+    ));
+    add_package_to_nix(&file, "fira-code", Scope::Fonts).unwrap();
+    assert_eq!(
+        list_packages(&file, Scope::Fonts).unwrap(),
+        ["noto-fonts", "fira-code"]
+    );
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::audit::Nixpkgs;
+use declair_rs::evalcache::{EvalCache, lock, pinned_revision};
+use declair_rs::info::package_details;
+
+const REV: &str = "0123456789abcdef0123456789abcdef01234567";
+
+#[test]
+fn revisions_are_read_from_pinned_references() {
+    assert_eq!(
+        pinned_revision(&format!("github:NixOS/nixpkgs/{}", REV)),
+        Some(REV)
+    );
+    assert_eq!(
+        pinned_revision(&format!("git+https://example.org/nixpkgs?rev={}", REV)),
+        Some(REV)
+    );
+    assert_eq!(pinned_revision("github:NixOS/nixpkgs/nixos-unstable"), None);
+    assert_eq!(pinned_revision("nixpkgs"), None);
+}
+
+#[test]
+fn registry_references_are_locked_with_flake_metadata() {
+    let metadata = FakeRunner::new().answer("nix", &[], 0, r#"{"locked":{"rev":"0123456789abcdef0123456789abcdef01234567"},"url":"github:NixOS/nixpkgs/0123456789abcdef0123456789abcdef01234567?narHash=sha256-x"}"#);
+    let (revision, locked) = lock(&metadata, &Nixpkgs::Flake("nixpkgs".to_string())).unwrap();
+    assert_eq!(revision, REV);
+    assert!(matches!(locked, Nixpkgs::Flake(url) if url.contains(REV)));
+
+    let pinned = FakeRunner::new().answer("nix-instantiate", &[], 1, "");
+    let reference = Nixpkgs::Flake(format!("github:NixOS/nixpkgs/{}", REV));
+    assert_eq!(lock(&pinned, &reference).unwrap().1, reference);
+    assert_eq!(pinned.count(), 0);
+    assert!(lock(&pinned, &Nixpkgs::NixPath).is_none());
+}
+
+#[test]
+fn cached_evaluations_are_not_repeated() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = EvalCache::new(dir.path(), REV);
+    let runner = FakeRunner::new().answer(
+        "nix",
+        &[],
+        0,
+        r#"{"version":"14.1.0","description":"Search tool"}"#,
+    );
+    let nixpkgs = Nixpkgs::Flake(format!("github:NixOS/nixpkgs/{}", REV));
+    for _ in 0..2 {
+        let details = package_details(&runner, &nixpkgs, Some(&cache), "ripgrep").unwrap();
+        assert_eq!(details.version.as_deref(), Some("14.1.0"));
+    }
+    assert_eq!(runner.count(), 1);
+
+    let other = EvalCache::new(dir.path(), "another-revision");
+    assert!(other.get("details", "ripgrep").is_none());
+}
+
+#[test]
+fn failed_evaluations_are_not_cached() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = EvalCache::new(dir.path(), REV);
+    let failing = FakeRunner::new().answer("nix", &[], 1, "");
+    let nixpkgs = Nixpkgs::NixPath;
+    assert!(package_details(&failing, &nixpkgs, Some(&cache), "ripgrep").is_err());
+    assert!(cache.get("details", "ripgrep").is_none());
+}
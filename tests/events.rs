@@ -0,0 +1,158 @@
+use declair_rs::events::{
+    Event, NixLine, NixLog, derivation_name, emit, event_sink, set_event_sink, wants_nix_log,
+};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn events_are_camel_case_json() {
+    let event = Event::EditApplied {
+        action: "add".into(),
+        packages: vec!["ripgrep".into()],
+        file: PathBuf::from("/etc/nixos/configuration.nix"),
+    };
+    assert_eq!(
+        event.to_line(),
+        r#"{"event":"editApplied","action":"add","packages":["ripgrep"],"file":"/etc/nixos/configuration.nix"}"#
+    );
+    let event = Event::RebuildFinished {
+        success: false,
+        exit_code: Some(1),
+    };
+    assert_eq!(
+        event.to_line(),
+        r#"{"event":"rebuildFinished","success":false,"exitCode":1}"#
+    );
+}
+
+#[test]
+fn emitted_events_are_written_one_per_line() {
+    let buffer = Buffer::default();
+    set_event_sink(Box::new(buffer.clone()));
+    emit(&Event::SearchStarted {
+        query: "firefox".into(),
+    });
+    emit(&Event::SearchFinished { results: 3 });
+    let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(
+        written,
+        "{\"event\":\"searchStarted\",\"query\":\"firefox\"}\n\
+         {\"event\":\"searchFinished\",\"results\":3}\n"
+    );
+}
+
+#[test]
+fn closed_descriptors_are_rejected() {
+    assert!(event_sink(None).is_ok());
+    let err = event_sink(Some(9999)).err().unwrap();
+    assert_eq!(err.exit_code(), 2);
+}
+
+#[test]
+fn the_nix_log_is_asked_for_with_both_words() {
+    let args = |words: &[&str]| words.iter().map(ToString::to_string).collect::<Vec<_>>();
+    assert!(wants_nix_log(&args(&[
+        "switch",
+        "--log-format",
+        "internal-json"
+    ])));
+    assert!(!wants_nix_log(&args(&["switch", "--log-format", "bar"])));
+    assert!(!wants_nix_log(&args(&["internal-json"])));
+}
+
+#[test]
+fn build_progress_becomes_events() {
+    let mut log = NixLog::new();
+    assert_eq!(
+        log.line(r#"@nix {"action":"start","id":7,"level":0,"parent":0,"text":"","type":104,"fields":[]}"#),
+        NixLine::Quiet
+    );
+    assert_eq!(
+        log.line(r#"@nix {"action":"result","id":7,"type":105,"fields":[2,5,1,0]}"#),
+        NixLine::Progress(Event::RebuildProgress {
+            done: 2,
+            expected: 5,
+            running: 1,
+            failed: 0,
+        })
+    );
+    // Progress of other activities, like downloads, isn't build progress.
+    assert_eq!(
+        log.line(r#"@nix {"action":"result","id":8,"type":105,"fields":[100,200,0,0]}"#),
+        NixLine::Quiet
+    );
+    assert_eq!(log.line(r#"@nix {"action":"stop","id":7}"#), NixLine::Quiet);
+    assert_eq!(
+        log.line(r#"@nix {"action":"result","id":7,"type":105,"fields":[5,5,0,0]}"#),
+        NixLine::Quiet
+    );
+}
+
+#[test]
+fn messages_become_text_again() {
+    let mut log = NixLog::new();
+    assert_eq!(
+        log.line(r#"@nix {"action":"msg","level":0,"msg":"error: undefined variable 'foo'"}"#),
+        NixLine::Text("error: undefined variable 'foo'".into())
+    );
+    assert_eq!(
+        log.line(r#"@nix {"action":"msg","level":5,"msg":"evaluating file"}"#),
+        NixLine::Quiet
+    );
+    assert_eq!(
+        log.line(r#"@nix {"action":"start","id":9,"level":3,"parent":0,"text":"building '/nix/store/abc-foo.drv'","type":105,"fields":[]}"#),
+        NixLine::Activity("building '/nix/store/abc-foo.drv'".into())
+    );
+    assert_eq!(
+        log.line("building the system configuration..."),
+        NixLine::Text("building the system configuration...".into())
+    );
+}
+
+#[test]
+fn builds_and_downloads_are_tracked() {
+    let mut log = NixLog::new();
+    let hash = "0123456789abcdfghijklmnpqrsvwxyz";
+    log.line(&format!(
+        r#"@nix {{"action":"start","id":3,"level":3,"parent":0,"text":"building","type":105,"fields":["/nix/store/{}-hello-2.12.drv","",1,1]}}"#,
+        hash
+    ));
+    log.line(
+        r#"@nix {"action":"start","id":4,"level":0,"parent":0,"text":"","type":103,"fields":[]}"#,
+    );
+    assert_eq!(log.building(), ["hello-2.12"]);
+    assert_eq!(log.downloads(), None);
+    log.line(r#"@nix {"action":"result","id":4,"type":105,"fields":[1,6,2,0]}"#);
+    assert_eq!(log.downloads(), Some((1, 6)));
+    assert_eq!(
+        log.line(
+            r#"@nix {"action":"result","id":3,"type":101,"fields":["checking for gcc... yes"]}"#
+        ),
+        NixLine::BuildLog("hello-2.12> checking for gcc... yes".into())
+    );
+    log.line(r#"@nix {"action":"stop","id":3}"#);
+    assert!(log.building().is_empty());
+}
+
+#[test]
+fn derivation_names_drop_the_store_path() {
+    assert_eq!(
+        derivation_name("/nix/store/0123456789abcdfghijklmnpqrsvwxyz-hello-2.12.drv"),
+        "hello-2.12"
+    );
+    assert_eq!(derivation_name("hello-2.12"), "hello-2.12");
+}
@@ -0,0 +1,82 @@
+use clap::Command;
+use declair_rs::examples::{
+    COMMAND_EXAMPLES, WORKFLOWS, command_examples, man_page, render_workflows, with_examples,
+    write_man_pages,
+};
+use std::fs;
+
+fn cli() -> Command {
+    Command::new("declair-rs")
+        .version("1.0")
+        .subcommand(Command::new("add").about("Add several packages"))
+        .subcommand(Command::new("group").subcommand(Command::new("list")))
+}
+
+#[test]
+fn help_ends_with_the_subcommand_examples() {
+    let mut cmd = with_examples(cli());
+    let help = cmd
+        .find_subcommand_mut("add")
+        .unwrap()
+        .render_long_help()
+        .to_string();
+    assert!(help.contains("Examples:\n  # Add several packages, review the diff and rebuild once\n  $ declair-rs add ripgrep fd bat"));
+}
+
+#[test]
+fn every_example_starts_with_the_binary() {
+    for (name, examples) in COMMAND_EXAMPLES {
+        assert!(!examples.is_empty(), "{} has no examples", name);
+        for example in *examples {
+            assert!(
+                example.command.starts_with("declair-rs "),
+                "{}",
+                example.command
+            );
+        }
+    }
+    assert!(command_examples("no-such-command").is_empty());
+}
+
+#[test]
+fn man_pages_have_an_examples_section() {
+    let page = man_page(cli());
+    assert!(page.contains(".SH EXAMPLES"));
+    assert!(page.contains(".SH VERSION"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let written = write_man_pages(cli(), dir.path()).unwrap();
+    let names: Vec<String> = written
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "declair-rs.1",
+            "declair-rs-add.1",
+            "declair-rs-group.1",
+            "declair-rs-group-list.1"
+        ]
+    );
+    let add = fs::read_to_string(dir.path().join("declair-rs-add.1")).unwrap();
+    assert!(add.contains(".SH EXAMPLES"));
+    assert!(add.contains(r"declair\-rs add ripgrep fd bat"));
+    let list = fs::read_to_string(dir.path().join("declair-rs-group-list.1")).unwrap();
+    assert!(!list.contains(".SH EXAMPLES"));
+}
+
+#[test]
+fn workflows_are_titled_shell_comments() {
+    let text = render_workflows(&WORKFLOWS[..1]);
+    assert_eq!(
+        text,
+        "# Try a program before installing it\n\
+         declair-rs search ripgrep\n\
+         declair-rs run ripgrep -- --version\n\
+         declair-rs -p ripgrep\n"
+    );
+    for workflow in WORKFLOWS {
+        assert!(!render_workflows(std::slice::from_ref(workflow)).contains("examples."));
+    }
+}
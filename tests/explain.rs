@@ -0,0 +1,28 @@
+use declair_rs::explain::Topic;
+use declair_rs::module::ModuleKind;
+
+const TOPICS: [Topic; 9] = [
+    Topic::Search,
+    Topic::PackageList,
+    Topic::ProgramModule,
+    Topic::Removal,
+    Topic::Rebuild(ModuleKind::Nixos),
+    Topic::Rebuild(ModuleKind::HomeManager),
+    Topic::Flake,
+    Topic::Generations(ModuleKind::Nixos),
+    Topic::Generations(ModuleKind::HomeManager),
+];
+
+#[test]
+fn every_topic_has_its_own_explanation() {
+    let texts: Vec<String> = TOPICS.iter().map(|topic| topic.text()).collect();
+    for (idx, text) in texts.iter().enumerate() {
+        assert!(!text.starts_with("explain."), "untranslated: {}", text);
+        assert!(!texts[idx + 1..].contains(text), "repeated: {}", text);
+    }
+}
+
+#[test]
+fn package_list_explains_with_pkgs() {
+    assert!(Topic::PackageList.text().contains("with pkgs;"));
+}
@@ -0,0 +1,50 @@
+use declair_rs::fix::{last_history_command, missing_command};
+
+#[test]
+fn shell_errors_name_the_missing_command() {
+    for message in [
+        "zsh: command not found: rg",
+        "bash: rg: command not found",
+        "fish: Unknown command: rg",
+        "sh: 1: rg: not found",
+        "/nix/store/abc-builder.sh: line 3: rg: command not found",
+        "error: builder failed\n> bash: rg: command not found\n",
+        "Unknown command: 'rg'",
+        "rg",
+    ] {
+        assert_eq!(
+            missing_command(message).as_deref(),
+            Some("rg"),
+            "{}",
+            message
+        );
+    }
+}
+
+#[test]
+fn messages_without_a_command_are_rejected() {
+    assert_eq!(missing_command("everything is fine"), None);
+    assert_eq!(missing_command(""), None);
+}
+
+#[test]
+fn last_command_of_each_history_format() {
+    assert_eq!(
+        last_history_command("ls\nrg foo src\n").as_deref(),
+        Some("rg")
+    );
+    assert_eq!(
+        last_history_command(": 1700000000:0;ls\n: 1700000001:0;convert a.png b.jpg\n").as_deref(),
+        Some("convert")
+    );
+    assert_eq!(
+        last_history_command("- cmd: fd -e rs\n  when: 1700000000\n").as_deref(),
+        Some("fd")
+    );
+}
+
+#[test]
+fn history_skips_declair_sudo_and_env_assignments() {
+    let history = "LANG=C sudo htop\ndeclair-rs fix\n/usr/bin/declair fix\n";
+    assert_eq!(last_history_command(history).as_deref(), Some("htop"));
+}
@@ -0,0 +1,12 @@
+{ config, pkgs, ... }:
+
+{
+  environment.systemPackages = with pkgs; [
+    # editors
+    vim
+
+    git # version control
+    # htop
+    ripgrep
+  ];
+}
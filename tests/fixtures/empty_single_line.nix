@@ -0,0 +1,5 @@
+{ config, pkgs, ... }:
+
+{
+  environment.systemPackages = with pkgs; [];
+}
@@ -0,0 +1,13 @@
+{ config, pkgs, ... }:
+
+{
+  environment.systemPackages = with pkgs; [
+    # editors
+    vim
+
+    git # version control
+    # htop
+    ripgrep
+    fd # managed by declair
+  ];
+}
\ No newline at end of file
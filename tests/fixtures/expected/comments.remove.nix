@@ -0,0 +1,11 @@
+{ config, pkgs, ... }:
+
+{
+  environment.systemPackages = with pkgs; [
+    # editors
+    vim
+
+    # htop
+    ripgrep
+  ];
+}
\ No newline at end of file
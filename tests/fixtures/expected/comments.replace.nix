@@ -0,0 +1,12 @@
+{ config, pkgs, ... }:
+
+{
+  environment.systemPackages = with pkgs; [
+    # editors
+    vim
+
+    gitFull # version control
+    # htop
+    ripgrep
+  ];
+}
\ No newline at end of file
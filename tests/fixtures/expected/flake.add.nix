@@ -0,0 +1,17 @@
+{
+  inputs.nixpkgs.url = "github:nixos/nixpkgs?ref=nixos-unstable";
+
+  outputs = { self, nixpkgs, ... }: {
+    nixosConfigurations.host = nixpkgs.lib.nixosSystem {
+      system = "x86_64-linux";
+      modules = [
+        ({ pkgs, ... }: {
+          environment.systemPackages = with pkgs; [
+            git
+            htop # managed by declair
+          ];
+        })
+      ];
+    };
+  };
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+{ config, pkgs, ... }:
+
+{
+    environment.systemPackages = with pkgs; [
+        git
+        htop # managed by declair
+    ];
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+{ config, pkgs, ... }:
+
+{
+  home.username = "user";
+  home.homeDirectory = "/home/user";
+
+  home.packages = with pkgs; [
+    firefox
+    neovim
+    ripgrep # managed by declair
+  ];
+
+  home.stateVersion = "24.05";
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+{ config, pkgs, ... }:
+
+{
+  home.username = "user";
+  home.homeDirectory = "/home/user";
+
+  home.packages = with pkgs; [
+    neovim
+  ];
+
+  home.stateVersion = "24.05";
+}
\ No newline at end of file
@@ -0,0 +1,15 @@
+{ config, pkgs, ... }:
+
+{
+  home-manager.users.alice = {
+    home.packages = with pkgs; [
+      firefox
+      ripgrep # managed by declair
+    ];
+  };
+
+  environment.systemPackages = with pkgs; [
+    git
+    htop # managed by declair
+  ];
+}
\ No newline at end of file
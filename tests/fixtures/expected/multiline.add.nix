@@ -0,0 +1,14 @@
+{ config, pkgs, ... }:
+
+{
+  imports = [ ./hardware-configuration.nix ];
+
+  environment.systemPackages = with pkgs; [
+    git
+    vim
+    wget
+    htop # managed by declair
+  ];
+
+  services.openssh.enable = true;
+}
\ No newline at end of file
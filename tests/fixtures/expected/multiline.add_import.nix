@@ -0,0 +1,13 @@
+{ config, pkgs, ... }:
+
+{
+  imports = [ ./hardware-configuration.nix ./declair-packages.nix ];
+
+  environment.systemPackages = with pkgs; [
+    git
+    vim
+    wget
+  ];
+
+  services.openssh.enable = true;
+}
\ No newline at end of file
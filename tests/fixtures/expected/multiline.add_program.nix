@@ -0,0 +1,14 @@
+{ config, pkgs, ... }:
+
+{
+  imports = [ ./hardware-configuration.nix ];
+
+  environment.systemPackages = with pkgs; [
+    git
+    vim
+    wget
+  ];
+
+  services.openssh.enable = true;
+  programs.htop.enable = true;
+}
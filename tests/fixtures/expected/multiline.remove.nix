@@ -0,0 +1,12 @@
+{ config, pkgs, ... }:
+
+{
+  imports = [ ./hardware-configuration.nix ];
+
+  environment.systemPackages = with pkgs; [
+    git
+    wget
+  ];
+
+  services.openssh.enable = true;
+}
\ No newline at end of file
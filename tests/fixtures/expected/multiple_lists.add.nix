@@ -0,0 +1,13 @@
+{ config, pkgs, ... }:
+
+{
+  environment.systemPackages = with pkgs; [
+    git
+    curl
+    htop # managed by declair
+  ];
+
+  fonts.packages = with pkgs; [
+    noto-fonts
+  ];
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+{ config, pkgs, ... }:
+
+{
+  services.openssh.enable = true;
+  imports = [ ./declair-packages.nix ];
+}
\ No newline at end of file
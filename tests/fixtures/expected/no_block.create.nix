@@ -0,0 +1,9 @@
+{ config, pkgs, ... }:
+
+{
+  services.openssh.enable = true;
+
+  environment.systemPackages = with pkgs; [
+    htop # managed by declair
+  ];
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+{ config, pkgs, ... }:
+
+{
+  home.packages = with pkgs; [
+    pkgs.firefox
+    htop # managed by declair
+  ];
+}
\ No newline at end of file
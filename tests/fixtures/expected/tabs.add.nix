@@ -0,0 +1,7 @@
+{ config, pkgs, ... }:
+
+{
+	environment.systemPackages = with pkgs; [
+		htop # managed by declair
+	];
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+{ config, pkgs, ... }:
+
+{
+  home.username = "user";
+  home.homeDirectory = "/home/user";
+
+  home.packages = with pkgs; [
+    firefox
+    neovim
+  ];
+
+  home.stateVersion = "24.05";
+}
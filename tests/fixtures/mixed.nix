@@ -0,0 +1,13 @@
+{ config, pkgs, ... }:
+
+{
+  home-manager.users.alice = {
+    home.packages = with pkgs; [
+      firefox
+    ];
+  };
+
+  environment.systemPackages = with pkgs; [
+    git
+  ];
+}
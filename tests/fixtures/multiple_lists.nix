@@ -0,0 +1,12 @@
+{ config, pkgs, ... }:
+
+{
+  environment.systemPackages = with pkgs; [
+    git
+    curl
+  ];
+
+  fonts.packages = with pkgs; [
+    noto-fonts
+  ];
+}
@@ -0,0 +1,7 @@
+{ config, pkgs, ... }:
+
+{
+  home.packages = [
+    pkgs.firefox
+  ];
+}
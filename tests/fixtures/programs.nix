@@ -0,0 +1,9 @@
+{ config, pkgs, ... }:
+
+{
+  programs.firefox.enable = true;
+
+  environment.systemPackages = with pkgs; [
+    git
+  ];
+}
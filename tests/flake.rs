@@ -0,0 +1,203 @@
+mod common;
+
+use common::fixtures_dir;
+use declair_rs::config::Config;
+use declair_rs::flake::{
+    FlakeOutput, find_flake_dir, flake_file, flake_outputs, locked_input, locked_nixpkgs,
+    owning_output,
+};
+use declair_rs::module::ModuleKind;
+use declair_rs::rebuild::{rebuild_command, rebuild_target, update_command};
+use std::fs;
+use std::path::Path;
+
+fn output(kind: ModuleKind, name: &str) -> FlakeOutput {
+    FlakeOutput {
+        kind,
+        name: name.to_string(),
+    }
+}
+
+/// A flake checkout with `mixed_flake.nix` as its `flake.nix` and the modules it imports.
+fn flake_checkout() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    fs::copy(
+        fixtures_dir().join("mixed_flake.nix"),
+        dir.path().join("flake.nix"),
+    )
+    .unwrap();
+    fs::create_dir_all(dir.path().join("hosts/laptop")).unwrap();
+    for file in [
+        "hosts/laptop/default.nix",
+        "common.nix",
+        "home.nix",
+        "unused.nix",
+    ] {
+        fs::write(dir.path().join(file), "{ }").unwrap();
+    }
+    dir
+}
+
+#[test]
+fn parses_outputs_and_their_modules() {
+    let contents = fs::read_to_string(fixtures_dir().join("mixed_flake.nix")).unwrap();
+    let outputs = flake_outputs(&contents);
+    assert_eq!(
+        outputs,
+        [
+            (
+                output(ModuleKind::Nixos, "laptop"),
+                vec!["hosts/laptop".to_string(), "common.nix".to_string()]
+            ),
+            (
+                output(ModuleKind::HomeManager, "alice@laptop"),
+                vec!["home.nix".to_string()]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn maps_files_to_owning_output() {
+    let dir = flake_checkout();
+    let owner = |file: &str| owning_output(dir.path(), &dir.path().join(file)).unwrap();
+    assert_eq!(
+        owner("home.nix"),
+        Some(output(ModuleKind::HomeManager, "alice@laptop"))
+    );
+    assert_eq!(
+        owner("hosts/laptop/default.nix"),
+        Some(output(ModuleKind::Nixos, "laptop"))
+    );
+    assert_eq!(
+        owner("common.nix"),
+        Some(output(ModuleKind::Nixos, "laptop"))
+    );
+    assert_eq!(owner("unused.nix"), None);
+    assert_eq!(owner("flake.nix"), None);
+}
+
+#[test]
+fn no_flake_means_no_owner() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("home.nix");
+    fs::write(&file, "{ }").unwrap();
+    assert_eq!(owning_output(dir.path(), &file).unwrap(), None);
+    assert_eq!(
+        owning_output(Path::new("/nonexistent"), &file).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn finds_the_nearest_flake_below_the_repo() {
+    let repo = tempfile::tempdir().unwrap();
+    let nix = repo.path().join("nix");
+    fs::create_dir_all(nix.join("hosts")).unwrap();
+    fs::write(nix.join("flake.nix"), "{ }").unwrap();
+    fs::write(nix.join("hosts/laptop.nix"), "{ }").unwrap();
+    assert_eq!(
+        find_flake_dir(repo.path(), &nix.join("hosts/laptop.nix")),
+        Some(nix.clone())
+    );
+
+    // A module outside the flake's directory still finds the repository's only flake.
+    fs::create_dir(repo.path().join("modules")).unwrap();
+    fs::write(repo.path().join("modules/common.nix"), "{ }").unwrap();
+    let common = repo.path().join("modules/common.nix");
+    assert_eq!(find_flake_dir(repo.path(), &common), Some(nix.clone()));
+    assert_eq!(flake_file(repo.path(), &common), nix.join("flake.nix"));
+
+    // With two candidates there is no telling which one builds the module.
+    fs::create_dir(repo.path().join("other")).unwrap();
+    fs::write(repo.path().join("other/flake.nix"), "{ }").unwrap();
+    assert_eq!(find_flake_dir(repo.path(), &common), None);
+    assert_eq!(
+        flake_file(repo.path(), &common),
+        repo.path().join("flake.nix")
+    );
+}
+
+#[test]
+fn rebuilds_a_flake_in_a_subdirectory() {
+    let repo = tempfile::tempdir().unwrap();
+    let checkout = flake_checkout();
+    fs::rename(checkout.path(), repo.path().join("nix")).unwrap();
+    let config = Config {
+        flake: true,
+        ..Default::default()
+    };
+    let file = repo.path().join("nix/hosts/laptop/default.nix");
+    let target = rebuild_target(&config, &file, repo.path()).unwrap();
+    assert_eq!(target.flake_dir.as_deref(), Some(Path::new("nix")));
+
+    let cmd = rebuild_command(&config, &target, repo.path());
+    assert_eq!(
+        cmd.args.join(" "),
+        "nixos-rebuild switch --flake ./nix#laptop"
+    );
+    assert_eq!(cmd.current_dir.as_deref(), Some(repo.path()));
+    let cmd = update_command(&config, &target, repo.path());
+    assert_eq!(cmd.current_dir, Some(repo.path().join("nix")));
+}
+
+#[test]
+fn reads_the_locked_nixpkgs_revision() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(locked_nixpkgs(dir.path()), None);
+
+    let lock = |nixpkgs: &str| {
+        format!(
+            r#"{{
+  "nodes": {{
+    "nixpkgs_2": {{ "locked": {} }},
+    "root": {{ "inputs": {{ "nixpkgs": "nixpkgs_2", "home-manager": "home-manager" }} }}
+  }},
+  "root": "root",
+  "version": 7
+}}"#,
+            nixpkgs
+        )
+    };
+    let cases = [
+        (
+            r#"{ "type": "github", "owner": "NixOS", "repo": "nixpkgs", "rev": "abc123", "narHash": "sha256-x" }"#,
+            Some("github:NixOS/nixpkgs/abc123"),
+        ),
+        (
+            r#"{ "type": "git", "url": "https://example.org/nixpkgs.git", "rev": "def456" }"#,
+            Some("git+https://example.org/nixpkgs.git?rev=def456"),
+        ),
+        (
+            r#"{ "type": "tarball", "url": "https://example.org/nixpkgs.tar.gz" }"#,
+            Some("https://example.org/nixpkgs.tar.gz"),
+        ),
+        (r#"{ "type": "indirect", "id": "nixpkgs" }"#, None),
+    ];
+    for (locked, expected) in cases {
+        fs::write(dir.path().join("flake.lock"), lock(locked)).unwrap();
+        assert_eq!(locked_nixpkgs(dir.path()).as_deref(), expected);
+    }
+}
+
+#[test]
+fn reads_other_locked_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("flake.lock"),
+        r#"{
+  "nodes": {
+    "home-manager": { "locked": { "type": "github", "owner": "nix-community", "repo": "home-manager", "rev": "abc123" } },
+    "root": { "inputs": { "home-manager": "home-manager" } }
+  },
+  "root": "root",
+  "version": 7
+}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        locked_input(dir.path(), "home-manager").as_deref(),
+        Some("github:nix-community/home-manager/abc123")
+    );
+    assert_eq!(locked_nixpkgs(dir.path()), None);
+}
@@ -0,0 +1,45 @@
+use declair_rs::gitignore::{IGNORE_PATTERNS, append_patterns, missing_patterns, writes_artifacts};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn only_unlisted_patterns_are_missing() {
+    let repo = tempfile::tempdir().unwrap();
+    assert_eq!(missing_patterns(repo.path()), IGNORE_PATTERNS);
+    fs::write(repo.path().join(".gitignore"), "result\n  *.declair.bak\n").unwrap();
+    assert_eq!(missing_patterns(repo.path()), [".*.declair.tmp"]);
+}
+
+#[test]
+fn patterns_are_appended_under_a_comment() {
+    let repo = tempfile::tempdir().unwrap();
+    let gitignore = repo.path().join(".gitignore");
+    fs::write(&gitignore, "result").unwrap();
+    append_patterns(repo.path(), &missing_patterns(repo.path())).unwrap();
+    assert_eq!(
+        fs::read_to_string(&gitignore).unwrap(),
+        "result\n\n# declair backups and temporary files\n*.declair.bak\n.*.declair.tmp\n"
+    );
+    assert!(missing_patterns(repo.path()).is_empty());
+
+    let fresh = tempfile::tempdir().unwrap();
+    append_patterns(fresh.path(), &["*.declair.bak"]).unwrap();
+    assert_eq!(
+        fs::read_to_string(fresh.path().join(".gitignore")).unwrap(),
+        "# declair backups and temporary files\n*.declair.bak\n"
+    );
+}
+
+#[test]
+fn only_backups_beside_the_file_end_up_in_the_repository() {
+    // Files in the temp dir keep their backup beside them.
+    let repo = tempfile::tempdir().unwrap();
+    let file = repo.path().join("configuration.nix");
+    fs::write(&file, "{ }\n").unwrap();
+    assert!(writes_artifacts(repo.path(), &file));
+    // Others are backed up to the state dir.
+    assert!(!writes_artifacts(
+        Path::new("/etc/nixos"),
+        Path::new("/etc/nixos/configuration.nix")
+    ));
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::config::Group;
+use declair_rs::error::DeclairError;
+use declair_rs::groups::{fetch_groups_source, parse_shared_groups, validate_group};
+
+#[test]
+fn single_group_file_is_named_after_the_file() {
+    let runner = FakeRunner::new().answer(
+        "curl",
+        &[],
+        0,
+        "packages = [\"git\", \"gdb\", \"python3Packages.requests\"]\n",
+    );
+    let (contents, stem) =
+        fetch_groups_source(&runner, "https://example.com/presets/devtools.toml").unwrap();
+    assert_eq!(stem, "devtools");
+    assert_eq!(runner.calls.borrow()[0].program, "curl");
+
+    let groups = parse_shared_groups(&contents, &stem).unwrap();
+    assert_eq!(
+        groups["devtools"].packages,
+        ["git", "gdb", "python3Packages.requests"]
+    );
+}
+
+#[test]
+fn groups_table_defines_several_groups() {
+    let groups = parse_shared_groups(
+        "[groups.dev]\npackages = [\"git\"]\n\n[groups.media]\npackages = [\"mpv\"]\n",
+        "ignored",
+    )
+    .unwrap();
+    assert_eq!(groups.keys().collect::<Vec<_>>(), ["dev", "media"]);
+}
+
+#[test]
+fn rejects_invalid_definitions() {
+    let invalid = |contents: &str| {
+        matches!(
+            parse_shared_groups(contents, "shared"),
+            Err(DeclairError::InvalidGroup { .. }) | Err(DeclairError::TomlDe(_))
+        )
+    };
+    assert!(invalid(""));
+    assert!(invalid("packages = []"));
+    assert!(invalid("packages = [\"git ]; evil = [\"]"));
+    assert!(invalid("packages = [\"${builtins.abort 1}\"]"));
+    assert!(invalid("[groups.\"bad name\"]\npackages = [\"git\"]"));
+    assert!(invalid("packages = [\"git\"]\nunknown = 1"));
+}
+
+#[test]
+fn validates_attribute_paths() {
+    let group = |pkgs: &[&str]| Group {
+        packages: pkgs.iter().map(|s| s.to_string()).collect(),
+        ..Default::default()
+    };
+    assert!(validate_group("ok", &group(&["_1password", "gnome.nautilus", "foo-bar'"])).is_ok());
+    assert!(validate_group("bad", &group(&["1password"])).is_err());
+    assert!(validate_group("bad", &group(&["a..b"])).is_err());
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::config::Config;
+use declair_rs::error::DeclairError;
+use declair_rs::hooks::{
+    HookContext, Hooks, Phase, RebuildEvent, expand, fire, notify_command, phase_command, run_phase,
+};
+use declair_rs::module::ModuleKind;
+use declair_rs::rebuild::{RebuildTarget, rebuild_with_hooks};
+use std::path::Path;
+use std::time::Duration;
+
+fn event(succeeded: bool) -> RebuildEvent {
+    RebuildEvent {
+        packages: vec!["ripgrep".to_string(), "fd".to_string()],
+        target: "NixOS".to_string(),
+        duration: Duration::from_secs(252),
+        succeeded,
+    }
+}
+
+fn hooks() -> Hooks {
+    Hooks {
+        on_success: Some("echo {status}: {package} in {duration}".to_string()),
+        on_failure: Some("logger rebuild of {target} failed".to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn variables_are_shell_quoted() {
+    assert_eq!(
+        expand("echo {status}: {package} in {duration}", &event(true)),
+        "echo success: 'ripgrep fd' in 4m12s"
+    );
+    let mut quoted = event(false);
+    quoted.target = "it's".to_string();
+    assert_eq!(
+        expand("echo {target} {unknown}", &quoted),
+        r"echo 'it'\''s' {unknown}"
+    );
+}
+
+#[test]
+fn hook_matches_outcome() {
+    let runner = FakeRunner::new().answer("sh", &[], 0, "");
+    fire(&runner, &hooks(), &event(true)).unwrap();
+    fire(&runner, &hooks(), &event(false)).unwrap();
+    let calls = runner.calls.borrow();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].program, "sh");
+    assert_eq!(calls[0].args[1], "echo success: 'ripgrep fd' in 4m12s");
+    assert_eq!(calls[1].args[1], "logger rebuild of NixOS failed");
+}
+
+#[test]
+fn notification_is_sent_when_enabled() {
+    let runner = FakeRunner::new().answer("notify-send", &[], 0, "");
+    let hooks = Hooks {
+        notify: true,
+        ..Default::default()
+    };
+    fire(&runner, &hooks, &event(false)).unwrap();
+    let calls = runner.calls.borrow();
+    assert_eq!(*calls, [notify_command(&event(false))]);
+    assert_eq!(calls[0].program, "notify-send");
+    assert!(calls[0].args.contains(&"--urgency=critical".to_string()));
+}
+
+#[test]
+fn short_rebuilds_are_skipped() {
+    let runner = FakeRunner::new();
+    let hooks = Hooks {
+        notify: true,
+        min_duration: 300,
+        ..hooks()
+    };
+    fire(&runner, &hooks, &event(true)).unwrap();
+    assert!(runner.calls.borrow().is_empty());
+}
+
+#[test]
+fn failing_hook_is_reported() {
+    let runner = FakeRunner::new().answer("sh", &[], 1, "");
+    let err = fire(&runner, &hooks(), &event(true)).unwrap_err();
+    assert!(matches!(err, DeclairError::Hook(_)));
+}
+
+fn context() -> HookContext {
+    HookContext::new(
+        "add",
+        vec!["ripgrep".to_string()],
+        "/etc/nixos/configuration.nix",
+    )
+}
+
+#[test]
+fn phase_hooks_get_context_in_environment() {
+    let cmd = phase_command("./check-secrets", Phase::PreEdit, &context(), None);
+    assert_eq!(cmd.program, "sh");
+    assert_eq!(cmd.args, ["-c", "./check-secrets"]);
+    let env: Vec<(&str, &str)> = cmd
+        .env
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    assert_eq!(
+        env,
+        [
+            ("DECLAIR_ACTION", "add"),
+            ("DECLAIR_PACKAGE", "ripgrep"),
+            ("DECLAIR_FILE", "/etc/nixos/configuration.nix"),
+            ("DECLAIR_PHASE", "pre_edit"),
+        ]
+    );
+
+    let cmd = phase_command(
+        "cachix push",
+        Phase::PostRebuild,
+        &context(),
+        Some("success"),
+    );
+    assert!(
+        cmd.env
+            .contains(&("DECLAIR_STATUS".to_string(), "success".to_string()))
+    );
+}
+
+#[test]
+fn phase_hooks_run_in_order_and_stop_at_failure() {
+    let hooks = Hooks {
+        post_edit: vec!["first".to_string(), "second".to_string()],
+        ..Default::default()
+    };
+    let runner = FakeRunner::new().answer("sh", &[], 0, "");
+    run_phase(&runner, &hooks, Phase::PostEdit, &context(), None).unwrap();
+    let scripts: Vec<String> = runner
+        .calls
+        .borrow()
+        .iter()
+        .map(|c| c.args[1].clone())
+        .collect();
+    assert_eq!(scripts, ["first", "second"]);
+
+    let runner = FakeRunner::new().answer("sh", &[], 1, "");
+    let err = run_phase(&runner, &hooks, Phase::PostEdit, &context(), None).unwrap_err();
+    assert!(matches!(err, DeclairError::Hook(_)));
+    assert_eq!(runner.count(), 1);
+}
+
+#[test]
+fn failing_pre_rebuild_hook_cancels_rebuild() {
+    let config = Config {
+        hooks: Hooks {
+            pre_rebuild: vec!["false".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let runner = FakeRunner::new().answer("sh", &[], 1, "");
+    let err = rebuild_with_hooks(
+        &runner,
+        &config,
+        &RebuildTarget::new(ModuleKind::Nixos),
+        Path::new("/etc/nixos"),
+        &context(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, DeclairError::Hook(_)));
+    assert_eq!(runner.count(), 1);
+}
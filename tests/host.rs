@@ -0,0 +1,64 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::error::DeclairError;
+use declair_rs::host::{
+    HostOs, Privilege, is_nixos_distribution, is_wsl_release, parse_wsl_list, wsl_distributions,
+};
+
+#[test]
+fn wsl_kernels_are_recognized() {
+    assert!(is_wsl_release("5.15.153.1-microsoft-standard-WSL2\n"));
+    assert!(is_wsl_release("4.4.0-19041-Microsoft"));
+    assert!(!is_wsl_release("6.6.30\n"));
+    assert!(!is_wsl_release(""));
+}
+
+#[test]
+fn only_windows_and_unknown_hosts_cant_rebuild() {
+    assert!(HostOs::Linux.is_unix());
+    assert!(HostOs::MacOs.is_unix());
+    assert!(HostOs::Wsl { distro: None }.is_unix());
+    assert!(!HostOs::Windows.is_unix());
+    assert!(!HostOs::Other.is_unix());
+}
+
+#[test]
+fn unsupported_hosts_fail_like_unsupported_platforms() {
+    let err = DeclairError::UnsupportedHost {
+        operation: "rebuild".to_string(),
+    };
+    assert_eq!(err.exit_code(), 5);
+    assert!(err.to_string().contains("`rebuild`"));
+}
+
+#[test]
+fn only_root_has_an_invoker() {
+    assert_eq!(Privilege::User.invoker(), None);
+    assert_eq!(Privilege::Root { invoker: None }.invoker(), None);
+    let sudo = Privilege::Root {
+        invoker: Some("alice".to_string()),
+    };
+    assert_eq!(sudo.invoker(), Some("alice"));
+}
+
+#[test]
+fn wsl_distributions_are_read_in_utf16_and_utf8() {
+    let utf16: Vec<u8> = "\u{feff}NixOS\r\nUbuntu-22.04\r\n\r\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    assert_eq!(parse_wsl_list(&utf16), ["NixOS", "Ubuntu-22.04"]);
+    assert_eq!(parse_wsl_list(b"Debian\r\n"), ["Debian"]);
+    assert!(parse_wsl_list(b"").is_empty());
+    assert!(is_nixos_distribution("NixOS"));
+    assert!(!is_nixos_distribution("Ubuntu"));
+}
+
+#[test]
+fn wsl_distributions_come_from_wsl_exe() {
+    let runner = FakeRunner::new().answer("wsl.exe", &[], 0, "Ubuntu\n");
+    assert_eq!(wsl_distributions(&runner), ["Ubuntu"]);
+    assert_eq!(runner.lines(), ["wsl.exe --list --quiet"]);
+    assert!(wsl_distributions(&FakeRunner::new().missing("wsl.exe")).is_empty());
+}
@@ -0,0 +1,89 @@
+use declair_rs::i18n::{EN, Language, RU, fill, lookup};
+use declair_rs::t;
+use std::collections::BTreeSet;
+
+fn keys(catalog: &[(&str, &str)]) -> BTreeSet<String> {
+    catalog.iter().map(|(k, _)| k.to_string()).collect()
+}
+
+#[test]
+fn catalogs_have_the_same_keys() {
+    assert_eq!(keys(EN), keys(RU));
+    assert_eq!(keys(EN).len(), EN.len(), "duplicate key in EN");
+    assert_eq!(keys(RU).len(), RU.len(), "duplicate key in RU");
+}
+
+#[test]
+fn translations_keep_the_placeholders() {
+    for (key, en) in EN {
+        let ru = lookup(Language::Ru, key);
+        assert_eq!(
+            en.matches("{}").count(),
+            ru.matches("{}").count(),
+            "{}",
+            key
+        );
+    }
+}
+
+#[test]
+fn fill_replaces_placeholders_in_order() {
+    assert_eq!(
+        fill("`{}` in `{}`", &[&"git", &"a.nix"]),
+        "`git` in `a.nix`"
+    );
+    assert_eq!(fill("line {}", &[&3]), "line 3");
+    assert_eq!(fill("{} and {}", &[&"{}", &"x"]), "{} and x");
+    assert_eq!(fill("no args {}", &[]), "no args {}");
+}
+
+#[test]
+fn lookup_falls_back_to_english_then_the_key() {
+    assert_eq!(lookup(Language::Ru, "block.abort"), "Отмена");
+    assert_eq!(lookup(Language::En, "block.abort"), "Abort");
+    assert_eq!(lookup(Language::Ru, "no.such.key"), "no.such.key");
+}
+
+#[test]
+fn language_follows_the_locale() {
+    assert_eq!(Language::from_locale("ru_RU.UTF-8"), Language::Ru);
+    assert_eq!(Language::from_locale("ru"), Language::Ru);
+    assert_eq!(Language::from_locale("en_US.UTF-8"), Language::En);
+    assert_eq!(Language::from_locale("C"), Language::En);
+}
+
+#[test]
+fn language_is_read_from_config() {
+    let parsed: toml::Value = toml::from_str("language = \"ru\"").unwrap();
+    let language: Language = parsed["language"].clone().try_into().unwrap();
+    assert_eq!(language, Language::Ru);
+}
+
+#[test]
+fn macro_defaults_to_english() {
+    assert_eq!(t!("add.package", "git", "a.nix"), "Added `git` to `a.nix`");
+    assert_eq!(t!("block.abort"), "Abort");
+}
+
+#[test]
+fn errors_are_built_from_the_catalog() {
+    let literal = regex::Regex::new(
+        r#"DeclairError::\w+\(\s*(format!\(\s*)?"|reason:\s*(format!\(\s*)?"[^"]*"\s*[,.)]"#,
+    )
+    .unwrap();
+    let src = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    for entry in std::fs::read_dir(src).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "rs") || path.ends_with("i18n.rs") {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path).unwrap();
+        if let Some(found) = literal.find(&source) {
+            panic!(
+                "untranslated error in {}: {}",
+                path.display(),
+                found.as_str()
+            );
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use declair_rs::imports::{local_imports, module_graph, module_tree, package_targets};
+use declair_rs::output::render_tree;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A flake whose only host imports a package module and a module importing the host back.
+fn checkout() -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let files = [
+        (
+            "flake.nix",
+            "{\n  outputs = { nixpkgs, ... }: {\n    nixosConfigurations.laptop = nixpkgs.lib.nixosSystem {\n      modules = [ ./hosts/laptop ];\n    };\n  };\n}\n",
+        ),
+        (
+            "hosts/laptop/default.nix",
+            "{ ... }:\n{\n  imports = [\n    ./hardware.nix\n    ../../packages.nix # the packages\n    # ./disabled.nix\n    ./missing.nix\n  ];\n}\n",
+        ),
+        (
+            "hosts/laptop/hardware.nix",
+            "{ imports = [ ./default.nix ]; }\n",
+        ),
+        ("hosts/laptop/disabled.nix", "{ }\n"),
+        (
+            "packages.nix",
+            "{ pkgs, ... }:\n{\n  environment.systemPackages = with pkgs; [\n    git\n  ];\n}\n",
+        ),
+    ];
+    for (name, contents) in files {
+        let path = dir.path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+fn relative(dir: &Path, files: Vec<std::path::PathBuf>) -> Vec<String> {
+    files
+        .iter()
+        .map(|f| {
+            let canonical = fs::canonicalize(f).unwrap();
+            let root = fs::canonicalize(dir).unwrap();
+            canonical.strip_prefix(root).unwrap().display().to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn imports_resolve_directories_and_skip_comments_and_missing_files() {
+    let dir = checkout();
+    let host = dir.path().join("hosts/laptop/default.nix");
+    assert_eq!(
+        relative(dir.path(), local_imports(&host).unwrap()),
+        ["hosts/laptop/hardware.nix", "packages.nix"]
+    );
+    assert_eq!(
+        relative(
+            dir.path(),
+            local_imports(&dir.path().join("flake.nix")).unwrap()
+        ),
+        ["hosts/laptop/default.nix"]
+    );
+}
+
+#[test]
+fn module_tree_visits_each_file_once() {
+    let dir = checkout();
+    assert_eq!(
+        relative(
+            dir.path(),
+            module_tree(&dir.path().join("flake.nix")).unwrap()
+        ),
+        [
+            "flake.nix",
+            "hosts/laptop/default.nix",
+            "hosts/laptop/hardware.nix",
+            "packages.nix"
+        ]
+    );
+}
+
+#[test]
+fn package_targets_prefer_modules_with_a_package_block() {
+    let dir = checkout();
+    let flake = dir.path().join("flake.nix");
+    assert_eq!(
+        relative(dir.path(), package_targets(&flake).unwrap()),
+        ["packages.nix"]
+    );
+
+    // Without a block anywhere, lists declair could adopt are offered instead.
+    fs::write(
+        dir.path().join("packages.nix"),
+        "{ pkgs, ... }:\n{\n  environment.systemPackages = [ pkgs.git ];\n}\n",
+    )
+    .unwrap();
+    assert_eq!(
+        relative(dir.path(), package_targets(&flake).unwrap()),
+        ["packages.nix"]
+    );
+
+    fs::write(dir.path().join("packages.nix"), "{ }\n").unwrap();
+    assert!(package_targets(&flake).unwrap().is_empty());
+}
+
+#[test]
+fn graph_counts_packages_and_keeps_cycles_as_edges() {
+    let dir = checkout();
+    let graph = module_graph(&dir.path().join("flake.nix")).unwrap();
+    let summary: Vec<(usize, Vec<usize>)> = graph
+        .nodes
+        .iter()
+        .map(|n| (n.packages, n.imports.clone()))
+        .collect();
+    // flake -> host -> {hardware, packages}; hardware imports the host back.
+    assert_eq!(
+        summary,
+        [(0, vec![1]), (0, vec![2, 3]), (0, vec![1]), (1, vec![])]
+    );
+}
+
+#[test]
+fn graph_renders_as_tree_dot_and_mermaid() {
+    let dir = checkout();
+    let target = dir.path().join("packages.nix");
+    let graph = module_graph(&dir.path().join("flake.nix")).unwrap();
+
+    assert_eq!(
+        render_tree(&[graph.tree(dir.path(), &target, "<-")]),
+        "flake.nix (0)\n\
+         └── hosts/laptop/default.nix (0)\n    \
+             ├── hosts/laptop/hardware.nix (0)\n    \
+             │   └── hosts/laptop/default.nix (0) ...\n    \
+             └── packages.nix (1) <-"
+    );
+
+    let dot = graph.to_dot(dir.path(), &target);
+    assert!(dot.starts_with("digraph modules {"));
+    assert!(dot.contains("  \"packages.nix\" [label=\"packages.nix\\n1 packages\", style=bold];"));
+    assert!(dot.contains("  \"hosts/laptop/hardware.nix\" -> \"hosts/laptop/default.nix\";"));
+
+    let mermaid = graph.to_mermaid(dir.path(), &target);
+    assert!(mermaid.starts_with("graph TD\n  m0[\"flake.nix (0)\"]"));
+    assert!(mermaid.contains("\n  m1 --> m3\n"));
+    assert!(mermaid.ends_with("  style m3 stroke-width:3px"));
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::audit::Nixpkgs;
+use declair_rs::error::DeclairError;
+use declair_rs::info::{PackageDetails, package_details};
+use declair_rs::search::PackageInfo;
+
+const RIPGREP_JSON: &str = r#"{
+  "version": "14.1.0",
+  "description": "Line-oriented search tool",
+  "longDescription": "ripgrep recursively searches directories for a regex pattern.\nIt respects gitignore rules.",
+  "homepage": "https://github.com/BurntSushi/ripgrep",
+  "license": [
+    { "spdxId": "MIT", "shortName": "mit", "fullName": "MIT License" },
+    { "shortName": "unlicense", "fullName": "The Unlicense" }
+  ]
+}"#;
+
+fn registry() -> Nixpkgs {
+    Nixpkgs::Flake("nixpkgs".to_string())
+}
+
+#[test]
+fn details_are_parsed_from_meta() {
+    let runner = FakeRunner::new().answer("nix", &[], 0, RIPGREP_JSON);
+    let details = package_details(&runner, &registry(), None, "ripgrep").unwrap();
+    assert_eq!(details.attr, "ripgrep");
+    assert_eq!(details.version.as_deref(), Some("14.1.0"));
+    assert_eq!(
+        details.homepage,
+        vec!["https://github.com/BurntSushi/ripgrep"]
+    );
+    assert_eq!(details.license, vec!["MIT", "unlicense"]);
+}
+
+#[test]
+fn license_and_homepage_shapes_are_accepted() {
+    let details = PackageDetails::parse(
+        "x",
+        br#"{ "license": "free", "homepage": ["https://a", "https://b"] }"#,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(details.license, vec!["free"]);
+    assert_eq!(details.homepage, vec!["https://a", "https://b"]);
+    assert_eq!(details.description, None);
+}
+
+#[test]
+fn missing_attributes_are_reported() {
+    let runner = FakeRunner::new().answer("nix", &[], 0, "null");
+    let err = package_details(&runner, &registry(), None, "nope").unwrap_err();
+    assert!(matches!(err, DeclairError::SearchFailed(_)));
+    assert!(err.to_string().contains("nope"));
+}
+
+#[test]
+fn render_wraps_descriptions() {
+    let details = PackageDetails::parse("ripgrep", RIPGREP_JSON.as_bytes())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        details.render(40),
+        "ripgrep 14.1.0\n\
+         \n\
+         Line-oriented search tool\n\
+         \n\
+         ripgrep recursively searches directories\n\
+         for a regex pattern.\n\
+         It respects gitignore rules.\n\
+         \n\
+         Homepage: https://github.com/BurntSushi/ripgrep\n\
+         License: MIT, unlicense"
+    );
+}
+
+#[test]
+fn search_results_give_fallback_details() {
+    let info = PackageInfo {
+        pname: "fd".to_string(),
+        version: "10.1.0".to_string(),
+        description: Some("Simple, fast alternative to find".to_string()),
+        ..Default::default()
+    };
+    let details = PackageDetails::from_info(&info);
+    assert_eq!(
+        details.render(80),
+        "fd 10.1.0\n\nSimple, fast alternative to find"
+    );
+}
+
+#[test]
+fn search_results_with_meta_give_full_details() {
+    let info = PackageInfo {
+        attr: "fd".to_string(),
+        pname: "fd".to_string(),
+        version: "10.1.0".to_string(),
+        description: Some("Simple, fast alternative to find".to_string()),
+        homepage: vec!["https://github.com/sharkdp/fd".to_string()],
+        broken: true,
+        ..Default::default()
+    };
+    let details = PackageDetails::from_info(&info);
+    assert_eq!(details.homepage, ["https://github.com/sharkdp/fd"]);
+    assert!(details.broken);
+}
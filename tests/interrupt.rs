@@ -0,0 +1,28 @@
+use declair_rs::error::DeclairError;
+use declair_rs::runner::{CommandRunner, CommandSpec, SystemRunner, install_interrupt_handler};
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Kept in its own test binary: it sends SIGINT to the whole test process.
+#[test]
+fn ctrl_c_stops_the_child_and_reports_interruption() {
+    install_interrupt_handler().unwrap();
+    thread::spawn(|| {
+        thread::sleep(Duration::from_millis(300));
+        // SAFETY: raising a signal declair has a handler for.
+        unsafe {
+            libc::raise(libc::SIGINT);
+        }
+    });
+    let start = Instant::now();
+    let err = SystemRunner::default()
+        .status(&CommandSpec::new("sleep", ["30"]))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    let err = DeclairError::from(err);
+    assert!(err.is_interrupted());
+    assert_eq!(err.exit_code(), 130);
+}
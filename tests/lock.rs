@@ -0,0 +1,44 @@
+mod common;
+
+use common::copy_fixture;
+use declair_rs::edit::add_package_to_nix;
+use declair_rs::error::DeclairError;
+use declair_rs::lock::{FileLock, FileSnapshot};
+use declair_rs::module::ModuleKind;
+use std::fs;
+
+#[test]
+fn unchanged_file_verifies() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let snapshot = FileSnapshot::capture(&file).unwrap();
+    snapshot.verify().unwrap();
+}
+
+#[test]
+fn modified_file_fails_verification() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let snapshot = FileSnapshot::capture(&file).unwrap();
+    add_package_to_nix(&file, "ripgrep", ModuleKind::Nixos).unwrap();
+
+    let err = snapshot.verify().unwrap_err();
+    assert!(matches!(err, DeclairError::ConcurrentModification { .. }));
+    assert_eq!(err.exit_code(), 10);
+}
+
+#[test]
+fn locked_file_is_not_edited() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let before = fs::read_to_string(&file).unwrap();
+    let _held = FileLock::acquire(&file).unwrap();
+
+    let err = add_package_to_nix(&file, "ripgrep", ModuleKind::Nixos).unwrap_err();
+    assert!(matches!(err, DeclairError::FileLocked { .. }));
+    assert_eq!(fs::read_to_string(&file).unwrap(), before);
+}
+
+#[test]
+fn lock_is_released_on_drop() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    drop(FileLock::acquire(&file).unwrap());
+    add_package_to_nix(&file, "ripgrep", ModuleKind::Nixos).unwrap();
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::copy_fixture;
+use declair_rs::error::DeclairError;
+use declair_rs::manifest::{SetDiff, SyncConflict, SyncPlan, read_manifest};
+use declair_rs::output::{OutputFormat, render};
+use std::collections::BTreeSet;
+use std::fs;
+use tempfile::TempDir;
+
+fn set(pkgs: &[&str]) -> BTreeSet<String> {
+    pkgs.iter().map(|p| p.to_string()).collect()
+}
+
+fn manifest(name: &str, contents: &str) -> BTreeSet<String> {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(name);
+    fs::write(&path, contents).unwrap();
+    read_manifest(&path).unwrap()
+}
+
+fn export(format: OutputFormat) -> String {
+    let rows = vec![
+        vec![
+            "git".to_string(),
+            "/etc/nixos/configuration.nix".to_string(),
+        ],
+        vec!["fd".to_string(), "/etc/nixos/configuration.nix".to_string()],
+    ];
+    render(format, &["package", "source"], &rows)
+}
+
+#[test]
+fn nix_files_contribute_every_list() {
+    let (_dir, file) = copy_fixture("multiple_lists.nix");
+    assert_eq!(
+        read_manifest(&file).unwrap(),
+        set(&["curl", "git", "noto-fonts"])
+    );
+}
+
+#[test]
+fn list_exports_are_read_back() {
+    let expected = set(&["fd", "git"]);
+    assert_eq!(manifest("laptop.nix", &export(OutputFormat::Nix)), expected);
+    assert_eq!(
+        manifest("laptop.json", &export(OutputFormat::Json)),
+        expected
+    );
+    assert_eq!(manifest("laptop.csv", &export(OutputFormat::Csv)), expected);
+    assert_eq!(manifest("laptop.tsv", &export(OutputFormat::Tsv)), expected);
+}
+
+#[test]
+fn plain_lists_and_json_names_are_read() {
+    assert_eq!(
+        manifest("laptop.txt", "# laptop\ngit\n\nfd  # finder\n"),
+        set(&["fd", "git"])
+    );
+    assert_eq!(manifest("laptop", r#"["git", "fd"]"#), set(&["fd", "git"]));
+}
+
+#[test]
+fn malformed_json_is_rejected() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("bad.json");
+    fs::write(&path, "[1, 2]").unwrap();
+    assert!(matches!(
+        read_manifest(&path),
+        Err(DeclairError::InvalidPath { .. })
+    ));
+}
+
+#[test]
+fn sets_are_compared() {
+    let diff = SetDiff::between(&set(&["git", "fd", "vim"]), &set(&["git", "htop"]));
+    assert_eq!(diff.only_local, ["fd", "vim"]);
+    assert_eq!(diff.only_other, ["htop"]);
+    assert_eq!(diff.common, ["git"]);
+    assert!(!diff.is_in_sync());
+    assert!(SetDiff::between(&set(&["git"]), &set(&["git"])).is_in_sync());
+}
+
+#[test]
+fn sync_applies_manifest_changes_and_keeps_local_edits() {
+    let base = set(&["git", "vim", "htop"]);
+    // The manifest dropped htop and gained fd; by hand, vim was removed and jq added.
+    let manifest = set(&["git", "vim", "fd"]);
+    let current = set(&["git", "htop", "jq"]);
+    let plan = SyncPlan::merge(Some(&base), &manifest, &current);
+    assert_eq!(plan.add, ["fd"]);
+    assert_eq!(plan.remove, ["htop"]);
+    assert_eq!(
+        plan.conflicts,
+        [
+            SyncConflict::AddedLocally("jq".to_string()),
+            SyncConflict::RemovedLocally("vim".to_string()),
+        ]
+    );
+
+    let mut plan = plan;
+    plan.overwrite(&SyncConflict::RemovedLocally("vim".to_string()));
+    assert_eq!(plan.add, ["fd", "vim"]);
+    assert_eq!(plan.conflicts.len(), 1);
+}
+
+#[test]
+fn first_sync_only_adds() {
+    let plan = SyncPlan::merge(None, &set(&["git", "fd"]), &set(&["git", "jq"]));
+    assert_eq!(plan.add, ["fd"]);
+    assert!(plan.remove.is_empty());
+    assert_eq!(
+        plan.conflicts,
+        [SyncConflict::AddedLocally("jq".to_string())]
+    );
+    assert!(SyncPlan::merge(None, &set(&["git"]), &set(&["git"])).is_empty());
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{copy_fixture, fixtures_dir};
+use declair_rs::config::Config;
+use declair_rs::module::{ModuleKind, Scope, edit_scope, is_font, module_kind};
+use std::fs;
+
+fn detect(fixture: &str) -> Option<ModuleKind> {
+    ModuleKind::detect(&fs::read_to_string(fixtures_dir().join(fixture)).unwrap())
+}
+
+#[test]
+fn detects_module_kind_from_attributes() {
+    assert_eq!(detect("home.nix"), Some(ModuleKind::HomeManager));
+    assert_eq!(detect("multiline.nix"), Some(ModuleKind::Nixos));
+    assert_eq!(detect("flake.nix"), Some(ModuleKind::Nixos));
+    assert_eq!(detect("no_block.nix"), None);
+}
+
+#[test]
+fn undetectable_files_fall_back_to_home_manager_flag() {
+    let (_dir, file) = copy_fixture("no_block.nix");
+    let config = Config {
+        home_manager: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        module_kind(&config, &file).unwrap(),
+        ModuleKind::HomeManager
+    );
+}
+
+#[test]
+fn configured_kind_overrides_detection() {
+    let (_dir, file) = copy_fixture("home.nix");
+    let mut config = Config::default();
+    config
+        .modules
+        .insert(file.display().to_string(), ModuleKind::Nixos);
+    assert_eq!(module_kind(&config, &file).unwrap(), ModuleKind::Nixos);
+}
+
+#[test]
+fn modules_table_parses() {
+    let cfg: Config = toml::from_str(
+        r#"
+nix_path = "~/nixos"
+auto_rebuild = true
+home_manager = false
+flake = true
+
+[modules]
+"~/nixos/home.nix" = "home-manager"
+"~/nixos/configuration.nix" = "nixos"
+"#,
+    )
+    .unwrap();
+    assert_eq!(cfg.modules["~/nixos/home.nix"], ModuleKind::HomeManager);
+    assert_eq!(cfg.modules["~/nixos/configuration.nix"], ModuleKind::Nixos);
+}
+
+#[test]
+fn scopes_parse_and_name_their_list() {
+    assert_eq!("system".parse(), Ok(Scope::System));
+    assert_eq!("home".parse(), Ok(Scope::Home));
+    let alice: Scope = "user:alice".parse().unwrap();
+    assert_eq!(alice, Scope::User("alice".to_string()));
+    assert_eq!(alice.packages_attr(), "users.users.alice.packages");
+    assert_eq!(alice.to_string(), "user:alice");
+    assert!("user:".parse::<Scope>().is_err());
+    assert!("user:a.b".parse::<Scope>().is_err());
+    assert!("everyone".parse::<Scope>().is_err());
+}
+
+#[test]
+fn scope_must_fit_the_module() {
+    let alice = Scope::User("alice".to_string());
+    assert_eq!(
+        edit_scope(None, ModuleKind::HomeManager).unwrap(),
+        Scope::Home
+    );
+    assert_eq!(edit_scope(Some(&alice), ModuleKind::Nixos).unwrap(), alice);
+    let err = edit_scope(Some(&alice), ModuleKind::HomeManager).unwrap_err();
+    assert_eq!(err.exit_code(), 2);
+}
+
+#[test]
+fn fonts_are_told_from_font_tools() {
+    assert!(is_font("noto-fonts"));
+    assert!(is_font("nerd-fonts.jetbrains-mono"));
+    assert!(is_font("jetbrains-mono"));
+    assert!(!is_font("fontconfig"));
+    assert!(!is_font("ripgrep"));
+    assert_eq!(
+        "fonts".parse::<Scope>().unwrap().packages_attr(),
+        "fonts.packages"
+    );
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::config::Config;
+use declair_rs::module::ModuleKind;
+use declair_rs::notices::{news_command, option_warnings, read_news, unread_news};
+use declair_rs::rebuild::RebuildTarget;
+use std::path::Path;
+
+const SWITCH_OUTPUT: &str = "\
+Starting Home Manager activation
+trace: warning: The option `programs.zsh.initExtra' defined in `/nix/store/abc-source/home.nix' has been renamed to `programs.zsh.initContent'.
+evaluation warning: The option `services.gpg-agent.pinentryFlavor' has been removed. Use `services.gpg-agent.pinentryPackage' instead.
+trace: warning: The option `programs.zsh.initExtra' defined in `/nix/store/abc-source/home.nix' has been renamed to `programs.zsh.initContent'.
+warning: Git tree '/home/user/config' is dirty
+Activating checkLinkTargets
+
+There are 2 unread and relevant news items.
+Read them by running the command \"home-manager news\".
+";
+
+fn flake_config() -> Config {
+    Config {
+        flake: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn option_migration_warnings_are_collected_once() {
+    assert_eq!(
+        option_warnings(SWITCH_OUTPUT),
+        [
+            "The option `programs.zsh.initExtra' defined in `/nix/store/abc-source/home.nix' has been renamed to `programs.zsh.initContent'.",
+            "The option `services.gpg-agent.pinentryFlavor' has been removed. Use `services.gpg-agent.pinentryPackage' instead.",
+        ]
+    );
+    assert!(option_warnings("warning: Git tree is dirty\n").is_empty());
+}
+
+#[test]
+fn unread_news_counts_are_read_from_the_switch() {
+    assert_eq!(unread_news(SWITCH_OUTPUT), Some(2));
+    assert_eq!(
+        unread_news("There is an unread and relevant news item.\n"),
+        Some(1)
+    );
+    assert_eq!(unread_news("Activating onFilesChange\n"), None);
+}
+
+#[test]
+fn news_is_printed_without_a_pager() {
+    let mut target = RebuildTarget::new(ModuleKind::HomeManager);
+    target.flake_output = Some("alice".to_string());
+    let cmd = news_command(&flake_config(), &target, Path::new("/home/alice/config"));
+    assert_eq!(cmd.program, "home-manager");
+    assert_eq!(cmd.args, ["news", "--flake", ".#alice"]);
+    assert!(cmd.env.contains(&("PAGER".to_string(), "cat".to_string())));
+}
+
+#[test]
+fn news_is_only_read_after_home_manager_announces_it() {
+    let runner = FakeRunner::new().answer(
+        "home-manager",
+        &[],
+        0,
+        "* 2025-01-10\n\n  A new module is available: 'programs.foo'.\n",
+    );
+    let dir = Path::new("/home/alice/config");
+    let hm = RebuildTarget::new(ModuleKind::HomeManager);
+    let (count, news) = read_news(&runner, &Config::default(), &hm, dir, SWITCH_OUTPUT).unwrap();
+    assert_eq!(count, 2);
+    assert!(news.contains("programs.foo"));
+
+    let nixos = RebuildTarget::new(ModuleKind::Nixos);
+    assert!(read_news(&runner, &Config::default(), &nixos, dir, SWITCH_OUTPUT).is_none());
+    assert!(read_news(&runner, &Config::default(), &hm, dir, "Activating\n").is_none());
+    assert_eq!(runner.count(), 1);
+}
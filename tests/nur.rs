@@ -0,0 +1,106 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::error::DeclairError;
+use declair_rs::nur::{Nur, flake_has_nur, is_nur_package, load_index, parse_index, search_index};
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+const INDEX: &str = r#"{
+  "nur.repos.mic92.hello-nur": {
+    "name": "hello-nur-1.0",
+    "meta": {"description": "Hello from the NUR", "license": {"spdxId": "GPL-3.0-only", "free": true}, "broken": true}
+  },
+  "nur.repos.alice.rofi-themes": {
+    "pname": "rofi-themes",
+    "version": "2024-01-02",
+    "description": "A collection of rofi themes"
+  },
+  "lib.something": {"name": "not-a-package"}
+}"#;
+
+#[test]
+fn index_entries_are_named_by_attribute_path() {
+    let index = parse_index(INDEX.as_bytes()).unwrap();
+    assert_eq!(index.len(), 2);
+    let hello = &index["nur.repos.mic92.hello-nur"];
+    assert_eq!(hello.pname, "nur.repos.mic92.hello-nur");
+    assert_eq!(hello.version, "1.0");
+    assert_eq!(hello.description.as_deref(), Some("Hello from the NUR"));
+    assert_eq!(hello.name(), "nur.repos.mic92.hello-nur");
+    assert_eq!(hello.license[0].name(), "GPL-3.0-only");
+    assert!(hello.broken);
+    assert_eq!(index["nur.repos.alice.rofi-themes"].version, "2024-01-02");
+}
+
+#[test]
+fn search_matches_every_word_in_path_or_description() {
+    let index = parse_index(INDEX.as_bytes()).unwrap();
+    let found = search_index(&index, "ROFI collection");
+    assert_eq!(
+        found.keys().collect::<Vec<_>>(),
+        ["nur.repos.alice.rofi-themes"]
+    );
+    assert_eq!(search_index(&index, "mic92").len(), 1);
+    assert!(search_index(&index, "rofi hello").is_empty());
+}
+
+#[test]
+fn fresh_cache_is_used_without_fetching() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("nur.json");
+    fs::write(&cache, INDEX).unwrap();
+    let runner = FakeRunner::new();
+    let index = load_index(&runner, &Nur::default(), &cache).unwrap();
+    assert_eq!(index, INDEX.as_bytes());
+    assert_eq!(runner.count(), 0);
+}
+
+#[test]
+fn stale_cache_is_refreshed() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("cache/nur.json");
+    let runner = FakeRunner::new().answer("curl", &[], 0, INDEX);
+    let nur = Nur {
+        cache_hours: 0,
+        ..Default::default()
+    };
+    load_index(&runner, &nur, &cache).unwrap();
+    assert_eq!(fs::read_to_string(&cache).unwrap(), INDEX);
+    assert_eq!(runner.count(), 1);
+}
+
+#[test]
+fn failed_fetch_falls_back_to_stale_cache() {
+    let dir = tempfile::tempdir().unwrap();
+    let cache = dir.path().join("nur.json");
+    let runner = FakeRunner::new()
+        .answer("curl", &[], 6, "")
+        .stderr("curl: (6) Could not resolve host");
+    let err = load_index(&runner, &Nur::default(), &cache).unwrap_err();
+    assert!(matches!(err, DeclairError::FetchFailed { .. }));
+
+    fs::write(&cache, INDEX).unwrap();
+    let old = SystemTime::now() - Duration::from_secs(7 * 24 * 3600);
+    fs::File::options()
+        .write(true)
+        .open(&cache)
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    let index = load_index(&runner, &Nur::default(), &cache).unwrap();
+    assert_eq!(index, INDEX.as_bytes());
+    assert_eq!(runner.count(), 2);
+}
+
+#[test]
+fn nur_packages_and_inputs_are_recognised() {
+    assert!(is_nur_package("nur.repos.mic92.hello-nur"));
+    assert!(!is_nur_package("nurl"));
+    assert!(flake_has_nur(
+        r#"inputs.nur.url = "github:nix-community/NUR";"#
+    ));
+    assert!(!flake_has_nur(
+        r#"inputs.nixpkgs.url = "github:nixos/nixpkgs";"#
+    ));
+}
@@ -0,0 +1,236 @@
+mod common;
+
+use common::write_config;
+use declair_rs::audit::Nixpkgs;
+use declair_rs::error::DeclairError;
+use declair_rs::options::{
+    HomeManager, OptionInfo, OptionTree, check_value, choices, is_declared, option_value,
+    options_command, parse_options, set_option, validate_option_name,
+};
+use std::fs;
+
+fn option(name: &str) -> OptionInfo {
+    OptionInfo {
+        name: name.to_string(),
+        type_name: "string".to_string(),
+        default: None,
+        description: None,
+    }
+}
+
+#[test]
+fn evaluated_options_are_parsed() {
+    let json = br#"[
+        { "name": "networking.hostName", "type": "string", "default": "\"nixos\"", "description": "The name of the machine." },
+        { "name": "services.openssh.enable", "type": "boolean", "default": "false", "description": null }
+    ]"#;
+    let options = parse_options(json).unwrap();
+    assert_eq!(options.len(), 2);
+    assert_eq!(options[0].type_name, "string");
+    assert_eq!(options[0].default.as_deref(), Some("\"nixos\""));
+    assert_eq!(options[1].description, None);
+    assert!(parse_options(b"{}").is_err());
+}
+
+#[test]
+fn placeholders_match_any_attribute() {
+    let options = [
+        option("networking.hostName"),
+        option("services.nginx.virtualHosts.<name>.root"),
+        option("fileSystems.*.device"),
+    ];
+    assert!(is_declared(&options, "networking.hostName"));
+    assert!(is_declared(
+        &options,
+        "services.nginx.virtualHosts.example.root"
+    ));
+    assert!(is_declared(&options, "fileSystems.boot.device"));
+    assert!(!is_declared(&options, "networking.hostname"));
+    assert!(!is_declared(
+        &options,
+        "services.nginx.virtualHosts.example"
+    ));
+}
+
+#[test]
+fn names_are_attribute_paths() {
+    assert!(validate_option_name("networking.hostName").is_ok());
+    assert!(validate_option_name("boot.loader.systemd-boot.enable").is_ok());
+    assert!(validate_option_name("a = 1; b").is_err());
+    assert!(validate_option_name("").is_err());
+}
+
+#[test]
+fn the_home_manager_tree_uses_the_locked_input() {
+    let tree = OptionTree::HomeManager {
+        nixpkgs: Nixpkgs::NixPath,
+        home_manager: HomeManager::Flake("github:nix-community/home-manager/abc".to_string()),
+    };
+    let expr = options_command(&tree).args.join(" ");
+    assert!(expr.contains("let src = <nixpkgs>;"));
+    assert!(expr.contains("(builtins.getFlake \"github:nix-community/home-manager/abc\").outPath"));
+    let expr = options_command(&OptionTree::Nixos(Nixpkgs::NixPath))
+        .args
+        .join(" ");
+    assert!(expr.contains("/nixos/lib/eval-config.nix"));
+}
+
+#[test]
+fn set_adds_the_assignment() {
+    let (_dir, file) = write_config("{ pkgs, ... }:\n{\n  environment.systemPackages = [ ];\n}\n");
+    assert_eq!(
+        set_option(&file, "networking.hostName", "\"box\"").unwrap(),
+        None
+    );
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{ pkgs, ... }:\n{\n  environment.systemPackages = [ ];\n  networking.hostName = \"box\";\n}\n"
+    );
+    assert_eq!(
+        option_value(&file, "networking.hostName")
+            .unwrap()
+            .as_deref(),
+        Some("\"box\"")
+    );
+}
+
+#[test]
+fn set_replaces_a_value_in_place() {
+    let (_dir, file) = write_config(
+        "{\n  networking.hostName = \"old\"; # the name\n  networking.hostNameAlias = 1;\n}\n",
+    );
+    assert_eq!(
+        set_option(&file, "networking.hostName", " \"new\" ")
+            .unwrap()
+            .as_deref(),
+        Some("\"old\"")
+    );
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n  networking.hostName = \"new\"; # the name\n  networking.hostNameAlias = 1;\n}\n"
+    );
+}
+
+#[test]
+fn set_replaces_a_multi_line_value() {
+    let (_dir, file) = write_config(
+        "{\n  boot.kernelParams = [\n    \"quiet\" # no logs\n    \"splash\"\n  ];\n  environment.systemPackages = with pkgs; [ git ];\n}\n",
+    );
+    assert_eq!(
+        set_option(&file, "boot.kernelParams", "[ \"quiet\" ]").unwrap(),
+        Some("[\n    \"quiet\" # no logs\n    \"splash\"\n  ]".to_string())
+    );
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n  boot.kernelParams = [ \"quiet\" ];\n  environment.systemPackages = with pkgs; [ git ];\n}\n"
+    );
+    assert_eq!(
+        option_value(&file, "environment.systemPackages")
+            .unwrap()
+            .as_deref(),
+        Some("with pkgs; [ git ]")
+    );
+}
+
+#[test]
+fn set_goes_into_nested_sets() {
+    let (_dir, file) = write_config(
+        "{ config, ... }:\nlet\n  port = 22;\nin\n{\n  services.openssh = {\n    enable = true;\n    settings = { PasswordAuthentication = false; };\n  };\n}\n",
+    );
+    assert_eq!(
+        set_option(&file, "services.openssh.ports", "[ 2222 ]").unwrap(),
+        None
+    );
+    assert_eq!(
+        set_option(&file, "services.openssh.settings.PermitRootLogin", "\"no\"").unwrap(),
+        None
+    );
+    assert_eq!(
+        set_option(&file, "services.openssh.enable", "false")
+            .unwrap()
+            .as_deref(),
+        Some("true")
+    );
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{ config, ... }:\nlet\n  port = 22;\nin\n{\n  services.openssh = {\n    enable = false;\n    settings = { PasswordAuthentication = false; PermitRootLogin = \"no\"; };\n    ports = [ 2222 ];\n  };\n}\n"
+    );
+    assert_eq!(
+        option_value(&file, "services.openssh.ports")
+            .unwrap()
+            .as_deref(),
+        Some("[ 2222 ]")
+    );
+    assert_eq!(option_value(&file, "port").unwrap(), None);
+}
+
+#[test]
+fn set_refuses_broken_and_empty_values() {
+    let (_dir, file) = write_config("{\n  networking.hostName = \"box\";\n}\n");
+    for value in ["  ", "[ 1", "\"a\"; users = { }", "{ a = 1; }}"] {
+        assert!(matches!(
+            set_option(&file, "networking.hostName", value),
+            Err(DeclairError::Config(_))
+        ));
+    }
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n  networking.hostName = \"box\";\n}\n"
+    );
+}
+
+#[test]
+fn values_are_checked_against_the_type() {
+    let ok = |value: &str, ty: &str| check_value("x", value, Some(ty)).is_ok();
+    assert!(ok("true", "boolean"));
+    assert!(!ok("\"yes\"", "boolean"));
+    assert!(ok(
+        "[ 2222 ]",
+        "list of 16 bit unsigned integer; between 0 and 65535 (both inclusive)"
+    ));
+    assert!(!ok(
+        "[ 70000 ]",
+        "list of 16 bit unsigned integer; between 0 and 65535 (both inclusive)"
+    ));
+    assert!(!ok(
+        "2222",
+        "list of 16 bit unsigned integer; between 0 and 65535 (both inclusive)"
+    ));
+    assert!(ok("null", "null or string"));
+    assert!(ok("\"box\"", "null or string"));
+    assert!(!ok("1", "null or string"));
+    assert!(ok(
+        "\"prohibit-password\"",
+        "one of \"yes\", \"without-password\", \"prohibit-password\", \"no\""
+    ));
+    assert!(!ok("\"maybe\"", "one of \"yes\", \"no\""));
+    assert!(ok("./hosts", "path"));
+    assert!(ok("{ }", "attribute set of (list of string)"));
+    assert!(!ok("[ ]", "attribute set of string"));
+    assert!(ok(
+        "[ pkgs.git (pkgs.callPackage ./x.nix { }) ]",
+        "list of package"
+    ));
+    assert!(!ok("[ 1 ]", "list of package"));
+    assert!(ok("lib.mkForce 1", "boolean"));
+    assert!(!ok("-1", "positive integer, meaning >0"));
+    assert!(ok("1.5", "floating point number"));
+    assert!(ok("\"a\"", "some type declair doesn't know"));
+}
+
+#[test]
+fn small_types_offer_choices() {
+    assert_eq!(
+        choices("boolean"),
+        Some(vec!["true".to_string(), "false".to_string()])
+    );
+    assert_eq!(
+        choices("null or one of \"a\", \"b\""),
+        Some(vec![
+            "null".to_string(),
+            "\"a\"".to_string(),
+            "\"b\"".to_string()
+        ])
+    );
+    assert_eq!(choices("string"), None);
+}
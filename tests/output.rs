@@ -0,0 +1,133 @@
+use declair_rs::output::{OutputFormat, TreeNode, render, render_tree};
+
+fn rows() -> Vec<Vec<String>> {
+    vec![
+        vec![
+            "git".to_string(),
+            "/etc/nixos/configuration.nix".to_string(),
+        ],
+        vec!["ripgrep".to_string(), "a\tb \"quoted\"".to_string()],
+    ]
+}
+
+#[test]
+fn table_aligns_columns() {
+    let out = render(OutputFormat::Table, &["package", "source"], &rows());
+    assert_eq!(
+        out,
+        "Package | Source\n\
+         --------+-----------------------------\n\
+         git     | /etc/nixos/configuration.nix\n\
+         ripgrep | a\tb \"quoted\""
+    );
+}
+
+#[test]
+fn tsv_has_header_and_no_stray_tabs() {
+    let out = render(OutputFormat::Tsv, &["package", "source"], &rows());
+    assert_eq!(
+        out,
+        "package\tsource\n\
+         git\t/etc/nixos/configuration.nix\n\
+         ripgrep\ta b \"quoted\""
+    );
+}
+
+#[test]
+fn nuon_is_a_table_literal() {
+    let out = render(OutputFormat::Nuon, &["package", "source"], &rows());
+    assert_eq!(
+        out,
+        r#"[[package, source]; ["git", "/etc/nixos/configuration.nix"] ["ripgrep", "a\tb \"quoted\""]]"#
+    );
+    assert_eq!(
+        render(OutputFormat::Nuon, &["package"], &[]),
+        "[[package];]"
+    );
+}
+
+#[test]
+fn tree_draws_branches() {
+    let tree = vec![
+        TreeNode::new(
+            "configuration.nix (3)",
+            vec![
+                TreeNode::new(
+                    "environment.systemPackages (2)",
+                    vec![TreeNode::leaf("git"), TreeNode::leaf("curl")],
+                ),
+                TreeNode::new("fonts.packages (1)", vec![TreeNode::leaf("noto-fonts")]),
+            ],
+        ),
+        TreeNode::new(
+            "home.nix (1)",
+            vec![TreeNode::new(
+                "home.packages (1)",
+                vec![TreeNode::leaf("firefox")],
+            )],
+        ),
+    ];
+    assert_eq!(
+        render_tree(&tree),
+        "configuration.nix (3)\n\
+         ├── environment.systemPackages (2)\n\
+         │   ├── git\n\
+         │   └── curl\n\
+         └── fonts.packages (1)\n\
+         \u{20}   └── noto-fonts\n\
+         home.nix (1)\n\
+         └── home.packages (1)\n\
+         \u{20}   └── firefox"
+    );
+}
+
+#[test]
+fn nix_is_a_package_block() {
+    let out = render(OutputFormat::Nix, &["package", "source"], &rows());
+    assert_eq!(out, "with pkgs; [\n  git\n  ripgrep\n]");
+    let out = render(
+        OutputFormat::Nix,
+        &["file", "list", "package"],
+        &[vec![
+            "a.nix".to_string(),
+            "home.packages".to_string(),
+            "fd".to_string(),
+        ]],
+    );
+    assert_eq!(out, "with pkgs; [\n  fd\n]");
+}
+
+#[test]
+fn json_is_an_array_of_objects() {
+    let out = render(OutputFormat::Json, &["package", "source"], &rows());
+    let parsed: serde_json::Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(
+        parsed,
+        serde_json::json!([
+            { "package": "git", "source": "/etc/nixos/configuration.nix" },
+            { "package": "ripgrep", "source": "a\tb \"quoted\"" }
+        ])
+    );
+    assert_eq!(render(OutputFormat::Json, &["package"], &[]), "[]");
+}
+
+#[test]
+fn csv_quotes_special_fields() {
+    let rows = vec![
+        vec!["git".to_string(), "plain".to_string()],
+        vec!["fd".to_string(), "a, \"b\"\nc".to_string()],
+    ];
+    assert_eq!(
+        render(OutputFormat::Csv, &["package", "source"], &rows),
+        "package,source\ngit,plain\nfd,\"a, \"\"b\"\"\nc\""
+    );
+}
+
+#[test]
+fn markdown_is_a_table() {
+    let rows = vec![vec!["git".to_string(), "a|b".to_string()]];
+    assert_eq!(
+        render(OutputFormat::Markdown, &["package", "source"], &rows),
+        "| Package | Source |\n| --- | --- |\n| git | a\\|b |"
+    );
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::write_config;
+use declair_rs::error::DeclairError;
+use declair_rs::overlay::{
+    add_overlay, list_overlays, overlay_expr, remove_overlay, validate_overlay,
+};
+use std::fs;
+
+const MULTILINE: &str = "\
+{ pkgs, inputs, ... }:
+{
+  nixpkgs.overlays = [
+    inputs.emacs.overlays.default
+    (final: prev: { hello = prev.hello; })
+  ];
+}
+";
+
+#[test]
+fn lists_multiline_overlays() {
+    let (_dir, file) = write_config(MULTILINE);
+    let overlays = list_overlays(&file).unwrap();
+    let names: Vec<&str> = overlays.iter().map(|o| o.name.as_str()).collect();
+    assert_eq!(names, ["emacs", "(final: prev: { hello = prev.hello; })"]);
+    assert_eq!(overlays[0].line, 4);
+}
+
+#[test]
+fn lists_single_line_overlays() {
+    let (_dir, file) = write_config(
+        "{\n  nixpkgs.overlays = [ inputs.nur.overlays.default (import ./my-overlay.nix) ];\n}\n",
+    );
+    let exprs: Vec<String> = list_overlays(&file)
+        .unwrap()
+        .into_iter()
+        .map(|o| o.expr)
+        .collect();
+    assert_eq!(
+        exprs,
+        ["inputs.nur.overlays.default", "(import ./my-overlay.nix)"]
+    );
+}
+
+#[test]
+fn add_and_remove_round_trip() {
+    let (_dir, file) = write_config(MULTILINE);
+    let expr = overlay_expr("rust", "https://example.com/rust.tar.gz", false);
+    add_overlay(&file, "rust", &expr).unwrap();
+    let contents = fs::read_to_string(&file).unwrap();
+    assert!(contents.contains(
+        "    (import (builtins.fetchTarball \"https://example.com/rust.tar.gz\")) # overlay: rust\n  ];"
+    ));
+    assert!(
+        list_overlays(&file)
+            .unwrap()
+            .iter()
+            .any(|o| o.name == "rust")
+    );
+
+    remove_overlay(&file, "rust").unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap().trim_end(),
+        MULTILINE.trim_end()
+    );
+}
+
+#[test]
+fn add_creates_list() {
+    let (_dir, file) = write_config("{ pkgs, ... }:\n{\n  networking.hostName = \"host\";\n}\n");
+    add_overlay(&file, "emacs", &overlay_expr("emacs", "", true)).unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{ pkgs, ... }:\n{\n  networking.hostName = \"host\";\n  nixpkgs.overlays = [\n    inputs.emacs.overlays.default # overlay: emacs\n  ];\n}"
+    );
+}
+
+#[test]
+fn add_expands_single_line_list() {
+    let (_dir, file) = write_config("{\n  nixpkgs.overlays = [ (import ./a.nix) ];\n}\n");
+    add_overlay(&file, "emacs", "inputs.emacs.overlays.default").unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n  nixpkgs.overlays = [\n    (import ./a.nix)\n    inputs.emacs.overlays.default # overlay: emacs\n  ];\n}"
+    );
+}
+
+#[test]
+fn remove_from_single_line_list() {
+    let (_dir, file) = write_config(
+        "{\n  nixpkgs.overlays = [ inputs.a.overlays.default inputs.b.overlays.default ];\n}\n",
+    );
+    remove_overlay(&file, "a").unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n  nixpkgs.overlays = [ inputs.b.overlays.default ];\n}"
+    );
+}
+
+#[test]
+fn duplicate_and_missing_overlays_fail() {
+    let (_dir, file) = write_config(MULTILINE);
+    let err = add_overlay(&file, "emacs", "inputs.emacs.overlays.default").unwrap_err();
+    assert!(matches!(err, DeclairError::OverlayExists { .. }));
+    assert_eq!(err.exit_code(), 5);
+    let err = remove_overlay(&file, "nope").unwrap_err();
+    assert!(matches!(err, DeclairError::OverlayNotFound { .. }));
+    assert_eq!(err.exit_code(), 6);
+}
+
+#[test]
+fn rejects_names_and_urls_that_break_nix() {
+    assert!(validate_overlay("emacs-overlay", "github:nix-community/emacs-overlay").is_ok());
+    assert!(validate_overlay("1st", "github:a/b").is_err());
+    assert!(validate_overlay("a b", "github:a/b").is_err());
+    assert!(validate_overlay("a", "https://x/\"; evil").is_err());
+    assert!(validate_overlay("a", "https://x/${evil}").is_err());
+}
@@ -0,0 +1,27 @@
+use declair_rs::pager::pager_command;
+
+#[test]
+fn declair_pager_wins_over_pager() {
+    assert_eq!(
+        pager_command(Some("most".into()), Some("more".into())).as_deref(),
+        Some("most")
+    );
+    assert_eq!(
+        pager_command(None, Some("less -S".into())).as_deref(),
+        Some("less -S")
+    );
+}
+
+#[test]
+fn less_is_the_default() {
+    assert_eq!(pager_command(None, None).as_deref(), Some("less"));
+}
+
+#[test]
+fn empty_or_cat_turns_paging_off() {
+    assert_eq!(
+        pager_command(Some(String::new()), Some("less".into())),
+        None
+    );
+    assert_eq!(pager_command(None, Some(" cat ".into())), None);
+}
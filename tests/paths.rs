@@ -0,0 +1,92 @@
+use declair_rs::completion::FileCompletion;
+use declair_rs::error::DeclairError;
+use declair_rs::host::home_dir;
+use declair_rs::paths::{contract_home, expand_path};
+use dialoguer::Completion;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn tildes_expand_to_home_directories() {
+    let home = home_dir().unwrap();
+    assert_eq!(expand_path("~").unwrap(), home);
+    assert_eq!(
+        expand_path("~/nixos/home.nix").unwrap(),
+        home.join("nixos/home.nix")
+    );
+    assert_eq!(
+        expand_path("~root/nixos").unwrap(),
+        PathBuf::from("/root/nixos")
+    );
+    assert!(matches!(
+        expand_path("~no-such-user-declair/x"),
+        Err(DeclairError::InvalidPath { .. })
+    ));
+    assert_eq!(
+        expand_path("/etc/nixos").unwrap(),
+        PathBuf::from("/etc/nixos")
+    );
+}
+
+#[test]
+fn variables_expand_in_every_syntax() {
+    let home = home_dir().unwrap();
+    let expected = home.join("nixos");
+    assert_eq!(expand_path("$HOME/nixos").unwrap(), expected);
+    assert_eq!(expand_path("${HOME}/nixos").unwrap(), expected);
+    // USERPROFILE falls back to the home directory when unset.
+    if std::env::var_os("USERPROFILE").is_none() {
+        assert_eq!(expand_path("$USERPROFILE/nixos").unwrap(), expected);
+    }
+    assert!(matches!(
+        expand_path("$DECLAIR_TEST_SURELY_UNSET/x"),
+        Err(DeclairError::InvalidPath { .. })
+    ));
+    // Dollar and percent signs that start no reference are kept.
+    assert_eq!(
+        expand_path("/a$/50%/b").unwrap(),
+        PathBuf::from("/a$/50%/b")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn percent_signs_are_file_name_characters_outside_windows() {
+    let home = home_dir().unwrap();
+    assert_eq!(
+        expand_path("~/cfg%foo%.nix").unwrap(),
+        home.join("cfg%foo%.nix")
+    );
+}
+
+#[test]
+fn paths_under_home_are_stored_with_a_tilde() {
+    let home = home_dir().unwrap();
+    assert_eq!(
+        contract_home(&home.join("nixos/flake.nix")),
+        "~/nixos/flake.nix"
+    );
+    assert_eq!(contract_home(&home), "~");
+    assert_eq!(
+        contract_home(Path::new("/etc/nixos/configuration.nix")),
+        "/etc/nixos/configuration.nix"
+    );
+}
+
+#[test]
+fn completion_keeps_the_typed_prefix() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("configuration.nix"), "{ }").unwrap();
+    fs::create_dir(dir.path().join("hosts")).unwrap();
+    let typed = format!("{}/conf", dir.path().display());
+    assert_eq!(
+        FileCompletion::new().get(&typed),
+        Some(format!("{}/configuration.nix", dir.path().display()))
+    );
+    let typed = format!("{}/ho", dir.path().display());
+    assert_eq!(
+        FileCompletion::new().get(&typed),
+        Some(format!("{}/hosts/", dir.path().display()))
+    );
+    assert_eq!(FileCompletion::new().get("~"), Some("~/".to_string()));
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::{FakeRunner, fixtures_dir};
+use declair_rs::audit::Nixpkgs;
+use declair_rs::platform::{
+    is_system, platform_command, platform_support, system_on_line, target_system,
+};
+use declair_rs::runner::CommandSpec;
+use std::path::PathBuf;
+
+#[test]
+fn systems_are_recognized() {
+    assert!(is_system("x86_64-linux"));
+    assert!(is_system("aarch64-darwin"));
+    assert!(!is_system("x86_64"));
+    assert!(!is_system("github:nixos/nixpkgs"));
+    assert!(!is_system("nixos-unstable"));
+}
+
+#[test]
+fn systems_are_read_from_common_settings() {
+    assert_eq!(
+        system_on_line("      system = \"x86_64-linux\";").as_deref(),
+        Some("x86_64-linux")
+    );
+    assert_eq!(
+        system_on_line("  nixpkgs.hostPlatform = lib.mkDefault \"aarch64-linux\";").as_deref(),
+        Some("aarch64-linux")
+    );
+    assert_eq!(
+        system_on_line("      pkgs = nixpkgs.legacyPackages.aarch64-darwin;").as_deref(),
+        Some("aarch64-darwin")
+    );
+    assert_eq!(system_on_line("  # system = \"x86_64-linux\";"), None);
+    assert_eq!(
+        system_on_line("  networking.hostName = \"x86_64-linux\";"),
+        None
+    );
+}
+
+#[test]
+fn target_system_takes_the_first_file_naming_one() {
+    let files = [
+        fixtures_dir().join("multiline.nix"),
+        PathBuf::from("/nonexistent/hardware-configuration.nix"),
+        fixtures_dir().join("flake.nix"),
+    ];
+    assert_eq!(target_system(&files).as_deref(), Some("x86_64-linux"));
+    assert_eq!(target_system(&files[..2]), None);
+}
+
+#[test]
+fn support_is_parsed_from_eval_output() {
+    let runner = FakeRunner::new().answer("nix", &[], 0, r#"{ "system": "x86_64-linux", "available": false, "platforms": ["aarch64-darwin", "x86_64-darwin"] }"#);
+    let support = platform_support(&runner, &Nixpkgs::NixPath, "iterm2", None)
+        .unwrap()
+        .unwrap();
+    assert!(!support.available);
+    assert_eq!(support.platforms, ["aarch64-darwin", "x86_64-darwin"]);
+
+    let missing = FakeRunner::new().answer("nix", &[], 0, "null");
+    assert_eq!(
+        platform_support(&missing, &Nixpkgs::NixPath, "nope", None).unwrap(),
+        None
+    );
+    let failing = FakeRunner::new().answer("nix", &[], 1, "");
+    assert!(platform_support(&failing, &Nixpkgs::NixPath, "iterm2", None).is_err());
+}
+
+#[test]
+fn the_target_system_is_passed_to_the_evaluation() {
+    let expr = |cmd: CommandSpec| {
+        cmd.args[cmd.args.iter().position(|a| a == "--expr").unwrap() + 1].clone()
+    };
+    let targeted = expr(platform_command(
+        &Nixpkgs::NixPath,
+        "iterm2",
+        Some("aarch64-linux"),
+    ));
+    assert!(targeted.contains("system = \"aarch64-linux\";"));
+    let local = expr(platform_command(&Nixpkgs::NixPath, "iterm2", None));
+    assert!(local.contains("system = builtins.currentSystem;"));
+    // A name can't end the string early and inject an expression.
+    let hostile = expr(platform_command(
+        &Nixpkgs::NixPath,
+        "x\" + abort \"${y}",
+        None,
+    ));
+    assert!(hostile.contains(r#"(pkgs.lib.splitString "." "x\" + abort \"\${y}")"#));
+}
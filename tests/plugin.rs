@@ -0,0 +1,138 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::FakeRunner;
+use declair_rs::error::DeclairError;
+use declair_rs::module::ModuleKind;
+use declair_rs::plugin::{Plugin, discover_in};
+use declair_rs::runner::SystemRunner;
+use serde_json::{Value, json};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// The JSON-RPC request of the last command `runner` ran.
+fn last_request(runner: &FakeRunner) -> Value {
+    let calls = runner.calls.borrow();
+    serde_json::from_slice(calls.last().unwrap().stdin.as_deref().unwrap()).unwrap()
+}
+
+fn write_script(dir: &Path, name: &str, body: &str, mode: u32) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+    path
+}
+
+fn plugin(path: &str) -> Plugin {
+    Plugin {
+        name: "nur".to_string(),
+        path: PathBuf::from(path),
+    }
+}
+
+#[test]
+fn discovers_executables_on_path() {
+    let first = tempfile::tempdir().unwrap();
+    let second = tempfile::tempdir().unwrap();
+    let nur = write_script(first.path(), "declair-nur", "", 0o755);
+    write_script(second.path(), "declair-nur", "", 0o755);
+    write_script(second.path(), "declair-overlay", "", 0o755);
+    write_script(second.path(), "declair-notes", "", 0o644);
+    write_script(second.path(), "declair-rs", "", 0o755);
+    write_script(second.path(), "other-tool", "", 0o755);
+
+    let path = std::env::join_paths([first.path(), second.path()]).unwrap();
+    let plugins = discover_in(&path);
+    let names: Vec<&str> = plugins.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["nur", "overlay"]);
+    assert_eq!(plugins[0].path, nur);
+}
+
+#[test]
+fn search_sends_query_and_parses_results() {
+    let runner = FakeRunner::new().answer(
+        "/bin/declair-nur",
+        &[],
+        0,
+        r#"{"jsonrpc": "2.0", "id": 1, "result": {
+            "nur.repos.alice.hello": {"pname": "hello", "version": "1.0", "description": "Hi"}
+        }}"#,
+    );
+    let results = plugin("/bin/declair-nur").search(&runner, "hello").unwrap();
+    assert_eq!(results["nur.repos.alice.hello"].pname, "hello");
+    assert_eq!(runner.calls.borrow()[0].program, "/bin/declair-nur");
+    let request = last_request(&runner);
+    assert_eq!(request["method"], "search");
+    assert_eq!(request["params"], json!({ "query": "hello" }));
+}
+
+#[test]
+fn edit_sends_file_package_and_kind() {
+    let runner = FakeRunner::new().answer(
+        "/bin/declair-nur",
+        &[],
+        0,
+        r#"{"jsonrpc": "2.0", "id": 1, "result": null}"#,
+    );
+    plugin("/bin/declair-nur")
+        .edit(
+            &runner,
+            "remove",
+            Path::new("/etc/nixos/home.nix"),
+            "git",
+            ModuleKind::HomeManager,
+        )
+        .unwrap();
+    let request = last_request(&runner);
+    assert_eq!(request["method"], "remove");
+    assert_eq!(
+        request["params"],
+        json!({
+            "file": "/etc/nixos/home.nix",
+            "package": "git",
+            "kind": "home-manager",
+            "scope": "home",
+        })
+    );
+}
+
+#[test]
+fn error_responses_are_reported() {
+    let runner = FakeRunner::new().answer(
+        "/bin/declair-nur",
+        &[],
+        0,
+        r#"{"jsonrpc": "2.0", "id": 1, "error": {"code": 1, "message": "no such repo"}}"#,
+    );
+    let err = plugin("/bin/declair-nur")
+        .search(&runner, "hello")
+        .unwrap_err();
+    assert!(matches!(&err, DeclairError::Plugin { reason, .. } if reason == "no such repo"));
+
+    let runner = FakeRunner::new().answer("/bin/declair-nur", &[], 0, "not json");
+    let err = plugin("/bin/declair-nur")
+        .search(&runner, "hello")
+        .unwrap_err();
+    assert!(matches!(err, DeclairError::Plugin { .. }));
+}
+
+#[test]
+fn real_plugin_reads_request_from_stdin() {
+    let dir = tempfile::tempdir().unwrap();
+    // Echo the query back as the only result's description.
+    let script = write_script(
+        dir.path(),
+        "declair-echo",
+        r#"query=$(sed 's/.*"query":"\([^"]*\)".*/\1/')
+printf '{"jsonrpc":"2.0","id":1,"result":{"echo":{"pname":"echo","version":"1","description":"%s"}}}' "$query""#,
+        0o755,
+    );
+    let plugin = Plugin {
+        name: "echo".to_string(),
+        path: script,
+    };
+    let results = plugin.search(&SystemRunner::default(), "ripgrep").unwrap();
+    assert_eq!(results["echo"].description.as_deref(), Some("ripgrep"));
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::audit::Nixpkgs;
+use declair_rs::error::DeclairError;
+use declair_rs::policy::{License, LicensePolicy, check_policy, package_licenses};
+
+const LICENSES_JSON: &str = r#"{
+  "ripgrep": [
+    { "spdxId": "MIT", "shortName": "mit", "free": true },
+    { "spdxId": "Unlicense", "shortName": "unlicense", "free": true }
+  ],
+  "vscode": [{ "spdxId": null, "shortName": "unfree", "free": false }],
+  "mastodon": [{ "spdxId": "AGPL-3.0-or-later", "shortName": "agpl3Plus", "free": true }],
+  "mystery": []
+}"#;
+
+fn policy(allow: &[&str], deny: &[&str]) -> LicensePolicy {
+    LicensePolicy {
+        allow: allow.iter().map(|s| s.to_string()).collect(),
+        deny: deny.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+fn licenses_of(pkg: &str) -> Vec<License> {
+    let runner = FakeRunner::new().answer("nix", &[], 0, LICENSES_JSON);
+    package_licenses(&runner, &Nixpkgs::NixPath, None, &[pkg.to_string()])
+        .unwrap()
+        .remove(pkg)
+        .unwrap()
+}
+
+#[test]
+fn deny_rules_match_spdx_ids_short_names_and_unfree() {
+    let deny = policy(&[], &["unfree", "AGPL-*"]);
+    assert!(deny.violations(&licenses_of("ripgrep")).is_empty());
+    assert_eq!(
+        deny.violations(&licenses_of("vscode")),
+        ["unfree (unfree) is denied by `unfree`"]
+    );
+    assert_eq!(
+        deny.violations(&licenses_of("mastodon")),
+        ["AGPL-3.0-or-later is denied by `AGPL-*`"]
+    );
+    assert!(deny.violations(&licenses_of("mystery")).is_empty());
+
+    let by_short_name = policy(&[], &["agpl3*"]);
+    assert_eq!(by_short_name.violations(&licenses_of("mastodon")).len(), 1);
+}
+
+#[test]
+fn allow_lists_require_every_license_to_match() {
+    let allow = policy(&["MIT", "Apache-2.0"], &[]);
+    assert_eq!(
+        allow.violations(&licenses_of("ripgrep")),
+        ["Unlicense is not allowed"]
+    );
+    assert_eq!(
+        allow.violations(&licenses_of("mystery")),
+        ["no license information"]
+    );
+    assert!(
+        policy(&["MIT", "Unlicense"], &[])
+            .violations(&licenses_of("ripgrep"))
+            .is_empty()
+    );
+}
+
+#[test]
+fn check_policy_fails_closed() {
+    let deny = policy(&[], &["unfree"]);
+    let runner = FakeRunner::new().answer("nix", &[], 0, LICENSES_JSON);
+    assert!(check_policy(&runner, &deny, &Nixpkgs::NixPath, None, "ripgrep").is_ok());
+    let err = check_policy(&runner, &deny, &Nixpkgs::NixPath, None, "vscode").unwrap_err();
+    assert!(matches!(err, DeclairError::PolicyViolation { .. }));
+    assert_eq!(err.exit_code(), 5);
+
+    let failing = FakeRunner::new()
+        .answer("nix", &[], 1, "")
+        .stderr("error: cannot fetch");
+    let err = check_policy(&failing, &deny, &Nixpkgs::NixPath, None, "ripgrep").unwrap_err();
+    assert!(err.to_string().contains("cannot fetch"));
+}
+
+#[test]
+fn an_empty_policy_checks_nothing() {
+    let runner = FakeRunner::new();
+    assert!(
+        check_policy(
+            &runner,
+            &LicensePolicy::default(),
+            &Nixpkgs::NixPath,
+            None,
+            "vscode"
+        )
+        .is_ok()
+    );
+    assert_eq!(runner.count(), 0);
+}
@@ -0,0 +1,48 @@
+use declair_rs::pool::{MAX_DEFAULT_JOBS, default_jobs, map_bounded};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn results_keep_the_order_of_the_items() {
+    let items: Vec<u64> = (0..50).collect();
+    let done = AtomicUsize::new(0);
+    let results = map_bounded(
+        &items,
+        4,
+        || {
+            done.fetch_add(1, Ordering::Relaxed);
+        },
+        |n| {
+            thread::sleep(Duration::from_millis(50 - n));
+            n * 2
+        },
+    );
+    assert_eq!(results, (0..50).map(|n| n * 2).collect::<Vec<_>>());
+    assert_eq!(done.load(Ordering::Relaxed), 50);
+}
+
+#[test]
+fn at_most_jobs_items_run_at_once() {
+    let running = AtomicUsize::new(0);
+    let peak = AtomicUsize::new(0);
+    map_bounded(
+        &[(); 20],
+        3,
+        || {},
+        |_| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10));
+            running.fetch_sub(1, Ordering::SeqCst);
+        },
+    );
+    assert!(peak.load(Ordering::SeqCst) <= 3);
+}
+
+#[test]
+fn no_items_and_zero_jobs() {
+    assert!(map_bounded(&[] as &[u8], 4, || {}, |n| *n).is_empty());
+    assert_eq!(map_bounded(&[1, 2], 0, || {}, |n| n + 1), [2, 3]);
+    assert!((1..=MAX_DEFAULT_JOBS).contains(&default_jobs()));
+}
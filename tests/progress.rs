@@ -0,0 +1,41 @@
+use declair_rs::progress::{MAX_LOGS, RebuildDisplay, save_log};
+use std::fs;
+
+#[test]
+fn without_a_terminal_the_log_becomes_plain_text() {
+    let mut display = RebuildDisplay::new(false);
+    assert_eq!(
+        display.line("building the system configuration..."),
+        Some("building the system configuration...".to_string())
+    );
+    assert_eq!(
+        display.line(r#"@nix {"action":"msg","level":0,"msg":"error: build failed"}"#),
+        Some("error: build failed".to_string())
+    );
+    assert_eq!(
+        display.line(r#"@nix {"action":"start","id":1,"level":0,"parent":0,"text":"","type":104,"fields":[]}"#),
+        None
+    );
+    assert_eq!(
+        display.line(r#"@nix {"action":"result","id":1,"type":105,"fields":[0,3,1,0]}"#),
+        None
+    );
+    display.finish();
+}
+
+#[test]
+fn only_the_newest_logs_are_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let logs = dir.path().join("logs");
+    for at in 0..MAX_LOGS as u64 + 2 {
+        let path = save_log(&logs, format!("log {}", at).as_bytes(), 100 + at).unwrap();
+        assert_eq!(fs::read_to_string(path).unwrap(), format!("log {}", at));
+    }
+    let mut names: Vec<String> = fs::read_dir(&logs)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(names.len(), MAX_LOGS);
+    assert_eq!(names[0], "rebuild-102.log");
+}
@@ -0,0 +1,88 @@
+use declair_rs::config::Config;
+use declair_rs::error::DeclairError;
+use declair_rs::project::{
+    PROJECT_FILE, ProjectConfig, find_project_config, load_project_config, read_project_config,
+};
+use declair_rs::rebuild::{RebuildMode, rebuild_target};
+use std::fs;
+
+#[test]
+fn the_nearest_project_file_wins() {
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("a/b/c");
+    fs::create_dir_all(&nested).unwrap();
+    assert_eq!(find_project_config(&nested), None);
+
+    fs::write(dir.path().join(PROJECT_FILE), "").unwrap();
+    assert_eq!(
+        find_project_config(&nested),
+        Some(dir.path().join(PROJECT_FILE))
+    );
+    fs::write(dir.path().join("a/b").join(PROJECT_FILE), "flake = true\n").unwrap();
+    let (path, project) = load_project_config(&nested).unwrap().unwrap();
+    assert_eq!(path, dir.path().join("a/b").join(PROJECT_FILE));
+    assert_eq!(project.flake, Some(true));
+}
+
+#[test]
+fn only_the_given_settings_are_overridden() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join(PROJECT_FILE);
+    fs::write(
+        &file,
+        "nix_path = \"shell.nix\"\nauto_rebuild = false\nrebuild_mode = \"test\"\nprefer_sets = [\"python312Packages\"]\n",
+    )
+    .unwrap();
+    let project = read_project_config(&file).unwrap();
+    let mut config = Config {
+        nix_path: "/etc/nixos/configuration.nix".to_string(),
+        auto_rebuild: true,
+        flake: true,
+        exclude_sets: vec!["haskellPackages".to_string()],
+        ..Default::default()
+    };
+    project.apply(&mut config, dir.path());
+    assert_eq!(
+        config.nix_path,
+        dir.path().join("shell.nix").to_string_lossy()
+    );
+    assert!(!config.auto_rebuild);
+    assert!(config.flake);
+    assert_eq!(config.rebuild_mode, Some(RebuildMode::Test));
+    assert_eq!(config.prefer_sets, ["python312Packages"]);
+    assert_eq!(config.exclude_sets, ["haskellPackages"]);
+
+    let absolute = ProjectConfig {
+        nix_path: Some("~/nix/home.nix".to_string()),
+        ..Default::default()
+    };
+    absolute.apply(&mut config, dir.path());
+    assert_eq!(config.nix_path, "~/nix/home.nix");
+}
+
+#[test]
+fn invalid_project_files_are_config_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join(PROJECT_FILE);
+    fs::write(&file, "rebuild_mode = \"sideways\"\n").unwrap();
+    assert!(matches!(
+        read_project_config(&file),
+        Err(DeclairError::Config(_))
+    ));
+}
+
+#[test]
+fn rebuilds_use_the_configured_mode_and_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(&file, "{ config, pkgs, ... }:\n{\n}\n").unwrap();
+    let config = Config {
+        nix_path: file.to_string_lossy().into_owned(),
+        rebuild_mode: Some(RebuildMode::Boot),
+        profile: Some("work".to_string()),
+        ..Default::default()
+    };
+    let target = rebuild_target(&config, &file, dir.path()).unwrap();
+    assert_eq!(target.mode, RebuildMode::Boot);
+    assert_eq!(target.profile.as_deref(), Some("work"));
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::error::DeclairError;
+use declair_rs::provides::{programs_db_providers, providers, rank_providers};
+use std::path::Path;
+
+#[test]
+fn nix_locate_results_drop_output_names() {
+    let runner = FakeRunner::new().answer("nix-locate", &[], 0, "imagemagick.out\ngraphicsmagick-imagemagick-compat.out\nimagemagickBig.bin\nimagemagick.out\n");
+    assert_eq!(
+        providers(&runner, "convert").unwrap(),
+        [
+            "imagemagick",
+            "imagemagickBig",
+            "graphicsmagick-imagemagick-compat"
+        ]
+    );
+    let calls = runner.calls.borrow();
+    assert_eq!(calls[0].program, "nix-locate");
+    assert_eq!(calls[0].args.last().unwrap(), "/bin/convert");
+}
+
+#[test]
+fn invalid_command_names_are_rejected() {
+    let runner = FakeRunner::new();
+    for name in ["", "--help", "rg'; DROP TABLE Programs; --", "a b"] {
+        let err = providers(&runner, name).unwrap_err();
+        assert!(matches!(err, DeclairError::SearchFailed(_)));
+    }
+    assert!(runner.calls.borrow().is_empty());
+}
+
+#[test]
+fn programs_db_is_queried_with_sqlite() {
+    let runner = FakeRunner::new().answer("sqlite3", &[], 0, "ripgrep\n");
+    let db = Path::new("/tmp/programs.sqlite");
+    assert_eq!(
+        programs_db_providers(&runner, db, "rg").unwrap(),
+        ["ripgrep"]
+    );
+    let calls = runner.calls.borrow();
+    assert_eq!(calls[0].program, "sqlite3");
+    assert!(calls[0].args[2].contains("name = 'rg'"));
+}
+
+#[test]
+fn failed_programs_db_query_is_reported() {
+    let runner = FakeRunner::new().answer("sqlite3", &[], 1, "");
+    let err = programs_db_providers(&runner, Path::new("/tmp/programs.sqlite"), "rg").unwrap_err();
+    assert_eq!(err.exit_code(), 7);
+}
+
+#[test]
+fn providers_named_like_the_command_rank_first() {
+    let ranked = rank_providers(
+        "git",
+        vec![
+            "gitMinimal".to_string(),
+            "busybox".to_string(),
+            "gitFull".to_string(),
+            "git".to_string(),
+            "perlPackages.git".to_string(),
+        ],
+    );
+    assert_eq!(
+        ranked,
+        [
+            "git",
+            "perlPackages.git",
+            "gitFull",
+            "gitMinimal",
+            "busybox"
+        ]
+    );
+}
@@ -0,0 +1,50 @@
+use declair_rs::queue::{Queued, RebuildQueue};
+use std::cell::Cell;
+use std::time::Duration;
+
+#[test]
+fn runs_rebuild_once_when_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    let queue = RebuildQueue::new(dir.path());
+    let rebuilds = Cell::new(0);
+    let queued = queue
+        .run(Duration::ZERO, || {
+            rebuilds.set(rebuilds.get() + 1);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(queued, Queued::Rebuilt(1));
+    assert_eq!(rebuilds.get(), 1);
+    assert!(!queue.is_dirty());
+}
+
+#[test]
+fn edits_during_a_rebuild_are_left_to_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let queue = RebuildQueue::new(dir.path());
+    let other = RebuildQueue::new(dir.path());
+    let rebuilds = Cell::new(0);
+    let queued = queue
+        .run(Duration::ZERO, || {
+            rebuilds.set(rebuilds.get() + 1);
+            if rebuilds.get() == 1 {
+                // Another run finishing its edit while this one rebuilds.
+                let deferred = other.run(Duration::ZERO, || panic!("rebuilt twice at once"));
+                assert_eq!(deferred.unwrap(), Queued::Deferred);
+            }
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(queued, Queued::Rebuilt(2));
+    assert!(!queue.is_dirty());
+}
+
+#[test]
+fn failed_rebuilds_stay_queued() {
+    let dir = tempfile::tempdir().unwrap();
+    let queue = RebuildQueue::new(dir.path());
+    let err = queue.run(Duration::ZERO, || Err(std::io::Error::other("boom").into()));
+    assert!(err.is_err());
+    assert!(queue.is_dirty());
+    assert!(queue.try_lock().unwrap().is_some());
+}
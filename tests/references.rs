@@ -0,0 +1,114 @@
+use declair_rs::references::{
+    ReferenceKind, find_references, matches_pattern, references_elsewhere, references_in,
+};
+use std::fs;
+use std::path::Path;
+
+const CONFIG: &str = "\
+{ pkgs, ... }:
+let
+  myRipgrep = pkgs.ripgrep.override { withPCRE2 = true; };
+in
+{
+  environment.systemPackages = with pkgs; [
+    ripgrep # managed by declair
+    ripgrep-all
+  ];
+  home.packages = with pkgs; [ git ripgrep ];
+  programs.ripgrep.enable = true;
+  environment.shellAliases.rg = \"${myRipgrep}/bin/rg\"; # not ripgrep
+  nixpkgs.overlays = [ (final: prev: { ripgrep = prev.ripgrep.overrideAttrs (_: { }); }) ];
+  # ripgrep in a comment only
+}
+";
+
+#[test]
+fn patterns_match_whole_names_with_wildcards() {
+    assert!(matches_pattern("ripgrep", "ripgrep"));
+    assert!(!matches_pattern("ripgrep-all", "ripgrep"));
+    assert!(matches_pattern("nodejs_20", "nodejs_*"));
+    assert!(matches_pattern("ripgrep-all", "*-all"));
+    assert!(matches_pattern("python312Packages", "python*Packages"));
+    assert!(!matches_pattern("nodejs", "nodejs_*"));
+    assert!(!matches_pattern("ab", "a*b*b"));
+}
+
+#[test]
+fn references_are_classified_by_how_they_name_the_package() {
+    let found: Vec<(usize, ReferenceKind)> =
+        references_in(Path::new("configuration.nix"), CONFIG, "ripgrep")
+            .iter()
+            .map(|r| (r.line, r.kind))
+            .collect();
+    assert_eq!(
+        found,
+        [
+            (3, ReferenceKind::Attribute),
+            (7, ReferenceKind::List),
+            (10, ReferenceKind::List),
+            (11, ReferenceKind::Option),
+            (13, ReferenceKind::Attribute),
+        ]
+    );
+
+    let bare = references_in(Path::new("x.nix"), "let tool = ripgrep; in tool", "ripgrep");
+    assert_eq!(bare[0].kind, ReferenceKind::Mention);
+}
+
+#[test]
+fn references_are_found_across_the_repository() {
+    let repo = tempfile::tempdir().unwrap();
+    fs::create_dir_all(repo.path().join("modules")).unwrap();
+    fs::create_dir_all(repo.path().join(".git")).unwrap();
+    fs::write(repo.path().join("configuration.nix"), CONFIG).unwrap();
+    fs::write(
+        repo.path().join("modules/shell.nix"),
+        "{ pkgs, ... }: { environment.systemPackages = [ pkgs.ripgrep ]; }\n",
+    )
+    .unwrap();
+    fs::write(repo.path().join(".git/ignored.nix"), "ripgrep\n").unwrap();
+    fs::write(repo.path().join("notes.txt"), "ripgrep\n").unwrap();
+
+    let found = find_references(repo.path(), "ripgrep");
+    let files: Vec<&Path> = found
+        .iter()
+        .map(|r| r.file.strip_prefix(repo.path()).unwrap())
+        .collect();
+    assert_eq!(files.len(), 6);
+    assert_eq!(files[5], Path::new("modules/shell.nix"));
+    assert_eq!(found[5].kind, ReferenceKind::Attribute);
+    assert_eq!(
+        found[5].text,
+        "{ pkgs, ... }: { environment.systemPackages = [ pkgs.ripgrep ]; }"
+    );
+}
+
+#[test]
+fn references_elsewhere_skip_the_entries_being_removed() {
+    let repo = tempfile::tempdir().unwrap();
+    let config = repo.path().join("configuration.nix");
+    fs::write(&config, CONFIG).unwrap();
+    fs::write(
+        repo.path().join("home.nix"),
+        "{ pkgs, ... }: {\n  home.packages = with pkgs; [\n    ripgrep\n  ];\n}\n",
+    )
+    .unwrap();
+    let found: Vec<(String, usize)> = references_elsewhere(repo.path(), &config, "ripgrep")
+        .into_iter()
+        .map(|r| {
+            (
+                r.file.file_name().unwrap().to_string_lossy().into_owned(),
+                r.line,
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("configuration.nix".to_string(), 3),
+            ("configuration.nix".to_string(), 11),
+            ("configuration.nix".to_string(), 13),
+            ("home.nix".to_string(), 3),
+        ]
+    );
+}
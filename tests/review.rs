@@ -0,0 +1,94 @@
+mod common;
+
+use common::{FakeRunner, copy_fixture};
+use declair_rs::edit::{add_import, add_package_to_nix};
+use declair_rs::error::DeclairError;
+use declair_rs::module::ModuleKind;
+use declair_rs::review::ChangeSet;
+use std::fs;
+
+#[test]
+fn staged_edits_accumulate_without_touching_the_file() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let before = fs::read_to_string(&file).unwrap();
+    let mut changes = ChangeSet::new();
+    add_package_to_nix(changes.stage(&file).unwrap(), "htop", ModuleKind::Nixos).unwrap();
+    add_package_to_nix(changes.stage(&file).unwrap(), "fd", ModuleKind::Nixos).unwrap();
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), before);
+    let pending = changes.changes().unwrap();
+    assert_eq!(pending.len(), 1);
+    let diff = pending[0].diff();
+    assert!(diff.contains("+    htop # managed by declair\n"));
+    assert!(diff.contains("+    fd # managed by declair\n"));
+    assert!(diff.contains(" wget\n"));
+}
+
+#[test]
+fn only_accepted_files_are_written() {
+    let (_dir, system) = copy_fixture("multiline.nix");
+    let (_dir2, home) = copy_fixture("home.nix");
+    let home_before = fs::read_to_string(&home).unwrap();
+    let mut changes = ChangeSet::new();
+    add_package_to_nix(changes.stage(&system).unwrap(), "htop", ModuleKind::Nixos).unwrap();
+    add_package_to_nix(changes.stage(&home).unwrap(), "fd", ModuleKind::HomeManager).unwrap();
+
+    let system = system.canonicalize().unwrap();
+    // Committing writable files needs no command.
+    let runner = FakeRunner::new();
+    let written = changes.commit(&runner, |p| p == system).unwrap();
+    assert_eq!(runner.count(), 0);
+    assert_eq!(written, std::slice::from_ref(&system));
+    assert!(fs::read_to_string(&system).unwrap().contains("htop"));
+    assert!(system.with_extension("declair.bak").exists());
+    assert_eq!(fs::read_to_string(&home).unwrap(), home_before);
+}
+
+#[test]
+fn unchanged_files_are_not_listed() {
+    let (_dir, file) = copy_fixture("multiline.nix");
+    let mut changes = ChangeSet::new();
+    changes.stage(&file).unwrap();
+    assert!(changes.changes().unwrap().is_empty());
+}
+
+#[test]
+fn concurrent_edit_aborts_before_any_write() {
+    let (_dir, first) = copy_fixture("multiline.nix");
+    let (_dir2, second) = copy_fixture("home.nix");
+    let first_before = fs::read_to_string(&first).unwrap();
+    let mut changes = ChangeSet::new();
+    add_package_to_nix(changes.stage(&first).unwrap(), "htop", ModuleKind::Nixos).unwrap();
+    add_package_to_nix(
+        changes.stage(&second).unwrap(),
+        "fd",
+        ModuleKind::HomeManager,
+    )
+    .unwrap();
+    add_import(&second, "./extra.nix").unwrap();
+
+    let err = changes.commit(&FakeRunner::new(), |_| true).unwrap_err();
+    assert!(matches!(err, DeclairError::ConcurrentModification { .. }));
+    assert_eq!(fs::read_to_string(&first).unwrap(), first_before);
+}
+
+#[test]
+fn failed_write_restores_the_files_already_written() {
+    let (_dir, first) = copy_fixture("multiline.nix");
+    let (_dir2, second) = copy_fixture("home.nix");
+    let first_before = fs::read_to_string(&first).unwrap();
+    let mut changes = ChangeSet::new();
+    add_package_to_nix(changes.stage(&first).unwrap(), "htop", ModuleKind::Nixos).unwrap();
+    add_package_to_nix(
+        changes.stage(&second).unwrap(),
+        "fd",
+        ModuleKind::HomeManager,
+    )
+    .unwrap();
+    // A directory where the second file's backup goes makes writing it fail.
+    fs::create_dir(second.with_extension("declair.bak")).unwrap();
+
+    let err = changes.commit(&FakeRunner::new(), |_| true).unwrap_err();
+    assert!(matches!(err, DeclairError::Backup { .. }));
+    assert_eq!(fs::read_to_string(&first).unwrap(), first_before);
+}
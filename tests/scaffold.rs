@@ -0,0 +1,109 @@
+use declair_rs::edit::{add_package_to_nix, list_packages};
+use declair_rs::module::ModuleKind;
+use declair_rs::scaffold::{
+    PACKAGES_MODULE, Template, derivation_template, import_path, new_package_path,
+    write_derivation, write_packages_module,
+};
+use std::path::Path;
+
+#[test]
+fn generated_module_is_editable() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = dir.path().join(PACKAGES_MODULE);
+    write_packages_module(&module, false).unwrap();
+    assert!(
+        list_packages(&module, ModuleKind::Nixos)
+            .unwrap()
+            .is_empty()
+    );
+
+    add_package_to_nix(&module, "git", ModuleKind::Nixos).unwrap();
+    assert_eq!(list_packages(&module, ModuleKind::Nixos).unwrap(), ["git"]);
+}
+
+#[test]
+fn home_manager_module_uses_home_packages() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = dir.path().join(PACKAGES_MODULE);
+    write_packages_module(&module, true).unwrap();
+    let contents = std::fs::read_to_string(&module).unwrap();
+    assert!(contents.contains("home.packages = with pkgs; ["));
+}
+
+#[test]
+fn existing_module_is_not_overwritten() {
+    let dir = tempfile::tempdir().unwrap();
+    let module = dir.path().join(PACKAGES_MODULE);
+    std::fs::write(&module, "keep me").unwrap();
+    assert!(write_packages_module(&module, false).is_err());
+    assert_eq!(std::fs::read_to_string(&module).unwrap(), "keep me");
+}
+
+#[test]
+fn import_path_is_relative_for_siblings() {
+    let importer = Path::new("/etc/nixos/configuration.nix");
+    assert_eq!(
+        import_path(importer, Path::new("/etc/nixos/declair-packages.nix")),
+        "./declair-packages.nix"
+    );
+    assert_eq!(
+        import_path(importer, Path::new("/home/user/declair-packages.nix")),
+        "/home/user/declair-packages.nix"
+    );
+}
+
+#[test]
+fn derivation_templates_are_named_after_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("pkgs").join("foo.nix");
+    write_derivation(&file, Template::Stdenv).unwrap();
+    let contents = std::fs::read_to_string(&file).unwrap();
+    assert!(contents.contains("pname = \"foo\";"));
+    assert!(contents.contains("stdenv.mkDerivation"));
+    assert!(write_derivation(&file, Template::Stdenv).is_err());
+
+    let default = dir.path().join("bar").join("default.nix");
+    write_derivation(&default, Template::Stdenv).unwrap();
+    assert!(
+        std::fs::read_to_string(&default)
+            .unwrap()
+            .contains("pname = \"bar\";")
+    );
+}
+
+#[test]
+fn templates_use_their_builder() {
+    for (template, builder, hash) in [
+        (Template::Stdenv, "stdenv.mkDerivation", None),
+        (
+            Template::Rust,
+            "rustPlatform.buildRustPackage",
+            Some("cargoHash"),
+        ),
+        (
+            Template::Python,
+            "python3Packages.buildPythonApplication",
+            None,
+        ),
+        (Template::Go, "buildGoModule", Some("vendorHash")),
+    ] {
+        let contents = derivation_template("tool", template);
+        assert!(contents.contains(builder), "{:?}", template);
+        assert!(contents.contains("pname = \"tool\";"));
+        assert!(!contents.contains("@name@"));
+        if let Some(hash) = hash {
+            assert!(contents.contains(&format!("{} = lib.fakeHash;", hash)));
+        }
+    }
+}
+
+#[test]
+fn new_packages_go_under_pkgs() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = new_package_path(dir.path(), "tool");
+    assert_eq!(path, dir.path().join("pkgs/tool/default.nix"));
+    write_derivation(&path, Template::Go).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(contents.contains("pname = \"tool\";"));
+    assert!(contents.contains("buildGoModule"));
+}
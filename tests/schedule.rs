@@ -0,0 +1,126 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::error::DeclairError;
+use declair_rs::schedule::{
+    SERVICE_NAME, TIMER_NAME, install, service_unit, stable_exe, timer_unit, uninstall,
+};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn service_runs_non_interactive_update() {
+    let unit = service_unit(Path::new("/home/alice/.cargo/bin/declair-rs"));
+    assert!(unit.contains("Type=oneshot\n"));
+    assert!(
+        unit.contains(
+            "ExecStart=/home/alice/.cargo/bin/declair-rs update --yes --no-interactive\n"
+        )
+    );
+}
+
+#[test]
+fn service_quotes_paths_with_spaces() {
+    let unit = service_unit(Path::new("/opt/my tools/declair-rs"));
+    assert!(unit.contains("ExecStart=\"/opt/my tools/declair-rs\" update"));
+}
+
+#[test]
+fn store_paths_are_resolved_through_the_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let profiles = [dir.path().join("profile")];
+    let other = dir.path().join("bin");
+    for bin in [profiles[0].join("bin"), other.clone()] {
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("declair-rs"), "").unwrap();
+    }
+    let store = Path::new("/nix/store/0123abcd-declair-rs-0.3.0/bin/declair-rs");
+    let resolved = stable_exe(store, &profiles, Some(other.as_os_str()));
+    assert_eq!(resolved, profiles[0].join("bin/declair-rs"));
+
+    // Without it in a profile, `PATH` has it; without either, the store path stays.
+    let missing = [dir.path().join("missing")];
+    let resolved = stable_exe(store, &missing, Some(other.as_os_str()));
+    assert_eq!(resolved, other.join("declair-rs"));
+    assert_eq!(stable_exe(store, &missing, None), store);
+
+    // Anywhere else the binary stays where it is.
+    let cargo = PathBuf::from("/home/alice/.cargo/bin/declair-rs");
+    assert_eq!(stable_exe(&cargo, &profiles, None), cargo);
+}
+
+#[test]
+fn timer_catches_up_missed_runs() {
+    let unit = timer_unit("Sat 04:00");
+    assert!(unit.contains("OnCalendar=Sat 04:00\n"));
+    assert!(unit.contains("Persistent=true\n"));
+    assert!(unit.contains("WantedBy=timers.target\n"));
+}
+
+#[test]
+fn install_writes_units_and_enables_timer() {
+    let dir = tempfile::tempdir().unwrap();
+    let unit_dir = dir.path().join("systemd/user");
+    let runner = FakeRunner::new().answer("systemctl", &[], 0, "");
+    install(
+        &runner,
+        &unit_dir,
+        Path::new("/usr/bin/declair-rs"),
+        "weekly",
+    )
+    .unwrap();
+
+    assert!(unit_dir.join(SERVICE_NAME).is_file());
+    let timer = std::fs::read_to_string(unit_dir.join(TIMER_NAME)).unwrap();
+    assert!(timer.contains("OnCalendar=weekly\n"));
+    assert_eq!(
+        runner.lines(),
+        [
+            "systemctl --user daemon-reload",
+            "systemctl --user enable --now declair-update.timer"
+        ]
+    );
+}
+
+#[test]
+fn install_reports_systemctl_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let runner = FakeRunner::new().answer("systemctl", &[], 1, "");
+    let err = install(
+        &runner,
+        dir.path(),
+        Path::new("/usr/bin/declair-rs"),
+        "daily",
+    )
+    .unwrap_err();
+    assert!(matches!(err, DeclairError::Systemd(_)));
+}
+
+#[test]
+fn uninstall_removes_units() {
+    let dir = tempfile::tempdir().unwrap();
+    let runner = FakeRunner::new().answer("systemctl", &[], 0, "");
+    install(
+        &runner,
+        dir.path(),
+        Path::new("/usr/bin/declair-rs"),
+        "daily",
+    )
+    .unwrap();
+    runner.calls.borrow_mut().clear();
+
+    uninstall(&runner, dir.path()).unwrap();
+    assert!(!dir.path().join(TIMER_NAME).exists());
+    assert!(!dir.path().join(SERVICE_NAME).exists());
+    assert_eq!(
+        runner.lines(),
+        [
+            "systemctl --user disable --now declair-update.timer",
+            "systemctl --user daemon-reload"
+        ]
+    );
+
+    // A second uninstall finds nothing to disable.
+    runner.calls.borrow_mut().clear();
+    uninstall(&runner, dir.path()).unwrap();
+    assert_eq!(runner.lines(), ["systemctl --user daemon-reload"]);
+}
@@ -0,0 +1,271 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::config::Config;
+use declair_rs::error::DeclairError;
+use declair_rs::search::{
+    NixVersion, PackageInfo, Query, SearchMode, attr_path, experimental_features, nix_version,
+    parse_nix_version, parse_search_results, search_configured, search_packages,
+};
+
+const NIX_ENV_JSON: &str = r#"{
+  "nixos.ripgrep": {
+    "name": "ripgrep-14.1.0",
+    "pname": "ripgrep",
+    "version": "14.1.0",
+    "meta": {
+      "description": "A utility that combines the usability of The Silver Searcher with the raw speed of grep",
+      "license": [{ "spdxId": "MIT", "shortName": "mit", "free": true }, { "spdxId": "Unlicense", "shortName": "unlicense", "free": true }],
+      "homepage": "https://github.com/BurntSushi/ripgrep",
+      "platforms": ["x86_64-linux", "aarch64-darwin"]
+    }
+  },
+  "nixos.ripgrep-all": {
+    "name": "ripgrep-all-0.10.6",
+    "meta": { "broken": true, "knownVulnerabilities": ["CVE-2024-0001"] }
+  }
+}"#;
+
+#[test]
+fn nix_versions_are_parsed() {
+    assert_eq!(
+        parse_nix_version("nix (Nix) 2.18.1\n"),
+        Some(NixVersion(2, 18, 1))
+    );
+    assert_eq!(
+        parse_nix_version("nix (Lix, like Nix) 2.91.0"),
+        Some(NixVersion(2, 91, 0))
+    );
+    assert_eq!(
+        parse_nix_version("nix (Nix) 2.3"),
+        Some(NixVersion(2, 3, 0))
+    );
+    assert_eq!(
+        parse_nix_version("nix (Nix) 2.25.0pre20241101_dirty"),
+        Some(NixVersion(2, 25, 0))
+    );
+    assert_eq!(parse_nix_version("garbage"), None);
+    assert!(NixVersion(2, 3, 16) < NixVersion(2, 4, 0));
+}
+
+#[test]
+fn version_of_missing_nix_is_unknown() {
+    let runner = FakeRunner::new().answer("nix", &[], 127, "");
+    assert_eq!(nix_version(&runner), None);
+}
+
+#[test]
+fn enabled_experimental_features_are_read_from_show_config() {
+    let runner = FakeRunner::new().answer(
+        "nix",
+        &["show-config"],
+        0,
+        "cores = 0\nexperimental-features = flakes nix-command\nsandbox = true\n",
+    );
+    assert_eq!(
+        experimental_features(&runner).unwrap(),
+        ["flakes", "nix-command"]
+    );
+}
+
+#[test]
+fn disabled_nix_command_means_no_features() {
+    let runner = FakeRunner::new().answer("nix", &["show-config"], 1, "");
+    assert!(experimental_features(&runner).unwrap().is_empty());
+}
+
+#[test]
+fn attribute_paths_drop_the_output_and_system() {
+    assert_eq!(attr_path("legacyPackages.x86_64-linux.ripgrep"), "ripgrep");
+    assert_eq!(
+        attr_path("legacyPackages.aarch64-darwin.python3Packages.requests"),
+        "python3Packages.requests"
+    );
+    assert_eq!(attr_path("packages.x86_64-linux.default"), "default");
+    assert_eq!(
+        attr_path("nur.repos.mic92.hello-nur"),
+        "nur.repos.mic92.hello-nur"
+    );
+}
+
+#[test]
+fn search_results_are_read_entry_by_entry() {
+    let json = r#"{
+      "legacyPackages.x86_64-linux.ripgrep": {"pname": "ripgrep", "version": "14.1.0", "description": "Line-oriented search tool"},
+      "legacyPackages.x86_64-linux.fd": {"pname": "fd", "version": "10.1.0", "description": ""}
+    }"#;
+    let results = parse_search_results(json.as_bytes()).unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results["legacyPackages.x86_64-linux.fd"].version, "10.1.0");
+
+    for bad in ["[]", "{\"x\": {\"pname\": 1}}", "{} trailing"] {
+        let err = parse_search_results(bad.as_bytes()).unwrap_err();
+        assert!(matches!(err, DeclairError::SearchFailed(_)), "{}", bad);
+    }
+}
+
+fn package(attr: &str, pname: &str, description: &str) -> PackageInfo {
+    PackageInfo {
+        attr: attr.to_string(),
+        pname: pname.to_string(),
+        description: Some(description.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn queries_match_by_mode() {
+    let requests = package(
+        "python3Packages.requests",
+        "requests",
+        "HTTP library for Python",
+    );
+    let cases = [
+        ("requests", SearchMode::Exact, true),
+        ("python3Packages.requests", SearchMode::Exact, true),
+        ("request", SearchMode::Exact, false),
+        ("REQ", SearchMode::Prefix, true),
+        ("python3", SearchMode::Prefix, true),
+        ("quests", SearchMode::Prefix, false),
+        ("http lib", SearchMode::Substring, true),
+        ("^req.*s$", SearchMode::Substring, false),
+        ("^req.*s$", SearchMode::Regex, true),
+        ("python[0-9]+P", SearchMode::Regex, true),
+    ];
+    for (text, mode, expected) in cases {
+        let query = Query::new(text, mode).unwrap();
+        assert_eq!(query.matches(&requests), expected, "{} {:?}", text, mode);
+    }
+}
+
+#[test]
+fn literal_queries_are_escaped_for_nix() {
+    assert_eq!(Query::substring("g++").nix_regex(), r"g\+\+");
+    assert_eq!(Query::substring("ripgrep-all").nix_regex(), "ripgrep-all");
+    assert_eq!(
+        Query::new("^rip.*", SearchMode::Regex).unwrap().nix_regex(),
+        "^rip.*"
+    );
+    let err = Query::new("(", SearchMode::Regex).unwrap_err();
+    assert!(matches!(err, DeclairError::InvalidRegex { .. }));
+    assert_eq!(err.exit_code(), 7);
+}
+
+#[test]
+fn exact_searches_narrow_nix_search_results() {
+    const SEARCH_JSON: &str = r#"{
+      "legacyPackages.x86_64-linux.ripgrep": {"pname": "ripgrep", "version": "14.1.0", "description": ""},
+      "legacyPackages.x86_64-linux.ripgrep-all": {"pname": "ripgrep-all", "version": "0.10.6", "description": ""}
+    }"#;
+    let runner = FakeRunner::new().answer("nix", &["search"], 0, SEARCH_JSON);
+    let query = Query::new("ripgrep", SearchMode::Exact).unwrap();
+    let results = search_configured(&runner, &Config::default(), &query).unwrap();
+    assert_eq!(
+        results.keys().collect::<Vec<_>>(),
+        ["legacyPackages.x86_64-linux.ripgrep"]
+    );
+    let calls = runner.calls.borrow();
+    assert!(calls[0].args.contains(&"ripgrep".to_string()));
+}
+
+#[test]
+fn excluded_sets_are_left_out_unless_queried() {
+    const SEARCH_JSON: &str = r#"{
+      "legacyPackages.x86_64-linux.pandoc": {"pname": "pandoc", "version": "3.1", "description": ""},
+      "legacyPackages.x86_64-linux.haskellPackages.pandoc": {"pname": "pandoc", "version": "3.1", "description": ""}
+    }"#;
+    let config = Config {
+        exclude_sets: vec!["haskellPackages".to_string()],
+        ..Default::default()
+    };
+    let runner = FakeRunner::new().answer("nix", &["search"], 0, SEARCH_JSON);
+    let results = search_configured(&runner, &config, &Query::substring("pandoc")).unwrap();
+    let names: Vec<&str> = results.values().map(|p| p.name()).collect();
+    assert_eq!(names, ["pandoc"]);
+
+    let runner = FakeRunner::new().answer("nix", &["search"], 0, SEARCH_JSON);
+    let query = Query::substring("haskellPackages.pandoc");
+    let results = search_configured(&runner, &config, &query).unwrap();
+    assert_eq!(results.len(), 2);
+}
+
+#[test]
+fn failing_nix_search_falls_back_to_nix_env() {
+    let runner = FakeRunner::new().answer("nix", &["search"], 1, "").answer(
+        "nix-env",
+        &["-qaP"],
+        0,
+        NIX_ENV_JSON,
+    );
+    let results = search_packages(&runner, "ripgrep").unwrap();
+    let rg = &results["nixos.ripgrep"];
+    assert_eq!(rg.pname, "ripgrep");
+    assert_eq!(rg.version, "14.1.0");
+    assert!(rg.description.is_some());
+    assert_eq!(rg.attr, "ripgrep");
+    assert_eq!(rg.name(), "ripgrep");
+    assert_eq!(
+        rg.license.iter().map(|l| l.name()).collect::<Vec<_>>(),
+        ["MIT", "Unlicense"]
+    );
+    assert_eq!(rg.homepage, ["https://github.com/BurntSushi/ripgrep"]);
+    assert_eq!(rg.platforms, ["x86_64-linux", "aarch64-darwin"]);
+    assert!(!rg.broken && !rg.insecure);
+    // Older Nix only reports `name`; pname and version are split off it.
+    let rga = &results["nixos.ripgrep-all"];
+    assert_eq!(rga.pname, "ripgrep-all");
+    assert_eq!(rga.version, "0.10.6");
+    assert!(rga.description.is_none());
+    assert!(rga.broken && rga.insecure);
+
+    let calls = runner.calls.borrow();
+    assert_eq!(calls[1].args.last().unwrap(), ".*ripgrep.*");
+}
+
+#[test]
+fn failed_fallback_reports_the_nix_version() {
+    let runner = FakeRunner::new()
+        .answer("nix", &["search"], 1, "")
+        .answer("nix", &["--version"], 0, "nix (Nix) 2.3.16")
+        .answer("nix-env", &["-qaP"], 1, "");
+    let err = search_packages(&runner, "ripgrep").unwrap_err();
+    let DeclairError::SearchFailed(reason) = err else {
+        panic!("unexpected error: {:?}", err);
+    };
+    assert!(reason.contains("Nix 2.3.16, too old for `nix search`"));
+}
+
+#[cfg(unix)]
+#[test]
+fn print_commands_still_searches() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    let nix = dir.path().join("nix");
+    std::fs::write(
+        &nix,
+        r#"#!/bin/sh
+case "$1" in
+  --version) echo "nix (Nix) 2.18.1" ;;
+  search) echo '{"legacyPackages.x86_64-linux.ripgrep":{"pname":"ripgrep","version":"14.1.0","description":"grep, faster"}}' ;;
+  *) exit 1 ;;
+esac
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&nix, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = std::env::join_paths(std::iter::once(dir.path().to_path_buf()).chain(
+        std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default()),
+    ))
+    .unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_declair-rs"))
+        .args(["--print-commands", "--no-interactive", "search", "ripgrep"])
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path().join("config"))
+        .env("PATH", path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("grep, faster"), "{}", stdout);
+    assert!(!stdout.contains("nix search"), "{}", stdout);
+}
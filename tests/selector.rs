@@ -0,0 +1,154 @@
+use declair_rs::search::PackageInfo;
+use declair_rs::selector::{
+    best_results, group_by_set, in_set, option_lines, package_set, page_size, wrap,
+};
+
+fn pkg(pname: &str, version: &str, description: Option<&str>) -> PackageInfo {
+    PackageInfo {
+        pname: pname.to_string(),
+        version: version.to_string(),
+        description: description.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn options_are_aligned_and_fit_the_width() {
+    let pkgs = vec![
+        pkg(
+            "fd",
+            "10.1.0",
+            Some("Simple, fast and user-friendly alternative to find"),
+        ),
+        pkg("ripgrep", "14.1.0", Some("Line-oriented search tool")),
+        pkg("empty", "1", None),
+    ];
+    let lines = option_lines(&pkgs, 40);
+    assert_eq!(
+        lines,
+        vec![
+            "fd       10.1.0  Simple, fast and user-…",
+            "ripgrep  14.1.0  Line-oriented search t…",
+            "empty    1",
+        ]
+    );
+    for (line, p) in lines.iter().zip(&pkgs) {
+        assert!(console::measure_text_width(line) <= 40);
+        assert_eq!(line.split_whitespace().next(), Some(p.pname.as_str()));
+    }
+}
+
+#[test]
+fn long_names_are_cut_too() {
+    let pkgs = vec![pkg(
+        "python312Packages.some-very-long-package-name",
+        "1.0",
+        Some("Description"),
+    )];
+    let line = &option_lines(&pkgs, 30)[0];
+    assert_eq!(line, "python312Packages.some-very-l…");
+}
+
+#[test]
+fn attribute_paths_and_flags_are_shown() {
+    let mut requests = pkg("requests", "2.32.3", Some("HTTP for Humans"));
+    requests.attr = "python3Packages.requests".to_string();
+    requests.insecure = true;
+    requests.installed = true;
+    let lines = option_lines(&[requests], 80);
+    assert_eq!(
+        lines,
+        ["python3Packages.requests  2.32.3  [installed] [insecure] HTTP for Humans"]
+    );
+}
+
+#[test]
+fn large_result_sets_keep_the_best_matches() {
+    let mut pkgs: Vec<PackageInfo> = (0..50)
+        .map(|i| pkg(&format!("aa-{:02}", i), "1", Some("mentions lib")))
+        .collect();
+    for name in ["libfoo", "lib", "python3Packages.lib", "glibc"] {
+        pkgs.push(pkg(name, "1", None));
+    }
+    let (kept, more) = best_results(pkgs.clone(), "lib", 4, &[]);
+    assert_eq!(more, 50);
+    let names: Vec<&str> = kept.iter().map(|p| p.name()).collect();
+    assert_eq!(names, ["glibc", "lib", "libfoo", "python3Packages.lib"]);
+
+    let (all, more) = best_results(pkgs, "lib", 100, &[]);
+    assert_eq!((all.len(), more), (54, 0));
+    assert_eq!(all[0].name(), "aa-00");
+}
+
+#[test]
+fn pages_leave_room_for_the_prompt() {
+    assert_eq!(page_size(24), 20);
+    assert_eq!(page_size(3), 5);
+}
+
+#[test]
+fn text_is_wrapped_at_spaces() {
+    assert_eq!(
+        wrap("one two three four\nfive", 9),
+        vec!["one two", "three", "four", "five"]
+    );
+    assert_eq!(
+        wrap("averyveryverylongword x", 5),
+        vec!["averyveryverylongword", "x"]
+    );
+}
+
+fn attr(attr: &str) -> PackageInfo {
+    PackageInfo {
+        attr: attr.to_string(),
+        pname: attr.rsplit('.').next().unwrap().to_string(),
+        version: "1.0".to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn exact_matches_are_grouped_by_set() {
+    let mut pkgs = vec![
+        attr("python311Packages.requests"),
+        attr("python312Packages.requests"),
+        attr("python312Packages.requests-mock"),
+        attr("requests"),
+    ];
+    let sets = group_by_set(&mut pkgs, "requests", &["python312Packages".to_string()]);
+    assert_eq!(sets, ["python312Packages", "", "python311Packages"]);
+    let names: Vec<&str> = pkgs.iter().map(|p| p.name()).collect();
+    assert_eq!(
+        names,
+        [
+            "python312Packages.requests",
+            "requests",
+            "python311Packages.requests",
+            "python312Packages.requests-mock",
+        ]
+    );
+}
+
+#[test]
+fn single_matches_are_left_alone() {
+    let mut pkgs = vec![attr("ripgrep"), attr("ripgrep-all")];
+    assert!(group_by_set(&mut pkgs, "ripgrep", &[]).is_empty());
+    assert_eq!(pkgs[1].name(), "ripgrep-all");
+    assert_eq!(package_set("python3Packages.numpy"), "python3Packages");
+    assert_eq!(package_set("numpy"), "");
+}
+
+#[test]
+fn preferred_sets_survive_truncation() {
+    let pkgs = vec![
+        attr("haskellPackages.lens"),
+        attr("lens"),
+        attr("nodePackages.lens"),
+    ];
+    let (kept, more) = best_results(pkgs, "lens", 2, &["nodePackages".to_string()]);
+    assert_eq!(more, 1);
+    let names: Vec<&str> = kept.iter().map(|p| p.name()).collect();
+    assert_eq!(names, ["lens", "nodePackages.lens"]);
+    assert!(in_set("nodePackages.typescript", "nodePackages"));
+    assert!(!in_set("nodePackages_latest.typescript", "nodePackages"));
+}
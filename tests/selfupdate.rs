@@ -0,0 +1,144 @@
+use declair_rs::selfupdate::{
+    InstallMethod, declair_input, detect, profile_element, self_update_command, store_path,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const STORE_PATH: &str = "/nix/store/0123456789abcdfghijklmnpqrsvwxyz-declair-rs-0.3.0";
+
+fn exe() -> PathBuf {
+    Path::new(STORE_PATH).join("bin/declair-rs")
+}
+
+#[test]
+fn store_path_of_an_executable() {
+    assert_eq!(store_path(&exe()).as_deref(), Some(Path::new(STORE_PATH)));
+    assert_eq!(store_path(Path::new("/usr/bin/declair-rs")), None);
+}
+
+#[test]
+fn profile_elements_by_name_or_index() {
+    let v3 = format!(
+        r#"{{"version":3,"elements":{{"hello":{{"storePaths":["/nix/store/x-hello"]}},"declair-rs":{{"storePaths":["{}"]}}}}}}"#,
+        STORE_PATH
+    );
+    assert_eq!(
+        profile_element(&v3, Path::new(STORE_PATH)).as_deref(),
+        Some("declair-rs")
+    );
+    let v2 = format!(
+        r#"{{"version":2,"elements":[{{"storePaths":["/nix/store/x-hello"]}},{{"storePaths":["{}"]}}]}}"#,
+        STORE_PATH
+    );
+    assert_eq!(
+        profile_element(&v2, Path::new(STORE_PATH)).as_deref(),
+        Some("1")
+    );
+    assert_eq!(profile_element(&v2, Path::new("/nix/store/y-other")), None);
+}
+
+const LOCK: &str = r#"{
+  "nodes": {
+    "declair": {
+      "locked": { "owner": "timasoft", "repo": "declair-rs", "rev": "abc", "type": "github" },
+      "original": { "owner": "timasoft", "repo": "declair-rs", "type": "github" }
+    },
+    "nixpkgs": {
+      "original": { "owner": "NixOS", "repo": "nixpkgs", "type": "github" }
+    },
+    "root": { "inputs": { "declair": "declair", "nixpkgs": "nixpkgs" } }
+  },
+  "root": "root",
+  "version": 7
+}"#;
+
+#[test]
+fn declair_input_is_found_by_repository() {
+    assert_eq!(declair_input(LOCK).as_deref(), Some("declair"));
+    let git = LOCK.replace(
+        r#""original": { "owner": "timasoft", "repo": "declair-rs", "type": "github" }"#,
+        r#""original": { "type": "git", "url": "https://codeberg.org/someone/declair-rs.git" }"#,
+    );
+    assert_eq!(declair_input(&git).as_deref(), Some("declair"));
+    let without = LOCK.replace("declair-rs", "other");
+    assert_eq!(declair_input(&without), None);
+}
+
+#[test]
+fn detects_profiles_before_flake_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let profile = dir.path().join("profile");
+    let flake = dir.path().join("flake");
+    fs::create_dir_all(&profile).unwrap();
+    fs::create_dir_all(&flake).unwrap();
+    fs::write(flake.join("flake.lock"), LOCK).unwrap();
+    let profiles = [profile.clone()];
+
+    assert_eq!(
+        detect(&exe(), &profiles, None, Some(&flake)),
+        InstallMethod::FlakeInput {
+            input: "declair".to_string(),
+            flake_dir: flake.clone(),
+        }
+    );
+    assert_eq!(
+        detect(&exe(), &profiles, None, None),
+        InstallMethod::NixStore {
+            store_path: PathBuf::from(STORE_PATH)
+        }
+    );
+    fs::write(
+        profile.join("manifest.nix"),
+        format!("[ {{ out = {{ outPath = \"{}\"; }}; }} ]", STORE_PATH),
+    )
+    .unwrap();
+    assert_eq!(
+        detect(&exe(), &profiles, None, Some(&flake)),
+        InstallMethod::NixEnv
+    );
+}
+
+#[test]
+fn detects_cargo_installs() {
+    let cargo = Path::new("/home/me/.cargo");
+    assert_eq!(
+        detect(&cargo.join("bin/declair-rs"), &[], Some(cargo), None),
+        InstallMethod::Cargo
+    );
+    assert_eq!(
+        detect(
+            Path::new("/usr/local/bin/declair-rs"),
+            &[],
+            Some(cargo),
+            None
+        ),
+        InstallMethod::Unknown
+    );
+}
+
+#[test]
+fn update_commands_per_method() {
+    let line = |method: InstallMethod| self_update_command(&method).map(|cmd| cmd.shell_line());
+    assert_eq!(
+        line(InstallMethod::NixProfile {
+            element: "declair-rs".to_string()
+        })
+        .as_deref(),
+        Some("nix profile upgrade declair-rs --extra-experimental-features 'nix-command flakes'")
+    );
+    assert_eq!(
+        line(InstallMethod::Cargo).as_deref(),
+        Some("cargo install --locked declair-rs")
+    );
+    assert_eq!(
+        line(InstallMethod::FlakeInput {
+            input: "declair".to_string(),
+            flake_dir: PathBuf::from("/etc/nixos"),
+        })
+        .as_deref(),
+        Some(
+            "cd /etc/nixos && nix flake update declair --extra-experimental-features 'nix-command flakes'"
+        )
+    );
+    assert_eq!(line(InstallMethod::Unknown), None);
+}
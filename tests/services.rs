@@ -0,0 +1,87 @@
+mod common;
+
+use common::{FakeRunner, write_config};
+use declair_rs::audit::Nixpkgs;
+use declair_rs::error::DeclairError;
+use declair_rs::services::{
+    disable_service, enable_service, list_services, service_exists, validate_service,
+};
+use std::fs;
+use std::path::Path;
+
+fn services(file: &Path) -> Vec<(String, String)> {
+    list_services(file)
+        .unwrap()
+        .into_iter()
+        .map(|s| (s.name, s.value))
+        .collect()
+}
+
+#[test]
+fn names_are_attribute_paths() {
+    assert!(validate_service("openssh").is_ok());
+    assert!(validate_service("xserver.desktopManager.gnome").is_ok());
+    assert!(validate_service("nginx-proxy").is_ok());
+    assert!(validate_service("").is_err());
+    assert!(validate_service("a..b").is_err());
+    assert!(validate_service("foo = true; bar").is_err());
+}
+
+#[test]
+fn enable_adds_and_disable_removes() {
+    let (_dir, file) = write_config("{ pkgs, ... }:\n{\n  networking.hostName = \"box\";\n}\n");
+    enable_service(&file, "openssh").unwrap();
+    assert!(
+        fs::read_to_string(&file)
+            .unwrap()
+            .contains("  services.openssh.enable = true;\n}")
+    );
+    assert_eq!(
+        services(&file),
+        vec![("openssh".to_string(), "true".to_string())]
+    );
+    assert!(matches!(
+        enable_service(&file, "openssh"),
+        Err(DeclairError::ProgramExists { .. })
+    ));
+    disable_service(&file, "openssh").unwrap();
+    assert!(services(&file).is_empty());
+    assert!(matches!(
+        disable_service(&file, "openssh"),
+        Err(DeclairError::ProgramNotFound { .. })
+    ));
+}
+
+#[test]
+fn enable_switches_a_disabled_service_in_place() {
+    let (_dir, file) = write_config(
+        "{\n  services.printing.enable = false; # for now\n  services.xserver.desktopManager.gnome.enable = lib.mkDefault true;\n}\n",
+    );
+    assert_eq!(
+        services(&file),
+        vec![
+            ("printing".to_string(), "false".to_string()),
+            (
+                "xserver.desktopManager.gnome".to_string(),
+                "lib.mkDefault true".to_string()
+            ),
+        ]
+    );
+    enable_service(&file, "printing").unwrap();
+    assert!(
+        fs::read_to_string(&file)
+            .unwrap()
+            .starts_with("{\n  services.printing.enable = true; # for now\n")
+    );
+}
+
+#[test]
+fn option_check_reads_the_evaluated_boolean() {
+    let nixpkgs = Nixpkgs::NixPath;
+    let yes = FakeRunner::new().answer("nix", &[], 0, "true\n");
+    let no = FakeRunner::new().answer("nix", &[], 0, "false\n");
+    let failing = FakeRunner::new().answer("nix", &[], 1, "");
+    assert!(service_exists(&yes, &nixpkgs, "openssh").unwrap());
+    assert!(!service_exists(&no, &nixpkgs, "opensssh").unwrap());
+    assert!(service_exists(&failing, &nixpkgs, "openssh").is_err());
+}
@@ -0,0 +1,224 @@
+use declair_rs::error::DeclairError;
+use declair_rs::state::{
+    MAX_RECENT_CONFIGS, MAX_TRANSACTIONS, PackageRecord, RebuildRecord, State, Transaction,
+    update_state,
+};
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn missing_state_file_is_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = State::load(&dir.path().join("state.json")).unwrap();
+    assert_eq!(state, State::default());
+}
+
+#[test]
+fn records_survive_a_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("data").join("state.json");
+    let file = Path::new("/etc/nixos/configuration.nix");
+    update_state(&path, |state| {
+        state.record_added("ripgrep", file, Some("needed for project X".to_string()));
+        state.record_added("htop", file, None);
+    })
+    .unwrap();
+
+    let state = State::load(&path).unwrap();
+    let record = state.get("ripgrep").unwrap();
+    assert_eq!(record.file, file);
+    assert_eq!(record.reason.as_deref(), Some("needed for project X"));
+    assert!(record.added_at > 0);
+    assert!(state.get("htop").unwrap().reason.is_none());
+    assert!(
+        !fs::read_to_string(&path)
+            .unwrap()
+            .contains("\"reason\": null")
+    );
+}
+
+#[test]
+fn forgetting_drops_the_record() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    let file = Path::new("/etc/nixos/configuration.nix");
+    update_state(&path, |state| state.record_added("htop", file, None)).unwrap();
+    update_state(&path, |state| state.forget("htop")).unwrap();
+    assert!(State::load(&path).unwrap().get("htop").is_none());
+}
+
+#[test]
+fn record_formats_time_and_author() {
+    let record = PackageRecord {
+        file: "/etc/nixos/configuration.nix".into(),
+        added_at: 1_700_000_000,
+        user: "alice".to_string(),
+        host: "laptop".to_string(),
+        reason: None,
+    };
+    assert_eq!(record.added_at_display(), "2023-11-14T22:13:20Z");
+    assert_eq!(record.added_by(), "alice@laptop");
+}
+
+#[test]
+fn invalid_state_file_is_a_config_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    fs::write(&path, "not json").unwrap();
+    assert_eq!(State::load(&path).unwrap_err().exit_code(), 2);
+}
+
+#[test]
+fn recent_configs_are_most_recent_first_without_repeats() {
+    let mut state = State::default();
+    assert!(state.remember_config("~/nixos"));
+    assert!(state.remember_config("~/src/test-config/flake.nix"));
+    assert!(!state.remember_config("~/src/test-config/flake.nix"));
+    assert!(state.remember_config("~/nixos"));
+    assert_eq!(
+        state.recent_configs,
+        ["~/nixos", "~/src/test-config/flake.nix"]
+    );
+    for i in 0..20 {
+        state.remember_config(&format!("/tmp/config-{}", i));
+    }
+    assert_eq!(state.recent_configs.len(), MAX_RECENT_CONFIGS);
+    assert_eq!(state.recent_configs[0], "/tmp/config-19");
+}
+
+#[test]
+fn recent_configs_survive_a_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    update_state(&path, |state| {
+        state.remember_config("~/nixos");
+    })
+    .unwrap();
+    assert_eq!(State::load(&path).unwrap().recent_configs, ["~/nixos"]);
+    // Older state files have no list.
+    fs::write(&path, r#"{"packages": {}}"#).unwrap();
+    assert!(State::load(&path).unwrap().recent_configs.is_empty());
+}
+
+#[test]
+fn rebuilds_apply_the_logged_edits() {
+    let file = Path::new("/etc/nixos/configuration.nix");
+    let mut state = State::default();
+    state.log(Transaction::now("add", vec!["ripgrep".to_string()], file));
+    state.record_rebuild(RebuildRecord {
+        at: 1,
+        target: "NixOS".to_string(),
+        generation: None,
+    });
+    state.log(Transaction::now("remove", vec!["htop".to_string()], file));
+    let pending = state.pending();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].action, "remove");
+
+    state.unlog(file);
+    assert!(state.pending().is_empty());
+    assert_eq!(state.transactions.len(), 1);
+}
+
+#[test]
+fn unfinished_rebuilds_are_interrupted_edits() {
+    let file = Path::new("/etc/nixos/configuration.nix");
+    let other = Path::new("/home/alice/nixos/home.nix");
+    let mut state = State::default();
+    state.log(Transaction::now("add", vec!["ripgrep".to_string()], file));
+    state.log(Transaction::now("add", vec!["htop".to_string()], other));
+    assert!(state.interrupted().is_empty());
+
+    state.rebuild_started(file, &[file.to_path_buf()]);
+    state.rebuild_started(other, &[other.to_path_buf()]);
+    let interrupted = state.interrupted();
+    assert_eq!(interrupted.len(), 2);
+    assert_eq!(interrupted[0].rebuilding, [file]);
+
+    // A failed rebuild was reported, so only the other repository's edit is left.
+    state.rebuild_stopped(Path::new("/etc/nixos"));
+    let interrupted = state.interrupted();
+    assert_eq!(interrupted.len(), 1);
+    assert_eq!(interrupted[0].packages, ["htop"]);
+
+    state.record_rebuild(RebuildRecord {
+        at: 1,
+        target: "NixOS".to_string(),
+        generation: None,
+    });
+    assert!(state.interrupted().is_empty());
+    assert!(state.transactions.iter().all(|t| t.rebuilding.is_empty()));
+}
+
+#[test]
+fn interrupted_edits_are_resumed_reverted_or_ignored() {
+    let file = Path::new("/etc/nixos/configuration.nix");
+    // The edit is marked when it is logged, before its rebuild starts.
+    let edit = || Transaction {
+        rebuilding: vec![file.to_path_buf()],
+        ..Transaction::now("add", vec!["ripgrep".to_string()], file)
+    };
+    let interrupted = || {
+        let mut state = State::default();
+        state.log(edit());
+        assert_eq!(state.interrupted().len(), 1);
+        state
+    };
+
+    // Rebuilding now applies it.
+    let mut state = interrupted();
+    state.rebuild_started(file, &[file.to_path_buf()]);
+    state.record_rebuild(RebuildRecord {
+        at: 1,
+        target: "NixOS".to_string(),
+        generation: None,
+    });
+    assert!(state.interrupted().is_empty());
+    assert!(state.pending().is_empty());
+
+    // Reverting drops it from the log.
+    let mut state = interrupted();
+    state.unlog(file);
+    assert!(state.interrupted().is_empty());
+    assert!(state.transactions.is_empty());
+
+    // Ignoring it keeps it pending without offering it again.
+    let mut state = interrupted();
+    state.rebuild_stopped(Path::new("/etc/nixos"));
+    assert!(state.interrupted().is_empty());
+    assert_eq!(state.pending().len(), 1);
+}
+
+#[test]
+fn transaction_log_is_bounded() {
+    let file = Path::new("/etc/nixos/configuration.nix");
+    let mut state = State::default();
+    for i in 0..MAX_TRANSACTIONS + 5 {
+        state.log(Transaction::now("add", vec![format!("pkg{}", i)], file));
+    }
+    assert_eq!(state.transactions.len(), MAX_TRANSACTIONS);
+    assert_eq!(state.transactions[0].packages, ["pkg5"]);
+}
+
+#[test]
+fn pinned_packages_block_removal() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.json");
+    update_state(&path, |state| {
+        assert!(state.pin("linuxPackages.perf"));
+        assert!(state.pin("iproute2"));
+        assert!(!state.pin("iproute2"));
+    })
+    .unwrap();
+
+    let mut state = State::load(&path).unwrap();
+    assert!(state.is_pinned("iproute2"));
+    assert!(state.ensure_unpinned(["ripgrep", "fd"]).is_ok());
+    let err = state.ensure_unpinned(["ripgrep", "iproute2"]).unwrap_err();
+    assert!(matches!(err, DeclairError::Pinned { ref pkg } if pkg == "iproute2"));
+    assert_eq!(err.exit_code(), 5);
+
+    assert!(state.unpin("iproute2"));
+    assert!(!state.unpin("iproute2"));
+    assert!(state.ensure_unpinned(["iproute2"]).is_ok());
+}
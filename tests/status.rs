@@ -0,0 +1,70 @@
+use declair_rs::state::{RebuildRecord, State, Transaction, unix_now};
+use declair_rs::status::{GenerationStatus, changed_since, status};
+use std::fs;
+use std::path::PathBuf;
+
+fn rebuilt(at: u64, generation: Option<&str>) -> State {
+    let mut state = State::default();
+    state.record_rebuild(RebuildRecord {
+        at,
+        target: "NixOS".to_string(),
+        generation: generation.map(PathBuf::from),
+    });
+    state
+}
+
+#[test]
+fn nothing_pending_right_after_a_rebuild() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("configuration.nix"), "{ }").unwrap();
+    let state = rebuilt(unix_now() + 60, Some("/nix/store/aaa-system"));
+    let status = status(
+        &state,
+        dir.path(),
+        Some(PathBuf::from("/nix/store/aaa-system")),
+    );
+    assert_eq!(status.generation, GenerationStatus::Matches);
+    assert!(status.changed_files.is_empty());
+    assert!(!status.rebuild_pending());
+}
+
+#[test]
+fn unapplied_edits_and_changed_files_need_a_rebuild() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("configuration.nix");
+    fs::write(&file, "{ }").unwrap();
+    fs::write(dir.path().join("flake.lock"), "{}").unwrap();
+    let mut state = rebuilt(0, Some("/nix/store/aaa-system"));
+    state.log(Transaction::now("add", vec!["ripgrep".to_string()], &file));
+
+    let status = status(
+        &state,
+        dir.path(),
+        Some(PathBuf::from("/nix/store/bbb-system")),
+    );
+    assert_eq!(status.pending.len(), 1);
+    assert_eq!(
+        status.changed_files,
+        [file.clone(), dir.path().join("flake.lock")]
+    );
+    assert_eq!(
+        status.generation,
+        GenerationStatus::Switched {
+            current: PathBuf::from("/nix/store/bbb-system")
+        }
+    );
+    assert!(status.rebuild_pending());
+    assert!(changed_since(dir.path(), unix_now() + 60).is_empty());
+}
+
+#[test]
+fn without_a_recorded_rebuild_the_generation_is_unknown() {
+    let dir = tempfile::tempdir().unwrap();
+    let status = status(
+        &State::default(),
+        dir.path(),
+        Some(PathBuf::from("/nix/store/a")),
+    );
+    assert_eq!(status.generation, GenerationStatus::Unknown);
+    assert!(status.rebuild_pending());
+}
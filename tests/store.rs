@@ -0,0 +1,76 @@
+mod common;
+
+use common::FakeRunner;
+use declair_rs::audit::Nixpkgs;
+use declair_rs::store::{StorePaths, nix_run_command, store_paths, store_paths_command};
+use std::path::PathBuf;
+
+#[test]
+fn store_paths_are_evaluated_in_the_configured_nixpkgs() {
+    let cmd = store_paths_command(
+        &Nixpkgs::Flake("github:NixOS/nixpkgs/abc".into()),
+        "ripgrep",
+    );
+    assert_eq!(cmd.program, "nix");
+    let expr = &cmd.args[cmd.args.iter().position(|a| a == "--expr").unwrap() + 1];
+    assert!(expr.contains("builtins.getFlake \"github:NixOS/nixpkgs/abc\""));
+    assert!(expr.contains("\"ripgrep\""));
+    assert!(expr.contains("meta.mainProgram"));
+}
+
+#[test]
+fn the_main_binary_prefers_the_bin_output() {
+    let runner = FakeRunner::new().answer("nix", &[], 0, r#"{"outputs":{"bin":"/nix/store/aaa-curl-8.9-bin","out":"/nix/store/bbb-curl-8.9","man":"/nix/store/ccc-curl-8.9-man"},"mainProgram":"curl"}"#);
+    let paths = store_paths(&runner, &Nixpkgs::NixPath, "curl")
+        .unwrap()
+        .unwrap();
+    assert_eq!(paths.outputs.len(), 3);
+    assert_eq!(
+        paths.binary(),
+        Some(PathBuf::from("/nix/store/aaa-curl-8.9-bin/bin/curl"))
+    );
+
+    let out_only = StorePaths {
+        outputs: [("out".to_string(), "/nix/store/ddd-ripgrep-14".to_string())].into(),
+        main_program: Some("rg".to_string()),
+    };
+    assert_eq!(
+        out_only.binary(),
+        Some(PathBuf::from("/nix/store/ddd-ripgrep-14/bin/rg"))
+    );
+    let library = StorePaths {
+        main_program: None,
+        ..out_only
+    };
+    assert_eq!(library.binary(), None);
+}
+
+#[test]
+fn missing_packages_and_failed_evaluations_are_told_apart() {
+    let missing = FakeRunner::new().answer("nix", &[], 0, "null");
+    assert_eq!(
+        store_paths(&missing, &Nixpkgs::NixPath, "nope").unwrap(),
+        None
+    );
+    let failed = FakeRunner::new().answer("nix", &[], 1, "");
+    assert!(store_paths(&failed, &Nixpkgs::NixPath, "curl").is_err());
+}
+
+#[test]
+fn packages_run_once_from_the_configured_nixpkgs() {
+    let args = ["--help".to_string()];
+    let flake = nix_run_command(
+        &Nixpkgs::Flake("github:NixOS/nixpkgs/abc".into()),
+        "ripgrep",
+        &args,
+    );
+    assert_eq!(flake.program, "nix");
+    assert_eq!(flake.args[0], "run");
+    assert_eq!(
+        flake.args[3..],
+        ["github:NixOS/nixpkgs/abc#ripgrep", "--", "--help"]
+    );
+
+    let channel = nix_run_command(&Nixpkgs::NixPath, "ripgrep", &[]);
+    assert_eq!(channel.args[3..], ["--file", "<nixpkgs>", "ripgrep", "--"]);
+}
@@ -0,0 +1,66 @@
+use declair_rs::syntax::{
+    Place, TokenKind, find_place, is_expression, module_body, nix_string, tokenize,
+};
+
+fn path(name: &str) -> Vec<String> {
+    name.split('.').map(str::to_string).collect()
+}
+
+#[test]
+fn strings_and_comments_hide_brackets() {
+    let text = "{ a = \"}\"; b = ''\n  ${ \"{\" } ''' } \n''; /* { */ c = 1; # }\n}";
+    let tokens = tokenize(text).unwrap();
+    assert_eq!(
+        tokens
+            .iter()
+            .filter(|t| matches!(t.kind, TokenKind::Punct(b'{' | b'}')))
+            .count(),
+        2
+    );
+    let body = module_body(text).unwrap();
+    let names: Vec<String> = body.bindings.iter().map(|b| b.path.join(".")).collect();
+    assert_eq!(names, ["a", "b", "c"]);
+    assert!(tokenize("{ a = \"open; }").is_none());
+}
+
+#[test]
+fn bindings_are_read_through_with_and_let() {
+    let text = "{ pkgs, ... }:\n{\n  inherit (pkgs) lib;\n  a = with pkgs; [ git ];\n  b = let x = 1; in x;\n  \"c\".d = 2;\n  e = rec { f = { g = 3; }; };\n}\n";
+    let body = module_body(text).unwrap();
+    let names: Vec<String> = body.bindings.iter().map(|b| b.path.join(".")).collect();
+    assert_eq!(names, ["a", "b", "c.d", "e"]);
+    assert_eq!(&text[body.bindings[0].value.clone()], "with pkgs; [ git ]");
+    let wanted = path("e.f.g");
+    match find_place(&body, &wanted) {
+        Place::Assigned(binding) => assert_eq!(&text[binding.value.clone()], "3"),
+        Place::Missing { .. } => panic!("e.f.g not found"),
+    }
+    let wanted = path("e.f.h");
+    match find_place(&body, &wanted) {
+        Place::Missing { rest, .. } => assert_eq!(rest, ["h"]),
+        Place::Assigned(_) => panic!("e.f.h found"),
+    }
+}
+
+#[test]
+fn expressions_are_recognized() {
+    assert!(is_expression("[ 1 2 ]"));
+    assert!(is_expression("with pkgs; [ git ]"));
+    assert!(is_expression("lib.mkForce \"a;b\""));
+    assert!(!is_expression("1; b = 2"));
+    assert!(!is_expression("[ 1"));
+    assert!(!is_expression(""));
+}
+
+#[test]
+fn a_module_must_end_in_a_set() {
+    assert!(module_body("{ ... }: lib.mkMerge [ { } ]").is_none());
+    assert!(module_body("{ a = 1 }").is_none());
+}
+
+#[test]
+fn strings_are_escaped() {
+    assert_eq!(nix_string("vim"), "\"vim\"");
+    assert_eq!(nix_string("a\"b\\c ${x}"), "\"a\\\"b\\\\c \\${x}\"");
+    assert_eq!(nix_string("a\nb\tc"), "\"a\\nb\\tc\"");
+}
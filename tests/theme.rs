@@ -0,0 +1,46 @@
+use declair_rs::output::{OutputFormat, render};
+use declair_rs::theme::{ColorChoice, Theme};
+
+#[test]
+fn color_choice_honours_no_color_only_in_auto() {
+    assert!(ColorChoice::Auto.enabled(false, true));
+    assert!(!ColorChoice::Auto.enabled(true, true));
+    assert!(!ColorChoice::Auto.enabled(false, false));
+    assert!(ColorChoice::Always.enabled(true, false));
+    assert!(!ColorChoice::Never.enabled(false, true));
+}
+
+#[test]
+fn theme_table_overrides_some_styles() {
+    let theme: Theme = toml::from_str("added = \"blue.bold\"\nheader = \"underlined\"").unwrap();
+    assert_eq!(theme.added, "blue.bold");
+    assert_eq!(theme.header, "underlined");
+    assert_eq!(theme.removed, Theme::default().removed);
+}
+
+#[test]
+fn styles_apply_only_when_colours_are_enabled() {
+    let theme = Theme::default();
+    let diff = "--- a.nix\n+++ a.nix\n@@ -1 +1 @@\n-htop\n+btop\n context";
+
+    console::set_colors_enabled(false);
+    assert_eq!(theme.diff(diff), diff);
+    let rows = vec![vec!["git".to_string(), "2.44".to_string()]];
+    let plain = render(OutputFormat::Table, &["name", "version"], &rows);
+    assert_eq!(plain, "Name | Version\n-----+--------\ngit  | 2.44");
+
+    console::set_colors_enabled(true);
+    let styled = theme.diff(diff);
+    assert!(styled.contains("\u{1b}[31m-htop"));
+    assert!(styled.contains("\u{1b}[32m+btop"));
+    assert!(styled.contains("--- a.nix\n+++ a.nix"));
+    let coloured = render(OutputFormat::Table, &["name", "version"], &rows);
+    assert_eq!(console::strip_ansi_codes(&coloured), plain);
+    assert_ne!(coloured, plain);
+    // Machine-readable output never carries escape codes.
+    assert_eq!(
+        render(OutputFormat::Tsv, &["name", "version"], &rows),
+        "name\tversion\ngit\t2.44"
+    );
+    console::set_colors_enabled(false);
+}
@@ -0,0 +1,44 @@
+use declair_rs::timings::{self, Timings, format_duration, run_timings};
+use serde_json::Value;
+use std::time::Duration;
+
+#[test]
+fn durations_are_formatted_compactly() {
+    assert_eq!(format_duration(Duration::from_millis(3240)), "3.2s");
+    assert_eq!(format_duration(Duration::from_secs(252)), "4m12s");
+    assert_eq!(format_duration(Duration::from_secs(3780)), "1h03m");
+}
+
+#[test]
+fn timings_summary_and_json() {
+    let mut timings = Timings::new();
+    timings.record("search", Duration::from_millis(3200));
+    timings.record("edit", Duration::ZERO);
+    timings.record("rebuild", Duration::from_secs(252));
+    assert_eq!(timings.summary(), "search 3.2s, edit 0.0s, rebuild 4m12s");
+    let json = timings.to_json();
+    assert_eq!(json["phases"][2]["phase"], "rebuild");
+    assert_eq!(json["total_seconds"], 255.2);
+}
+
+#[test]
+fn phases_are_recorded_for_the_whole_run() {
+    assert_eq!(timings::time("precheck", || 42), 42);
+    timings::record("write", Duration::from_millis(10));
+    let summary = run_timings().summary();
+    assert!(summary.contains("precheck 0.0s"), "{summary}");
+    assert!(summary.contains("write 0.0s"), "{summary}");
+}
+
+#[test]
+fn timings_json_goes_to_stderr_after_any_subcommand() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_declair-rs"))
+        .args(["examples", "--timings"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let json: Value = serde_json::from_str(stderr.lines().last().unwrap()).unwrap();
+    assert!(json["phases"].is_array());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("total_seconds"));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::write_config;
+use declair_rs::error::DeclairError;
+use declair_rs::module::ModuleKind;
+use declair_rs::variables::{list_variables, set_variable, unset_variable};
+use std::fs;
+use std::path::Path;
+
+fn variables(file: &Path, kind: ModuleKind) -> Vec<(String, String)> {
+    list_variables(file, kind)
+        .unwrap()
+        .into_iter()
+        .map(|v| (v.name, v.value))
+        .collect()
+}
+
+#[test]
+fn set_creates_the_set() {
+    let (_dir, file) = write_config("{ pkgs, ... }:\n{\n  networking.hostName = \"box\";\n}\n");
+    assert_eq!(
+        set_variable(&file, ModuleKind::Nixos, "EDITOR", "vim").unwrap(),
+        None
+    );
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{ pkgs, ... }:\n{\n  networking.hostName = \"box\";\n  environment.variables = {\n    EDITOR = \"vim\";\n  };\n}"
+    );
+}
+
+#[test]
+fn set_replaces_and_adds_in_a_multiline_set() {
+    let (_dir, file) =
+        write_config("{\n  home.sessionVariables = {\n    EDITOR = \"vim\"; # mine\n  };\n}\n");
+    assert_eq!(
+        set_variable(&file, ModuleKind::HomeManager, "EDITOR", "hx").unwrap(),
+        Some("\"vim\"".to_string())
+    );
+    set_variable(&file, ModuleKind::HomeManager, "PAGER", "less -R").unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n  home.sessionVariables = {\n    EDITOR = \"hx\"; # mine\n    PAGER = \"less -R\";\n  };\n}"
+    );
+    unset_variable(&file, ModuleKind::HomeManager, "EDITOR").unwrap();
+    assert_eq!(
+        variables(&file, ModuleKind::HomeManager),
+        [("PAGER".to_string(), "\"less -R\"".to_string())]
+    );
+}
+
+#[test]
+fn single_line_sets_and_dotted_assignments() {
+    let (_dir, file) = write_config(
+        "{\n  environment.variables = { EDITOR = \"vim\"; };\n  environment.variables.BROWSER = \"firefox\";\n}\n",
+    );
+    assert_eq!(
+        variables(&file, ModuleKind::Nixos),
+        [
+            ("EDITOR".to_string(), "\"vim\"".to_string()),
+            ("BROWSER".to_string(), "\"firefox\"".to_string()),
+        ]
+    );
+    set_variable(&file, ModuleKind::Nixos, "BROWSER", "chromium").unwrap();
+    set_variable(&file, ModuleKind::Nixos, "PAGER", "less").unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n  environment.variables = {\n    EDITOR = \"vim\";\n    PAGER = \"less\";\n  };\n  environment.variables.BROWSER = \"chromium\";\n}"
+    );
+}
+
+#[test]
+fn unset_from_a_single_line_set() {
+    let (_dir, file) = write_config("{\n  environment.variables = { A = \"1\"; B = \"2\"; };\n}\n");
+    unset_variable(&file, ModuleKind::Nixos, "A").unwrap();
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "{\n  environment.variables = { B = \"2\"; };\n}"
+    );
+    assert!(matches!(
+        unset_variable(&file, ModuleKind::Nixos, "A"),
+        Err(DeclairError::VariableNotFound { .. })
+    ));
+}
+
+#[test]
+fn invalid_names_are_refused() {
+    let (_dir, file) = write_config("{\n}\n");
+    let err = set_variable(&file, ModuleKind::Nixos, "1BAD", "x").unwrap_err();
+    assert_eq!(err.exit_code(), 2);
+    assert!(set_variable(&file, ModuleKind::Nixos, "A-B", "x").is_err());
+}
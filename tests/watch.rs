@@ -0,0 +1,54 @@
+use declair_rs::error::DeclairError;
+use declair_rs::watch::{PackageDiff, watch_files};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+fn pkgs(names: &[&str]) -> Vec<String> {
+    names.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn diff_reports_added_and_removed_packages() {
+    let diff = PackageDiff::between(&pkgs(&["git", "htop", "vim"]), &pkgs(&["git", "vim", "fd"]));
+    assert_eq!(diff.added, ["fd"]);
+    assert_eq!(diff.removed, ["htop"]);
+    assert_eq!(diff.summary(), "+fd -htop");
+}
+
+#[test]
+fn reordering_is_not_a_change() {
+    let diff = PackageDiff::between(&pkgs(&["git", "vim"]), &pkgs(&["vim", "git"]));
+    assert!(diff.is_empty());
+    assert_eq!(diff.summary(), "");
+}
+
+#[test]
+fn reports_changed_files_after_debounce() {
+    let dir = tempfile::tempdir().unwrap();
+    let watched = dir.path().canonicalize().unwrap().join("home.nix");
+    let other = watched.with_file_name("other.nix");
+    fs::write(&watched, "{ }").unwrap();
+
+    let writer = {
+        let (watched, other) = (watched.clone(), other.clone());
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            fs::write(&other, "{ }").unwrap();
+            fs::write(&watched, "{ home.packages = [ ]; }").unwrap();
+        })
+    };
+
+    let mut seen = Vec::new();
+    let result = watch_files(
+        std::slice::from_ref(&watched),
+        Duration::from_millis(100),
+        |changed| {
+            seen.extend_from_slice(changed);
+            Err(DeclairError::Watch("stop".to_string()))
+        },
+    );
+    writer.join().unwrap();
+    assert!(result.is_err());
+    assert_eq!(seen, [watched]);
+}
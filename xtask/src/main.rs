@@ -0,0 +1,43 @@
+//! Build tasks of declair, run with `cargo xtask <task>`:
+//!
+//! * `man [DIR]`: write the man pages of `declair-rs` and each of its subcommands to
+//!   `DIR` (by default `target/man`), for packagers to install into `share/man/man1`.
+
+use declair_rs::cli;
+use declair_rs::examples::write_man_pages;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["man"] => man(&workspace_dir().join("target/man")),
+        ["man", dir] => man(&PathBuf::from(dir)),
+        _ => {
+            eprintln!("usage: cargo xtask man [DIR]");
+            exit(2);
+        }
+    }
+}
+
+/// The directory of the workspace, above this crate's.
+fn workspace_dir() -> PathBuf {
+    let xtask = Path::new(env!("CARGO_MANIFEST_DIR"));
+    xtask.parent().unwrap_or(xtask).to_path_buf()
+}
+
+fn man(dir: &Path) {
+    match write_man_pages(cli::command(), dir) {
+        Ok(pages) => println!("wrote {} man pages to {}", pages.len(), dir.display()),
+        Err(e) => {
+            eprintln!("failed to write the man pages to {}: {}", dir.display(), e);
+            exit(1);
+        }
+    }
+}